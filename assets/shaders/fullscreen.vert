@@ -0,0 +1,11 @@
+#version 450
+
+// A triangle that covers the whole screen, without any vertex buffer
+
+layout(location = 0) out vec2 o_uv;
+
+void main() {
+    vec2 position = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    o_uv = vec2(position.x, 1.0 - position.y);
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,63 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform sampler u_sampler;
+layout(set = 0, binding = 1) uniform texture2D u_input;
+layout(set = 0, binding = 2) uniform PostProcess {
+    float u_exposure;
+    float u_gamma;
+    float u_tone_mapping;
+    float u_fxaa;
+    vec4 u_filter;
+    vec2 u_texel_size;
+};
+
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_SPAN_MAX = 8.0;
+
+// The luma is computed on approximately gamma-encoded colors, where the edges are perceived
+float luma(vec3 color) {
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+vec3 fetch(vec2 uv) {
+    return texture(sampler2D(u_input, u_sampler), uv).rgb;
+}
+
+void main() {
+    vec3 color_center = fetch(i_uv);
+    if (u_fxaa < 0.5) {
+        o_color = vec4(color_center, 1.0);
+        return;
+    }
+
+    /* FXAA */
+    // find the direction of the edge from the luma of the diagonal neighbours
+    float luma_nw = luma(fetch(i_uv + vec2(-1.0, -1.0) * u_texel_size));
+    float luma_ne = luma(fetch(i_uv + vec2(1.0, -1.0) * u_texel_size));
+    float luma_sw = luma(fetch(i_uv + vec2(-1.0, 1.0) * u_texel_size));
+    float luma_se = luma(fetch(i_uv + vec2(1.0, 1.0) * u_texel_size));
+    float luma_center = luma(color_center);
+    float luma_min = min(luma_center, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_center, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 direction = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    float inverse_smallest = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_smallest, -FXAA_SPAN_MAX, FXAA_SPAN_MAX) * u_texel_size;
+
+    // blend along the edge
+    vec3 color_a = 0.5 * (fetch(i_uv + direction * (1.0 / 3.0 - 0.5)) + fetch(i_uv + direction * (2.0 / 3.0 - 0.5)));
+    vec3 color_b = color_a * 0.5 + 0.25 * (fetch(i_uv - direction * 0.5) + fetch(i_uv + direction * 0.5));
+    float luma_b = luma(color_b);
+    // the wider blend is only kept if it didn't sample beyond the edge
+    if (luma_b < luma_min || luma_b > luma_max) {
+        o_color = vec4(color_a, 1.0);
+    } else {
+        o_color = vec4(color_b, 1.0);
+    }
+}
@@ -0,0 +1,18 @@
+#version 450
+
+layout(set = 1, binding = 0) uniform sampler s_icons;
+layout(set = 1, binding = 1) uniform texture2D t_icons;
+
+layout(location = 0) in vec2 i_uv;
+
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    // the atlas is sRGB, so the sampled colors are already linear
+    vec4 color = texture(sampler2D(t_icons, s_icons), i_uv);
+    // the empty pixels around the item must not hide what is drawn behind it later
+    if (color.a < 0.5) {
+        discard;
+    }
+    o_color = color;
+}
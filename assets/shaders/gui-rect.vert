@@ -9,8 +9,13 @@ layout(location = 1) in vec4 i_color;
 
 layout(location = 0) out vec4 o_color;
 
+// the colors of the ui are sRGB, like the colors of a picture
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+
 void main() {
     gl_Position = u_transform * vec4(i_position, 1.0);
 
-    o_color = i_color;
+    o_color = vec4(srgb_to_linear(i_color.rgb), i_color.a);
 }
@@ -11,6 +11,11 @@ layout(location = 1) out float occl;
 layout(location = 2) out vec3 v_Rgb;
 
 
+// the colors of the models are sRGB, like the colors of a picture
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+
 vec3 get_normal(uint id) {
     if (id == 0u) {
         return vec3(1.0, 0.0, 0.0);
@@ -53,6 +58,6 @@ void main() {
     float gg = float(g)/255.0;
     float bb = float(b)/255.0;
 
-    v_Rgb = vec3(rr,gg,bb);
+    v_Rgb = srgb_to_linear(vec3(rr,gg,bb));
 
 }
\ No newline at end of file
@@ -0,0 +1,14 @@
+#version 450
+
+// position relative to the chunk origin, the last component is not used
+layout(location = 0) in uvec4 i_position;
+// per instance, the last component is not used
+layout(location = 5) in ivec4 i_chunk_origin;
+
+layout(set = 0, binding = 0) uniform Transform {
+    mat4 u_light_view_proj;
+};
+
+void main() {
+    gl_Position = u_light_view_proj * vec4(vec3(i_chunk_origin.xyz) + vec3(i_position.xyz), 1.0);
+}
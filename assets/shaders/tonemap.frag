@@ -0,0 +1,45 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+
+layout(location = 0) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform sampler u_sampler;
+layout(set = 0, binding = 1) uniform texture2D u_input;
+layout(set = 0, binding = 2) uniform PostProcess {
+    float u_exposure;
+    float u_gamma;
+    float u_tone_mapping;
+    float u_fxaa;
+    // rgb: color of the filter, a: strength of the filter
+    vec4 u_filter;
+    vec2 u_texel_size;
+};
+
+// Filmic curve fitted to ACES by Krzysztof Narkowicz
+vec3 aces(vec3 color) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(sampler2D(u_input, u_sampler), i_uv).rgb;
+
+    /* COLOR FILTER */
+    // the filter tints the scene and washes out the contrast, like murky water or a cloud of dust
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(color, luminance * u_filter.rgb * 2.0, u_filter.a);
+
+    /* TONE MAPPING */
+    color = mix(clamp(color, 0.0, 1.0), aces(color * u_exposure), u_tone_mapping);
+
+    /* GAMMA */
+    // the frame buffer is sRGB so the output is linear, the gamma only adjusts the brightness of the midtones
+    color = pow(color, vec3(1.0 / u_gamma));
+
+    o_color = vec4(color, 1.0);
+}
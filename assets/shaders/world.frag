@@ -2,38 +2,213 @@
 
 layout(location = 0) flat in vec3 i_norm;
 layout(location = 1) in float i_occl;
-layout(location = 2) flat in vec2 i_texture_top_left;
+layout(location = 2) flat in uint i_texture_layer;
 layout(location = 3) flat in vec2 i_texture_size;
 layout(location = 4) flat in vec2 i_texture_max_uv;
 layout(location = 5) in vec2 i_texture_uv;
 layout(location = 6) flat in float i_light_level;
+layout(location = 7) flat in float i_block_light_level;
+layout(location = 8) flat in float i_emissive;
+layout(location = 9) in vec3 i_world_position;
+layout(location = 10) in vec3 i_chunk_position;
+layout(location = 11) flat in int i_light_volume;
 
 layout(location = 0) out vec4 o_color;
 
 layout(set = 0, binding = 1) uniform sampler u_sampler;
-layout(set = 0, binding = 2) uniform texture2D u_texture_atlas;
+// one layer per texture
+layout(set = 0, binding = 2) uniform texture2DArray u_textures;
+layout(set = 0, binding = 3) uniform World {
+    float u_daylight;
+    float u_cascade_count;
+    float u_pcf_radius;
+    float u_shadow_texel_size;
+    vec4 u_sun_direction;
+    vec4 u_camera_position;
+    // debug modes: wireframe, light heat map
+    vec4 u_debug;
+    // x: bias of the mipmap level of the block textures
+    vec4 u_texture_lod;
+    vec4 u_cascade_ends;
+    mat4 u_light_view_proj[3];
+};
+layout(set = 0, binding = 4) uniform texture2DArray u_shadow_maps;
+layout(set = 0, binding = 5) uniform samplerShadow u_shadow_sampler;
+layout(set = 0, binding = 6) uniform Clusters {
+    // number of clusters along x, y and z
+    vec4 u_cluster_grid;
+    // near and far depths of the slices, and log(far / near)
+    vec4 u_cluster_depth;
+    vec4 u_screen_size;
+    vec4 u_camera_forward;
+};
+// position and radius, then color and intensity, then direction and cosine of the cone angle of each light.
+// the cosine is below -1 for the point lights, which light every direction
+layout(set = 0, binding = 7) readonly buffer Lights {
+    vec4 u_lights[];
+};
+// offset in u_light_indices and number of lights of each cluster
+layout(set = 0, binding = 8) readonly buffer ClusterLights {
+    uvec2 u_clusters[];
+};
+layout(set = 0, binding = 9) readonly buffer LightIndices {
+    uint u_light_indices[];
+};
+// sunlight, block light and ambient occlusion at the corners of the blocks of the chunks
+layout(set = 0, binding = 10) uniform texture3D u_light_volumes;
+layout(set = 0, binding = 11) uniform sampler u_volume_sampler;
 
-const vec3 SUN_DIRECTION = normalize(vec3(0, 1, 0.5));
 const float SUN_FRACTION = 0.1;
+// how much of the sunlight remains in the shadows
+const float SHADOW_LIGHT = 0.5;
+// the position is moved along the normal before the lookup in the shadow maps, to avoid shadow acne
+const float NORMAL_OFFSET = 0.05;
 const vec2 EPSILON = vec2(1e-7, 1e-7);
+// CHUNK_SIZE + 1
+const int VOLUME_SIZE = 33;
+
+// Fraction of the sunlight that reaches the fragment, averaged over the neighbouring texels of the shadow map
+float get_sunlit_fraction() {
+    // the faces turned away from the sun are always in the shadow
+    if (dot(i_norm, u_sun_direction.xyz) <= 0.0) {
+        return 0.0;
+    }
+    float distance = length(i_world_position - u_camera_position.xyz);
+    for (int cascade = 0; cascade < int(u_cascade_count); cascade++) {
+        if (distance < u_cascade_ends[cascade]) {
+            vec4 light_position = u_light_view_proj[cascade] * vec4(i_world_position + NORMAL_OFFSET * i_norm, 1.0);
+            vec3 coords = light_position.xyz / light_position.w;
+            vec2 uv = coords.xy * vec2(0.5, -0.5) + 0.5;
+            int radius = int(u_pcf_radius);
+            float lit = 0.0;
+            for (int dx = -radius; dx <= radius; dx++) {
+                for (int dy = -radius; dy <= radius; dy++) {
+                    vec2 offset = vec2(dx, dy) * u_shadow_texel_size;
+                    lit += texture(sampler2DArrayShadow(u_shadow_maps, u_shadow_sampler), vec4(uv + offset, float(cascade), coords.z));
+                }
+            }
+            float side = float(2 * radius + 1);
+            return lit / (side * side);
+        }
+    }
+    // beyond the last cascade, or at night when there is no cascade
+    return 1.0;
+}
+
+// Light of the point lights of the cluster of the fragment
+vec3 get_dynamic_light() {
+    ivec3 grid = ivec3(u_cluster_grid.xyz);
+    // the rows of clusters start from the top of the screen, like gl_FragCoord
+    ivec2 tile = clamp(ivec2(gl_FragCoord.xy / u_screen_size.xy * vec2(grid.xy)), ivec2(0), grid.xy - 1);
+    float depth = dot(i_world_position - u_camera_position.xyz, u_camera_forward.xyz);
+    // the same slices as the CPU: the first one ends at the near depth, the others grow exponentially
+    int slice = 0;
+    if (depth >= u_cluster_depth.x) {
+        slice = min(grid.z - 1, int(1.0 + log(depth / u_cluster_depth.x) / u_cluster_depth.z * float(grid.z - 1)));
+    }
+    uvec2 cluster = u_clusters[(slice * grid.y + tile.y) * grid.x + tile.x];
+
+    vec3 light = vec3(0.0);
+    for (uint i = cluster.x; i < cluster.x + cluster.y; i++) {
+        uint index = u_light_indices[i];
+        vec4 position_radius = u_lights[3 * index];
+        vec4 color_intensity = u_lights[3 * index + 1];
+        vec4 spot = u_lights[3 * index + 2];
+        vec3 to_light = position_radius.xyz - i_world_position;
+        float distance = length(to_light);
+        float attenuation = clamp(1.0 - distance / position_radius.w, 0.0, 1.0);
+        if (spot.w >= -1.0) {
+            // the edge of the cone fades over the outer fifth of its angle
+            float cos_to_fragment = dot(-to_light / max(distance, 1e-4), spot.xyz);
+            attenuation *= smoothstep(spot.w, mix(spot.w, 1.0, 0.2), cos_to_fragment);
+        }
+        // soften the lambert term so that the faces turned away are not completely dark
+        float lambert = 0.5 + 0.5 * max(dot(i_norm, to_light / max(distance, 1e-4)), 0.0);
+        light += color_intensity.rgb * color_intensity.a * attenuation * attenuation * lambert;
+    }
+    return light;
+}
+
+// Smooth sunlight, block light and occlusion, interpolated between the corners of the light volume of the chunk
+vec3 get_volume_light() {
+    ivec3 texture_size = textureSize(sampler3D(u_light_volumes, u_volume_sampler), 0);
+    // the same slots as the CPU
+    ivec3 slots = texture_size / VOLUME_SIZE;
+    ivec3 slot = ivec3(i_light_volume % slots.x, i_light_volume / slots.x % slots.y, i_light_volume / (slots.x * slots.y));
+    // the fragments are on the faces of the blocks, so the texels are never mixed with the ones of another slot
+    vec3 texel = vec3(slot * VOLUME_SIZE) + i_chunk_position + 0.5;
+    return texture(sampler3D(u_light_volumes, u_volume_sampler), texel / vec3(texture_size)).rgb;
+}
+
+// Blue for 0, then cyan, green, yellow and red for 1
+vec3 heat_map(float x) {
+    return clamp(1.5 - abs(4.0 * x - vec3(3.0, 2.0, 1.0)), 0.0, 1.0);
+}
+
+// How much a fragment is on the edges of its quad, and on the edges of the blocks inside the quad
+vec2 get_wireframe() {
+    // the position in the quad, in blocks
+    vec2 quad_uv = i_texture_uv / i_texture_size;
+    vec2 quad_size = i_texture_max_uv / i_texture_size;
+    vec2 pixel_size = max(fwidth(quad_uv), vec2(1e-6));
+    // the lines are about one pixel wide
+    vec2 quad_edge = min(quad_uv, quad_size - quad_uv) / pixel_size;
+    vec2 block_edge = abs(fract(quad_uv + 0.5) - 0.5) / pixel_size;
+    return vec2(
+        1.0 - step(1.0, min(quad_edge.x, quad_edge.y)),
+        1.0 - step(1.0, min(block_edge.x, block_edge.y))
+    );
+}
 
 void main() {
     /* TEXTURE ACCESS */
     // avoid going out of bounds when multisampling is enabled
     vec2 corrected_uv = clamp(i_texture_uv, EPSILON, i_texture_max_uv - EPSILON);
-    // compute the texture gradients before texture wrapping
-    vec2 x_derivative = dFdx(corrected_uv);
-    vec2 y_derivative = dFdy(corrected_uv);
-    // wrap texture
-    vec2 actual_uv = i_texture_top_left + mod(corrected_uv, i_texture_size);
+    // compute the texture gradients before texture wrapping, scaled to bias the mipmap level
+    float lod_scale = exp2(u_texture_lod.x);
+    vec2 x_derivative = dFdx(corrected_uv) * lod_scale;
+    vec2 y_derivative = dFdy(corrected_uv) * lod_scale;
+    // wrap texture, which starts at the top left corner of its layer
+    vec3 actual_uv = vec3(mod(corrected_uv, i_texture_size), float(i_texture_layer));
     // get texture value
-    vec4 tex_color = textureGrad(sampler2D(u_texture_atlas, u_sampler), actual_uv, x_derivative, y_derivative);
+    vec4 tex_color = textureGrad(sampler2DArray(u_textures, u_sampler), actual_uv, x_derivative, y_derivative);
 
     /* VARIOUS BRIGHTNESS FACTORS */
-    float light_factor = pow(0.8, 15.0 - i_light_level);
-    float normal_factor = 1.0 - SUN_FRACTION + SUN_FRACTION * dot(i_norm, SUN_DIRECTION);
-    float total_factor = light_factor * i_occl * normal_factor;
+    // the chunks without a light volume use the light of their vertices
+    float light_level = i_light_level;
+    float block_light_level = i_block_light_level;
+    float occl = i_occl;
+    if (i_light_volume >= 0) {
+        vec3 volume_light = get_volume_light();
+        light_level = volume_light.x * 15.0;
+        block_light_level = volume_light.y * 15.0;
+        occl = volume_light.z;
+    }
+    float sunlight_factor = pow(0.8, 15.0 - light_level) * u_daylight;
+    // the shadows fade in after sunrise and out before sunset
+    float shadow_strength = (1.0 - SHADOW_LIGHT) * smoothstep(0.0, 0.2, u_sun_direction.y);
+    sunlight_factor *= 1.0 - shadow_strength * (1.0 - get_sunlit_fraction());
+    float block_light_factor = pow(0.8, 15.0 - block_light_level) * step(0.5, block_light_level);
+    float light_factor = max(sunlight_factor, block_light_factor);
+    float normal_factor = 1.0 - SUN_FRACTION + SUN_FRACTION * dot(i_norm, u_sun_direction.xyz);
+    float total_factor = light_factor * occl * normal_factor;
+    // emissive blocks glow at full brightness
+    total_factor = mix(total_factor, 1.0, i_emissive);
+
+    // the point lights are added on top, the emissive blocks are already at full brightness
+    vec3 dynamic_light = get_dynamic_light() * occl * (1.0 - i_emissive);
 
     /* OUTPUT */
-    o_color = vec4(total_factor, total_factor, total_factor, 1.0) * tex_color;
+    o_color = vec4(vec3(total_factor) + dynamic_light, 1.0) * tex_color;
+
+    /* DEBUG MODES */
+    if (u_debug.y > 0.5) {
+        o_color = vec4(heat_map(max(light_level, block_light_level) / 15.0), 1.0);
+    }
+    if (u_debug.x > 0.5) {
+        // the quads of the mesh in black, the blocks inside them in grey
+        vec2 wireframe = get_wireframe();
+        o_color.rgb = mix(o_color.rgb, vec3(0.3), 0.5 * wireframe.y);
+        o_color.rgb = mix(o_color.rgb, vec3(0.0), wireframe.x);
+    }
 }
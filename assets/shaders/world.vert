@@ -1,15 +1,17 @@
 #version 450
 
-layout(location = 0) in vec3 i_position;
-layout(location = 1) in vec2 i_texture_top_left;
-layout(location = 2) in vec2 i_texture_size;
-layout(location = 3) in vec2 i_texture_max_uv;
-layout(location = 4) in vec2 i_texture_uv;
-// occl at end, then face then light
-layout(location = 5) in uint i_occl_and_face;
-// light: 4 bits
-// occl: 2 bits
-// face: 3 bits
+// position relative to the chunk origin, then face in the low byte and occl in the high byte
+layout(location = 0) in uvec4 i_position_face_occl;
+// sunlight, block light, emissive
+layout(location = 1) in uvec4 i_light;
+// layer of the texture in the texture array
+layout(location = 2) in uint i_texture_layer;
+// size of the texture relative to its layer
+layout(location = 3) in vec2 i_texture_size;
+// repeats of the texture up to this vertex, then on the whole quad
+layout(location = 4) in uvec4 i_texture_repeats;
+// per instance, then the slot of the light volume of the chunk, or -1
+layout(location = 5) in ivec4 i_chunk_origin;
 
 layout(set = 0, binding = 0) uniform Transform {
     mat4 u_view_proj;
@@ -17,11 +19,16 @@ layout(set = 0, binding = 0) uniform Transform {
 
 layout(location = 0) flat out vec3 o_norm;
 layout(location = 1) out float o_occl;
-layout(location = 2) flat out vec2 o_texture_top_left;
+layout(location = 2) flat out uint o_texture_layer;
 layout(location = 3) flat out vec2 o_texture_size;
 layout(location = 4) flat out vec2 o_texture_max_uv;
 layout(location = 5) out vec2 o_texture_uv;
 layout(location = 6) flat out float o_light_level;
+layout(location = 7) flat out float o_block_light_level;
+layout(location = 8) flat out float o_emissive;
+layout(location = 9) out vec3 o_world_position;
+layout(location = 10) out vec3 o_chunk_position;
+layout(location = 11) flat out int o_light_volume;
 
 vec3 get_normal(uint id) {
     if(id == 0u) {
@@ -52,18 +59,22 @@ float get_occl(uint code_occl) {
 }
 
 void main() {
-
-    uint light_level = (i_occl_and_face & 0x000001E0u) >> 5;
-    uint occl_code = (i_occl_and_face & 0x00000018u) >> 3;
-    uint face_index = (i_occl_and_face & 0x00000007u) >> 0;
+    uint face_index = i_position_face_occl.w & 0xFFu;
+    uint occl_code = i_position_face_occl.w >> 8;
+    vec3 position = vec3(i_chunk_origin.xyz) + vec3(i_position_face_occl.xyz);
 
     o_norm = get_normal(face_index);
     o_occl = get_occl(occl_code);
-    o_texture_top_left = i_texture_top_left;
+    o_texture_layer = i_texture_layer;
     o_texture_size = i_texture_size;
-    o_texture_max_uv = i_texture_max_uv;
-    o_texture_uv = i_texture_uv;
-    o_light_level = float(light_level);
+    o_texture_max_uv = i_texture_size * vec2(i_texture_repeats.zw);
+    o_texture_uv = i_texture_size * vec2(i_texture_repeats.xy);
+    o_light_level = float(i_light.x);
+    o_block_light_level = float(i_light.y);
+    o_emissive = float(i_light.z);
+    o_world_position = position;
+    o_chunk_position = vec3(i_position_face_occl.xyz);
+    o_light_volume = i_chunk_origin.w;
 
-    gl_Position = u_view_proj * vec4(i_position, 1.0);
+    gl_Position = u_view_proj * vec4(position, 1.0);
 }
@@ -0,0 +1,183 @@
+//! The animations of the characters and the robots. Each entity stands idle, walks when it moves, swings when it
+//! mines or hits something, and plays the emotes that the server tells about. The parts of the models, such as the
+//! wheels of the robots, are drawn as separate models that follow the pose of each part.
+
+use crate::render::Model;
+use common::animation::{AnimationSet, Pose, BODY_PART, IDLE_ANIMATION, MINE_ANIMATION, WALK_ANIMATION};
+use common::data::vox::{part_model_name, VoxelModel};
+use common::player::DEFAULT_CHARACTER_MODEL;
+use common::registry::{namespaced_key, Registry};
+use nalgebra::{Similarity3, Translation3, UnitQuaternion, Vector3};
+use std::collections::BTreeMap;
+
+/// Horizontal speed above which an entity walks, in blocks per second
+const WALK_SPEED: f64 = 0.5;
+/// How fast the measured speed follows the movement of the entity, to ignore the frames without network update
+const SPEED_SMOOTHING: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+enum AnimationState {
+    Idle,
+    Walk,
+    Mine,
+    Emote(String),
+}
+
+impl AnimationState {
+    fn animation_name(&self) -> &str {
+        match self {
+            Self::Idle => IDLE_ANIMATION,
+            Self::Walk => WALK_ANIMATION,
+            Self::Mine => MINE_ANIMATION,
+            Self::Emote(name) => name,
+        }
+    }
+}
+
+/// The animation state of an entity
+pub struct Animator {
+    state: AnimationState,
+    /// Seconds since the animation of the state started
+    time: f32,
+    /// Position of the entity at the previous update
+    previous_pos: Option<Vector3<f64>>,
+    /// Smoothed horizontal speed of the entity
+    speed: f64,
+}
+
+impl Default for Animator {
+    fn default() -> Self {
+        Self {
+            state: AnimationState::Idle,
+            time: 0.0,
+            previous_pos: None,
+            speed: 0.0,
+        }
+    }
+}
+
+impl Animator {
+    /// Play an animation once, such as an emote. Walking interrupts the emotes, but not the mining swing.
+    pub fn play(&mut self, name: &str) {
+        self.state = match name {
+            MINE_ANIMATION => AnimationState::Mine,
+            _ => AnimationState::Emote(name.to_owned()),
+        };
+        self.time = 0.0;
+    }
+
+    /// Advance the animation of an entity at `pos`, which keeps swinging while it is `mining`
+    pub fn update(&mut self, animations: Option<&AnimationSet>, pos: Vector3<f64>, mining: bool, seconds_delta: f64) {
+        self.time += seconds_delta as f32;
+        if let Some(previous_pos) = self.previous_pos.filter(|_| seconds_delta > 0.0) {
+            let movement = Vector3::new(pos.x - previous_pos.x, 0.0, pos.z - previous_pos.z);
+            let smoothing = (SPEED_SMOOTHING * seconds_delta).min(1.0);
+            self.speed += (movement.norm() / seconds_delta - self.speed) * smoothing;
+        }
+        self.previous_pos = Some(pos);
+        // The states without animation end right away
+        let animation = animations.and_then(|animations| animations.get(self.state.animation_name()));
+        let finished = animation.is_none_or(|animation| animation.is_finished(self.time));
+        let next = match &self.state {
+            AnimationState::Mine if !finished => return,
+            _ if self.speed > WALK_SPEED => AnimationState::Walk,
+            AnimationState::Emote(_) if !finished => return,
+            _ if mining => AnimationState::Mine,
+            _ => AnimationState::Idle,
+        };
+        if next != self.state || finished {
+            self.state = next;
+            self.time = 0.0;
+        }
+    }
+
+    /// The pose of a part of the model at this time of the animation
+    pub fn pose(&self, animations: Option<&AnimationSet>, part: &str) -> Pose {
+        match animations.and_then(|animations| animations.get(self.state.animation_name())) {
+            Some(animation) => animation.sample(part, self.time),
+            None => Pose::default(),
+        }
+    }
+}
+
+/// The rotation of a pose, see `Pose` for the order of the rotations
+fn pose_rotation(pose: &Pose) -> UnitQuaternion<f32> {
+    let [x, y, z] = pose.rotation.map(f32::to_radians);
+    UnitQuaternion::from_axis_angle(&Vector3::y_axis(), y)
+        * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), z)
+        * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), x)
+}
+
+fn similarity(translation: Vector3<f32>, rotation: UnitQuaternion<f32>, scaling: f32) -> Similarity3<f32> {
+    Similarity3::from_parts(Translation3::from(translation), rotation, scaling)
+}
+
+/// The model drawn with a transformation from its voxels to the world
+fn transformed_model(mesh_id: u32, transform: &Similarity3<f32>) -> Model {
+    // Find the angles of the rotation, which is applied around x, then z, then y
+    let matrix = transform.isometry.rotation.to_rotation_matrix();
+    let m = matrix.matrix();
+    let translation = transform.isometry.translation.vector;
+    Model {
+        mesh_id,
+        pos_x: translation.x,
+        pos_y: translation.y,
+        pos_z: translation.z,
+        scale: transform.scaling(),
+        rot_offset: [0.0, 0.0, 0.0],
+        rot_x: (-m[(1, 2)]).atan2(m[(1, 1)]),
+        rot_y: (-m[(2, 0)]).atan2(m[(0, 0)]),
+        rot_z: m[(1, 0)].clamp(-1.0, 1.0).asin(),
+    }
+}
+
+/// The models of an entity whose feet are centered on `feet`, facing `yaw` degrees: the model, then its parts.
+/// `pose` gives the pose of each part by name, and the pose of the body moves the whole model.
+pub fn posed_models(
+    models: &Registry<VoxelModel>,
+    mesh_id: u32,
+    feet: Vector3<f64>,
+    yaw: f64,
+    scale: f32,
+    pose: impl Fn(&str) -> Pose,
+) -> Vec<Model> {
+    let model = models.get_value_by_id(mesh_id).unwrap();
+    let name = models.get_name_by_id(mesh_id).unwrap();
+    // The body turns around the center of the feet
+    let center = Vector3::new(model.size_x as f32 / 2.0, 0.0, model.size_z as f32 / 2.0);
+    let body = pose(BODY_PART);
+    let yaw = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw.to_radians() as f32);
+    let entity = similarity(feet.cast::<f32>(), yaw, scale)
+        * similarity(Vector3::from(body.offset), pose_rotation(&body), 1.0)
+        * similarity(-center, UnitQuaternion::identity(), 1.0);
+    let mut result = vec![transformed_model(mesh_id, &entity)];
+    // The transformation of each part in the model, which also moves the parts attached to it
+    let mut part_transforms: Vec<Similarity3<f32>> = Vec::with_capacity(model.parts.len());
+    for (index, part) in model.parts.iter().enumerate() {
+        let parent = part.parent.filter(|&parent| parent < index).map(|parent| part_transforms[parent]);
+        let pivot = Vector3::from(part.pivot());
+        let part_pose = pose(&part.name);
+        let transform = parent.unwrap_or_else(Similarity3::identity)
+            * similarity(pivot + Vector3::from(part_pose.offset), pose_rotation(&part_pose), 1.0)
+            * similarity(-pivot, UnitQuaternion::identity(), 1.0);
+        part_transforms.push(transform);
+        if let Some(part_mesh) = models.get_id_by_name(&part_model_name(name, &part.name)) {
+            let origin = Vector3::from(part.origin.map(|c| c as f32));
+            let to_world = entity * transform * similarity(origin, UnitQuaternion::identity(), 1.0);
+            result.push(transformed_model(part_mesh, &to_world));
+        }
+    }
+    result
+}
+
+/// The animations of a character model. The custom models of the players use the ones of the default character.
+pub fn character_animations<'a>(
+    models: &Registry<VoxelModel>,
+    animations: &'a BTreeMap<String, AnimationSet>,
+    mesh_id: u32,
+) -> Option<&'a AnimationSet> {
+    match models.get_name_by_id(mesh_id).and_then(|name| animations.get(name)) {
+        Some(model_animations) => Some(model_animations),
+        None => animations.get(&namespaced_key(DEFAULT_CHARACTER_MODEL)),
+    }
+}
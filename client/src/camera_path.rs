@@ -0,0 +1,124 @@
+//! Cinematic camera paths, for trailers and time-lapses. The keyframes are the positions and orientations of the
+//! camera, placed with the `campath` console command. The camera goes through them along a Catmull-Rom spline, which
+//! passes through every keyframe without sudden changes of direction. The paths are saved as RON files.
+
+use crate::input::YawPitch;
+use anyhow::{anyhow, Context, Result};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The folder of the saved camera paths
+pub const CAMERA_PATHS_FOLDER: &str = "camera_paths";
+/// Seconds between two keyframes when the playback duration is not given
+pub const DEFAULT_SEGMENT_DURATION: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub position: [f64; 3],
+    /// In degrees, like `YawPitch`
+    pub yaw: f64,
+    pub pitch: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    /// The position and the orientation of the camera at `t`, from 0 at the first keyframe to the number of
+    /// keyframes minus one at the last keyframe. None if there is no keyframe.
+    pub fn sample(&self, t: f64) -> Option<(Vector3<f64>, YawPitch)> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let t = t.clamp(0.0, last as f64);
+        let segment = (t.floor() as usize).min(last.saturating_sub(1));
+        let local_t = t - segment as f64;
+        // The keyframes before the first one and after the last one are the ends of the path
+        let index = |offset: isize| (segment as isize + offset).clamp(0, last as isize) as usize;
+        let indices = [index(-1), index(0), index(1), index(2)];
+
+        let position = catmull_rom(indices.map(|i| Vector3::from(self.keyframes[i].position)), local_t);
+        // The yaw turns the short way between two keyframes, even across -180 and 180 degrees
+        let mut yaws = indices.map(|i| self.keyframes[i].yaw);
+        for i in 1..yaws.len() {
+            yaws[i] = yaws[i - 1] + wrap_degrees(yaws[i] - yaws[i - 1]);
+        }
+        let yaw = wrap_degrees(catmull_rom(yaws, local_t));
+        let pitch = catmull_rom(indices.map(|i| self.keyframes[i].pitch), local_t).clamp(-90.0, 90.0);
+        Some((position, YawPitch { yaw, pitch }))
+    }
+
+    /// Save the path in the camera paths folder
+    pub fn save(&self, name: &str) -> Result<PathBuf> {
+        let path = path_of(name)?;
+        std::fs::create_dir_all(CAMERA_PATHS_FOLDER).context("Failed to create the camera paths folder")?;
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .context("Failed to serialize the camera path")?;
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Load a path saved by `save`
+    pub fn load(name: &str) -> Result<Self> {
+        let path = path_of(name)?;
+        let contents = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        ron::from_str(&contents).with_context(|| format!("Malformed camera path {}", path.display()))
+    }
+}
+
+/// The playback of a camera path
+#[derive(Debug, Clone)]
+pub struct CameraPathPlayback {
+    path: CameraPath,
+    /// Seconds between two keyframes
+    segment_duration: f64,
+    elapsed: f64,
+}
+
+impl CameraPathPlayback {
+    pub fn new(path: CameraPath, segment_duration: f64) -> Self {
+        Self {
+            path,
+            segment_duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Move the camera along the path. Return true once the end of the path is reached.
+    pub fn update(&mut self, seconds_delta: f64) -> bool {
+        self.elapsed += seconds_delta;
+        let duration = self.path.keyframes.len().saturating_sub(1) as f64 * self.segment_duration;
+        self.elapsed >= duration
+    }
+
+    /// The position and the orientation of the camera
+    pub fn camera(&self) -> Option<(Vector3<f64>, YawPitch)> {
+        self.path.sample(self.elapsed / self.segment_duration)
+    }
+}
+
+/// Interpolate between `points[1]` (t = 0) and `points[2]` (t = 1), with the tangents given by their neighbours
+fn catmull_rom<T>(points: [T; 4], t: f64) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T> + std::ops::Mul<f64, Output = T>,
+{
+    let [p0, p1, p2, p3] = points;
+    let (t2, t3) = (t * t, t * t * t);
+    (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+/// An angle in degrees between -180 and 180
+fn wrap_degrees(angle: f64) -> f64 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// The file of a camera path. The names are limited to letters, digits, `-` and `_`, to stay in the folder.
+fn path_of(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(anyhow!("Invalid camera path name {}, use letters, digits, - and _", name));
+    }
+    Ok(PathBuf::from(CAMERA_PATHS_FOLDER).join(format!("{}.ron", name)))
+}
@@ -0,0 +1,208 @@
+//! Drop-down console to enter commands. The commands starting with `/` are sent to the server,
+//! the other ones are run by the client.
+
+use crate::gui::Gui;
+use std::collections::VecDeque;
+use winit::keyboard::{Key, NamedKey};
+
+/// Maximum number of lines kept in the output
+const MAX_OUTPUT_LINES: usize = 200;
+/// Maximum number of commands kept in the history
+const MAX_HISTORY: usize = 100;
+const LINE_HEIGHT: i32 = 20;
+
+pub const INPUT_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+pub const INFO_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const ERROR_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+pub const SERVER_COLOR: [f32; 4] = [0.6, 0.9, 1.0, 1.0];
+
+/// A command that the console knows about, used for the help and the tab completion
+#[derive(Debug, Clone)]
+pub struct ConsoleCommand {
+    /// The name, starting with `/` for the server commands
+    pub name: String,
+    pub description: String,
+}
+
+pub struct Console {
+    open: bool,
+    input: String,
+    history: Vec<String>,
+    /// The position in the history while browsing it with the arrow keys
+    history_position: Option<usize>,
+    output: VecDeque<(String, [f32; 4])>,
+    commands: Vec<ConsoleCommand>,
+    /// The commands entered since the last call to `drain_submitted`
+    submitted: Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            input: String::new(),
+            history: Vec::new(),
+            history_position: None,
+            output: VecDeque::new(),
+            commands: Vec::new(),
+            submitted: Vec::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Add a command to the help and the tab completion
+    pub fn register_command(&mut self, name: impl Into<String>, description: impl Into<String>) {
+        let name = name.into();
+        self.commands.retain(|command| command.name != name);
+        self.commands.push(ConsoleCommand {
+            name,
+            description: description.into(),
+        });
+        self.commands.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// Remove the commands whose name starts with `prefix`, such as the commands of the server
+    pub fn unregister_commands(&mut self, prefix: &str) {
+        self.commands.retain(|command| !command.name.starts_with(prefix));
+    }
+
+    pub fn get_commands(&self) -> &[ConsoleCommand] {
+        &self.commands
+    }
+
+    /// Print some text, which can have multiple lines
+    pub fn print(&mut self, text: &str, color: [f32; 4]) {
+        for line in text.lines() {
+            if self.output.len() == MAX_OUTPUT_LINES {
+                self.output.pop_front();
+            }
+            self.output.push_back((line.to_owned(), color));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    /// Edit the input line with a key press, only when the console is open
+    pub fn handle_key_typed(&mut self, key: &Key) {
+        if !self.open {
+            return;
+        }
+        match key {
+            Key::Named(NamedKey::Enter) => self.submit(),
+            Key::Named(NamedKey::Backspace) => {
+                self.input.pop();
+            }
+            Key::Named(NamedKey::Tab) => self.complete(),
+            Key::Named(NamedKey::ArrowUp) => self.browse_history(true),
+            Key::Named(NamedKey::ArrowDown) => self.browse_history(false),
+            Key::Named(NamedKey::Space) => self.input.push(' '),
+            // The backtick opens and closes the console
+            Key::Character(text) => self.input.extend(text.chars().filter(|c| !c.is_control() && *c != '`')),
+            _ => (),
+        }
+    }
+
+    fn submit(&mut self) {
+        let command = std::mem::take(&mut self.input).trim().to_owned();
+        self.history_position = None;
+        if command.is_empty() {
+            return;
+        }
+        self.print(&format!("> {}", command), INPUT_COLOR);
+        if self.history.last() != Some(&command) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(command.clone());
+        }
+        self.submitted.push(command);
+    }
+
+    /// Run a command like if it was entered, without adding it to the history
+    pub fn run(&mut self, command: &str) {
+        self.print(&format!("> {}", command), INPUT_COLOR);
+        self.submitted.push(command.to_owned());
+    }
+
+    /// Return the commands entered since the last call
+    pub fn drain_submitted(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.submitted)
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        self.history_position = match (self.history_position, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(position), true) => Some(position.saturating_sub(1)),
+            (Some(position), false) if position + 1 < self.history.len() => Some(position + 1),
+            (Some(_), false) => None,
+        };
+        self.input = match self.history_position {
+            Some(position) => self.history[position].clone(),
+            None => String::new(),
+        };
+    }
+
+    /// Complete the name of the command, or list the candidates if there are several of them
+    fn complete(&mut self) {
+        if self.input.contains(' ') {
+            return;
+        }
+        let candidates = self
+            .commands
+            .iter()
+            .filter(|command| command.name.starts_with(&self.input))
+            .map(|command| command.name.clone())
+            .collect::<Vec<_>>();
+        match candidates.len() {
+            0 => (),
+            1 => self.input = format!("{} ", candidates[0]),
+            _ => {
+                // Complete the common prefix of the candidates
+                let mut prefix = candidates[0].clone();
+                for candidate in candidates.iter() {
+                    while !candidate.starts_with(&prefix) {
+                        prefix.pop();
+                    }
+                }
+                self.input = prefix;
+                self.print(&candidates.join("  "), INFO_COLOR);
+            }
+        }
+    }
+
+    /// Draw the console over the top half of the window
+    pub fn render(&self, gui: &mut Gui, window_width: i32, window_height: i32) {
+        if !self.open {
+            return;
+        }
+        let height = window_height / 2;
+        let style = gui.style("console");
+        let margin = style.padding;
+        gui.rect(0, 0, window_width, height, style.background, 0.004);
+        let input_y = height - margin - LINE_HEIGHT;
+        gui.text(margin, input_y, LINE_HEIGHT, format!("> {}_", self.input), INFO_COLOR, 0.003);
+        // The most recent output is just above the input line
+        let max_lines = ((input_y - margin) / LINE_HEIGHT).max(0) as usize;
+        for (i, (line, color)) in self.output.iter().rev().take(max_lines).enumerate() {
+            let y = input_y - (i as i32 + 1) * LINE_HEIGHT;
+            gui.text(margin, y, LINE_HEIGHT, line.clone(), *color, 0.003);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Crash reports: when the game panics or fails, a report with everything needed to investigate
+//! is written to the `crash-reports` folder.
+
+use log::{Log, Metadata, Record};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Folder where the crash reports are written
+const CRASH_REPORT_FOLDER: &str = "crash-reports";
+/// Number of log lines kept for the crash reports
+const RECENT_LOG_LINES: usize = 200;
+
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+/// Information about the game added to the crash reports, such as the settings or the GPU
+static CONTEXT: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Logger forwarding the records to `env_logger` and to the remote administration of the server,
+/// and keeping the last lines for the crash reports
+struct RecordingLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for RecordingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        // The server streams the logs to its remote administration tools
+        server::forward_log(&line);
+        if let Ok(mut recent_logs) = RECENT_LOGS.lock() {
+            if recent_logs.len() == RECENT_LOG_LINES {
+                recent_logs.pop_front();
+            }
+            recent_logs.push_back(line);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Initialize the logger and install the panic hook writing the crash reports
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter();
+    log::set_boxed_logger(Box::new(RecordingLogger { inner })).expect("Failed to set the logger");
+    log::set_max_level(max_level);
+
+    std::panic::set_hook(Box::new(|info| {
+        let thread = std::thread::current();
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Unknown panic payload".to_owned()
+        };
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_else(|| "unknown location".to_owned());
+        let description = format!(
+            "Thread '{}' panicked at {}:\n{}",
+            thread.name().unwrap_or("<unnamed>"),
+            location,
+            message,
+        );
+        write_report(&description);
+    }));
+}
+
+/// Add some information to the future crash reports
+pub fn set_context(key: impl ToString, value: impl ToString) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.insert(key.to_string(), value.to_string());
+    }
+}
+
+/// Write a crash report for an error that stops the game
+pub fn report_error(error: &anyhow::Error) {
+    write_report(&format!("Fatal error: {:?}", error));
+}
+
+/// Write the crash report, and tell the player where to find it
+fn write_report(description: &str) {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    let mut report = String::new();
+    let _ = writeln!(report, "=== MarsRobots crash report ===\n");
+    let _ = writeln!(report, "{}\n", description);
+    let _ = writeln!(report, "=== Backtrace ===\n{}\n", backtrace);
+    // Don't wait for the locks: the panic might have happened while they were held
+    if let Ok(context) = CONTEXT.try_lock() {
+        for (key, value) in context.iter() {
+            let _ = writeln!(report, "=== {} ===\n{}\n", key, value);
+        }
+    }
+    let _ = writeln!(report, "=== Recent log lines ===");
+    if let Ok(recent_logs) = RECENT_LOGS.try_lock() {
+        for line in recent_logs.iter() {
+            let _ = writeln!(report, "{}", line);
+        }
+    }
+
+    match save_report(Path::new(CRASH_REPORT_FOLDER), &report) {
+        Ok(path) => eprintln!(
+            "\nThe game crashed: {}\nA crash report was written to {}. Please attach it when reporting the problem.\n",
+            description,
+            path.display(),
+        ),
+        Err(e) => eprintln!("\nThe game crashed: {}\nThe crash report couldn't be saved ({}):\n{}", description, e, report),
+    }
+}
+
+fn save_report(folder: &Path, report: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(folder)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = folder.join(format!("crash-{}.txt", timestamp));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
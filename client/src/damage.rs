@@ -0,0 +1,119 @@
+//! Feedback of the hits sent by the server: the damage rising above the entities that were hurt, the red vignette on
+//! the side of the window the damage of the player came from, and the shake of the camera. The shake grows with the
+//! trauma of the player, which each hit raises and which wears off over time.
+
+use crate::render::Billboard;
+use nalgebra::Vector3;
+
+/// Seconds during which a damage number is shown
+const NUMBER_LIFETIME: f64 = 1.0;
+/// Speed at which the damage numbers rise, in blocks per second
+const NUMBER_RISE_SPEED: f64 = 0.8;
+/// Height of the text of the damage numbers, in blocks
+const NUMBER_SIZE: f64 = 0.35;
+/// Color of the health lost by the players and the creatures
+const HEALTH_COLOR: [f32; 4] = [1.0, 0.25, 0.2, 1.0];
+/// Color of the energy lost by the robots
+const ENERGY_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+/// Seconds during which a vignette is shown
+const VIGNETTE_LIFETIME: f64 = 0.8;
+/// Trauma added for each point of health lost, the trauma being between 0 and 1
+const TRAUMA_PER_DAMAGE: f64 = 0.08;
+/// Trauma lost every second
+const TRAUMA_DECAY: f64 = 1.2;
+/// Rotation of the camera at full trauma and full intensity, in degrees
+const MAX_SHAKE_ANGLE: f64 = 5.0;
+/// How fast the camera shakes, in radians per second
+const SHAKE_SPEED: f64 = 40.0;
+
+struct DamageNumber {
+    pos: Vector3<f64>,
+    text: String,
+    color: [f32; 4],
+    age: f64,
+}
+
+/// A hit taken by the player, shown by a vignette
+pub struct Vignette {
+    /// Direction the damage came from, None if it has no direction
+    pub from: Option<Vector3<f64>>,
+    /// Opacity of the vignette, from 0 to 1
+    pub opacity: f64,
+}
+
+#[derive(Default)]
+pub struct HitFeedback {
+    numbers: Vec<DamageNumber>,
+    /// The direction and the age of the hits taken by the player
+    hits: Vec<(Option<Vector3<f64>>, f64)>,
+    trauma: f64,
+    /// Seconds since the start, which move the camera shake
+    time: f64,
+}
+
+impl HitFeedback {
+    /// Show the damage taken by an entity above it. `energy` is true for the energy lost by the robots.
+    pub fn add_number(&mut self, pos: Vector3<f64>, amount: f64, energy: bool) {
+        self.numbers.push(DamageNumber {
+            pos,
+            text: format!("-{:.0}", amount.max(1.0)),
+            color: if energy { ENERGY_COLOR } else { HEALTH_COLOR },
+            age: 0.0,
+        });
+    }
+
+    /// The player, or the robot they control, was hurt
+    pub fn hurt(&mut self, amount: f64, from: Option<Vector3<f64>>) {
+        self.hits.push((from, 0.0));
+        self.trauma = (self.trauma + amount * TRAUMA_PER_DAMAGE).min(1.0);
+    }
+
+    pub fn update(&mut self, seconds_delta: f64) {
+        self.time += seconds_delta;
+        self.trauma = (self.trauma - TRAUMA_DECAY * seconds_delta).max(0.0);
+        for number in self.numbers.iter_mut() {
+            number.age += seconds_delta;
+            number.pos.y += NUMBER_RISE_SPEED * seconds_delta;
+        }
+        self.numbers.retain(|number| number.age < NUMBER_LIFETIME);
+        for (_, age) in self.hits.iter_mut() {
+            *age += seconds_delta;
+        }
+        self.hits.retain(|&(_, age)| age < VIGNETTE_LIFETIME);
+    }
+
+    /// The damage numbers, fading out as they rise
+    pub fn billboards(&self) -> impl Iterator<Item = Billboard> + '_ {
+        self.numbers.iter().map(|number| {
+            let mut color = number.color;
+            color[3] = (1.0 - number.age / NUMBER_LIFETIME) as f32;
+            Billboard {
+                pos: number.pos,
+                text: number.text.clone(),
+                color,
+                size: NUMBER_SIZE,
+                wrap_width: None,
+            }
+        })
+    }
+
+    /// The vignettes of the recent hits taken by the player
+    pub fn vignettes(&self) -> impl Iterator<Item = Vignette> + '_ {
+        self.hits.iter().map(|&(from, age)| Vignette {
+            from,
+            opacity: 1.0 - age / VIGNETTE_LIFETIME,
+        })
+    }
+
+    /// The rotation added to the yaw and the pitch of the camera, in degrees. `intensity` goes from 0, without shake,
+    /// to 1.
+    pub fn shake(&self, intensity: f64) -> (f64, f64) {
+        // The square of the trauma makes the small hits barely noticeable
+        let angle = MAX_SHAKE_ANGLE * self.trauma * self.trauma * intensity.clamp(0.0, 1.0);
+        let t = self.time * SHAKE_SPEED;
+        // Waves of unrelated frequencies, so that the shake doesn't look periodic
+        let yaw = (t.sin() + (2.3 * t + 1.0).sin()) / 2.0;
+        let pitch = ((1.7 * t + 2.0).sin() + (2.9 * t).sin()) / 2.0;
+        (angle * yaw, angle * pitch)
+    }
+}
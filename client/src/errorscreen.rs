@@ -0,0 +1,149 @@
+//! Screen listing the errors that prevent the game from starting, for example invalid game data
+
+use crate::gui::{Gui, Navigation};
+use crate::input::InputState;
+use crate::render::{UiRenderer, Uploader};
+use crate::settings::Settings;
+use crate::ui::Ui;
+use crate::window::{State, StateTransition, WindowBuffers, WindowData, WindowFlags};
+use anyhow::Result;
+use winit::event::{ElementState, MouseButton};
+
+const TITLE_COLOR: [f32; 4] = [1.0, 0.4, 0.1, 1.0];
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const LINE_HEIGHT: i32 = 22;
+const MARGIN: i32 = 40;
+
+pub struct ErrorScreen {
+    title: String,
+    lines: Vec<String>,
+    ui: Ui,
+    ui_renderer: UiRenderer,
+    uploader: Uploader,
+    gui: Gui,
+    should_exit: bool,
+}
+
+impl ErrorScreen {
+    pub fn new(
+        device: &mut wgpu::Device,
+        title: String,
+        lines: Vec<String>,
+    ) -> Result<(Box<dyn State>, wgpu::CommandBuffer)> {
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let ui_renderer = UiRenderer::new(device);
+        Ok((
+            Box::new(Self {
+                title,
+                lines,
+                ui: Ui::new(),
+                ui_renderer,
+                uploader: Uploader::new(),
+                gui: Gui::new(),
+                should_exit: false,
+            }),
+            encoder.finish(),
+        ))
+    }
+}
+
+impl State for ErrorScreen {
+    fn update(
+        &mut self,
+        _settings: &mut Settings,
+        _input_state: &InputState,
+        _data: &WindowData,
+        flags: &mut WindowFlags,
+        _seconds_delta: f64,
+        _device: &mut wgpu::Device,
+    ) -> Result<StateTransition> {
+        flags.grab_cursor = false;
+        flags.text_input = false;
+        if self.should_exit {
+            Ok(StateTransition::CloseWindow)
+        } else {
+            Ok(StateTransition::KeepCurrent)
+        }
+    }
+
+    fn render<'a>(
+        &mut self,
+        _settings: &Settings,
+        buffers: WindowBuffers<'a>,
+        device: &mut wgpu::Device,
+        data: &WindowData,
+        _input_state: &InputState,
+    ) -> Result<(StateTransition, wgpu::CommandBuffer)> {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.uploader.recall(device);
+        crate::render::clear_color_and_depth(&mut encoder, buffers);
+
+        let win_h = data.logical_window_size.height as i32;
+        self.gui.prepare();
+        self.gui.text(MARGIN, MARGIN, 30, self.title.clone(), TITLE_COLOR, 0.02);
+        // Keep the space for the quit button at the bottom
+        let max_lines = ((win_h - 4 * MARGIN - 60) / LINE_HEIGHT).max(1) as usize;
+        for (i, line) in self.lines.iter().take(max_lines).enumerate() {
+            let y = 2 * MARGIN + i as i32 * LINE_HEIGHT;
+            self.gui.text(MARGIN, y, LINE_HEIGHT - 4, line.clone(), TEXT_COLOR, 0.02);
+        }
+        if self.lines.len() > max_lines {
+            let text = format!("... and {} more, see the log for the full list", self.lines.len() - max_lines);
+            let y = 2 * MARGIN + max_lines as i32 * LINE_HEIGHT;
+            self.gui.text(MARGIN, y, LINE_HEIGHT - 4, text, TEXT_COLOR, 0.02);
+        }
+        if self.gui.button(0, MARGIN, win_h - MARGIN - 40, 200, 40).text("Quit".to_owned(), [0.0, 0.0, 0.0, 1.0]).build() {
+            self.should_exit = true;
+        }
+        self.gui.finish();
+
+        self.ui_renderer.render(
+            buffers,
+            device,
+            &mut encoder,
+            &mut self.uploader,
+            data,
+            &self.ui.ui,
+            &mut self.gui,
+            false,
+        );
+
+        self.uploader.finish();
+        Ok((StateTransition::KeepCurrent, encoder.finish()))
+    }
+
+    fn handle_mouse_motion(&mut self, _settings: Settings, _delta: (f64, f64)) {}
+
+    fn handle_cursor_movement(&mut self, logical_position: winit::dpi::LogicalPosition<f64>) {
+        let (x, y) = logical_position.into();
+        self.gui.update_mouse_position(x, y);
+    }
+
+    fn handle_mouse_state_changes(&mut self, changes: Vec<(MouseButton, ElementState)>) {
+        for (button, state) in changes.into_iter() {
+            if button == MouseButton::Left {
+                self.gui.update_mouse_button(state == ElementState::Pressed);
+            }
+        }
+    }
+
+    fn handle_key_state_changes(&mut self, changes: Vec<(Option<u32>, ElementState)>) {
+        for (key, state) in changes.into_iter() {
+            // Escape key
+            if key == Some(1) && state == ElementState::Pressed {
+                self.should_exit = true;
+            }
+        }
+    }
+
+    fn handle_navigation(&mut self, navigation: Navigation) {
+        self.gui.navigate(navigation);
+    }
+
+    fn recreate_gpu_resources(&mut self, device: &mut wgpu::Device) -> Result<wgpu::CommandBuffer> {
+        self.ui_renderer = UiRenderer::new(device);
+        self.uploader = Uploader::new();
+        Ok(device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None }).finish())
+    }
+}
@@ -0,0 +1,58 @@
+//! Controllers, to use the menus without the mouse. The d-pad moves the focus and the sliders, the south button (A
+//! or cross) presses the focused widget, and the start and east (B or circle) buttons act as the escape key. Without
+//! controller support on the system, the controllers are ignored.
+
+use crate::gui::Navigation;
+use gilrs::{Button, EventType, Gilrs};
+use log::warn;
+
+/// What a button of a controller does
+pub enum GamepadAction {
+    Navigate(Navigation),
+    /// Open or close the menu, like the escape key
+    Escape,
+}
+
+pub struct Gamepads {
+    /// None if the controllers are not supported
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                warn!("Controllers are not supported, only the keyboard and the mouse can be used: {}", e);
+                None
+            }
+        };
+        Self { gilrs }
+    }
+
+    /// Return the actions of the buttons pressed since the last call
+    pub fn poll(&mut self) -> Vec<GamepadAction> {
+        let mut actions = Vec::new();
+        let gilrs = match self.gilrs.as_mut() {
+            Some(gilrs) => gilrs,
+            None => return actions,
+        };
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            let button = match event {
+                EventType::ButtonPressed(button, _) => button,
+                _ => continue,
+            };
+            let action = match button {
+                Button::DPadUp => GamepadAction::Navigate(Navigation::Previous),
+                Button::DPadDown => GamepadAction::Navigate(Navigation::Next),
+                Button::DPadLeft => GamepadAction::Navigate(Navigation::Decrease),
+                Button::DPadRight => GamepadAction::Navigate(Navigation::Increase),
+                Button::South => GamepadAction::Navigate(Navigation::Activate),
+                Button::East | Button::Start => GamepadAction::Escape,
+                _ => continue,
+            };
+            actions.push(action);
+        }
+        actions
+    }
+}
@@ -0,0 +1,45 @@
+//! Achievement notifications and list
+
+use common::achievement::Achievement;
+
+/// Seconds during which a newly unlocked achievement is shown
+pub const TOAST_DURATION: f64 = 5.0;
+
+const TOAST_WIDTH: i32 = 320;
+const TOAST_HEIGHT: i32 = 50;
+const LINE_HEIGHT: i32 = 22;
+const LOCKED_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+
+/// Draw the recently unlocked achievements in the top right corner of the window
+pub fn render_toasts(gui: &mut super::Gui, toasts: &[(Achievement, f64)], window_width: i32) {
+    let style = gui.style("achievements");
+    let margin = style.padding;
+    let x = window_width - TOAST_WIDTH - margin;
+    for (i, (achievement, _)) in toasts.iter().enumerate() {
+        let y = margin + i as i32 * (TOAST_HEIGHT + margin);
+        gui.rect(x, y, TOAST_WIDTH, TOAST_HEIGHT, style.background, 0.02);
+        let title = format!("Achievement unlocked: {}", achievement.name());
+        gui.text(x + margin, y, TOAST_HEIGHT / 2, title, style.accent, 0.01);
+        let description = achievement.description().to_owned();
+        gui.text(x + margin, y + TOAST_HEIGHT / 2, TOAST_HEIGHT / 2, description, style.color, 0.01);
+    }
+}
+
+/// Draw the list of all the achievements in the center of the window, the locked ones are grayed out
+pub fn render_achievement_list(gui: &mut super::Gui, unlocked: &[Achievement], window_width: i32, window_height: i32) {
+    let style = gui.style("achievements");
+    let margin = style.padding;
+    let width = 500;
+    let height = (Achievement::ALL.len() as i32 + 2) * LINE_HEIGHT + 2 * margin;
+    let x = (window_width - width) / 2;
+    let y = (window_height - height) / 2;
+    gui.rect(x, y, width, height, style.background, 0.02);
+    let title = format!("Achievements ({}/{})", unlocked.len(), Achievement::ALL.len());
+    gui.text(x + margin, y + margin, LINE_HEIGHT, title, style.accent, 0.01);
+    for (i, achievement) in Achievement::ALL.iter().enumerate() {
+        let color = if unlocked.contains(achievement) { style.color } else { LOCKED_COLOR };
+        let text = format!("{}: {}", achievement.name(), achievement.description());
+        let line_y = y + margin + (i as i32 + 2) * LINE_HEIGHT;
+        gui.text(x + margin, line_y, LINE_HEIGHT, text, color, 0.01);
+    }
+}
@@ -0,0 +1,66 @@
+//! The red vignette showing where the damage taken by the player came from
+
+use crate::damage::HitFeedback;
+use nalgebra::Vector3;
+
+/// Width of the vignette along the edges of the window, in pixels
+const VIGNETTE_WIDTH: i32 = 120;
+/// The vignette fades out towards the center of the window in this number of bands
+const VIGNETTE_BANDS: i32 = 8;
+/// Opacity of the band along the edge of the window, for a fresh hit
+const MAX_OPACITY: f64 = 0.6;
+/// The opacity of the band along the edge with the reduced flashing
+const REDUCED_MAX_OPACITY: f64 = 0.25;
+
+/// Tint the edges of the window towards the recent hits taken by the player: the top edge for the damage that came
+/// from the front, the right edge for the damage that came from the right, and so on. The damage without direction
+/// tints all the edges. `yaw` is the yaw of the camera, in degrees. With `reduce_flashing`, the vignette is fainter.
+pub fn render_damage_vignette(
+    gui: &mut super::Gui,
+    feedback: &HitFeedback,
+    yaw: f64,
+    reduce_flashing: bool,
+    window_width: i32,
+    window_height: i32,
+) {
+    let yaw = yaw.to_radians();
+    let forward = Vector3::new(-yaw.sin(), 0.0, -yaw.cos());
+    let right = Vector3::new(yaw.cos(), 0.0, -yaw.sin());
+    // Opacity of the top, bottom, left and right edges
+    let mut edges = [0.0f64; 4];
+    for vignette in feedback.vignettes() {
+        let from = vignette.from.map(|from| Vector3::new(from.x, 0.0, from.z)).filter(|from| from.norm() > 1e-6);
+        let weights = match from {
+            Some(from) => {
+                let (front, side) = (from.normalize().dot(&forward), from.normalize().dot(&right));
+                [front.max(0.0), (-front).max(0.0), (-side).max(0.0), side.max(0.0)]
+            }
+            None => [1.0; 4],
+        };
+        for (edge, weight) in edges.iter_mut().zip(weights) {
+            *edge = edge.max(weight * vignette.opacity);
+        }
+    }
+    let [top, bottom, left, right] = edges;
+    let max_opacity = if reduce_flashing { REDUCED_MAX_OPACITY } else { MAX_OPACITY };
+    let [r, g, b] = gui.palette().damage;
+    for band in 0..VIGNETTE_BANDS {
+        let offset = band * VIGNETTE_WIDTH / VIGNETTE_BANDS;
+        let size = VIGNETTE_WIDTH / VIGNETTE_BANDS;
+        let fade = max_opacity * (1.0 - band as f64 / VIGNETTE_BANDS as f64);
+        let color = |opacity: f64| [r, g, b, (opacity * fade) as f32];
+        // Behind the rest of the interface, and in front of the teleport fade
+        if top > 0.0 {
+            gui.rect(0, offset, window_width, size, color(top), 0.045);
+        }
+        if bottom > 0.0 {
+            gui.rect(0, window_height - offset - size, window_width, size, color(bottom), 0.045);
+        }
+        if left > 0.0 {
+            gui.rect(offset, 0, size, window_height, color(left), 0.045);
+        }
+        if right > 0.0 {
+            gui.rect(window_width - offset - size, 0, size, window_height, color(right), 0.045);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Heads-up display: the vitals of the player, the biome and the temperature around them, their status effects, the
+//! hotbar, the progress of the item being used, the notices of the server, the captions of the sounds, and the
+//! download of the new game data of the server
+
+use crate::render::IconAtlas;
+use crate::sound::SoundCues;
+use common::biome::BiomeId;
+use common::effect::StatusEffect;
+use common::inventory::Inventory;
+use common::item::{Item, ItemStack};
+use common::player::{Vitals, MAX_VITAL};
+use common::registry::Registry;
+
+/// Seconds during which a notice is shown
+pub const NOTICE_DURATION: f64 = 4.0;
+
+const MARGIN: i32 = 10;
+pub(super) const SLOT_SIZE: i32 = 56;
+const SLOT_SPACING: i32 = 4;
+/// Space between the border of a slot and the icon of its items
+const ICON_MARGIN: i32 = 6;
+/// Width of the border of the selected slot
+const SELECTED_BORDER: i32 = 2;
+const METER_WIDTH: i32 = 200;
+const METER_HEIGHT: i32 = 16;
+const TEXT_HEIGHT: i32 = 16;
+const EFFECT_SIZE: i32 = 40;
+/// The labels of the meters take the warning color below this fraction of the maximum, and the oxygen alarm goes off
+pub const LOW_VITAL: f64 = 0.2;
+
+/// Draw the health, oxygen and energy meters in the bottom left corner of the window
+pub fn render_vitals(gui: &mut super::Gui, vitals: &Vitals, window_height: i32) {
+    let style = gui.style("hud");
+    let palette = gui.palette();
+    let meters = [
+        ("Health", vitals.health, palette.health),
+        ("Oxygen", vitals.oxygen, palette.oxygen),
+        ("Energy", vitals.energy, palette.energy),
+    ];
+    let label_width = 70;
+    for (i, (label, value, color)) in meters.into_iter().enumerate() {
+        let y = window_height - (3 - i as i32) * (METER_HEIGHT + MARGIN) - MARGIN;
+        let label_color = if value < LOW_VITAL * MAX_VITAL { palette.warning } else { style.color };
+        gui.text(MARGIN, y, TEXT_HEIGHT, label.to_owned(), label_color, 0.01);
+        let x = MARGIN + label_width;
+        gui.rect(x, y, METER_WIDTH, METER_HEIGHT, style.background, 0.02);
+        let filled = (METER_WIDTH as f64 * (value / MAX_VITAL).clamp(0.0, 1.0)) as i32;
+        gui.rect(x, y, filled, METER_HEIGHT, color, 0.01);
+        gui.text(x + METER_WIDTH + MARGIN, y, TEXT_HEIGHT, format!("{:.0}", value), label_color, 0.01);
+    }
+}
+
+/// Draw the name of the biome and the temperature of the air above the vitals
+pub fn render_climate(gui: &mut super::Gui, biome: BiomeId, temperature: f64, window_height: i32) {
+    let style = gui.style("hud");
+    let y = window_height - 4 * (METER_HEIGHT + MARGIN) - MARGIN;
+    let text = format!("{}, {:.0} °C", biome.biome().name, temperature);
+    gui.text(MARGIN, y, TEXT_HEIGHT, text, style.color, 0.01);
+}
+
+/// Draw the status effects of the player above the climate, with the first letters of their name, their level and the
+/// seconds before they wear off
+pub fn render_status_effects(gui: &mut super::Gui, effects: &[StatusEffect], window_height: i32) {
+    let style = gui.style("hud");
+    let y = window_height - 4 * (METER_HEIGHT + MARGIN) - 2 * MARGIN - EFFECT_SIZE;
+    for (i, effect) in effects.iter().enumerate() {
+        let x = MARGIN + i as i32 * (EFFECT_SIZE + MARGIN / 2);
+        let palette = gui.palette();
+        let color = if effect.kind.is_harmful() { palette.harmful_effect } else { palette.beneficial_effect };
+        gui.rect(x, y, EFFECT_SIZE, EFFECT_SIZE, color, 0.02);
+        let abbreviation = effect.kind.name().chars().take(2).collect::<String>();
+        gui.text(x + 3, y + 2, TEXT_HEIGHT, format!("{} {}", abbreviation, effect.level()), style.color, 0.01);
+        if let Some(remaining) = effect.remaining {
+            let text = format!("{:.0}s", remaining.ceil());
+            gui.text(x + 3, y + EFFECT_SIZE - TEXT_HEIGHT - 2, TEXT_HEIGHT, text, style.color, 0.01);
+        }
+    }
+}
+
+/// Draw the slots of the inventory at the bottom of the window, with the icon and the number of their items
+pub fn render_hotbar(
+    gui: &mut super::Gui,
+    inventory: &Inventory,
+    selected_slot: usize,
+    items: &Registry<Item>,
+    icons: &IconAtlas,
+    window_width: i32,
+    window_height: i32,
+) {
+    let style = gui.style("hud");
+    let width = inventory.size() as i32 * (SLOT_SIZE + SLOT_SPACING) - SLOT_SPACING;
+    let y = window_height - SLOT_SIZE - MARGIN;
+    for (i, slot) in inventory.slots().iter().enumerate() {
+        let x = (window_width - width) / 2 + i as i32 * (SLOT_SIZE + SLOT_SPACING);
+        if i == selected_slot {
+            let border = 2 * SELECTED_BORDER;
+            gui.rect(x - SELECTED_BORDER, y - SELECTED_BORDER, SLOT_SIZE + border, SLOT_SIZE + border, style.accent, 0.03);
+        }
+        gui.rect(x, y, SLOT_SIZE, SLOT_SIZE, style.background, 0.02);
+        // The number key of the slot
+        gui.text(x + 2, y, TEXT_HEIGHT, (i + 1).to_string(), style.accent, 0.01);
+        if let Some(stack) = slot {
+            render_stack(gui, stack, items, icons, x, y, 0.01);
+        }
+    }
+}
+
+/// Draw the icon and the number of the items of a stack in the slot at (x, y)
+pub(super) fn render_stack(
+    gui: &mut super::Gui,
+    stack: &ItemStack,
+    items: &Registry<Item>,
+    icons: &IconAtlas,
+    x: i32,
+    y: i32,
+    z: f32,
+) {
+    let style = gui.style("hud");
+    match icons.get(stack.item) {
+        Some(uv) => gui.icon(x + ICON_MARGIN, y + ICON_MARGIN, SLOT_SIZE - 2 * ICON_MARGIN, uv, z),
+        // The items without a model show their name, shortened to fit in the slot
+        None => {
+            let name = match stack.custom_name() {
+                Some(name) => name.to_owned(),
+                None => items.get_name_by_id(stack.item).unwrap_or("?").replace('_', " "),
+            };
+            let name = name.chars().take(7).collect::<String>();
+            gui.text(x + 2, y + SLOT_SIZE / 2 - TEXT_HEIGHT / 2, TEXT_HEIGHT, name, style.color, z);
+        }
+    }
+    let count = stack.count.to_string();
+    let count_x = x + SLOT_SIZE - 4 - 9 * count.len() as i32;
+    gui.text(count_x, y + SLOT_SIZE - TEXT_HEIGHT - 2, TEXT_HEIGHT, count, style.color, z);
+}
+
+/// Draw the progress of the use of an item above the hotbar, `progress` going from 0 to 1
+pub fn render_item_use(gui: &mut super::Gui, progress: f64, window_width: i32, window_height: i32) {
+    let style = gui.style("hud");
+    let x = (window_width - METER_WIDTH) / 2;
+    let y = window_height - SLOT_SIZE - 2 * MARGIN - METER_HEIGHT;
+    gui.rect(x, y, METER_WIDTH, METER_HEIGHT / 2, style.background, 0.02);
+    let filled = (METER_WIDTH as f64 * progress.clamp(0.0, 1.0)) as i32;
+    gui.rect(x, y, filled, METER_HEIGHT / 2, gui.palette().progress, 0.01);
+}
+
+/// Draw a short notice in the middle of the window, above the hotbar
+pub fn render_notice(gui: &mut super::Gui, text: &str, window_width: i32, window_height: i32) {
+    let style = gui.style("hud");
+    let width = (text.chars().count() as i32 * TEXT_HEIGHT * 3 / 5).max(METER_WIDTH) + 2 * MARGIN;
+    let x = (window_width - width) / 2;
+    let y = window_height - SLOT_SIZE - 4 * MARGIN - METER_HEIGHT - TEXT_HEIGHT;
+    gui.rect(x, y - MARGIN / 2, width, TEXT_HEIGHT + MARGIN, style.background, 0.02);
+    gui.text(x + MARGIN, y, TEXT_HEIGHT, text.to_owned(), style.color, 0.01);
+}
+
+/// Draw the captions of the recent sounds in the bottom right corner of the window, the newest at the bottom
+pub fn render_sound_cues(gui: &mut super::Gui, cues: &SoundCues, window_width: i32, window_height: i32) {
+    let style = gui.style("hud");
+    let mut y = window_height - MARGIN - TEXT_HEIGHT;
+    for caption in cues.captions().rev() {
+        let text_width = caption.chars().count() as f32 * (TEXT_HEIGHT * 3 / 5) as f32 * gui.font_scale;
+        let width = text_width as i32 + 2 * MARGIN;
+        let x = window_width - MARGIN - width;
+        gui.rect(x, y - MARGIN / 2, width, TEXT_HEIGHT + MARGIN, style.background, 0.02);
+        gui.text(x + MARGIN, y, TEXT_HEIGHT, caption.to_owned(), style.color, 0.01);
+        y -= TEXT_HEIGHT + MARGIN + MARGIN / 2;
+    }
+}
+
+/// Draw the progress of the download of the game data at the top of the window
+pub fn render_data_download(gui: &mut super::Gui, progress: f32, window_width: i32) {
+    let style = gui.style("hud");
+    let x = (window_width - METER_WIDTH) / 2;
+    let text = format!("Downloading the game data: {:.0}%", progress * 100.0);
+    gui.text(x, MARGIN, TEXT_HEIGHT, text, style.color, 0.01);
+    let y = MARGIN + TEXT_HEIGHT + MARGIN / 2;
+    gui.rect(x, y, METER_WIDTH, METER_HEIGHT / 2, style.background, 0.02);
+    let filled = (METER_WIDTH as f32 * progress.clamp(0.0, 1.0)) as i32;
+    gui.rect(x, y, filled, METER_HEIGHT / 2, gui.palette().progress, 0.01);
+}
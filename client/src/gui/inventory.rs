@@ -0,0 +1,211 @@
+//! Inventory screen: the slots of the player and of the container they opened. The stacks are dragged from a slot to
+//! another with the left button, half of a stack with the right button, and shift-click moves a stack to the other
+//! inventory. The screen only sends the changes to the server, then shows the inventories that the server sends back.
+//! Without the mouse, the focused slot is pressed once to pick up its stack, and another slot is pressed to put it.
+
+use super::hud::{render_stack, SLOT_SIZE};
+use super::tooltip::item_tooltip;
+use crate::render::IconAtlas;
+use common::inventory::{Inventory, InventoryId, SlotId, SlotTransaction};
+use common::item::Item;
+use common::registry::Registry;
+
+const CLOSE_BUTTON_ID: u32 = 1200;
+/// Id of the first slot of the container, the next slots use the next ids
+const FIRST_CONTAINER_SLOT_ID: u32 = 1300;
+/// Id of the first slot of the player
+const FIRST_PLAYER_SLOT_ID: u32 = 1500;
+const SLOT_SPACING: i32 = 4;
+const COLUMNS: i32 = 9;
+const LINE_HEIGHT: i32 = 30;
+const BUTTON_WIDTH: i32 = 90;
+
+/// Items being dragged with the mouse
+struct Drag {
+    from: SlotId,
+    count: u32,
+    /// The drag started with the right button, and ends when it is released
+    right_button: bool,
+    /// The drag started by pressing a slot with the keyboard or a controller, and ends when a slot is pressed
+    keyboard: bool,
+}
+
+/// What the player did on the inventory screen
+pub enum InventoryAction {
+    Transaction(SlotTransaction),
+    Close,
+}
+
+#[derive(Default)]
+pub struct InventoryScreen {
+    drag: Option<Drag>,
+    /// The state of the mouse buttons at the last frame, to know when they are pressed and released
+    was_left_down: bool,
+    was_right_down: bool,
+}
+
+impl InventoryScreen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw the open container and the inventory of the player below it in the center of the window, and return what
+    /// the player did. With `quick_transfer`, a click moves the stack to the other inventory instead.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        gui: &mut super::Gui,
+        inventory: &Inventory,
+        container: Option<&Inventory>,
+        items: &Registry<Item>,
+        icons: &IconAtlas,
+        quick_transfer: bool,
+        window_width: i32,
+        window_height: i32,
+    ) -> Option<InventoryAction> {
+        let mut sections = Vec::new();
+        if let Some(container) = container {
+            sections.push(("Storage crate", InventoryId::Container, container));
+        }
+        sections.push(("Inventory", InventoryId::Player, inventory));
+        let (style, slot_style, button_text) = (gui.style("panel"), gui.style("slot"), gui.style("button").color);
+        let margin = style.padding;
+        let rows = |inventory: &Inventory| (inventory.size() as i32 + COLUMNS - 1) / COLUMNS;
+        let section_height = |inventory: &Inventory| LINE_HEIGHT + rows(inventory) * (SLOT_SIZE + SLOT_SPACING);
+        let width = COLUMNS * (SLOT_SIZE + SLOT_SPACING) - SLOT_SPACING + 2 * margin;
+        let height = sections.iter().map(|&(_, _, inventory)| section_height(inventory)).sum::<i32>() + 2 * margin;
+        let x = (window_width - width) / 2;
+        let y = (window_height - height) / 2;
+        gui.rect(x, y, width, height, style.background, 0.02);
+        let mut action = None;
+        let close_x = x + width - margin - BUTTON_WIDTH;
+        let close_button = gui.button(CLOSE_BUTTON_ID, close_x, y + margin, BUTTON_WIDTH, LINE_HEIGHT - 4);
+        if close_button.text("Close".to_owned(), button_text).build() {
+            action = Some(InventoryAction::Close);
+        }
+
+        // The stack being dragged is cancelled if the server changed it meanwhile
+        let stack_at = |slot: SlotId| match slot.inventory {
+            InventoryId::Player => inventory.get(slot.slot),
+            InventoryId::Container => container.and_then(|container| container.get(slot.slot)),
+        };
+        if let Some(drag) = &self.drag {
+            if !stack_at(drag.from).is_some_and(|stack| stack.count >= drag.count) {
+                self.drag = None;
+            }
+        }
+
+        let mut hovered = None;
+        // The focused slot and its position, and the slot pressed without the mouse
+        let mut focused = None;
+        let mut pressed = None;
+        let mut section_y = y + margin;
+        for (title, id, inventory) in sections {
+            gui.text(x + margin, section_y, LINE_HEIGHT, title.to_owned(), style.accent, 0.01);
+            let first_slot_id = match id {
+                InventoryId::Player => FIRST_PLAYER_SLOT_ID,
+                InventoryId::Container => FIRST_CONTAINER_SLOT_ID,
+            };
+            for (i, stack) in inventory.slots().iter().enumerate() {
+                let slot = SlotId { inventory: id, slot: i };
+                let slot_x = x + margin + (i as i32 % COLUMNS) * (SLOT_SIZE + SLOT_SPACING);
+                let slot_y = section_y + LINE_HEIGHT + (i as i32 / COLUMNS) * (SLOT_SIZE + SLOT_SPACING);
+                if gui.focus_target(first_slot_id + i as u32) {
+                    pressed = Some(slot);
+                }
+                if gui.is_focused(first_slot_id + i as u32) {
+                    focused = Some((slot, slot_x, slot_y));
+                    gui.draw_focus_outline(slot_x, slot_y, SLOT_SIZE, SLOT_SIZE);
+                }
+                let color = if gui.is_mouse_inside(slot_x, slot_y, SLOT_SIZE, SLOT_SIZE) {
+                    hovered = Some(slot);
+                    slot_style.hovered
+                } else {
+                    slot_style.background
+                };
+                gui.rect(slot_x, slot_y, SLOT_SIZE, SLOT_SIZE, color, 0.015);
+                // The dragged items leave the rest of their stack in the slot
+                let dragged = self.drag.as_ref().filter(|drag| drag.from == slot).map_or(0, |drag| drag.count);
+                if let Some(stack) = stack.as_ref().filter(|stack| stack.count > dragged) {
+                    let stack = stack.with_count(stack.count - dragged);
+                    render_stack(gui, &stack, items, icons, slot_x, slot_y, 0.01);
+                }
+            }
+            section_y += section_height(inventory);
+        }
+
+        let left_pressed = gui.mouse_down && !self.was_left_down;
+        let left_released = !gui.mouse_down && self.was_left_down;
+        let right_pressed = gui.right_mouse_down && !self.was_right_down;
+        let right_released = !gui.right_mouse_down && self.was_right_down;
+        self.was_left_down = gui.mouse_down;
+        self.was_right_down = gui.right_mouse_down;
+        match self.drag.take() {
+            None => {
+                let clicked = hovered.and_then(|slot| Some((slot, stack_at(slot)?)));
+                let pressed = pressed.and_then(|slot| Some((slot, stack_at(slot)?)));
+                match (clicked, pressed) {
+                    (_, Some((slot, _))) if quick_transfer => {
+                        if container.is_some() {
+                            action = Some(InventoryAction::Transaction(SlotTransaction::QuickTransfer(slot)));
+                        }
+                    }
+                    (_, Some((slot, stack))) => {
+                        self.drag = Some(Drag { from: slot, count: stack.count, right_button: false, keyboard: true });
+                    }
+                    _ => (),
+                }
+                match clicked.filter(|_| pressed.is_none()) {
+                    Some((slot, _)) if left_pressed && quick_transfer => {
+                        if container.is_some() {
+                            action = Some(InventoryAction::Transaction(SlotTransaction::QuickTransfer(slot)));
+                        }
+                    }
+                    Some((slot, stack)) if left_pressed => {
+                        self.drag = Some(Drag { from: slot, count: stack.count, right_button: false, keyboard: false });
+                    }
+                    Some((slot, stack)) if right_pressed => {
+                        let count = stack.count.div_ceil(2);
+                        self.drag = Some(Drag { from: slot, count, right_button: true, keyboard: false });
+                    }
+                    _ => (),
+                }
+            }
+            Some(drag) => {
+                // Pressing the slot the stack comes from puts it back
+                let (released, to) = if drag.keyboard {
+                    (pressed.is_some(), pressed)
+                } else if drag.right_button {
+                    (right_released, hovered)
+                } else {
+                    (left_released, hovered)
+                };
+                if !released {
+                    self.drag = Some(drag);
+                } else if let Some(to) = to.filter(|&to| to != drag.from) {
+                    let transaction = SlotTransaction::Move { from: drag.from, to, count: drag.count };
+                    action = Some(InventoryAction::Transaction(transaction));
+                }
+            }
+        }
+
+        // The dragged items follow the mouse, or the focus without the mouse, above the slots
+        if let Some(drag) = &self.drag {
+            if let Some(stack) = stack_at(drag.from) {
+                let (drag_x, drag_y) = match focused.filter(|_| drag.keyboard) {
+                    Some((_, slot_x, slot_y)) => (slot_x + SLOT_SIZE / 2, slot_y + SLOT_SIZE / 2),
+                    None => (gui.mouse_x - SLOT_SIZE / 2, gui.mouse_y - SLOT_SIZE / 2),
+                };
+                gui.rect(drag_x, drag_y, SLOT_SIZE, SLOT_SIZE, slot_style.hovered, 0.004);
+                render_stack(gui, &stack.with_count(drag.count), items, icons, drag_x, drag_y, 0.003);
+            }
+        } else if let Some(stack) = hovered.and_then(stack_at) {
+            gui.tooltip(item_tooltip(&stack, items));
+        } else if let Some((slot, slot_x, slot_y)) = focused {
+            if let Some(stack) = stack_at(slot) {
+                gui.focus_tooltip(item_tooltip(&stack, items), slot_x, slot_y, SLOT_SIZE, SLOT_SIZE);
+            }
+        }
+        action
+    }
+}
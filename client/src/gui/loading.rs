@@ -0,0 +1,42 @@
+//! Screen shown while joining a world: the progress of each step, and a tip
+
+use crate::loading::{LoadingProgress, LoadingStage};
+
+const LINE_HEIGHT: i32 = 30;
+const BAR_WIDTH: i32 = 400;
+const BAR_HEIGHT: i32 = 12;
+/// Opacity of the names of the steps that didn't start yet
+const NEXT_STAGE_OPACITY: f32 = 0.5;
+
+/// Cover the window, in front of the rest of the interface, with the steps of the loading and a tip
+pub fn render_loading_progress(
+    gui: &mut super::Gui,
+    progress: &LoadingProgress,
+    window_width: i32,
+    window_height: i32,
+) {
+    let style = gui.style("loading");
+    gui.rect(0, 0, window_width, window_height, style.background, 0.003);
+    let x = (window_width - BAR_WIDTH) / 2;
+    let stage_height = LINE_HEIGHT + BAR_HEIGHT + style.padding;
+    let mut y = (window_height - LoadingStage::ALL.len() as i32 * stage_height) / 2;
+    for &stage in LoadingStage::ALL.iter() {
+        let fraction = progress.stage_fraction(stage);
+        let (text, color) = if stage == progress.stage() {
+            (format!("{}: {:.0}%", stage.label(), fraction * 100.0), style.color)
+        } else if stage < progress.stage() {
+            (stage.label().to_owned(), style.color)
+        } else {
+            let [r, g, b, a] = style.color;
+            (stage.label().to_owned(), [r, g, b, a * NEXT_STAGE_OPACITY])
+        };
+        gui.text(x, y, LINE_HEIGHT, text, color, 0.002);
+        gui.rect(x, y + LINE_HEIGHT, BAR_WIDTH, BAR_HEIGHT, style.hovered, 0.002);
+        let filled = (BAR_WIDTH as f32 * fraction) as i32;
+        gui.rect(x, y + LINE_HEIGHT, filled, BAR_HEIGHT, style.accent, 0.001);
+        y += stage_height;
+    }
+    let tip = format!("Tip: {}", progress.tip());
+    let tip_color = gui.style("text").color;
+    gui.text(x, window_height - 2 * LINE_HEIGHT, LINE_HEIGHT, tip, tip_color, 0.002);
+}
@@ -1,12 +1,57 @@
 use crate::ui::PrimitiveBuffer;
 
+pub mod achievements;
+pub mod damage;
 pub mod experiments;
+pub mod hud;
+pub mod inventory;
+pub mod loading;
+pub mod palette;
+pub mod replay;
+pub mod settings;
+pub mod sign;
+pub mod splash;
+pub mod statistics;
+pub mod teleport;
+pub mod theme;
+pub mod tooltip;
+pub mod trade;
+
+use self::palette::{ColorPalette, Palette};
+use self::theme::{ClassStyle, UiTheme};
+use self::tooltip::TooltipLine;
+use crate::settings::AccessibilitySettings;
+use common::theme::{Theme, DEFAULT_THEME};
+use log::warn;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Number of steps of the sliders moved with the keyboard or a controller
+const SLIDER_STEPS: f32 = 20.0;
+/// Bounds of the multiplier of the size of the text
+const MIN_FONT_SCALE: f32 = 0.5;
+const MAX_FONT_SCALE: f32 = 2.0;
+
+/// A command of the keyboard or of a controller, to use the GUI without the mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Navigation {
+    /// Focus the previous widget
+    Previous,
+    /// Focus the next widget
+    Next,
+    /// Decrease the focused slider, or focus the previous widget
+    Decrease,
+    /// Increase the focused slider, or focus the next widget
+    Increase,
+    /// Press the focused widget
+    Activate,
+}
 
 /// Immediate-mode GUI
 pub struct Gui {
     pub(self) mouse_x: i32,
     pub(self) mouse_y: i32,
     pub(self) mouse_down: bool,
+    pub(self) right_mouse_down: bool,
 
     pub(self) hot_item: u32,
     /// Active item. Ids 0 and 1 are reserved.
@@ -15,6 +60,29 @@ pub struct Gui {
     pub(self) active_item: u32,
 
     pub(self) primitives: PrimitiveBuffer,
+    /// The tooltip to draw at the end of the frame, empty if nothing with a tooltip is hovered
+    pub(self) tooltip: Vec<TooltipLine>,
+    /// Where the tooltip is drawn if not next to the mouse, such as the tooltip of the focused widget
+    pub(self) tooltip_position: Option<(i32, i32)>,
+
+    /// Focused item, 0 if none. The focus is moved with the keyboard or a controller.
+    pub(self) focused_item: u32,
+    /// The items that can be focused, in the order they were drawn during the frame
+    pub(self) focusable_items: Vec<u32>,
+    /// The navigation commands received since the last frame, handled one per frame
+    pub(self) navigation_queue: VecDeque<Navigation>,
+    /// The navigation command of the frame, None once a widget used it
+    pub(self) navigation: Option<Navigation>,
+
+    /// The colors that carry a meaning
+    pub(self) palette: &'static Palette,
+    /// Multiplier of the size of the text
+    pub(self) font_scale: f32,
+    /// The themes of the game data, by name
+    pub(self) themes: BTreeMap<String, Theme>,
+    /// The name of the theme in use, None if it must be resolved again
+    pub(self) theme_name: Option<String>,
+    pub(self) theme: UiTheme,
 }
 
 impl Gui {
@@ -24,9 +92,21 @@ impl Gui {
             mouse_x: 0,
             mouse_y: 0,
             mouse_down: false,
+            right_mouse_down: false,
             hot_item: 0,
             active_item: 0,
             primitives: Default::default(),
+            tooltip: Vec::new(),
+            tooltip_position: None,
+            focused_item: 0,
+            focusable_items: Vec::new(),
+            navigation_queue: VecDeque::new(),
+            navigation: None,
+            palette: ColorPalette::default().colors(),
+            font_scale: 1.0,
+            themes: BTreeMap::new(),
+            theme_name: None,
+            theme: UiTheme::new(None),
         }
     }
 
@@ -39,6 +119,49 @@ impl Gui {
     /// Update the state of the mouse button
     pub fn update_mouse_button(&mut self, is_down: bool) {
         self.mouse_down = is_down;
+        // The mouse takes over from the keyboard
+        if is_down {
+            self.focused_item = 0;
+        }
+    }
+
+    /// Update the state of the right mouse button
+    pub fn update_right_mouse_button(&mut self, is_down: bool) {
+        self.right_mouse_down = is_down;
+    }
+
+    /// Use the palette and the size of the text of the accessibility settings
+    pub fn set_accessibility(&mut self, settings: &AccessibilitySettings) {
+        self.palette = settings.palette.colors();
+        self.font_scale = settings.font_scale.clamp(MIN_FONT_SCALE, MAX_FONT_SCALE);
+    }
+
+    /// The colors that carry a meaning, such as the warnings
+    pub fn palette(&self) -> &'static Palette {
+        self.palette
+    }
+
+    /// Use the themes of new game data. The theme in use is resolved again on the next `select_theme`.
+    pub fn set_themes(&mut self, themes: BTreeMap<String, Theme>) {
+        self.themes = themes;
+        self.theme_name = None;
+    }
+
+    /// Use the theme `name` of the game data, or its default theme if it has no such theme
+    pub fn select_theme(&mut self, name: &str) {
+        if self.theme_name.as_deref() == Some(name) {
+            return;
+        }
+        if !self.themes.is_empty() && !self.themes.contains_key(name) {
+            warn!("The game data has no theme {}, using the default theme", name);
+        }
+        self.theme = UiTheme::new(self.themes.get(name).or_else(|| self.themes.get(DEFAULT_THEME)));
+        self.theme_name = Some(name.to_owned());
+    }
+
+    /// The style of a class of widgets in the theme in use
+    pub fn style(&self, class: &str) -> ClassStyle {
+        self.theme.style(class)
     }
 
     /// Drain stores primitives
@@ -46,13 +169,39 @@ impl Gui {
         std::mem::replace(&mut self.primitives, PrimitiveBuffer::default())
     }
 
+    /// Move the focus or press the focused widget during the next frames
+    pub fn navigate(&mut self, navigation: Navigation) {
+        self.navigation_queue.push_back(navigation);
+    }
+
     /// Prepare for frame drawing
     pub fn prepare(&mut self) {
         self.hot_item = 0;
+        self.tooltip.clear();
+        self.tooltip_position = None;
+        self.focusable_items.clear();
+        self.navigation = self.navigation_queue.pop_front();
     }
 
     /// Finish the frame
     pub fn finish(&mut self) {
+        // The focus is lost when the focused item is not drawn anymore, for example when its menu is closed
+        let focused = self.focusable_items.iter().position(|&id| id == self.focused_item);
+        if focused.is_none() {
+            self.focused_item = 0;
+        }
+        // The moves that no slider used go through the items, starting from the first or the last one
+        let count = self.focusable_items.len();
+        let next = match (self.navigation.take(), focused) {
+            (Some(Navigation::Next | Navigation::Increase), Some(i)) => Some((i + 1) % count),
+            (Some(Navigation::Previous | Navigation::Decrease), Some(i)) => Some((i + count - 1) % count),
+            (Some(Navigation::Next | Navigation::Increase), None) => Some(0),
+            (Some(Navigation::Previous | Navigation::Decrease), None) => count.checked_sub(1),
+            _ => None,
+        };
+        if let Some(next) = next.filter(|_| count > 0) {
+            self.focused_item = self.focusable_items[next];
+        }
         if !self.mouse_down {
             // If the mouse button is not down, then we allow an item to become active
             // when the mouse button will be pressed.
@@ -71,6 +220,34 @@ impl Gui {
         x <= self.mouse_x && self.mouse_x < x + w && y <= self.mouse_y && self.mouse_y < y + h
     }
 
+    /// Whether the item with this id is focused
+    pub fn is_focused(&self, id: u32) -> bool {
+        self.focused_item == id + 2
+    }
+
+    /// Let the keyboard and the controllers focus an item drawn by something else, such as the buttons of the menu,
+    /// and return whether it was pressed
+    pub fn focus_target(&mut self, id: u32) -> bool {
+        self.register_focusable(id + 2)
+    }
+
+    /// Add an item to the focus order of the frame, and return whether it was pressed without the mouse
+    fn register_focusable(&mut self, id: u32) -> bool {
+        self.focusable_items.push(id);
+        if self.focused_item == id && self.navigation == Some(Navigation::Activate) {
+            self.navigation = None;
+            return true;
+        }
+        false
+    }
+
+    /// Draw the outline of the focused item around its rectangle
+    fn draw_focus_outline(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        let style = self.style("focus");
+        let o = style.padding;
+        self.primitives.draw_rect(x - o, y - o, w + 2 * o, h + 2 * o, style.accent, 0.015);
+    }
+
     /// Draw a button, returning whether the button was pressed
     pub fn button(&mut self, id: u32, x: i32, y: i32, w: i32, h: i32) -> ButtonBuilder {
         ButtonBuilder {
@@ -81,12 +258,71 @@ impl Gui {
             w,
             h,
             text: None,
+            tooltip: Vec::new(),
         }
     }
 
+    /// Draw a horizontal slider between `min` and `max`, returning its value after the mouse dragged it
+    pub fn slider(&mut self, id: u32, x: i32, y: i32, w: i32, h: i32, value: f32, (min, max): (f32, f32)) -> f32 {
+        let id = id + 2;
+        if self.is_mouse_inside(x, y, w, h) {
+            self.hot_item = id;
+            if self.active_item == 0 && self.mouse_down {
+                self.active_item = id;
+            }
+        }
+        self.register_focusable(id);
+        let step = match self.navigation {
+            Some(Navigation::Decrease) if self.focused_item == id => -1.0,
+            Some(Navigation::Increase) if self.focused_item == id => 1.0,
+            _ => 0.0,
+        };
+        // The slider follows the mouse until the button is released, even outside of the slider
+        let value = if self.active_item == id && self.mouse_down {
+            min + (max - min) * ((self.mouse_x - x) as f32 / w as f32).clamp(0.0, 1.0)
+        } else if step != 0.0 {
+            self.navigation = None;
+            (value + step * (max - min) / SLIDER_STEPS).clamp(min, max)
+        } else {
+            value
+        };
+        if self.focused_item == id {
+            self.draw_focus_outline(x, y, w, h);
+        }
+        let filled = (w as f32 * ((value - min) / (max - min)).clamp(0.0, 1.0)) as i32;
+        let style = self.style("slider");
+        self.primitives.draw_rect(x + 3, y + 3, w, h, [0.0, 0.0, 0.0, 1.0], 0.02);
+        self.primitives.draw_rect(x, y, w, h, style.background, 0.01);
+        self.primitives.draw_rect(x, y, filled, h, style.accent, 0.008);
+        value
+    }
+
+    /// Draw a filled rectangle
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: [f32; 4], z: f32) {
+        self.primitives.draw_rect(x, y, w, h, color, z);
+    }
+
+    /// Draw the icon of an item, `uv` being its rectangle in the icon atlas
+    pub fn icon(&mut self, x: i32, y: i32, size: i32, uv: [f32; 4], z: f32) {
+        self.primitives.draw_icon(x, y, size, uv, z);
+    }
+
     /// Draw text, aligned to the left but centered vertically
     pub fn text(&mut self, x: i32, y: i32, h: i32, text: String, color: [f32; 4], z: f32) {
-        self.primitives.draw_text_simple(x, y, h, text, color, z);
+        let font_size = self.style("text").font_size * self.font_scale;
+        self.primitives.draw_text_sized(x, y, h, text, color, z, font_size);
+    }
+
+    /// Show a tooltip next to the mouse for this frame, replacing the tooltip set before in the frame
+    pub fn tooltip(&mut self, lines: Vec<TooltipLine>) {
+        self.tooltip = lines;
+        self.tooltip_position = None;
+    }
+
+    /// Show a tooltip next to the center of the focused item for this frame, replacing the tooltip set before
+    pub fn focus_tooltip(&mut self, lines: Vec<TooltipLine>, x: i32, y: i32, w: i32, h: i32) {
+        self.tooltip = lines;
+        self.tooltip_position = Some((x + w / 2, y + h / 2));
     }
 }
 
@@ -102,14 +338,25 @@ pub struct ButtonBuilder<'a> {
     w: i32,
     h: i32,
     text: Option<(String, [f32; 4])>,
+    tooltip: Vec<TooltipLine>,
 }
 
 impl<'a> ButtonBuilder<'a> {
     /// Build the button
     pub fn build(self) -> bool {
-        let Self { gui, id, x, y, w, h, text } = self;
+        let Self { gui, id, x, y, w, h, text, tooltip } = self;
+        let activated = gui.register_focusable(id);
+        if gui.focused_item == id {
+            gui.draw_focus_outline(x, y, w, h);
+            if !tooltip.is_empty() {
+                gui.focus_tooltip(tooltip.clone(), x, y, w, h);
+            }
+        }
         // Check if the mouse is inside the button
         if gui.is_mouse_inside(x, y, w, h) {
+            if !tooltip.is_empty() {
+                gui.tooltip(tooltip);
+            }
             // Then the button is hot
             gui.hot_item = id;
             // Maybe the button should also be active
@@ -120,22 +367,23 @@ impl<'a> ButtonBuilder<'a> {
         // Draw the shadow
         gui.primitives.draw_rect(x + 3, y + 3, w, h, [0.0, 0.0, 0.0, 1.0], 0.02);
         // Draw the button
+        let style = gui.style("button");
         let draw_pos;
         let button_color;
         if gui.hot_item == id {
             if gui.active_item == id {
                 // Hot and active
                 draw_pos = (x+2, y+2);
-                button_color = [0.7, 0.7, 0.7, 1.0];
+                button_color = style.hovered;
             } else {
                 // Just hot
                 draw_pos = (x, y);
-                button_color = [0.7, 0.7, 0.7, 1.0];
+                button_color = style.hovered;
             }
         } else {
             // Not hot but might be active
             draw_pos = (x, y);
-            button_color = [0.8, 0.8, 0.8, 1.0];
+            button_color = style.background;
         }
         gui.primitives.draw_rect(draw_pos.0, draw_pos.1, w, h, button_color, 0.01);
         if let Some((text, color)) = text {
@@ -145,7 +393,7 @@ impl<'a> ButtonBuilder<'a> {
         if !gui.mouse_down && gui.active_item == id && gui.hot_item == id {
             return true
         }
-        false
+        activated
     }
 
     /// Add text to the button
@@ -153,4 +401,10 @@ impl<'a> ButtonBuilder<'a> {
         self.text = Some((text, color));
         self
     }
+
+    /// Show a tooltip while the mouse is over the button
+    pub fn tooltip(mut self, lines: Vec<TooltipLine>) -> Self {
+        self.tooltip = lines;
+        self
+    }
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+//! The colors of the interface that carry a meaning, such as the low vitals or the harmful status effects. The
+//! colorblind palettes replace the colors that look alike to the colorblind players.
+
+use serde::{Deserialize, Serialize};
+
+/// The palette of the interface, set in the accessibility settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorPalette {
+    Default,
+    /// For the deuteranopia and the protanopia, where red and green look alike
+    RedGreen,
+    /// For the tritanopia, where blue and green, and yellow and violet, look alike
+    BlueYellow,
+}
+
+impl Default for ColorPalette {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+impl ColorPalette {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::RedGreen => "red-green safe",
+            Self::BlueYellow => "blue-yellow safe",
+        }
+    }
+
+    /// The palette after this one, to go through them with a button
+    pub fn next(self) -> Self {
+        match self {
+            Self::Default => Self::RedGreen,
+            Self::RedGreen => Self::BlueYellow,
+            Self::BlueYellow => Self::Default,
+        }
+    }
+
+    pub fn colors(self) -> &'static Palette {
+        match self {
+            Self::Default => &DEFAULT,
+            Self::RedGreen => &RED_GREEN,
+            Self::BlueYellow => &BLUE_YELLOW,
+        }
+    }
+}
+
+pub struct Palette {
+    pub health: [f32; 4],
+    pub oxygen: [f32; 4],
+    pub energy: [f32; 4],
+    /// The warnings, such as the low vitals and the trades the player can't afford
+    pub warning: [f32; 4],
+    pub harmful_effect: [f32; 4],
+    pub beneficial_effect: [f32; 4],
+    /// The progress bars, such as the use of an item
+    pub progress: [f32; 4],
+    /// The vignette of the damage taken by the player, without opacity
+    pub damage: [f32; 3],
+}
+
+const DEFAULT: Palette = Palette {
+    health: [0.85, 0.2, 0.2, 1.0],
+    oxygen: [0.3, 0.6, 1.0, 1.0],
+    energy: [1.0, 0.8, 0.2, 1.0],
+    warning: [1.0, 0.3, 0.2, 1.0],
+    harmful_effect: [0.6, 0.15, 0.1, 0.8],
+    beneficial_effect: [0.15, 0.5, 0.2, 0.8],
+    progress: [0.4, 0.9, 0.4, 1.0],
+    damage: [0.8, 0.0, 0.0],
+};
+
+/// Orange and blue instead of red and green
+const RED_GREEN: Palette = Palette {
+    health: [0.9, 0.6, 0.0, 1.0],
+    oxygen: [0.35, 0.7, 0.9, 1.0],
+    energy: [0.95, 0.9, 0.25, 1.0],
+    warning: [1.0, 0.55, 0.0, 1.0],
+    harmful_effect: [0.7, 0.35, 0.0, 0.8],
+    beneficial_effect: [0.0, 0.35, 0.65, 0.8],
+    progress: [0.35, 0.7, 0.9, 1.0],
+    damage: [0.9, 0.5, 0.0],
+};
+
+/// Red and cyan instead of blue and green, and pink instead of yellow
+const BLUE_YELLOW: Palette = Palette {
+    health: [0.85, 0.2, 0.2, 1.0],
+    oxygen: [0.2, 0.8, 0.85, 1.0],
+    energy: [0.9, 0.5, 0.7, 1.0],
+    warning: [1.0, 0.25, 0.25, 1.0],
+    harmful_effect: [0.65, 0.1, 0.1, 0.8],
+    beneficial_effect: [0.0, 0.5, 0.55, 0.8],
+    progress: [0.2, 0.8, 0.85, 1.0],
+    damage: [0.8, 0.0, 0.0],
+};
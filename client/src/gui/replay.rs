@@ -0,0 +1,45 @@
+//! Timeline of the replay mode
+
+use common::network::replay::ReplayControl;
+
+const MARGIN: i32 = 10;
+const LINE_HEIGHT: i32 = 22;
+const BAR_HEIGHT: i32 = 8;
+
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Draw the timeline at the bottom of the window. If it is `interactive`, return the position of the replay
+/// under the cursor while the mouse button is held on the timeline.
+pub fn render_timeline(
+    gui: &mut super::Gui,
+    control: &ReplayControl,
+    interactive: bool,
+    window_width: i32,
+    window_height: i32,
+) -> Option<f64> {
+    let (position, duration) = (control.position(), control.duration());
+    let x = MARGIN;
+    let y = window_height - MARGIN - BAR_HEIGHT;
+    let width = window_width - 2 * MARGIN;
+    let progress = if duration > 0.0 { (position / duration).clamp(0.0, 1.0) } else { 0.0 };
+    let style = gui.style("panel");
+    gui.rect(x, y, width, BAR_HEIGHT, style.background, 0.004);
+    gui.rect(x, y, (width as f64 * progress) as i32, BAR_HEIGHT, style.accent, 0.003);
+    let text = format!(
+        "{} / {} - speed x{}{}",
+        format_time(position),
+        format_time(duration),
+        control.speed(),
+        if control.is_paused() { " - paused" } else { "" }
+    );
+    gui.text(x, y - MARGIN - LINE_HEIGHT, LINE_HEIGHT, text, style.color, 0.003);
+    // The bar is thin, so it can be grabbed a bit above and below
+    if interactive && gui.mouse_down && gui.is_mouse_inside(x, y - MARGIN, width, BAR_HEIGHT + 2 * MARGIN) {
+        Some((gui.mouse_x - x) as f64 / width as f64 * duration)
+    } else {
+        None
+    }
+}
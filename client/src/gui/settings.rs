@@ -0,0 +1,97 @@
+//! Settings that can be changed from the menu
+
+use crate::settings::{AccessibilitySettings, PostProcessSettings};
+
+const SLIDER_ID: u32 = 1000;
+const CAMERA_SHAKE_SLIDER_ID: u32 = 1001;
+/// Id of the first accessibility option, the next options use the next ids
+const FIRST_ACCESSIBILITY_ID: u32 = 1010;
+const OPTION_WIDTH: i32 = 360;
+const SLIDER_WIDTH: i32 = 400;
+const SLIDER_HEIGHT: i32 = 40;
+const SLIDER_SPACING: i32 = 10;
+/// The gamma of the darkest and of the brightest end of the slider
+const BRIGHTNESS_RANGE: (f32, f32) = (0.5, 2.0);
+/// The text sizes that the text size option goes through
+const FONT_SCALES: [f32; 3] = [1.0, 1.25, 1.5];
+
+/// Draw the brightness slider under the buttons of the menu, and return the new gamma if it was dragged
+pub fn render_brightness_slider(
+    gui: &mut super::Gui,
+    settings: &PostProcessSettings,
+    window_width: i32,
+    window_height: i32,
+) -> Option<f32> {
+    let x = window_width / 2 - SLIDER_WIDTH / 2;
+    // The 3 buttons of the menu are centered, and 100 pixels high
+    let y = window_height / 2 + 150 + SLIDER_HEIGHT;
+    let gamma = gui.slider(SLIDER_ID, x, y, SLIDER_WIDTH, SLIDER_HEIGHT, settings.gamma, BRIGHTNESS_RANGE);
+    let text = format!("Brightness: {:.0}%", gamma * 100.0);
+    gui.text(x + 10, y, SLIDER_HEIGHT, text, gui.style("slider").color, 0.005);
+    if gamma != settings.gamma {
+        Some(gamma)
+    } else {
+        None
+    }
+}
+
+/// Draw the camera shake slider under the brightness slider, and return the new intensity if it was dragged
+pub fn render_camera_shake_slider(
+    gui: &mut super::Gui,
+    camera_shake: f64,
+    window_width: i32,
+    window_height: i32,
+) -> Option<f64> {
+    let x = window_width / 2 - SLIDER_WIDTH / 2;
+    let y = window_height / 2 + 150 + 2 * SLIDER_HEIGHT + SLIDER_SPACING;
+    let shake = camera_shake as f32;
+    let new_shake = gui.slider(CAMERA_SHAKE_SLIDER_ID, x, y, SLIDER_WIDTH, SLIDER_HEIGHT, shake, (0.0, 1.0));
+    let text = format!("Camera shake: {:.0}%", new_shake * 100.0);
+    gui.text(x + 10, y, SLIDER_HEIGHT, text, gui.style("slider").color, 0.005);
+    if new_shake != shake {
+        Some(new_shake as f64)
+    } else {
+        None
+    }
+}
+
+/// Draw the accessibility options to the right of the buttons of the menu, and return the new settings if an option
+/// was pressed
+pub fn render_accessibility_options(
+    gui: &mut super::Gui,
+    settings: &AccessibilitySettings,
+    window_width: i32,
+    window_height: i32,
+) -> Option<AccessibilitySettings> {
+    let x = window_width / 2 + 200 + 4 * SLIDER_SPACING;
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+    let options = vec![
+        format!("Colors: {}", settings.palette.name()),
+        format!("Sound captions: {}", on_off(settings.sound_cues)),
+        format!("Less flashing: {}", on_off(settings.reduce_flashing)),
+        format!("Less camera shake: {}", on_off(settings.reduce_screen_shake)),
+        format!("Text size: {:.0}%", settings.font_scale * 100.0),
+    ];
+    let text_color = gui.style("button").color;
+    let mut pressed = None;
+    for (i, text) in options.into_iter().enumerate() {
+        // Next to the 3 buttons of the menu
+        let y = window_height / 2 - 150 + i as i32 * (SLIDER_HEIGHT + SLIDER_SPACING);
+        let id = FIRST_ACCESSIBILITY_ID + i as u32;
+        if gui.button(id, x, y, OPTION_WIDTH, SLIDER_HEIGHT).text(text, text_color).build() {
+            pressed = Some(i);
+        }
+    }
+    let mut new_settings = settings.clone();
+    match pressed? {
+        0 => new_settings.palette = settings.palette.next(),
+        1 => new_settings.sound_cues = !settings.sound_cues,
+        2 => new_settings.reduce_flashing = !settings.reduce_flashing,
+        3 => new_settings.reduce_screen_shake = !settings.reduce_screen_shake,
+        _ => {
+            let current = FONT_SCALES.iter().position(|&scale| scale == settings.font_scale);
+            new_settings.font_scale = FONT_SCALES[current.map_or(0, |i| (i + 1) % FONT_SCALES.len())];
+        }
+    }
+    Some(new_settings)
+}
@@ -0,0 +1,91 @@
+//! Editor of the text of the signs, opened when the player places a sign or interacts with one
+
+use common::sign::MAX_SIGN_TEXT_LENGTH;
+use common::world::BlockPos;
+use winit::keyboard::{Key, NamedKey};
+
+const SAVE_BUTTON_ID: u32 = 1600;
+const CANCEL_BUTTON_ID: u32 = 1601;
+const LINE_HEIGHT: i32 = 30;
+const WIDTH: i32 = 560;
+const BUTTON_WIDTH: i32 = 90;
+/// The text is shown on lines of at most this number of characters
+const MAX_LINE_LENGTH: usize = 40;
+
+/// What the player did in the sign editor
+pub enum SignAction {
+    /// Send the text to the server
+    Save,
+    Cancel,
+}
+
+/// The text of a sign being written
+pub struct SignEditor {
+    pub pos: BlockPos,
+    pub text: String,
+}
+
+impl SignEditor {
+    pub fn new(pos: BlockPos, text: String) -> Self {
+        Self { pos, text }
+    }
+
+    /// Type some text, which stops at the maximum length of the signs. Enter saves the text.
+    pub fn handle_key_typed(&mut self, key: &Key) -> Option<SignAction> {
+        let typed = match key {
+            Key::Named(NamedKey::Enter) => return Some(SignAction::Save),
+            Key::Named(NamedKey::Backspace) => {
+                self.text.pop();
+                return None;
+            }
+            Key::Named(NamedKey::Space) => " ",
+            Key::Character(text) => text.as_str(),
+            _ => return None,
+        };
+        for c in typed.chars().filter(|c| !c.is_control()) {
+            if self.text.len() + c.len_utf8() > MAX_SIGN_TEXT_LENGTH {
+                break;
+            }
+            self.text.push(c);
+        }
+        None
+    }
+}
+
+/// Draw the sign editor in the center of the window, and return what the player clicked
+pub fn render_sign_editor(
+    gui: &mut super::Gui,
+    editor: &SignEditor,
+    window_width: i32,
+    window_height: i32,
+) -> Option<SignAction> {
+    let (style, button_text) = (gui.style("panel"), gui.style("button").color);
+    let margin = style.padding;
+    // The cursor is drawn after the text, on a new line once the last line is full
+    let chars = editor.text.chars().chain(std::iter::once('_')).collect::<Vec<_>>();
+    let lines = chars.chunks(MAX_LINE_LENGTH).map(|line| line.iter().collect::<String>()).collect::<Vec<_>>();
+    let height = (lines.len() as i32 + 2) * LINE_HEIGHT + 2 * margin;
+    let x = (window_width - WIDTH) / 2;
+    let y = (window_height - height) / 2;
+    gui.rect(x, y, WIDTH, height, style.background, 0.02);
+    gui.text(x + margin, y + margin, LINE_HEIGHT, "Sign".to_owned(), style.accent, 0.01);
+    for (i, line) in lines.into_iter().enumerate() {
+        let line_y = y + margin + (i as i32 + 1) * LINE_HEIGHT;
+        gui.text(x + margin, line_y, LINE_HEIGHT, line, style.color, 0.01);
+    }
+    let buttons_y = y + height - margin - LINE_HEIGHT;
+    let length = format!("{}/{}", editor.text.len(), MAX_SIGN_TEXT_LENGTH);
+    gui.text(x + margin, buttons_y, LINE_HEIGHT, length, style.color, 0.01);
+    let mut action = None;
+    let cancel_x = x + WIDTH - margin - BUTTON_WIDTH;
+    let cancel_button = gui.button(CANCEL_BUTTON_ID, cancel_x, buttons_y, BUTTON_WIDTH, LINE_HEIGHT - 4);
+    if cancel_button.text("Cancel".to_owned(), button_text).build() {
+        action = Some(SignAction::Cancel);
+    }
+    let save_x = cancel_x - margin - BUTTON_WIDTH;
+    let save_button = gui.button(SAVE_BUTTON_ID, save_x, buttons_y, BUTTON_WIDTH, LINE_HEIGHT - 4);
+    if save_button.text("Save".to_owned(), button_text).build() {
+        action = Some(SignAction::Save);
+    }
+    action
+}
@@ -0,0 +1,26 @@
+//! Screen shown when the game starts, while the renderers are warmed up
+
+const LOGO: &str = "MarsRobots";
+const LOGO_HEIGHT: i32 = 100;
+const LINE_HEIGHT: i32 = 30;
+const BAR_WIDTH: i32 = 400;
+const BAR_HEIGHT: i32 = 12;
+
+/// Cover the window with the logo of the game, and the progress of the warm-up under it
+pub fn render_splash(gui: &mut super::Gui, progress: f32, status: String, window_width: i32, window_height: i32) {
+    let style = gui.style("splash");
+    let text_color = gui.style("text").color;
+    let font_size = gui.style("text").font_size * gui.font_scale;
+    gui.rect(0, 0, window_width, window_height, style.background, 0.003);
+    let y = window_height / 2 - LOGO_HEIGHT;
+    let logo_size = style.font_size * gui.font_scale;
+    gui.primitives.draw_text_centered(0, y, window_width, LOGO_HEIGHT, LOGO.to_owned(), style.color, 0.002, logo_size);
+
+    let x = (window_width - BAR_WIDTH) / 2;
+    let y = window_height / 2 + style.padding;
+    gui.rect(x, y, BAR_WIDTH, BAR_HEIGHT, style.hovered, 0.002);
+    let filled = (BAR_WIDTH as f32 * progress.clamp(0.0, 1.0)) as i32;
+    gui.rect(x, y, filled, BAR_HEIGHT, style.accent, 0.001);
+    let y = y + BAR_HEIGHT + style.padding;
+    gui.primitives.draw_text_centered(0, y, window_width, LINE_HEIGHT, status, text_color, 0.002, font_size);
+}
@@ -0,0 +1,24 @@
+//! Statistics screen
+
+use common::statistics::PlayerStatistics;
+
+const LINE_HEIGHT: i32 = 22;
+
+/// Draw the statistics of the player in the center of the window
+pub fn render_statistics(gui: &mut super::Gui, statistics: &PlayerStatistics, window_width: i32, window_height: i32) {
+    let lines = statistics.summary();
+    let style = gui.style("panel");
+    let margin = style.padding;
+    // Keep the box inside the window if many kinds of blocks were mined
+    let max_lines = ((window_height - 4 * margin) / LINE_HEIGHT - 2).max(1) as usize;
+    let width = 400;
+    let height = (lines.len().min(max_lines) as i32 + 2) * LINE_HEIGHT + 2 * margin;
+    let x = (window_width - width) / 2;
+    let y = (window_height - height) / 2;
+    gui.rect(x, y, width, height, style.background, 0.02);
+    gui.text(x + margin, y + margin, LINE_HEIGHT, "Statistics".to_owned(), style.accent, 0.01);
+    for (i, line) in lines.into_iter().take(max_lines).enumerate() {
+        let line_y = y + margin + (i as i32 + 2) * LINE_HEIGHT;
+        gui.text(x + margin, line_y, LINE_HEIGHT, line, style.color, 0.01);
+    }
+}
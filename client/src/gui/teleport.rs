@@ -0,0 +1,20 @@
+//! Screen hiding the world during the far teleports
+
+use crate::teleport::TeleportFade;
+
+const LINE_HEIGHT: i32 = 30;
+
+/// Cover the window with black, and tell the player that the destination is loading
+pub fn render_teleport_fade(gui: &mut super::Gui, fade: &TeleportFade, window_width: i32, window_height: i32) {
+    let opacity = fade.opacity() as f32;
+    if opacity <= 0.0 {
+        return;
+    }
+    // Behind the rest of the interface
+    gui.rect(0, 0, window_width, window_height, [0.0, 0.0, 0.0, opacity], 0.05);
+    if fade.is_loading() {
+        let x = window_width / 2 - 80;
+        let y = window_height / 2 - LINE_HEIGHT / 2;
+        gui.text(x, y, LINE_HEIGHT, "Teleporting...".to_owned(), [1.0, 1.0, 1.0, opacity], 0.04);
+    }
+}
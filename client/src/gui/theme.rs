@@ -0,0 +1,74 @@
+//! The theme of the interface, resolved from the themes of the game data. Each widget looks up the style of its class,
+//! with all the properties set: the properties missing from the theme of the data come from the built-in theme.
+
+use common::theme::{Style, Theme};
+use log::warn;
+use std::collections::HashMap;
+
+/// The class whose properties are used for the ones a class doesn't set
+const DEFAULT_CLASS: &str = "default";
+
+/// The style of a class of widgets, with all its properties
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassStyle {
+    pub color: [f32; 4],
+    pub background: [f32; 4],
+    pub hovered: [f32; 4],
+    pub accent: [f32; 4],
+    pub padding: i32,
+    pub font_size: f32,
+}
+
+impl ClassStyle {
+    fn new(style: Style) -> Self {
+        // The default class of the built-in theme sets all the properties
+        Self {
+            color: style.color.unwrap_or_default(),
+            background: style.background.unwrap_or_default(),
+            hovered: style.hovered.unwrap_or_default(),
+            accent: style.accent.unwrap_or_default(),
+            padding: style.padding.unwrap_or_default(),
+            font_size: style.font_size.unwrap_or_default(),
+        }
+    }
+}
+
+pub struct UiTheme {
+    classes: HashMap<String, ClassStyle>,
+    /// The style of the classes that no theme defines
+    default: ClassStyle,
+}
+
+impl UiTheme {
+    /// Resolve the styles of the classes of `theme`, or of the built-in theme if there is no theme. A property of a
+    /// class comes from the class of `theme`, or else from the class of the built-in theme, or else from the default
+    /// class of `theme`, or else from the default class of the built-in theme.
+    pub fn new(theme: Option<&Theme>) -> Self {
+        let builtin = Theme::builtin();
+        if let Some(theme) = theme {
+            for class in theme.classes.keys().filter(|class| !builtin.classes.contains_key(*class)) {
+                warn!("The theme has a style for the class {}, which no widget uses", class);
+            }
+        }
+        let theme_default = class_style(theme, DEFAULT_CLASS);
+        let builtin_default = class_style(Some(&builtin), DEFAULT_CLASS);
+        let resolve = |class: &str| {
+            let style = class_style(theme, class).or(&class_style(Some(&builtin), class));
+            ClassStyle::new(style.or(&theme_default).or(&builtin_default))
+        };
+        Self {
+            classes: builtin.classes.keys().map(|class| (class.clone(), resolve(class))).collect(),
+            default: resolve(DEFAULT_CLASS),
+        }
+    }
+
+    /// The style of a class of widgets, such as `button` or `panel`
+    pub fn style(&self, class: &str) -> ClassStyle {
+        self.classes.get(class).copied().unwrap_or(self.default)
+    }
+}
+
+/// The style of a class in a theme, without properties if the theme doesn't have the class
+fn class_style(theme: Option<&Theme>, class: &str) -> Style {
+    theme.and_then(|theme| theme.classes.get(class)).cloned().unwrap_or_default()
+}
@@ -0,0 +1,122 @@
+//! Tooltips: a panel next to the mouse that describes the slot or the widget under it. The widgets set the tooltip
+//! while the frame is drawn, and it is drawn last, above the rest of the UI and moved to stay inside the window.
+
+use common::item::{Item, ItemStack, ItemType};
+use common::registry::Registry;
+
+const LINE_HEIGHT: i32 = 22;
+/// Width of a character of the text, which uses a monospace font
+const CHAR_WIDTH: i32 = 12;
+/// The font size for which `LINE_HEIGHT` and `CHAR_WIDTH` are set
+const BASE_FONT_SIZE: f32 = 20.0;
+/// Distance between the mouse and the corner of the panel
+const MOUSE_OFFSET: i32 = 16;
+/// The descriptions are wrapped to lines of at most this number of characters
+const MAX_LINE_LENGTH: usize = 40;
+const TITLE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SUBTITLE_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const DESCRIPTION_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+const STAT_COLOR: [f32; 4] = [0.6, 0.9, 1.0, 1.0];
+
+/// A line of a tooltip, with its color
+pub type TooltipLine = (String, [f32; 4]);
+
+/// The tooltip of a stack: its name, the description of its item, its data and the stats of its item
+pub fn item_tooltip(stack: &ItemStack, items: &Registry<Item>) -> Vec<TooltipLine> {
+    let item = items.get_value_by_id(stack.item);
+    let name = items.get_name_by_id(stack.item).unwrap_or("?").replace('_', " ");
+    let mut lines = Vec::new();
+    match stack.custom_name() {
+        Some(custom_name) => {
+            lines.push((custom_name.to_owned(), TITLE_COLOR));
+            lines.push((name, SUBTITLE_COLOR));
+        }
+        None => lines.push((name, TITLE_COLOR)),
+    }
+    if let Some(item) = item {
+        for line in wrap(item.ty.description()) {
+            lines.push((line, DESCRIPTION_COLOR));
+        }
+    }
+    if let Some(durability) = stack.durability() {
+        lines.push((format!("Durability: {} uses left", durability), STAT_COLOR));
+    }
+    if let Some(charge) = stack.charge() {
+        lines.push((format!("Charge: {}%", charge), STAT_COLOR));
+    }
+    if let Some(program) = stack.robot_program() {
+        lines.push((format!("Program: {}", program), STAT_COLOR));
+    }
+    match item.map(|item| &item.ty) {
+        Some(ItemType::Consumable { use_time, health, oxygen, energy, .. }) => {
+            for (amount, vital) in [(health, "health"), (oxygen, "oxygen"), (energy, "energy")] {
+                if *amount > 0.0 {
+                    lines.push((format!("Restores {} {}", amount, vital), STAT_COLOR));
+                }
+            }
+            lines.push((format!("Used in {} s", use_time), STAT_COLOR));
+        }
+        Some(ItemType::Flashlight { energy_use, .. }) => {
+            lines.push((format!("Uses {} energy per second while on", energy_use), STAT_COLOR));
+        }
+        Some(ItemType::BlockItem { .. }) => lines.push(("Block".to_owned(), STAT_COLOR)),
+        Some(ItemType::NormalItem { .. }) | None => (),
+    }
+    lines
+}
+
+/// Split a text into lines of at most `MAX_LINE_LENGTH` characters, between its words
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > MAX_LINE_LENGTH {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draw the tooltip set during the frame below and to the right of the mouse, or on the other side of the mouse if
+/// it doesn't fit in the window. The tooltip of the focused item is placed the same way around its center. It must be
+/// called after everything else of the frame is drawn.
+pub fn render_tooltip(gui: &mut super::Gui, window_width: i32, window_height: i32) {
+    let lines = std::mem::take(&mut gui.tooltip);
+    if lines.is_empty() {
+        return;
+    }
+    // The panel grows with the size of the text
+    let style = gui.style("tooltip");
+    let font_size = style.font_size * gui.font_scale;
+    let scale = font_size / BASE_FONT_SIZE;
+    let (char_width, line_height) = ((CHAR_WIDTH as f32 * scale) as i32, (LINE_HEIGHT as f32 * scale) as i32);
+    let longest = lines.iter().map(|(line, _)| line.chars().count()).max().unwrap_or(0) as i32;
+    let padding = style.padding;
+    let width = longest * char_width + 2 * padding;
+    let height = lines.len() as i32 * line_height + 2 * padding;
+    let place = |mouse: i32, size: i32, window_size: i32| {
+        let position = if mouse + MOUSE_OFFSET + size <= window_size {
+            mouse + MOUSE_OFFSET
+        } else {
+            mouse - MOUSE_OFFSET - size
+        };
+        position.min(window_size - size).max(0)
+    };
+    let (anchor_x, anchor_y) = gui.tooltip_position.unwrap_or((gui.mouse_x, gui.mouse_y));
+    let x = place(anchor_x, width, window_width);
+    let y = place(anchor_y, height, window_height);
+    let overlay = gui.primitives.overlay.get_or_insert_with(Default::default);
+    overlay.draw_rect(x - 1, y - 1, width + 2, height + 2, style.accent, 0.0);
+    overlay.draw_rect(x, y, width, height, style.background, 0.0);
+    for (i, (line, color)) in lines.into_iter().enumerate() {
+        let line_y = y + padding + i as i32 * line_height;
+        overlay.draw_text_sized(x + padding, line_y, line_height, line, color, 0.0, font_size);
+    }
+}
@@ -0,0 +1,68 @@
+//! Trade screen of the supply terminals
+
+use super::tooltip::item_tooltip;
+use common::inventory::Inventory;
+use common::item::{Item, ItemStack};
+use common::registry::Registry;
+use common::trade::Trade;
+
+/// Id of the button of the first trade, the next trades use the next ids
+const FIRST_BUTTON_ID: u32 = 1100;
+const CLOSE_BUTTON_ID: u32 = 1099;
+const LINE_HEIGHT: i32 = 30;
+const WIDTH: i32 = 560;
+const BUTTON_WIDTH: i32 = 90;
+
+/// What the player clicked on the trade screen
+pub enum TradeAction {
+    /// Make the trade with this index
+    Trade(usize),
+    Close,
+}
+
+/// Draw the trades of a supply terminal in the center of the window, and return what the player clicked
+pub fn render_trades(
+    gui: &mut super::Gui,
+    trades: &[Trade],
+    inventory: &Inventory,
+    items: &Registry<Item>,
+    window_width: i32,
+    window_height: i32,
+) -> Option<TradeAction> {
+    let (style, button_text) = (gui.style("panel"), gui.style("button").color);
+    let margin = style.padding;
+    // Keep the box inside the window if the terminal has many trades
+    let max_lines = ((window_height - 4 * margin) / LINE_HEIGHT - 2).max(1) as usize;
+    let height = (trades.len().min(max_lines) as i32 + 2) * LINE_HEIGHT + 2 * margin;
+    let x = (window_width - WIDTH) / 2;
+    let y = (window_height - height) / 2;
+    gui.rect(x, y, WIDTH, height, style.background, 0.02);
+    gui.text(x + margin, y + margin, LINE_HEIGHT, "Supply terminal".to_owned(), style.accent, 0.01);
+    let mut action = None;
+    let close_x = x + WIDTH - margin - BUTTON_WIDTH;
+    let close_button = gui.button(CLOSE_BUTTON_ID, close_x, y + margin, BUTTON_WIDTH, LINE_HEIGHT - 4);
+    if close_button.text("Close".to_owned(), button_text).build() {
+        action = Some(TradeAction::Close);
+    }
+    let stack_name = |stack: &ItemStack| {
+        let name = items.get_name_by_id(stack.item).unwrap_or("?").replace('_', " ");
+        format!("{} {}", stack.count, name)
+    };
+    for (i, trade) in trades.iter().take(max_lines).enumerate() {
+        let line_y = y + margin + (i as i32 + 2) * LINE_HEIGHT;
+        let cost = trade.cost.iter().map(stack_name).collect::<Vec<_>>().join(" + ");
+        let text = format!("{} -> {}", cost, stack_name(&trade.result));
+        // The server checks the inventory again when the trade is made
+        let affordable = trade.cost.iter().all(|stack| inventory.count(stack.item) >= stack.count);
+        // The trades the player doesn't have the items for are shown as warnings
+        let color = if affordable { style.color } else { gui.palette().warning };
+        gui.text(x + margin, line_y, LINE_HEIGHT, text, color, 0.01);
+        let button_x = x + WIDTH - margin - BUTTON_WIDTH;
+        let button = gui.button(FIRST_BUTTON_ID + i as u32, button_x, line_y, BUTTON_WIDTH, LINE_HEIGHT - 4);
+        let button = button.tooltip(item_tooltip(&trade.result, items));
+        if button.text("Trade".to_owned(), button_text).build() {
+            action = Some(TradeAction::Trade(i));
+        }
+    }
+    action
+}
@@ -1,5 +1,6 @@
-use common::player::PlayerInput;
-use std::collections::HashMap;
+use crate::gui::Navigation;
+use common::player::{PlayerInput, PLAYER_INVENTORY_SLOTS};
+use std::collections::{HashMap, HashSet};
 use winit::event::{ElementState, KeyEvent, MouseButton};
 use winit::keyboard::ModifiersState;
 use winit::platform::scancode::PhysicalKeyExtScancode;
@@ -45,8 +46,18 @@ impl Default for YawPitch {
     }
 }
 
+/// A change of the state of a key or of a mouse button. The changes of a frame are buffered, and given to the window
+/// state in the order they happened at the beginning of the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    Key(Option<u32>, ElementState),
+    MouseButton(MouseButton, ElementState),
+}
+
 pub struct InputState {
     keys: HashMap<u32, ElementState>,
+    /// The keys pressed since the last frame, which count as pressed for one frame even if they were already released
+    tapped_keys: HashSet<u32>,
     mouse_buttons: HashMap<MouseButton, ElementState>,
     modifiers_state: ModifiersState,
     flying: bool,
@@ -57,6 +68,7 @@ impl InputState {
     pub fn new() -> InputState {
         Self {
             keys: HashMap::new(),
+            tapped_keys: HashSet::new(),
             mouse_buttons: HashMap::new(),
             modifiers_state: ModifiersState::default(),
             flying:true,
@@ -64,10 +76,19 @@ impl InputState {
         }
     }
 
-    pub fn process_keyboard_input(&mut self, key: KeyEvent) -> bool {
+    /// Update the state of a key. The toggles such as flight are disabled while typing text. The presses repeated by
+    /// the system while a key is held are ignored, so that holding a gameplay key doesn't toggle it again and again:
+    /// only the navigation keys and the typed text repeat.
+    pub fn process_keyboard_input(&mut self, key: KeyEvent, allow_toggles: bool) -> bool {
+        if key.repeat {
+            return false;
+        }
         let previous_state = self.keys.get(&key.physical_key.to_scancode().unwrap()).cloned();
         self.keys.insert(key.physical_key.to_scancode().unwrap(), key.state);
-        if let &Some(ElementState::Pressed) = &previous_state {
+        if key.state == ElementState::Pressed {
+            self.tapped_keys.insert(key.physical_key.to_scancode().unwrap());
+        }
+        if previous_state == Some(ElementState::Pressed) && allow_toggles {
             if key.physical_key.to_scancode().unwrap() == TOGGLE_FLIGHT {
                 self.flying = !self.flying;
             }
@@ -99,16 +120,23 @@ impl InputState {
             .unwrap_or(ElementState::Released)
     }
 
+    /// Forget the keys tapped during the frame, once the window state was updated
+    pub fn end_frame(&mut self) {
+        self.tapped_keys.clear();
+    }
+
     pub fn clear(&mut self) {
         self.keys.clear();
+        self.tapped_keys.clear();
         self.mouse_buttons.clear();
         self.modifiers_state = ModifiersState::default();
     }
 
+    /// Whether a key is held, or was tapped during the frame
     fn is_key_pressed(&self, key: u32) -> bool {
         match self.get_key_state(key) {
             ElementState::Pressed => true,
-            ElementState::Released => false,
+            ElementState::Released => self.tapped_keys.contains(&key),
         }
     }
 
@@ -127,8 +155,38 @@ impl InputState {
     }
 }
 
+/// The navigation command of a key of the keyboard, to use the menus without the mouse
+pub fn key_navigation(key: u32, modifiers_state: ModifiersState) -> Option<Navigation> {
+    match key {
+        NAVIGATE_NEXT if modifiers_state.shift_key() => Some(Navigation::Previous),
+        NAVIGATE_NEXT | NAVIGATE_DOWN => Some(Navigation::Next),
+        NAVIGATE_UP => Some(Navigation::Previous),
+        NAVIGATE_LEFT => Some(Navigation::Decrease),
+        NAVIGATE_RIGHT => Some(Navigation::Increase),
+        NAVIGATE_ACTIVATE | NAVIGATE_ACTIVATE_KEYPAD => Some(Navigation::Activate),
+        _ => None,
+    }
+}
 
-
+/// The scancode of a key named in the settings: a letter, a digit, or a function key from F1 to F12
+pub fn scancode_from_name(name: &str) -> Option<u32> {
+    const LETTER_ROWS: [(&str, u32); 3] = [("QWERTYUIOP", 16), ("ASDFGHJKL", 30), ("ZXCVBNM", 44)];
+    let name = name.to_ascii_uppercase();
+    let mut chars = name.chars();
+    match (chars.next(), chars.as_str()) {
+        (Some(c), "") if c.is_ascii_alphabetic() => LETTER_ROWS
+            .iter()
+            .find_map(|(row, first)| row.find(c).map(|position| first + position as u32)),
+        (Some('0'), "") => Some(11),
+        (Some(c), "") if c.is_ascii_digit() => c.to_digit(10).map(|digit| digit + 1),
+        (Some('F'), number) => match number.parse::<u32>().ok()? {
+            number @ 1..=10 => Some(58 + number),
+            number @ 11..=12 => Some(76 + number),
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
 pub const MOVE_FORWARD: u32 = 17;
 pub const MOVE_LEFT: u32 = 30;
@@ -137,4 +195,30 @@ pub const MOVE_RIGHT: u32 = 32;
 pub const MOVE_UP: u32 = 57;
 pub const MOVE_DOWN: u32 = 42;
 pub const TOGGLE_FLIGHT: u32 = 33;
-pub const TOGGLE_CULLING: u32 = 46;
\ No newline at end of file
+pub const TOGGLE_CULLING: u32 = 46;
+pub const MARK_MINING_AREA: u32 = 50;
+pub const TOGGLE_ROBOT_CONTROL: u32 = 19;
+pub const TOGGLE_TRACE_RECORDING: u32 = 61;
+pub const TOGGLE_ACHIEVEMENTS: u32 = 38;
+pub const TOGGLE_STATISTICS: u32 = 36;
+pub const TOGGLE_INVENTORY: u32 = 18;
+pub const TOGGLE_CONSOLE: u32 = 41;
+pub const EMOTE_WAVE: u32 = 34;
+/// Turns the flashlight, or the headlights of the controlled robot, on or off
+pub const TOGGLE_LIGHT: u32 = 35;
+/// The number keys from 1 to 9, selecting the slots of the inventory
+pub const HOTBAR_SLOTS: [u32; PLAYER_INVENTORY_SLOTS] = [2, 3, 4, 5, 6, 7, 8, 9, 10];
+pub const REPLAY_TOGGLE_PAUSE: u32 = 25;
+pub const REPLAY_SEEK_BACKWARD: u32 = 105;
+pub const REPLAY_SEEK_FORWARD: u32 = 106;
+pub const REPLAY_FASTER: u32 = 103;
+pub const REPLAY_SLOWER: u32 = 108;
+pub const ESCAPE: u32 = 1;
+/// Tab focuses the next widget of the menus, and the previous one with shift
+pub const NAVIGATE_NEXT: u32 = 15;
+pub const NAVIGATE_UP: u32 = 103;
+pub const NAVIGATE_DOWN: u32 = 108;
+pub const NAVIGATE_LEFT: u32 = 105;
+pub const NAVIGATE_RIGHT: u32 = 106;
+pub const NAVIGATE_ACTIVATE: u32 = 28;
+pub const NAVIGATE_ACTIVATE_KEYPAD: u32 = 96;
@@ -0,0 +1,386 @@
+//! Screen shown while joining a server: the client checks that it can use the content of the server, then the game
+//! data is loaded from the cache, or the data pack of the server is downloaded with a progress bar. The game starts
+//! once the data and the id of the player are received, and keeps showing the progress until the chunks around the
+//! player are received and meshed.
+
+use crate::errorscreen::ErrorScreen;
+use crate::gui::Gui;
+use crate::input::InputState;
+use crate::render::{UiRenderer, Uploader};
+use crate::settings::Settings;
+use crate::singleplayer::SinglePlayer;
+use crate::ui::Ui;
+use crate::window::{State, StateTransition, WindowBuffers, WindowData, WindowFlags};
+use crate::world::ChunkProgress;
+use anyhow::{anyhow, Result};
+use common::content::GAME_VERSION;
+use common::data::{cache::DataCache, pack::DataPackDownload, Data};
+use common::network::{
+    messages::{ToClient, ToServer},
+    replay::ReplayControl,
+    Client, ClientEvent,
+};
+use common::player::PlayerId;
+use log::info;
+use std::path::PathBuf;
+use winit::event::{ElementState, MouseButton};
+
+/// The folder of the game data received from the servers, by content hash
+pub const DATA_CACHE_FOLDER: &str = "cache/data";
+/// Distance in chunks from the player of the chunks that are received and meshed before the world is shown
+pub const SPAWN_AREA_RADIUS: i64 = 2;
+/// Seconds after the start of the game after which the world is shown, even if the chunks are still missing
+const WORLD_LOAD_TIMEOUT: f64 = 30.0;
+/// Seconds during which each tip is shown
+const TIP_DURATION: f64 = 6.0;
+const TIPS: [&str; 8] = [
+    "Watch your oxygen: the alarm beeps when it runs low.",
+    "Dust storms slow down the players who are not sheltered under a roof.",
+    "Resting in a respawn pod makes it your respawn point.",
+    "The night is skipped once all the players rest in a respawn pod.",
+    "Your mining robot can dig for you, take control of it to explore.",
+    "The supply terminals trade your items for the ones you can't craft.",
+    "Type help in the console to list its commands.",
+    "The accessibility options of the menu can caption the alarms and reduce the flashing.",
+];
+
+/// The steps of joining a world, in order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoadingStage {
+    /// Checking the content and waiting for the game data
+    #[default]
+    Joining,
+    /// Loading the game data from the cache, or downloading the data pack of the server
+    GameData,
+    /// Receiving the chunks around the player
+    Chunks,
+    /// Meshing the chunks around the player
+    Meshes,
+}
+
+impl LoadingStage {
+    pub const ALL: [LoadingStage; 4] = [
+        LoadingStage::Joining,
+        LoadingStage::GameData,
+        LoadingStage::Chunks,
+        LoadingStage::Meshes,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LoadingStage::Joining => "Joining the server",
+            LoadingStage::GameData => "Loading the game data",
+            LoadingStage::Chunks => "Receiving the world",
+            LoadingStage::Meshes => "Building the world",
+        }
+    }
+}
+
+/// The progress of joining a world, shown by the loading screen and then by the game until the chunks around the
+/// player are ready
+#[derive(Debug, Clone, Default)]
+pub struct LoadingProgress {
+    stage: LoadingStage,
+    /// The part of the current stage that is done, from 0 to 1
+    fraction: f32,
+    /// Seconds since the player started joining, to change the tips
+    elapsed: f64,
+    /// Seconds since the start of the game, while the chunks around the player are loaded
+    world_elapsed: f64,
+}
+
+impl LoadingProgress {
+    pub fn set_stage(&mut self, stage: LoadingStage, fraction: f32) {
+        self.stage = stage;
+        self.fraction = fraction.clamp(0.0, 1.0);
+    }
+
+    pub fn update(&mut self, seconds_delta: f64) {
+        self.elapsed += seconds_delta;
+    }
+
+    /// Follow the chunks around the player, and return whether the world can be shown
+    pub fn update_world(&mut self, chunks: ChunkProgress, seconds_delta: f64) -> bool {
+        self.update(seconds_delta);
+        self.world_elapsed += seconds_delta;
+        let total = chunks.total.max(1) as f32;
+        if chunks.received < chunks.total {
+            self.set_stage(LoadingStage::Chunks, chunks.received as f32 / total);
+        } else if chunks.meshed < chunks.total {
+            self.set_stage(LoadingStage::Meshes, chunks.meshed as f32 / total);
+        } else {
+            return true;
+        }
+        self.world_elapsed > WORLD_LOAD_TIMEOUT
+    }
+
+    pub fn stage(&self) -> LoadingStage {
+        self.stage
+    }
+
+    /// The part of a stage that is done, 1 for the previous stages and 0 for the next ones
+    pub fn stage_fraction(&self, stage: LoadingStage) -> f32 {
+        match stage.cmp(&self.stage) {
+            std::cmp::Ordering::Less => 1.0,
+            std::cmp::Ordering::Equal => self.fraction,
+            std::cmp::Ordering::Greater => 0.0,
+        }
+    }
+
+    pub fn tip(&self) -> &'static str {
+        TIPS[(self.elapsed / TIP_DURATION) as usize % TIPS.len()]
+    }
+}
+
+/// Load the data pack received from the server, and check that it gives the game data that the server announced.
+/// The data is then stored in the cache.
+pub fn load_data_pack(download: DataPackDownload, data_hash: u64, data_cache: &DataCache) -> Result<Data> {
+    let data = download.finish()?.load()?;
+    if data.content_hash() != data_hash {
+        return Err(anyhow!("The data pack of the server doesn't give the same game data as the server"));
+    }
+    data_cache.store(data_hash, &data);
+    Ok(data)
+}
+
+pub struct LoadingScreen {
+    /// None once it was given to the game
+    client: Option<Box<dyn Client>>,
+    replay: Option<ReplayControl>,
+    data_cache: DataCache,
+    data: Option<Data>,
+    /// The hash of the game data that was requested from the server
+    requested_data_hash: Option<u64>,
+    download: Option<DataPackDownload>,
+    player_id: Option<PlayerId>,
+    /// The other messages received before the game starts, which the game handles first
+    pending_messages: Vec<ToClient>,
+    progress: LoadingProgress,
+    ui: Ui,
+    ui_renderer: UiRenderer,
+    uploader: Uploader,
+    gui: Gui,
+    should_exit: bool,
+}
+
+impl LoadingScreen {
+    pub fn new_factory(client: Box<dyn Client>, replay: Option<ReplayControl>) -> crate::window::StateFactory {
+        Box::new(move |_settings, device| Self::new(device, client, replay))
+    }
+
+    pub fn new(
+        device: &mut wgpu::Device,
+        client: Box<dyn Client>,
+        replay: Option<ReplayControl>,
+    ) -> Result<(Box<dyn State>, wgpu::CommandBuffer)> {
+        info!("Joining the server");
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let ui_renderer = UiRenderer::new(device);
+        Ok((
+            Box::new(Self {
+                client: Some(client),
+                replay,
+                data_cache: DataCache::new(PathBuf::from(DATA_CACHE_FOLDER)),
+                data: None,
+                requested_data_hash: None,
+                download: None,
+                player_id: None,
+                pending_messages: Vec::new(),
+                progress: LoadingProgress::default(),
+                ui: Ui::new(),
+                ui_renderer,
+                uploader: Uploader::new(),
+                gui: Gui::new(),
+                should_exit: false,
+            }),
+            encoder.finish(),
+        ))
+    }
+
+    /// Handle the messages of the server, and return the screen to show instead of this one if there is one
+    fn handle_server_messages(&mut self) -> Option<StateTransition> {
+        let client = self.client.as_mut()?;
+        loop {
+            match client.receive_event() {
+                ClientEvent::NoEvent => break,
+                // Sent first by the server, before the game data whose registries depend on the content
+                ClientEvent::ServerMessage(ToClient::Content(content)) => {
+                    let missing = content.missing_for(GAME_VERSION);
+                    if !missing.is_empty() {
+                        log::error!("Missing content: {}", missing.join(", "));
+                        return Some(StateTransition::ReplaceCurrent(Box::new(move |_, device| {
+                            ErrorScreen::new(device, "This server uses content that you don't have".to_owned(), missing)
+                        })));
+                    }
+                    client.send(ToServer::Hello(GAME_VERSION.to_owned()));
+                }
+                ClientEvent::ServerMessage(ToClient::DataHash(hash)) => {
+                    // The data changed on the server, the data pack being downloaded isn't sent anymore
+                    self.download = None;
+                    match self.data_cache.load(hash) {
+                        Some(data) => {
+                            info!("Loaded the game data from the cache");
+                            self.data = Some(data);
+                        }
+                        None => {
+                            self.requested_data_hash = Some(hash);
+                            client.send(ToServer::RequestData);
+                        }
+                    }
+                }
+                ClientEvent::ServerMessage(ToClient::DataPack(hash, size)) => match DataPackDownload::new(hash, size) {
+                    Ok(download) => self.download = Some(download),
+                    Err(e) => return Some(error_screen(e)),
+                },
+                ClientEvent::ServerMessage(ToClient::DataPackChunk(bytes)) => {
+                    let (download, data_hash) = match (&mut self.download, self.requested_data_hash) {
+                        (Some(download), Some(data_hash)) => (download, data_hash),
+                        _ => continue,
+                    };
+                    if let Err(e) = download.add_chunk(&bytes) {
+                        return Some(error_screen(e));
+                    }
+                    if download.is_complete() {
+                        info!("Received the data pack");
+                        let download = self.download.take().unwrap();
+                        match load_data_pack(download, data_hash, &self.data_cache) {
+                            Ok(data) => self.data = Some(data),
+                            Err(e) => return Some(error_screen(e)),
+                        }
+                    }
+                }
+                // The replays send the data directly
+                ClientEvent::ServerMessage(ToClient::GameData(data)) => self.data = Some(data),
+                ClientEvent::ServerMessage(ToClient::CurrentId(id)) => self.player_id = Some(id),
+                ClientEvent::ServerMessage(ToClient::DataErrors(report)) => {
+                    let lines = report
+                        .errors
+                        .iter()
+                        .map(|error| format!("{}: {}", error.source, error.message))
+                        .collect();
+                    return Some(StateTransition::ReplaceCurrent(Box::new(move |_, device| {
+                        ErrorScreen::new(device, "The game data couldn't be loaded".to_owned(), lines)
+                    })));
+                }
+                ClientEvent::ServerMessage(message) => self.pending_messages.push(message),
+                _ => (),
+            }
+        }
+        if self.data.is_some() && self.player_id.is_some() {
+            info!("Received game data");
+            let client = self.client.take().unwrap();
+            let replay = self.replay.take();
+            let data = self.data.take().unwrap();
+            let player_id = self.player_id.unwrap();
+            let pending_messages = std::mem::take(&mut self.pending_messages);
+            let progress = self.progress.clone();
+            return Some(StateTransition::ReplaceCurrent(SinglePlayer::new_factory(
+                client,
+                replay,
+                data,
+                player_id,
+                pending_messages,
+                progress,
+            )));
+        }
+        None
+    }
+}
+
+/// Show an error that prevents joining the server
+fn error_screen(error: anyhow::Error) -> StateTransition {
+    log::error!("{:#}", error);
+    let lines = vec![format!("{:#}", error)];
+    StateTransition::ReplaceCurrent(Box::new(move |_, device| {
+        ErrorScreen::new(device, "The game data couldn't be received".to_owned(), lines)
+    }))
+}
+
+impl State for LoadingScreen {
+    fn update(
+        &mut self,
+        _settings: &mut Settings,
+        _input_state: &InputState,
+        _data: &WindowData,
+        flags: &mut WindowFlags,
+        seconds_delta: f64,
+        _device: &mut wgpu::Device,
+    ) -> Result<StateTransition> {
+        flags.grab_cursor = false;
+        flags.text_input = false;
+        if self.should_exit {
+            return Ok(StateTransition::CloseWindow);
+        }
+        let transition = self.handle_server_messages();
+        self.progress.update(seconds_delta);
+        match &self.download {
+            Some(download) => self.progress.set_stage(LoadingStage::GameData, download.progress()),
+            None if self.requested_data_hash.is_some() => self.progress.set_stage(LoadingStage::GameData, 0.0),
+            None => (),
+        }
+        Ok(transition.unwrap_or(StateTransition::KeepCurrent))
+    }
+
+    fn render<'a>(
+        &mut self,
+        _settings: &Settings,
+        buffers: WindowBuffers<'a>,
+        device: &mut wgpu::Device,
+        data: &WindowData,
+        _input_state: &InputState,
+    ) -> Result<(StateTransition, wgpu::CommandBuffer)> {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.uploader.recall(device);
+        crate::render::clear_color_and_depth(&mut encoder, buffers);
+
+        let (win_w, win_h) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+        self.gui.prepare();
+        crate::gui::loading::render_loading_progress(&mut self.gui, &self.progress, win_w, win_h);
+        self.gui.finish();
+
+        self.ui_renderer.render(
+            buffers,
+            device,
+            &mut encoder,
+            &mut self.uploader,
+            data,
+            &self.ui.ui,
+            &mut self.gui,
+            false,
+        );
+
+        self.uploader.finish();
+        Ok((StateTransition::KeepCurrent, encoder.finish()))
+    }
+
+    fn handle_mouse_motion(&mut self, _settings: Settings, _delta: (f64, f64)) {}
+
+    fn handle_cursor_movement(&mut self, logical_position: winit::dpi::LogicalPosition<f64>) {
+        let (x, y) = logical_position.into();
+        self.gui.update_mouse_position(x, y);
+    }
+
+    fn handle_mouse_state_changes(&mut self, changes: Vec<(MouseButton, ElementState)>) {
+        for (button, state) in changes.into_iter() {
+            if button == MouseButton::Left {
+                self.gui.update_mouse_button(state == ElementState::Pressed);
+            }
+        }
+    }
+
+    fn handle_key_state_changes(&mut self, changes: Vec<(Option<u32>, ElementState)>) {
+        for (key, state) in changes.into_iter() {
+            // Escape key
+            if key == Some(1) && state == ElementState::Pressed {
+                self.should_exit = true;
+            }
+        }
+    }
+
+    fn recreate_gpu_resources(&mut self, device: &mut wgpu::Device) -> Result<wgpu::CommandBuffer> {
+        self.ui_renderer = UiRenderer::new(device);
+        self.uploader = Uploader::new();
+        Ok(device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None }).finish())
+    }
+}
@@ -0,0 +1,53 @@
+//! Keys bound to console commands in the settings. A key can run a sequence of commands separated by `;`, which are
+//! run by the console in order, like if they were entered one after the other.
+
+use crate::input::scancode_from_name;
+use std::collections::BTreeMap;
+
+/// The separator of the commands of a macro
+const COMMAND_SEPARATOR: char = ';';
+
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    /// The commands of each key, by scancode, with the name of the key in the settings
+    bindings: BTreeMap<u32, (String, Vec<String>)>,
+}
+
+impl Macros {
+    /// Parse the macros of the settings. The unknown keys and the macros without command are reported and ignored.
+    pub fn parse(macros: &BTreeMap<String, String>) -> Self {
+        let mut bindings = BTreeMap::new();
+        for (key_name, sequence) in macros.iter() {
+            let commands = sequence
+                .split(COMMAND_SEPARATOR)
+                .map(str::trim)
+                .filter(|command| !command.is_empty())
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            match scancode_from_name(key_name) {
+                None => log::warn!("Unknown key {} in the macros of the settings", key_name),
+                Some(_) if commands.is_empty() => log::warn!("The macro of the key {} has no command", key_name),
+                Some(scancode) => {
+                    if let Some((previous_name, _)) = bindings.insert(scancode, (key_name.clone(), commands)) {
+                        log::warn!("The keys {} and {} of the macros are the same key", previous_name, key_name);
+                    }
+                }
+            }
+        }
+        Self { bindings }
+    }
+
+    /// The commands bound to a key, empty if the key has no macro
+    pub fn commands(&self, scancode: u32) -> &[String] {
+        self.bindings.get(&scancode).map(|(_, commands)| commands.as_slice()).unwrap_or_default()
+    }
+
+    /// Describe the macros, one line per key
+    pub fn describe(&self) -> Vec<String> {
+        let separator = format!("{} ", COMMAND_SEPARATOR);
+        self.bindings
+            .values()
+            .map(|(key_name, commands)| format!("{}: {}", key_name, commands.join(&separator)))
+            .collect()
+    }
+}
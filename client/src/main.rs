@@ -1,15 +1,38 @@
 
 use anyhow::Result;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use log::{error, info};
 use server::launch_server;
+use common::data::load_data;
+use common::network::limits::{ConnectionLimits, LimitedServer};
+use common::network::replay::{RecordingClient, ReplayClient, ReplayControl};
+use common::network::Client;
 
 
+mod animation;
+mod camera_path;
+mod console;
+mod crash;
+mod damage;
+mod errorscreen;
 mod fps;
+mod gamepad;
 mod input;
 mod gui;
+mod loading;
+mod macros;
+mod packs;
+mod particles;
+mod render_distance;
+mod replay;
+mod serverlist;
 mod settings;
 mod singleplayer;
+mod sound;
+mod splash;
+mod teleport;
 mod ui;
 mod render;
 mod window;
@@ -18,26 +41,62 @@ mod texture;
 mod mainmenu;
 
 fn main() -> Result<()>{
-    env_logger::init();
+    crash::init();
 
     info!("Starting up..");
     let config_folder = Path::new("config");
     let config_file = config_folder.join("config/settings.toml");
     let settings = settings::load_settings(&config_folder, &config_file)?;
     info!("Loaded settings: {:?}", settings);
+    crash::set_context("Settings", format!("{:#?}", settings));
 
-    let (client, server) = common::network::dummy::new();
+    // `--record <file>` saves the session in a replay, `--replay <file>` plays a replay instead of starting a server
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).map(PathBuf::from);
+    // Set when the window is closed, the server saves the world before stopping
+    let stop_server = Arc::new(AtomicBool::new(false));
+    let mut server_thread = None;
+    let (client, replay): (Box<dyn Client>, Option<ReplayControl>) = match option("--replay") {
+        Some(replay_path) => {
+            info!("Playing replay {}", replay_path.display());
+            // The game data is not part of the replay
+            let data = load_data(PathBuf::from("data"))?;
+            let (client, control) = ReplayClient::open(&replay_path, data)?;
+            (Box::new(client), Some(control))
+        }
+        None => {
+            let (client, server) = common::network::dummy::new();
+            let server = LimitedServer::new(server, ConnectionLimits::default());
 
-    std::thread::spawn(move||{
-        if let Err(e) = launch_server(Box::new(server)) {
-            error!(
-                "An error occurred while running the server. Cause: {}",
-                e
-            );
+            let stop = stop_server.clone();
+            server_thread = Some(std::thread::spawn(move||{
+                if let Err(e) = launch_server(Box::new(server), stop) {
+                    error!(
+                        "An error occurred while running the server. Cause: {}",
+                        e
+                    );
+                }
+            }));
+            match option("--record") {
+                Some(record_path) => {
+                    info!("Recording replay {}", record_path.display());
+                    (Box::new(RecordingClient::new(client, &record_path)?), None)
+                }
+                None => (Box::new(client), None),
+            }
         }
-    });
-    window::open_window(
+    };
+    let result = window::open_window(
         settings,
-        Box::new(singleplayer::SinglePlayer::new_factory(Box::new(client))),
-    )
+        splash::SplashScreen::new_factory(loading::LoadingScreen::new_factory(client, replay)),
+    );
+    if let Err(e) = &result {
+        crash::report_error(e);
+    }
+    stop_server.store(true, Ordering::Relaxed);
+    if let Some(server_thread) = server_thread {
+        info!("Waiting for the server to save the world..");
+        let _ = server_thread.join();
+    }
+    result
 }
\ No newline at end of file
@@ -0,0 +1,63 @@
+//! The data packs installed by dropping their zip archive on the window. Each pack is extracted into its own folder
+//! of the packs folder, named after the archive, and replaces the pack of the same name if it was already installed.
+
+use anyhow::{anyhow, Context, Result};
+use common::content::ContentInfo;
+use common::data::pack::read_pack_info;
+use common::data::DataFormat;
+use std::path::{Path, PathBuf};
+
+/// The folder of the installed data packs, one folder per pack
+pub const PACKS_FOLDER: &str = "packs";
+
+/// Extract the zip archive of a data pack into the packs folder, and return the description of the pack
+pub fn install_pack(archive_path: &Path) -> Result<ContentInfo> {
+    let is_zip = archive_path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("zip"));
+    let name = archive_path.file_stem().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    if !is_zip || name.is_empty() {
+        return Err(anyhow!("{} is not the zip archive of a data pack", archive_path.display()));
+    }
+    let file = std::fs::File::open(archive_path)
+        .with_context(|| format!("Failed to open {}", archive_path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read the zip archive {}", archive_path.display()))?;
+
+    // The pack is extracted next to its final folder, so that a broken archive doesn't replace an installed pack
+    let pack_folder = Path::new(PACKS_FOLDER).join(&name);
+    let temporary_folder = Path::new(PACKS_FOLDER).join(format!(".{}.tmp", name));
+    if temporary_folder.exists() {
+        std::fs::remove_dir_all(&temporary_folder)
+            .with_context(|| format!("Failed to remove {}", temporary_folder.display()))?;
+    }
+    // The paths leaving the folder are not extracted
+    archive
+        .extract(&temporary_folder)
+        .with_context(|| format!("Failed to extract {}", archive_path.display()))?;
+    let info = match check_pack(temporary_folder.clone()) {
+        Ok(info) => info,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&temporary_folder);
+            return Err(e.context(format!("{} is not a data pack", archive_path.display())));
+        }
+    };
+
+    if pack_folder.exists() {
+        std::fs::remove_dir_all(&pack_folder)
+            .with_context(|| format!("Failed to remove the previous version in {}", pack_folder.display()))?;
+    }
+    std::fs::rename(&temporary_folder, &pack_folder)
+        .with_context(|| format!("Failed to move the data pack to {}", pack_folder.display()))?;
+    log::info!("Installed the data pack {} {} in {}", info.name, info.version, pack_folder.display());
+    Ok(info)
+}
+
+/// Read the description of an extracted pack, which must be at the root of the archive
+fn check_pack(folder: PathBuf) -> Result<ContentInfo> {
+    let has_pack_file = DataFormat::ALL
+        .iter()
+        .any(|format| folder.join(format!("pack.{}", format.extension())).is_file());
+    if !has_pack_file {
+        return Err(anyhow!("There is no pack.ron, pack.json or pack.toml file at the root of the archive"));
+    }
+    read_pack_info(folder)
+}
@@ -0,0 +1,193 @@
+//! Short-lived visual effects, such as the debris thrown by explosions and their flash
+
+use crate::render::{Model, PointLight};
+use common::data::vox::VoxelModel;
+use common::item::ItemMesh;
+use common::registry::Registry;
+use nalgebra::Vector3;
+
+/// Falling acceleration of the debris
+const GRAVITY_ACCELERATION: f64 = 25.0;
+/// Seconds before a debris disappears
+const DEBRIS_LIFETIME: f64 = 1.5;
+/// Size of the debris, in blocks
+const DEBRIS_SIZE: f32 = 0.25;
+/// Initial speed of the debris for each unit of explosion power
+const DEBRIS_SPEED_PER_POWER: f64 = 2.5;
+/// Seconds before a flame of a trail disappears
+const FLAME_LIFETIME: f64 = 0.6;
+/// Initial size of the flames, in blocks. They shrink until they disappear.
+const FLAME_SIZE: f32 = 0.8;
+/// Color of the light of the flames and the explosions
+const FIRE_LIGHT_COLOR: [f32; 3] = [1.0, 0.6, 0.25];
+/// Radius of the light of a flame, in blocks
+const FLAME_LIGHT_RADIUS: f32 = 4.0;
+/// Seconds before the flash of an explosion fades out
+const FLASH_LIFETIME: f64 = 0.4;
+/// Radius of the flash for each unit of explosion power, in blocks
+const FLASH_RADIUS_PER_POWER: f64 = 4.0;
+/// Intensity of the light of a fresh flash
+const FLASH_INTENSITY: f32 = 3.0;
+/// Intensity of the light of a fresh flash with the reduced flashing
+const REDUCED_FLASH_INTENSITY: f32 = 0.8;
+
+struct Particle {
+    pos: Vector3<f64>,
+    velocity: Vector3<f64>,
+    /// Seconds since the particle was spawned
+    age: f64,
+    lifetime: f64,
+    /// True if the particle falls
+    gravity: bool,
+    /// True if the particle shrinks with age
+    shrinks: bool,
+    /// True if the particle lights its surroundings
+    glows: bool,
+    mesh: ItemMesh,
+}
+
+/// The light of an explosion, fading out quickly
+struct Flash {
+    pos: Vector3<f64>,
+    age: f64,
+    radius: f32,
+}
+
+/// All the particles of the world
+pub struct Particles {
+    particles: Vec<Particle>,
+    flashes: Vec<Flash>,
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Self {
+            particles: Vec::new(),
+            flashes: Vec::new(),
+        }
+    }
+
+    /// Throw the debris of an explosion in every direction
+    pub fn spawn_explosion(&mut self, center: Vector3<f64>, power: f64, debris: Vec<ItemMesh>) {
+        self.flashes.push(Flash {
+            pos: center,
+            age: 0.0,
+            radius: (power * FLASH_RADIUS_PER_POWER) as f32,
+        });
+        let n = debris.len();
+        let golden_angle = std::f64::consts::PI * (3.0 - f64::sqrt(5.0));
+        for (i, mesh) in debris.into_iter().enumerate() {
+            // Spread the directions evenly on a sphere
+            let y = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+            let r = f64::sqrt(1.0 - y * y);
+            let theta = golden_angle * i as f64;
+            let direction = Vector3::new(r * theta.cos(), y.abs(), r * theta.sin());
+            let mesh = scale_mesh(mesh, DEBRIS_SIZE);
+            self.particles.push(Particle {
+                pos: center,
+                velocity: direction * power * DEBRIS_SPEED_PER_POWER,
+                age: 0.0,
+                lifetime: DEBRIS_LIFETIME,
+                gravity: true,
+                shrinks: false,
+                glows: false,
+                mesh,
+            });
+        }
+    }
+
+    /// Leave a flame behind a moving object. `mesh` is the model of the flame, for a size of 1 block.
+    pub fn spawn_flame(&mut self, pos: Vector3<f64>, mesh: ItemMesh) {
+        self.particles.push(Particle {
+            pos,
+            velocity: Vector3::zeros(),
+            age: 0.0,
+            lifetime: FLAME_LIFETIME,
+            gravity: false,
+            shrinks: true,
+            glows: true,
+            mesh: scale_mesh(mesh, FLAME_SIZE),
+        });
+    }
+
+    /// Move the particles, and remove the old ones
+    pub fn tick(&mut self, dt: f64) {
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+            if particle.gravity {
+                particle.velocity.y -= GRAVITY_ACCELERATION * dt;
+            }
+            particle.pos += particle.velocity * dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+        for flash in self.flashes.iter_mut() {
+            flash.age += dt;
+        }
+        self.flashes.retain(|flash| flash.age < FLASH_LIFETIME);
+    }
+
+    /// Add the lights of the glowing particles and the explosion flashes to `lights`. With `reduce_flashing`, the
+    /// flashes are dimmer.
+    pub fn lights(&self, lights: &mut Vec<PointLight>, reduce_flashing: bool) {
+        let flash_intensity = if reduce_flashing { REDUCED_FLASH_INTENSITY } else { FLASH_INTENSITY };
+        for particle in self.particles.iter().filter(|particle| particle.glows) {
+            lights.push(PointLight {
+                position: particle.pos,
+                color: FIRE_LIGHT_COLOR,
+                intensity: 1.0 - (particle.age / particle.lifetime) as f32,
+                radius: FLAME_LIGHT_RADIUS,
+                spot: None,
+            });
+        }
+        for flash in self.flashes.iter() {
+            lights.push(PointLight {
+                position: flash.pos,
+                color: FIRE_LIGHT_COLOR,
+                intensity: flash_intensity * (1.0 - flash.age / FLASH_LIFETIME) as f32,
+                radius: flash.radius,
+                spot: None,
+            });
+        }
+    }
+
+    /// Add the models of the particles to `models`
+    pub fn render(&self, models: &mut Vec<Model>) {
+        for particle in self.particles.iter() {
+            let ItemMesh::SimpleMesh { mesh_id, scale, mesh_center } = &particle.mesh;
+            let scale = if particle.shrinks {
+                scale * (1.0 - (particle.age / particle.lifetime) as f32)
+            } else {
+                *scale
+            };
+            models.push(Model {
+                mesh_id: *mesh_id,
+                pos_x: particle.pos.x as f32 - mesh_center.0 * scale,
+                pos_y: particle.pos.y as f32 - mesh_center.1 * scale,
+                pos_z: particle.pos.z as f32 - mesh_center.2 * scale,
+                scale,
+                rot_offset: [mesh_center.0 * scale, mesh_center.1 * scale, mesh_center.2 * scale],
+                rot_y: (particle.age * 10.0) as f32,
+                rot_x: 0.0,
+                rot_z: 0.0,
+            });
+        }
+    }
+}
+
+/// Return the mesh of a model, scaled to a size of 1 block
+pub fn get_unit_mesh(models: &Registry<VoxelModel>, name: &str) -> ItemMesh {
+    let mesh_id = models.get_id_by_name(&name.to_owned()).unwrap();
+    let model = models.get_value_by_id(mesh_id).unwrap();
+    let size = usize::max(model.size_x, usize::max(model.size_y, model.size_z));
+    ItemMesh::SimpleMesh {
+        mesh_id,
+        scale: 1.0 / size as f32,
+        mesh_center: (model.size_x as f32 / 2.0, model.size_y as f32 / 2.0, model.size_z as f32 / 2.0),
+    }
+}
+
+/// Scale a mesh by `size`
+pub fn scale_mesh(mesh: ItemMesh, size: f32) -> ItemMesh {
+    let ItemMesh::SimpleMesh { mesh_id, scale, mesh_center } = mesh;
+    ItemMesh::SimpleMesh { mesh_id, scale: scale * size, mesh_center }
+}
@@ -0,0 +1,137 @@
+//! Text rendering in the 3d world, for the name tags, the labels and the texts of the signs
+
+use super::init::DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR;
+use super::{Frustum, Uploader};
+use crate::window::{WindowBuffers, WindowData};
+use nalgebra::Vector3;
+use wgpu_glyph::ab_glyph::FontVec;
+
+/// Billboards further than this distance are not drawn, in blocks
+const MAX_BILLBOARD_DISTANCE: f64 = 64.0;
+/// Billboards whose text would be smaller than this height are not drawn, in pixels
+const MIN_TEXT_HEIGHT: f64 = 6.0;
+
+/// Some text floating in the world, always facing the camera
+#[derive(Debug, Clone)]
+pub struct Billboard {
+    /// Position of the bottom center of the text
+    pub pos: Vector3<f64>,
+    pub text: String,
+    pub color: [f32; 4],
+    /// Height of the text, in blocks
+    pub size: f64,
+    /// The text is wrapped to lines of at most this width, in blocks, or stays on one line if None. The lines are
+    /// stacked upwards from the position.
+    pub wrap_width: Option<f64>,
+}
+
+pub struct BillboardRenderer {
+    glyph_brush: wgpu_glyph::GlyphBrush<wgpu::DepthStencilState, FontVec>,
+}
+
+impl BillboardRenderer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let font = FontVec::try_from_vec(
+            include_bytes!("../../../assets/fonts/IBMPlexMono-Regular.ttf").to_vec()
+        ).expect("Failed to load default font.");
+        let glyph_brush = wgpu_glyph::GlyphBrushBuilder::using_font(font)
+            // Hidden by the terrain, but the billboards don't hide each other
+            .depth_stencil_state(wgpu::DepthStencilState {
+                depth_write_enabled: false,
+                ..DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR
+            })
+            .multisample_state(wgpu::MultisampleState {
+                count: super::compatibility::sample_count(),
+                ..Default::default()
+            })
+            // Drawn with the world, before the post-processing
+            .build(device, super::HDR_FORMAT);
+        Self { glyph_brush }
+    }
+
+    /// Draw the billboards on top of the world. The depth buffer must still contain the world.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        buffers: WindowBuffers,
+        data: &WindowData,
+        frustum: &Frustum,
+        billboards: &[Billboard],
+    ) {
+        let winit::dpi::PhysicalSize {
+            width: win_w,
+            height: win_h,
+        } = data.physical_window_size;
+        if win_w == 0 || win_h == 0 {
+            return;
+        }
+        let view_proj = frustum.get_view_projection(win_w as f64 / win_h as f64);
+        // Number of pixels per block at a distance of 1 block
+        let pixels_per_block = view_proj[(1, 1)] * win_h as f64 / 2.0;
+
+        for billboard in billboards {
+            if (billboard.pos - frustum.position).norm() > MAX_BILLBOARD_DISTANCE {
+                continue;
+            }
+            let clip = view_proj * billboard.pos.push(1.0);
+            // Behind the camera
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let (x, y, depth) = (clip.x / clip.w, clip.y / clip.w, clip.z / clip.w);
+            let text_height = billboard.size * pixels_per_block / clip.w;
+            if text_height < MIN_TEXT_HEIGHT || depth < 0.0 || depth > 1.0 {
+                continue;
+            }
+            // Cull the billboards outside of the screen, keeping some margin for the size of the text. A wrapped text
+            // is narrower but has more lines.
+            let mut text_width = text_height * billboard.text.chars().count() as f64;
+            let mut lines_height = text_height;
+            let wrap_width = billboard.wrap_width.map(|width| width * pixels_per_block / clip.w);
+            if let Some(wrap_width) = wrap_width {
+                lines_height *= (text_width / wrap_width).ceil().max(1.0);
+                text_width = text_width.min(wrap_width);
+            }
+            let margin = text_width / win_w as f64;
+            if x < -1.0 - margin || x > 1.0 + margin || y < -1.0 || y > 1.0 + 2.0 * lines_height / win_h as f64 {
+                continue;
+            }
+            let layout = match wrap_width {
+                Some(_) => wgpu_glyph::Layout::default_wrap(),
+                None => wgpu_glyph::Layout::default_single_line(),
+            };
+            let section = wgpu_glyph::Section::default()
+                .with_screen_position((
+                    ((x + 1.0) / 2.0 * win_w as f64) as f32,
+                    ((1.0 - y) / 2.0 * win_h as f64) as f32,
+                ))
+                .with_bounds((wrap_width.unwrap_or(f64::INFINITY) as f32, f32::INFINITY))
+                .with_layout(
+                    layout
+                        .h_align(wgpu_glyph::HorizontalAlign::Center)
+                        .v_align(wgpu_glyph::VerticalAlign::Bottom),
+                )
+                .add_text(
+                    wgpu_glyph::Text::new(&billboard.text)
+                        .with_scale(text_height as f32)
+                        .with_color(billboard.color)
+                        .with_z(depth as f32),
+                );
+            self.glyph_brush.queue(section);
+        }
+
+        self.glyph_brush
+            .draw_queued(
+                device,
+                uploader.belt(),
+                encoder,
+                buffers.multisampled_texture_buffer,
+                super::render::create_default_depth_stencil_attachment(buffers.depth_buffer),
+                win_w,
+                win_h,
+            )
+            .expect("couldn't draw billboards");
+    }
+}
@@ -3,7 +3,7 @@
 use std::collections::HashMap;
 use std::hash::Hash;
 
-use super::{ buffer_from_slice, to_u8_slice };
+use super::{to_u8_slice, Uploader};
 
 /// A buffer that will automatically resize itself when necessary
 pub struct DynamicBuffer<T: Copy> {
@@ -41,6 +41,7 @@ impl<T: Copy + 'static> DynamicBuffer<T> {
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
         data: &[T],
     ) {
         if data.is_empty() {
@@ -58,19 +59,7 @@ impl<T: Copy + 'static> DynamicBuffer<T> {
             self.capacity = data.len();
         }
 
-        let src_buffer = buffer_from_slice(
-            device,
-            wgpu::BufferUsages::COPY_SRC,
-            to_u8_slice(data)
-        );
-
-        encoder.copy_buffer_to_buffer(
-            &src_buffer,
-            0,
-            &self.buffer,
-            0,
-            (data.len() * std::mem::size_of::<T>()) as u64,
-        );
+        uploader.upload_buffer(device, encoder, &self.buffer, 0, to_u8_slice(data));
         self.len = data.len();
     }
 
@@ -167,6 +156,7 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
         object: K,
         data: &[T],
     ) {
@@ -184,17 +174,12 @@ impl<K: Hash + Eq + Clone + std::fmt::Debug, T: Copy + std::fmt::Debug + 'static
             self.segments.len() - 1
         });
         // Copy data into the buffer
-        let src_buffer = buffer_from_slice(
+        uploader.upload_buffer(
             device,
-            wgpu::BufferUsages::COPY_SRC,
-            to_u8_slice(data)
-        );
-        encoder.copy_buffer_to_buffer(
-            &src_buffer,
-            0,
+            encoder,
             &self.buffer,
             (self.segments[insert_position].pos * std::mem::size_of::<T>()) as u64,
-            (data.len() * std::mem::size_of::<T>()) as u64,
+            to_u8_slice(data),
         );
         // Update current segment
         self.segments[insert_position].free = false;
@@ -360,6 +345,7 @@ mod tests {
         }, None))
         .expect("Failed to request device.");
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let mut uploader = Uploader::new();
 
         // Create initial buffer
         let mut multi_buffer = MultiBuffer::with_capacity(&device, 10, BufferUsages::empty());
@@ -369,28 +355,28 @@ mod tests {
         let seg3 = [9u16];
 
         // Single insert
-        multi_buffer.update(&device, &mut encoder, 0u16, &seg1);
+        multi_buffer.update(&device, &mut encoder, &mut uploader, 0u16, &seg1);
         multi_buffer.remove(&0u16);
         assert_eq!(multi_buffer.get_pos_len(&0), None);
 
         // Double insert
-        multi_buffer.update(&device, &mut encoder, 1u16, &seg2);
+        multi_buffer.update(&device, &mut encoder, &mut uploader, 1u16, &seg2);
         assert_eq!(multi_buffer.get_pos_len(&1), Some((0, 4)));
-        multi_buffer.update(&device, &mut encoder, 2u16, &seg2);
+        multi_buffer.update(&device, &mut encoder, &mut uploader, 2u16, &seg2);
         assert_eq!(multi_buffer.get_pos_len(&2), Some((4, 4)));
         multi_buffer.remove(&1u16);
         assert_eq!(multi_buffer.get_pos_len(&1), None);
         assert_eq!(multi_buffer.get_pos_len(&2), Some((4, 4)));
 
         // Triple insert
-        multi_buffer.update(&device, &mut encoder, 0u16, &seg1);
+        multi_buffer.update(&device, &mut encoder, &mut uploader, 0u16, &seg1);
         assert_eq!(multi_buffer.get_pos_len(&0), Some((0, 3)));
-        multi_buffer.update(&device, &mut encoder, 1u16, &seg3);
+        multi_buffer.update(&device, &mut encoder, &mut uploader, 1u16, &seg3);
         assert_eq!(multi_buffer.get_pos_len(&1), Some((3, 1)));
         // Now we have 8 items
 
         // Reallocate
-        multi_buffer.update(&device, &mut encoder, 3u16, &seg2);
+        multi_buffer.update(&device, &mut encoder, &mut uploader, 3u16, &seg2);
         assert_eq!(multi_buffer.get_pos_len(&3), Some((8, 4)));
     }
 }
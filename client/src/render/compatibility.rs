@@ -0,0 +1,48 @@
+//! The compatibility mode of the weak GPUs, such as the old integrated GPUs, whose adapters don't have the limits or
+//! the capabilities that the renderers use by default. It is chosen when the window opens, from the adapter, and:
+//! * disables the multisampling, the passes draw directly into the resolved buffers;
+//! * only generates the first mipmap levels of the textures;
+//! * disables the shadows, the light volumes, and the tone mapping and FXAA passes;
+//! * requests the downlevel limits of wgpu, with smaller buffers and fewer bindings.
+
+use crate::window::SAMPLE_COUNT;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Number of mipmap levels of the textures in the compatibility mode, including the full-size level
+pub const MAX_MIP_LEVELS: u32 = 2;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the adapter lacks the default limits of wgpu or some capabilities of WebGPU
+pub fn is_needed(adapter: &wgpu::Adapter) -> bool {
+    let compliant = adapter.get_downlevel_capabilities().is_webgpu_compliant();
+    !compliant || !wgpu::Limits::default().check_limits(&adapter.limits())
+}
+
+/// Enable or disable the compatibility mode. It must be set before the device and the renderers are created.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Number of samples of the multisampled buffers and of the pipelines drawing into them
+pub fn sample_count() -> u32 {
+    if is_enabled() {
+        1
+    } else {
+        SAMPLE_COUNT
+    }
+}
+
+/// The limits to request when creating the device
+pub fn required_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+    if is_enabled() {
+        // The window can still be as large as the adapter allows
+        wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
+    } else {
+        wgpu::Limits::default()
+    }
+}
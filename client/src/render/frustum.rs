@@ -17,7 +17,8 @@ impl Plane {
     }
 }
 
-const FOV: f64 = 90.0f64 * 2.0 * std::f64::consts::PI / 360.0;
+/// Vertical field of view, in radians
+pub const FOV: f64 = 90.0f64 * 2.0 * std::f64::consts::PI / 360.0;
 
 /// The player's frustum
 #[derive(Debug, Clone, Copy)]
@@ -102,6 +103,22 @@ impl Frustum {
         ]
     }
 
+    /// Get the 8 corners, in world coordinates, of the part of the frustum between the distances `near` and `far`
+    /// from the camera
+    pub fn get_slice_corners(&self, aspect_ratio: f64, near: f64, far: f64) -> [Vector3<f64>; 8] {
+        let inverse_view = self.get_view_matrix().try_inverse().unwrap();
+        let t = (FOV / 2.0).tan();
+        let mut corners = [Vector3::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let distance = if i < 4 { near } else { far };
+            let h = t * distance * if i % 2 == 0 { 1.0 } else { -1.0 };
+            let w = t * distance * aspect_ratio * if i % 4 < 2 { 1.0 } else { -1.0 };
+            let corner_in_view = inverse_view * Vector4::new(w, h, -distance, 1.0);
+            *corner = corner_in_view.xyz() / corner_in_view.w;
+        }
+        corners
+    }
+
     /// Checks whether the frustum contains the chunk. This function may return false positives.
     pub fn contains_chunk(
         planes: &[[Plane; 2]; 3],
@@ -0,0 +1,166 @@
+//! GPU timestamp queries, measuring how long the GPU spends on each pass
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Maximum number of passes measured per frame
+const MAX_PASSES: u32 = 8;
+/// Number of frames whose results can be waiting to be read back at the same time
+const FRAMES_IN_FLIGHT: usize = 3;
+/// Weight of the last frame in the average timings
+const AVERAGE_WEIGHT: f64 = 0.05;
+
+/// A buffer receiving the timestamps of one frame
+struct ReadbackSlot {
+    buffer: wgpu::Buffer,
+    /// The passes measured in the buffer, if it is in use
+    passes: Option<Vec<&'static str>>,
+    map_requested: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+/// Measures the passes of the frames. The results are read a few frames later, so that the CPU never waits for the GPU.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<ReadbackSlot>,
+    current_slot: usize,
+    /// The passes of the current frame
+    passes: Vec<&'static str>,
+    /// Nanoseconds per timestamp tick
+    timestamp_period: f64,
+    /// Average milliseconds spent on each pass
+    averages: Vec<(&'static str, f64)>,
+}
+
+impl GpuTimer {
+    /// Return the device features required by the timer
+    pub fn required_features() -> wgpu::Features {
+        wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS
+    }
+
+    /// Create the timer. The device must have been created with the `required_features`.
+    pub fn new(device: &wgpu::Device, timestamp_period: f32) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu_timer_queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2 * MAX_PASSES,
+        });
+        let buffer_size = 2 * MAX_PASSES as u64 * std::mem::size_of::<u64>() as u64;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer_resolve_buffer"),
+            size: buffer_size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let slots = (0..FRAMES_IN_FLIGHT)
+            .map(|_| ReadbackSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("gpu_timer_readback_buffer"),
+                    size: buffer_size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                passes: None,
+                map_requested: false,
+                mapped: Arc::new(AtomicBool::new(false)),
+            })
+            .collect();
+        Self {
+            query_set,
+            resolve_buffer,
+            slots,
+            current_slot: 0,
+            passes: Vec::new(),
+            timestamp_period: timestamp_period as f64,
+            averages: Vec::new(),
+        }
+    }
+
+    /// Start measuring a pass. Passes that don't fit in the query set are not measured.
+    pub fn begin_pass(&mut self, encoder: &mut wgpu::CommandEncoder, name: &'static str) {
+        if (self.passes.len() as u32) < MAX_PASSES {
+            encoder.write_timestamp(&self.query_set, 2 * self.passes.len() as u32);
+            self.passes.push(name);
+        }
+    }
+
+    /// Stop measuring the pass started by the last call to `begin_pass`
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if (self.passes.len() as u32) <= MAX_PASSES && !self.passes.is_empty() {
+            encoder.write_timestamp(&self.query_set, 2 * self.passes.len() as u32 - 1);
+        }
+    }
+
+    /// Copy the timestamps of the frame to a readback buffer. Must be called once per frame, after the last pass.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let passes = std::mem::take(&mut self.passes);
+        let slot = &mut self.slots[self.current_slot];
+        // Drop the measurements if the GPU is too far behind
+        if passes.is_empty() || slot.passes.is_some() {
+            return;
+        }
+        let query_count = 2 * passes.len() as u32;
+        encoder.resolve_query_set(&self.query_set, 0..query_count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &slot.buffer,
+            0,
+            query_count as u64 * std::mem::size_of::<u64>() as u64,
+        );
+        slot.passes = Some(passes);
+        self.current_slot = (self.current_slot + 1) % FRAMES_IN_FLIGHT;
+    }
+
+    /// Read the timestamps of the previous frames that are available.
+    /// Must be called before the commands of the current frame are encoded.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        for slot in self.slots.iter_mut() {
+            // The copy was submitted with the previous frame, so the buffer can now be mapped
+            if slot.passes.is_some() && !slot.map_requested {
+                slot.map_requested = true;
+                let mapped = slot.mapped.clone();
+                slot.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                    if result.is_ok() {
+                        mapped.store(true, Ordering::Release);
+                    }
+                });
+            }
+        }
+        device.poll(wgpu::Maintain::Poll);
+
+        for slot in self.slots.iter_mut() {
+            if !slot.mapped.swap(false, Ordering::Acquire) {
+                continue;
+            }
+            let passes = slot.passes.take().unwrap();
+            let timestamps = {
+                let data = slot.buffer.slice(..).get_mapped_range();
+                data.chunks_exact(8)
+                    .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                    .collect::<Vec<_>>()
+            };
+            slot.buffer.unmap();
+            slot.map_requested = false;
+
+            for (i, name) in passes.into_iter().enumerate() {
+                let ticks = timestamps[2 * i + 1].saturating_sub(timestamps[2 * i]);
+                let millis = ticks as f64 * self.timestamp_period / 1_000_000.0;
+                match self.averages.iter_mut().find(|(pass, _)| *pass == name) {
+                    Some((_, average)) => *average += (millis - *average) * AVERAGE_WEIGHT,
+                    None => self.averages.push((name, millis)),
+                }
+            }
+        }
+    }
+
+    /// Text summary of the average timings, for the debug overlay
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (name, millis) in self.averages.iter() {
+            summary.push_str(&format!("GPU {:20} {:6.2} ms\n", name, millis));
+        }
+        summary
+    }
+}
@@ -0,0 +1,174 @@
+//! Icons of the items, drawn in the slots of the UI instead of their 3d model. Each item model is rendered once, when
+//! the game data is loaded, into an offscreen image on the CPU, and the images are packed into one atlas texture that
+//! the UI renderer samples. The flat items are seen from the front, and the other models from above their front right
+//! corner.
+
+use common::data::vox::VoxelModel;
+use common::item::{ItemId, ItemMesh};
+use common::registry::Registry;
+use nalgebra::Vector3;
+
+/// Size of the icon of an item in the atlas, in pixels
+const ICON_SIZE: u32 = 64;
+/// Empty pixels around each icon, so that the sampling doesn't bleed into the next icon
+const ICON_PADDING: u32 = 2;
+/// Brightness of the faces of the voxels facing each axis, so that the shape of the models is visible
+const FACE_SHADES: [f32; 3] = [0.7, 0.85, 1.0];
+
+/// The icons of all the items, packed in a square grid
+pub struct IconAtlas {
+    /// Width and height of the atlas, in pixels
+    size: u32,
+    /// The RGBA8 sRGB pixels of the atlas
+    pixels: Vec<u8>,
+    /// The rectangle of the icon of each item in the atlas, as (u0, v0, u1, v1), None if its model is missing
+    uvs: Vec<Option<[f32; 4]>>,
+}
+
+impl IconAtlas {
+    /// Render the icon of each item, the mesh of item `i` being `item_meshes[i]`
+    pub fn bake(item_meshes: &[ItemMesh], models: &Registry<VoxelModel>) -> Self {
+        let columns = ((item_meshes.len() as f64).sqrt().ceil() as u32).max(1);
+        let size = columns * ICON_SIZE;
+        let mut pixels = vec![0; (4 * size * size) as usize];
+        let mut uvs = Vec::with_capacity(item_meshes.len());
+        for (i, mesh) in item_meshes.iter().enumerate() {
+            let ItemMesh::SimpleMesh { mesh_id, .. } = mesh;
+            let model = match models.get_value_by_id(*mesh_id) {
+                Some(model) if model.is_well_formed() && model.full.contains(&true) => model,
+                _ => {
+                    uvs.push(None);
+                    continue;
+                }
+            };
+            let (cell_x, cell_y) = (i as u32 % columns * ICON_SIZE, i as u32 / columns * ICON_SIZE);
+            let icon = render_icon(model);
+            for y in 0..ICON_SIZE {
+                let row = (4 * ((cell_y + y) * size + cell_x)) as usize;
+                let icon_row = (4 * y * ICON_SIZE) as usize;
+                pixels[row..row + 4 * ICON_SIZE as usize]
+                    .copy_from_slice(&icon[icon_row..icon_row + 4 * ICON_SIZE as usize]);
+            }
+            let to_uv = |pixel: u32| pixel as f32 / size as f32;
+            uvs.push(Some([
+                to_uv(cell_x),
+                to_uv(cell_y),
+                to_uv(cell_x + ICON_SIZE),
+                to_uv(cell_y + ICON_SIZE),
+            ]));
+        }
+        Self { size, pixels, uvs }
+    }
+
+    /// The rectangle of the icon of an item in the atlas, None if it has no icon
+    pub fn get(&self, item: ItemId) -> Option<[f32; 4]> {
+        self.uvs.get(item as usize).copied().flatten()
+    }
+
+    pub(super) fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub(super) fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+/// Render a model into an `ICON_SIZE`x`ICON_SIZE` RGBA8 image, with an orthographic camera that fits the model
+fn render_icon(model: &VoxelModel) -> Vec<u8> {
+    let size = Vector3::new(model.size_x as f32, model.size_y as f32, model.size_z as f32);
+    let direction = if model.size_z == 1 {
+        Vector3::new(0.0, 0.0, -1.0)
+    } else {
+        Vector3::new(-1.0, -1.0, -1.0).normalize()
+    };
+    let right = direction.cross(&Vector3::y()).normalize();
+    let up = right.cross(&direction);
+    let center = size / 2.0;
+    // Half of the width of the view, large enough for all the corners of the model
+    let mut extent: f32 = 0.0;
+    for corner in 0..8 {
+        let corner = Vector3::new(
+            if corner & 1 == 0 { 0.0 } else { size.x },
+            if corner & 2 == 0 { 0.0 } else { size.y },
+            if corner & 4 == 0 { 0.0 } else { size.z },
+        );
+        extent = extent.max((corner - center).dot(&right).abs()).max((corner - center).dot(&up).abs());
+    }
+    let drawn_size = (ICON_SIZE - 2 * ICON_PADDING) as f32;
+    let mut pixels = vec![0; (4 * ICON_SIZE * ICON_SIZE) as usize];
+    for y in ICON_PADDING..ICON_SIZE - ICON_PADDING {
+        for x in ICON_PADDING..ICON_SIZE - ICON_PADDING {
+            let s = ((x - ICON_PADDING) as f32 + 0.5) / drawn_size * 2.0 - 1.0;
+            let t = 1.0 - ((y - ICON_PADDING) as f32 + 0.5) / drawn_size * 2.0;
+            let origin = center + right * (s * extent) + up * (t * extent) - direction * size.norm();
+            if let Some((color, axis)) = cast_ray(model, origin, direction) {
+                let shade = FACE_SHADES[axis];
+                let pixel = (4 * (y * ICON_SIZE + x)) as usize;
+                for channel in 0..3 {
+                    let value = (color >> (8 * channel)) & 0xFF;
+                    pixels[pixel + channel] = (value as f32 * shade).round() as u8;
+                }
+                pixels[pixel + 3] = 255;
+            }
+        }
+    }
+    pixels
+}
+
+/// The color of the first full voxel hit by a ray and the axis of the face it hits, walking the voxels one by one
+fn cast_ray(model: &VoxelModel, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(u32, usize)> {
+    let size = [model.size_x as i64, model.size_y as i64, model.size_z as i64];
+    // Where the ray enters the box of the model
+    let mut t_enter: f32 = 0.0;
+    let mut t_exit = f32::INFINITY;
+    let mut axis = 0;
+    for i in 0..3 {
+        if direction[i] == 0.0 {
+            if origin[i] < 0.0 || origin[i] > size[i] as f32 {
+                return None;
+            }
+            continue;
+        }
+        let t0 = -origin[i] / direction[i];
+        let t1 = (size[i] as f32 - origin[i]) / direction[i];
+        if t0.min(t1) > t_enter {
+            t_enter = t0.min(t1);
+            axis = i;
+        }
+        t_exit = t_exit.min(t0.max(t1));
+    }
+    if t_enter > t_exit {
+        return None;
+    }
+    let entry = origin + direction * t_enter;
+    let mut voxel = [0; 3];
+    let mut step = [0; 3];
+    // The distance along the ray to the next voxel boundary of each axis, and between two boundaries
+    let mut t_next = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+    for i in 0..3 {
+        voxel[i] = (entry[i].floor() as i64).clamp(0, size[i] - 1);
+        if direction[i] > 0.0 {
+            step[i] = 1;
+            t_delta[i] = 1.0 / direction[i];
+            t_next[i] = t_enter + (voxel[i] as f32 + 1.0 - entry[i]) / direction[i];
+        } else if direction[i] < 0.0 {
+            step[i] = -1;
+            t_delta[i] = -1.0 / direction[i];
+            t_next[i] = t_enter + (voxel[i] as f32 - entry[i]) / direction[i];
+        }
+    }
+    loop {
+        let index = (voxel[0] * size[1] * size[2] + voxel[1] * size[2] + voxel[2]) as usize;
+        if model.full[index] {
+            return Some((model.voxels[index], axis));
+        }
+        axis = (0..3).min_by(|&a, &b| t_next[a].total_cmp(&t_next[b])).unwrap();
+        voxel[axis] += step[axis];
+        if voxel[axis] < 0 || voxel[axis] >= size[axis] {
+            return None;
+        }
+        t_next[axis] += t_delta[axis];
+    }
+}
@@ -1,29 +1,68 @@
 //! Helpers for pipeline creation and initialization
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::sync::Mutex;
+use super::pipeline_cache::pipeline_cache;
 use wgpu::{FragmentState, VertexState};
 use wgpu_types::{BlendComponent, BlendFactor, BlendOperation, BlendState, DepthBiasState};
 
 /// Shader stage
+#[derive(Debug, Clone, Copy)]
 pub enum ShaderStage {
     Vertex,
     Fragment,
+    Compute,
 }
 
-/// Load a GLSL shader from a file and compile it to SPIR-V
+/// The shaders of all the renderers, compiled by the splash screen before the game starts
+pub const SHADERS: [(ShaderStage, &str); 20] = [
+    (ShaderStage::Vertex, "assets/shaders/gui-rect.vert"),
+    (ShaderStage::Fragment, "assets/shaders/gui-rect.frag"),
+    (ShaderStage::Vertex, "assets/shaders/gui-icon.vert"),
+    (ShaderStage::Fragment, "assets/shaders/gui-icon.frag"),
+    (ShaderStage::Vertex, "assets/shaders/world.vert"),
+    (ShaderStage::Fragment, "assets/shaders/world.frag"),
+    (ShaderStage::Vertex, "assets/shaders/skybox.vert"),
+    (ShaderStage::Fragment, "assets/shaders/skybox.frag"),
+    (ShaderStage::Vertex, "assets/shaders/target.vert"),
+    (ShaderStage::Fragment, "assets/shaders/target.frag"),
+    (ShaderStage::Vertex, "assets/shaders/model.vert"),
+    (ShaderStage::Fragment, "assets/shaders/model.frag"),
+    (ShaderStage::Vertex, "assets/shaders/debug_lines.vert"),
+    (ShaderStage::Fragment, "assets/shaders/debug_lines.frag"),
+    (ShaderStage::Vertex, "assets/shaders/shadow.vert"),
+    (ShaderStage::Compute, "assets/shaders/light_volume.comp"),
+    (ShaderStage::Vertex, "assets/shaders/fullscreen.vert"),
+    (ShaderStage::Fragment, "assets/shaders/tonemap.frag"),
+    (ShaderStage::Fragment, "assets/shaders/fxaa.frag"),
+    (ShaderStage::Fragment, "assets/shaders/mipmap.frag"),
+];
+
+/// The SPIR-V of the shaders compiled so far, by path. The shaders don't change while the game runs, so each one is
+/// only compiled once, and the renderers created again later, such as after a lost device, don't wait for shaderc.
+static COMPILED_SHADERS: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+/// Load a GLSL shader from a file and compile it to SPIR-V, or return it if it was already compiled
 pub fn load_glsl_shader<'a, P: AsRef<Path>>(stage: ShaderStage, path: P) -> Vec<u8> {
+    let path_display = path.as_ref().display().to_string();
+    if let Some(bytes) = COMPILED_SHADERS.lock().unwrap().get(&path_display) {
+        return bytes.clone();
+    }
     let ty = match stage {
         ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
         ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+        ShaderStage::Compute => shaderc::ShaderKind::Compute,
     };
-    let path_display = path.as_ref().display().to_string();
     log::info!("Loading GLSL shader from {}", path_display);
     let glsl_source = std::fs::read_to_string(path).expect("Couldn't read shader from file");
 
     let mut compiler = shaderc::Compiler::new().unwrap();
-    compiler.compile_into_spirv(&glsl_source, ty, &path_display, "main", None)
+    let bytes = compiler.compile_into_spirv(&glsl_source, ty, &path_display, "main", None)
         .expect("Couldn't compile shader.")
         .as_binary_u8()
-        .to_vec()
+        .to_vec();
+    COMPILED_SHADERS.lock().unwrap().insert(path_display, bytes.clone());
+    bytes
 }
 
 /// Default `RasterizationStateDescriptor` with no backface culling
@@ -103,6 +142,7 @@ pub fn create_default_pipeline(
 
     log::trace!("Creating render pipeline.");
 
+    let cache = pipeline_cache();
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(&pipeline_layout),
@@ -122,6 +162,6 @@ pub fn create_default_pipeline(
             targets: &[],
         }),
         multiview: None,
-        cache: None,
+        cache: cache.as_deref(),
     })
 }
@@ -0,0 +1,92 @@
+//! Generation of the mipmaps of a texture on the GPU. Each level is drawn from the previous one by a fullscreen pass,
+//! whose linear filtering averages the 4 texels under each new texel. The texels are read and written through sRGB
+//! views, so the average is computed on the linear colors.
+
+use super::postprocess::{create_pass, create_pass_pipeline};
+use wgpu_types::SamplerBindingType;
+
+/// Format of the views of the passes. The textures must have this format, or allow it in their `view_formats`.
+pub const MIPMAP_VIEW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// The number of levels of the full mip chain of a texture, down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Fill the levels after the first one of each array layer of a texture. The texture needs the `RENDER_ATTACHMENT`
+/// and `TEXTURE_BINDING` usages.
+pub fn generate_mipmaps(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+    let bind_group_layout = device.create_bind_group_layout(&MIPMAP_BIND_GROUP_LAYOUT);
+    let pipeline = create_pass_pipeline(
+        device,
+        &bind_group_layout,
+        "assets/shaders/mipmap.frag",
+        MIPMAP_VIEW_FORMAT,
+        1,
+    );
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("mipmap sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let create_level_view = |layer, level| {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("mipmap level"),
+            format: Some(MIPMAP_VIEW_FORMAT),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    };
+    let levels = (0..texture.depth_or_array_layers())
+        .flat_map(|layer| (1..texture.mip_level_count()).map(move |level| (layer, level)));
+    for (layer, level) in levels {
+        let source = create_level_view(layer, level - 1);
+        let target = create_level_view(layer, level);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&source),
+                },
+            ],
+        });
+        let mut rpass = create_pass(encoder, &target);
+        rpass.set_pipeline(&pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+const MIPMAP_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+    label: None,
+    entries: &[
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+    ],
+};
@@ -2,16 +2,32 @@
 
 /* WebGPU HELPER MODULES */
 mod buffers;
+pub mod compatibility;
 mod init;
 mod render;
-pub use self::render::{buffer_from_slice, clear_color_and_depth, clear_depth, to_u8_slice};
+pub use self::init::{load_glsl_shader, ShaderStage, SHADERS};
+mod upload;
+pub use self::render::{clear_color_and_depth, clear_depth, to_u8_slice};
+pub use self::upload::Uploader;
 
 /* OTHER HELPER MODULES */
 mod frustum;
+mod gpu_timing;
+mod mipmaps;
+mod pipeline_cache;
 pub use self::frustum::Frustum;
+pub use self::gpu_timing::GpuTimer;
+pub use self::mipmaps::{generate_mipmaps, mip_level_count};
+pub use self::pipeline_cache::DiskPipelineCache;
 
 /* RENDERING-RESPONSIBLE MODULES */
+mod billboard;
+mod icons;
+mod postprocess;
 mod ui;
 pub mod world;
+pub use self::billboard::{Billboard, BillboardRenderer};
+pub use self::icons::IconAtlas;
+pub use self::postprocess::{ColorFilter, PostProcessing, HDR_FORMAT};
 pub use self::ui::UiRenderer;
-pub use self::world::{Model, WorldRenderer};
+pub use self::world::{DebugBox, DebugRenderModes, Model, PointLight, ShadowQuality, Spot, WorldRenderer};
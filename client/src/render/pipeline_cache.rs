@@ -0,0 +1,114 @@
+//! The pipeline cache of the driver, saved on disk so that the pipelines compiled during a run are reused by the next
+//! ones. Only some backends, such as Vulkan, support pipeline caches.
+//!
+//! There is one file per adapter, named after the key that wgpu gives to the adapter and a hash of the sources of the
+//! shaders, so that the pipelines of modified shaders are compiled again. wgpu also checks that the data was saved by
+//! the same driver and the same version of wgpu, and starts from an empty cache otherwise.
+
+use super::init::SHADERS;
+use anyhow::{Context, Result};
+use common::data::cache::hash_bytes;
+use log::info;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The folder of the pipeline caches, one file per adapter
+pub const PIPELINE_CACHE_FOLDER: &str = "cache/pipelines";
+
+/// The pipeline cache of the current device, None if the device doesn't support pipeline caches
+static PIPELINE_CACHE: Mutex<Option<DiskPipelineCache>> = Mutex::new(None);
+
+/// A pipeline cache and the file it is saved to
+pub struct DiskPipelineCache {
+    cache: Arc<wgpu::PipelineCache>,
+    /// The beginning of the names of the files of the adapter, for all the versions of the shaders
+    prefix: String,
+    path: PathBuf,
+}
+
+impl DiskPipelineCache {
+    pub fn required_features() -> wgpu::Features {
+        wgpu::Features::PIPELINE_CACHE
+    }
+
+    /// Create the pipeline cache of a new device, with the pipelines saved by the previous runs on the same adapter.
+    /// The pipelines created afterwards use it.
+    pub fn load(adapter: &wgpu::Adapter, device: &wgpu::Device) {
+        let mut current = PIPELINE_CACHE.lock().unwrap();
+        *current = None;
+        if !device.features().contains(Self::required_features()) {
+            return;
+        }
+        let prefix = match wgpu::util::pipeline_cache_key(&adapter.get_info()) {
+            Some(key) => format!("{}_", key),
+            None => return,
+        };
+        let path = Path::new(PIPELINE_CACHE_FOLDER).join(format!("{}{:016x}.bin", prefix, shaders_hash()));
+        let data = std::fs::read(&path).ok();
+        if data.is_some() {
+            info!("Loading the pipeline cache {}", path.display());
+        }
+        // Safety: the data was returned by `get_data` in a previous run, wgpu rejects the data of other adapters
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("pipeline cache"),
+                data: data.as_deref(),
+                // Start from an empty cache if the data is invalid or outdated
+                fallback: true,
+            })
+        };
+        *current = Some(Self {
+            cache: Arc::new(cache),
+            prefix,
+            path,
+        });
+    }
+
+    /// Save the pipelines compiled so far, and remove the caches of the previous versions of the shaders
+    pub fn save() {
+        if let Some(disk_cache) = PIPELINE_CACHE.lock().unwrap().as_ref() {
+            if let Some(data) = disk_cache.cache.get_data() {
+                if let Err(e) = disk_cache.write(&data) {
+                    log::error!("{:#}", e);
+                }
+            }
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> Result<()> {
+        std::fs::create_dir_all(PIPELINE_CACHE_FOLDER).context("Failed to create the pipeline cache folder")?;
+        // A crash while writing must not leave a truncated cache
+        let temporary_path = self.path.with_extension("tmp");
+        std::fs::write(&temporary_path, data)
+            .with_context(|| format!("Failed to write {}", temporary_path.display()))?;
+        std::fs::rename(&temporary_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        info!("Saved the pipeline cache {}", self.path.display());
+
+        let entries = std::fs::read_dir(PIPELINE_CACHE_FOLDER).context("Failed to list the pipeline caches")?;
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            if name.starts_with(&self.prefix) && path != self.path {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    log::warn!("Failed to remove the outdated pipeline cache {}: {}", path.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The pipeline cache of the current device, for the descriptors of the pipelines
+pub fn pipeline_cache() -> Option<Arc<wgpu::PipelineCache>> {
+    PIPELINE_CACHE.lock().unwrap().as_ref().map(|disk_cache| disk_cache.cache.clone())
+}
+
+/// A hash of the paths and the sources of the shaders
+fn shaders_hash() -> u64 {
+    let mut sources = Vec::new();
+    for (_, path) in SHADERS.iter() {
+        sources.extend_from_slice(path.as_bytes());
+        sources.extend(std::fs::read(path).unwrap_or_default());
+    }
+    hash_bytes(&sources)
+}
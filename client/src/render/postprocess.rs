@@ -0,0 +1,343 @@
+//! Screen-space effects. The world is rendered into an offscreen HDR buffer, then the post-processing passes draw it
+//! into the frame buffer of the window, where the UI is drawn on top:
+//! 1. color filter, tone mapping and gamma, into an intermediate buffer;
+//! 2. FXAA, or a plain copy if it is disabled, into the frame buffer of the window.
+//! In the compatibility mode, only the plain copy of the world is done.
+
+use super::compatibility;
+use super::init::{load_glsl_shader, ShaderStage};
+use super::pipeline_cache::pipeline_cache;
+use super::{to_u8_slice, Uploader};
+use crate::settings::PostProcessSettings;
+use crate::window::{WindowBuffers, COLOR_FORMAT};
+use wgpu::ShaderModuleDescriptor;
+use wgpu_types::SamplerBindingType;
+
+/// Format of the offscreen buffer of the world. The colors may be brighter than 1 before the tone mapping.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Format of the output of the tone mapping, the input of the FXAA
+const LDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const UNIFORM_SIZE: u64 = 4 * 12;
+
+/// A color tint applied to the whole screen, for example when the camera is underwater, or the haze of the biomes.
+/// It could also be used for the weather, such as dust storms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorFilter {
+    pub color: [f32; 3],
+    /// From 0 (no filter) to 1
+    pub strength: f32,
+}
+
+impl ColorFilter {
+    pub const NONE: Self = Self {
+        color: [1.0, 1.0, 1.0],
+        strength: 0.0,
+    };
+    pub const UNDERWATER: Self = Self {
+        color: [0.2, 0.45, 0.8],
+        strength: 0.6,
+    };
+    pub const IN_LAVA: Self = Self {
+        color: [1.0, 0.35, 0.05],
+        strength: 0.8,
+    };
+
+    /// The filter between `self` (t = 0) and `other` (t = 1)
+    pub fn lerp(&self, other: &ColorFilter, t: f32) -> ColorFilter {
+        ColorFilter {
+            color: std::array::from_fn(|i| self.color[i] + (other.color[i] - self.color[i]) * t),
+            strength: self.strength + (other.strength - self.strength) * t,
+        }
+    }
+}
+
+/// The buffers of the world, sized like the window
+struct OffscreenBuffers {
+    size: (u32, u32),
+    /// None in the compatibility mode, the world is drawn directly into `hdr_resolved`
+    hdr_multisampled: Option<wgpu::TextureView>,
+    hdr_resolved: wgpu::TextureView,
+    /// The output of the tone mapping, None in the compatibility mode where the tone mapping is skipped
+    ldr: Option<(wgpu::TextureView, wgpu::BindGroup)>,
+    fxaa_bind_group: wgpu::BindGroup,
+}
+
+pub struct PostProcessing {
+    sampler: wgpu::Sampler,
+    uniform: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    tone_mapping_pipeline: wgpu::RenderPipeline,
+    fxaa_pipeline: wgpu::RenderPipeline,
+    /// Created on the first frame, and recreated when the window is resized
+    buffers: Option<OffscreenBuffers>,
+}
+
+impl PostProcessing {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post-processing sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: UNIFORM_SIZE,
+            usage: (wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&POST_PROCESS_BIND_GROUP_LAYOUT);
+        let tone_mapping_pipeline = create_pass_pipeline(
+            device,
+            &bind_group_layout,
+            "assets/shaders/tonemap.frag",
+            LDR_FORMAT,
+            1,
+        );
+        let fxaa_pipeline = create_pass_pipeline(
+            device,
+            &bind_group_layout,
+            "assets/shaders/fxaa.frag",
+            COLOR_FORMAT,
+            compatibility::sample_count(),
+        );
+        Self {
+            sampler,
+            uniform,
+            bind_group_layout,
+            tone_mapping_pipeline,
+            fxaa_pipeline,
+            buffers: None,
+        }
+    }
+
+    /// Recreate the offscreen buffers if the size of the window changed
+    pub fn resize(&mut self, device: &wgpu::Device, (width, height): (u32, u32)) {
+        if self.buffers.as_ref().map(|buffers| buffers.size) == Some((width, height)) {
+            return;
+        }
+        let create_view = |format, sample_count| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("post-processing buffer"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let sample_count = compatibility::sample_count();
+        let hdr_multisampled = (sample_count > 1).then(|| create_view(HDR_FORMAT, sample_count));
+        let hdr_resolved = create_view(HDR_FORMAT, 1);
+        let (ldr, fxaa_bind_group) = if compatibility::is_enabled() {
+            (None, self.create_bind_group(device, &hdr_resolved))
+        } else {
+            let ldr = create_view(LDR_FORMAT, 1);
+            let tone_mapping_bind_group = self.create_bind_group(device, &hdr_resolved);
+            let fxaa_bind_group = self.create_bind_group(device, &ldr);
+            (Some((ldr, tone_mapping_bind_group)), fxaa_bind_group)
+        };
+        self.buffers = Some(OffscreenBuffers {
+            size: (width, height),
+            hdr_multisampled,
+            hdr_resolved,
+            ldr,
+            fxaa_bind_group,
+        });
+    }
+
+    /// The buffers to render the world into, instead of the buffers of the window. The depth buffer is shared.
+    /// `resize` must have been called first.
+    pub fn world_buffers<'a>(&'a self, window_buffers: WindowBuffers<'a>) -> WindowBuffers<'a> {
+        let buffers = self.buffers.as_ref().expect("The post-processing buffers were not created");
+        WindowBuffers {
+            texture_buffer: &buffers.hdr_resolved,
+            multisampled_texture_buffer: buffers.hdr_multisampled.as_ref().unwrap_or(&buffers.hdr_resolved),
+            depth_buffer: window_buffers.depth_buffer,
+        }
+    }
+
+    /// Apply the passes to the world, and draw the result into the multisampled frame buffer of the window
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        window_buffers: WindowBuffers,
+        settings: &PostProcessSettings,
+        color_filter: ColorFilter,
+    ) {
+        let buffers = self.buffers.as_ref().expect("The post-processing buffers were not created");
+        let color_filter = if settings.color_filters { color_filter } else { ColorFilter::NONE };
+        let as_float = |enabled: bool| if enabled { 1.0 } else { 0.0 };
+        // In the compatibility mode, the FXAA pass only copies the world into the frame buffer
+        let fxaa = settings.fxaa && !compatibility::is_enabled();
+        let uniform: [f32; 12] = [
+            settings.exposure,
+            settings.gamma.max(0.1),
+            as_float(settings.tone_mapping),
+            as_float(fxaa),
+            color_filter.color[0],
+            color_filter.color[1],
+            color_filter.color[2],
+            color_filter.strength,
+            1.0 / buffers.size.0 as f32,
+            1.0 / buffers.size.1 as f32,
+            0.0,
+            0.0,
+        ];
+        uploader.upload_buffer(device, encoder, &self.uniform, 0, to_u8_slice(&uniform));
+
+        // The world was drawn into the multisampled buffer
+        super::render::encode_resolve_render_pass(encoder, self.world_buffers(window_buffers));
+
+        if let Some((ldr, tone_mapping_bind_group)) = &buffers.ldr {
+            let mut rpass = create_pass(encoder, ldr);
+            rpass.set_pipeline(&self.tone_mapping_pipeline);
+            rpass.set_bind_group(0, tone_mapping_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+        {
+            let mut rpass = create_pass(encoder, window_buffers.multisampled_texture_buffer);
+            rpass.set_pipeline(&self.fxaa_pipeline);
+            rpass.set_bind_group(0, &buffers.fxaa_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+
+    fn create_bind_group(&self, device: &wgpu::Device, input: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(self.uniform.as_entire_buffer_binding()),
+                },
+            ],
+        })
+    }
+}
+
+/// Create a render pass that overwrites the whole target, without depth buffer
+pub(super) fn create_pass<'a>(encoder: &'a mut wgpu::CommandEncoder, target: &'a wgpu::TextureView) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("post-processing"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+const POST_PROCESS_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+    label: None,
+    entries: &[
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+    ],
+};
+
+/// Create the pipeline of a fullscreen pass
+pub(super) fn create_pass_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    fragment_shader_path: &str,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/fullscreen.vert");
+    let vertex_shader_module = device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&vertex_shader_bytes),
+    });
+    let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, fragment_shader_path);
+    let fragment_shader_module = device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&fragment_shader_bytes),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+    let cache = pipeline_cache();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(fragment_shader_path),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vertex_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fragment_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+        cache: cache.as_deref(),
+    })
+}
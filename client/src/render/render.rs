@@ -1,8 +1,8 @@
 //! Helpers for renderer passes
 
 use wgpu::StoreOp;
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use crate::window::WindowBuffers;
+use super::compatibility;
 
 /// Create an attachment for the depth buffer that doesn't clear it.
 pub fn create_default_depth_stencil_attachment(
@@ -46,6 +46,10 @@ pub fn create_default_render_pass<'a>(
 
 /// Encode a render pass to resolve the multisampled frame buffer to the window frame buffer
 pub fn encode_resolve_render_pass<'a>(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
+    // Without multisampling, the passes already drew into the resolved buffer
+    if compatibility::sample_count() == 1 {
+        return;
+    }
     let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         label: None,
         color_attachments: &[Option::from(wgpu::RenderPassColorAttachment {
@@ -116,13 +120,3 @@ pub fn clear_depth(encoder: &mut wgpu::CommandEncoder, buffers: WindowBuffers) {
 pub fn to_u8_slice<T: Copy>(v: &[T]) -> &[u8] {
     unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * std::mem::size_of::<T>()) }
 }
-
-/// Helper to create a buffer from an existing slice.
-pub fn buffer_from_slice(device: &wgpu::Device, usage: wgpu::BufferUsages, data: &[u8]) -> wgpu::Buffer {
-    device.create_buffer_init(&BufferInitDescriptor {
-        label: None,
-        usage,
-        contents: &data
-    })
-}
-
@@ -1,9 +1,14 @@
 //! Ui rendering
 
-use super::{ buffer_from_slice, to_u8_slice };
+use super::{to_u8_slice, Uploader};
 use super::buffers::DynamicBuffer;
-use super::init::{load_glsl_shader, ShaderStage};
-use crate::ui::PrimitiveBuffer;
+use super::compatibility;
+use super::icons::IconAtlas;
+use super::init::{
+    load_glsl_shader, ShaderStage, DEFAULT_COLOR_STATE_DESCRIPTOR, DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR,
+};
+use super::pipeline_cache::pipeline_cache;
+use crate::ui::{PrimitiveBuffer, RectanglePrimitive, TextPrimitive};
 use crate::window::{WindowBuffers, WindowData};
 use std::collections::{BTreeMap, HashMap};
 use wgpu::{Label, ShaderSource};
@@ -20,6 +25,18 @@ pub struct UiRenderer {
     pipeline: wgpu::RenderPipeline,
     vertex_buffer: DynamicBuffer<UiVertex>,
     index_buffer: DynamicBuffer<u32>,
+    // Icon rendering
+    icon_sampler: wgpu::Sampler,
+    icon_bind_group_layout: wgpu::BindGroupLayout,
+    icon_pipeline: wgpu::RenderPipeline,
+    /// The bind group of the icon atlas, None until the atlas is set
+    icon_bind_group: Option<wgpu::BindGroup>,
+    icon_vertex_buffer: DynamicBuffer<IconVertex>,
+    icon_index_buffer: DynamicBuffer<u32>,
+    // Overlay rendering, the rectangles drawn above the text
+    overlay_pipeline: wgpu::RenderPipeline,
+    overlay_vertex_buffer: DynamicBuffer<UiVertex>,
+    overlay_index_buffer: DynamicBuffer<u32>,
 }
 
 impl<'a> UiRenderer {
@@ -109,6 +126,33 @@ impl<'a> UiRenderer {
 
         log::trace!("Created pipeline.");
 
+        let icon_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("icon atlas sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let icon_bind_group_layout = device.create_bind_group_layout(&ICON_BIND_GROUP_LAYOUT);
+        let icon_pipeline = create_ui_pipeline(
+            device,
+            &[&uniform_layout, &icon_bind_group_layout],
+            "gui-icon",
+            std::mem::size_of::<IconVertex>(),
+            &ICON_VERTEX_ATTRIBUTES,
+            true,
+        );
+        let overlay_pipeline = create_ui_pipeline(
+            device,
+            &[&uniform_layout],
+            "gui-rect",
+            std::mem::size_of::<UiVertex>(),
+            &UI_VERTEX_ATTRIBUTES,
+            false,
+        );
+
         Self {
             glyph_brush,
             fonts,
@@ -117,14 +161,64 @@ impl<'a> UiRenderer {
             pipeline,
             vertex_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsages::VERTEX),
             index_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsages::INDEX),
+            icon_sampler,
+            icon_bind_group_layout,
+            icon_pipeline,
+            icon_bind_group: None,
+            icon_vertex_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsages::VERTEX),
+            icon_index_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsages::INDEX),
+            overlay_pipeline,
+            overlay_vertex_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsages::VERTEX),
+            overlay_index_buffer: DynamicBuffer::with_capacity(device, 64, wgpu::BufferUsages::INDEX),
         }
     }
 
+    /// Upload the icons of the items, replacing the previous atlas
+    pub fn set_icon_atlas(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        atlas: &IconAtlas,
+    ) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("icon atlas"),
+            size: wgpu::Extent3d {
+                width: atlas.size(),
+                height: atlas.size(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        uploader.upload_texture(device, encoder, &texture, 0, 0, (atlas.size(), atlas.size()), atlas.pixels());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.icon_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("icon atlas"),
+            layout: &self.icon_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&self.icon_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+            ],
+        }));
+    }
+
     pub fn render<Message>(
         &mut self,
         buffers: WindowBuffers<'a>,
         device: &mut wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
         data: &WindowData,
         ui: &quint::Ui<PrimitiveBuffer, Message>,
         gui: &mut crate::gui::Gui,
@@ -139,38 +233,10 @@ impl<'a> UiRenderer {
         let mut rect_vertices: Vec<UiVertex> = Vec::new();
         let mut rect_indices: Vec<u32> = Vec::new();
 
-        use crate::ui::{RectanglePrimitive, TextPrimitive, TrianglesPrimitive};
+        use crate::ui::{IconPrimitive, TrianglesPrimitive};
 
         // Rectangles
-        for RectanglePrimitive {
-            layout: l,
-            color,
-            z,
-        } in primitive_buffer.rectangle.into_iter()
-        {
-            let a = UiVertex {
-                position: [l.x, l.y, z],
-                color: color.clone(),
-            };
-            let b = UiVertex {
-                position: [l.x + l.width, l.y, z],
-                color: color.clone(),
-            };
-            let c = UiVertex {
-                position: [l.x, l.y + l.height, z],
-                color: color.clone(),
-            };
-            let d = UiVertex {
-                position: [l.x + l.width, l.y + l.height, z],
-                color: color.clone(),
-            };
-            let a_index = rect_vertices.len() as u32;
-            let b_index = a_index + 1;
-            let c_index = b_index + 1;
-            let d_index = c_index + 1;
-            rect_vertices.extend([a, b, c, d].iter());
-            rect_indices.extend([b_index, a_index, c_index, b_index, c_index, d_index].iter());
-        }
+        push_rectangles(primitive_buffer.rectangle, &mut rect_vertices, &mut rect_indices);
         // Triangles
         for TrianglesPrimitive {
             vertices,
@@ -186,78 +252,23 @@ impl<'a> UiRenderer {
             );
             rect_indices.extend(indices.into_iter().map(|id| id + index_offset));
         }
-        // Text
-        for TextPrimitive {
-            x, y, w, h,
-            mut parts,
-            z,
-            center_horizontally, center_vertically,
-        } in primitive_buffer.text.into_iter()
-        {
-            let dpi = data.hidpi_factor as f32;
-
-            // Apply DPI to font size
-            for p in parts.iter_mut() {
-                p.font_size.x *= dpi;
-                p.font_size.y *= dpi;
-            }
-            // Get font IDs
-            let Self { ref fonts, .. } = &self;
-            let parts: Vec<wgpu_glyph::Text> = parts
-                .iter()
-                .map(|part| wgpu_glyph::Text::new(&part.text)
-                    .with_scale(part.font_size)
-                    .with_color(part.color)
-                    .with_font_id(part
-                        .font
-                        .clone()
-                        .and_then(|f| fonts.get(&f).cloned())
-                        .unwrap_or_default())
-                )
-                .collect();
-            // Calculate positions
-            let mut x = x as f32;
-            let mut y = y as f32;
-            let mut w = match w {
-                Some(w) => w as f32,
-                None => std::f32::INFINITY,
-            };
-            let mut h = match h {
-                Some(h) => h as f32,
-                None => std::f32::INFINITY,
-            };
-            if center_horizontally {
-                x += w/2.0;
+        // Icons, only once the atlas is set
+        let mut icon_vertices: Vec<IconVertex> = Vec::new();
+        let mut icon_indices: Vec<u32> = Vec::new();
+        if self.icon_bind_group.is_some() {
+            for IconPrimitive { layout: l, uv, z } in primitive_buffer.icons.into_iter() {
+                let index = icon_vertices.len() as u32;
+                icon_vertices.extend([
+                    IconVertex { position: [l.x, l.y, z], uv: [uv[0], uv[1]] },
+                    IconVertex { position: [l.x + l.width, l.y, z], uv: [uv[2], uv[1]] },
+                    IconVertex { position: [l.x, l.y + l.height, z], uv: [uv[0], uv[3]] },
+                    IconVertex { position: [l.x + l.width, l.y + l.height, z], uv: [uv[2], uv[3]] },
+                ]);
+                icon_indices.extend([index + 1, index, index + 2, index + 1, index + 2, index + 3]);
             }
-            if center_vertically {
-                y += h/2.0;
-            }
-            // Apply DPI to positions
-            x *= dpi;
-            y *= dpi;
-            w *= dpi;
-            h *= dpi;
-            let v_align = if center_vertically {
-                wgpu_glyph::VerticalAlign::Center
-            } else {
-                wgpu_glyph::VerticalAlign::Top
-            };
-            let h_align = if center_horizontally {
-                wgpu_glyph::HorizontalAlign::Center
-            } else {
-                wgpu_glyph::HorizontalAlign::Left
-            };
-            let section = wgpu_glyph::Section::default()
-                .with_screen_position((x, y))
-                .with_bounds((w, h))
-                .with_layout(wgpu_glyph::Layout::Wrap {
-                    line_breaker: Default::default(),
-                    v_align,
-                    h_align,
-                })
-                .with_text(parts);
-            self.glyph_brush.queue(section);
         }
+        // Text
+        self.queue_text(primitive_buffer.text, data);
         // Crosshair
         if draw_crosshair {
             let (cx, cy) = (
@@ -333,16 +344,13 @@ impl<'a> UiRenderer {
                 0.5,
                 1.0,
             ];
-            let src_buffer = buffer_from_slice(
-                device,
-                wgpu::BufferUsages::COPY_SRC,
-                to_u8_slice(&transformation_matrix[..])
-            );
-            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.transform_buffer, 0, 16 * 4);
+            uploader.upload_buffer(device, encoder, &self.transform_buffer, 0, to_u8_slice(&transformation_matrix[..]));
             // Update vertex buffer
-            self.vertex_buffer.upload(device, encoder, &rect_vertices);
+            self.vertex_buffer.upload(device, encoder, uploader, &rect_vertices);
             // Update index buffer
-            self.index_buffer.upload(device, encoder, &rect_indices);
+            self.index_buffer.upload(device, encoder, uploader, &rect_indices);
+            self.icon_vertex_buffer.upload(device, encoder, uploader, &icon_vertices);
+            self.icon_index_buffer.upload(device, encoder, uploader, &icon_indices);
             // Draw
             {
                 let mut rpass = super::render::create_default_render_pass(encoder, buffers);
@@ -351,6 +359,13 @@ impl<'a> UiRenderer {
                 rpass.set_vertex_buffer(0, self.vertex_buffer.get_buffer().slice(..));
                 rpass.set_index_buffer(self.index_buffer.get_buffer().slice(..), Default::default());
                 rpass.draw_indexed(0..(self.index_buffer.len() as u32), 0, 0..1);
+                if let Some(icon_bind_group) = self.icon_bind_group.as_ref().filter(|_| !icon_indices.is_empty()) {
+                    rpass.set_pipeline(&self.icon_pipeline);
+                    rpass.set_bind_group(1, icon_bind_group, &[]);
+                    rpass.set_vertex_buffer(0, self.icon_vertex_buffer.get_buffer().slice(..));
+                    rpass.set_index_buffer(self.icon_index_buffer.get_buffer().slice(..), Default::default());
+                    rpass.draw_indexed(0..(self.icon_index_buffer.len() as u32), 0, 0..1);
+                }
             }
         }
 
@@ -359,11 +374,10 @@ impl<'a> UiRenderer {
 
         // Draw text
         // TODO: use depth buffer
-        let mut staging_belt = wgpu::util::StagingBelt::new(128);
         self.glyph_brush
             .draw_queued(
                 device,
-                &mut staging_belt,
+                uploader.belt(),
                 encoder,
                 buffers.texture_buffer,
                 //create_default_depth_stencil_attachment(buffers.depth_buffer),
@@ -371,6 +385,142 @@ impl<'a> UiRenderer {
                 data.physical_window_size.height,
             )
             .expect("couldn't draw queued glyphs");
+
+        // Draw the overlay above the text, directly into the resolved frame buffer
+        if let Some(overlay) = primitive_buffer.overlay {
+            let mut overlay_vertices = Vec::new();
+            let mut overlay_indices = Vec::new();
+            push_rectangles(overlay.rectangle, &mut overlay_vertices, &mut overlay_indices);
+            self.overlay_vertex_buffer.upload(device, encoder, uploader, &overlay_vertices);
+            self.overlay_index_buffer.upload(device, encoder, uploader, &overlay_indices);
+            {
+                let mut rpass = create_overlay_pass(encoder, buffers.texture_buffer);
+                rpass.set_pipeline(&self.overlay_pipeline);
+                rpass.set_bind_group(0, &self.uniforms_bind_group, &[]);
+                rpass.set_vertex_buffer(0, self.overlay_vertex_buffer.get_buffer().slice(..));
+                rpass.set_index_buffer(self.overlay_index_buffer.get_buffer().slice(..), Default::default());
+                rpass.draw_indexed(0..(self.overlay_index_buffer.len() as u32), 0, 0..1);
+            }
+            self.queue_text(overlay.text, data);
+            self.glyph_brush
+                .draw_queued(
+                    device,
+                    uploader.belt(),
+                    encoder,
+                    buffers.texture_buffer,
+                    data.physical_window_size.width,
+                    data.physical_window_size.height,
+                )
+                .expect("couldn't draw queued glyphs");
+        }
+    }
+
+    /// Queue the text primitives in the glyph brush
+    fn queue_text(&mut self, text: Vec<TextPrimitive>, data: &WindowData) {
+        for TextPrimitive {
+            x, y, w, h,
+            mut parts,
+            z,
+            center_horizontally, center_vertically,
+        } in text.into_iter()
+        {
+            let dpi = data.hidpi_factor as f32;
+
+            // Apply DPI to font size
+            for p in parts.iter_mut() {
+                p.font_size.x *= dpi;
+                p.font_size.y *= dpi;
+            }
+            // Get font IDs
+            let Self { ref fonts, .. } = &self;
+            let parts: Vec<wgpu_glyph::Text> = parts
+                .iter()
+                .map(|part| wgpu_glyph::Text::new(&part.text)
+                    .with_scale(part.font_size)
+                    .with_color(part.color)
+                    .with_font_id(part
+                        .font
+                        .clone()
+                        .and_then(|f| fonts.get(&f).cloned())
+                        .unwrap_or_default())
+                )
+                .collect();
+            // Calculate positions
+            let mut x = x as f32;
+            let mut y = y as f32;
+            let mut w = match w {
+                Some(w) => w as f32,
+                None => std::f32::INFINITY,
+            };
+            let mut h = match h {
+                Some(h) => h as f32,
+                None => std::f32::INFINITY,
+            };
+            if center_horizontally {
+                x += w/2.0;
+            }
+            if center_vertically {
+                y += h/2.0;
+            }
+            // Apply DPI to positions
+            x *= dpi;
+            y *= dpi;
+            w *= dpi;
+            h *= dpi;
+            let v_align = if center_vertically {
+                wgpu_glyph::VerticalAlign::Center
+            } else {
+                wgpu_glyph::VerticalAlign::Top
+            };
+            let h_align = if center_horizontally {
+                wgpu_glyph::HorizontalAlign::Center
+            } else {
+                wgpu_glyph::HorizontalAlign::Left
+            };
+            let section = wgpu_glyph::Section::default()
+                .with_screen_position((x, y))
+                .with_bounds((w, h))
+                .with_layout(wgpu_glyph::Layout::Wrap {
+                    line_breaker: Default::default(),
+                    v_align,
+                    h_align,
+                })
+                .with_text(parts);
+            self.glyph_brush.queue(section);
+        }
+    }
+}
+
+/// Add the vertices and the indices of the triangles of some rectangles
+fn push_rectangles(rectangles: Vec<RectanglePrimitive>, vertices: &mut Vec<UiVertex>, indices: &mut Vec<u32>) {
+    for RectanglePrimitive {
+        layout: l,
+        color,
+        z,
+    } in rectangles.into_iter()
+    {
+        let a = UiVertex {
+            position: [l.x, l.y, z],
+            color: color.clone(),
+        };
+        let b = UiVertex {
+            position: [l.x + l.width, l.y, z],
+            color: color.clone(),
+        };
+        let c = UiVertex {
+            position: [l.x, l.y + l.height, z],
+            color: color.clone(),
+        };
+        let d = UiVertex {
+            position: [l.x + l.width, l.y + l.height, z],
+            color: color.clone(),
+        };
+        let a_index = vertices.len() as u32;
+        let b_index = a_index + 1;
+        let c_index = b_index + 1;
+        let d_index = c_index + 1;
+        vertices.extend([a, b, c, d].iter());
+        indices.extend([b_index, a_index, c_index, b_index, c_index, d_index].iter());
     }
 }
 
@@ -392,3 +542,121 @@ const UI_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
         offset: 12,
     },
 ];
+
+#[derive(Debug, Clone, Copy)]
+struct IconVertex {
+    position: [f32; 3],
+    uv: [f32; 2],
+}
+
+const ICON_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+    wgpu::VertexAttribute {
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float32x3,
+        offset: 0,
+    },
+    wgpu::VertexAttribute {
+        shader_location: 1,
+        format: wgpu::VertexFormat::Float32x2,
+        offset: 12,
+    },
+];
+
+const ICON_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+    label: None,
+    entries: &[
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+    ],
+};
+
+/// Create a pipeline of the UI drawing the shaders `assets/shaders/<shaders>.vert` and `.frag`. The pipelines drawn
+/// in the multisampled pass of the UI use the depth buffer, and the others draw into the resolved frame buffer.
+fn create_ui_pipeline(
+    device: &wgpu::Device,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    shaders: &str,
+    vertex_size: usize,
+    vertex_attributes: &[wgpu::VertexAttribute],
+    multisampled: bool,
+) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, format!("assets/shaders/{}.vert", shaders));
+    let vertex_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&vertex_shader_bytes),
+    });
+    let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, format!("assets/shaders/{}.frag", shaders));
+    let fragment_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&fragment_shader_bytes),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    let cache = pipeline_cache();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(shaders),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vertex_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: vertex_size as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: vertex_attributes,
+            }],
+        },
+        primitive: Default::default(),
+        depth_stencil: if multisampled { Some(DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR) } else { None },
+        multisample: wgpu::MultisampleState {
+            count: if multisampled { compatibility::sample_count() } else { 1 },
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fragment_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            targets: &[Some(DEFAULT_COLOR_STATE_DESCRIPTOR[0].clone())],
+        }),
+        multiview: None,
+        cache: cache.as_deref(),
+    })
+}
+
+/// Create a render pass that draws into the resolved frame buffer, without depth buffer and without clearing
+fn create_overlay_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    target: &'a wgpu::TextureView,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("ui overlay"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
@@ -0,0 +1,134 @@
+//! Uploads of data to the GPU. The buffer, mesh, texture and text updates of a frame are all written into the chunks
+//! of one persistent staging belt, and copied by the command encoder of the frame. The chunks are reused once the GPU
+//! is done with them, instead of creating a new staging buffer for every upload.
+
+use common::debug::send_debug_info;
+use std::num::NonZeroU64;
+use wgpu::util::StagingBelt;
+
+/// Size of the chunks of the staging belt, in bytes. The larger uploads get a chunk of their own.
+const BELT_CHUNK_SIZE: u64 = 1 << 20;
+
+/// The uploader of a window state. `recall` must be called once the commands of the previous frame were submitted,
+/// before the first upload of the frame, and `finish` after the last upload, before the commands are submitted.
+pub struct Uploader {
+    belt: StagingBelt,
+    /// The buffer the textures are copied from, grown when a texture doesn't fit
+    texture_staging: Option<wgpu::Buffer>,
+    /// Number of uploads and bytes uploaded since the last recall
+    uploads: usize,
+    uploaded_bytes: u64,
+}
+
+impl Uploader {
+    pub fn new() -> Self {
+        Self {
+            belt: StagingBelt::new(BELT_CHUNK_SIZE),
+            texture_staging: None,
+            uploads: 0,
+            uploaded_bytes: 0,
+        }
+    }
+
+    /// Copy `data` into `target` at `offset`. The offset and the length of the data must be multiples of
+    /// `wgpu::COPY_BUFFER_ALIGNMENT`.
+    pub fn upload_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        offset: u64,
+        data: &[u8],
+    ) {
+        let size = match NonZeroU64::new(data.len() as u64) {
+            Some(size) => size,
+            None => return,
+        };
+        self.belt.write_buffer(encoder, target, offset, size, device).copy_from_slice(data);
+        self.uploads += 1;
+        self.uploaded_bytes += size.get();
+    }
+
+    /// Copy the RGBA8 pixels `data` of size `width`x`height` into level `mip_level` of array layer `layer` of
+    /// `texture`
+    pub fn upload_texture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        layer: u32,
+        mip_level: u32,
+        (width, height): (u32, u32),
+        data: &[u8],
+    ) {
+        // The rows of the copy must be aligned
+        let row_size = 4 * width as usize;
+        let bytes_per_row = wgpu::util::align_to(row_size, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+        let mut padded = Vec::with_capacity(bytes_per_row * height as usize);
+        for row in data.chunks(row_size) {
+            padded.extend_from_slice(row);
+            padded.resize(padded.len() + bytes_per_row - row_size, 0);
+        }
+        let size = padded.len() as u64;
+        if self.texture_staging.as_ref().map(|buffer| buffer.size() < size).unwrap_or(true) {
+            self.texture_staging = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("texture staging"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }));
+        }
+        let staging = self.texture_staging.as_ref().unwrap();
+        // The copies are executed in order, so the staging buffer can be reused by the next texture of the frame
+        self.belt
+            .write_buffer(encoder, staging, 0, NonZeroU64::new(size).unwrap(), device)
+            .copy_from_slice(&padded);
+        self.uploads += 1;
+        self.uploaded_bytes += size;
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row as u32),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: layer },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// The staging belt, for the libraries that upload their own data such as the glyph brushes
+    pub fn belt(&mut self) -> &mut StagingBelt {
+        &mut self.belt
+    }
+
+    /// Close the chunks written during the frame. Must be called before the commands are submitted.
+    pub fn finish(&mut self) {
+        self.belt.finish();
+        send_debug_info(
+            "Render",
+            "uploads",
+            format!("{} uploads, {} KiB uploaded", self.uploads, self.uploaded_bytes / 1024),
+        );
+    }
+
+    /// Reuse the chunks the GPU is done with. Must be called after the commands of the previous frame were submitted.
+    pub fn recall(&mut self, device: &wgpu::Device) {
+        self.belt.recall();
+        // The chunks come back when the device is polled
+        device.poll(wgpu::Maintain::Poll);
+        self.uploads = 0;
+        self.uploaded_bytes = 0;
+    }
+}
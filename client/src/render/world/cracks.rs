@@ -0,0 +1,89 @@
+//! The cracks of the blocks being mined, drawn with lines on the faces of the blocks. Each stage of the damage adds a
+//! few segments to the cracks, which spread from the middle of the faces.
+
+use super::super::buffers::DynamicBuffer;
+use super::super::Uploader;
+use super::debug::{create_line_pipeline, LineVertex};
+use crate::window::WindowBuffers;
+use common::world::BlockPos;
+
+/// Color of the cracks
+const CRACK_COLOR: [f32; 4] = [0.05, 0.04, 0.03, 1.0];
+/// Distance between the cracks and the face of the block, so that they are not hidden by the face
+const CRACK_OFFSET: f32 = 0.002;
+/// The segments added by each stage of the damage, in the coordinates of a face from 0 to 1
+const CRACK_SEGMENTS: [[[f32; 4]; 2]; 8] = [
+    [[0.5, 0.5, 0.38, 0.42], [0.5, 0.5, 0.6, 0.57]],
+    [[0.38, 0.42, 0.27, 0.45], [0.6, 0.57, 0.7, 0.55]],
+    [[0.5, 0.5, 0.53, 0.35], [0.27, 0.45, 0.2, 0.33]],
+    [[0.7, 0.55, 0.76, 0.68], [0.53, 0.35, 0.47, 0.22]],
+    [[0.2, 0.33, 0.08, 0.3], [0.76, 0.68, 0.9, 0.72]],
+    [[0.47, 0.22, 0.52, 0.06], [0.6, 0.57, 0.55, 0.75]],
+    [[0.55, 0.75, 0.6, 0.9], [0.27, 0.45, 0.24, 0.62]],
+    [[0.24, 0.62, 0.1, 0.7], [0.76, 0.68, 0.88, 0.9]],
+];
+
+/// Draws the cracks of the damaged blocks into the world buffers
+pub(super) struct BlockCracks {
+    pipeline: wgpu::RenderPipeline,
+    vertices: DynamicBuffer<LineVertex>,
+}
+
+impl BlockCracks {
+    /// The bind group must have the view-projection matrix at binding 0
+    pub fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        Self {
+            pipeline: create_line_pipeline(device, bind_group_layout),
+            // Room for a few blocks with all their cracks
+            vertices: DynamicBuffer::with_capacity(device, 4 * 6 * 4 * CRACK_SEGMENTS.len(), wgpu::BufferUsages::VERTEX),
+        }
+    }
+
+    /// Draw the cracks of the `damaged_blocks`, with the stage of their damage
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        buffers: WindowBuffers,
+        bind_group: &wgpu::BindGroup,
+        damaged_blocks: &[(BlockPos, u8)],
+    ) {
+        let mut vertices = Vec::new();
+        for &(pos, stage) in damaged_blocks {
+            for face in 0..6 {
+                push_face_cracks(&mut vertices, pos, face, stage);
+            }
+        }
+        if vertices.is_empty() {
+            return;
+        }
+
+        self.vertices.upload(device, encoder, uploader, &vertices);
+        let mut rpass = super::super::render::create_default_render_pass(encoder, buffers);
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertices.get_buffer().slice(..));
+        rpass.draw(0..self.vertices.len() as u32, 0..1);
+    }
+}
+
+/// Add the cracks of a face of a block, from 0 to 5: +x, -x, +y, -y, +z, -z
+fn push_face_cracks(vertices: &mut Vec<LineVertex>, pos: BlockPos, face: usize, stage: u8) {
+    let axis = face / 2;
+    let normal_coord = if face % 2 == 0 { 1.0 + CRACK_OFFSET } else { -CRACK_OFFSET };
+    let origin = [pos.px as f32, pos.py as f32, pos.pz as f32];
+    let vertex = |u: f32, v: f32| {
+        let mut position = origin;
+        position[axis] += normal_coord;
+        position[(axis + 1) % 3] += u;
+        position[(axis + 2) % 3] += v;
+        LineVertex { position, color: CRACK_COLOR }
+    };
+    for segments in CRACK_SEGMENTS.iter().take(stage as usize) {
+        for &[u1, v1, u2, v2] in segments {
+            vertices.push(vertex(u1, v1));
+            vertices.push(vertex(u2, v2));
+        }
+    }
+}
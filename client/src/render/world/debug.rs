@@ -0,0 +1,230 @@
+//! Debug render modes, to see what the meshing, the lighting and the physics are doing. The wireframe and the light
+//! heat map are drawn by the chunk shader, the boxes are drawn with lines on top of the world.
+
+use super::super::buffers::DynamicBuffer;
+use super::super::init::{load_glsl_shader, ShaderStage, DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR};
+use super::super::pipeline_cache::pipeline_cache;
+use super::super::postprocess::HDR_FORMAT;
+use super::super::Uploader;
+use crate::render::compatibility;
+use crate::window::WindowBuffers;
+use common::world::{BlockPos, CHUNK_SIZE};
+use nalgebra::Vector3;
+use wgpu::ShaderModuleDescriptor;
+
+/// The chunk borders are drawn around the chunks up to this distance from the chunk of the camera
+const CHUNK_BORDER_DISTANCE: i64 = 2;
+const CAMERA_CHUNK_COLOR: [f32; 4] = [1.0, 0.1, 0.1, 1.0];
+const CHUNK_BORDER_COLOR: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
+/// Color of the boxes of the entities
+pub const ENTITY_BOX_COLOR: [f32; 4] = [0.1, 1.0, 0.3, 1.0];
+/// Color of the borders of the claims where the player can build, and of the other claims
+pub const ALLOWED_CLAIM_COLOR: [f32; 4] = [0.2, 0.6, 1.0, 1.0];
+pub const PROTECTED_CLAIM_COLOR: [f32; 4] = [1.0, 0.3, 0.2, 1.0];
+
+/// The debug render modes, toggled with the `debugdraw` console command
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DebugRenderModes {
+    /// Draw the edges of the quads of the chunk meshes, and the edges of the blocks inside the quads
+    pub wireframe: bool,
+    /// Draw the borders of the chunks around the camera
+    pub chunk_borders: bool,
+    /// Draw the boxes of the players, the robots and the dropped items
+    pub entity_boxes: bool,
+    /// Replace the color of the chunks by the light level, from blue in the dark to red in full light
+    pub light_heat_map: bool,
+}
+
+impl DebugRenderModes {
+    /// The names of the modes, for the console
+    pub const NAMES: [&'static str; 4] = ["wireframe", "chunks", "boxes", "light"];
+
+    /// Toggle a mode from its name, returning whether it is now enabled, or `None` if the name is unknown
+    pub fn toggle(&mut self, name: &str) -> Option<bool> {
+        let mode = match name {
+            "wireframe" => &mut self.wireframe,
+            "chunks" => &mut self.chunk_borders,
+            "boxes" => &mut self.entity_boxes,
+            "light" => &mut self.light_heat_map,
+            _ => return None,
+        };
+        *mode = !*mode;
+        Some(*mode)
+    }
+
+    /// The modes drawn by the chunk shader, as the `u_debug` vector of the `World` uniform
+    pub(super) fn to_uniform(&self) -> [f32; 4] {
+        let as_float = |enabled: bool| if enabled { 1.0 } else { 0.0 };
+        [as_float(self.wireframe), as_float(self.light_heat_map), 0.0, 0.0]
+    }
+}
+
+/// A box drawn with lines, in world coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct DebugBox {
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+    pub color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(super) struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+const LINE_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+    wgpu::VertexAttribute {
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float32x3,
+        offset: 0,
+    },
+    wgpu::VertexAttribute {
+        shader_location: 1,
+        format: wgpu::VertexFormat::Float32x4,
+        offset: 4 * 3,
+    },
+];
+
+/// Draws the debug boxes into the world buffers
+pub(super) struct DebugLines {
+    pipeline: wgpu::RenderPipeline,
+    vertices: DynamicBuffer<LineVertex>,
+}
+
+impl DebugLines {
+    /// The bind group must have the view-projection matrix at binding 0
+    pub fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        Self {
+            pipeline: create_line_pipeline(device, bind_group_layout),
+            vertices: DynamicBuffer::with_capacity(device, 24 * 64, wgpu::BufferUsages::VERTEX),
+        }
+    }
+
+    /// Draw the chunk borders around the camera if they are enabled, and the `boxes`
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        buffers: WindowBuffers,
+        bind_group: &wgpu::BindGroup,
+        modes: &DebugRenderModes,
+        camera_position: Vector3<f64>,
+        boxes: &[DebugBox],
+    ) {
+        let mut vertices = Vec::new();
+        if modes.chunk_borders {
+            let camera_chunk = BlockPos::from(camera_position).containing_chunk_pos();
+            let size = CHUNK_SIZE as f64;
+            let d = CHUNK_BORDER_DISTANCE;
+            for dx in -d..=d {
+                for dy in -d..=d {
+                    for dz in -d..=d {
+                        let min = Vector3::new(
+                            (camera_chunk.px + dx) as f64,
+                            (camera_chunk.py + dy) as f64,
+                            (camera_chunk.pz + dz) as f64,
+                        ) * size;
+                        let color = if (dx, dy, dz) == (0, 0, 0) { CAMERA_CHUNK_COLOR } else { CHUNK_BORDER_COLOR };
+                        push_box_lines(&mut vertices, min, min + Vector3::new(size, size, size), color);
+                    }
+                }
+            }
+        }
+        for debug_box in boxes {
+            push_box_lines(&mut vertices, debug_box.min, debug_box.max, debug_box.color);
+        }
+        if vertices.is_empty() {
+            return;
+        }
+
+        self.vertices.upload(device, encoder, uploader, &vertices);
+        let mut rpass = super::super::render::create_default_render_pass(encoder, buffers);
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertices.get_buffer().slice(..));
+        rpass.draw(0..self.vertices.len() as u32, 0..1);
+    }
+}
+
+/// Add the 12 edges of a box
+fn push_box_lines(vertices: &mut Vec<LineVertex>, min: Vector3<f64>, max: Vector3<f64>, color: [f32; 4]) {
+    let corner = |i: usize| LineVertex {
+        position: [
+            (if i & 1 == 0 { min.x } else { max.x }) as f32,
+            (if i & 2 == 0 { min.y } else { max.y }) as f32,
+            (if i & 4 == 0 { min.z } else { max.z }) as f32,
+        ],
+        color,
+    };
+    // Each corner is linked to the corners that differ by one coordinate
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            if i & axis == 0 {
+                vertices.push(corner(i));
+                vertices.push(corner(i | axis));
+            }
+        }
+    }
+}
+
+/// Create the pipeline of the lines. They are hidden by the terrain, but don't hide what is drawn after them.
+pub(super) fn create_line_pipeline(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/debug_lines.vert");
+    let vertex_shader_module = device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&vertex_shader_bytes),
+    });
+    let fragment_shader_bytes = load_glsl_shader(ShaderStage::Fragment, "assets/shaders/debug_lines.frag");
+    let fragment_shader_module = device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&fragment_shader_bytes),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+    let cache = pipeline_cache();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("debug lines pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vertex_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<LineVertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &LINE_VERTEX_ATTRIBUTES,
+            }],
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            ..DEFAULT_DEPTH_STENCIL_STATE_DESCRIPTOR
+        }),
+        multisample: wgpu::MultisampleState {
+            count: compatibility::sample_count(),
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fragment_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+        cache: cache.as_deref(),
+    })
+}
@@ -0,0 +1,86 @@
+//! Drawing of the chunks with a single indirect multi-draw call. All the chunk meshes already live in the shared
+//! buffers of a `MultiBuffer`, so only the list of the draw arguments of the visible chunks has to be uploaded every
+//! frame. When the device doesn't support indirect multi-draw, the chunks are drawn one by one with the same arguments.
+
+use super::ChunkVertex;
+use crate::render::buffers::{DynamicBuffer, MultiBuffer};
+use crate::render::Uploader;
+use common::world::ChunkPos;
+use wgpu::util::DrawIndexedIndirectArgs;
+
+/// The draw calls of a set of chunks
+pub struct ChunkDraws {
+    buffer: DynamicBuffer<DrawIndexedIndirectArgs>,
+    draws: Vec<DrawIndexedIndirectArgs>,
+    /// True if the device supports indirect multi-draw
+    multi_draw: bool,
+}
+
+impl ChunkDraws {
+    /// Return the device features required to draw the chunks with a single call. The chunk origins are passed as
+    /// instance data, hence the need for a non-zero first instance.
+    pub fn required_features() -> wgpu::Features {
+        wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE
+    }
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            buffer: DynamicBuffer::with_capacity(device, 1000, wgpu::BufferUsages::INDIRECT),
+            draws: Vec::new(),
+            multi_draw: device.features().contains(Self::required_features()),
+        }
+    }
+
+    /// Replace the draw calls by the ones of `chunks`. The instance of each chunk is its index in `all_chunks`.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        vertex_buffers: &MultiBuffer<ChunkPos, ChunkVertex>,
+        index_buffers: &MultiBuffer<ChunkPos, u32>,
+        all_chunks: &[ChunkPos],
+        mut is_visible: impl FnMut(ChunkPos) -> bool,
+    ) {
+        self.draws.clear();
+        for (instance, &chunk_pos) in all_chunks.iter().enumerate() {
+            if is_visible(chunk_pos) {
+                let (index_pos, index_len) = index_buffers.get_pos_len(&chunk_pos).unwrap();
+                let (vertex_pos, _) = vertex_buffers.get_pos_len(&chunk_pos).unwrap();
+                self.draws.push(DrawIndexedIndirectArgs {
+                    index_count: index_len as u32,
+                    instance_count: 1,
+                    first_index: index_pos as u32,
+                    base_vertex: vertex_pos as i32,
+                    first_instance: instance as u32,
+                });
+            }
+        }
+        if self.multi_draw {
+            self.buffer.upload(device, encoder, uploader, &self.draws);
+        }
+    }
+
+    /// Number of chunks to draw
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    /// Draw the chunks. The chunk buffers must already be bound.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        if self.draws.is_empty() {
+            return;
+        }
+        if self.multi_draw {
+            rpass.multi_draw_indexed_indirect(self.buffer.get_buffer(), 0, self.draws.len() as u32);
+        } else {
+            for draw in self.draws.iter() {
+                rpass.draw_indexed(
+                    draw.first_index..(draw.first_index + draw.index_count),
+                    draw.base_vertex,
+                    draw.first_instance..(draw.first_instance + draw.instance_count),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Smooth lighting of the chunks with light volumes. A compute pass averages the light levels and the opaque blocks
+//! around each corner of the blocks of a chunk into a 3D texture, that the chunk shader samples with linear filtering.
+//! The light of a chunk can then change without meshing it again.
+//!
+//! The volumes of all the chunks are slots of a single 3D texture. The chunks without a slot, because the light
+//! volumes are disabled, the texture is full or the volume was not computed yet, keep the light of their vertices.
+
+use crate::render::compatibility;
+use crate::render::init::{load_glsl_shader, ShaderStage};
+use crate::render::pipeline_cache::pipeline_cache;
+use crate::render::{to_u8_slice, Uploader};
+use common::world::{ChunkPos, CHUNK_SIZE};
+use std::collections::HashMap;
+use wgpu::ShaderModuleDescriptor;
+
+/// Number of corners of the blocks of a chunk along each axis
+const VOLUME_SIZE: u32 = CHUNK_SIZE + 1;
+/// Number of slots of the volume texture along each axis
+const SLOTS: [u32; 3] = [8, 8, 8];
+/// The maximum number of volumes computed in a frame. The other ones wait for the next frames.
+const MAX_VOLUMES_PER_FRAME: usize = 16;
+/// Number of words of the blocks of a chunk and of its border, two blocks per word
+const BLOCKS_WORDS: usize = ((CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) / 2) as usize;
+/// Size of the work groups of the compute shader along each axis
+const WORKGROUP_SIZE: u32 = 4;
+
+/// The light volumes of the chunks
+pub struct LightVolumes {
+    texture_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    /// The compute pass, `None` if the light volumes are disabled
+    compute: Option<VolumeCompute>,
+    slots: HashMap<ChunkPos, u32>,
+    free_slots: Vec<u32>,
+    /// The packed blocks of the volumes to compute
+    pending: HashMap<ChunkPos, Vec<u32>>,
+}
+
+struct VolumeCompute {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    blocks_buffer: wgpu::Buffer,
+    /// The texel origin of the slot of each volume of the batch
+    jobs_buffer: wgpu::Buffer,
+}
+
+impl LightVolumes {
+    pub fn new(device: &wgpu::Device, enabled: bool) -> Self {
+        let enabled = enabled && !compatibility::is_enabled();
+        // A single texel is enough to fill the binding of the chunk shader when the volumes are disabled
+        let size = if enabled {
+            wgpu::Extent3d {
+                width: SLOTS[0] * VOLUME_SIZE,
+                height: SLOTS[1] * VOLUME_SIZE,
+                depth_or_array_layers: SLOTS[2] * VOLUME_SIZE,
+            }
+        } else {
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            }
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("light volumes"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("light volumes"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let compute = if enabled {
+            Some(VolumeCompute::new(device, &texture_view))
+        } else {
+            None
+        };
+        let slot_count = if enabled { SLOTS[0] * SLOTS[1] * SLOTS[2] } else { 0 };
+
+        Self {
+            texture_view,
+            sampler,
+            compute,
+            slots: HashMap::new(),
+            // The first slots are used first
+            free_slots: (0..slot_count).rev().collect(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.compute.is_some()
+    }
+
+    pub fn texture_view(&self) -> &wgpu::TextureView {
+        &self.texture_view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// The slot of the volume of a chunk, or -1 if the chunk uses the light of its vertices
+    pub fn get_slot(&self, pos: ChunkPos) -> i32 {
+        self.slots.get(&pos).map(|&slot| slot as i32).unwrap_or(-1)
+    }
+
+    /// Whether the light of a chunk comes from its volume
+    pub fn has_volume(&self, pos: ChunkPos) -> bool {
+        self.slots.contains_key(&pos)
+    }
+
+    /// Compute the volume of a chunk from its packed blocks, during one of the next frames
+    pub fn update(&mut self, pos: ChunkPos, blocks: Vec<u32>) {
+        if self.is_enabled() {
+            self.pending.insert(pos, blocks);
+        }
+    }
+
+    /// Free the slot of a chunk
+    pub fn remove(&mut self, pos: ChunkPos) {
+        self.pending.remove(&pos);
+        if let Some(slot) = self.slots.remove(&pos) {
+            self.free_slots.push(slot);
+        }
+    }
+
+    /// Compute some of the pending volumes. Must be called before the chunk origins are uploaded, so that the chunks
+    /// only use the slots that were computed.
+    pub fn compute(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, uploader: &mut Uploader) {
+        let compute = match &self.compute {
+            Some(compute) => compute,
+            None => return,
+        };
+        let batch = self.pending.keys().copied().take(MAX_VOLUMES_PER_FRAME).collect::<Vec<_>>();
+        let mut jobs: Vec<[i32; 4]> = Vec::with_capacity(batch.len());
+        for pos in batch {
+            let blocks = self.pending.remove(&pos).unwrap();
+            let slot = match self.slots.get(&pos) {
+                Some(&slot) => slot,
+                None => match self.free_slots.pop() {
+                    Some(slot) => slot,
+                    // The texture is full, the chunk keeps the light of its vertices
+                    None => continue,
+                },
+            };
+            self.slots.insert(pos, slot);
+            let offset = (jobs.len() * BLOCKS_WORDS * std::mem::size_of::<u32>()) as u64;
+            uploader.upload_buffer(device, encoder, &compute.blocks_buffer, offset, to_u8_slice(&blocks));
+            jobs.push(slot_origin(slot));
+        }
+        if jobs.is_empty() {
+            return;
+        }
+        uploader.upload_buffer(device, encoder, &compute.jobs_buffer, 0, to_u8_slice(&jobs));
+
+        let groups = (VOLUME_SIZE + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("light volumes"),
+            timestamp_writes: None,
+        });
+        cpass.set_pipeline(&compute.pipeline);
+        cpass.set_bind_group(0, &compute.bind_group, &[]);
+        // The work groups of the volumes are stacked along z
+        cpass.dispatch_workgroups(groups, groups, groups * jobs.len() as u32);
+    }
+
+    /// Number of chunks with a volume, and number of volumes waiting to be computed
+    pub fn stats(&self) -> (usize, usize) {
+        (self.slots.len(), self.pending.len())
+    }
+}
+
+impl VolumeCompute {
+    fn new(device: &wgpu::Device, texture_view: &wgpu::TextureView) -> Self {
+        let create_storage_buffer = |size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                mapped_at_creation: false,
+                label: None,
+                size: size as u64,
+                usage: (wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST),
+            })
+        };
+        let blocks_buffer = create_storage_buffer(MAX_VOLUMES_PER_FRAME * BLOCKS_WORDS * std::mem::size_of::<u32>());
+        let jobs_buffer = create_storage_buffer(MAX_VOLUMES_PER_FRAME * std::mem::size_of::<[i32; 4]>());
+
+        let bind_group_layout = device.create_bind_group_layout(&VOLUME_BIND_GROUP_LAYOUT);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(blocks_buffer.as_entire_buffer_binding()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(jobs_buffer.as_entire_buffer_binding()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(texture_view),
+                },
+            ],
+        });
+
+        let shader_bytes = load_glsl_shader(ShaderStage::Compute, "assets/shaders/light_volume.comp");
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::util::make_spirv(&shader_bytes),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let cache = pipeline_cache();
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("light volumes"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            blocks_buffer,
+            jobs_buffer,
+        }
+    }
+}
+
+/// The first texel of a slot. The chunk shader finds the same texel from the slot.
+fn slot_origin(slot: u32) -> [i32; 4] {
+    let x = slot % SLOTS[0];
+    let y = slot / SLOTS[0] % SLOTS[1];
+    let z = slot / (SLOTS[0] * SLOTS[1]);
+    [(x * VOLUME_SIZE) as i32, (y * VOLUME_SIZE) as i32, (z * VOLUME_SIZE) as i32, 0]
+}
+
+const VOLUME_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+    label: None,
+    entries: &[
+        wgpu::BindGroupLayoutEntry {
+            // packed blocks
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            // slot origins
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            // volumes
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: None,
+        },
+    ],
+};
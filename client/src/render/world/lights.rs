@@ -0,0 +1,192 @@
+//! Dynamic point lights and spot lights, with clustered forward shading. The view frustum is split into a grid of
+//! clusters, and the list of the lights that reach each cluster is built on the CPU every frame. The chunk shader then
+//! only loops over the lights of the cluster of each fragment.
+//!
+//! The lights don't cast shadows: they are added on top of the light levels computed by the server. The spot lights are
+//! assigned to the clusters like point lights, and the shader only lights the fragments inside their cone.
+
+use crate::render::frustum::FOV;
+use crate::render::Frustum;
+use nalgebra::{Vector3, Vector4};
+
+/// Number of clusters along the width of the screen
+pub const CLUSTERS_X: usize = 16;
+/// Number of clusters along the height of the screen
+pub const CLUSTERS_Y: usize = 9;
+/// Number of depth slices of the clusters. The slices get exponentially thicker with the distance.
+pub const CLUSTERS_Z: usize = 24;
+pub const CLUSTER_COUNT: usize = CLUSTERS_X * CLUSTERS_Y * CLUSTERS_Z;
+/// Depth where the first slice ends. The first slice starts at the camera.
+pub const CLUSTER_NEAR: f64 = 1.0;
+/// Depth where the last slice ends. The lights further away are ignored.
+pub const CLUSTER_FAR: f64 = 256.0;
+/// The maximum number of lights in a frame. The closest lights to the camera are kept.
+pub const MAX_LIGHTS: usize = 256;
+/// The maximum total length of the light lists of the clusters
+pub const MAX_LIGHT_INDICES: usize = 32 * 1024;
+
+/// A light lighting the blocks in a sphere around it, or in a cone for a spot light
+#[derive(Debug, Clone, Copy)]
+pub struct PointLight {
+    pub position: Vector3<f64>,
+    pub color: [f32; 3],
+    /// Multiplier of the color at the center of the light
+    pub intensity: f32,
+    /// The distance where the light fades to nothing, in blocks
+    pub radius: f32,
+    /// The cone lit by a spot light, or None for a light in every direction
+    pub spot: Option<Spot>,
+}
+
+/// The cone lit by a spot light
+#[derive(Debug, Clone, Copy)]
+pub struct Spot {
+    /// Direction of the axis of the cone, normalized
+    pub direction: Vector3<f64>,
+    /// Cosine of the angle between the axis and the side of the cone
+    pub cos_angle: f32,
+}
+
+/// The lights and the light lists of the clusters, in the layout of the storage buffers of the chunk shader
+pub struct LightClusters {
+    /// Position and radius, then color and intensity, then direction and cosine of the cone angle of each light.
+    /// The cosine is below -1 for the point lights.
+    pub lights: Vec<[f32; 12]>,
+    /// Offset in `light_indices` and number of lights of each cluster
+    pub clusters: Vec<[u32; 2]>,
+    pub light_indices: Vec<u32>,
+}
+
+impl LightClusters {
+    /// Assign the lights to the clusters of the frustum
+    pub fn build(frustum: &Frustum, aspect_ratio: f64, lights: &[PointLight]) -> Self {
+        let camera = frustum.position;
+        let mut lights = lights
+            .iter()
+            .filter(|light| (light.position - camera).norm() < CLUSTER_FAR + light.radius as f64)
+            .copied()
+            .collect::<Vec<_>>();
+        if lights.len() > MAX_LIGHTS {
+            lights.sort_by(|a, b| {
+                let distance_a = (a.position - camera).norm_squared();
+                let distance_b = (b.position - camera).norm_squared();
+                distance_a.partial_cmp(&distance_b).unwrap()
+            });
+            lights.truncate(MAX_LIGHTS);
+        }
+
+        // The lists of the clusters, in view space where the camera looks towards -z
+        let view_matrix = frustum.get_view_matrix();
+        let (tan_y, tan_x) = {
+            let tan_y = (FOV / 2.0).tan();
+            (tan_y, tan_y * aspect_ratio)
+        };
+        let mut cluster_lights = vec![Vec::new(); CLUSTER_COUNT];
+        for (light_index, light) in lights.iter().enumerate() {
+            let center = (view_matrix * Vector4::new(light.position.x, light.position.y, light.position.z, 1.0)).xyz();
+            let radius = light.radius as f64;
+            let depth = -center.z;
+            if depth + radius < 0.0 {
+                continue;
+            }
+            let first_slice = depth_slice(depth - radius);
+            let last_slice = depth_slice(depth + radius);
+            for z in first_slice..=last_slice {
+                let (near, far) = (slice_start(z), slice_start(z + 1));
+                for y in 0..CLUSTERS_Y {
+                    // The rows go from the top of the screen to the bottom
+                    let top = tan_y * (1.0 - 2.0 * y as f64 / CLUSTERS_Y as f64);
+                    let bottom = tan_y * (1.0 - 2.0 * (y + 1) as f64 / CLUSTERS_Y as f64);
+                    let (min_y, max_y) = extent(bottom, top, near, far);
+                    if center.y + radius < min_y || center.y - radius > max_y {
+                        continue;
+                    }
+                    for x in 0..CLUSTERS_X {
+                        let left = tan_x * (2.0 * x as f64 / CLUSTERS_X as f64 - 1.0);
+                        let right = tan_x * (2.0 * (x + 1) as f64 / CLUSTERS_X as f64 - 1.0);
+                        let (min_x, max_x) = extent(left, right, near, far);
+                        let closest = Vector3::new(
+                            center.x.max(min_x).min(max_x),
+                            center.y.max(min_y).min(max_y),
+                            center.z.max(-far).min(-near),
+                        );
+                        if (closest - center).norm_squared() <= radius * radius {
+                            cluster_lights[cluster_index(x, y, z)].push(light_index as u32);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut clusters = Vec::with_capacity(CLUSTER_COUNT);
+        let mut light_indices = Vec::new();
+        for list in cluster_lights {
+            let count = list.len().min(MAX_LIGHT_INDICES - light_indices.len());
+            clusters.push([light_indices.len() as u32, count as u32]);
+            light_indices.extend_from_slice(&list[..count]);
+        }
+        if light_indices.len() == MAX_LIGHT_INDICES {
+            log::warn!("Too many lights in the clusters, some lights were ignored");
+        }
+
+        Self {
+            lights: lights
+                .iter()
+                .map(|light| {
+                    let (direction, cos_angle) = match light.spot {
+                        Some(spot) => (spot.direction.cast::<f32>(), spot.cos_angle),
+                        None => (Vector3::zeros(), -2.0),
+                    };
+                    [
+                        light.position.x as f32,
+                        light.position.y as f32,
+                        light.position.z as f32,
+                        light.radius,
+                        light.color[0],
+                        light.color[1],
+                        light.color[2],
+                        light.intensity,
+                        direction.x,
+                        direction.y,
+                        direction.z,
+                        cos_angle,
+                    ]
+                })
+                .collect(),
+            clusters,
+            light_indices,
+        }
+    }
+}
+
+/// The index of the cluster, in the order of the shader
+fn cluster_index(x: usize, y: usize, z: usize) -> usize {
+    (z * CLUSTERS_Y + y) * CLUSTERS_X + x
+}
+
+/// The depth where a slice starts. The first slice starts at the camera.
+fn slice_start(slice: usize) -> f64 {
+    if slice == 0 {
+        0.0
+    } else {
+        CLUSTER_NEAR * (CLUSTER_FAR / CLUSTER_NEAR).powf((slice - 1) as f64 / (CLUSTERS_Z - 1) as f64)
+    }
+}
+
+/// The slice containing a depth. The shader must compute the same slices.
+fn depth_slice(depth: f64) -> usize {
+    if depth < CLUSTER_NEAR {
+        0
+    } else {
+        let slice = 1.0 + (depth / CLUSTER_NEAR).ln() / (CLUSTER_FAR / CLUSTER_NEAR).ln() * (CLUSTERS_Z - 1) as f64;
+        (slice as usize).min(CLUSTERS_Z - 1)
+    }
+}
+
+/// The extent along an axis of the part of a tile between the depths `near` and `far`, given the slopes of its sides
+fn extent(slope_min: f64, slope_max: f64, near: f64, far: f64) -> (f64, f64) {
+    let values = [slope_min * near, slope_min * far, slope_max * near, slope_max * far];
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    (min, max)
+}
@@ -59,74 +59,60 @@ pub struct ChunkMeshData {
     pub light_chunk: Arc<LightChunk>,
     /// The light chunks that are adjacent to the current light chunk
     pub all_light_chunks: [Option<Arc<LightChunk>>; 27],
+    /// False if only the light changed and the mesh can be kept
+    pub mesh: bool,
+    /// Whether to gather the blocks of the light volume of the chunk
+    pub light_volume: bool,
+    /// Whether the chunk is the lowest of the world: the blocks below it are solid, and its bottom faces are hidden
+    pub bottom_of_world: bool,
 }
 
-/// Greedy meshing : compressed adjacent quads, return the number of uncompressed and compressed quads
-///
-/// `quads`: Buffer that is reused every time.
-pub fn greedy_meshing(
-    chunk_data: ChunkMeshData,
-    meshes: &Vec<BlockMesh>,
-    quads: &mut Vec<Quad>,
-) -> (Vec<ChunkVertex>, Vec<u32>, u32, u32) {
-    let chunk_pos = chunk_data.chunk.pos;
-    let offset_x = chunk_pos.px as f32 * CHUNK_SIZE as f32;
-    let offset_y = chunk_pos.py as f32 * CHUNK_SIZE as f32;
-    let offset_z = chunk_pos.pz as f32 * CHUNK_SIZE as f32;
-
-    let mut res_vertex: Vec<ChunkVertex> = Vec::new();
-    let mut res_index: Vec<usize> = Vec::new();
-
-    let mut tot_quad = 0;
-    let mut act_quad = 0;
+/// Size of the chunk and its border of adjacent blocks, along each axis
+const N_SIZE: usize = (CHUNK_SIZE + 2) as usize;
 
-    let mut n_of_different_vertex = 0;
-
-    const N_SIZE: usize = (CHUNK_SIZE + 2) as usize;
-    let mut chunk_mask = [false; N_SIZE * N_SIZE * N_SIZE];
-    let mut light_levels = [15; N_SIZE * N_SIZE * N_SIZE];
-
-    #[inline(always)]
-    fn ind(x: i32, y: i32, z: i32) -> usize {
-        let (a, b, c) = (x as usize, y as usize, z as usize);
-        uind(a, b, c)
-    }
-
-    #[inline(always)]
-    fn uind(a: usize, b: usize, c: usize) -> usize {
-        (a * N_SIZE * N_SIZE + b * N_SIZE + c) as usize
-    }
+#[inline(always)]
+fn uind(a: usize, b: usize, c: usize) -> usize {
+    (a * N_SIZE * N_SIZE + b * N_SIZE + c) as usize
+}
 
+#[inline(always)]
+fn chunk_index(x: usize, y: usize, z: usize) -> usize {
     #[inline(always)]
-    fn chunk_index(x: usize, y: usize, z: usize) -> usize {
-        #[inline(always)]
-        fn f(x: usize) -> usize {
-            if x == 0 {
-                0
-            } else if x == N_SIZE - 1 {
-                2
-            } else {
-                1
-            }
+    fn f(x: usize) -> usize {
+        if x == 0 {
+            0
+        } else if x == N_SIZE - 1 {
+            2
+        } else {
+            1
         }
-        9 * f(x) + 3 * f(y) + f(z)
     }
+    9 * f(x) + 3 * f(y) + f(z)
+}
 
+#[inline(always)]
+fn outside_position(x: usize, y: usize, z: usize) -> (u32, u32, u32) {
     #[inline(always)]
-    fn outside_position(x: usize, y: usize, z: usize) -> (u32, u32, u32) {
-        #[inline(always)]
-        fn f(x: usize) -> u32 {
-            if x == 0 {
-                CHUNK_SIZE - 1
-            } else if x == N_SIZE - 1 {
-                0
-            } else {
-                x as u32 - 1
-            }
+    fn f(x: usize) -> u32 {
+        if x == 0 {
+            CHUNK_SIZE - 1
+        } else if x == N_SIZE - 1 {
+            0
+        } else {
+            x as u32 - 1
         }
-        (f(x), f(y), f(z))
     }
+    (f(x), f(y), f(z))
+}
 
+/// Fill whether each block of the chunk and of its border is opaque, and the light level of each block.
+/// Return the number of opaque blocks in the chunk.
+fn gather_blocks(
+    chunk_data: &ChunkMeshData,
+    meshes: &Vec<BlockMesh>,
+    chunk_mask: &mut [bool; N_SIZE * N_SIZE * N_SIZE],
+    light_levels: &mut [u8; N_SIZE * N_SIZE * N_SIZE],
+) -> u32 {
     // TODO: for light, we don't need the 8 corners
 
     let mut opaque_blocks_count = 0;
@@ -163,6 +149,8 @@ pub fn greedy_meshing(
                         if let Some(c) = &chunk_data.all_chunks[ci] {
                             *chunk_mask.get_unchecked_mut(uind(i, j, k)) =
                                 (*meshes.get_unchecked(c.get_block_at_unsafe(outside_position(i, j, k)) as usize)).is_opaque();
+                        } else if j == 0 && chunk_data.bottom_of_world {
+                            *chunk_mask.get_unchecked_mut(uind(i, j, k)) = true;
                         }
                         if let Some(lc) = &chunk_data.all_light_chunks[ci] {
                             *light_levels.get_unchecked_mut(uind(i, j, k)) = lc.get_light_at_unsafe(outside_position(i, j, k));
@@ -172,6 +160,48 @@ pub fn greedy_meshing(
             }
         }
     }
+    opaque_blocks_count
+}
+
+/// The blocks of the chunk and of its border, packed for the light volume compute shader: two blocks per word, each
+/// with the light level in the low byte and 1 in the next bit if the block is opaque
+pub fn light_volume_blocks(chunk_data: &ChunkMeshData, meshes: &Vec<BlockMesh>) -> Vec<u32> {
+    let mut chunk_mask = [false; N_SIZE * N_SIZE * N_SIZE];
+    let mut light_levels = [15; N_SIZE * N_SIZE * N_SIZE];
+    gather_blocks(chunk_data, meshes, &mut chunk_mask, &mut light_levels);
+    let mut blocks = vec![0; N_SIZE * N_SIZE * N_SIZE / 2];
+    for (i, (&opaque, &light)) in chunk_mask.iter().zip(light_levels.iter()).enumerate() {
+        let block = light as u32 | ((opaque as u32) << 8);
+        blocks[i / 2] |= block << (16 * (i % 2));
+    }
+    blocks
+}
+
+/// Greedy meshing : compressed adjacent quads, return the number of uncompressed and compressed quads
+///
+/// `quads`: Buffer that is reused every time.
+pub fn greedy_meshing(
+    chunk_data: ChunkMeshData,
+    meshes: &Vec<BlockMesh>,
+    quads: &mut Vec<Quad>,
+) -> (Vec<ChunkVertex>, Vec<u32>, u32, u32) {
+    let mut res_vertex: Vec<ChunkVertex> = Vec::new();
+    let mut res_index: Vec<usize> = Vec::new();
+
+    let mut tot_quad = 0;
+    let mut act_quad = 0;
+
+    let mut n_of_different_vertex = 0;
+
+    let mut chunk_mask = [false; N_SIZE * N_SIZE * N_SIZE];
+    let mut light_levels = [15; N_SIZE * N_SIZE * N_SIZE];
+    let opaque_blocks_count = gather_blocks(&chunk_data, meshes, &mut chunk_mask, &mut light_levels);
+
+    #[inline(always)]
+    fn ind(x: i32, y: i32, z: i32) -> usize {
+        let (a, b, c) = (x as usize, y as usize, z as usize);
+        uind(a, b, c)
+    }
 
 
     const D_DELTA0: [[i32; 3]; 6] = [
@@ -324,13 +354,13 @@ pub fn greedy_meshing(
         [1, 3, 0, 2, 0, 3],
     ];
 
-    let uvs = [
-        [[1.0, 1.0], [0.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
-        [[0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 0.0]],
-        [[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]],
-        [[1.0, 0.0], [1.0, 1.0], [0.0, 0.0], [0.0, 1.0]],
-        [[0.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]],
-        [[1.0, 1.0], [1.0, 0.0], [0.0, 1.0], [0.0, 0.0]],
+    let uvs: [[[u8; 2]; 4]; 6] = [
+        [[1, 1], [0, 1], [1, 0], [0, 0]],
+        [[0, 1], [1, 1], [0, 0], [1, 0]],
+        [[0, 0], [0, 1], [1, 0], [1, 1]],
+        [[1, 0], [1, 1], [0, 0], [0, 1]],
+        [[0, 1], [0, 0], [1, 1], [1, 0]],
+        [[1, 1], [1, 0], [0, 1], [0, 0]],
     ];
     let uv_directions = [[1, 0], [1, 0], [0, 1], [0, 1], [0, 1], [0, 1]];
 
@@ -464,9 +494,9 @@ pub fn greedy_meshing(
                             let (px3, py3, pz3) = ijk_to_pos(s, i, j_end, k);
                             let (px4, py4, pz4) = ijk_to_pos(s, i, j_end, k_end);
 
-                            let mut px_ = [px as f32, px2 as f32, px3 as f32, px4 as f32];
-                            let mut py_ = [py as f32, py2 as f32, py3 as f32, py4 as f32];
-                            let mut pz_ = [pz as f32, pz2 as f32, pz3 as f32, pz4 as f32];
+                            let mut px_ = [px as u16, px2 as u16, px3 as u16, px4 as u16];
+                            let mut py_ = [py as u16, py2 as u16, py3 as u16, py4 as u16];
+                            let mut pz_ = [pz as u16, pz2 as u16, pz3 as u16, pz4 as u16];
                             let v = [
                                 current_quad.v1,
                                 current_quad.v2,
@@ -477,60 +507,46 @@ pub fn greedy_meshing(
                             if s == 0 {
                                 // 1x
                                 for kk in 0..4 {
-                                    px_[kk] = px_[kk] + 1.0;
+                                    px_[kk] = px_[kk] + 1;
                                 }
                             } else if s == 2 {
                                 // 1y
                                 for kk in 0..4 {
-                                    py_[kk] = py_[kk] + 1.0;
+                                    py_[kk] = py_[kk] + 1;
                                 }
                             } else if s == 4 {
                                 // 1z
                                 for kk in 0..4 {
-                                    pz_[kk] = pz_[kk] + 1.0;
+                                    pz_[kk] = pz_[kk] + 1;
                                 }
                             }
 
-                            let uv = match meshes[current_quad.block_id as usize] {
+                            let (uv, emissive) = match meshes[current_quad.block_id as usize] {
                                 BlockMesh::Empty => continue,
-                                BlockMesh::FullCube { texture } => texture[s],
+                                BlockMesh::FullCube { texture, emissive } => (texture[s], emissive as u8),
                             };
 
-                            let texture_top_left = [uv.x, uv.y];
-                            let texture_size = [uv.width, uv.height];
-                            let uv_factors = [(j_end - j) as f32, (k_end - k) as f32];
+                            let texture_size = [to_unorm16(uv.width), to_unorm16(uv.height)];
+                            let uv_factors = [(j_end - j) as u8, (k_end - k) as u8];
                             let uv_factors = [
                                 uv_factors[uv_directions[s][0]],
                                 uv_factors[uv_directions[s][1]],
                             ];
-                            let uvs = [
-                                [
-                                    uvs[s][0][0] * uv.width * uv_factors[0],
-                                    uvs[s][0][1] * uv.height * uv_factors[1],
-                                ],
-                                [
-                                    uvs[s][1][0] * uv.width * uv_factors[0],
-                                    uvs[s][1][1] * uv.height * uv_factors[1],
-                                ],
-                                [
-                                    uvs[s][2][0] * uv.width * uv_factors[0],
-                                    uvs[s][2][1] * uv.height * uv_factors[1],
-                                ],
-                                [
-                                    uvs[s][3][0] * uv.width * uv_factors[0],
-                                    uvs[s][3][1] * uv.height * uv_factors[1],
-                                ],
-                            ];
-                            let texture_max_uv = [uv.width * uv_factors[0], uv.height * uv_factors[1]];
 
                             for kk in 0..4 {
                                 res_vertex.push(ChunkVertex {
-                                    pos: [px_[kk] + offset_x, py_[kk] + offset_y, pz_[kk] + offset_z],
-                                    texture_top_left,
-                                    texture_uv: uvs[kk],
-                                    texture_max_uv,
+                                    pos: [px_[kk], py_[kk], pz_[kk]],
+                                    face: (v[kk] & 0x7) as u8,
+                                    occl: ((v[kk] >> 3) & 0x3) as u8,
+                                    light: [((v[kk] >> 5) & 0xF) as u8, ((v[kk] >> 9) & 0xF) as u8, emissive, 0],
+                                    texture_layer: uv.layer,
                                     texture_size,
-                                    occl_and_face: v[kk],
+                                    texture_repeats: [
+                                        uvs[s][kk][0] * uv_factors[0],
+                                        uvs[s][kk][1] * uv_factors[1],
+                                        uv_factors[0],
+                                        uv_factors[1],
+                                    ],
                                 });
                             }
 
@@ -560,3 +576,8 @@ pub fn greedy_meshing(
     let res_index: Vec<u32> = res_index.iter().map(|x| *x as u32).collect();
     (res_vertex, res_index, tot_quad, act_quad)
 }
+
+/// Convert a texture size relative to its layer, between 0 and 1, to a 16-bit normalized value
+fn to_unorm16(x: f32) -> u16 {
+    (x.max(0.0).min(1.0) * u16::MAX as f32).round() as u16
+}
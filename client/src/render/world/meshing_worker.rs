@@ -1,19 +1,26 @@
 //! Meshing worker, allowing meshing to be performed in a separate thread
-use super::meshing::{greedy_meshing, ChunkMeshData};
+use super::meshing::{greedy_meshing, light_volume_blocks, ChunkMeshData};
 use crate::render::world::ChunkVertex;
 use common::block::BlockMesh;
+use common::profile_scope;
 use common::world::ChunkPos;
 use common::worker::{WorkerState, Worker};
 
-pub type ChunkMesh = (ChunkPos, Vec<ChunkVertex>, Vec<u32>);
+/// The result of the meshing of a chunk
+pub struct ChunkMesh {
+    pub pos: ChunkPos,
+    /// The vertices and the indices, or `None` if only the light changed
+    pub mesh: Option<(Vec<ChunkVertex>, Vec<u32>)>,
+    /// The blocks of the light volume, if the light volumes are enabled
+    pub light_volume: Option<Vec<u32>>,
+}
+
 pub type MeshingWorker = Worker<ChunkMeshData, ChunkMesh, MeshingState>;
 
-pub fn start_meshing_worker(block_meshes: Vec<BlockMesh>) -> MeshingWorker {
-    MeshingWorker::new(
-        MeshingState::new(block_meshes),
-        WORKER_CHANNEL_SIZE,
-        "Meshing".to_owned(),
-    )
+/// Start a meshing worker with `threads` threads
+pub fn start_meshing_worker(block_meshes: Vec<BlockMesh>, threads: usize) -> MeshingWorker {
+    let states = (0..threads.max(1)).map(|_| MeshingState::new(block_meshes.clone())).collect();
+    MeshingWorker::with_states(states, WORKER_CHANNEL_SIZE, "Meshing".to_owned())
 }
 
 pub struct MeshingState {
@@ -32,9 +39,20 @@ impl MeshingState {
 
 impl WorkerState<ChunkMeshData, ChunkMesh> for MeshingState {
     fn compute(&mut self, input: ChunkMeshData) -> ChunkMesh {
+        profile_scope!("meshing");
         let pos = input.chunk.pos;
-        let (vertices, indices, _, _) = greedy_meshing(input, &self.block_meshes, &mut self.quads_reuse);
-        (pos, vertices, indices)
+        let light_volume = if input.light_volume {
+            Some(light_volume_blocks(&input, &self.block_meshes))
+        } else {
+            None
+        };
+        let mesh = if input.mesh {
+            let (vertices, indices, _, _) = greedy_meshing(input, &self.block_meshes, &mut self.quads_reuse);
+            Some((vertices, indices))
+        } else {
+            None
+        };
+        ChunkMesh { pos, mesh, light_volume }
     }
 }
 
@@ -1,25 +1,41 @@
 //! World rendering
 
-use super::buffers::MultiBuffer;
+use super::buffers::{DynamicBuffer, MultiBuffer};
 use super::frustum::Frustum;
 use super::init::{create_default_pipeline, load_glsl_shader, ShaderStage};
-use super::{ to_u8_slice, buffer_from_slice };
-use crate::texture::load_image;
+use super::{to_u8_slice, Uploader};
+use crate::texture::load_texture_array;
 use crate::window::WindowBuffers;
-use image::{ImageBuffer, Rgba};
 use nalgebra::{Matrix4, Similarity3, Translation3, UnitQuaternion, Vector3};
 use wgpu::ShaderModuleDescriptor;
 use wgpu_types::SamplerBindingType;
 use common::data::vox::VoxelModel;
+use common::data::TextureArray;
 use common::debug::send_debug_info;
 use common::registry::Registry;
-use common::world::{BlockPos, ChunkPos};
+use common::time::WorldTime;
+use common::world::{BlockPos, ChunkPos, CHUNK_SIZE};
 
 mod meshing;
+mod cracks;
+mod debug;
+mod indirect;
+mod light_volumes;
+mod lights;
 mod meshing_worker;
 mod model;
+mod shadows;
 mod skybox;
+pub use self::debug::{DebugBox, DebugRenderModes, ALLOWED_CLAIM_COLOR, ENTITY_BOX_COLOR, PROTECTED_CLAIM_COLOR};
+pub use self::indirect::ChunkDraws;
+pub use self::lights::{PointLight, Spot};
 pub use self::model::Model;
+pub use self::shadows::ShadowQuality;
+use self::cracks::BlockCracks;
+use self::debug::DebugLines;
+use self::light_volumes::LightVolumes;
+use self::lights::LightClusters;
+use self::shadows::{ShadowMaps, ShadowUniform};
 pub use self::meshing::ChunkMeshData;
 pub use self::meshing_worker::{ChunkMesh, MeshingWorker, start_meshing_worker};
 
@@ -29,9 +45,26 @@ pub struct WorldRenderer {
     uniform_view_proj: wgpu::Buffer,
     // Model matrix
     uniform_model: wgpu::Buffer,
+    // Daylight factor, sun and shadow parameters
+    uniform_world: wgpu::Buffer,
+    // Kept to recreate the chunk bind group when the shadow maps change
+    textures_view: wgpu::TextureView,
+    texture_anisotropy: u16,
+    // Bias of the mipmap level of the block textures
+    texture_lod_bias: f32,
+    shadow_maps: ShadowMaps,
+    // Clustered point lights: grid parameters, lights, light lists of the clusters
+    uniform_clusters: wgpu::Buffer,
+    storage_lights: wgpu::Buffer,
+    storage_clusters: wgpu::Buffer,
+    storage_light_indices: wgpu::Buffer,
+    // Smooth lighting of the chunks
+    light_volumes: LightVolumes,
     // Chunk rendering
     chunk_index_buffers: MultiBuffer<ChunkPos, u32>,
     chunk_vertex_buffers: MultiBuffer<ChunkPos, ChunkVertex>,
+    chunk_origins: DynamicBuffer<ChunkOrigin>,
+    chunk_draws: ChunkDraws,
     chunk_pipeline: wgpu::RenderPipeline,
     chunk_bind_group: wgpu::BindGroup,
     // Skybox rendering
@@ -47,18 +80,25 @@ pub struct WorldRenderer {
     model_index_buffers: MultiBuffer<u32, u32>,
     model_vertex_buffers: MultiBuffer<u32, RgbVertex>,
     model_pipeline: wgpu::RenderPipeline,
+    // Debug boxes rendering
+    debug_lines: DebugLines,
+    // Damaged blocks rendering
+    block_cracks: BlockCracks,
 }
 
 impl WorldRenderer {
     pub fn new(
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
-        texture_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+        uploader: &mut Uploader,
+        textures: &TextureArray,
         models: &Registry<VoxelModel>,
+        shadow_quality: ShadowQuality,
+        light_volumes: bool,
+        texture_anisotropy: u16,
     ) -> Self {
-        // Load texture atlas
-        let texture_atlas = load_image(device, encoder, texture_atlas);
-        let texture_atlas_view = texture_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        // Load the block textures
+        let textures_view = create_texture_array_view(&load_texture_array(device, encoder, uploader, textures));
 
         // Create uniform buffers
         let uniform_view_proj = device.create_buffer(&wgpu::BufferDescriptor {
@@ -73,14 +113,48 @@ impl WorldRenderer {
             size: 64,
             usage: (wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST),
         });
+        let uniform_world = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: WORLD_UNIFORM_SIZE,
+            usage: (wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST),
+        });
+
+        let shadow_maps = ShadowMaps::new(device, shadow_quality);
+
+        // Create light buffers
+        let uniform_clusters = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: CLUSTERS_UNIFORM_SIZE,
+            usage: (wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST),
+        });
+        let create_storage_buffer = |size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                mapped_at_creation: false,
+                label: None,
+                size: size as u64,
+                usage: (wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST),
+            })
+        };
+        let storage_lights = create_storage_buffer(lights::MAX_LIGHTS * std::mem::size_of::<[f32; 12]>());
+        let storage_clusters = create_storage_buffer(lights::CLUSTER_COUNT * std::mem::size_of::<[u32; 2]>());
+        let storage_light_indices = create_storage_buffer(lights::MAX_LIGHT_INDICES * std::mem::size_of::<u32>());
+
+        let light_volumes = LightVolumes::new(device, light_volumes);
 
         // Create uniform bind group
         let chunk_bind_group_layout = device.create_bind_group_layout(&CHUNK_BIND_GROUP_LAYOUT);
         let chunk_bind_group = create_chunk_bind_group(
             device,
             &chunk_bind_group_layout,
-            &texture_atlas_view,
+            &textures_view,
+            texture_anisotropy,
             &uniform_view_proj,
+            &uniform_world,
+            &shadow_maps,
+            [&uniform_clusters, &storage_lights, &storage_clusters, &storage_light_indices],
+            &light_volumes,
         );
 
         // Create chunk pipeline
@@ -160,6 +234,9 @@ impl WorldRenderer {
             )
         };
 
+        let debug_lines = DebugLines::new(device, &vpm_bind_group_layout);
+        let block_cracks = BlockCracks::new(device, &vpm_bind_group_layout);
+
         // Create model pipeline
         let model_pipeline = {
             let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/model.vert");
@@ -187,14 +264,25 @@ impl WorldRenderer {
         for mesh_id in 0..models.get_number_of_ids() {
             let (vertices, indices) =
                 self::model::mesh_model(models.get_value_by_id(mesh_id).unwrap());
-            model_index_buffers.update(device, encoder, mesh_id, &indices);
-            model_vertex_buffers.update(device, encoder, mesh_id, &vertices);
+            model_index_buffers.update(device, encoder, uploader, mesh_id, &indices);
+            model_vertex_buffers.update(device, encoder, uploader, mesh_id, &vertices);
         }
 
         Self {
             uniform_view_proj,
             uniform_model,
+            uniform_world,
+            textures_view,
+            texture_anisotropy,
+            shadow_maps,
+            uniform_clusters,
+            storage_lights,
+            storage_clusters,
+            storage_light_indices,
+            light_volumes,
             chunk_index_buffers: MultiBuffer::with_capacity(device, 1000, wgpu::BufferUsages::INDEX),
+            chunk_origins: DynamicBuffer::with_capacity(device, 1000, wgpu::BufferUsages::VERTEX),
+            chunk_draws: ChunkDraws::new(device),
             chunk_vertex_buffers: MultiBuffer::with_capacity(
                 device,
                 1000,
@@ -211,19 +299,92 @@ impl WorldRenderer {
             model_pipeline,
             model_index_buffers,
             model_vertex_buffers,
+            debug_lines,
+            block_cracks,
+            texture_lod_bias: 0.0,
         }
     }
 
+    /// Replace the block textures, after the data was reloaded
+    pub fn update_textures(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        textures: &TextureArray,
+    ) {
+        self.textures_view = create_texture_array_view(&load_texture_array(device, encoder, uploader, textures));
+        self.recreate_chunk_bind_group(device);
+    }
+
+    /// Recreate the shadow maps if the quality changed
+    pub fn set_shadow_quality(&mut self, device: &wgpu::Device, quality: ShadowQuality) {
+        if self.shadow_maps.quality() != quality.supported() {
+            self.shadow_maps = ShadowMaps::new(device, quality);
+            self.recreate_chunk_bind_group(device);
+        }
+    }
+
+    /// Sample the block textures `bias` mipmap levels above the usual level, to save bandwidth
+    pub fn set_texture_lod_bias(&mut self, bias: f32) {
+        self.texture_lod_bias = bias;
+    }
+
+    fn recreate_chunk_bind_group(&mut self, device: &wgpu::Device) {
+        let chunk_bind_group_layout = device.create_bind_group_layout(&CHUNK_BIND_GROUP_LAYOUT);
+        self.chunk_bind_group = create_chunk_bind_group(
+            device,
+            &chunk_bind_group_layout,
+            &self.textures_view,
+            self.texture_anisotropy,
+            &self.uniform_view_proj,
+            &self.uniform_world,
+            &self.shadow_maps,
+            [&self.uniform_clusters, &self.storage_lights, &self.storage_clusters, &self.storage_light_indices],
+            &self.light_volumes,
+        );
+    }
+
+    /// Whether the light of the chunks is computed in light volumes
+    pub fn light_volumes_enabled(&self) -> bool {
+        self.light_volumes.is_enabled()
+    }
+
+    /// Whether the light of a chunk comes from its light volume instead of its vertices
+    pub fn has_light_volume(&self, pos: ChunkPos) -> bool {
+        self.light_volumes.has_volume(pos)
+    }
+
+    /// Upload a model that was added to the registry after the creation of the renderer
+    pub fn upload_model(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        mesh_id: u32,
+        model: &VoxelModel,
+    ) {
+        let (vertices, indices) = self::model::mesh_model(model);
+        self.model_index_buffers.update(device, encoder, uploader, mesh_id, &indices);
+        self.model_vertex_buffers.update(device, encoder, uploader, mesh_id, &vertices);
+    }
+
     pub fn render(
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
         buffers: WindowBuffers,
         data: &crate::window::WindowData,
         frustum: &Frustum,
         enable_culling: bool,
         pointed_block: Option<(BlockPos, usize)>,
+        damaged_blocks: &[(BlockPos, u8)],
         models: &[model::Model],
+        lights: &[PointLight],
+        world_time: WorldTime,
+        debug_modes: &DebugRenderModes,
+        debug_boxes: &[DebugBox],
     ) {
         //============= RENDER =============//
         // TODO: what if win_h is 0 ?
@@ -251,68 +412,128 @@ impl WorldRenderer {
         .into();
 
         // Update view_proj matrix
-        let src_buffer = buffer_from_slice(
+        uploader.upload_buffer(device, encoder, &self.uniform_view_proj, 0, to_u8_slice(&view_proj));
+
+        // Render the shadow maps
+        let sun_direction = Vector3::from(world_time.sun_direction());
+        self.shadow_maps.update_cascades(frustum, aspect_ratio, sun_direction, &opengl_to_wgpu);
+        // Compute the light volumes of the chunks whose light changed
+        self.light_volumes.compute(device, encoder, uploader);
+        if self.light_volumes.is_enabled() {
+            let (volumes, pending) = self.light_volumes.stats();
+            send_debug_info(
+                "Render",
+                "lightvolumes",
+                format!("{} light volumes, {} waiting", volumes, pending),
+            );
+        }
+        // Every chunk is drawn as an instance, with the origin of the chunk as instance data
+        let chunks = self.chunk_index_buffers.keys().collect::<Vec<_>>();
+        let chunk_origins = get_chunk_origins(&chunks, &self.light_volumes);
+        self.chunk_origins.upload(device, encoder, uploader, &chunk_origins);
+        self.shadow_maps.render(
             device,
-            wgpu::BufferUsages::COPY_SRC,
-            to_u8_slice(&view_proj)
+            encoder,
+            uploader,
+            &self.chunk_vertex_buffers,
+            &self.chunk_index_buffers,
+            &chunks,
+            self.chunk_origins.get_buffer(),
         );
-        encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_view_proj, 0, 64);
+
+        // Update daylight, sun and shadows
+        let uniform = WorldUniform {
+            daylight: world_time.daylight() as f32,
+            shadows: self.shadow_maps.get_uniform(),
+            sun_direction: sun_direction.cast::<f32>().push(0.0).into(),
+            camera_position: frustum.position.cast::<f32>().push(1.0).into(),
+            debug: debug_modes.to_uniform(),
+            texture_lod_bias: self.texture_lod_bias,
+        };
+        uploader.upload_buffer(device, encoder, &self.uniform_world, 0, to_u8_slice(&uniform.to_std140()));
+
+        // Assign the point lights to the clusters
+        let light_clusters = LightClusters::build(frustum, aspect_ratio, lights);
+        send_debug_info(
+            "Render",
+            "pointlights",
+            format!("{} point lights, {} light indices", light_clusters.lights.len(), light_clusters.light_indices.len()),
+        );
+        let forward = -Vector3::new(view_mat[(2, 0)], view_mat[(2, 1)], view_mat[(2, 2)]);
+        let clusters_uniform: [f32; 16] = [
+            lights::CLUSTERS_X as f32,
+            lights::CLUSTERS_Y as f32,
+            lights::CLUSTERS_Z as f32,
+            0.0,
+            lights::CLUSTER_NEAR as f32,
+            lights::CLUSTER_FAR as f32,
+            (lights::CLUSTER_FAR / lights::CLUSTER_NEAR).ln() as f32,
+            0.0,
+            data.physical_window_size.width as f32,
+            data.physical_window_size.height as f32,
+            0.0,
+            0.0,
+            forward.x as f32,
+            forward.y as f32,
+            forward.z as f32,
+            0.0,
+        ];
+        uploader.upload_buffer(device, encoder, &self.uniform_clusters, 0, to_u8_slice(&clusters_uniform));
+        for (bytes, buffer) in [
+            (to_u8_slice(&light_clusters.lights), &self.storage_lights),
+            (to_u8_slice(&light_clusters.clusters), &self.storage_clusters),
+            (to_u8_slice(&light_clusters.light_indices), &self.storage_light_indices),
+        ] {
+            uploader.upload_buffer(device, encoder, buffer, 0, bytes);
+        }
 
         // Draw all the chunks
         {
+            self.chunk_draws.update(
+                device,
+                encoder,
+                uploader,
+                &self.chunk_vertex_buffers,
+                &self.chunk_index_buffers,
+                &chunks,
+                |chunk_pos| !enable_culling || Frustum::contains_chunk(&planes, &view_mat, chunk_pos),
+            );
             let mut rpass = super::render::create_default_render_pass(encoder, buffers);
             rpass.set_pipeline(&self.chunk_pipeline);
             rpass.set_bind_group(0, &self.chunk_bind_group, &[]);
             rpass.set_vertex_buffer(0, self.chunk_vertex_buffers.get_buffer().slice(..));
+            rpass.set_vertex_buffer(1, self.chunk_origins.get_buffer().slice(..));
             rpass.set_index_buffer(self.chunk_index_buffers.get_buffer().slice(..), Default::default());
-            let mut count = 0;
-            for chunk_pos in self.chunk_index_buffers.keys() {
-                if !enable_culling || Frustum::contains_chunk(&planes, &view_mat, chunk_pos) {
-                    count += 1;
-                    let (index_pos, index_len) =
-                        self.chunk_index_buffers.get_pos_len(&chunk_pos).unwrap();
-                    let (vertex_pos, _) =
-                        self.chunk_vertex_buffers.get_pos_len(&chunk_pos).unwrap();
-                    rpass.draw_indexed(
-                        (index_pos as u32)..((index_pos + index_len) as u32),
-                        vertex_pos as i32,
-                        0..1,
-                    );
-                }
-            }
+            self.chunk_draws.draw(&mut rpass);
             send_debug_info(
                 "Render",
                 "renderedchunks",
-                format!("{} chunks were rendered", count),
+                format!("{} chunks were rendered", self.chunk_draws.len()),
             );
         }
 
         // Draw the skybox
         {
             // Update model buffer
-            let src_buffer = buffer_from_slice(
-                device,
-                wgpu::BufferUsages::COPY_SRC,
-                to_u8_slice(&[
-                    1.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    1.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    1.0,
-                    0.0,
-                    frustum.position.x as f32,
-                    frustum.position.y as f32,
-                    frustum.position.z as f32,
-                    1.0,
-                ])
-            );
-            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 64);
+            let model_matrix = [
+                1.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                0.0,
+                frustum.position.x as f32,
+                frustum.position.y as f32,
+                frustum.position.z as f32,
+                1.0,
+            ];
+            uploader.upload_buffer(device, encoder, &self.uniform_model, 0, to_u8_slice(&model_matrix));
             let mut rpass = super::render::create_default_render_pass(encoder, buffers);
             rpass.set_pipeline(&self.skybox_pipeline);
             rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
@@ -325,42 +546,28 @@ impl WorldRenderer {
         if let Some((target_pos, target_face)) = pointed_block {
             // Generate the vertices
             // TODO: maybe check if they changed since last frame
-            let src_buffer = buffer_from_slice(
-                device,
-                wgpu::BufferUsages::COPY_SRC,
-                to_u8_slice(&create_target_vertices(target_face))
-            );
-            encoder.copy_buffer_to_buffer(
-                &src_buffer,
-                0,
-                &self.target_vertex_buffer,
-                0,
-                8 * std::mem::size_of::<SkyboxVertex>() as u64,
-            );
+            let target_vertices = create_target_vertices(target_face);
+            uploader.upload_buffer(device, encoder, &self.target_vertex_buffer, 0, to_u8_slice(&target_vertices));
             // Update model buffer
-            let src_buffer = buffer_from_slice(
-                device,
-                wgpu::BufferUsages::COPY_SRC,
-                to_u8_slice(&[
-                    1.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    1.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    0.0,
-                    1.0,
-                    0.0,
-                    target_pos.px as f32,
-                    target_pos.py as f32,
-                    target_pos.pz as f32,
-                    1.0,
-                ])
-            );
-            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 64);
+            let model_matrix = [
+                1.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                0.0,
+                target_pos.px as f32,
+                target_pos.py as f32,
+                target_pos.pz as f32,
+                1.0,
+            ];
+            uploader.upload_buffer(device, encoder, &self.uniform_model, 0, to_u8_slice(&model_matrix));
             let mut rpass = super::render::create_default_render_pass(encoder, buffers);
             rpass.set_pipeline(&self.target_pipeline);
             rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
@@ -368,6 +575,9 @@ impl WorldRenderer {
             rpass.draw(0..8, 0..1);
         }
 
+        // Draw the cracks of the blocks being mined
+        self.block_cracks.render(device, encoder, uploader, buffers, &self.vpm_bind_group, damaged_blocks);
+
         // Draw the models
         for model in models {
             // Compute model matrix
@@ -375,22 +585,18 @@ impl WorldRenderer {
             transform.append_scaling_mut(model.scale);
             let offset_translation = Translation3::from(-Vector3::from(model.rot_offset));
             transform.append_translation_mut(&offset_translation);
-            transform.append_rotation_mut(&UnitQuaternion::from_axis_angle(
-                &Vector3::y_axis(),
-                model.rot_y,
-            ));
+            transform.append_rotation_mut(
+                &(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), model.rot_y)
+                    * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), model.rot_z)
+                    * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), model.rot_x)),
+            );
             transform.append_translation_mut(&Translation3::from(
                 Vector3::new(model.pos_x, model.pos_y, model.pos_z)
                     + &Vector3::from(model.rot_offset),
             ));
             let transformation_matrix: Matrix4<f32> = nalgebra::convert(transform);
             // Update model buffer
-            let src_buffer = buffer_from_slice(
-                device,
-                wgpu::BufferUsages::COPY_SRC,
-                to_u8_slice(transformation_matrix.as_ref())
-            );
-            encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_model, 0, 64);
+            uploader.upload_buffer(device, encoder, &self.uniform_model, 0, to_u8_slice(transformation_matrix.as_ref()));
             // Draw model
             let mut rpass = super::render::create_default_render_pass(encoder, buffers);
             rpass.set_pipeline(&self.model_pipeline);
@@ -411,75 +617,125 @@ impl WorldRenderer {
                 0..1,
             );
         }
+
+        // Draw the debug boxes over the models
+        self.debug_lines.render(
+            device,
+            encoder,
+            uploader,
+            buffers,
+            &self.vpm_bind_group,
+            debug_modes,
+            frustum.position,
+            debug_boxes,
+        );
     }
 
     pub fn update_chunk_mesh(
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
         chunk_mesh: ChunkMesh,
     ) {
-        let (pos, vertices, indices) = chunk_mesh;
-        if vertices.len() > 0 && indices.len() > 0 {
-            self.chunk_vertex_buffers
-                .update(device, encoder, pos, &vertices[..]);
-            self.chunk_index_buffers
-                .update(device, encoder, pos, &indices[..]);
+        let ChunkMesh { pos, mesh, light_volume } = chunk_mesh;
+        if let Some((vertices, indices)) = mesh {
+            if vertices.len() > 0 && indices.len() > 0 {
+                self.chunk_vertex_buffers
+                    .update(device, encoder, uploader, pos, &vertices[..]);
+                self.chunk_index_buffers
+                    .update(device, encoder, uploader, pos, &indices[..]);
+            }
+        }
+        if let Some(blocks) = light_volume {
+            self.light_volumes.update(pos, blocks);
         }
     }
 
     pub fn remove_chunk_mesh(&mut self, pos: ChunkPos) {
         self.chunk_vertex_buffers.remove(&pos);
         self.chunk_index_buffers.remove(&pos);
+        self.light_volumes.remove(pos);
     }
 }
 
 /*========== CHUNK RENDERING ==========*/
-/// Chunk vertex
+/// Chunk vertex, packed to keep the meshes small. The position is relative to the origin of the chunk, which is
+/// given to the shader by the per-instance `ChunkOrigin`.
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct ChunkVertex {
-    pub pos: [f32; 3],
-    pub texture_top_left: [f32; 2],
-    pub texture_size: [f32; 2],
-    pub texture_max_uv: [f32; 2],
-    pub texture_uv: [f32; 2],
-    pub occl_and_face: u32,
+    pub pos: [u16; 3],
+    /// Index of the face, from 0 to 5: +x, -x, +y, -y, +z, -z
+    pub face: u8,
+    /// Ambient occlusion code, from 0 (the darkest) to 3
+    pub occl: u8,
+    /// Sunlight level, block light level, 1 if the block is emissive, and an unused byte
+    pub light: [u8; 4],
+    /// Layer of the texture in the texture array
+    pub texture_layer: u32,
+    /// Size of the texture relative to its layer, 16-bit normalized
+    pub texture_size: [u16; 2],
+    /// Number of times the texture is repeated up to this vertex, then on the whole quad, along the axes of the face
+    pub texture_repeats: [u8; 4],
 }
 
 /// Chunk vertex attributes
-const CHUNK_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 6] = [
+const CHUNK_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 5] = [
+    // The position, then the face and the occlusion in the last component
     wgpu::VertexAttribute {
         shader_location: 0,
-        format: wgpu::VertexFormat::Float32x3,
+        format: wgpu::VertexFormat::Uint16x4,
         offset: 0,
     },
     wgpu::VertexAttribute {
         shader_location: 1,
-        format: wgpu::VertexFormat::Float32x2,
-        offset: 4 * 3,
+        format: wgpu::VertexFormat::Uint8x4,
+        offset: 8,
     },
     wgpu::VertexAttribute {
         shader_location: 2,
-        format: wgpu::VertexFormat::Float32x2,
-        offset: 4 * (3 + 2),
+        format: wgpu::VertexFormat::Uint32,
+        offset: 8 + 4,
     },
     wgpu::VertexAttribute {
         shader_location: 3,
-        format: wgpu::VertexFormat::Float32x2,
-        offset: 4 * (3 + 2 + 2),
+        format: wgpu::VertexFormat::Unorm16x2,
+        offset: 8 + 4 + 4,
     },
     wgpu::VertexAttribute {
         shader_location: 4,
-        format: wgpu::VertexFormat::Float32x2,
-        offset: 4 * (3 + 2 + 2 + 2),
-    },
-    wgpu::VertexAttribute {
-        shader_location: 5,
-        format: wgpu::VertexFormat::Uint32,
-        offset: 4 * (3 + 2 + 2 + 2 + 2),
+        format: wgpu::VertexFormat::Uint8x4,
+        offset: 8 + 4 + 4 + 4,
     },
 ];
 
+/// Position of the first block of a chunk, then the slot of its light volume or -1, one per instance
+type ChunkOrigin = [i32; 4];
+
+/// Chunk origin attributes, in the per-instance vertex buffer
+const CHUNK_ORIGIN_ATTRIBUTES: [wgpu::VertexAttribute; 1] = [wgpu::VertexAttribute {
+    shader_location: 5,
+    format: wgpu::VertexFormat::Sint32x4,
+    offset: 0,
+}];
+
+/// The origin of each chunk, for the instance with the same index in `chunks`
+fn get_chunk_origins(chunks: &[ChunkPos], light_volumes: &LightVolumes) -> Vec<ChunkOrigin> {
+    chunks
+        .iter()
+        .map(|&pos| {
+            let size = CHUNK_SIZE as i64;
+            [
+                (pos.px * size) as i32,
+                (pos.py * size) as i32,
+                (pos.pz * size) as i32,
+                light_volumes.get_slot(pos),
+            ]
+        })
+        .collect()
+}
+
 const CHUNK_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
     wgpu::BindGroupLayoutDescriptor {
         label: None,
@@ -506,33 +762,135 @@ const CHUNK_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
                 ty: wgpu::BindingType::Texture {
                     sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D2,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                // shadow maps
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                // shadow comparison sampler
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(SamplerBindingType::Comparison),
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                // cluster grid
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None
+            },
+            LIGHT_STORAGE_LAYOUT_ENTRIES[0],
+            LIGHT_STORAGE_LAYOUT_ENTRIES[1],
+            LIGHT_STORAGE_LAYOUT_ENTRIES[2],
+            wgpu::BindGroupLayoutEntry {
+                // light volumes
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
                 },
                 count: None
             },
+            wgpu::BindGroupLayoutEntry {
+                // light volume sampler
+                binding: 11,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None
+            },
         ],
     };
 
+/// The read-only storage buffers of the lights, the clusters and the light indices, at bindings 7 to 9
+const LIGHT_STORAGE_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 3] = {
+    const fn entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+    [entry(7), entry(8), entry(9)]
+};
+
+/// Size of the `Clusters` uniform of the chunk shaders, in bytes
+const CLUSTERS_UNIFORM_SIZE: u64 = 4 * 16;
+
+/// View of all the layers of the block textures
+fn create_texture_array_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("block textures"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    })
+}
+
 /// Create chunk bind group
 fn create_chunk_bind_group(
     device: &wgpu::Device,
     layout: &wgpu::BindGroupLayout,
-    texture_atlas_view: &wgpu::TextureView,
+    textures_view: &wgpu::TextureView,
+    texture_anisotropy: u16,
     uniform_view_proj: &wgpu::Buffer,
+    uniform_world: &wgpu::Buffer,
+    shadow_maps: &ShadowMaps,
+    // The cluster grid uniform, then the storage buffers of the lights, the clusters and the light indices
+    light_buffers: [&wgpu::Buffer; 4],
+    light_volumes: &LightVolumes,
 ) -> wgpu::BindGroup {
-    // Create texture sampler
+    // Create texture sampler. The anisotropic filtering only works with linear filtering, so the texels are only
+    // kept sharp without it. Each texture has its own layer, so the filtering can wrap around it.
+    let anisotropy = texture_anisotropy.clamp(1, 16);
+    let filter = if anisotropy > 1 {
+        wgpu::FilterMode::Linear
+    } else {
+        wgpu::FilterMode::Nearest
+    };
     let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-        label: None,
-        address_mode_u: wgpu::AddressMode::ClampToEdge,
-        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        label: Some("block textures"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
         address_mode_w: wgpu::AddressMode::ClampToEdge,
-        mag_filter: wgpu::FilterMode::Nearest,
-        min_filter: wgpu::FilterMode::Nearest,
+        mag_filter: filter,
+        min_filter: filter,
         mipmap_filter: wgpu::FilterMode::Linear,
         lod_min_clamp: 0.0,
-        lod_max_clamp: 5.0,
-        compare: Some(wgpu::CompareFunction::Always),
-        anisotropy_clamp: 0,
+        lod_max_clamp: 32.0,
+        compare: None,
+        anisotropy_clamp: anisotropy,
         border_color: None,
     });
 
@@ -552,12 +910,86 @@ fn create_chunk_bind_group(
             },
             wgpu::BindGroupEntry {
                 binding: 2,
-                resource: wgpu::BindingResource::TextureView(texture_atlas_view),
+                resource: wgpu::BindingResource::TextureView(textures_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Buffer(
+                    uniform_world.as_entire_buffer_binding()
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(shadow_maps.texture_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(shadow_maps.sampler()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::Buffer(light_buffers[0].as_entire_buffer_binding()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::Buffer(light_buffers[1].as_entire_buffer_binding()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: wgpu::BindingResource::Buffer(light_buffers[2].as_entire_buffer_binding()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: wgpu::BindingResource::Buffer(light_buffers[3].as_entire_buffer_binding()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: wgpu::BindingResource::TextureView(light_volumes.texture_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::Sampler(light_volumes.sampler()),
             },
         ],
     })
 }
 
+/// Size of the `World` uniform of the chunk shaders, in bytes
+const WORLD_UNIFORM_SIZE: u64 = 4 * (6 * 4 + 16 * shadows::MAX_CASCADES as u64);
+
+/// The `World` uniform of the chunk shaders
+struct WorldUniform {
+    daylight: f32,
+    shadows: ShadowUniform,
+    sun_direction: [f32; 4],
+    camera_position: [f32; 4],
+    debug: [f32; 4],
+    texture_lod_bias: f32,
+}
+
+impl WorldUniform {
+    /// Lay out the uniform as the shaders expect it: 4 floats, then 5 vec4, then the matrices
+    fn to_std140(&self) -> Vec<f32> {
+        let mut data = vec![
+            self.daylight,
+            self.shadows.cascade_count,
+            self.shadows.pcf_radius,
+            self.shadows.texel_size,
+        ];
+        data.extend_from_slice(&self.sun_direction);
+        data.extend_from_slice(&self.camera_position);
+        data.extend_from_slice(&self.debug);
+        data.extend_from_slice(&[self.texture_lod_bias, 0.0, 0.0, 0.0]);
+        data.extend_from_slice(&self.shadows.cascade_ends);
+        for matrix in self.shadows.light_view_proj.iter() {
+            for column in matrix.iter() {
+                data.extend_from_slice(column);
+            }
+        }
+        data
+    }
+}
+
 /*========== SKYBOX RENDERING ==========*/
 /// Skybox vertex
 #[derive(Debug, Clone, Copy)]
@@ -13,6 +13,9 @@ pub struct Model {
     pub scale: f32,
     /// Model rotation (after scaling)
     pub rot_y: f32,
+    /// Rotations around the x then the z axis, applied before `rot_y` to tilt the model
+    pub rot_x: f32,
+    pub rot_z: f32,
     /// Offset to apply before rotating the model
     pub rot_offset: [f32; 3],
 }
@@ -0,0 +1,429 @@
+//! Cascaded shadow maps of the sun. The terrain is rendered from the direction of the sun into a few depth maps,
+//! each covering a larger slice of the view frustum, and the main pass compares the depth of each fragment with the
+//! map of its slice.
+
+use super::indirect::ChunkDraws;
+use super::ChunkVertex;
+use crate::render::buffers::MultiBuffer;
+use crate::render::compatibility;
+use crate::render::init::{load_glsl_shader, ShaderStage};
+use crate::render::pipeline_cache::pipeline_cache;
+use crate::render::{to_u8_slice, Frustum, Uploader};
+use common::debug::send_debug_info;
+use common::world::{ChunkPos, CHUNK_SIZE};
+use nalgebra::{Matrix4, Orthographic3, Point3, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+use wgpu::ShaderModuleDescriptor;
+
+/// The maximum number of cascades, the size of the array of matrices in the shaders
+pub const MAX_CASCADES: usize = 3;
+const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// How far from the shadowed slice the blocks still cast shadows, towards the sun
+const CASTER_DISTANCE: f64 = 128.0;
+
+/// The quality of the shadows, set in the settings or with the `shadows` console command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl Default for ShadowQuality {
+    fn default() -> Self {
+        ShadowQuality::Medium
+    }
+}
+
+impl ShadowQuality {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(ShadowQuality::Off),
+            "low" => Some(ShadowQuality::Low),
+            "medium" => Some(ShadowQuality::Medium),
+            "high" => Some(ShadowQuality::High),
+            _ => None,
+        }
+    }
+
+    /// The quality actually used, the shadows being off in the compatibility mode
+    pub fn supported(self) -> Self {
+        if compatibility::is_enabled() {
+            ShadowQuality::Off
+        } else {
+            self
+        }
+    }
+
+    /// The distance from the camera where each cascade ends
+    fn cascade_ends(self) -> &'static [f64] {
+        match self {
+            ShadowQuality::Off => &[],
+            ShadowQuality::Low => &[24.0, 96.0],
+            ShadowQuality::Medium => &[16.0, 48.0, 160.0],
+            ShadowQuality::High => &[16.0, 64.0, 256.0],
+        }
+    }
+
+    /// The width and height of the depth maps
+    fn resolution(self) -> u32 {
+        match self {
+            ShadowQuality::Off => 1,
+            ShadowQuality::Low => 1024,
+            ShadowQuality::Medium => 2048,
+            ShadowQuality::High => 4096,
+        }
+    }
+
+    /// The radius in texels of the square of samples averaged by the percentage-closer filtering
+    fn pcf_radius(self) -> u32 {
+        match self {
+            ShadowQuality::Off => 0,
+            ShadowQuality::Low | ShadowQuality::Medium => 1,
+            ShadowQuality::High => 2,
+        }
+    }
+}
+
+/// A slice of the view frustum and the orthographic projection from the sun that covers it
+struct Cascade {
+    /// The transformation from the world to the light space, before the projection
+    light_view: Matrix4<f64>,
+    /// Center and half size of the slice along the x and y axes of the light space
+    center: Vector3<f64>,
+    half_size: f64,
+    view_proj: Matrix4<f64>,
+    /// Distance from the camera where the slice ends
+    end: f64,
+}
+
+/// The parameters of the shadows in the `World` uniform of the chunk shaders
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowUniform {
+    pub cascade_count: f32,
+    pub pcf_radius: f32,
+    pub texel_size: f32,
+    pub cascade_ends: [f32; 4],
+    pub light_view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+}
+
+pub struct ShadowMaps {
+    quality: ShadowQuality,
+    /// All the depth maps, one layer per cascade
+    texture_view: wgpu::TextureView,
+    /// The view of each layer, to render into it
+    layer_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    uniform_light_view_proj: Vec<wgpu::Buffer>,
+    bind_groups: Vec<wgpu::BindGroup>,
+    /// The chunks to draw into the map of each cascade
+    chunk_draws: Vec<ChunkDraws>,
+    pipeline: wgpu::RenderPipeline,
+    cascades: Vec<Cascade>,
+}
+
+impl ShadowMaps {
+    pub fn new(device: &wgpu::Device, quality: ShadowQuality) -> Self {
+        let quality = quality.supported();
+        let cascade_count = quality.cascade_ends().len();
+        let resolution = quality.resolution();
+        // There is always at least one layer, so that the bind group of the chunks is valid when the shadows are off
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow maps"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: cascade_count.max(1) as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..cascade_count)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer as u32,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&SHADOW_BIND_GROUP_LAYOUT);
+        let uniform_light_view_proj: Vec<_> = (0..cascade_count)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    mapped_at_creation: false,
+                    label: None,
+                    size: 64,
+                    usage: (wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST),
+                })
+            })
+            .collect();
+        let bind_groups = uniform_light_view_proj
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(buffer.as_entire_buffer_binding()),
+                    }],
+                })
+            })
+            .collect();
+
+        let chunk_draws = (0..cascade_count).map(|_| ChunkDraws::new(device)).collect();
+        let pipeline = create_shadow_pipeline(device, &bind_group_layout);
+
+        Self {
+            quality,
+            texture_view,
+            layer_views,
+            sampler,
+            uniform_light_view_proj,
+            bind_groups,
+            chunk_draws,
+            pipeline,
+            cascades: Vec::new(),
+        }
+    }
+
+    pub fn quality(&self) -> ShadowQuality {
+        self.quality
+    }
+
+    pub fn texture_view(&self) -> &wgpu::TextureView {
+        &self.texture_view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Fit the cascades to the view frustum and the current direction of the sun. There are no shadows at night.
+    pub fn update_cascades(
+        &mut self,
+        frustum: &Frustum,
+        aspect_ratio: f64,
+        sun_direction: Vector3<f64>,
+        opengl_to_wgpu: &Matrix4<f64>,
+    ) {
+        self.cascades.clear();
+        if sun_direction.y <= 0.0 {
+            return;
+        }
+        let resolution = self.quality.resolution() as f64;
+        // The sun is never exactly vertical, so the y axis can be used as the up vector
+        let light_view = Matrix4::look_at_rh(&Point3::origin(), &Point3::from(-sun_direction), &Vector3::y());
+        let mut start = 0.1;
+        for &end in self.quality.cascade_ends() {
+            let corners = frustum.get_slice_corners(aspect_ratio, start, end);
+            let center = corners.iter().sum::<Vector3<f64>>() / 8.0;
+            // The radius only depends on the size of the slice, so the shadows don't change when the camera rotates
+            let half_size = corners
+                .iter()
+                .map(|corner| (corner - center).norm())
+                .fold(0.0, f64::max)
+                .ceil();
+            // Move the center by whole texels, so that the shadows don't flicker when the camera moves
+            let texel_size = 2.0 * half_size / resolution;
+            let mut center = (light_view * center.push(1.0)).xyz();
+            center.x = (center.x / texel_size).floor() * texel_size;
+            center.y = (center.y / texel_size).floor() * texel_size;
+            let projection = Orthographic3::new(
+                center.x - half_size,
+                center.x + half_size,
+                center.y - half_size,
+                center.y + half_size,
+                -center.z - half_size - CASTER_DISTANCE,
+                -center.z + half_size,
+            );
+            self.cascades.push(Cascade {
+                light_view,
+                center,
+                half_size,
+                view_proj: opengl_to_wgpu * projection.as_matrix() * light_view,
+                end,
+            });
+            start = end;
+        }
+    }
+
+    /// The parameters of the current cascades for the main pass
+    pub fn get_uniform(&self) -> ShadowUniform {
+        let mut uniform = ShadowUniform {
+            cascade_count: self.cascades.len() as f32,
+            pcf_radius: self.quality.pcf_radius() as f32,
+            texel_size: 1.0 / self.quality.resolution() as f32,
+            cascade_ends: [0.0; 4],
+            light_view_proj: [[[0.0; 4]; 4]; MAX_CASCADES],
+        };
+        for (i, cascade) in self.cascades.iter().enumerate() {
+            uniform.cascade_ends[i] = cascade.end as f32;
+            uniform.light_view_proj[i] = nalgebra::convert::<Matrix4<f64>, Matrix4<f32>>(cascade.view_proj).into();
+        }
+        uniform
+    }
+
+    /// Render the depth of the chunks into the map of each cascade
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        chunk_vertex_buffers: &MultiBuffer<ChunkPos, ChunkVertex>,
+        chunk_index_buffers: &MultiBuffer<ChunkPos, u32>,
+        chunks: &[ChunkPos],
+        chunk_origins: &wgpu::Buffer,
+    ) {
+        let mut count = 0;
+        for (i, cascade) in self.cascades.iter().enumerate() {
+            let view_proj: [[f32; 4]; 4] =
+                nalgebra::convert::<Matrix4<f64>, Matrix4<f32>>(cascade.view_proj).into();
+            uploader.upload_buffer(device, encoder, &self.uniform_light_view_proj[i], 0, to_u8_slice(&view_proj));
+            let chunk_draws = &mut self.chunk_draws[i];
+            chunk_draws.update(device, encoder, uploader, chunk_vertex_buffers, chunk_index_buffers, chunks, |chunk_pos| {
+                cascade.may_shadow_chunk(chunk_pos)
+            });
+            count += chunk_draws.len();
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("shadow map"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[i],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &self.bind_groups[i], &[]);
+            rpass.set_vertex_buffer(0, chunk_vertex_buffers.get_buffer().slice(..));
+            rpass.set_vertex_buffer(1, chunk_origins.slice(..));
+            rpass.set_index_buffer(chunk_index_buffers.get_buffer().slice(..), wgpu::IndexFormat::Uint32);
+            chunk_draws.draw(&mut rpass);
+        }
+        send_debug_info(
+            "Render",
+            "shadowchunks",
+            format!("{} chunks were rendered in {} shadow maps", count, self.cascades.len()),
+        );
+    }
+}
+
+impl Cascade {
+    /// Check whether the chunk is in the box of the cascade, or between the box and the sun. This function may return
+    /// false positives.
+    fn may_shadow_chunk(&self, chunk_pos: ChunkPos) -> bool {
+        let half_chunk = CHUNK_SIZE as f64 / 2.0;
+        let chunk_center = Vector4::new(
+            chunk_pos.px as f64 * CHUNK_SIZE as f64 + half_chunk,
+            chunk_pos.py as f64 * CHUNK_SIZE as f64 + half_chunk,
+            chunk_pos.pz as f64 * CHUNK_SIZE as f64 + half_chunk,
+            1.0,
+        );
+        let chunk_center = (self.light_view * chunk_center).xyz();
+        let radius = half_chunk * 3.0f64.sqrt();
+        let max_distance = self.half_size + radius;
+        (chunk_center.x - self.center.x).abs() <= max_distance
+            && (chunk_center.y - self.center.y).abs() <= max_distance
+            && chunk_center.z - self.center.z <= max_distance + CASTER_DISTANCE
+            && self.center.z - chunk_center.z <= max_distance
+    }
+}
+
+const SHADOW_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
+    label: None,
+    entries: &[wgpu::BindGroupLayoutEntry {
+        // light view proj
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }],
+};
+
+/// Create the depth-only pipeline of the shadow pass. The depth is biased to avoid shadow acne.
+fn create_shadow_pipeline(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+    let vertex_shader_bytes = load_glsl_shader(ShaderStage::Vertex, "assets/shaders/shadow.vert");
+    let vertex_shader_module = device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::util::make_spirv(&vertex_shader_bytes),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+    let cache = pipeline_cache();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("shadow pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vertex_shader_module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ChunkVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    // Only the position is used
+                    attributes: &super::CHUNK_VERTEX_ATTRIBUTES[..1],
+                },
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<super::ChunkOrigin>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &super::CHUNK_ORIGIN_ATTRIBUTES,
+                },
+            ],
+        },
+        primitive: Default::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: Default::default(),
+            bias: wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: Default::default(),
+        fragment: None,
+        multiview: None,
+        cache: cache.as_deref(),
+    })
+}
@@ -0,0 +1,134 @@
+//! Automatic render distance, to hold a target frame rate.
+//!
+//! The frame time is smoothed over a fraction of a second, and the distance only changes when the frame rate leaves a
+//! band around the target. Raising the distance loads and meshes new chunks, which makes the next frames slower: if
+//! the distance has to be lowered right after being raised, the tuner waits longer before the next raise.
+
+use crate::settings::AutoRenderDistanceSettings;
+use common::player::RenderDistance;
+
+/// Seconds over which the frame time is averaged
+const SMOOTHING_SECONDS: f64 = 0.5;
+/// The distance is lowered below this fraction of the target frame rate
+const LOWER_THRESHOLD: f64 = 0.9;
+/// The distance is raised above this fraction of the target frame rate
+const RAISE_THRESHOLD: f64 = 1.15;
+/// Seconds to wait after lowering the distance, before changing it again
+const LOWER_COOLDOWN: f64 = 1.0;
+/// Seconds to wait after raising the distance, before raising it again. Doubled when a raise is quickly undone.
+const MIN_RAISE_COOLDOWN: f64 = 3.0;
+const MAX_RAISE_COOLDOWN: f64 = 60.0;
+/// Mipmap level bias of the block textures at the minimum distance
+const MAX_LOD_BIAS: f32 = 1.0;
+
+pub struct RenderDistanceTuner {
+    target_fps: f64,
+    min_distance: u64,
+    max_distance: u64,
+    distance: u64,
+    /// Smoothed duration of a frame, in seconds
+    average_frame_time: f64,
+    /// Seconds before the distance can change again
+    cooldown: f64,
+    raise_cooldown: f64,
+    /// Seconds since the last raise
+    since_raise: f64,
+}
+
+impl RenderDistanceTuner {
+    pub fn new(settings: &AutoRenderDistanceSettings, distance: u64) -> Self {
+        let min_distance = settings.min_distance.min(settings.max_distance);
+        let target_fps = settings.target_fps.max(1.0);
+        Self {
+            target_fps,
+            min_distance,
+            max_distance: settings.max_distance,
+            distance: distance.max(min_distance).min(settings.max_distance),
+            average_frame_time: 1.0 / target_fps,
+            cooldown: LOWER_COOLDOWN,
+            raise_cooldown: MIN_RAISE_COOLDOWN,
+            since_raise: f64::INFINITY,
+        }
+    }
+
+    /// Start again from `distance`, when the tuner is enabled again
+    pub fn reset(&mut self, distance: u64) {
+        self.distance = distance.max(self.min_distance).min(self.max_distance);
+        self.average_frame_time = 1.0 / self.target_fps;
+        self.cooldown = LOWER_COOLDOWN;
+        self.raise_cooldown = MIN_RAISE_COOLDOWN;
+        self.since_raise = f64::INFINITY;
+    }
+
+    /// Record the duration of a frame, and return the new distance if it changed
+    pub fn update(&mut self, seconds_delta: f64) -> Option<u64> {
+        let smoothing = (seconds_delta / SMOOTHING_SECONDS).min(1.0);
+        self.average_frame_time += (seconds_delta - self.average_frame_time) * smoothing;
+        self.cooldown -= seconds_delta;
+        self.since_raise += seconds_delta;
+        if self.cooldown > 0.0 {
+            return None;
+        }
+
+        let fps = self.fps();
+        if fps < self.target_fps * LOWER_THRESHOLD && self.distance > self.min_distance {
+            if self.since_raise < 2.0 * self.raise_cooldown {
+                self.raise_cooldown = (2.0 * self.raise_cooldown).min(MAX_RAISE_COOLDOWN);
+            }
+            self.distance -= 1;
+            self.cooldown = LOWER_COOLDOWN;
+            Some(self.distance)
+        } else if fps > self.target_fps * RAISE_THRESHOLD && self.distance < self.max_distance {
+            self.distance += 1;
+            self.cooldown = self.raise_cooldown;
+            self.since_raise = 0.0;
+            Some(self.distance)
+        } else {
+            None
+        }
+    }
+
+    /// The render distance, in chunks
+    pub fn distance(&self) -> u64 {
+        self.distance
+    }
+
+    /// Bias of the mipmap level of the block textures: the closer to the minimum distance, the blurrier
+    pub fn lod_bias(&self) -> f32 {
+        if self.max_distance == self.min_distance {
+            return 0.0;
+        }
+        let range = (self.max_distance - self.min_distance) as f32;
+        MAX_LOD_BIAS * (self.max_distance - self.distance) as f32 / range
+    }
+
+    /// The smoothed frame rate
+    pub fn fps(&self) -> f64 {
+        1.0 / self.average_frame_time.max(1e-6)
+    }
+
+    /// The state of the tuner, for the debug info
+    pub fn summary(&self) -> String {
+        format!(
+            "Auto render distance: {} chunks ({}..={}), texture LOD bias {:.2}\n{:.0} fps, target {:.0}",
+            self.distance,
+            self.min_distance,
+            self.max_distance,
+            self.lod_bias(),
+            self.fps(),
+            self.target_fps,
+        )
+    }
+}
+
+/// The same render distance in every direction
+pub fn cubic_render_distance(chunks: u64) -> RenderDistance {
+    RenderDistance {
+        x_max: chunks,
+        x_min: chunks,
+        y_max: chunks,
+        y_min: chunks,
+        z_max: chunks,
+        z_min: chunks,
+    }
+}
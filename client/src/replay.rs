@@ -0,0 +1,83 @@
+//! Replay mode: the messages of a recorded session are played back while the camera flies freely.
+//! See `common::network::replay` for the recording.
+
+use common::network::replay::ReplayControl;
+use common::player::PlayerInput;
+use nalgebra::Vector3;
+
+/// Speed of the free camera, in blocks per second
+const FREE_CAMERA_SPEED: f64 = 20.0;
+/// Seconds skipped by the seek keys
+pub const SEEK_STEP: f64 = 10.0;
+
+pub struct ReplayMode {
+    pub control: ReplayControl,
+    /// Position of the free camera, starting at the camera of the recorded player
+    camera: Option<Vector3<f64>>,
+    /// The position of the timeline under the cursor while the timeline is dragged
+    scrub_target: Option<f64>,
+}
+
+impl ReplayMode {
+    pub fn new(control: ReplayControl) -> Self {
+        Self {
+            control,
+            camera: None,
+            scrub_target: None,
+        }
+    }
+
+    pub fn camera_position(&self) -> Option<Vector3<f64>> {
+        self.camera
+    }
+
+    /// Move the free camera with the movement keys, horizontally in the direction of the yaw.
+    /// The camera starts at `start` once the recorded player is known.
+    pub fn move_camera(&mut self, input: PlayerInput, seconds_delta: f64, start: Option<Vector3<f64>>) {
+        if self.camera.is_none() {
+            self.camera = start;
+        }
+        let camera = match self.camera.as_mut() {
+            Some(camera) => camera,
+            None => return,
+        };
+        let yaw = input.yaw.to_radians();
+        let forward = Vector3::new(-yaw.sin(), 0.0, -yaw.cos());
+        let right = Vector3::new(yaw.cos(), 0.0, -yaw.sin());
+        let mut direction = Vector3::zeros();
+        let keys = [
+            (input.key_move_forward, forward),
+            (input.key_move_backward, -forward),
+            (input.key_move_right, right),
+            (input.key_move_left, -right),
+            (input.key_move_up, Vector3::y()),
+            (input.key_move_down, -Vector3::y()),
+        ];
+        for (pressed, key_direction) in keys.iter() {
+            if *pressed {
+                direction += key_direction;
+            }
+        }
+        if direction.norm() > 0.0 {
+            *camera += direction.normalize() * FREE_CAMERA_SPEED * seconds_delta;
+        }
+    }
+
+    /// Seek relatively to the current position. Return true if the state of the game must be reset.
+    pub fn skip(&self, seconds: f64) -> bool {
+        self.control.seek(self.control.position() + seconds)
+    }
+
+    /// Follow the cursor while the timeline is dragged, the position only changes when the cursor moves.
+    /// Return true if the state of the game must be reset.
+    pub fn scrub(&mut self, target: Option<f64>) -> bool {
+        if target == self.scrub_target {
+            return false;
+        }
+        self.scrub_target = target;
+        match target {
+            Some(position) => self.control.seek(position),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! List of the saved servers, with their status and latency queried in the background
+
+use crate::gui::Gui;
+use crate::settings::SavedServer;
+use common::network::status::{query_status, ServerStatus};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Seconds between two status queries of the servers while the list is shown
+const REFRESH_INTERVAL: f64 = 5.0;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const MARGIN: i32 = 10;
+const LINE_HEIGHT: i32 = 22;
+const BACKGROUND_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 0.8];
+const TITLE_COLOR: [f32; 4] = [0.6, 0.9, 1.0, 1.0];
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const PENDING_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const ONLINE_COLOR: [f32; 4] = [0.4, 1.0, 0.4, 1.0];
+const OFFLINE_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+
+/// The result of the last status query of a server
+enum ServerState {
+    Pending,
+    Online(ServerStatus, Duration),
+    Offline(String),
+}
+
+pub struct ServerList {
+    servers: Vec<(SavedServer, Arc<Mutex<ServerState>>)>,
+    /// Seconds before the servers are queried again
+    time_until_refresh: f64,
+}
+
+impl ServerList {
+    pub fn new(servers: &[SavedServer]) -> Self {
+        Self {
+            servers: servers
+                .iter()
+                .map(|server| (server.clone(), Arc::new(Mutex::new(ServerState::Pending))))
+                .collect(),
+            time_until_refresh: 0.0,
+        }
+    }
+
+    /// Query the status of the servers regularly. Only called while the list is shown.
+    pub fn update(&mut self, seconds_delta: f64) {
+        self.time_until_refresh -= seconds_delta;
+        if self.time_until_refresh > 0.0 {
+            return;
+        }
+        self.time_until_refresh = REFRESH_INTERVAL;
+        for (server, state) in self.servers.iter() {
+            let address = server.address.clone();
+            let state = state.clone();
+            // The previous status stays shown until the new one arrives
+            std::thread::spawn(move || {
+                let new_state = match query_status(&address, QUERY_TIMEOUT) {
+                    Ok((status, ping)) => ServerState::Online(status, ping),
+                    Err(e) => ServerState::Offline(format!("{:#}", e)),
+                };
+                *state.lock().unwrap() = new_state;
+            });
+        }
+    }
+
+    /// Draw the list at the top of the window, above the buttons of the menu
+    pub fn render(&self, gui: &mut Gui, window_width: i32) {
+        let width = 700;
+        let lines = self.servers.len().max(1) as i32 * 2;
+        let height = (lines + 2) * LINE_HEIGHT + 2 * MARGIN;
+        let x = (window_width - width) / 2;
+        let y = MARGIN;
+        gui.rect(x, y, width, height, BACKGROUND_COLOR, 0.004);
+        gui.text(x + MARGIN, y + MARGIN, LINE_HEIGHT, "Servers".to_owned(), TITLE_COLOR, 0.003);
+        if self.servers.is_empty() {
+            let text = "No saved servers, add them to the settings".to_owned();
+            gui.text(x + MARGIN, y + MARGIN + 2 * LINE_HEIGHT, LINE_HEIGHT, text, PENDING_COLOR, 0.003);
+        }
+        for (i, (server, state)) in self.servers.iter().enumerate() {
+            let line_y = y + MARGIN + (2 * i as i32 + 2) * LINE_HEIGHT;
+            let (status, color) = match &*state.lock().unwrap() {
+                ServerState::Pending => ("Querying...".to_owned(), PENDING_COLOR),
+                ServerState::Online(status, ping) => (
+                    format!(
+                        "{}/{} players - {} ms - version {} - {}",
+                        status.players,
+                        status.max_players,
+                        ping.as_millis(),
+                        status.version,
+                        status.motd
+                    ),
+                    ONLINE_COLOR,
+                ),
+                ServerState::Offline(error) => (format!("Offline: {}", error), OFFLINE_COLOR),
+            };
+            let title = format!("{} ({})", server.name, server.address);
+            gui.text(x + MARGIN, line_y, LINE_HEIGHT, title, TEXT_COLOR, 0.003);
+            gui.text(x + 2 * MARGIN, line_y + LINE_HEIGHT, LINE_HEIGHT, status, color, 0.003);
+        }
+    }
+}
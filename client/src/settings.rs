@@ -1,7 +1,11 @@
+use crate::gui::palette::ColorPalette;
+use crate::render::ShadowQuality;
+use common::theme::DEFAULT_THEME;
 use anyhow::{Context, Result};
 use log::info;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::OpenOptions,
     io::{Read, Write},
     path::Path,
@@ -53,7 +57,188 @@ fn write_settings(path: impl AsRef<Path>, settings: &Settings) -> Result<()> {
 pub struct Settings {
     pub window_size: (u32, u32),
     pub invert_mouse: bool,
+    /// Turn the camera with the raw motion of the mouse, without the acceleration of the system
+    pub raw_mouse_input: bool,
     pub render_distance: (u64,u64,u64,u64,u64,u64),
+    /// Change the render distance to hold a target frame rate
+    pub auto_render_distance: AutoRenderDistanceSettings,
+    /// Milliseconds spent uploading chunk meshes to the GPU in a frame. The other meshes wait for the next frames.
+    pub mesh_upload_budget_ms: f64,
+    /// Number of threads meshing the chunks
+    pub meshing_threads: usize,
+    pub player_name: String,
+    /// The name of a character model of the game data, or the path to a custom `.vox` model
+    pub character_model: String,
+    /// Measure the time spent by the GPU on each pass, if the GPU supports it
+    pub gpu_timing: bool,
+    pub graphics: GraphicsSettings,
+    /// The quality of the shadows of the sun: off, low, medium or high
+    pub shadows: ShadowQuality,
+    /// Compute smooth light and ambient occlusion on the GPU instead of using the light of the vertices
+    pub light_volumes: bool,
+    /// Anisotropic filtering of the block textures, from 1 (off) to 16. Above 1, the textures are filtered linearly.
+    pub texture_anisotropy: u16,
+    pub post_processing: PostProcessSettings,
+    /// How much the camera shakes when the player is hurt, from 0 (no shake) to 1. It can be changed with the slider
+    /// of the menu.
+    pub camera_shake: f64,
+    /// Volume of the sound effects, from 0 to 1
+    pub sound_volume: f32,
+    pub accessibility: AccessibilitySettings,
+    /// The theme of the interface, one of the `themes` of the game data. The default theme is used if the data has
+    /// no theme with this name.
+    pub theme: String,
+    /// The servers shown in the server list
+    pub servers: Vec<SavedServer>,
+    /// Keys bound to console commands, such as `F7 = "debugdraw wireframe; debugdraw chunks"`. The commands
+    /// separated by `;` run in order. The keys are letters, digits, or function keys from F1 to F12.
+    pub macros: BTreeMap<String, String>,
+}
+
+/// The GPU drawing the game and the API used to talk to it. They are chosen when the game starts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// The graphics API: auto, vulkan, dx12, metal or gl
+    pub backend: GraphicsBackend,
+    /// The name of the adapter to use, among the adapters listed in the log and in the debug overlay. If it is empty,
+    /// or if this adapter can't draw to the window, the adapter is chosen with the power preference.
+    pub adapter: String,
+    /// Which adapter to choose when none is named: high_performance for the most powerful GPU, or low_power for the
+    /// integrated GPU of the laptops, which saves the battery
+    pub power_preference: PowerPreference,
+    /// Use the compatibility mode of the weak GPUs even if the adapter doesn't need it: no multisampling, shadows,
+    /// light volumes or post-processing, and fewer mipmaps
+    pub compatibility_mode: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphicsBackend {
+    /// The best API of the platform
+    #[default]
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl GraphicsBackend {
+    /// The backends that wgpu may use
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            Self::Auto => wgpu::Backends::all(),
+            Self::Vulkan => wgpu::Backends::VULKAN,
+            Self::Dx12 => wgpu::Backends::DX12,
+            Self::Metal => wgpu::Backends::METAL,
+            Self::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerPreference {
+    #[default]
+    HighPerformance,
+    LowPower,
+}
+
+impl PowerPreference {
+    pub fn to_wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            Self::HighPerformance => wgpu::PowerPreference::HighPerformance,
+            Self::LowPower => wgpu::PowerPreference::LowPower,
+        }
+    }
+}
+
+/// The screen-space effects applied to the world
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PostProcessSettings {
+    /// Map the bright colors to the screen with a filmic curve instead of clamping them
+    pub tone_mapping: bool,
+    /// Multiplier of the colors before the tone mapping
+    pub exposure: f32,
+    /// Brightness of the midtones, applied on the linear colors before the sRGB encoding. Above 1, the midtones are
+    /// brighter. It can be changed with the slider of the menu.
+    pub gamma: f32,
+    /// Tint the screen when the camera is in water or in lava
+    pub color_filters: bool,
+    /// Smooth the edges that the multisampling misses, such as the edges inside the textures
+    pub fxaa: bool,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        Self {
+            tone_mapping: true,
+            exposure: 1.0,
+            gamma: 1.0,
+            color_filters: true,
+            fxaa: false,
+        }
+    }
+}
+
+/// Options for the players with impaired sight or hearing, or sensitive to the flashing lights. They can be changed
+/// with the buttons of the menu.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    /// The colors of the interface: default, red_green or blue_yellow, the last two being safe for colorblind players
+    pub palette: ColorPalette,
+    /// Show a caption when an important sound plays, such as a dust storm rising or the oxygen alarm
+    pub sound_cues: bool,
+    /// Dim the flashes of the explosions and the damage vignette
+    pub reduce_flashing: bool,
+    /// Never shake the camera, whatever the camera shake setting
+    pub reduce_screen_shake: bool,
+    /// Size of the text of the interface, 1 being the normal size
+    pub font_scale: f32,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            palette: ColorPalette::default(),
+            sound_cues: false,
+            reduce_flashing: false,
+            reduce_screen_shake: false,
+            font_scale: 1.0,
+        }
+    }
+}
+
+/// The bounds of the automatic render distance
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AutoRenderDistanceSettings {
+    pub enabled: bool,
+    pub target_fps: f64,
+    /// The render distance stays between these numbers of chunks
+    pub min_distance: u64,
+    pub max_distance: u64,
+}
+
+impl Default for AutoRenderDistanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_fps: 60.0,
+            min_distance: 4,
+            max_distance: 16,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedServer {
+    pub name: String,
+    /// The address of the status listener of the server, such as `example.com:25567`
+    pub address: String,
 }
 
 impl Default for Settings {
@@ -61,7 +246,25 @@ impl Default for Settings {
         Self {
             window_size: (1600, 900),
             invert_mouse: false,
+            raw_mouse_input: true,
             render_distance: (0,0,0,0,0,0),
+            auto_render_distance: AutoRenderDistanceSettings::default(),
+            mesh_upload_budget_ms: 2.0,
+            meshing_threads: 2,
+            player_name: "Player".to_owned(),
+            character_model: common::player::DEFAULT_CHARACTER_MODEL.to_owned(),
+            gpu_timing: false,
+            graphics: GraphicsSettings::default(),
+            shadows: ShadowQuality::default(),
+            light_volumes: false,
+            texture_anisotropy: 1,
+            post_processing: PostProcessSettings::default(),
+            camera_shake: 0.5,
+            sound_volume: 1.0,
+            accessibility: AccessibilitySettings::default(),
+            theme: DEFAULT_THEME.to_owned(),
+            servers: Vec::new(),
+            macros: BTreeMap::new(),
         }
     }
 }
\ No newline at end of file
@@ -2,91 +2,323 @@ use anyhow::Result;
 use log::info;
 
 use common::{
-    block::Block,
-    network::{messages::ToClient, messages::ToServer, Client, ClientEvent},
-    player::RenderDistance,
-    registry::Registry,
-    world::BlockPos,
+    achievement::Achievement,
+    animation::{AnimationSet, HEAD_PART, IDLE_ANIMATION, MINE_ANIMATION, WALK_ANIMATION},
+    block::{get_block_dynamic_lights, get_solid_blocks, Block, BlockType},
+    claim::ClaimInfo,
+    combat::Combatant,
+    effect::StatusEffect,
+    inventory::{Inventory, STORAGE_CRATE_BLOCK},
+    meteor::MeteorInfo,
+    network::{
+        dummy::{get_network_conditions, set_network_conditions, NetworkConditions},
+        messages::ToClient,
+        messages::ToServer,
+        replay::ReplayControl,
+        stats::NetworkStats,
+        Client, ClientEvent,
+    },
+    player::{
+        CharacterModel, PlayerCapabilities, PlayerId, PlayerProfile, RenderDistance, Vitals, DEFAULT_CHARACTER_MODEL,
+        MAX_VITAL, MELEE_REACH, PLAYER_INVENTORY_SLOTS,
+    },
+    mob::{MobInfo, MobState, MOB_SIDE},
+    registry::{namespaced_key, Registry},
+    robot::{RobotId, RobotInfo, RobotState, ROBOT_SIDE},
+    statistics::PlayerStatistics,
+    trade::Trade,
+    world::{BlockPos, DimensionId},
 };
 
-use crate::input::YawPitch;
+use crate::input::{
+    YawPitch, EMOTE_WAVE, HOTBAR_SLOTS, MARK_MINING_AREA, REPLAY_FASTER, REPLAY_SEEK_BACKWARD, REPLAY_SEEK_FORWARD,
+    REPLAY_SLOWER, REPLAY_TOGGLE_PAUSE, TOGGLE_ACHIEVEMENTS, TOGGLE_CONSOLE, TOGGLE_INVENTORY, TOGGLE_LIGHT,
+    TOGGLE_ROBOT_CONTROL, TOGGLE_STATISTICS, TOGGLE_TRACE_RECORDING,
+};
 //use crate::model::model::Model;
 //use crate::world::meshing::ChunkMeshData;
-use crate::render::{Frustum, UiRenderer, WorldRenderer};
+use crate::render::{
+    Billboard, BillboardRenderer, ColorFilter, DebugBox, DebugRenderModes, Frustum, GpuTimer, IconAtlas,
+    PointLight, PostProcessing, ShadowQuality, Spot, UiRenderer, Uploader, WorldRenderer,
+};
+use crate::render::world::{ALLOWED_CLAIM_COLOR, ENTITY_BOX_COLOR, PROTECTED_CLAIM_COLOR};
 use crate::window::WindowBuffers;
 use crate::{
+    animation::{character_animations, posed_models, Animator},
+    camera_path::{CameraPath, CameraPathPlayback, Keyframe, DEFAULT_SEGMENT_DURATION},
+    console::{Console, ERROR_COLOR, INFO_COLOR, SERVER_COLOR},
+    damage::HitFeedback,
+    errorscreen::ErrorScreen,
+    loading::{load_data_pack, LoadingProgress, DATA_CACHE_FOLDER, SPAWN_AREA_RADIUS},
+    macros::Macros,
+    serverlist::ServerList,
     fps::FpsCounter,
     input::InputState,
-    settings::Settings,
+    particles::{get_unit_mesh, scale_mesh, Particles},
+    render_distance::{cubic_render_distance, RenderDistanceTuner},
+    replay::{ReplayMode, SEEK_STEP},
+    settings::{AccessibilitySettings, Settings},
+    sound::{SoundCues, Sounds},
+    teleport::TeleportFade,
     ui::Ui,
     window::{State, StateTransition, WindowData, WindowFlags},
     world::World,
 };
 use nalgebra::Vector3;
-use std::time::Instant;
-use common::data::vox::VoxelModel;
-use common::debug::{send_debug_info, send_perf_breakdown, DebugInfo};
-use common::item::{Item, ItemMesh};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use common::data::{
+    cache::DataCache,
+    pack::DataPackDownload,
+    vox::{load_voxel_model, replace_model, VoxelModel},
+    Data, TextureArray,
+};
+use common::debug::{profiler::Profiler, send_debug_info, send_perf_breakdown, DebugInfo};
+use common::profile_scope;
+use common::item::{Item, ItemMesh, ItemStack, ItemType};
+use common::physics::aabb::AABB;
 use common::physics::simulation::{ClientPhysicsSimulation, PhysicsState, ServerState};
-use common::time::BreakdownCounter;
+use common::time::{BreakdownCounter, FixedTimestep, WorldTime, TICK_DURATION};
 use winit::event::{ElementState, MouseButton};
-use crate::gui::Gui;
+use crate::gui::inventory::{InventoryAction, InventoryScreen};
+use crate::gui::sign::{SignAction, SignEditor};
+use crate::gui::trade::TradeAction;
+use crate::gui::{Gui, Navigation};
+
+/// Size of the items lying in the world, in blocks
+const DROPPED_ITEM_SIZE: f32 = 0.4;
+/// Size of the meteors, in blocks
+const METEOR_SIZE: f32 = 2.0;
+/// Height of the name tags above the players' heads, in blocks
+const NAME_TAG_HEIGHT: f64 = 0.2;
+/// Height of the text of the name tags, in blocks
+const NAME_TAG_SIZE: f64 = 0.3;
+/// Height of the robot labels above the center of the robots, in blocks
+const ROBOT_LABEL_HEIGHT: f64 = 0.8;
+/// The model drawn for the robots, fitted to their size
+const ROBOT_MODEL: &str = "robot";
+/// The emote played with its own key
+const WAVE_EMOTE: &str = "wave";
+/// How fast the haze changes to the one of the biome of the camera, per second
+const BIOME_HAZE_SMOOTHING: f64 = 0.5;
+/// Height of the creature labels above the center of the creatures, in blocks
+const MOB_LABEL_HEIGHT: f64 = 0.7;
+/// Color of the labels of the creatures that are not attacking
+const MOB_LABEL_COLOR: [f32; 4] = [0.9, 0.9, 0.8, 1.0];
+/// Color of the labels of the creatures chasing or attacking a target
+const ANGRY_MOB_LABEL_COLOR: [f32; 4] = [1.0, 0.35, 0.25, 1.0];
+/// Color of the lamps of the robots
+const ROBOT_LIGHT_COLOR: [f32; 3] = [0.8, 0.9, 1.0];
+/// Radius of the light of the lamps of the robots, in blocks
+const ROBOT_LIGHT_RADIUS: f32 = 8.0;
+/// Color of the flashlights and of the headlights of the robots
+const SPOT_LIGHT_COLOR: [f32; 3] = [1.0, 0.95, 0.8];
+/// Distance lit by the flashlights, in blocks
+const FLASHLIGHT_RANGE: f32 = 20.0;
+/// Distance lit by the headlights of the robots, in blocks
+const HEADLIGHTS_RANGE: f32 = 16.0;
+/// Angle between the axis and the side of the cone of the flashlights and of the headlights, in degrees
+const SPOT_LIGHT_ANGLE: f64 = 25.0;
+/// Distance up to which the light-emitting blocks light their surroundings, in blocks
+const BLOCK_LIGHT_DISTANCE: f64 = 96.0;
+/// Seconds between two updates of the statistics while they are shown
+const STATISTICS_REFRESH_INTERVAL: f64 = 1.0;
+/// Seconds between two updates of the network statistics, and between two pings
+const NETWORK_STATS_INTERVAL: f64 = 1.0;
+/// The borders of the claims are drawn up to this distance from the camera
+const CLAIM_BORDER_DISTANCE: f64 = 64.0;
+/// The trade screen closes when the camera is further from the supply terminal, a bit less than the reach allowed by
+/// the server. So does the sign editor with its sign.
+const TRADE_DISTANCE: f64 = 10.0;
+/// Height of the bottom of the texts of the signs above the signs, in blocks
+const SIGN_TEXT_HEIGHT: f64 = 0.1;
+/// Height of the text of the signs, in blocks
+const SIGN_TEXT_SIZE: f64 = 0.2;
+/// The texts of the signs are wrapped to lines of at most this width, in blocks
+const SIGN_TEXT_WIDTH: f64 = 2.0;
+/// Color of the texts of the signs
+const SIGN_TEXT_COLOR: [f32; 4] = [1.0, 0.95, 0.8, 1.0];
+/// The explosions are heard up to this distance from the camera for each unit of power
+const EXPLOSION_HEARING_DISTANCE: f64 = 16.0;
 
 /// State of a singleplayer world
 pub struct SinglePlayer {
     fps_counter: FpsCounter,
     ui: Ui,
     ui_renderer: UiRenderer,
+    billboard_renderer: BillboardRenderer,
+    post_processing: PostProcessing,
+    /// The staging belt of all the uploads to the GPU
+    uploader: Uploader,
     gui: Gui,
     world: World,
-    #[allow(dead_code)] // TODO: remove this
     block_registry: Registry<Block>,
     item_registry: Registry<Item>,
     item_meshes: Vec<ItemMesh>,
+    /// The icons of the items, baked from their models when the data is loaded
+    icon_atlas: IconAtlas,
     model_registry: Registry<VoxelModel>,
+    /// The animations of the models, by model name
+    animations: BTreeMap<String, AnimationSet>,
+    /// Kept to recreate the renderer if the GPU device is lost
+    textures: TextureArray,
+    /// Whether the block textures and the item icons were reloaded and must be uploaded to the GPU
+    textures_changed: bool,
     client: Box<dyn Client>,
     render_distance: RenderDistance,
+    /// Changes the render distance to hold the target frame rate, if `auto_render_distance` is enabled
+    render_distance_tuner: RenderDistanceTuner,
+    auto_render_distance: bool,
     // TODO: put this in the settigs
     physics_simulation: ClientPhysicsSimulation,
+    timestep: FixedTimestep,
+    /// Camera position before the last physics tick, to interpolate the camera between two ticks
+    previous_camera_position: Option<Vector3<f64>>,
     yaw_pitch: YawPitch,
     debug_info: DebugInfo,
+    profiler: Profiler,
+    /// Created on the first frame if GPU timing is enabled
+    gpu_timer: Option<GpuTimer>,
+    /// The shadow maps are recreated on the next frame when it changes
+    shadow_quality: ShadowQuality,
+    /// The gamma set with the brightness slider of the menu, saved in the settings on the next update
+    new_gamma: Option<f32>,
+    /// The intensity set with the camera shake slider of the menu, saved in the settings on the next update
+    new_camera_shake: Option<f64>,
+    /// The accessibility options changed in the menu, saved in the settings on the next update
+    new_accessibility: Option<AccessibilitySettings>,
+    /// The renderer settings that can't change in game, kept to recreate the renderer
+    light_volumes: bool,
+    texture_anisotropy: u16,
     start_time: Instant,
     client_timing: BreakdownCounter,
+    /// The first corner of the mining area being marked
+    mining_area_corner: Option<BlockPos>,
+    robots: Vec<RobotInfo>,
+    dropped_items: Vec<(Vector3<f64>, ItemStack)>,
+    /// The robot the camera is bound to, if any
+    controlled_robot: Option<RobotId>,
+    /// What the server allows the player to do
+    capabilities: PlayerCapabilities,
+    /// The claims of the current dimension, and whether their borders are drawn
+    claims: Vec<ClaimInfo>,
+    show_claims: bool,
+    vitals: Vitals,
+    /// The status effects of the player, whose durations are counted down between the updates of the server
+    status_effects: Vec<StatusEffect>,
+    inventory: Inventory,
+    selected_slot: usize,
+    /// The seconds since the selected item started being used, and the seconds the use lasts
+    item_use: Option<(f64, f64)>,
+    world_time: WorldTime,
+    particles: Particles,
+    meteors: Vec<MeteorInfo>,
+    mobs: Vec<MobInfo>,
+    player_id: PlayerId,
+    player_profiles: HashMap<PlayerId, PlayerProfile>,
+    /// The model of each player's character
+    character_meshes: HashMap<PlayerId, u32>,
+    /// The animation state of each player's character and of each robot
+    animators: HashMap<PlayerId, Animator>,
+    robot_animators: HashMap<RobotId, Animator>,
+    /// The players whose flashlight is on
+    flashlights: HashSet<PlayerId>,
+    /// The models that were received from the server and must be uploaded to the GPU
+    models_to_upload: Vec<u32>,
+    unlocked_achievements: Vec<Achievement>,
+    /// The recently unlocked achievements, and the seconds during which they are still shown
+    achievement_toasts: Vec<(Achievement, f64)>,
+    /// A short message of the server shown above the hotbar, and the seconds during which it is still shown
+    notice: Option<(String, f64)>,
+    /// The number of players resting in a respawn pod to skip the night, and the number of connected players
+    resting_players: (u32, u32),
+    show_achievements: bool,
+    statistics: PlayerStatistics,
+    /// Seconds before the statistics are requested again, None if they are not shown
+    statistics_refresh: Option<f64>,
+    data_cache: DataCache,
+    /// The hash of the game data requested from the server, to check and cache the data when it arrives
+    requested_data_hash: Option<u64>,
+    /// The data pack of the new game data of the server, while it is downloaded
+    data_download: Option<DataPackDownload>,
+    /// The messages received by the `LoadingScreen` before the game started, handled first
+    pending_messages: VecDeque<ToClient>,
+    /// The supply terminal the player is trading with and its trades, until the trade screen is closed
+    trade: Option<(BlockPos, Vec<Trade>)>,
+    /// The inventory screen, while it is open
+    inventory_screen: Option<InventoryScreen>,
+    /// The container the player opened and its content, until the inventory screen is closed
+    container: Option<(BlockPos, Inventory)>,
+    /// The texts of the signs of the dimension of the player
+    signs: Vec<(BlockPos, String)>,
+    /// The text of the sign being written, until the sign editor is closed
+    sign_editor: Option<SignEditor>,
+    console: Console,
+    /// The keys bound to console commands in the settings
+    macros: Macros,
+    show_debug_info: bool,
+    debug_modes: DebugRenderModes,
+    server_list: ServerList,
+    /// The reason of the kick, if the server kicked the player
+    kicked: Option<String>,
+    /// The statistics of the network at the last update, to compute the bandwidth
+    previous_network_stats: (Instant, NetworkStats),
+    /// The number and time of the last ping sent to the server
+    last_ping: (u32, Instant),
+    /// Round-trip time of the last ping answered by the server
+    round_trip_time: Option<Duration>,
+    /// Set if a replay is played instead of a live game
+    replay: Option<ReplayMode>,
+    /// The keyframes of the cinematic camera path being placed, and the playback of a path
+    camera_path: CameraPath,
+    camera_path_playback: Option<CameraPathPlayback>,
+    /// The progress of joining the world, until the chunks around the player are received and meshed
+    world_loading: Option<LoadingProgress>,
+    teleport_fade: TeleportFade,
+    hit_feedback: HitFeedback,
+    sounds: Sounds,
+    /// The captions of the important sounds, shown if enabled in the accessibility settings
+    sound_cues: SoundCues,
+    /// The haze of the biomes around the camera, changing smoothly from one biome to the next
+    biome_haze: ColorFilter,
+    /// The dimension of the camera, the chunks of the other dimensions are ignored
+    dimension: DimensionId,
 }
 
 impl SinglePlayer {
-    pub fn new_factory(client: Box<dyn Client>) -> crate::window::StateFactory {
-        Box::new(move |settings, device| Self::new(settings, device, client))
+    /// Start the game with the data and the id of the player, received by the `LoadingScreen` with `pending_messages`.
+    /// The `progress` of the loading is shown until the chunks around the player are ready.
+    pub fn new_factory(
+        client: Box<dyn Client>,
+        replay: Option<ReplayControl>,
+        data: Data,
+        player_id: PlayerId,
+        pending_messages: Vec<ToClient>,
+        progress: LoadingProgress,
+    ) -> crate::window::StateFactory {
+        Box::new(move |settings, device| {
+            Self::new(settings, device, client, replay, data, player_id, pending_messages, progress)
+        })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         settings: &mut Settings,
         device: &mut wgpu::Device,
         mut client: Box<dyn Client>,
+        replay: Option<ReplayControl>,
+        data: Data,
+        player_id: PlayerId,
+        pending_messages: Vec<ToClient>,
+        progress: LoadingProgress,
     ) -> Result<(Box<dyn State>, wgpu::CommandBuffer)> {
         info!("Launching singleplayer");
-        // Wait for data and player_id from the server
-        let (data, player_id) = {
-            let mut data = None;
-            let mut player_id = None;
-            loop {
-                if data.is_some() && player_id.is_some() {
-                    break (data.unwrap(), player_id.unwrap());
-                }
-                match client.receive_event() {
-                    ClientEvent::ServerMessage(ToClient::GameData(game_data)) => {
-                        data = Some(game_data)
-                    }
-                    ClientEvent::ServerMessage(ToClient::CurrentId(id)) => player_id = Some(id),
-                    _ => (),
-                }
-            }
-        };
-        info!("Received game data from the server");
+        let data_cache = DataCache::new(PathBuf::from(DATA_CACHE_FOLDER));
 
         // Set render distance
         let (x1, x2, y1, y2, z1, z2) = settings.render_distance;
-        let render_distance = RenderDistance {
+        let mut render_distance = RenderDistance {
             x_max: x1,
             x_min: x2,
             y_max: y1,
@@ -94,33 +326,117 @@ impl SinglePlayer {
             z_max: z1,
             z_min: z2,
         };
+        let render_distance_tuner = RenderDistanceTuner::new(&settings.auto_render_distance, x1);
+        if settings.auto_render_distance.enabled {
+            render_distance = cubic_render_distance(render_distance_tuner.distance());
+        }
         client.send(ToServer::SetRenderDistance(render_distance));
+        // Tell the other players who we are
+        let model = if settings.character_model.ends_with(".vox") {
+            match load_voxel_model(&settings.character_model) {
+                Some(model) => CharacterModel::Custom(model),
+                None => {
+                    log::warn!("Couldn't load character model {}", settings.character_model);
+                    CharacterModel::Builtin(DEFAULT_CHARACTER_MODEL.to_owned())
+                }
+            }
+        } else {
+            CharacterModel::Builtin(settings.character_model.clone())
+        };
+        client.send(ToServer::SetProfile(PlayerProfile {
+            name: settings.player_name.clone(),
+            model,
+        }));
         // Create the renderers
-        let ui_renderer = UiRenderer::new(device);
+        let mut ui_renderer = UiRenderer::new(device);
 
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut uploader = Uploader::new();
+
+        let icon_atlas = IconAtlas::bake(&data.item_meshes, &data.models);
+        ui_renderer.set_icon_atlas(device, &mut encoder, &mut uploader, &icon_atlas);
+        let mut gui = Gui::new();
+        gui.set_themes(data.themes);
 
         let world_renderer = WorldRenderer::new(
             device,
             &mut encoder,
-            data.texture_atlas,
+            &mut uploader,
+            &data.textures,
             &data.models,
+            settings.shadows,
+            settings.light_volumes,
+            settings.texture_anisotropy,
+        );
+
+        // The commands of the server are registered when the server sends them
+        let mut console = Console::new();
+        console.register_command("help", "List the commands");
+        console.register_command("clear", "Clear the console");
+        console.register_command(
+            "render_distance",
+            "Set the render distance: render_distance <chunks|auto>",
+        );
+        console.register_command("shadows", "Set the quality of the shadows: shadows <off|low|medium|high>");
+        console.register_command("debug", "Show or hide the debug info");
+        console.register_command(
+            "debugdraw",
+            "Toggle a debug render mode: debugdraw <wireframe|chunks|boxes|light>",
+        );
+        console.register_command("claims", "Show or hide the borders of the claims");
+        console.register_command("trace", "Start recording a trace, or write it");
+        console.register_command("achievements", "Show or hide the achievements");
+        console.register_command("stats", "Show or hide the statistics");
+        console.register_command("macros", "List the keys bound to commands in the settings");
+        console.register_command(
+            "emote",
+            "Play an animation of your character that the other players see: emote <name>, or emote to list them",
+        );
+        console.register_command("light", "Turn your flashlight, or the headlights of your robot, on or off");
+        console.register_command(
+            "campath",
+            "Place and play a cinematic camera path: campath add, undo, clear, play [seconds per keyframe], stop, \
+             save <name>, load <name>",
+        );
+        console.register_command(
+            "netsim",
+            "Simulate a bad connection: netsim <latency ms> <jitter ms> <drop %> <reorder %>, or netsim off",
         );
+        if replay.is_some() {
+            console.register_command("replay", "Control the replay: replay pause, replay play, replay seek <s>, replay speed <x>");
+        }
+        uploader.finish();
 
         Ok((
             Box::new(Self {
                 fps_counter: FpsCounter::new(),
                 ui: Ui::new(),
                 ui_renderer,
-                gui: Gui::new(),
-                world: World::new(data.meshes.clone(), world_renderer),
+                billboard_renderer: BillboardRenderer::new(device),
+                post_processing: PostProcessing::new(device),
+                uploader,
+                gui,
+                world: World::new(
+                    data.meshes.clone(),
+                    get_solid_blocks(&data.blocks),
+                    get_block_dynamic_lights(&data.blocks),
+                    world_renderer,
+                    Duration::from_secs_f64(settings.mesh_upload_budget_ms.max(0.0) / 1000.0),
+                    settings.meshing_threads,
+                ),
                 block_registry: data.blocks,
                 model_registry: data.models,
+                animations: data.animations,
+                textures: data.textures,
+                textures_changed: false,
                 item_registry: data.items,
                 item_meshes: data.item_meshes,
+                icon_atlas,
                 client,
                 render_distance,
+                render_distance_tuner,
+                auto_render_distance: settings.auto_render_distance.enabled,
                 physics_simulation: ClientPhysicsSimulation::new(
                     ServerState {
                         physics_state: PhysicsState::default(),
@@ -129,10 +445,78 @@ impl SinglePlayer {
                     },
                     player_id,
                 ),
+                timestep: FixedTimestep::new(TICK_DURATION),
+                previous_camera_position: None,
                 yaw_pitch: Default::default(),
                 debug_info: DebugInfo::new_current(),
+                profiler: Profiler::new_current(),
+                gpu_timer: None,
+                shadow_quality: settings.shadows,
+                new_gamma: None,
+                new_camera_shake: None,
+                new_accessibility: None,
+                light_volumes: settings.light_volumes,
+                texture_anisotropy: settings.texture_anisotropy,
                 start_time: Instant::now(),
                 client_timing: BreakdownCounter::new(),
+                mining_area_corner: None,
+                robots: Vec::new(),
+                dropped_items: Vec::new(),
+                controlled_robot: None,
+                capabilities: PlayerCapabilities::default(),
+                claims: Vec::new(),
+                show_claims: true,
+                vitals: Vitals::default(),
+                status_effects: Vec::new(),
+                inventory: Inventory::new(PLAYER_INVENTORY_SLOTS),
+                selected_slot: 0,
+                item_use: None,
+                world_time: WorldTime::default(),
+                particles: Particles::new(),
+                meteors: Vec::new(),
+                mobs: Vec::new(),
+                player_id,
+                player_profiles: HashMap::new(),
+                character_meshes: HashMap::new(),
+                animators: HashMap::new(),
+                robot_animators: HashMap::new(),
+                flashlights: HashSet::new(),
+                models_to_upload: Vec::new(),
+                unlocked_achievements: Vec::new(),
+                achievement_toasts: Vec::new(),
+                notice: None,
+                resting_players: (0, 0),
+                show_achievements: false,
+                statistics: PlayerStatistics::default(),
+                statistics_refresh: None,
+                data_cache,
+                requested_data_hash: None,
+                data_download: None,
+                pending_messages: pending_messages.into(),
+                trade: None,
+                inventory_screen: None,
+                container: None,
+                signs: Vec::new(),
+                sign_editor: None,
+                console,
+                macros: Macros::parse(&settings.macros),
+                show_debug_info: true,
+                debug_modes: DebugRenderModes::default(),
+                server_list: ServerList::new(&settings.servers),
+                kicked: None,
+                previous_network_stats: (Instant::now(), NetworkStats::default()),
+                last_ping: (0, Instant::now()),
+                round_trip_time: None,
+                replay: replay.map(ReplayMode::new),
+                camera_path: CameraPath::default(),
+                camera_path_playback: None,
+                world_loading: Some(progress),
+                teleport_fade: TeleportFade::default(),
+                hit_feedback: HitFeedback::default(),
+                sounds: Sounds::new(settings.sound_volume),
+                sound_cues: SoundCues::default(),
+                biome_haze: ColorFilter::NONE,
+                dimension: DimensionId::SURFACE,
             }),
             encoder.finish(),
         ))
@@ -140,53 +524,880 @@ impl SinglePlayer {
 
     fn handle_server_messages(&mut self) {
         loop {
-            match self.client.receive_event() {
+            let event = match self.pending_messages.pop_front() {
+                Some(message) => ClientEvent::ServerMessage(message),
+                None => self.client.receive_event(),
+            };
+            match event {
                 ClientEvent::NoEvent => break,
                 ClientEvent::ServerMessage(message) => match message {
-                    ToClient::Chunk(chunk, light_chunk) => {
-                        self.world.add_chunk(chunk, light_chunk);
+                    ToClient::Chunk(dimension, chunk, light_chunk) => {
+                        if dimension == self.dimension {
+                            self.world.add_chunk(chunk, light_chunk);
+                        }
                     }
                     ToClient::UpdatePhysics(server_state) => {
                         self.physics_simulation.receive_server_update(server_state);
                     }
-                    ToClient::GameData(_) => {}
+                    // The server reloaded the data
+                    ToClient::DataHash(hash) => {
+                        self.data_download = None;
+                        match self.data_cache.load(hash) {
+                            Some(data) => self.reload_data(data),
+                            None => {
+                                self.requested_data_hash = Some(hash);
+                                self.client.send(ToServer::RequestData);
+                            }
+                        }
+                    }
+                    ToClient::DataPack(hash, size) => match DataPackDownload::new(hash, size) {
+                        Ok(download) => self.data_download = Some(download),
+                        Err(e) => self.console.print(&format!("{:#}", e), ERROR_COLOR),
+                    },
+                    ToClient::DataPackChunk(bytes) => self.receive_data_pack_chunk(&bytes),
+                    ToClient::GameData(data) => self.reload_data(data),
+                    ToClient::DataErrors(_) => {}
                     ToClient::CurrentId(_) => {}
+                    ToClient::Content(_) => {}
+                    ToClient::UpdateRobots(robots) => {
+                        self.robots = robots;
+                    }
+                    ToClient::ControlRobot(robot) => {
+                        self.controlled_robot = robot;
+                    }
+                    ToClient::UpdateDroppedItems(dropped_items) => {
+                        self.dropped_items = dropped_items;
+                    }
+                    ToClient::UpdateTime(world_time) => {
+                        self.world_time = world_time;
+                    }
+                    ToClient::Explosion(center, power, debris) => {
+                        let distance = (center - self.get_camera_position()).norm();
+                        let loudness = 1.0 - distance / (power * EXPLOSION_HEARING_DISTANCE);
+                        if loudness > 0.0 {
+                            self.sounds.play_explosion(loudness as f32);
+                            self.sound_cues.add("[Explosion]");
+                        }
+                        let debris = debris.into_iter().map(|item| self.item_meshes[item as usize].clone()).collect();
+                        self.particles.spawn_explosion(center, power, debris);
+                    }
+                    ToClient::UpdateMeteors(meteors) => {
+                        self.meteors = meteors;
+                    }
+                    ToClient::UpdateMobs(mobs) => {
+                        self.mobs = mobs;
+                    }
+                    ToClient::PlayerProfile(id, profile) => {
+                        self.update_character_mesh(id, &profile);
+                        self.player_profiles.insert(id, profile);
+                    }
+                    ToClient::Achievements(achievements) => {
+                        self.unlocked_achievements = achievements;
+                    }
+                    ToClient::AchievementUnlocked(achievement) => {
+                        self.unlocked_achievements.push(achievement);
+                        self.achievement_toasts.push((achievement, crate::gui::achievements::TOAST_DURATION));
+                    }
+                    // The replay goes on after the kick
+                    ToClient::Kicked(reason) if self.replay.is_some() => {
+                        self.console.print(&format!("Kicked by the server: {}", reason), SERVER_COLOR);
+                    }
+                    ToClient::Kicked(reason) => {
+                        info!("Kicked by the server: {}", reason);
+                        self.kicked = Some(reason);
+                    }
+                    ToClient::ServerCommands(commands) => {
+                        // The list is sent again when the permissions of the player change
+                        self.console.unregister_commands("/");
+                        for (name, description) in commands {
+                            self.console.register_command(format!("/{}", name), description);
+                        }
+                    }
+                    ToClient::CommandOutput(output) => {
+                        self.console.print(&output, SERVER_COLOR);
+                    }
+                    ToClient::Statistics(statistics) => {
+                        self.statistics = statistics;
+                    }
+                    ToClient::Pong(ping) => {
+                        // The answers to the older pings are ignored
+                        if ping == self.last_ping.0 {
+                            self.round_trip_time = Some(self.last_ping.1.elapsed());
+                        }
+                    }
+                    ToClient::Teleporting(teleporting) => {
+                        self.teleport_fade.set_teleporting(teleporting);
+                    }
+                    ToClient::EnterDimension(dimension, name) => {
+                        self.dimension = dimension;
+                        self.world.clear_chunks();
+                        self.console.print(&format!("Entering the {}", name), SERVER_COLOR);
+                    }
+                    ToClient::WorldHeight(height) => self.world.set_height(height),
+                    ToClient::Capabilities(capabilities) => {
+                        self.capabilities = capabilities;
+                    }
+                    ToClient::Claims(claims) => self.claims = claims,
+                    ToClient::Vitals(vitals) => {
+                        let low_oxygen = crate::gui::hud::LOW_VITAL * MAX_VITAL;
+                        if vitals.oxygen < low_oxygen && self.vitals.oxygen >= low_oxygen {
+                            self.sounds.play_oxygen_alarm();
+                            self.sound_cues.add("[Oxygen alarm beeping]");
+                        }
+                        self.vitals = vitals;
+                    }
+                    ToClient::StatusEffects(effects) => self.status_effects = effects,
+                    ToClient::RespawnPointSet(pod) => {
+                        let text = format!("Respawn point set at {} {} {}", pod.px, pod.py + 1, pod.pz);
+                        self.console.print(&text, SERVER_COLOR);
+                        self.notice = Some((text, crate::gui::hud::NOTICE_DURATION));
+                    }
+                    ToClient::RestingPlayers(resting, online) => self.resting_players = (resting, online),
+                    ToClient::Inventory(inventory) => self.inventory = inventory,
+                    ToClient::UsingItem(use_time) => self.item_use = use_time.map(|use_time| (0.0, use_time)),
+                    ToClient::PlayAnimation(id, name) => self.animators.entry(id).or_default().play(&name),
+                    ToClient::Flashlight(id, true) => {
+                        self.flashlights.insert(id);
+                    }
+                    ToClient::Flashlight(id, false) => {
+                        self.flashlights.remove(&id);
+                    }
+                    ToClient::BlockDamage(pos, stage) => self.world.set_block_damage(pos, stage),
+                    ToClient::Trades(pos, trades) => self.trade = Some((pos, trades)),
+                    // The recorded player was asked for the text, not the viewer of the replay
+                    ToClient::EditSign(_, _) if self.replay.is_some() => (),
+                    ToClient::EditSign(pos, text) => self.sign_editor = Some(SignEditor::new(pos, text)),
+                    ToClient::Signs(signs) => self.signs = signs,
+                    ToClient::Container(pos, content) => {
+                        self.container = Some((pos, content));
+                        if self.inventory_screen.is_none() {
+                            self.inventory_screen = Some(InventoryScreen::new());
+                        }
+                    }
+                    ToClient::CloseContainer => self.container = None,
+                    ToClient::EntityDamaged(target, amount, from) => self.show_damage(target, amount, from),
+                    ToClient::DustStorm(true) => {
+                        self.sounds.play_storm();
+                        self.sound_cues.add("[Dust storm rising]");
+                    }
+                    ToClient::DustStorm(false) => self.sound_cues.add("[Wind dying down]"),
                 },
                 ClientEvent::Disconnected => unimplemented!("server disconnected"),
                 ClientEvent::Connected => {}
             }
         }
     }
+
+    /// Show the damage taken by an entity above it, or around the screen if the player or the robot they control was
+    /// hurt
+    fn show_damage(&mut self, target: Combatant, amount: f64, from: Option<Vector3<f64>>) {
+        // Above the name tags and the labels
+        let above = |height: f64| Vector3::new(0.0, height + NAME_TAG_SIZE, 0.0);
+        let pos = match target {
+            Combatant::Player(id) => self.physics_simulation.get_state().players.get(&id).map(|player| {
+                let aabb = &player.aabb;
+                aabb.pos + Vector3::new(aabb.size_x / 2.0, aabb.size_y, aabb.size_z / 2.0) + above(NAME_TAG_HEIGHT)
+            }),
+            Combatant::Robot(id) => {
+                self.robots.iter().find(|robot| robot.id == id).map(|robot| robot.pos + above(ROBOT_LABEL_HEIGHT))
+            }
+            Combatant::Mob(id) => {
+                self.mobs.iter().find(|mob| mob.id == id).map(|mob| mob.pos + above(MOB_LABEL_HEIGHT))
+            }
+        };
+        // The recorded player is seen from the free camera of the replays
+        let hurts_camera = self.replay.is_none()
+            && match target {
+                Combatant::Player(id) => id == self.player_id,
+                Combatant::Robot(id) => Some(id) == self.controlled_robot,
+                Combatant::Mob(_) => false,
+            };
+        if hurts_camera {
+            self.hit_feedback.hurt(amount, from);
+            self.sounds.play_hurt((amount / MAX_VITAL) as f32);
+        } else if let Some(pos) = pos {
+            let energy = matches!(target, Combatant::Robot(_));
+            self.hit_feedback.add_number(pos, amount, energy);
+        }
+    }
+
+    /// Find the model of a player's character, registering it if it is a custom model. Each player has a single
+    /// custom model, replaced with its meshes when their profile changes.
+    fn update_character_mesh(&mut self, id: PlayerId, profile: &PlayerProfile) {
+        let mesh_id = match &profile.model {
+            CharacterModel::Builtin(name) => self.model_registry.get_id_by_name(name),
+            CharacterModel::Custom(model) => {
+                let name = format!("custom:player_{}", id.raw());
+                let ids = replace_model(&mut self.model_registry, name, model.clone());
+                // The model comes before its parts
+                let mesh_id = ids[0];
+                self.models_to_upload.extend(ids);
+                Some(mesh_id)
+            }
+        };
+        if let Some(mesh_id) = mesh_id {
+            self.character_meshes.insert(id, mesh_id);
+        }
+    }
+
+    /// Add a chunk to the data pack being downloaded, and use the new data once it is complete
+    fn receive_data_pack_chunk(&mut self, bytes: &[u8]) {
+        let (download, data_hash) = match (&mut self.data_download, self.requested_data_hash) {
+            (Some(download), Some(data_hash)) => (download, data_hash),
+            _ => return,
+        };
+        let result = match download.add_chunk(bytes) {
+            Ok(()) if !download.is_complete() => return,
+            Ok(()) => load_data_pack(self.data_download.take().unwrap(), data_hash, &self.data_cache),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(data) => self.reload_data(data),
+            Err(e) => {
+                self.data_download = None;
+                self.console.print(&format!("Failed to receive the game data: {:#}", e), ERROR_COLOR);
+            }
+        }
+    }
+
+    /// Replace the game data after it was reloaded by the server.
+    /// Only the chunks containing blocks that look different are meshed again.
+    fn reload_data(&mut self, data: Data) {
+        info!("Reloading game data");
+        self.world.update_block_meshes(
+            data.meshes,
+            get_solid_blocks(&data.blocks),
+            get_block_dynamic_lights(&data.blocks),
+        );
+        self.block_registry = data.blocks;
+        self.item_registry = data.items;
+        self.item_meshes = data.item_meshes;
+        self.icon_atlas = IconAtlas::bake(&self.item_meshes, &data.models);
+        self.textures = data.textures;
+        self.textures_changed = true;
+        // Upload all the models again, and add the custom character models to the new registry
+        self.model_registry = data.models;
+        self.animations = data.animations;
+        self.gui.set_themes(data.themes);
+        self.models_to_upload = (0..self.model_registry.get_number_of_ids()).collect();
+        self.character_meshes.clear();
+        for (id, profile) in self.player_profiles.clone() {
+            self.update_character_mesh(id, &profile);
+        }
+    }
+
+    /// Start recording a trace, or write the trace that is being recorded
+    fn toggle_trace_recording(&mut self) {
+        if self.profiler.is_recording_trace() {
+            let path = format!("trace-{}.json", self.start_time.elapsed().as_secs());
+            if let Err(e) = self.profiler.write_chrome_trace(&path) {
+                log::error!("{:?}", e);
+            }
+        } else {
+            info!("Recording a trace, press the key again to write it");
+            self.profiler.start_trace();
+        }
+    }
+
+    fn toggle_statistics(&mut self) {
+        // Request the statistics right away when the screen opens
+        self.statistics_refresh = match self.statistics_refresh {
+            None => Some(0.0),
+            Some(_) => None,
+        };
+    }
+
+    /// Run a command entered in the console. The commands starting with `/` are sent to the server.
+    fn run_console_command(&mut self, command: String) {
+        if command.starts_with('/') {
+            self.client.send(ToServer::Command(command));
+            return;
+        }
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("help"), _) => {
+                let help = self
+                    .console
+                    .get_commands()
+                    .iter()
+                    .map(|command| format!("{}: {}", command.name, command.description))
+                    .collect::<Vec<_>>();
+                self.console.print(&help.join("\n"), INFO_COLOR);
+            }
+            (Some("clear"), _) => self.console.clear(),
+            (Some("render_distance"), Some("auto")) => {
+                self.auto_render_distance = true;
+                self.render_distance_tuner.reset(self.render_distance.x_max);
+                self.set_render_distance(self.render_distance_tuner.distance());
+                self.console.print("Render distance set to automatic", INFO_COLOR);
+            }
+            (Some("render_distance"), Some(chunks)) => match chunks.parse::<u64>() {
+                Ok(chunks) => {
+                    self.auto_render_distance = false;
+                    self.world.set_texture_lod_bias(0.0);
+                    self.set_render_distance(chunks);
+                    self.console.print(&format!("Render distance set to {} chunks", chunks), INFO_COLOR);
+                }
+                Err(_) => self.console.print(&format!("Invalid number of chunks: {}", chunks), ERROR_COLOR),
+            },
+            (Some("render_distance"), None) => {
+                self.console.print("Usage: render_distance <chunks|auto>", ERROR_COLOR);
+            }
+            (Some("shadows"), Some(quality)) => match ShadowQuality::from_name(quality) {
+                Some(quality) => {
+                    self.shadow_quality = quality;
+                    self.console.print(&format!("Shadow quality set to {:?}", quality), INFO_COLOR);
+                }
+                None => self.console.print(&format!("Invalid shadow quality: {}", quality), ERROR_COLOR),
+            },
+            (Some("shadows"), None) => {
+                self.console.print("Usage: shadows <off|low|medium|high>", ERROR_COLOR);
+            }
+            (Some("debug"), _) => self.show_debug_info = !self.show_debug_info,
+            (Some("debugdraw"), Some(mode)) => match self.debug_modes.toggle(mode) {
+                Some(enabled) => {
+                    let state = if enabled { "enabled" } else { "disabled" };
+                    self.console.print(&format!("Debug render mode {} {}", mode, state), INFO_COLOR);
+                }
+                None => self.console.print(&format!("Invalid debug render mode: {}", mode), ERROR_COLOR),
+            },
+            (Some("debugdraw"), None) => {
+                let usage = format!("Usage: debugdraw <{}>", DebugRenderModes::NAMES.join("|"));
+                self.console.print(&usage, ERROR_COLOR);
+            }
+            (Some("claims"), _) => self.show_claims = !self.show_claims,
+            (Some("trace"), _) => self.toggle_trace_recording(),
+            (Some("achievements"), _) => self.show_achievements = !self.show_achievements,
+            (Some("stats"), _) => self.toggle_statistics(),
+            (Some("macros"), _) => {
+                let macros = self.macros.describe();
+                if macros.is_empty() {
+                    self.console.print("No key is bound to commands in the settings", INFO_COLOR);
+                } else {
+                    self.console.print(&macros.join("\n"), INFO_COLOR);
+                }
+            }
+            (Some("emote"), Some(name)) => self.client.send(ToServer::Emote(name.to_owned())),
+            (Some("emote"), None) => {
+                let emotes = self.get_emotes();
+                let text = match emotes.is_empty() {
+                    true => "Your character has no emotes".to_owned(),
+                    false => format!("Emotes: {}", emotes.join(", ")),
+                };
+                self.console.print(&text, INFO_COLOR);
+            }
+            (Some("light"), _) => self.client.send(ToServer::ToggleLight),
+            (Some("netsim"), _) => self.simulate_network_conditions(command.split_whitespace().skip(1).collect()),
+            (Some("replay"), _) if self.replay.is_some() => self.control_replay(command.split_whitespace().skip(1).collect()),
+            (Some("campath"), _) => self.control_camera_path(command.split_whitespace().skip(1).collect()),
+            (Some(name), _) => {
+                self.console.print(&format!("Unknown command: {}, type help for the list of commands", name), ERROR_COLOR);
+            }
+            (None, _) => (),
+        }
+    }
+
+    /// Change the conditions simulated by the singleplayer network, see `common::network::dummy`
+    fn simulate_network_conditions(&mut self, args: Vec<&str>) {
+        let conditions = match args.as_slice() {
+            [] => {
+                let conditions = get_network_conditions();
+                self.console.print(
+                    &format!(
+                        "Latency {} ms, jitter {} ms, drop {}%, reorder {}%",
+                        conditions.latency.as_millis(),
+                        conditions.jitter.as_millis(),
+                        conditions.drop_rate * 100.0,
+                        conditions.reorder_rate * 100.0,
+                    ),
+                    INFO_COLOR,
+                );
+                return;
+            }
+            ["off"] => NetworkConditions::default(),
+            [latency, jitter, drop, reorder] => {
+                match (latency.parse::<u64>(), jitter.parse::<u64>(), drop.parse::<f64>(), reorder.parse::<f64>()) {
+                    (Ok(latency), Ok(jitter), Ok(drop), Ok(reorder))
+                        if (0.0..=100.0).contains(&drop) && (0.0..=100.0).contains(&reorder) =>
+                    {
+                        NetworkConditions {
+                            latency: Duration::from_millis(latency),
+                            jitter: Duration::from_millis(jitter),
+                            drop_rate: drop / 100.0,
+                            reorder_rate: reorder / 100.0,
+                        }
+                    }
+                    _ => {
+                        self.console.print("Invalid network conditions", ERROR_COLOR);
+                        return;
+                    }
+                }
+            }
+            _ => {
+                self.console.print("Usage: netsim <latency ms> <jitter ms> <drop %> <reorder %>, or netsim off", ERROR_COLOR);
+                return;
+            }
+        };
+        set_network_conditions(conditions);
+        self.console.print("Network conditions updated", INFO_COLOR);
+    }
+
+    /// Control the playback of the replay from the console
+    fn control_replay(&mut self, args: Vec<&str>) {
+        let control = match self.replay.as_ref() {
+            Some(replay) => replay.control.clone(),
+            None => return,
+        };
+        match args.as_slice() {
+            ["pause"] => control.set_paused(true),
+            ["play"] => control.set_paused(false),
+            ["seek", seconds] => match seconds.parse::<f64>() {
+                Ok(seconds) => {
+                    if control.seek(seconds) {
+                        self.reset_replayed_state();
+                    }
+                }
+                Err(_) => self.console.print(&format!("Invalid number of seconds: {}", seconds), ERROR_COLOR),
+            },
+            ["speed", speed] => match speed.parse::<f64>() {
+                Ok(speed) if speed > 0.0 => control.set_speed(speed),
+                _ => self.console.print(&format!("Invalid speed: {}", speed), ERROR_COLOR),
+            },
+            _ => self.console.print("Usage: replay pause, replay play, replay seek <s>, replay speed <x>", ERROR_COLOR),
+        }
+    }
+
+    /// Place, play, save and load the cinematic camera path from the console
+    fn control_camera_path(&mut self, args: Vec<&str>) {
+        match args.as_slice() {
+            ["add"] => {
+                let position = self.get_camera_position();
+                self.camera_path.keyframes.push(Keyframe {
+                    position: position.into(),
+                    yaw: self.yaw_pitch.yaw,
+                    pitch: self.yaw_pitch.pitch,
+                });
+                let text = format!("Keyframe {} added", self.camera_path.keyframes.len());
+                self.console.print(&text, INFO_COLOR);
+            }
+            ["undo"] => {
+                if self.camera_path.keyframes.pop().is_some() {
+                    let text = format!("Last keyframe removed, {} left", self.camera_path.keyframes.len());
+                    self.console.print(&text, INFO_COLOR);
+                }
+            }
+            ["clear"] => {
+                self.camera_path.keyframes.clear();
+                self.console.print("Camera path cleared", INFO_COLOR);
+            }
+            ["play"] | ["play", _] => {
+                let segment_duration = match args.get(1).map(|seconds| seconds.parse::<f64>()) {
+                    None => DEFAULT_SEGMENT_DURATION,
+                    Some(Ok(seconds)) if seconds > 0.0 => seconds,
+                    Some(_) => {
+                        self.console.print(&format!("Invalid number of seconds: {}", args[1]), ERROR_COLOR);
+                        return;
+                    }
+                };
+                if self.camera_path.keyframes.len() < 2 {
+                    self.console.print("The camera path needs at least 2 keyframes, see campath add", ERROR_COLOR);
+                    return;
+                }
+                self.camera_path_playback = Some(CameraPathPlayback::new(self.camera_path.clone(), segment_duration));
+                // The console would hide the path
+                self.console.close();
+            }
+            ["stop"] => self.camera_path_playback = None,
+            ["save", name] => match self.camera_path.save(name) {
+                Ok(path) => self.console.print(&format!("Camera path saved to {}", path.display()), INFO_COLOR),
+                Err(e) => self.console.print(&format!("{:#}", e), ERROR_COLOR),
+            },
+            ["load", name] => match CameraPath::load(name) {
+                Ok(path) => {
+                    let text = format!("Camera path {} loaded, {} keyframes", name, path.keyframes.len());
+                    self.camera_path = path;
+                    self.console.print(&text, INFO_COLOR);
+                }
+                Err(e) => self.console.print(&format!("{:#}", e), ERROR_COLOR),
+            },
+            _ => self.console.print(
+                "Usage: campath add, campath undo, campath clear, campath play [seconds per keyframe], campath stop, \
+                 campath save <name>, campath load <name>",
+                ERROR_COLOR,
+            ),
+        }
+    }
+
+    /// Forget everything the replayed messages told, because the replay starts again from the beginning
+    fn reset_replayed_state(&mut self) {
+        self.world.clear_chunks();
+        self.physics_simulation = ClientPhysicsSimulation::new(
+            ServerState {
+                physics_state: PhysicsState::default(),
+                server_time: Instant::now(),
+                input: Default::default(),
+            },
+            self.player_id,
+        );
+        self.previous_camera_position = None;
+        self.robots.clear();
+        self.dropped_items.clear();
+        self.controlled_robot = None;
+        self.world_time = WorldTime::default();
+        self.meteors.clear();
+        self.mobs.clear();
+        self.animators.clear();
+        self.robot_animators.clear();
+        self.flashlights.clear();
+        self.unlocked_achievements.clear();
+        self.teleport_fade = TeleportFade::default();
+        self.dimension = DimensionId::SURFACE;
+        self.vitals = Vitals::default();
+        self.status_effects.clear();
+        self.signs.clear();
+        self.resting_players = (0, 0);
+        self.inventory = Inventory::new(PLAYER_INVENTORY_SLOTS);
+        self.item_use = None;
+    }
+
+    /// The animations of the player's character that are not played by themselves
+    fn get_emotes(&self) -> Vec<String> {
+        let mesh_id = self.character_meshes.get(&self.player_id).copied();
+        let animations = mesh_id.and_then(|mesh_id| character_animations(&self.model_registry, &self.animations, mesh_id));
+        let names = animations.into_iter().flat_map(|animations| animations.keys());
+        let names = names.filter(|name| ![IDLE_ANIMATION, WALK_ANIMATION, MINE_ANIMATION].contains(&name.as_str()));
+        names.cloned().collect()
+    }
+
+    /// Return true if the item of the selected slot is used with right clicks
+    fn is_holding_consumable(&self) -> bool {
+        let item = self.inventory.get(self.selected_slot).and_then(|stack| self.item_registry.get_value_by_id(stack.item));
+        matches!(item.map(|item| &item.ty), Some(ItemType::Consumable { .. }))
+    }
+
+    /// Return true if the pointed block reacts to right clicks
+    fn is_pointing_at_interactive_block(&self) -> bool {
+        match self.get_pointed_block() {
+            Some((pos, _face)) => {
+                let block = self.block_registry.get_value_by_id(self.world.get_block(pos) as u32).unwrap();
+                block.name == STORAGE_CRATE_BLOCK
+                    || matches!(
+                        block.block_type,
+                        BlockType::Door { .. }
+                            | BlockType::OpenDoor { .. }
+                            | BlockType::Explosive { .. }
+                            | BlockType::Elevator { .. }
+                            | BlockType::Terminal { .. }
+                    )
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a screen that frees the cursor and stops the player is open, the trade or the inventory screen, or the
+    /// sign editor
+    fn is_screen_open(&self) -> bool {
+        self.trade.is_some() || self.inventory_screen.is_some() || self.sign_editor.is_some()
+    }
+
+    /// Close the sign editor, and send the text to the server if the player saved it
+    fn close_sign_editor(&mut self, action: SignAction) {
+        if let Some(editor) = self.sign_editor.take() {
+            if let SignAction::Save = action {
+                self.client.send(ToServer::SetSignText(editor.pos, editor.text));
+            }
+        }
+    }
+
+    /// Close the inventory screen and the container the player opened, if there is one
+    fn close_inventory_screen(&mut self) {
+        self.inventory_screen = None;
+        if self.container.take().is_some() {
+            self.client.send(ToServer::CloseContainer);
+        }
+    }
+
+    /// The color filter of the liquid the camera is in, or else the haze of the biome
+    fn get_color_filter(&self) -> ColorFilter {
+        let block = self.world.get_block(BlockPos::from(self.get_camera_position()));
+        match self.block_registry.get_name_by_id(block as u32) {
+            Some("core:water") => ColorFilter::UNDERWATER,
+            Some("core:lava") => ColorFilter::IN_LAVA,
+            _ => self.biome_haze,
+        }
+    }
+
+    /// Return the position of the camera, which follows the controlled robot if there is one.
+    /// The position of the player is interpolated between the last two physics ticks.
+    /// In a replay, the camera flies freely. While a camera path is played, the camera follows it.
+    fn get_camera_position(&self) -> Vector3<f64> {
+        if let Some((camera, _)) = self.camera_path_playback.as_ref().and_then(|playback| playback.camera()) {
+            return camera;
+        }
+        if let Some(camera) = self.replay.as_ref().and_then(|replay| replay.camera_position()) {
+            return camera;
+        }
+        let robot = self
+            .controlled_robot
+            .and_then(|id| self.robots.iter().find(|robot| robot.id == id));
+        match robot {
+            Some(robot) => robot.pos,
+            None => {
+                let current = self.physics_simulation.get_camera_position();
+                match self.previous_camera_position {
+                    Some(previous) => previous.lerp(&current, self.timestep.alpha()),
+                    None => current,
+                }
+            }
+        }
+    }
+
+    /// Use the same render distance in every direction, and tell the server
+    fn set_render_distance(&mut self, chunks: u64) {
+        self.render_distance = cubic_render_distance(chunks);
+        self.client.send(ToServer::SetRenderDistance(self.render_distance));
+    }
+
+    /// The boxes of the entities, if the debug render mode is enabled
+    fn get_debug_boxes(&self) -> Vec<DebugBox> {
+        let mut boxes = Vec::new();
+        if self.show_claims {
+            let camera = self.physics_simulation.get_player().get_camera_position();
+            for claim in self.claims.iter() {
+                let min = Vector3::new(claim.min.px as f64, claim.min.py as f64, claim.min.pz as f64);
+                let max = Vector3::new(claim.max.px as f64, claim.max.py as f64, claim.max.pz as f64) + Vector3::repeat(1.0);
+                // The distance from the camera to the closest point of the claim
+                let distance = (camera - camera.sup(&min).inf(&max)).norm();
+                if distance <= CLAIM_BORDER_DISTANCE {
+                    let color = if claim.allowed { ALLOWED_CLAIM_COLOR } else { PROTECTED_CLAIM_COLOR };
+                    boxes.push(DebugBox { min, max, color });
+                }
+            }
+        }
+        if !self.debug_modes.entity_boxes {
+            return boxes;
+        }
+        let aabb_box = |aabb: &AABB| DebugBox {
+            min: aabb.pos,
+            max: aabb.pos + Vector3::new(aabb.size_x, aabb.size_y, aabb.size_z),
+            color: ENTITY_BOX_COLOR,
+        };
+        for (id, player) in self.physics_simulation.get_state().players.iter() {
+            // The box of the player would surround the camera
+            if *id != self.player_id || self.replay.is_some() {
+                boxes.push(aabb_box(&player.aabb));
+            }
+        }
+        boxes.extend(self.robots.iter().map(|robot| aabb_box(&robot.aabb())));
+        boxes.extend(self.mobs.iter().map(|mob| aabb_box(&mob.aabb())));
+        // The dropped items stand on their position
+        let half_item = DROPPED_ITEM_SIZE as f64 / 2.0;
+        for (pos, _) in self.dropped_items.iter() {
+            boxes.push(DebugBox {
+                min: pos - Vector3::new(half_item, 0.0, half_item),
+                max: pos + Vector3::new(half_item, 2.0 * half_item, half_item),
+                color: ENTITY_BOX_COLOR,
+            });
+        }
+        boxes
+    }
+
+    /// Return the block the player is looking at and the pointed face
+    fn get_pointed_block(&self) -> Option<(BlockPos, usize)> {
+        if self.controlled_robot.is_some() || self.replay.is_some() {
+            return None;
+        }
+        let pp = self.physics_simulation.get_player();
+        let dir = look_direction(self.yaw_pitch.yaw, self.yaw_pitch.pitch);
+        pp.get_pointed_at(dir, 10.0, &self.world)
+    }
+
+    /// Whether the closest thing the player aims at is a creature, a robot or another player in reach
+    fn is_pointing_at_entity(&self) -> bool {
+        let pp = self.physics_simulation.get_player();
+        let eye = pp.get_camera_position();
+        let dir = look_direction(self.yaw_pitch.yaw, self.yaw_pitch.pitch);
+        let block_distance = self.get_pointed_block().and_then(|(block, _face)| {
+            let block_pos = Vector3::new(block.px as f64, block.py as f64, block.pz as f64);
+            AABB::new(block_pos, (1.0, 1.0, 1.0)).intersect_ray(eye, dir)
+        });
+        let mut boxes = self.mobs.iter().map(MobInfo::aabb).collect::<Vec<_>>();
+        boxes.extend(self.robots.iter().map(RobotInfo::aabb));
+        let other_players = self.physics_simulation.get_state().players.iter().filter(|(&id, _)| id != self.player_id);
+        boxes.extend(other_players.map(|(_, player)| player.aabb.clone()));
+        boxes
+            .iter()
+            .filter_map(|aabb| aabb.intersect_ray(eye, dir))
+            .any(|distance| distance <= MELEE_REACH && block_distance.is_none_or(|block| distance < block))
+    }
+}
+
+/// The direction of a look `yaw` and `pitch` degrees away from the -z axis
+fn look_direction(yaw: f64, pitch: f64) -> Vector3<f64> {
+    let (y, p) = (yaw.to_radians(), pitch.to_radians());
+    Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos())
 }
 
 impl State for SinglePlayer {
     fn update(
         &mut self,
-        _settings: &mut Settings,
+        settings: &mut Settings,
         input_state: &InputState,
         _data: &WindowData,
         flags: &mut WindowFlags,
-        _seconds_delta: f64,
+        seconds_delta: f64,
         _device: &mut wgpu::Device,
     ) -> Result<StateTransition> {
         self.client_timing.start_frame();
+        if let Some(gamma) = self.new_gamma.take() {
+            settings.post_processing.gamma = gamma;
+        }
+        if let Some(camera_shake) = self.new_camera_shake.take() {
+            settings.camera_shake = camera_shake;
+        }
+        if let Some(accessibility) = self.new_accessibility.take() {
+            settings.accessibility = accessibility;
+        }
         // Handle server messages
         self.handle_server_messages();
+        let elapsed = self.previous_network_stats.0.elapsed().as_secs_f64();
+        if elapsed >= NETWORK_STATS_INTERVAL {
+            let network_stats = self.client.stats();
+            let round_trip_time = match self.round_trip_time {
+                Some(round_trip_time) => format!("{:.1} ms", round_trip_time.as_secs_f64() * 1000.0),
+                None => "unknown".to_owned(),
+            };
+            let summary = network_stats.bandwidth_summary(&self.previous_network_stats.1, elapsed);
+            send_debug_info("Network", "client", format!("Client\nRTT: {}\n{}", round_trip_time, summary));
+            self.previous_network_stats = (Instant::now(), network_stats);
+            self.last_ping = (self.last_ping.0.wrapping_add(1), Instant::now());
+            self.client.send(ToServer::Ping(self.last_ping.0));
+        }
         self.client_timing.record_part("Network events");
 
+        for command in self.console.drain_submitted() {
+            self.run_console_command(command);
+        }
+
         // Collect input
-        let frame_input =
-            input_state.get_physics_input(self.yaw_pitch, self.ui.should_update_camera());
+        let allow_movement = self.ui.should_update_camera()
+            && !self.console.is_open()
+            && !self.is_screen_open()
+            && self.camera_path_playback.is_none();
+        let mut frame_input = input_state.get_physics_input(self.yaw_pitch, allow_movement);
+        // The server doesn't let the player fly if they can't, don't predict it
+        frame_input.flying &= self.capabilities.can_fly && self.vitals.energy > 0.0;
         // Send input to server
         self.client.send(ToServer::UpdateInput(frame_input));
         self.client_timing.record_part("Collect and send input");
 
-        // Update physics
-        self.physics_simulation
-            .step_simulation(frame_input, Instant::now(), &self.world);
+        // Update physics. The server moves the controlled robot instead of the player.
+        // In a replay, the recorded player moves by itself and the keys move the free camera.
+        let player_input = match self.replay {
+            Some(_) => self.physics_simulation.get_player_input(self.player_id).unwrap_or_default(),
+            None if self.controlled_robot.is_some() => frame_input.without_movement(),
+            None => frame_input,
+        };
+        self.timestep.update();
+        let mut ticks = 0;
+        while self.timestep.consume_tick() {
+            ticks += 1;
+        }
+        if self.replay.as_ref().is_some_and(|replay| replay.control.is_paused()) {
+            // Show the recorded state of the paused position, without prediction
+            self.physics_simulation.freeze(Instant::now());
+            ticks = 0;
+        }
+        // The ticks are spaced evenly and end now, to stay in sync with the clock of the server
+        let now = Instant::now();
+        for i in (0..ticks).rev() {
+            profile_scope!("client physics");
+            self.previous_camera_position = self
+                .physics_simulation
+                .get_state()
+                .players
+                .get(&self.player_id)
+                .map(|player| player.get_camera_position());
+            self.physics_simulation
+                .step_simulation(player_input, now - self.timestep.tick_duration() * i, &self.world);
+        }
+        let recorded_camera = self
+            .physics_simulation
+            .get_state()
+            .players
+            .get(&self.player_id)
+            .map(|player| player.get_camera_position());
+        if let Some(replay) = self.replay.as_mut() {
+            replay.move_camera(frame_input, seconds_delta, recorded_camera);
+        }
+        if let Some(playback) = self.camera_path_playback.as_mut() {
+            if playback.update(seconds_delta) {
+                self.camera_path_playback = None;
+                self.console.print("Camera path finished", INFO_COLOR);
+            }
+        }
         self.client_timing.record_part("Update physics");
 
-        let p = self.physics_simulation.get_camera_position();
+        // Animate the characters and the robots
+        let default_character_mesh = self.model_registry.get_id_by_name(&DEFAULT_CHARACTER_MODEL.to_owned()).unwrap();
+        let players = &self.physics_simulation.get_state().players;
+        for (id, player) in players.iter() {
+            let mesh_id = self.character_meshes.get(id).copied().unwrap_or(default_character_mesh);
+            let animations = character_animations(&self.model_registry, &self.animations, mesh_id);
+            let animator = self.animators.entry(*id).or_default();
+            animator.update(animations, player.aabb.pos, false, seconds_delta);
+        }
+        self.animators.retain(|id, _| players.contains_key(id));
+        let robot_animations = self.animations.get(&namespaced_key(ROBOT_MODEL));
+        for robot in self.robots.iter() {
+            let mining = matches!(robot.state, RobotState::Mining { .. });
+            self.robot_animators.entry(robot.id).or_default().update(robot_animations, robot.pos, mining, seconds_delta);
+        }
+        let robots = &self.robots;
+        self.robot_animators.retain(|id, _| robots.iter().any(|robot| robot.id == *id));
+        self.particles.tick(seconds_delta);
+        for (_, time_left) in self.achievement_toasts.iter_mut() {
+            *time_left -= seconds_delta;
+        }
+        self.achievement_toasts.retain(|(_, time_left)| *time_left > 0.0);
+        if let Some((_, time_left)) = self.notice.as_mut() {
+            *time_left -= seconds_delta;
+        }
+        self.notice = self.notice.take().filter(|(_, time_left)| *time_left > 0.0);
+        if let Some((elapsed, _)) = self.item_use.as_mut() {
+            *elapsed += seconds_delta;
+        }
+        // The trade screen closes when the player walks away from the terminal, and the sign editor from the sign
+        if let Some((pos, _)) = &self.trade {
+            let center = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+            if (center - self.get_camera_position()).norm() > TRADE_DISTANCE {
+                self.trade = None;
+            }
+        }
+        if let Some(pos) = self.sign_editor.as_ref().map(|editor| editor.pos) {
+            let center = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+            if (center - self.get_camera_position()).norm() > TRADE_DISTANCE {
+                self.sign_editor = None;
+            }
+        }
+        if let Some(refresh) = self.statistics_refresh.as_mut() {
+            *refresh -= seconds_delta;
+            if *refresh <= 0.0 {
+                *refresh = STATISTICS_REFRESH_INTERVAL;
+                self.client.send(ToServer::RequestStatistics);
+            }
+        }
+        if self.ui.should_show_server_list() {
+            self.server_list.update(seconds_delta);
+        }
+        // Fiery trail of the falling meteors
+        for meteor in self.meteors.iter() {
+            if let Some(pos) = meteor.pos {
+                self.particles.spawn_flame(pos, get_unit_mesh(&self.model_registry, "fire"));
+            }
+        }
+
+        let p = self.get_camera_position();
         let player_chunk = BlockPos::from(p).containing_chunk_pos();
 
         // Debug current player position, yaw and pitch
@@ -207,19 +1418,59 @@ impl State for SinglePlayer {
             ),
         );
 
-        // Remove chunks that are too far
-        self.world.remove_far_chunks(player_chunk, &self.render_distance);
+        // Adapt the render distance to the frame rate
+        if self.auto_render_distance {
+            if let Some(chunks) = self.render_distance_tuner.update(seconds_delta) {
+                self.set_render_distance(chunks);
+            }
+            self.world.set_texture_lod_bias(self.render_distance_tuner.lod_bias());
+            send_debug_info("Chunks", "autodistance", self.render_distance_tuner.summary());
+        }
+
+        // Remove chunks that are too far. The free camera of a replay can leave the chunks sent by the server,
+        // which are around the recorded player.
+        let streamed_chunk = match (&self.replay, recorded_camera) {
+            (Some(_), Some(recorded_camera)) => BlockPos::from(recorded_camera).containing_chunk_pos(),
+            _ => player_chunk,
+        };
+        self.world.remove_far_chunks(streamed_chunk, &self.render_distance);
         self.client_timing.record_part("Drop far chunks");
 
         // Send chunks to meshing
         self.world.enqueue_chunks_for_meshing(player_chunk, &self.render_distance);
         self.client_timing.record_part("Send chunks to meshing");
 
+        if let Some(loading) = self.world_loading.as_mut() {
+            let chunks = self.world.chunk_progress(player_chunk, SPAWN_AREA_RADIUS);
+            if loading.update_world(chunks, seconds_delta) {
+                info!("Loaded the world around the player");
+                self.world_loading = None;
+            }
+        }
+        self.teleport_fade.update(self.world.is_chunk_meshed(player_chunk), seconds_delta);
+        self.hit_feedback.update(seconds_delta);
+        self.sound_cues.update(seconds_delta);
+        for remaining in self.status_effects.iter_mut().filter_map(|effect| effect.remaining.as_mut()) {
+            *remaining = (*remaining - seconds_delta).max(0.0);
+        }
+        let haze = match self.world.biome_at(BlockPos::from(self.get_camera_position())) {
+            Some(biome) => ColorFilter {
+                color: biome.biome().haze_color,
+                strength: biome.biome().haze_strength,
+            },
+            None => ColorFilter::NONE,
+        };
+        self.biome_haze = self.biome_haze.lerp(&haze, (BIOME_HAZE_SMOOTHING * seconds_delta).min(1.0) as f32);
+
         send_debug_info("Chunks", "clientloaded", format!("Client loaded {} chunks", self.world.num_loaded_chunks()));
 
-        flags.grab_cursor = self.ui.should_capture_mouse();
+        flags.grab_cursor = self.ui.should_capture_mouse() && !self.console.is_open() && !self.is_screen_open();
+        flags.text_input = self.console.is_open() || self.sign_editor.is_some();
 
-        if self.ui.should_exit() {
+        if let Some(reason) = self.kicked.take() {
+            let title = "You were kicked from the server".to_owned();
+            Ok(StateTransition::ReplaceCurrent(Box::new(move |_, device| ErrorScreen::new(device, title, vec![reason]))))
+        } else if self.ui.should_exit() {
             //Ok(StateTransition::ReplaceCurrent(Box::new(crate::mainmenu::MainMenu::new)))
             Ok(StateTransition::CloseWindow)
         } else {
@@ -229,7 +1480,7 @@ impl State for SinglePlayer {
 
     fn render<'a>(
         &mut self,
-        _settings: &Settings,
+        settings: &Settings,
         buffers: WindowBuffers<'a>,
         device: &mut wgpu::Device,
         data: &WindowData,
@@ -238,20 +1489,22 @@ impl State for SinglePlayer {
         // Count fps TODO: move this to update
         self.fps_counter.add_frame();
         send_debug_info("Player", "fps", format!("fps = {}", self.fps_counter.fps()));
+        send_debug_info("GPU", "adapter", format!("Adapter: {}", data.adapter));
+        send_debug_info("GPU", "available", format!("Available: {}", data.available_adapters.join(", ")));
 
+        let path_camera = self.camera_path_playback.as_ref().and_then(|playback| playback.camera());
+        let mut yaw_pitch = path_camera.map_or(self.yaw_pitch, |(_, yaw_pitch)| yaw_pitch);
+        let camera_shake = if settings.accessibility.reduce_screen_shake { 0.0 } else { settings.camera_shake };
+        let (yaw_shake, pitch_shake) = self.hit_feedback.shake(camera_shake);
+        yaw_pitch.yaw += yaw_shake;
+        yaw_pitch.pitch += pitch_shake;
         let frustum = Frustum::new(
-            self.physics_simulation.get_camera_position(),
-            self.yaw_pitch,
+            self.get_camera_position(),
+            yaw_pitch,
         );
 
         // Try raytracing TODO: move this to update
-        let pp = self.physics_simulation.get_player();
-        let pointed_block = {
-            let y = self.yaw_pitch.yaw.to_radians();
-            let p = self.yaw_pitch.pitch.to_radians();
-            let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-            pp.get_pointed_at(dir, 10.0, &self.world)
-        };
+        let pointed_block = self.get_pointed_block();
         if let Some((x, face)) = pointed_block {
             send_debug_info(
                 "Player",
@@ -269,8 +1522,33 @@ impl State for SinglePlayer {
         // Begin rendering
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        // The commands of the previous frame were submitted
+        self.uploader.recall(device);
+
+        // The world is drawn into the offscreen buffers of the post-processing
+        let winit::dpi::PhysicalSize { width, height } = data.physical_window_size;
+        self.post_processing.resize(device, (width, height));
+        let world_buffers = self.post_processing.world_buffers(buffers);
+        crate::render::clear_color_and_depth(&mut encoder, world_buffers);
 
-        crate::render::clear_color_and_depth(&mut encoder, buffers);
+        if self.gpu_timer.is_none() {
+            self.gpu_timer = data.gpu_timestamp_period.map(|period| GpuTimer::new(device, period));
+        }
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.collect(device);
+        }
+
+        if self.textures_changed {
+            self.textures_changed = false;
+            self.world.update_textures(device, &mut encoder, &mut self.uploader, &self.textures);
+            self.ui_renderer.set_icon_atlas(device, &mut encoder, &mut self.uploader, &self.icon_atlas);
+        }
+
+        // Upload the custom character models
+        for mesh_id in self.models_to_upload.drain(..) {
+            let model = self.model_registry.get_value_by_id(mesh_id).unwrap();
+            self.world.upload_model(device, &mut encoder, &mut self.uploader, mesh_id, model);
+        }
 
         let mut models_to_draw = Vec::new();
         models_to_draw.push(crate::render::Model {
@@ -284,8 +1562,148 @@ impl State for SinglePlayer {
             scale: 0.3,
             rot_offset: [0.0, 0.0, 0.0],
             rot_y: 0.0,
+            rot_x: 0.0,
+            rot_z: 0.0,
         });
+        let mut billboards = Vec::new();
+        let robot_mesh = self.model_registry.get_id_by_name(&ROBOT_MODEL.to_owned()).unwrap();
+        let robot_scale = ROBOT_SIDE as f32 / self.model_registry.get_value_by_id(robot_mesh).unwrap().size_y as f32;
+        let robot_animations = self.animations.get(&namespaced_key(ROBOT_MODEL));
+        for robot in self.robots.iter() {
+            if Some(robot.id) == self.controlled_robot {
+                continue;
+            }
+            let label = match self.player_profiles.get(&robot.owner) {
+                Some(profile) => format!("{}'s robot", profile.name),
+                None => "Robot".to_owned(),
+            };
+            billboards.push(Billboard {
+                pos: robot.pos + Vector3::new(0.0, ROBOT_LABEL_HEIGHT, 0.0),
+                text: label,
+                color: [0.6, 0.9, 1.0, 1.0],
+                size: NAME_TAG_SIZE,
+                wrap_width: None,
+            });
+            let animator = self.robot_animators.get(&robot.id);
+            let pose = |part: &str| animator.map(|animator| animator.pose(robot_animations, part)).unwrap_or_default();
+            let feet = robot.pos - Vector3::new(0.0, ROBOT_SIDE / 2.0, 0.0);
+            models_to_draw.extend(posed_models(&self.model_registry, robot_mesh, feet, robot.yaw, robot_scale, pose));
+        }
+        let drone_mesh = get_unit_mesh(&self.model_registry, "drone");
+        for mob in self.mobs.iter() {
+            let ItemMesh::SimpleMesh { mesh_id, scale, mesh_center } = scale_mesh(drone_mesh.clone(), MOB_SIDE as f32);
+            models_to_draw.push(crate::render::Model {
+                mesh_id,
+                pos_x: mob.pos.x as f32 - mesh_center.0 * scale,
+                pos_y: mob.pos.y as f32 - mesh_center.1 * scale,
+                pos_z: mob.pos.z as f32 - mesh_center.2 * scale,
+                scale,
+                rot_offset: [mesh_center.0 * scale, mesh_center.1 * scale, mesh_center.2 * scale],
+                rot_y: mob.yaw.to_radians() as f32,
+                rot_x: 0.0,
+                rot_z: 0.0,
+            });
+            let name = mob.kind.replace('_', " ");
+            let label = if mob.health < mob.max_health {
+                format!("{} ({:.0}/{:.0})", name, mob.health.max(0.0), mob.max_health)
+            } else {
+                name
+            };
+            let color = match mob.state {
+                MobState::Wandering => MOB_LABEL_COLOR,
+                MobState::Chasing | MobState::Attacking => ANGRY_MOB_LABEL_COLOR,
+            };
+            billboards.push(Billboard {
+                pos: mob.pos + Vector3::new(0.0, MOB_LABEL_HEIGHT, 0.0),
+                text: label,
+                color,
+                size: NAME_TAG_SIZE,
+                wrap_width: None,
+            });
+        }
+        let default_character_mesh = self
+            .model_registry
+            .get_id_by_name(&DEFAULT_CHARACTER_MODEL.to_owned())
+            .unwrap();
+        for (id, player) in self.physics_simulation.get_state().players.iter() {
+            // The recorded player is seen from the free camera
+            if *id == self.player_id && self.replay.is_none() {
+                continue;
+            }
+            let mesh_id = self.character_meshes.get(id).copied().unwrap_or(default_character_mesh);
+            let model = self.model_registry.get_value_by_id(mesh_id).unwrap();
+            // Fit the model to the height of the player
+            let aabb = &player.aabb;
+            let scale = (aabb.size_y / model.size_y as f64) as f32;
+            let (yaw, pitch) = match self.physics_simulation.get_player_input(*id) {
+                Some(input) => (input.yaw, input.pitch),
+                None => (0.0, 0.0),
+            };
+            let animator = self.animators.get(id);
+            let animations = character_animations(&self.model_registry, &self.animations, mesh_id);
+            // The head looks up and down with the player
+            let pose = |part: &str| {
+                let mut pose = animator.map(|animator| animator.pose(animations, part)).unwrap_or_default();
+                if part == HEAD_PART {
+                    pose.rotation[0] += pitch as f32;
+                }
+                pose
+            };
+            let feet = aabb.pos + Vector3::new(aabb.size_x / 2.0, 0.0, aabb.size_z / 2.0);
+            models_to_draw.extend(posed_models(&self.model_registry, mesh_id, feet, yaw, scale, pose));
+            if let Some(profile) = self.player_profiles.get(id) {
+                let head = aabb.pos + Vector3::new(aabb.size_x / 2.0, aabb.size_y + NAME_TAG_HEIGHT, aabb.size_z / 2.0);
+                billboards.push(Billboard {
+                    pos: head,
+                    text: profile.name.clone(),
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    size: NAME_TAG_SIZE,
+                    wrap_width: None,
+                });
+            }
+        }
+        for (pos, text) in self.signs.iter() {
+            billboards.push(Billboard {
+                pos: Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 1.0 + SIGN_TEXT_HEIGHT, pos.pz as f64 + 0.5),
+                text: text.clone(),
+                color: SIGN_TEXT_COLOR,
+                size: SIGN_TEXT_SIZE,
+                wrap_width: Some(SIGN_TEXT_WIDTH),
+            });
+        }
         let item_rotation = (Instant::now() - self.start_time).as_secs_f32(); // TODO: use f64
+        for (pos, stack) in self.dropped_items.iter() {
+            let ItemMesh::SimpleMesh { mesh_id, scale, mesh_center } = &self.item_meshes[stack.item as usize];
+            let scale = scale * DROPPED_ITEM_SIZE;
+            models_to_draw.push(crate::render::Model {
+                mesh_id: *mesh_id,
+                pos_x: pos.x as f32 - mesh_center.0 * scale,
+                pos_y: pos.y as f32,
+                pos_z: pos.z as f32 - mesh_center.2 * scale,
+                scale,
+                rot_offset: [mesh_center.0 * scale, mesh_center.1 * scale, mesh_center.2 * scale],
+                rot_y: item_rotation,
+                rot_x: 0.0,
+                rot_z: 0.0,
+            });
+        }
+        self.particles.render(&mut models_to_draw);
+        billboards.extend(self.hit_feedback.billboards());
+        for pos in self.meteors.iter().filter_map(|meteor| meteor.pos) {
+            let ItemMesh::SimpleMesh { mesh_id, scale, mesh_center } =
+                scale_mesh(get_unit_mesh(&self.model_registry, "meteor"), METEOR_SIZE);
+            models_to_draw.push(crate::render::Model {
+                mesh_id,
+                pos_x: pos.x as f32 - mesh_center.0 * scale,
+                pos_y: pos.y as f32 - mesh_center.1 * scale,
+                pos_z: pos.z as f32 - mesh_center.2 * scale,
+                scale,
+                rot_offset: [0.0, 0.0, 0.0],
+                rot_y: 0.0,
+                rot_x: 0.0,
+                rot_z: 0.0,
+            });
+        }
         models_to_draw.push(crate::render::Model {
             mesh_id: self
                 .model_registry
@@ -297,45 +1715,272 @@ impl State for SinglePlayer {
             scale: 1.0 / 32.0,
             rot_offset: [0.5, 0.5, 1.0 / 64.0],
             rot_y: item_rotation,
+            rot_x: 0.0,
+            rot_z: 0.0,
         });
+        // Collect the lights: the lamps, the robots that still have energy and their headlights, the flashlights, the
+        // flames and the explosions
+        let mut lights = Vec::new();
+        self.world.get_point_lights(frustum.position, BLOCK_LIGHT_DISTANCE, &mut lights);
+        let spot = |direction: Vector3<f64>| Spot { direction, cos_angle: SPOT_LIGHT_ANGLE.to_radians().cos() as f32 };
+        for robot in self.robots.iter().filter(|robot| robot.energy > 0.0) {
+            lights.push(PointLight {
+                position: robot.pos + Vector3::new(0.0, ROBOT_LABEL_HEIGHT, 0.0),
+                color: ROBOT_LIGHT_COLOR,
+                intensity: 1.0,
+                radius: ROBOT_LIGHT_RADIUS,
+                spot: None,
+            });
+            if robot.headlights {
+                let direction = look_direction(robot.yaw, 0.0);
+                lights.push(PointLight {
+                    // In front of the robot, so that it doesn't light itself
+                    position: robot.pos + direction * (ROBOT_SIDE / 2.0 + 0.1),
+                    color: SPOT_LIGHT_COLOR,
+                    intensity: 2.0,
+                    radius: HEADLIGHTS_RANGE,
+                    spot: Some(spot(direction)),
+                });
+            }
+        }
+        for (id, player) in self.physics_simulation.get_state().players.iter() {
+            if !self.flashlights.contains(id) {
+                continue;
+            }
+            // The flashlight of the player follows the camera, the others follow the last input received
+            let (yaw, pitch) = match self.physics_simulation.get_player_input(*id) {
+                _ if *id == self.player_id && self.replay.is_none() => (self.yaw_pitch.yaw, self.yaw_pitch.pitch),
+                Some(input) => (input.yaw, input.pitch),
+                None => continue,
+            };
+            lights.push(PointLight {
+                position: player.get_camera_position(),
+                color: SPOT_LIGHT_COLOR,
+                intensity: 2.0,
+                radius: FLASHLIGHT_RANGE,
+                spot: Some(spot(look_direction(yaw, pitch))),
+            });
+        }
+        self.particles.lights(&mut lights, settings.accessibility.reduce_flashing);
         // Draw chunks
-        self.world.render_chunks(
-            device,
-            &mut encoder,
-            buffers,
-            data,
-            &frustum,
-            input_state.enable_culling,
-            pointed_block,
-            &models_to_draw,
-        );
+        {
+            profile_scope!("render world");
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.begin_pass(&mut encoder, "world");
+            }
+            self.world.set_shadow_quality(device, self.shadow_quality);
+            let debug_boxes = self.get_debug_boxes();
+            self.world.render_chunks(
+                device,
+                &mut encoder,
+                &mut self.uploader,
+                world_buffers,
+                data,
+                &frustum,
+                input_state.enable_culling,
+                pointed_block,
+                &models_to_draw,
+                &lights,
+                self.world_time,
+                &self.debug_modes,
+                &debug_boxes,
+            );
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.end_pass(&mut encoder);
+            }
+        }
         self.client_timing.record_part("Render chunks");
 
+        // Draw the billboards before clearing the depth buffer, so that they are hidden by the terrain
+        {
+            profile_scope!("render billboards");
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.begin_pass(&mut encoder, "billboards");
+            }
+            self.billboard_renderer.render(
+                device,
+                &mut encoder,
+                &mut self.uploader,
+                world_buffers,
+                data,
+                &frustum,
+                &billboards,
+            );
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.end_pass(&mut encoder);
+            }
+        }
+        self.client_timing.record_part("Render billboards");
+
+        // Apply the screen-space effects, and draw the world into the frame buffer of the window
+        {
+            profile_scope!("post-processing");
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.begin_pass(&mut encoder, "post-processing");
+            }
+            let color_filter = self.get_color_filter();
+            self.post_processing.render(
+                device,
+                &mut encoder,
+                &mut self.uploader,
+                buffers,
+                &settings.post_processing,
+                color_filter,
+            );
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.end_pass(&mut encoder);
+            }
+        }
+        self.client_timing.record_part("Post-processing");
+
         crate::render::clear_depth(&mut encoder, buffers);
 
         // Draw ui
+        self.gui.select_theme(&settings.theme);
+        self.ui.set_theme(&self.gui);
         self.ui.rebuild(&mut self.debug_info, data)?;
+        self.gui.set_accessibility(&settings.accessibility);
         self.gui.prepare();
-        crate::gui::experiments::render_debug_info(&mut self.gui, &mut self.debug_info);
+        if self.show_debug_info {
+            crate::gui::experiments::render_debug_info(&mut self.gui, &mut self.debug_info);
+        }
+        if let Some(meteor) = self.meteors.iter().min_by(|a, b| a.time_to_impact.partial_cmp(&b.time_to_impact).unwrap()) {
+            let text = format!("Meteor incoming! Impact in {:.0} s", meteor.time_to_impact.ceil());
+            let x = data.logical_window_size.width as i32 / 2 - 150;
+            self.gui.text(x, 40, 30, text, [1.0, 0.4, 0.1, 1.0], 0.02);
+        }
+        let (win_w, win_h) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+        crate::gui::teleport::render_teleport_fade(&mut self.gui, &self.teleport_fade, win_w, win_h);
+        if let Some(loading) = &self.world_loading {
+            crate::gui::loading::render_loading_progress(&mut self.gui, loading, win_w, win_h);
+        }
+        let (feedback, yaw) = (&self.hit_feedback, self.yaw_pitch.yaw);
+        let reduce_flashing = settings.accessibility.reduce_flashing;
+        crate::gui::damage::render_damage_vignette(&mut self.gui, feedback, yaw, reduce_flashing, win_w, win_h);
+        crate::gui::hud::render_vitals(&mut self.gui, &self.vitals, win_h);
+        let camera = self.get_camera_position();
+        if let Some(biome) = self.world.biome_at(BlockPos::from(camera)) {
+            crate::gui::hud::render_climate(&mut self.gui, biome, biome.temperature_at(camera.y), win_h);
+        }
+        crate::gui::hud::render_status_effects(&mut self.gui, &self.status_effects, win_h);
+        let (gui, inventory, items, icons) = (&mut self.gui, &self.inventory, &self.item_registry, &self.icon_atlas);
+        crate::gui::hud::render_hotbar(gui, inventory, self.selected_slot, items, icons, win_w, win_h);
+        if let Some((elapsed, use_time)) = self.item_use {
+            crate::gui::hud::render_item_use(&mut self.gui, elapsed / use_time, win_w, win_h);
+        }
+        if let Some(download) = &self.data_download {
+            crate::gui::hud::render_data_download(&mut self.gui, download.progress(), win_w);
+        }
+        if let Some((text, _)) = &self.notice {
+            crate::gui::hud::render_notice(&mut self.gui, text, win_w, win_h);
+        } else if self.resting_players.0 > 0 {
+            let (resting, online) = self.resting_players;
+            let text = format!("{}/{} players resting, the night is skipped when everyone rests", resting, online);
+            crate::gui::hud::render_notice(&mut self.gui, &text, win_w, win_h);
+        }
+        if settings.accessibility.sound_cues {
+            crate::gui::hud::render_sound_cues(&mut self.gui, &self.sound_cues, win_w, win_h);
+        }
+        crate::gui::achievements::render_toasts(&mut self.gui, &self.achievement_toasts, win_w);
+        if self.show_achievements {
+            crate::gui::achievements::render_achievement_list(&mut self.gui, &self.unlocked_achievements, win_w, win_h);
+        }
+        if self.statistics_refresh.is_some() {
+            crate::gui::statistics::render_statistics(&mut self.gui, &self.statistics, win_w, win_h);
+        }
+        if let Some((pos, trades)) = &self.trade {
+            let (gui, inventory, items) = (&mut self.gui, &self.inventory, &self.item_registry);
+            match crate::gui::trade::render_trades(gui, trades, inventory, items, win_w, win_h) {
+                Some(TradeAction::Trade(index)) => self.client.send(ToServer::Trade(*pos, index)),
+                Some(TradeAction::Close) => self.trade = None,
+                None => (),
+            }
+        }
+        if let Some(editor) = &self.sign_editor {
+            if let Some(action) = crate::gui::sign::render_sign_editor(&mut self.gui, editor, win_w, win_h) {
+                self.close_sign_editor(action);
+            }
+        }
+        if let Some(screen) = self.inventory_screen.as_mut() {
+            let container = self.container.as_ref().map(|(_, content)| content);
+            let quick_transfer = input_state.get_modifiers_state().shift_key();
+            let (gui, inventory) = (&mut self.gui, &self.inventory);
+            let (items, icons) = (&self.item_registry, &self.icon_atlas);
+            match screen.render(gui, inventory, container, items, icons, quick_transfer, win_w, win_h) {
+                Some(InventoryAction::Transaction(transaction)) => self.client.send(ToServer::MoveItems(transaction)),
+                Some(InventoryAction::Close) => self.close_inventory_screen(),
+                None => (),
+            }
+        }
+        if !self.ui.should_capture_mouse() {
+            self.ui.focus_menu_buttons(&mut self.gui);
+        }
+        if self.ui.should_show_server_list() {
+            self.server_list.render(&mut self.gui, win_w);
+        } else if !self.ui.should_capture_mouse() {
+            let slider = crate::gui::settings::render_brightness_slider(&mut self.gui, &settings.post_processing, win_w, win_h);
+            if let Some(gamma) = slider {
+                self.new_gamma = Some(gamma);
+            }
+            let slider =
+                crate::gui::settings::render_camera_shake_slider(&mut self.gui, settings.camera_shake, win_w, win_h);
+            if let Some(camera_shake) = slider {
+                self.new_camera_shake = Some(camera_shake);
+            }
+            let accessibility = &settings.accessibility;
+            let options =
+                crate::gui::settings::render_accessibility_options(&mut self.gui, accessibility, win_w, win_h);
+            if let Some(accessibility) = options {
+                self.new_accessibility = Some(accessibility);
+            }
+        }
+        if let Some(replay) = self.replay.as_mut() {
+            // The timeline can be dragged while the cursor is free, in the menu
+            let interactive = !self.ui.should_capture_mouse();
+            let target = crate::gui::replay::render_timeline(&mut self.gui, &replay.control, interactive, win_w, win_h);
+            if replay.scrub(target) {
+                self.reset_replayed_state();
+            }
+        }
+        self.console.render(&mut self.gui, win_w, win_h);
+        crate::gui::tooltip::render_tooltip(&mut self.gui, win_w, win_h);
         self.gui.finish();
-        self.ui_renderer.render(
-            buffers,
-            device,
-            &mut encoder,
-            &data,
-            &self.ui.ui,
-            &mut self.gui,
-            self.ui.should_capture_mouse(),
-        );
+        {
+            profile_scope!("render ui");
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.begin_pass(&mut encoder, "ui");
+            }
+            self.ui_renderer.render(
+                buffers,
+                device,
+                &mut encoder,
+                &mut self.uploader,
+                &data,
+                &self.ui.ui,
+                &mut self.gui,
+                self.ui.should_capture_mouse(),
+            );
+            if let Some(timer) = self.gpu_timer.as_mut() {
+                timer.end_pass(&mut encoder);
+            }
+        }
         self.client_timing.record_part("Render UI");
 
+        self.profiler.end_frame();
+        send_debug_info("Profiler", "spans", self.profiler.summary());
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.resolve(&mut encoder);
+            send_debug_info("Profiler", "gpu", gpu_timer.summary());
+        }
+
         send_perf_breakdown("Client performance", "mainloop", "Client main loop", self.client_timing.extract_part_averages());
 
+        self.uploader.finish();
         Ok((StateTransition::KeepCurrent, encoder.finish()))
     }
 
     fn handle_mouse_motion(&mut self, _settings: Settings, delta: (f64, f64)) {
-        if self.ui.should_update_camera() {
+        if self.ui.should_update_camera() && !self.console.is_open() && !self.is_screen_open() {
             self.yaw_pitch.update_cursor(delta.0, delta.1);
         }
     }
@@ -351,25 +1996,51 @@ impl State for SinglePlayer {
         changes: Vec<(winit::event::MouseButton, winit::event::ElementState)>,
     ) {
         for (button, state) in changes.iter() {
-            let pp = self.physics_simulation.get_player();
+            // The state of a replay can be empty after seeking, but then no block is clicked anyway
+            let pos = self
+                .physics_simulation
+                .get_state()
+                .players
+                .get(&self.player_id)
+                .map(|player| player.aabb.pos)
+                .unwrap_or_else(Vector3::zeros);
             let y = self.yaw_pitch.yaw;
             let p = self.yaw_pitch.pitch;
             match *button {
+                // The player can't interact with blocks while typing a command, trading or watching a replay
+                _ if self.console.is_open() || self.is_screen_open() || self.replay.is_some() => {}
+                // A controlled robot can only hit
+                MouseButton::Left if self.controlled_robot.is_some() => {
+                    if *state == ElementState::Pressed {
+                        self.client.send(ToServer::Attack(pos, y, p));
+                    }
+                }
+                _ if self.controlled_robot.is_some() => {}
                 MouseButton::Left => match *state {
                     ElementState::Pressed => {
-                        self.client.send(ToServer::BreakBlock(pp.aabb.pos, y, p));
+                        if self.is_pointing_at_entity() {
+                            self.client.send(ToServer::Attack(pos, y, p));
+                        } else {
+                            self.client.send(ToServer::BreakBlock(pos, y, p));
+                        }
                     }
                     _ => {}
                 },
                 MouseButton::Right => match *state {
                     ElementState::Pressed => {
-                        self.client.send(ToServer::PlaceBlock(pp.aabb.pos, y, p));
+                        if self.is_pointing_at_interactive_block() {
+                            self.client.send(ToServer::InteractBlock(pos, y, p));
+                        } else if self.is_holding_consumable() {
+                            self.client.send(ToServer::UseItem);
+                        } else {
+                            self.client.send(ToServer::PlaceBlock(pos, y, p));
+                        }
                     }
                     _ => {}
                 },
                 MouseButton::Middle => match *state {
                     ElementState::Pressed => {
-                        self.client.send(ToServer::SelectBlock(pp.aabb.pos, y, p));
+                        self.client.send(ToServer::SelectBlock(pos, y, p));
                     }
                     _ => {}
                 },
@@ -384,13 +2055,157 @@ impl State for SinglePlayer {
                         self.gui.update_mouse_button(false);
                     }
                 },
+                MouseButton::Right => self.gui.update_right_mouse_button(*state == ElementState::Pressed),
                 _ => {}
             }
         }
         self.ui.handle_mouse_state_changes(changes);
     }
 
+    fn recreate_gpu_resources(&mut self, device: &mut wgpu::Device) -> Result<wgpu::CommandBuffer> {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        // The custom character models are in the registry, so they are uploaded again too
+        self.models_to_upload.clear();
+        // The staging buffers belonged to the lost device
+        self.uploader = Uploader::new();
+        let world_renderer = WorldRenderer::new(
+            device,
+            &mut encoder,
+            &mut self.uploader,
+            &self.textures,
+            &self.model_registry,
+            self.shadow_quality,
+            self.light_volumes,
+            self.texture_anisotropy,
+        );
+        self.world.replace_renderer(world_renderer);
+        self.ui_renderer = UiRenderer::new(device);
+        self.ui_renderer.set_icon_atlas(device, &mut encoder, &mut self.uploader, &self.icon_atlas);
+        self.billboard_renderer = BillboardRenderer::new(device);
+        self.post_processing = PostProcessing::new(device);
+        self.gpu_timer = None;
+        self.uploader.finish();
+        Ok(encoder.finish())
+    }
+
     fn handle_key_state_changes(&mut self, changes: Vec<(std::option::Option<u32>, winit::event::ElementState)>) {
+        let pressed = |key: u32| changes.iter().any(|change| *change == (Some(key), ElementState::Pressed));
+        if pressed(TOGGLE_CONSOLE) {
+            self.console.toggle();
+            return;
+        }
+        // The other keys are used to type the command while the console is open, and escape closes it
+        if self.console.is_open() {
+            if pressed(1) {
+                self.console.close();
+            }
+            return;
+        }
+        // The other keys are used to type the text of the sign, and escape closes the editor without saving
+        if self.sign_editor.is_some() {
+            if pressed(1) {
+                self.close_sign_editor(SignAction::Cancel);
+            }
+            return;
+        }
+        // Escape closes the trade screen instead of opening the menu
+        if self.trade.is_some() && pressed(1) {
+            self.trade = None;
+            return;
+        }
+        if self.inventory_screen.is_some() && (pressed(1) || pressed(TOGGLE_INVENTORY)) {
+            self.close_inventory_screen();
+            return;
+        }
+        for (key, state) in changes.iter() {
+            if *key == Some(MARK_MINING_AREA) && *state == ElementState::Pressed && self.ui.should_update_camera() {
+                // The first press marks a corner, the second one sends the area to the robot
+                if let Some((block, _face)) = self.get_pointed_block() {
+                    match self.mining_area_corner.take() {
+                        None => self.mining_area_corner = Some(block),
+                        Some(corner) => self.client.send(ToServer::AssignMiningArea(corner, block)),
+                    }
+                }
+            }
+            // The macros run like the other gameplay keys, when no menu is open
+            if let Some(key) = key.filter(|_| *state == ElementState::Pressed && self.ui.should_update_camera()) {
+                for command in self.macros.commands(key) {
+                    self.console.run(command);
+                }
+            }
+            if *key == Some(TOGGLE_TRACE_RECORDING) && *state == ElementState::Pressed {
+                self.toggle_trace_recording();
+            }
+            if *key == Some(TOGGLE_ACHIEVEMENTS) && *state == ElementState::Pressed {
+                self.show_achievements = !self.show_achievements;
+            }
+            if *key == Some(TOGGLE_STATISTICS) && *state == ElementState::Pressed {
+                self.toggle_statistics();
+            }
+            let can_open = self.ui.should_update_camera() && self.trade.is_none();
+            if *key == Some(TOGGLE_INVENTORY) && *state == ElementState::Pressed && can_open {
+                self.inventory_screen = Some(InventoryScreen::new());
+            }
+            let can_act = self.ui.should_update_camera() && self.replay.is_none();
+            if *key == Some(EMOTE_WAVE) && *state == ElementState::Pressed && can_act {
+                self.client.send(ToServer::Emote(WAVE_EMOTE.to_owned()));
+            }
+            if *key == Some(TOGGLE_LIGHT) && *state == ElementState::Pressed && can_act {
+                self.client.send(ToServer::ToggleLight);
+            }
+            if *key == Some(TOGGLE_ROBOT_CONTROL) && *state == ElementState::Pressed && self.ui.should_update_camera() {
+                self.client.send(ToServer::SetRobotControl(self.controlled_robot.is_none()));
+            }
+            let slot = HOTBAR_SLOTS.iter().position(|slot_key| *key == Some(*slot_key));
+            if let Some(slot) = slot.filter(|_| *state == ElementState::Pressed && self.replay.is_none()) {
+                // The use of the previous item stops, the server tells it too
+                self.selected_slot = slot;
+                self.item_use = None;
+                self.client.send(ToServer::SelectSlot(slot));
+            }
+        }
+        // The arrows move the focus of the menus instead
+        let menu_open = !self.ui.should_capture_mouse() || self.is_screen_open();
+        if let Some(replay) = self.replay.as_ref().filter(|_| !menu_open) {
+            let control = &replay.control;
+            if pressed(REPLAY_TOGGLE_PAUSE) {
+                control.set_paused(!control.is_paused());
+            }
+            if pressed(REPLAY_FASTER) {
+                control.set_speed(control.speed() * 2.0);
+            }
+            if pressed(REPLAY_SLOWER) {
+                control.set_speed(control.speed() / 2.0);
+            }
+            let restart = (pressed(REPLAY_SEEK_BACKWARD) && replay.skip(-SEEK_STEP))
+                | (pressed(REPLAY_SEEK_FORWARD) && replay.skip(SEEK_STEP));
+            if restart {
+                self.reset_replayed_state();
+            }
+        }
         self.ui.handle_key_state_changes(changes);
     }
+
+    fn handle_key_typed(&mut self, key: &winit::keyboard::Key) {
+        self.console.handle_key_typed(key);
+        // The console opened above the sign editor gets the text
+        if self.console.is_open() {
+            return;
+        }
+        if let Some(action) = self.sign_editor.as_mut().and_then(|editor| editor.handle_key_typed(key)) {
+            self.close_sign_editor(action);
+        }
+    }
+
+    fn handle_navigation(&mut self, navigation: Navigation) {
+        // The navigation keys keep their other uses in the game, such as seeking in a replay
+        if !self.ui.should_capture_mouse() || self.is_screen_open() {
+            self.gui.navigate(navigation);
+        }
+    }
+
+    fn show_notice(&mut self, text: String) {
+        self.notice = Some((text, crate::gui::hud::NOTICE_DURATION));
+    }
 }
@@ -0,0 +1,129 @@
+//! Sound effects. The game data has no sounds yet, so the effects are synthesized when they are played. Without an
+//! audio device, the game stays silent. The important sounds can also be shown as captions, see `SoundCues`.
+
+use log::warn;
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, OutputStreamHandle};
+use std::time::Duration;
+
+/// Frequencies of the two tones of the hurt sound, a low thump
+const HURT_FREQUENCIES: (f32, f32) = (110.0, 165.0);
+const HURT_DURATION: Duration = Duration::from_millis(180);
+/// Volume of the hurt sound at full volume, for a hit that takes all the health
+const HURT_VOLUME: f32 = 0.6;
+/// Frequency of the two beeps of the oxygen alarm
+const ALARM_FREQUENCY: f32 = 880.0;
+const ALARM_BEEP_DURATION: Duration = Duration::from_millis(120);
+/// Time between the starts of the two beeps
+const ALARM_BEEP_INTERVAL: Duration = Duration::from_millis(250);
+const ALARM_VOLUME: f32 = 0.25;
+/// Frequencies of the rumble of the wind when a dust storm rises
+const STORM_FREQUENCIES: (f32, f32) = (55.0, 87.0);
+const STORM_DURATION: Duration = Duration::from_millis(2500);
+const STORM_VOLUME: f32 = 0.4;
+/// Frequencies of the boom of an explosion
+const EXPLOSION_FREQUENCIES: (f32, f32) = (40.0, 62.0);
+const EXPLOSION_DURATION: Duration = Duration::from_millis(900);
+/// Volume of the explosion sound at full volume, for an explosion right next to the player
+const EXPLOSION_VOLUME: f32 = 0.8;
+/// Seconds during which a caption is shown
+const CUE_DURATION: f64 = 3.0;
+
+pub struct Sounds {
+    /// The stream plays as long as it is kept, None if there is no audio device
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    /// Volume of all the sounds, from 0 to 1
+    volume: f32,
+}
+
+impl Sounds {
+    pub fn new(volume: f32) -> Self {
+        let output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                warn!("No audio output, the game will be silent: {}", e);
+                None
+            }
+        };
+        Self { output, volume }
+    }
+
+    /// Play a sound, `volume` being its volume when the sounds are at full volume
+    fn play(&self, name: &str, volume: f32, sound: impl Source<Item = f32> + Send + 'static) {
+        let handle = match &self.output {
+            Some((_, handle)) => handle,
+            None => return,
+        };
+        let volume = self.volume.clamp(0.0, 1.0) * volume;
+        if volume <= 0.0 {
+            return;
+        }
+        if let Err(e) = handle.play_raw(sound.amplify(volume)) {
+            warn!("Failed to play the {} sound: {}", name, e);
+        }
+    }
+
+    /// Play the sound of the player getting hurt, louder for the stronger hits. `strength` goes from 0 to 1.
+    pub fn play_hurt(&self, strength: f32) {
+        let mut tone = SineWave::new(HURT_FREQUENCIES.0)
+            .mix(SineWave::new(HURT_FREQUENCIES.1))
+            .take_duration(HURT_DURATION);
+        tone.set_filter_fadeout();
+        // The two tones add up
+        let volume = HURT_VOLUME * (0.5 + strength.clamp(0.0, 1.0) / 2.0) / 2.0;
+        self.play("hurt", volume, tone.fade_in(Duration::from_millis(10)));
+    }
+
+    /// Play the two beeps of the alarm of the suit when the oxygen gets low
+    pub fn play_oxygen_alarm(&self) {
+        let beep = || SineWave::new(ALARM_FREQUENCY).take_duration(ALARM_BEEP_DURATION);
+        self.play("oxygen alarm", ALARM_VOLUME, beep().mix(beep().delay(ALARM_BEEP_INTERVAL)));
+    }
+
+    /// Play the rumble of the wind when a dust storm rises
+    pub fn play_storm(&self) {
+        let mut rumble = SineWave::new(STORM_FREQUENCIES.0)
+            .mix(SineWave::new(STORM_FREQUENCIES.1))
+            .take_duration(STORM_DURATION);
+        rumble.set_filter_fadeout();
+        self.play("storm", STORM_VOLUME / 2.0, rumble.fade_in(STORM_DURATION / 3));
+    }
+
+    /// Play the boom of an explosion, quieter for the explosions further away. `loudness` goes from 0 to 1.
+    pub fn play_explosion(&self, loudness: f32) {
+        let mut boom = SineWave::new(EXPLOSION_FREQUENCIES.0)
+            .mix(SineWave::new(EXPLOSION_FREQUENCIES.1))
+            .take_duration(EXPLOSION_DURATION);
+        boom.set_filter_fadeout();
+        // The two tones add up
+        let volume = EXPLOSION_VOLUME * loudness.clamp(0.0, 1.0) / 2.0;
+        self.play("explosion", volume, boom.fade_in(Duration::from_millis(5)));
+    }
+}
+
+/// Captions of the important sounds, for the players who can't hear them. They are shown for a few seconds if the
+/// sound cues are enabled in the accessibility settings.
+#[derive(Default)]
+pub struct SoundCues {
+    /// The captions and the seconds before they disappear, the newest last
+    cues: Vec<(&'static str, f64)>,
+}
+
+impl SoundCues {
+    /// Show the caption of a sound, or show it longer if it is already shown
+    pub fn add(&mut self, caption: &'static str) {
+        self.cues.retain(|&(shown, _)| shown != caption);
+        self.cues.push((caption, CUE_DURATION));
+    }
+
+    pub fn update(&mut self, seconds_delta: f64) {
+        for (_, remaining) in self.cues.iter_mut() {
+            *remaining -= seconds_delta;
+        }
+        self.cues.retain(|&(_, remaining)| remaining > 0.0);
+    }
+
+    pub fn captions(&self) -> impl DoubleEndedIterator<Item = &'static str> + '_ {
+        self.cues.iter().map(|&(caption, _)| caption)
+    }
+}
@@ -0,0 +1,153 @@
+//! Screen shown when the game starts, while the renderers are warmed up. The shaders of all the renderers are compiled
+//! one per frame, so that the progress bar moves, then the pipelines that don't depend on the game data are created
+//! once. The renderers created when the world is entered find their shaders already compiled, instead of stopping the
+//! first frames of the game to compile them.
+
+use crate::gui::Gui;
+use crate::input::InputState;
+use crate::render::{load_glsl_shader, DiskPipelineCache, PostProcessing, UiRenderer, Uploader, SHADERS};
+use crate::settings::Settings;
+use crate::ui::Ui;
+use crate::window::{State, StateFactory, StateTransition, WindowBuffers, WindowData, WindowFlags};
+use anyhow::Result;
+use log::info;
+use std::time::Instant;
+use winit::event::{ElementState, MouseButton};
+
+pub struct SplashScreen {
+    /// The screen shown after this one, None once it was shown
+    next: Option<StateFactory>,
+    /// The number of shaders of `SHADERS` compiled so far
+    compiled_shaders: usize,
+    started: Instant,
+    ui: Ui,
+    ui_renderer: UiRenderer,
+    uploader: Uploader,
+    gui: Gui,
+    should_exit: bool,
+}
+
+impl SplashScreen {
+    pub fn new_factory(next: StateFactory) -> StateFactory {
+        Box::new(move |_settings, device| Self::new(device, next))
+    }
+
+    pub fn new(device: &mut wgpu::Device, next: StateFactory) -> Result<(Box<dyn State>, wgpu::CommandBuffer)> {
+        info!("Warming up the renderers");
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let ui_renderer = UiRenderer::new(device);
+        Ok((
+            Box::new(Self {
+                next: Some(next),
+                compiled_shaders: 0,
+                started: Instant::now(),
+                ui: Ui::new(),
+                ui_renderer,
+                uploader: Uploader::new(),
+                gui: Gui::new(),
+                should_exit: false,
+            }),
+            encoder.finish(),
+        ))
+    }
+
+    /// The part of the warm-up that is done, the creation of the pipelines being the last step
+    fn progress(&self) -> f32 {
+        self.compiled_shaders as f32 / (SHADERS.len() + 1) as f32
+    }
+}
+
+impl State for SplashScreen {
+    fn update(
+        &mut self,
+        _settings: &mut Settings,
+        _input_state: &InputState,
+        _data: &WindowData,
+        flags: &mut WindowFlags,
+        _seconds_delta: f64,
+        device: &mut wgpu::Device,
+    ) -> Result<StateTransition> {
+        flags.grab_cursor = false;
+        flags.text_input = false;
+        if self.should_exit {
+            return Ok(StateTransition::CloseWindow);
+        }
+        match SHADERS.get(self.compiled_shaders) {
+            Some(&(stage, path)) => {
+                load_glsl_shader(stage, path);
+                self.compiled_shaders += 1;
+                Ok(StateTransition::KeepCurrent)
+            }
+            None => {
+                // Invalid pipelines are reported before the game starts, and the pipeline cache keeps the compiled
+                // pipelines for the next runs
+                drop(PostProcessing::new(device));
+                DiskPipelineCache::save();
+                info!("Warmed up the renderers in {:.2} s", self.started.elapsed().as_secs_f64());
+                Ok(self.next.take().map(StateTransition::ReplaceCurrent).unwrap_or(StateTransition::KeepCurrent))
+            }
+        }
+    }
+
+    fn render<'a>(
+        &mut self,
+        settings: &Settings,
+        buffers: WindowBuffers<'a>,
+        device: &mut wgpu::Device,
+        data: &WindowData,
+        _input_state: &InputState,
+    ) -> Result<(StateTransition, wgpu::CommandBuffer)> {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.uploader.recall(device);
+        crate::render::clear_color_and_depth(&mut encoder, buffers);
+
+        let (win_w, win_h) = (data.logical_window_size.width as i32, data.logical_window_size.height as i32);
+        let status = match self.compiled_shaders {
+            compiled if compiled < SHADERS.len() => format!("Compiling the shaders ({}/{})", compiled, SHADERS.len()),
+            _ => "Creating the pipelines".to_owned(),
+        };
+        self.gui.set_accessibility(&settings.accessibility);
+        self.gui.prepare();
+        crate::gui::splash::render_splash(&mut self.gui, self.progress(), status, win_w, win_h);
+        self.gui.finish();
+
+        self.ui_renderer.render(
+            buffers,
+            device,
+            &mut encoder,
+            &mut self.uploader,
+            data,
+            &self.ui.ui,
+            &mut self.gui,
+            false,
+        );
+
+        self.uploader.finish();
+        Ok((StateTransition::KeepCurrent, encoder.finish()))
+    }
+
+    fn handle_mouse_motion(&mut self, _settings: Settings, _delta: (f64, f64)) {}
+
+    fn handle_cursor_movement(&mut self, logical_position: winit::dpi::LogicalPosition<f64>) {
+        let (x, y) = logical_position.into();
+        self.gui.update_mouse_position(x, y);
+    }
+
+    fn handle_mouse_state_changes(&mut self, _changes: Vec<(MouseButton, ElementState)>) {}
+
+    fn handle_key_state_changes(&mut self, changes: Vec<(Option<u32>, ElementState)>) {
+        for (key, state) in changes.into_iter() {
+            // Escape key
+            if key == Some(1) && state == ElementState::Pressed {
+                self.should_exit = true;
+            }
+        }
+    }
+
+    fn recreate_gpu_resources(&mut self, device: &mut wgpu::Device) -> Result<wgpu::CommandBuffer> {
+        self.ui_renderer = UiRenderer::new(device);
+        self.uploader = Uploader::new();
+        Ok(device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None }).finish())
+    }
+}
@@ -0,0 +1,50 @@
+//! The fade hiding the world while the player is teleported far away, until the chunks around the destination are
+//! loaded
+
+/// Opacity gained or lost every second
+const FADE_SPEED: f64 = 3.0;
+/// Seconds after the arrival after which the world is shown, even if the chunks are still missing
+const LOAD_TIMEOUT: f64 = 5.0;
+
+#[derive(Default)]
+pub struct TeleportFade {
+    /// Set while the server moves the player
+    teleporting: bool,
+    /// Seconds since the arrival, while the chunks around the player are loaded
+    loading: Option<f64>,
+    opacity: f64,
+}
+
+impl TeleportFade {
+    /// Start hiding the world when the server starts a far teleport, and wait for the chunks when it is done
+    pub fn set_teleporting(&mut self, teleporting: bool) {
+        self.teleporting = teleporting;
+        self.loading = if teleporting { None } else { Some(0.0) };
+    }
+
+    /// Fade out or in, depending on whether the chunk of the camera is loaded
+    pub fn update(&mut self, camera_chunk_loaded: bool, seconds_delta: f64) {
+        if let Some(waited) = self.loading.as_mut() {
+            *waited += seconds_delta;
+            if camera_chunk_loaded || *waited > LOAD_TIMEOUT {
+                self.loading = None;
+            }
+        }
+        let step = FADE_SPEED * seconds_delta;
+        self.opacity = if self.is_loading() {
+            (self.opacity + step).min(1.0)
+        } else {
+            (self.opacity - step).max(0.0)
+        };
+    }
+
+    /// Whether the player is teleported or waits for the chunks of the destination
+    pub fn is_loading(&self) -> bool {
+        self.teleporting || self.loading.is_some()
+    }
+
+    /// Opacity of the screen hiding the world, from 0 to 1
+    pub fn opacity(&self) -> f64 {
+        self.opacity
+    }
+}
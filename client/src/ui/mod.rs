@@ -1,4 +1,6 @@
 use self::widgets::{Text, WithStyle};
+use crate::gui::theme::{ClassStyle, UiTheme};
+use crate::gui::Gui;
 use crate::ui::widgets::Button;
 use crate::window::WindowData;
 use anyhow::Result;
@@ -17,14 +19,29 @@ pub mod widgets;
 #[derive(Debug, Clone, Copy)]
 pub enum Message {
     ExitMenu,
+    ToggleServerList,
     ExitGame,
 }
 
+/// The buttons of the menu, from top to bottom
+const MENU_BUTTONS: [(&str, Message); 3] = [
+    ("RESUME", Message::ExitMenu),
+    ("SERVERS", Message::ToggleServerList),
+    ("EXIT", Message::ExitGame),
+];
+/// Id of the first button of the menu in the focus of the Gui, the next buttons use the next ids
+const FIRST_MENU_BUTTON_ID: u32 = 900;
+
 pub struct Ui {
     pub ui: quint::Ui<PrimitiveBuffer, Message>,
     messages: Vec<Message>,
     show_menu: bool,
+    show_server_list: bool,
     should_exit: bool,
+    /// The button of the menu focused with the keyboard or a controller
+    focused_button: Option<usize>,
+    /// The style of the buttons of the menu, from the theme
+    button_style: ClassStyle,
 }
 
 impl Ui {
@@ -33,10 +50,18 @@ impl Ui {
             ui: quint::Ui::new(),
             messages: Vec::new(),
             show_menu: false,
+            show_server_list: false,
             should_exit: false,
+            focused_button: None,
+            button_style: UiTheme::new(None).style("menu.button"),
         }
     }
 
+    /// Use the style of the buttons of the menu of the theme of `gui`
+    pub fn set_theme(&mut self, gui: &Gui) {
+        self.button_style = gui.style("menu.button");
+    }
+
     pub fn cursor_moved(&mut self, p: LogicalPosition<f64>) {
         self.ui.set_cursor_position(quint::Position {
             x: p.x as f32,
@@ -134,19 +159,21 @@ impl Ui {
     }
 
     fn draw_menu(&self) -> WidgetTree<PrimitiveBuffer, Message> {
-        let menu_button = |text: &'static str, message| {
+        let menu_button = |i: usize, &(text, message): &(&str, Message)| {
             wt! {
                 Button {
                     text: vec![
                         TextPart {
                             text: text.to_owned(),
-                            font_size: PxScale::from(50.0),
-                            color: [1.0, 1.0, 1.0, 1.0],
+                            font_size: PxScale::from(self.button_style.font_size),
+                            color: self.button_style.color,
                             font: Some("arcade".to_owned()),
                         },
                     ],
                     message,
                     style: Style::default().absolute_size(400.0, 100.0),
+                    focused: self.focused_button == Some(i),
+                    theme: self.button_style,
                 },
             }
         };
@@ -159,10 +186,7 @@ impl Ui {
                     .center_main()
                     .vertical(),
             }),
-            vec![
-                menu_button("RESUME", Message::ExitMenu),
-                menu_button("EXIT", Message::ExitGame),
-            ],
+            MENU_BUTTONS.iter().enumerate().map(|(i, button)| menu_button(i, button)).collect(),
         );
         buttons_container
     }
@@ -187,15 +211,37 @@ impl Ui {
             if key == Some(1) {
                 if let winit::event::ElementState::Pressed = state {
                     self.show_menu = !self.show_menu;
+                    self.show_server_list = false;
+                    self.focused_button = None;
                 }
             }
         }
     }
 
+    /// Let the keyboard and the controllers focus and press the buttons of the menu, in the focus order of the Gui.
+    /// It must be called during the frames of the Gui while the menu is shown.
+    pub fn focus_menu_buttons(&mut self, gui: &mut Gui) {
+        self.focused_button = None;
+        for (i, &(_, message)) in MENU_BUTTONS.iter().enumerate() {
+            let id = FIRST_MENU_BUTTON_ID + i as u32;
+            if gui.focus_target(id) {
+                self.messages.push(message);
+            }
+            if gui.is_focused(id) {
+                self.focused_button = Some(i);
+            }
+        }
+    }
+
     fn update(&mut self) {
         for message in self.messages.drain(..) {
             match message {
-                Message::ExitMenu => self.show_menu = false,
+                Message::ExitMenu => {
+                    self.show_menu = false;
+                    self.show_server_list = false;
+                    self.focused_button = None;
+                }
+                Message::ToggleServerList => self.show_server_list = !self.show_server_list,
                 Message::ExitGame => self.should_exit = true,
             }
         }
@@ -205,6 +251,11 @@ impl Ui {
         !self.show_menu
     }
 
+    /// Whether the server list is shown over the menu
+    pub fn should_show_server_list(&self) -> bool {
+        self.show_server_list
+    }
+
     pub fn should_exit(&self) -> bool {
         self.should_exit
     }
@@ -255,6 +306,15 @@ pub struct TrianglesPrimitive {
     pub color: [f32; 4],
 }
 
+/// An image of the icon atlas of the items
+#[derive(Debug, Clone)]
+pub struct IconPrimitive {
+    pub layout: quint::Layout,
+    /// The rectangle of the icon in the atlas, as (u0, v0, u1, v1)
+    pub uv: [f32; 4],
+    pub z: f32,
+}
+
 #[derive(Debug, Clone)]
 pub struct TextPart {
     pub text: String,
@@ -268,6 +328,9 @@ pub struct PrimitiveBuffer {
     pub rectangle: Vec<RectanglePrimitive>,
     pub text: Vec<TextPrimitive>,
     pub triangles: Vec<TrianglesPrimitive>,
+    pub icons: Vec<IconPrimitive>,
+    /// Drawn after the rest of the UI and its text, such as the tooltips. Only its rectangles and its text are drawn.
+    pub overlay: Option<Box<PrimitiveBuffer>>,
 }
 
 impl PrimitiveBuffer {
@@ -303,7 +366,9 @@ impl PrimitiveBuffer {
         })
     }*/
 
-    pub fn draw_text_simple(&mut self, x: i32, y: i32, h: i32, text: String, color: [f32; 4], z: f32) {
+    /// Draw text, aligned to the left but centered vertically in a line of height `h`
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_text_sized(&mut self, x: i32, y: i32, h: i32, text: String, color: [f32; 4], z: f32, font_size: f32) {
         self.text.push(TextPrimitive {
             x,
             y,
@@ -311,7 +376,7 @@ impl PrimitiveBuffer {
             h: Some(h),
             parts: vec![TextPart {
                 text,
-                font_size: PxScale::from(20.0),
+                font_size: PxScale::from(font_size),
                 color,
                 font: None,
             }],
@@ -321,6 +386,49 @@ impl PrimitiveBuffer {
         });
     }
 
+    /// Draw text, centered in the rectangle of size `w` x `h`
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_text_centered(
+        &mut self,
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        text: String,
+        color: [f32; 4],
+        z: f32,
+        font_size: f32,
+    ) {
+        self.text.push(TextPrimitive {
+            x,
+            y,
+            w: Some(w),
+            h: Some(h),
+            parts: vec![TextPart {
+                text,
+                font_size: PxScale::from(font_size),
+                color,
+                font: None,
+            }],
+            z,
+            center_horizontally: true,
+            center_vertically: true,
+        });
+    }
+
+    pub fn draw_icon(&mut self, x: i32, y: i32, size: i32, uv: [f32; 4], z: f32) {
+        self.icons.push(IconPrimitive {
+            layout: quint::Layout {
+                x: x as f32,
+                y: y as f32,
+                width: size as f32,
+                height: size as f32,
+            },
+            uv,
+            z,
+        });
+    }
+
     pub fn draw_triangles(&mut self, vertices: Vec<[f32; 3]>, indices: Vec<u32>, color: [f32; 4]) {
         self.triangles.push(TrianglesPrimitive {
             vertices,
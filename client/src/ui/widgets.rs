@@ -1,6 +1,11 @@
 use super::{PrimitiveBuffer, TextPart};
+use crate::gui::theme::ClassStyle;
 use quint::{Event, Layout, Position, Style, Widget};
 
+/// Multipliers of the color of a button for the shades of its bevel
+const DARK_SHADE: f32 = 0.75;
+const LIGHT_SHADE: f32 = 1.25;
+
 pub struct Text {
     pub text: Vec<TextPart>,
 }
@@ -16,6 +21,10 @@ where
     pub message: Message,
     pub text: Vec<TextPart>,
     pub style: Style,
+    /// Whether the button is focused with the keyboard or a controller, which highlights it
+    pub focused: bool,
+    /// The colors of the button and the width of its bevel
+    pub theme: ClassStyle,
 }
 
 impl<T> Widget<PrimitiveBuffer, T> for Text {
@@ -43,29 +52,29 @@ where
     }
 
     fn render(&self, buffer: &mut PrimitiveBuffer, cursor_position: Position, mut l: Layout) {
-        let hovering = l.is_position_inside(cursor_position);
+        // The focused button is pushed like the hovered one, and lighter
+        let hovering = l.is_position_inside(cursor_position) || self.focused;
         // Padded Layout
-        let mut pl = l.with_padding(6.0);
+        let mut pl = l.with_padding(self.theme.padding as f32);
         if hovering {
             l.y += 2.0;
             pl.y += 2.0;
         }
 
-        let main_color = if hovering {
-            [0.75, 0.22, 0.22, 1.0]
-        } else {
-            [0.8, 0.2, 0.2, 1.0]
-        };
-        let dark_shade = if hovering {
-            [0.55, 0.12, 0.12, 1.0]
+        let main_color = if self.focused {
+            self.theme.accent
+        } else if hovering {
+            self.theme.hovered
         } else {
-            [0.6, 0.1, 0.1, 1.0]
+            self.theme.background
         };
-        let light_shade = if hovering {
-            [0.95, 0.32, 0.32, 1.0]
-        } else {
-            [1.0, 0.3, 0.3, 1.0]
+        // The bevel is a darker and a lighter shade of the color of the button
+        let shade = |factor: f32| {
+            let [r, g, b, a] = main_color;
+            [(r * factor).min(1.0), (g * factor).min(1.0), (b * factor).min(1.0), a]
         };
+        let dark_shade = shade(DARK_SHADE);
+        let light_shade = shade(LIGHT_SHADE);
 
         // Top-left lighter shade
         buffer.draw_triangles(
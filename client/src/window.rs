@@ -1,10 +1,13 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 use wgpu::{Device, TextureView, Surface, SurfaceConfiguration};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use common::data::DataSource;
 use futures::executor::block_on;
 use log::{info, warn};
-use texture_packer::texture::Texture;
-use wgpu_types::{TextureFormat, TextureUsages};
+use wgpu_types::TextureUsages;
 use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
 use winit::event::{ElementState, MouseButton, WindowEvent};
 use winit::event::WindowEvent::RedrawRequested;
@@ -12,17 +15,19 @@ use winit::event_loop;
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::KeyCode;
 use winit::platform::scancode::PhysicalKeyExtScancode;
-use winit::window::{CursorGrabMode, Window};
+use winit::window::{CursorGrabMode, CustomCursor, CustomCursorSource, Icon, Window};
 use crate::{
-    input::InputState,
-    settings::Settings
+    gamepad::{GamepadAction, Gamepads},
+    gui::Navigation,
+    input::{InputEvent, InputState, ESCAPE},
+    render::{compatibility, world::ChunkDraws, DiskPipelineCache, GpuTimer},
+    settings::{GraphicsSettings, Settings},
 };
 pub type StateFactory =
     Box<dyn FnOnce(&mut Settings, &mut Device) -> Result<(Box<dyn State>, wgpu::CommandBuffer)>>;
 
 pub enum StateTransition {
     KeepCurrent,
-    #[allow(dead_code)]
     ReplaceCurrent(StateFactory),
     CloseWindow,
 }
@@ -33,12 +38,19 @@ pub struct WindowData {
     pub physical_window_size: PhysicalSize<u32>,
     pub hidpi_factor: f64,
     pub focused: bool,
+    /// Nanoseconds per GPU timestamp tick, if GPU timing is enabled and supported
+    pub gpu_timestamp_period: Option<f32>,
+    /// The adapter drawing the game, and all the adapters of the backends of the settings
+    pub adapter: String,
+    pub available_adapters: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct WindowFlags {
     pub grab_cursor: bool,
     pub window_title: String,
+    /// Whether the keyboard is used to type text, which disables the keyboard toggles such as flight
+    pub text_input: bool,
 }
 
 pub trait State{
@@ -65,6 +77,16 @@ pub trait State{
     fn handle_cursor_movement(&mut self, logical_position: LogicalPosition<f64>);
     fn handle_mouse_state_changes(&mut self, changes: Vec<(MouseButton, ElementState)>);
     fn handle_key_state_changes(&mut self, changes: Vec<(Option<u32>, ElementState)>);
+    /// Called for every key press, including the repeated ones, with the key of the keyboard layout
+    fn handle_key_typed(&mut self, _key: &winit::keyboard::Key) {}
+    /// Called for the navigation keys and the navigation buttons of the controllers, to use the menus without the mouse
+    fn handle_navigation(&mut self, _navigation: Navigation) {}
+    /// Show a short message to the player, such as the result of the installation of a dropped data pack. The states
+    /// without HUD ignore it.
+    fn show_notice(&mut self, _text: String) {}
+
+    /// Recreate all the GPU resources on a new device, after the previous one was lost
+    fn recreate_gpu_resources(&mut self, device: &mut Device) -> Result<wgpu::CommandBuffer>;
 }
 
 pub const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
@@ -76,35 +98,71 @@ trait ApplicationHandler<T> {
 
 
 
-pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
+pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> Result<()> {
     info!("Opening window");
     let window_title = "MarsRobots".to_owned();
-    let event_loop = EventLoop::new().unwrap();
-    let window_attributes = Window::default_attributes().with_title(window_title);
-    let window = event_loop.create_window(window_attributes).unwrap();
+    let event_loop = EventLoop::new().context("Failed to create the event loop")?;
+    let data_source = DataSource::new(PathBuf::from("data"));
+    let window_attributes = Window::default_attributes()
+        .with_title(window_title)
+        .with_window_icon(load_window_icon(&data_source));
+    let window = event_loop.create_window(window_attributes).context("Failed to create the window")?;
     let hidpi_factor = window.scale_factor();
     window.inner_size();
     info!("Creating the swap chain");
-    let instance = wgpu::Instance::default();
-    let surface = instance.create_surface(&window).unwrap();
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: settings.graphics.backend.backends(),
+        ..Default::default()
+    });
+    let surface = instance.create_surface(&window).context("Failed to create the surface")?;
     //Get the Device and the render Queue
-    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance, //TODO: Configurable ?
-        force_fallback_adapter: false,
-        compatible_surface: Some(&surface),
-    }))
-        .expect("No such adapter");
-    let (mut device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-        label: None,
-        required_features: wgpu::Features::empty(),
-        required_limits: wgpu::Limits::default(),
-        memory_hints: Default::default(),
-    }, None))
-        .expect("Unable to create device");
+    let available_adapters = instance
+        .enumerate_adapters(settings.graphics.backend.backends())
+        .iter()
+        .map(|adapter| describe_adapter(&adapter.get_info()))
+        .collect::<Vec<_>>();
+    info!("Available adapters: {:#?}", available_adapters);
+    let adapter = select_adapter(&instance, &surface, &settings.graphics)?;
+    info!("Using the adapter {}", describe_adapter(&adapter.get_info()));
+    crate::crash::set_context("GPU adapter", format!("{:#?}", adapter.get_info()));
+    if compatibility::is_needed(&adapter) {
+        warn!("The adapter lacks some limits or features, the game is rendered in the compatibility mode");
+        compatibility::set_enabled(true);
+    } else if settings.graphics.compatibility_mode {
+        info!("The compatibility mode is enabled in the settings");
+        compatibility::set_enabled(true);
+    }
+    let gpu_timing = settings.gpu_timing && adapter.features().contains(GpuTimer::required_features());
+    if settings.gpu_timing && !gpu_timing {
+        warn!("GPU timing is not supported by the adapter");
+    }
+    let mut required_features = if gpu_timing {
+        GpuTimer::required_features()
+    } else {
+        wgpu::Features::empty()
+    };
+    if adapter.features().contains(ChunkDraws::required_features()) {
+        required_features |= ChunkDraws::required_features();
+    } else {
+        info!("Indirect multi-draw is not supported by the adapter, the chunks will be drawn one by one");
+    }
+    if adapter.features().contains(DiskPipelineCache::required_features()) {
+        required_features |= DiskPipelineCache::required_features();
+    } else {
+        info!("Pipeline caches are not supported by the adapter, the pipelines will be compiled at every start");
+    }
+    let device_lost = Arc::new(AtomicBool::new(false));
+    let (mut device, mut queue) = request_device(&adapter, required_features, &device_lost)?;
 
 
     let swap_chain_capabilities = surface.get_capabilities(&adapter);
-    let swapchain_format = swap_chain_capabilities.formats[0];
+    // The frames are always drawn through sRGB views, so the surface only needs the same channels
+    let swapchain_format = if swap_chain_capabilities.formats.contains(&COLOR_FORMAT) {
+        COLOR_FORMAT
+    } else {
+        COLOR_FORMAT.remove_srgb_suffix()
+    };
+    let swapchain_view_formats = if swapchain_format == COLOR_FORMAT { vec![] } else { vec![COLOR_FORMAT] };
     info!("Creating the multisampled texture buffer");
     let texture_view_descriptor = wgpu::TextureViewDescriptor::default();
     let mut msaa_texture_descriptor = wgpu::TextureDescriptor {
@@ -112,13 +170,13 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
         size: wgpu::Extent3d {
             width: surface.get_current_texture().iter().size_hint().0 as u32,
             height: surface.get_current_texture().iter().size_hint().1.unwrap() as u32,
-            depth_or_array_layers: 0,
+            depth_or_array_layers: 1,
         },
         mip_level_count: 1,
-        sample_count: SAMPLE_COUNT,
+        sample_count: compatibility::sample_count(),
         dimension: wgpu::TextureDimension::D2,
-        format: DEPTH_FORMAT,
-        usage: wgpu::TextureUsages::empty(),
+        format: COLOR_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         view_formats: &[],
     };
     let mut msaa_texture = device.create_texture(&msaa_texture_descriptor);
@@ -129,10 +187,10 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
         size: wgpu::Extent3d {
             width: swapchain_format.block_dimensions().0,
             height: swapchain_format.block_dimensions().1,
-            depth_or_array_layers: 0,
+            depth_or_array_layers: 1,
         },
         mip_level_count: 1,
-        sample_count: SAMPLE_COUNT,
+        sample_count: compatibility::sample_count(),
         dimension: wgpu::TextureDimension::D2,
         format: DEPTH_FORMAT,
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -150,29 +208,49 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
             physical_window_size,
             hidpi_factor,
             focused: false,
+            gpu_timestamp_period: Some(queue.get_timestamp_period()).filter(|_| gpu_timing),
+            adapter: describe_adapter(&adapter.get_info()),
+            available_adapters,
         }
     };
     let mut input_state = InputState::new();
+    let mut gamepads = Gamepads::new();
+
+    // The custom cursor can only be created once the event loop runs
+    let mut custom_cursor = load_custom_cursor(&data_source);
 
     let mut window_flags = WindowFlags {
         grab_cursor: false,
         window_title: window_title.clone(),
+        text_input: false,
     };
 
     info!("Done initializing the window. Moving on to the first state...");
 
     let (mut state, cmd) =
-        initial_state(&mut settings, &mut device).expect("Failed to create initial window state");
+        initial_state(&mut settings, &mut device).context("Failed to create initial window state")?;
     queue.submit(vec![cmd]);
 
     let mut previous_time = std::time::Instant::now();
 
     let mut window_resized = false;
-    let mut mouse_state_changes = Vec::new();
-    let mut key_state_changes = Vec::new();
+    let mut input_events = Vec::new();
+    // The position of the grabbed cursor, to turn the camera with its motion when the raw input is disabled
+    let mut grabbed_cursor_position: Option<PhysicalPosition<f64>> = None;
+    let mut typed_keys = Vec::new();
+    let mut navigations = Vec::new();
+
+    // The error that stopped the main loop, if any
+    let mut fatal_error = None;
+    let fatal_error_ref = &mut fatal_error;
 
     // Main loop
-    event_loop.run_app(&mut move |event,_| {
+    event_loop.run_app(&mut move |event, event_loop_target| {
+        // Stop the main loop, and report the error once the loop has exited
+        let mut exit_with_error = |error: anyhow::Error| {
+            *fatal_error_ref = Some(error);
+            event_loop_target.exit();
+        };
         use winit::event::Event::*;
         match event {
             /* NORMAL EVENT HANDLING */
@@ -181,19 +259,48 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                 match event {
                     Resized(_) | ScaleFactorChanged { .. } => window_resized = true,
                     Moved(_) => (),
-                    CloseRequested | Destroyed => (),
-                    DroppedFile(_) | HoveredFile(_) | HoveredFileCancelled => (),
+                    // The main loop stops, so that the server can save the world
+                    CloseRequested => event_loop_target.exit(),
+                    Destroyed => (),
+                    DroppedFile(path) => {
+                        let notice = match crate::packs::install_pack(&path) {
+                            Ok(info) => format!("Installed the data pack {} {}", info.name, info.version),
+                            Err(e) => {
+                                warn!("{:#}", e);
+                                format!("Failed to install {}", path.display())
+                            }
+                        };
+                        state.show_notice(notice);
+                    }
+                    HoveredFile(_) | HoveredFileCancelled => (),
                     Focused(focused) => {
                         window_data.focused = focused;
                         input_state.clear();
                     }
                     KeyboardInput { event, .. } => {
                         let input = event;
-                        if input_state.process_keyboard_input(input.clone()) {
-                            key_state_changes.push((input.physical_key.to_scancode(), input.state));
+                        if input.state == ElementState::Pressed {
+                            typed_keys.push(input.logical_key.clone());
+                            // The navigation keys repeat while held, and are ignored while typing text
+                            let navigation = input.physical_key.to_scancode().and_then(|key| {
+                                crate::input::key_navigation(key, input_state.get_modifiers_state())
+                            });
+                            if let Some(navigation) = navigation.filter(|_| !window_flags.text_input) {
+                                navigations.push(navigation);
+                            }
+                        }
+                        if input_state.process_keyboard_input(input.clone(), !window_flags.text_input) {
+                            input_events.push(InputEvent::Key(input.physical_key.to_scancode(), input.state));
                         }
                     }
-                    CursorMoved { position, .. } => state.handle_cursor_movement(position.to_logical(hidpi_factor)),
+                    CursorMoved { position, .. } => {
+                        if let Some(previous_position) = grabbed_cursor_position.as_mut() {
+                            let delta = (position.x - previous_position.x, position.y - previous_position.y);
+                            *previous_position = position;
+                            state.handle_mouse_motion(settings, delta);
+                        }
+                        state.handle_cursor_movement(position.to_logical(hidpi_factor));
+                    }
                     CursorEntered { .. } | CursorLeft { .. } | MouseWheel { .. } => (),
                     MouseInput {
                         button,
@@ -201,7 +308,7 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                         ..
                     } => {
                         if input_state.process_mouse_input(button, element_state) {
-                            mouse_state_changes.push((button, element_state));
+                            input_events.push(InputEvent::MouseButton(button, element_state));
                         }
                     }
                     // weird events
@@ -215,12 +322,29 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                 }
                 use winit::event::DeviceEvent::*;
                 match event {
-                    MouseMotion { delta } => state.handle_mouse_motion(settings, delta),
+                    MouseMotion { delta } if settings.raw_mouse_input => state.handle_mouse_motion(settings, delta),
                     _ => (),
                 }
             }
             /* MAIN LOOP TICK */
             _MainEventsCleared => {
+                if let Some(source) = custom_cursor.take() {
+                    window.set_cursor(event_loop_target.create_custom_cursor(source));
+                }
+                // Try to recreate the device if the driver was reset
+                if device_lost.swap(false, Ordering::SeqCst) {
+                    warn!("The GPU device was lost, trying to recreate it...");
+                    match recreate_device(&adapter, required_features, &device_lost, state.as_mut()) {
+                        Ok((new_device, new_queue, cmd)) => {
+                            device = new_device;
+                            queue = new_queue;
+                            queue.submit(vec![cmd]);
+                            // Recreate the surface configuration and the buffers
+                            window_resized = true;
+                        }
+                        Err(e) => return exit_with_error(e),
+                    }
+                }
                 // If the window was resized, update the SwapChain and the window data
                 if window_resized {
                     info!("The window was resized, adjusting buffers...");
@@ -228,16 +352,20 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                     window_data.physical_window_size = window.inner_size();
                     window_data.hidpi_factor = window.scale_factor();
                     window_data.logical_window_size = window_data.physical_window_size.to_logical(window_data.hidpi_factor);
+                    // The surface can't be configured while the window is minimized
+                    if window_data.physical_window_size.width == 0 || window_data.physical_window_size.height == 0 {
+                        return;
+                    }
                     // Update SwapChain
                     let config = SurfaceConfiguration {
                         usage: TextureUsages::RENDER_ATTACHMENT,
-                        format: TextureFormat::R8Unorm,
+                        format: swapchain_format,
                         width: window_data.physical_window_size.width,
                         height: window_data.physical_window_size.height,
                         present_mode: Default::default(),
                         desired_maximum_frame_latency: 0,
                         alpha_mode: Default::default(),
-                        view_formats: Default::default(),
+                        view_formats: swapchain_view_formats.clone(),
                     };
                     surface.configure(&device, &config);
 
@@ -255,26 +383,43 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                 }
                 window_resized = false;
 
+                // The buttons of the controllers act like the navigation keys and the escape key
+                for action in gamepads.poll() {
+                    match action {
+                        GamepadAction::Navigate(navigation) => navigations.push(navigation),
+                        GamepadAction::Escape => {
+                            input_events.push(InputEvent::Key(Some(ESCAPE), ElementState::Pressed));
+                            input_events.push(InputEvent::Key(Some(ESCAPE), ElementState::Released));
+                        }
+                    }
+                }
+
                 // Update state
-                let (v1, v2) = (Vec::new(), Vec::new()); // TODO: clean up
-                state.handle_mouse_state_changes(std::mem::replace(&mut mouse_state_changes, v1));
-                state.handle_key_state_changes(std::mem::replace(&mut key_state_changes, v2));
+                dispatch_input_events(state.as_mut(), input_events.drain(..));
+                for key in typed_keys.drain(..) {
+                    state.handle_key_typed(&key);
+                }
+                for navigation in navigations.drain(..) {
+                    state.handle_navigation(navigation);
+                }
                 let seconds_delta = {
                     let current_time = Instant::now();
                     let delta = current_time - previous_time;
                     previous_time = current_time;
                     delta.as_secs() as f64 + delta.subsec_nanos() as f64 / 1e9
                 };
-                let state_transition = state
-                    .update(
-                        &mut settings,
-                        &input_state,
-                        &window_data,
-                        &mut window_flags,
-                        seconds_delta,
-                        &mut device,
-                    )
-                    .expect("Failed to `update` the current window state"); // TODO: remove this
+                let state_transition = match state.update(
+                    &mut settings,
+                    &input_state,
+                    &window_data,
+                    &mut window_flags,
+                    seconds_delta,
+                    &mut device,
+                ) {
+                    Ok(state_transition) => state_transition,
+                    Err(e) => return exit_with_error(e.context("Failed to `update` the current window state")),
+                };
+                input_state.end_frame();
 
                 // Update window flags
                 window.set_title(&window_flags.window_title);
@@ -282,7 +427,10 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                     window.set_cursor_visible(false);
                     let PhysicalSize { width, height } = window_data.physical_window_size;
                     let center_pos = PhysicalPosition { x: width / 2, y: height / 2 };
-                    match window.set_cursor_grab(CursorGrabMode::Locked) {
+                    // Without the raw input, the cursor must still move to follow the accelerated motion
+                    let grab_mode =
+                        if settings.raw_mouse_input { CursorGrabMode::Locked } else { CursorGrabMode::Confined };
+                    match window.set_cursor_grab(grab_mode) {
                         Err(err) => warn!("Failed to grab cursor ({:?})", err),
                         _ => (),
                     }
@@ -290,7 +438,11 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                         Err(err) => warn!("Failed to center cursor ({:?})", err),
                         _ => (),
                     }
+                    if !settings.raw_mouse_input {
+                        grabbed_cursor_position = Some(center_pos.cast());
+                    }
                 } else {
+                    grabbed_cursor_position = None;
                     window.set_cursor_visible(true);
                     match window.set_cursor_grab(CursorGrabMode::None) {
                         Err(err) => warn!("Failed to ungrab cursor ({:?})", err),
@@ -303,44 +455,69 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
                     StateTransition::KeepCurrent => (),
                     StateTransition::ReplaceCurrent(new_state) => {
                         info!("Transitioning to a new window state...");
-                        let (new_state, cmd) = new_state(&mut settings, &mut device)
-                            .expect("Failed to create next window state");
+                        let (new_state, cmd) = match new_state(&mut settings, &mut device) {
+                            Ok(new_state) => new_state,
+                            Err(e) => return exit_with_error(e.context("Failed to create next window state")),
+                        };
                         state = new_state;
                         queue.submit(vec![cmd]);
                         return;
                     }
                     StateTransition::CloseWindow => {
-
+                        event_loop_target.exit();
+                        return;
                     }
                 }
 
                 // Render frame
-                let swap_chain_output = surface.get_current_texture().expect("Failed to unwrap swap chain output.");
-                let (state_transition, commands) = state
-                    .render(
-                        &settings,
-                        WindowBuffers {
-                            texture_buffer: &swap_chain_output.texture.create_view(&Default::default()),
-                            multisampled_texture_buffer: &msaa_texture_view,
-                            depth_buffer: &depth_texture_view,
-                        },
-                        &mut device,
-                        &window_data,
-                        &input_state,
-                    )
-                    .expect("Failed to `render` the current window state");
+                let swap_chain_output = match surface.get_current_texture() {
+                    Ok(output) => output,
+                    Err(wgpu::SurfaceError::Outdated) | Err(wgpu::SurfaceError::Lost) => {
+                        info!("The surface is outdated, reconfiguring it");
+                        window_resized = true;
+                        return;
+                    }
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        warn!("Timed out while waiting for the next frame, skipping it");
+                        return;
+                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        return exit_with_error(anyhow::anyhow!("Out of memory while acquiring the next frame"));
+                    }
+                };
+                let frame_view = swap_chain_output.texture.create_view(&wgpu::TextureViewDescriptor {
+                    format: Some(COLOR_FORMAT),
+                    ..Default::default()
+                });
+                // Without multisampling, the passes draw directly into the frame
+                let multisampled_view =
+                    if compatibility::sample_count() > 1 { &msaa_texture_view } else { &frame_view };
+                let (state_transition, commands) = match state.render(
+                    &settings,
+                    WindowBuffers {
+                        texture_buffer: &frame_view,
+                        multisampled_texture_buffer: multisampled_view,
+                        depth_buffer: &depth_texture_view,
+                    },
+                    &mut device,
+                    &window_data,
+                    &input_state,
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return exit_with_error(e.context("Failed to `render` the current window state")),
+                };
                 queue.submit(vec![commands]);
                 match state_transition {
                     StateTransition::KeepCurrent => (),
                     StateTransition::ReplaceCurrent(new_state) => {
-                        let (new_state, cmd) = new_state(&mut settings, &mut device)
-                            .expect("Failed to create next window state");
+                        let (new_state, cmd) = match new_state(&mut settings, &mut device) {
+                            Ok(new_state) => new_state,
+                            Err(e) => return exit_with_error(e.context("Failed to create next window state")),
+                        };
                         state = new_state;
                         queue.submit(vec![cmd]);
                     }
-                    StateTransition::CloseWindow => {
-                        ();
-                    }
+                    StateTransition::CloseWindow => event_loop_target.exit(),
                 }
             }
             // TODO: handle this
@@ -349,19 +526,165 @@ pub fn open_window(mut settings: Settings, initial_state: StateFactory) -> () {
             }
             _ => (),
         }
-    }).expect("TODO: panic message")
+    }).context("The event loop failed")?;
+    DiskPipelineCache::save();
+
+    match fatal_error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Use the adapter named in the settings if it can draw to the window, or else the adapter of the power preference
+fn select_adapter(
+    instance: &wgpu::Instance,
+    surface: &Surface,
+    graphics: &GraphicsSettings,
+) -> Result<wgpu::Adapter> {
+    if !graphics.adapter.is_empty() {
+        let named_adapter = instance
+            .enumerate_adapters(graphics.backend.backends())
+            .into_iter()
+            .find(|adapter| adapter.get_info().name == graphics.adapter && adapter.is_surface_supported(surface));
+        match named_adapter {
+            Some(adapter) => return Ok(adapter),
+            None => warn!("The adapter {} is not available, choosing one with the power preference", graphics.adapter),
+        }
+    }
+    block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: graphics.power_preference.to_wgpu(),
+        force_fallback_adapter: false,
+        compatible_surface: Some(surface),
+    }))
+    .context("No suitable GPU adapter found")
+}
+
+/// Give the buffered changes of the keys and of the mouse buttons to the state, in the order they happened
+fn dispatch_input_events(state: &mut dyn State, events: impl Iterator<Item = InputEvent>) {
+    let mut key_changes = Vec::new();
+    let mut mouse_changes = Vec::new();
+    for event in events {
+        match event {
+            InputEvent::Key(key, element_state) => {
+                if !mouse_changes.is_empty() {
+                    state.handle_mouse_state_changes(std::mem::take(&mut mouse_changes));
+                }
+                key_changes.push((key, element_state));
+            }
+            InputEvent::MouseButton(button, element_state) => {
+                if !key_changes.is_empty() {
+                    state.handle_key_state_changes(std::mem::take(&mut key_changes));
+                }
+                mouse_changes.push((button, element_state));
+            }
+        }
+    }
+    state.handle_mouse_state_changes(mouse_changes);
+    state.handle_key_state_changes(key_changes);
+}
+
+/// The icon of the window in the `icon.png` file of the data
+fn load_window_icon(data_source: &DataSource) -> Option<Icon> {
+    let icon = data_source
+        .read(&data_source.path(WINDOW_ICON_FILE))
+        .context("Failed to read the window icon")
+        .and_then(|bytes| image::load_from_memory(&bytes).context("Failed to decode the window icon"))
+        .map(|image| image.into_rgba8())
+        .and_then(|image| {
+            let (width, height) = image.dimensions();
+            Icon::from_rgba(image.into_raw(), width, height).context("Invalid window icon")
+        });
+    match icon {
+        Ok(icon) => Some(icon),
+        Err(e) => {
+            warn!("{:#}", e);
+            None
+        }
+    }
+}
+
+/// The cursor in the `cursor.png` file of the data, if there is one, shown instead of the cursor of the system when
+/// the cursor is not grabbed. Its hotspot is the top left corner of the image.
+fn load_custom_cursor(data_source: &DataSource) -> Option<CustomCursorSource> {
+    let bytes = data_source.read(&data_source.path(CURSOR_FILE)).ok()?;
+    let cursor = image::load_from_memory(&bytes)
+        .context("Failed to decode the cursor")
+        .and_then(|image| {
+            let image = image.into_rgba8();
+            let width = u16::try_from(image.width()).context("The cursor is too wide")?;
+            let height = u16::try_from(image.height()).context("The cursor is too high")?;
+            CustomCursor::from_rgba(image.into_raw(), width, height, 0, 0).context("Invalid cursor")
+        });
+    match cursor {
+        Ok(cursor) => Some(cursor),
+        Err(e) => {
+            warn!("{:#}", e);
+            None
+        }
+    }
+}
+
+/// The name, the backend and the driver of an adapter
+fn describe_adapter(info: &wgpu::AdapterInfo) -> String {
+    format!("{} ({:?}, {:?}, {} {})", info.name, info.backend, info.device_type, info.driver, info.driver_info)
+}
+
+/// Create the device with the pipeline cache of the adapter, and set `device_lost` when it is lost
+fn request_device(
+    adapter: &wgpu::Adapter,
+    required_features: wgpu::Features,
+    device_lost: &Arc<AtomicBool>,
+) -> Result<(Device, wgpu::Queue)> {
+    let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        label: None,
+        required_features,
+        required_limits: compatibility::required_limits(adapter),
+        memory_hints: Default::default(),
+    }, None))
+        .context("Unable to create device")?;
+    let device_lost = device_lost.clone();
+    device.set_device_lost_callback(move |reason, message| {
+        // Dropping or destroying the device on purpose also calls this
+        if matches!(reason, wgpu::DeviceLostReason::Unknown | wgpu::DeviceLostReason::DeviceInvalid) {
+            log::error!("GPU device lost ({:?}): {}", reason, message);
+            device_lost.store(true, Ordering::SeqCst);
+        }
+    });
+    DiskPipelineCache::load(adapter, &device);
+    Ok((device, queue))
+}
+
+/// Create a new device after the previous one was lost, and recreate the GPU resources of the state
+fn recreate_device(
+    adapter: &wgpu::Adapter,
+    required_features: wgpu::Features,
+    device_lost: &Arc<AtomicBool>,
+    state: &mut dyn State,
+) -> Result<(Device, wgpu::Queue, wgpu::CommandBuffer)> {
+    let (mut device, queue) = request_device(adapter, required_features, device_lost)
+        .context("Failed to recreate the GPU device")?;
+    let cmd = state
+        .recreate_gpu_resources(&mut device)
+        .context("Failed to recreate the GPU resources")?;
+    info!("The GPU device was recreated");
+    Ok((device, queue, cmd))
 }
 
+/// Dark grey, in linear space like all the colors drawn into the sRGB buffers. It is 0.2 once encoded.
 pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
-    r: 0.2,
-    g: 0.2,
-    b: 0.2,
+    r: 0.0331,
+    g: 0.0331,
+    b: 0.0331,
     a: 1.0,
 };
 
 pub const CLEAR_DEPTH: f32 = 1.0;
 pub  const SAMPLE_COUNT: u32 = 4;
 
+/// The files of the data with the icon of the window and the custom cursor
+const WINDOW_ICON_FILE: &str = "icon.png";
+const CURSOR_FILE: &str = "cursor.png";
+
 
 #[derive(Debug, Clone, Copy)]
 pub struct WindowBuffers<'a> {
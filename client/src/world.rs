@@ -1,13 +1,20 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use common::{
-    block::BlockMesh,
+    biome::BiomeId,
+    debug::send_debug_info,
+    block::{BlockId, BlockMesh},
+    data::{vox::VoxelModel, TextureArray},
     physics::BlockContainer,
     player::{CloseChunks, RenderDistance},
-    world::{BlockPos, ChunkPos, Chunk, LightChunk},
+    time::WorldTime,
+    worker::{CancelToken, Priority},
+    world::{BlockPos, ChunkPos, Chunk, LightChunk, WorldHeight, BLOCK_DAMAGE_STAGES, CHUNK_SIZE},
 };
-use crate::render::WorldRenderer;
-use crate::render::world::{ChunkMeshData, MeshingWorker, start_meshing_worker};
+use crate::render::{DebugBox, DebugRenderModes, PointLight, ShadowQuality, Uploader, WorldRenderer};
+use nalgebra::Vector3;
+use crate::render::world::{ChunkMesh, ChunkMeshData, MeshingWorker, start_meshing_worker};
 
 /// Client-side world.
 /// It is currently responsible for:
@@ -18,20 +25,105 @@ pub struct World {
     chunks: HashMap<ChunkPos, ClientChunk>,
     /// The meshing worker
     meshing_worker: MeshingWorker,
+    meshing_threads: usize,
+    /// The meshes waiting to be uploaded to the GPU, at most one per chunk
+    pending_meshes: HashMap<ChunkPos, ChunkMesh>,
+    /// Time spent uploading meshes in a frame, after which the remaining meshes wait for the next frames
+    mesh_upload_budget: Duration,
     /// The chunks the player can see
     close_chunks: CloseChunks,
     /// The renderer
     renderer: WorldRenderer,
+    /// Whether each block is solid
+    solid_blocks: Vec<bool>,
+    /// The mesh of each block
+    block_meshes: Vec<BlockMesh>,
+    /// The color and the light level of the dynamic light of each block, if it has one
+    dynamic_lights: Vec<Option<([f32; 3], u8)>>,
+    /// The stage of the cracks of the blocks being mined
+    block_damages: HashMap<BlockPos, u8>,
+    /// The vertical bounds of the dimension, the server never sends the chunks outside of them
+    height: WorldHeight,
 }
 
 impl World {
     /// Create a new empty world using the provided chunks
-    pub fn new(block_meshes: Vec<BlockMesh>, renderer: WorldRenderer) -> Self {
+    pub fn new(
+        block_meshes: Vec<BlockMesh>,
+        solid_blocks: Vec<bool>,
+        dynamic_lights: Vec<Option<([f32; 3], u8)>>,
+        renderer: WorldRenderer,
+        mesh_upload_budget: Duration,
+        meshing_threads: usize,
+    ) -> Self {
         Self {
+            solid_blocks,
+            dynamic_lights,
             chunks: HashMap::new(),
-            meshing_worker: start_meshing_worker(block_meshes),
+            meshing_worker: start_meshing_worker(block_meshes.clone(), meshing_threads),
+            meshing_threads,
+            pending_meshes: HashMap::new(),
+            mesh_upload_budget,
+            block_meshes,
             close_chunks: CloseChunks::new(&RenderDistance::default()),
             renderer,
+            block_damages: HashMap::new(),
+            height: WorldHeight::UNBOUNDED,
+        }
+    }
+
+    /// Return block at position `pos` in the world. 0 is returned if the chunk is not loaded
+    pub fn get_block(&self, pos: BlockPos) -> BlockId {
+        match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => 0,
+            Some(client_chunk) => client_chunk.chunk.get_block_at(pos.pos_in_containing_chunk()),
+        }
+    }
+
+    /// Return the biome of the column at position `pos`, if its chunk is loaded
+    pub fn biome_at(&self, pos: BlockPos) -> Option<BiomeId> {
+        let (x, _, z) = pos.pos_in_containing_chunk();
+        let client_chunk = self.chunks.get(&pos.containing_chunk_pos())?;
+        Some(client_chunk.chunk.get_biome_at((x, z)))
+    }
+
+    /// Whether a chunk was received and meshed. The chunks outside of the vertical bounds never are.
+    pub fn is_chunk_meshed(&self, pos: ChunkPos) -> bool {
+        match self.chunks.get(&pos) {
+            Some(client_chunk) => !client_chunk.needs_remesh && !client_chunk.is_in_meshing_queue,
+            None => !self.height.contains_chunk(pos),
+        }
+    }
+
+    /// Count the chunks received and meshed among the chunks at most `radius` chunks away from `center`. The chunks
+    /// outside of the vertical bounds count as both.
+    pub fn chunk_progress(&self, center: ChunkPos, radius: i64) -> ChunkProgress {
+        let mut progress = ChunkProgress::default();
+        for i in -radius..=radius {
+            for j in -radius..=radius {
+                for k in -radius..=radius {
+                    let pos = center.offset(i, j, k);
+                    progress.total += 1;
+                    if self.chunks.contains_key(&pos) || !self.height.contains_chunk(pos) {
+                        progress.received += 1;
+                    }
+                    if self.is_chunk_meshed(pos) {
+                        progress.meshed += 1;
+                    }
+                }
+            }
+        }
+        progress
+    }
+
+    /// Set the vertical bounds of the dimension, when the player joins or enters another dimension
+    pub fn set_height(&mut self, height: WorldHeight) {
+        self.height = height;
+        // The lowest chunks hide their bottom faces
+        for (pos, client_chunk) in self.chunks.iter_mut() {
+            if pos.py == height.min_chunk_y {
+                client_chunk.needs_remesh = true;
+            }
         }
     }
 
@@ -39,11 +131,20 @@ impl World {
     pub fn add_chunk(&mut self, chunk: Arc<Chunk>, light_chunk: Arc<LightChunk>) {
         // TODO: make sure this only happens once
         let chunk_pos = chunk.pos;
+        // If only the light changed, the chunks with a light volume don't need to be meshed again
+        let (light_only, needs_remesh) = match self.chunks.get(&chunk_pos) {
+            Some(client_chunk) => (client_chunk.chunk.data == chunk.data, client_chunk.needs_remesh),
+            None => (false, true),
+        };
+        let lights = find_lights(&chunk, &self.dynamic_lights);
         self.chunks.insert(chunk_pos, ClientChunk {
             chunk,
             light_chunk,
+            lights,
             is_in_meshing_queue: false,
-            needs_remesh: true,
+            meshing_job: None,
+            needs_remesh: needs_remesh || !light_only,
+            needs_relight: false,
         });
         // Queue adjacent chunks for meshing
         for i in -1..=1 {
@@ -51,54 +152,125 @@ impl World {
                 for k in -1..=1 {
                     let adjacent_chunk_pos = chunk_pos.offset(i, j, k);
                     if let Some(client_chunk) = self.chunks.get_mut(&adjacent_chunk_pos) {
-                        client_chunk.needs_remesh = true;
+                        if light_only && self.renderer.has_light_volume(adjacent_chunk_pos) {
+                            client_chunk.needs_relight = true;
+                        } else {
+                            client_chunk.needs_remesh = true;
+                        }
                     }
                 }
             }
         }
     }
 
-    /// Fetch the new chunk meshes from the meshing worker
+    /// Fetch the new chunk meshes from the meshing worker, and upload the closest ones to the GPU until the upload
+    /// budget of the frame is spent. The chunks stay in the meshing queue until their mesh is uploaded.
     pub fn get_new_chunk_meshes(
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        camera_chunk: ChunkPos,
     ) {
         while let Some(mesh) = self.meshing_worker.get_result() {
-            if let Some(client_chunk) = self.chunks.get_mut(&mesh.0) {
+            if self.chunks.contains_key(&mesh.pos) {
+                // A newer mesh replaces the one that wasn't uploaded yet
+                self.pending_meshes.insert(mesh.pos, mesh);
+            }
+        }
+
+        let mut positions = self.pending_meshes.keys().copied().collect::<Vec<_>>();
+        positions.sort_unstable_by_key(|pos| pos.squared_euclidian_distance(camera_chunk));
+        let start = Instant::now();
+        let mut uploaded = 0;
+        for pos in positions {
+            // At least one mesh is uploaded every frame
+            if uploaded > 0 && start.elapsed() >= self.mesh_upload_budget {
+                break;
+            }
+            let mesh = self.pending_meshes.remove(&pos).expect("Logic error");
+            if let Some(client_chunk) = self.chunks.get_mut(&pos) {
                 client_chunk.is_in_meshing_queue = false;
-                self.renderer.update_chunk_mesh(device, encoder, mesh);
+                client_chunk.meshing_job = None;
+                self.renderer.update_chunk_mesh(device, encoder, uploader, mesh);
+                uploaded += 1;
             }
         }
+        send_debug_info(
+            "Chunks",
+            "meshupload",
+            format!(
+                "Uploaded {} chunk meshes in {:.2} ms, {} waiting",
+                uploaded,
+                start.elapsed().as_secs_f64() * 1000.0,
+                self.pending_meshes.len()
+            ),
+        );
     }
 
     /// Remove chunks that are too far for the player
     pub fn remove_far_chunks(&mut self, player_chunk: ChunkPos, render_distance: &RenderDistance) {
-        let Self { ref mut chunks, ref mut renderer, .. } = self;
-        chunks.retain(|chunk_pos, _| {
+        let Self { ref mut chunks, ref mut renderer, ref mut pending_meshes, ref mut block_damages, .. } = self;
+        chunks.retain(|chunk_pos, client_chunk| {
             if render_distance.is_chunk_visible(player_chunk, *chunk_pos) {
                 true
             } else {
+                // The mesh of an unloaded chunk is not needed anymore
+                if let Some(token) = client_chunk.meshing_job.take() {
+                    token.cancel();
+                }
                 renderer.remove_chunk_mesh(*chunk_pos);
+                pending_meshes.remove(chunk_pos);
                 false
             }
-        })
+        });
+        block_damages.retain(|pos, _| chunks.contains_key(&pos.containing_chunk_pos()));
+    }
+
+    /// Remove all the chunks, when a replay starts again from the beginning
+    pub fn clear_chunks(&mut self) {
+        for (chunk_pos, client_chunk) in self.chunks.drain() {
+            if let Some(token) = client_chunk.meshing_job {
+                token.cancel();
+            }
+            self.renderer.remove_chunk_mesh(chunk_pos);
+        }
+        self.pending_meshes.clear();
+        self.block_damages.clear();
+    }
+
+    /// Set the stage of the cracks of a block being mined, 0 when it is intact again
+    pub fn set_block_damage(&mut self, pos: BlockPos, stage: u8) {
+        match stage {
+            0 => self.block_damages.remove(&pos),
+            _ => self.block_damages.insert(pos, stage.min(BLOCK_DAMAGE_STAGES)),
+        };
     }
 
-    /// Start the meshing of a few chunks
+    /// Start the meshing of a few chunks. The chunks around the player come first, so that the blocks they place or
+    /// break are shown quickly, and the chunks whose light changed come last.
     pub fn enqueue_chunks_for_meshing(&mut self, player_chunk: ChunkPos, render_distance: &RenderDistance) {
         self.close_chunks.update(render_distance);
         for pos in self.close_chunks.get_close_chunks() {
             let pos = pos.offset_by_pos(player_chunk);
             if let Some(client_chunk) = self.chunks.get(&pos) {
-                if client_chunk.needs_remesh && !client_chunk.is_in_meshing_queue {
-                    let res = self.meshing_worker.enqueue(self.create_chunk_mesh_data(pos));
+                if (client_chunk.needs_remesh || client_chunk.needs_relight) && !client_chunk.is_in_meshing_queue {
+                    let priority = if !client_chunk.needs_remesh {
+                        Priority::Low
+                    } else if pos.squared_euclidian_distance(player_chunk) <= 3 {
+                        Priority::High
+                    } else {
+                        Priority::Normal
+                    };
+                    let res = self.meshing_worker.enqueue_with_priority(self.create_chunk_mesh_data(pos), priority);
                     match res {
                         // If the meshing queue is not full, update chunk status
-                        Ok(()) => {
+                        Ok(token) => {
                             let client_chunk = self.chunks.get_mut(&pos).expect("Logic error");
                             client_chunk.needs_remesh = false;
+                            client_chunk.needs_relight = false;
                             client_chunk.is_in_meshing_queue = true;
+                            client_chunk.meshing_job = Some(token);
                         },
                         // If the meshing queue is full, stop
                         Err(_) => break,
@@ -130,6 +302,9 @@ impl World {
             light_chunk: client_chunk.light_chunk.clone(),
             all_chunks,
             all_light_chunks,
+            mesh: client_chunk.needs_remesh,
+            light_volume: self.renderer.light_volumes_enabled(),
+            bottom_of_world: pos.py == self.height.min_chunk_y,
         }
     }
 
@@ -138,16 +313,152 @@ impl World {
         &mut self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
         buffers: crate::window::WindowBuffers,
         data: &crate::window::WindowData,
         frustum: &crate::render::Frustum,
         enable_culling: bool,
         pointed_block: Option<(BlockPos, usize)>,
         models: &[crate::render::world::Model],
+        lights: &[PointLight],
+        world_time: WorldTime,
+        debug_modes: &DebugRenderModes,
+        debug_boxes: &[DebugBox],
     ) {
         // TODO: remove some of the parameters and calculate them here instead
-        self.get_new_chunk_meshes(device, encoder);
-        self.renderer.render(device, encoder, buffers, data, frustum, enable_culling, pointed_block, models);
+        let camera_chunk = BlockPos::from(frustum.position).containing_chunk_pos();
+        self.get_new_chunk_meshes(device, encoder, uploader, camera_chunk);
+        // The blocks broken before they were told to be intact have no cracks
+        let damaged_blocks = self
+            .block_damages
+            .iter()
+            .filter(|(pos, _)| self.get_block(**pos) != 0)
+            .map(|(&pos, &stage)| (pos, stage))
+            .collect::<Vec<_>>();
+        self.renderer.render(
+            device,
+            encoder,
+            uploader,
+            buffers,
+            data,
+            frustum,
+            enable_culling,
+            pointed_block,
+            &damaged_blocks,
+            models,
+            lights,
+            world_time,
+            debug_modes,
+            debug_boxes,
+        );
+    }
+
+    /// Add the lights of the light-emitting blocks closer than `max_distance` to `lights`
+    pub fn get_point_lights(&self, camera: Vector3<f64>, max_distance: f64, lights: &mut Vec<PointLight>) {
+        let camera_chunk = BlockPos::from(camera).containing_chunk_pos();
+        let max_chunk_distance = (max_distance / CHUNK_SIZE as f64).ceil() as u64 + 1;
+        for (chunk_pos, client_chunk) in self.chunks.iter() {
+            if chunk_pos.squared_euclidian_distance(camera_chunk) > max_chunk_distance * max_chunk_distance {
+                continue;
+            }
+            for &pos in client_chunk.lights.iter() {
+                let block = client_chunk.chunk.get_block_at(pos.pos_in_containing_chunk());
+                if let Some((color, light_level)) = self.dynamic_lights[block as usize] {
+                    let position = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+                    if (position - camera).norm() < max_distance {
+                        lights.push(PointLight {
+                            position,
+                            color,
+                            intensity: 1.0,
+                            radius: light_level as f32,
+                            spot: None,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn set_shadow_quality(&mut self, device: &wgpu::Device, quality: ShadowQuality) {
+        self.renderer.set_shadow_quality(device, quality);
+    }
+
+    pub fn set_texture_lod_bias(&mut self, bias: f32) {
+        self.renderer.set_texture_lod_bias(bias);
+    }
+
+    /// Replace the renderer, after the GPU device was lost. All the chunks will be meshed again.
+    pub fn replace_renderer(&mut self, renderer: WorldRenderer) {
+        self.renderer = renderer;
+        for client_chunk in self.chunks.values_mut() {
+            client_chunk.needs_remesh = true;
+        }
+    }
+
+    /// Replace the block meshes after the data was reloaded. The chunks containing the blocks whose mesh changed
+    /// are meshed again, as well as their neighbours since the faces between the chunks may have changed.
+    pub fn update_block_meshes(
+        &mut self,
+        block_meshes: Vec<BlockMesh>,
+        solid_blocks: Vec<bool>,
+        dynamic_lights: Vec<Option<([f32; 3], u8)>>,
+    ) {
+        let changed_blocks = (0..block_meshes.len())
+            .map(|id| self.block_meshes.get(id) != Some(&block_meshes[id]))
+            .collect::<Vec<_>>();
+        // The chunks in the queue of the old worker will never come back, and the pending meshes are outdated
+        self.meshing_worker = start_meshing_worker(block_meshes.clone(), self.meshing_threads);
+        self.pending_meshes.clear();
+        self.block_meshes = block_meshes;
+        self.solid_blocks = solid_blocks;
+        self.dynamic_lights = dynamic_lights;
+
+        let mut changed_chunks = Vec::new();
+        for (pos, client_chunk) in self.chunks.iter_mut() {
+            client_chunk.lights = find_lights(&client_chunk.chunk, &self.dynamic_lights);
+            if client_chunk.is_in_meshing_queue {
+                client_chunk.is_in_meshing_queue = false;
+                client_chunk.meshing_job = None;
+                client_chunk.needs_remesh = true;
+            }
+            if client_chunk.chunk.data.iter().any(|&block| changed_blocks[block as usize]) {
+                changed_chunks.push(*pos);
+            }
+        }
+        for chunk_pos in changed_chunks {
+            for i in -1..=1 {
+                for j in -1..=1 {
+                    for k in -1..=1 {
+                        if let Some(client_chunk) = self.chunks.get_mut(&chunk_pos.offset(i, j, k)) {
+                            client_chunk.needs_remesh = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replace the block textures of the renderer
+    pub fn update_textures(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        textures: &TextureArray,
+    ) {
+        self.renderer.update_textures(device, encoder, uploader, textures);
+    }
+
+    /// Upload a model that was added to the registry after the creation of the renderer
+    pub fn upload_model(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        uploader: &mut Uploader,
+        mesh_id: u32,
+        model: &VoxelModel,
+    ) {
+        self.renderer.upload_model(device, encoder, uploader, mesh_id, model);
     }
 
     /// Number of loaded chunks
@@ -158,22 +469,55 @@ impl World {
 
 impl BlockContainer for World {
     fn is_block_full(&self, pos: BlockPos) -> bool {
-        // TODO: use BlockRegistry
-        match self.chunks.get(&pos.containing_chunk_pos()) {
-            None => false,
-            Some(chunk) => chunk.chunk.get_block_at(pos.pos_in_containing_chunk()) != 0,
-        }
+        self.height.is_below(pos) || self.solid_blocks[self.get_block(pos) as usize]
+    }
+
+    fn is_block_targetable(&self, pos: BlockPos) -> bool {
+        self.get_block(pos) != 0
     }
 }
 
+/// The number of chunks of an area that were received and meshed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkProgress {
+    pub received: usize,
+    pub meshed: usize,
+    pub total: usize,
+}
+
 /// The data for each chunk stored by the client
 struct ClientChunk {
     /// The chunk itself
     pub chunk: Arc<Chunk>,
     /// The light chunk
     pub light_chunk: Arc<LightChunk>,
+    /// The blocks of the chunk with a dynamic light
+    pub lights: Vec<BlockPos>,
     /// True if the chunk is in the meshing queue
     pub is_in_meshing_queue: bool,
+    /// Cancels the meshing of the chunk when it is unloaded
+    pub meshing_job: Option<CancelToken>,
     /// True if the chunk needs to be meshed, for example before it never was meshed or because it changed.
     pub needs_remesh: bool,
-}
\ No newline at end of file
+    /// True if only the light of the chunk changed, and its light volume needs to be computed again
+    pub needs_relight: bool,
+}
+/// Find the blocks of a chunk with a dynamic light
+fn find_lights(chunk: &Chunk, dynamic_lights: &[Option<([f32; 3], u8)>]) -> Vec<BlockPos> {
+    let mut lights = Vec::new();
+    for i in 0..CHUNK_SIZE {
+        for j in 0..CHUNK_SIZE {
+            for k in 0..CHUNK_SIZE {
+                let block = chunk.get_block_at((i, j, k));
+                if dynamic_lights.get(block as usize).copied().flatten().is_some() {
+                    lights.push(BlockPos {
+                        px: chunk.pos.px * CHUNK_SIZE as i64 + i as i64,
+                        py: chunk.pos.py * CHUNK_SIZE as i64 + j as i64,
+                        pz: chunk.pos.pz * CHUNK_SIZE as i64 + k as i64,
+                    });
+                }
+            }
+        }
+    }
+    lights
+}
@@ -0,0 +1,40 @@
+//! Milestones that the players unlock by playing
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstBlockMined,
+    FirstBlockPlaced,
+    FirstRobotBuilt,
+    /// Be close to a meteor impact
+    SurvivedMeteorImpact,
+}
+
+impl Achievement {
+    /// All the achievements, in the order they are listed
+    pub const ALL: [Achievement; 4] = [
+        Self::FirstBlockMined,
+        Self::FirstBlockPlaced,
+        Self::FirstRobotBuilt,
+        Self::SurvivedMeteorImpact,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FirstBlockMined => "Breaking ground",
+            Self::FirstBlockPlaced => "Builder",
+            Self::FirstRobotBuilt => "Automation",
+            Self::SurvivedMeteorImpact => "Close call",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::FirstBlockMined => "Mine a block",
+            Self::FirstBlockPlaced => "Place a block",
+            Self::FirstRobotBuilt => "Build a mining robot",
+            Self::SurvivedMeteorImpact => "Survive a meteor impact nearby",
+        }
+    }
+}
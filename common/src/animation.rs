@@ -0,0 +1,120 @@
+//! Keyframe animations of the voxel models, such as walking or waving.
+//!
+//! The animations of a model are in a data file next to it with the same name, for example `model/chr_knight.ron` for
+//! `model/chr_knight.vox`. An animation moves and rotates the parts of the model over time, interpolating between its
+//! keyframes. The part named `body` moves the whole model, and the other parts are the parts of the voxel model, such
+//! as the wheels of the robots, which turn around their pivot.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The part of the model that moves the whole model
+pub const BODY_PART: &str = "body";
+/// The part of the character models that follows the pitch of the player
+pub const HEAD_PART: &str = "head";
+/// Played when the entity stands still
+pub const IDLE_ANIMATION: &str = "idle";
+/// Played when the entity moves
+pub const WALK_ANIMATION: &str = "walk";
+/// Played when the entity breaks a block or hits something
+pub const MINE_ANIMATION: &str = "mine";
+/// Longest name of an animation that the players can play as an emote
+pub const MAX_ANIMATION_NAME_LENGTH: usize = 32;
+
+/// The animations of a model, by name
+pub type AnimationSet = BTreeMap<String, Animation>;
+
+/// The offset and the rotation of a part at some time of an animation
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pose {
+    /// Offset of the part, in voxels of the model: x to the right, y up and z to the back of the model
+    pub offset: [f32; 3],
+    /// Rotation of the part around the x, y and z axes of the model, in degrees. The rotations are applied in this
+    /// order: a positive x rotation leans the part back, a positive y rotation turns it left and a positive z
+    /// rotation tilts it to the left.
+    pub rotation: [f32; 3],
+}
+
+impl Pose {
+    /// The pose between `self` (t = 0) and `other` (t = 1)
+    pub fn lerp(&self, other: &Pose, t: f32) -> Pose {
+        let lerp = |a: [f32; 3], b: [f32; 3]| std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+        Pose {
+            offset: lerp(self.offset, other.offset),
+            rotation: lerp(self.rotation, other.rotation),
+        }
+    }
+}
+
+/// The pose of a part at some time, see `Pose` for the units
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// Seconds since the start of the animation
+    pub time: f32,
+    #[serde(default)]
+    pub offset: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+}
+
+impl Keyframe {
+    pub fn pose(&self) -> Pose {
+        Pose { offset: self.offset, rotation: self.rotation }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Animation {
+    /// Whether the animation starts over when it ends, otherwise it is played once
+    #[serde(default)]
+    pub looping: bool,
+    /// The keyframes of each part, sorted by time
+    pub parts: BTreeMap<String, Vec<Keyframe>>,
+}
+
+impl Animation {
+    /// Check that every part has keyframes, sorted by time
+    pub fn validate(&self) -> Result<(), String> {
+        for (part, keyframes) in self.parts.iter() {
+            if keyframes.is_empty() {
+                return Err(format!("part {} has no keyframes", part));
+            }
+            if keyframes.iter().any(|keyframe| !keyframe.time.is_finite() || keyframe.time < 0.0) {
+                return Err(format!("part {} has a keyframe with an invalid time", part));
+            }
+            if keyframes.windows(2).any(|pair| pair[0].time > pair[1].time) {
+                return Err(format!("the keyframes of part {} are not sorted by time", part));
+            }
+        }
+        Ok(())
+    }
+
+    /// Seconds until the last keyframe
+    pub fn duration(&self) -> f32 {
+        let last_times = self.parts.values().filter_map(|keyframes| keyframes.last());
+        last_times.map(|keyframe| keyframe.time).fold(0.0, f32::max)
+    }
+
+    /// Whether an animation played once has ended after `time` seconds
+    pub fn is_finished(&self, time: f32) -> bool {
+        !self.looping && time >= self.duration()
+    }
+
+    /// The pose of a part `time` seconds after the start of the animation
+    pub fn sample(&self, part: &str, time: f32) -> Pose {
+        let keyframes = match self.parts.get(part) {
+            Some(keyframes) if !keyframes.is_empty() => keyframes,
+            _ => return Pose::default(),
+        };
+        let duration = self.duration();
+        let time = if self.looping && duration > 0.0 { time.rem_euclid(duration) } else { time.min(duration) };
+        match keyframes.iter().position(|keyframe| keyframe.time > time) {
+            Some(0) => keyframes[0].pose(),
+            Some(next) => {
+                let (from, to) = (&keyframes[next - 1], &keyframes[next]);
+                from.pose().lerp(&to.pose(), (time - from.time) / (to.time - from.time))
+            }
+            None => keyframes[keyframes.len() - 1].pose(),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! The biomes: the climate of each column of the world. The world generator chooses them from the height of the
+//! ground and a temperature noise, and sends them with the chunks. The clients show the biome and the temperature
+//! around the camera, and tint the view with the haze of the biome.
+
+use serde::{Deserialize, Serialize};
+
+/// The temperature drops by this much for each block above the sea level, in degrees Celsius
+const TEMPERATURE_LAPSE: f64 = 0.1;
+
+/// The id of a biome, an index in `BIOMES`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BiomeId(pub u8);
+
+impl BiomeId {
+    pub const PLAINS: Self = Self(0);
+    pub const OCEAN: Self = Self(1);
+    pub const SHORE: Self = Self(2);
+    pub const TUNDRA: Self = Self(3);
+    pub const STEPPE: Self = Self(4);
+    pub const HIGHLANDS: Self = Self(5);
+    pub const FROZEN_PEAKS: Self = Self(6);
+    pub const CAVERNS: Self = Self(7);
+
+    /// The description of the biome, the unknown ids being plains
+    pub fn biome(self) -> &'static Biome {
+        BIOMES.get(self.0 as usize).unwrap_or(&BIOMES[0])
+    }
+
+    /// The temperature of the air at height `y` in this biome, in degrees Celsius
+    pub fn temperature_at(self, y: f64) -> f64 {
+        self.biome().temperature - y.max(0.0) * TEMPERATURE_LAPSE
+    }
+}
+
+#[derive(Debug)]
+pub struct Biome {
+    pub name: &'static str,
+    /// Temperature of the air at the sea level, in degrees Celsius
+    pub temperature: f64,
+    /// Color of the haze of the air
+    pub haze_color: [f32; 3],
+    /// Strength of the haze, from 0 (clear air) to 1
+    pub haze_strength: f32,
+}
+
+/// The biomes, by id
+pub const BIOMES: [Biome; 8] = [
+    Biome { name: "Plains", temperature: -20.0, haze_color: [0.9, 0.6, 0.4], haze_strength: 0.05 },
+    Biome { name: "Ocean", temperature: -10.0, haze_color: [0.5, 0.6, 0.8], haze_strength: 0.08 },
+    Biome { name: "Shore", temperature: -15.0, haze_color: [0.7, 0.65, 0.6], haze_strength: 0.05 },
+    Biome { name: "Tundra", temperature: -55.0, haze_color: [0.75, 0.8, 0.9], haze_strength: 0.1 },
+    Biome { name: "Steppe", temperature: 5.0, haze_color: [1.0, 0.55, 0.3], haze_strength: 0.12 },
+    Biome { name: "Highlands", temperature: -35.0, haze_color: [0.85, 0.6, 0.5], haze_strength: 0.03 },
+    Biome { name: "Frozen peaks", temperature: -70.0, haze_color: [0.8, 0.85, 1.0], haze_strength: 0.06 },
+    Biome { name: "Caverns", temperature: 35.0, haze_color: [1.0, 0.4, 0.15], haze_strength: 0.15 },
+];
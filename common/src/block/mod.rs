@@ -1,25 +1,239 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::data::TextureRect;
+use crate::registry::Registry;
 
 pub type BlockId = u16;
 
-#[derive(Debug, Clone, Deserialize)]
+/// Blast resistance of the blocks that don't declare one
+pub const DEFAULT_BLAST_RESISTANCE: f64 = 1.0;
+/// Minimum light level of the crops that don't declare one
+pub const DEFAULT_CROP_MIN_LIGHT: u8 = 10;
+
+fn default_crop_min_light() -> u8 {
+    DEFAULT_CROP_MIN_LIGHT
+}
+
+fn default_harvest_count() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "Block")]
 pub enum BlockType {
     Air,
-    NormalCube { face_texture: Vec<String>},
+    NormalCube {
+        face_texture: Vec<String>,
+        /// How hard it is to destroy the block with an explosion
+        #[serde(default)]
+        blast_resistance: Option<f64>,
+        /// The loot table dropped instead of the block when a player breaks it
+        #[serde(default)]
+        loot_table: Option<String>,
+    },
+    /// A conveyor belt moving the items on top of it towards `facing`.
+    /// If `requires_signal` is set, it only runs while it receives a logic signal.
+    Conveyor {
+        face_texture: Vec<String>,
+        facing: Facing,
+        #[serde(default)]
+        requires_signal: bool,
+    },
+    /// A block of the logic signal layer
+    Logic { face_texture: Vec<String>, component: LogicComponent },
+    /// A closed door. Airlock doors are paired with the closest other airlock door, and cycle
+    /// so that at most one of them is open.
+    Door {
+        face_texture: Vec<String>,
+        #[serde(default)]
+        airlock: bool,
+    },
+    /// The open state of door `closed`. Registered automatically for every door.
+    OpenDoor { closed: String },
+    /// A block emitting light of level `light_level` (at most 15).
+    /// If `requires_signal` is set, it is only lit while it receives a logic signal.
+    /// If `light_color` is set, the clients also light the nearby blocks with a dynamic light of that color.
+    Lamp {
+        face_texture: Vec<String>,
+        light_level: u8,
+        #[serde(default)]
+        requires_signal: bool,
+        #[serde(default)]
+        light_color: Option<[f32; 3]>,
+    },
+    /// The unlit state of lamp `lit`. Registered automatically for every lamp.
+    UnlitLamp { face_texture: Vec<String>, lit: String },
+    /// A block that explodes with strength `power` when it is interacted with or receives a logic signal
+    Explosive { face_texture: Vec<String>, power: f64 },
+    /// An elevator to the dimension named `dimension`. The players using it arrive in the same column of that
+    /// dimension, on an elevator leading back.
+    Elevator { face_texture: Vec<String>, dimension: String },
+    /// A supply terminal, where the players exchange their items for others with the trades of the list `trades`
+    Terminal { face_texture: Vec<String>, trades: String },
+    /// A crop, growing through the stages of `stage_textures` in the greenhouses: the air around it must be sealed,
+    /// and its light level must be at least `min_light`. Every stage lasts `growth_time` seconds.
+    /// The block is the first stage. The grown crop drops `harvest_count` items `harvest`, the younger stages drop the
+    /// crop itself to plant it again.
+    Crop {
+        stage_textures: Vec<Vec<String>>,
+        growth_time: f64,
+        #[serde(default = "default_crop_min_light")]
+        min_light: u8,
+        harvest: String,
+        #[serde(default = "default_harvest_count")]
+        harvest_count: u32,
+    },
+    /// The stage `stage` of crop `crop`, counting from 0. Registered automatically for every stage after the first.
+    CropStage { face_texture: Vec<String>, crop: String, stage: usize },
+}
+
+impl BlockType {
+    /// The texture of each face, if the block is a cube
+    pub fn face_textures(&self) -> Option<&[String]> {
+        match self {
+            Self::Air | Self::OpenDoor { .. } => None,
+            Self::NormalCube { face_texture, .. }
+            | Self::Conveyor { face_texture, .. }
+            | Self::Logic { face_texture, .. }
+            | Self::Door { face_texture, .. }
+            | Self::Lamp { face_texture, .. }
+            | Self::UnlitLamp { face_texture, .. }
+            | Self::Explosive { face_texture, .. }
+            | Self::Elevator { face_texture, .. }
+            | Self::Terminal { face_texture, .. }
+            | Self::CropStage { face_texture, .. } => Some(face_texture),
+            Self::Crop { stage_textures, .. } => stage_textures.first().map(Vec::as_slice),
+        }
+    }
+
+    /// The power of the explosion, if the block is explosive
+    pub fn explosive_power(&self) -> Option<f64> {
+        match self {
+            Self::Explosive { power, .. } => Some(*power),
+            _ => None,
+        }
+    }
+
+    /// The minimum explosion strength needed to destroy the block
+    pub fn blast_resistance(&self) -> f64 {
+        match self {
+            Self::Air | Self::Explosive { .. } => 0.0,
+            Self::NormalCube { blast_resistance: Some(blast_resistance), .. } => *blast_resistance,
+            _ => DEFAULT_BLAST_RESISTANCE,
+        }
+    }
+
+    /// The loot table dropped when a player breaks the block, if it has one
+    pub fn loot_table(&self) -> Option<&str> {
+        match self {
+            Self::NormalCube { loot_table, .. } => loot_table.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The name of the trade list, if the block is a supply terminal
+    pub fn trade_list(&self) -> Option<&str> {
+        match self {
+            Self::Terminal { trades, .. } => Some(trades),
+            _ => None,
+        }
+    }
+
+    /// The level of the light emitted by the block
+    pub fn light_level(&self) -> u8 {
+        match self {
+            Self::Lamp { light_level, .. } => u8::min(*light_level, 15),
+            _ => 0,
+        }
+    }
+
+    /// The color of the dynamic light of the block, if it has one
+    pub fn light_color(&self) -> Option<[f32; 3]> {
+        match self {
+            Self::Lamp { light_color, .. } => *light_color,
+            _ => None,
+        }
+    }
+
+    /// Return true if the block stops players and items
+    pub fn is_solid(&self) -> bool {
+        !matches!(self, Self::Air | Self::OpenDoor { .. })
+    }
+}
+
+/// Return the level of the light emitted by each block of the registry
+pub fn get_block_light_levels(block_registry: &Registry<Block>) -> Vec<u8> {
+    block_registry
+        .iter()
+        .map(|(_, _, block)| block.block_type.light_level())
+        .collect()
+}
+
+/// Return the color and the level of the dynamic light of each block of the registry, if it has one
+pub fn get_block_dynamic_lights(block_registry: &Registry<Block>) -> Vec<Option<([f32; 3], u8)>> {
+    block_registry
+        .iter()
+        .map(|(_, _, block)| {
+            let block_type = &block.block_type;
+            block_type.light_color().map(|color| (color, block_type.light_level()))
+        })
+        .collect()
+}
+
+/// Return whether each block of the registry is solid
+pub fn get_solid_blocks(block_registry: &Registry<Block>) -> Vec<bool> {
+    block_registry
+        .iter()
+        .map(|(_, _, block)| block.block_type.is_solid())
+        .collect()
+}
+
+/// The behavior of a logic block
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum LogicComponent {
+    /// Carries the signal to the adjacent wires and blocks
+    Wire,
+    /// On during the day
+    DaylightSensor,
+    /// On during the night
+    NightSensor,
+    /// On when a player or a robot is closer than `range` blocks
+    ProximitySensor { range: f64 },
+    /// On for `on` seconds, then off for `off` seconds
+    Timer { on: f64, off: f64 },
+}
+
+/// A horizontal direction
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Facing {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Facing {
+    /// The (x, z) offset of the neighbor in this direction
+    pub fn offset(self) -> (i64, i64) {
+        match self {
+            Self::North => (0, -1),
+            Self::South => (0, 1),
+            Self::East => (1, 0),
+            Self::West => (-1, 0),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub name: String,
     pub block_type: BlockType,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BlockMesh {
     Empty,
-    FullCube { texture: [TextureRect; 6] },
+    /// `emissive` cubes are drawn at full brightness
+    FullCube { texture: [TextureRect; 6], emissive: bool },
 }
 
 impl BlockMesh  {
@@ -0,0 +1,16 @@
+//! Protected regions claimed by the players
+
+use crate::world::BlockPos;
+use serde::{Deserialize, Serialize};
+
+/// What the clients know about a claim of their dimension
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimInfo {
+    pub name: String,
+    pub owner: String,
+    /// The corners of the claim, both included
+    pub min: BlockPos,
+    pub max: BlockPos,
+    /// Whether the player receiving the claim can build in it
+    pub allowed: bool,
+}
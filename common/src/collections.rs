@@ -1,3 +1,6 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
 /// Create a new zero-initialized vector
 pub unsafe fn zero_initialized_vec<T>(size: usize) -> Vec<T> {
     let mut v: Vec<T> = Vec::with_capacity(size);
@@ -44,3 +47,112 @@ pub fn merge_arrays<T: Ord + Copy>(output: &mut Vec<T>, input: &[Vec<T>]) {
         }
     }
 }
+
+/// A queue with a fixed capacity: pushing into a full buffer drops the oldest element.
+/// Used for the histories of the netcode, such as the inputs waiting for the confirmation of the server.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+    elements: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "A ring buffer needs a capacity of at least 1");
+        Self {
+            elements: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.elements.len() == self.capacity
+    }
+
+    /// Add an element after the newest one, and return the oldest one if it was dropped to make room
+    pub fn push(&mut self, element: T) -> Option<T> {
+        let dropped = if self.is_full() { self.elements.pop_front() } else { None };
+        self.elements.push_back(element);
+        dropped
+    }
+
+    /// Remove the oldest element
+    pub fn pop_oldest(&mut self) -> Option<T> {
+        self.elements.pop_front()
+    }
+
+    pub fn oldest(&self) -> Option<&T> {
+        self.elements.front()
+    }
+
+    pub fn newest(&self) -> Option<&T> {
+        self.elements.back()
+    }
+
+    /// The element at `index`, from the oldest one
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.elements.get(index)
+    }
+
+    /// Remove the oldest elements as long as `predicate` returns true
+    pub fn drop_oldest_while(&mut self, mut predicate: impl FnMut(&T) -> bool) {
+        while self.elements.front().is_some_and(&mut predicate) {
+            self.elements.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    /// Iterate from the oldest element to the newest one
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.elements.iter()
+    }
+}
+
+/// A state written by one thread and read by others. The writer modifies the back buffer, then publishes it:
+/// the readers only see complete states, and they never block the writer for longer than a copy.
+/// Used to hand the state of the fixed-timestep ticks of the server to the threads answering requests.
+#[derive(Debug, Default)]
+pub struct DoubleBuffer<T> {
+    front: RwLock<T>,
+    back: Mutex<T>,
+}
+
+impl<T: Clone> DoubleBuffer<T> {
+    pub fn new(state: T) -> Self {
+        Self {
+            front: RwLock::new(state.clone()),
+            back: Mutex::new(state),
+        }
+    }
+
+    /// Lock the back buffer to modify it. It starts from the last published state.
+    pub fn write(&self) -> MutexGuard<'_, T> {
+        self.back.lock().unwrap()
+    }
+
+    /// Copy the back buffer to the front buffer, where the readers see it
+    pub fn publish(&self) {
+        let back = self.back.lock().unwrap();
+        self.front.write().unwrap().clone_from(&back);
+    }
+
+    /// Lock the last published state to read it
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.front.read().unwrap()
+    }
+}
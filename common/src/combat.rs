@@ -0,0 +1,14 @@
+//! The entities that fight each other, shared with the clients to show where the hits land
+
+use crate::mob::MobId;
+use crate::player::PlayerId;
+use crate::robot::RobotId;
+use serde::{Deserialize, Serialize};
+
+/// Something that hits or is hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Combatant {
+    Player(PlayerId),
+    Robot(RobotId),
+    Mob(MobId),
+}
@@ -0,0 +1,60 @@
+//! The content of a server: the version of the game, the data pack and the mods. The server sends it first to the
+//! joining clients, which only join if they can use all of it. The clients receive the data pack and the blocks and
+//! items of the mods with the game data, but the block and item types and the messages change between versions of
+//! the game: a client must have the same version as the server, and a data pack can require a minimum version.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// The version of the game
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Maximum length of the game versions sent by the clients
+pub const MAX_VERSION_LENGTH: usize = 32;
+
+/// A data pack or a mod, and its version. The data packs are described in the `pack.ron` file of the data directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "DataPack")]
+pub struct ContentInfo {
+    pub name: String,
+    pub version: String,
+    /// The oldest version of the game that can use this content, for example a data pack using new block types
+    #[serde(default)]
+    pub min_game_version: Option<String>,
+}
+
+/// All the content of a server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentManifest {
+    pub game_version: String,
+    pub data_packs: Vec<ContentInfo>,
+    pub mods: Vec<ContentInfo>,
+}
+
+impl ContentManifest {
+    /// Describe the content that a game of version `game_version` is missing to use this content, if there is some
+    pub fn missing_for(&self, game_version: &str) -> Vec<String> {
+        let mut missing = Vec::new();
+        if self.game_version != game_version {
+            missing.push(format!("MarsBots {} (you have {})", self.game_version, game_version));
+        }
+        let data_packs = self.data_packs.iter().map(|info| ("Data pack", info));
+        for (kind, info) in data_packs.chain(self.mods.iter().map(|info| ("Mod", info))) {
+            if let Some(min_version) = &info.min_game_version {
+                if compare_versions(game_version, min_version) == Ordering::Less {
+                    missing.push(format!("{} {} {} requires MarsBots {}", kind, info.name, info.version, min_version));
+                }
+            }
+        }
+        missing
+    }
+}
+
+/// Compare two versions made of numbers separated by dots, such as `0.1.2`. The missing numbers are zeros.
+pub fn compare_versions(version1: &str, version2: &str) -> Ordering {
+    let numbers = |version: &str| version.split('.').map(|n| n.trim().parse::<u32>().unwrap_or(0)).collect::<Vec<_>>();
+    let (mut numbers1, mut numbers2) = (numbers(version1), numbers(version2));
+    let len = numbers1.len().max(numbers2.len());
+    numbers1.resize(len, 0);
+    numbers2.resize(len, 0);
+    numbers1.cmp(&numbers2)
+}
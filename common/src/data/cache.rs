@@ -0,0 +1,134 @@
+//! Cache of the game data received from the servers, so that it is only sent again when it changes.
+//!
+//! The server announces the content hash of its data when a player joins. The client loads the data with this hash
+//! from its cache, or asks the server for it and stores it in the cache. The data is stored in a gzip-compressed
+//! bincode file named after its hash.
+
+use super::Data;
+use anyhow::{Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Maximum number of cached data, the least recently used ones are removed
+const MAX_CACHED_DATA: usize = 8;
+
+/// The FNV-1a hash of the bytes written to it
+pub(super) struct ContentHasher(u64);
+
+impl ContentHasher {
+    pub(super) fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub(super) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Write for ContentHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The hash of some bytes, the same on every machine
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = ContentHasher::new();
+    hasher.write_all(bytes).expect("the hasher never fails");
+    hasher.finish()
+}
+
+impl Data {
+    /// A hash of the content of the data, the same for the same data packs and mods on every machine
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ContentHasher::new();
+        bincode::serialize_into(&mut hasher, self).expect("the data can always be serialized");
+        hasher.finish()
+    }
+}
+
+pub struct DataCache {
+    folder: PathBuf,
+}
+
+impl DataCache {
+    pub fn new(folder: PathBuf) -> Self {
+        Self { folder }
+    }
+
+    fn path(&self, hash: u64) -> PathBuf {
+        self.folder.join(format!("{:016x}.bin.gz", hash))
+    }
+
+    /// Load the data with this hash, if it is in the cache. The damaged files are ignored.
+    pub fn load(&self, hash: u64) -> Option<Data> {
+        let path = self.path(hash);
+        let file = File::open(&path).ok()?;
+        match bincode::deserialize_from::<_, Data>(GzDecoder::new(BufReader::new(file))) {
+            // The files of another version of the game could be decoded differently
+            Ok(data) if data.content_hash() == hash => {
+                // Mark the file as recently used
+                let _ = File::options().append(true).open(&path).and_then(|file| file.set_modified(SystemTime::now()));
+                Some(data)
+            }
+            Ok(_) => {
+                log::warn!("Cached data {} doesn't have the right hash", path.display());
+                None
+            }
+            Err(e) => {
+                log::warn!("Cached data {} is damaged: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Store the data with this hash, and remove the least recently used data if the cache is full
+    pub fn store(&self, hash: u64, data: &Data) {
+        if let Err(e) = self.write(hash, data) {
+            log::error!("{:#}", e);
+        }
+        self.remove_old_data();
+    }
+
+    fn write(&self, hash: u64, data: &Data) -> Result<()> {
+        std::fs::create_dir_all(&self.folder).context("Failed to create the data cache folder")?;
+        let path = self.path(hash);
+        let file = File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::fast());
+        bincode::serialize_into(&mut encoder, data).context("Failed to cache the data")?;
+        encoder.finish().context("Failed to cache the data")?;
+        Ok(())
+    }
+
+    fn remove_old_data(&self) {
+        let mut files = match std::fs::read_dir(&self.folder) {
+            Ok(entries) => entries
+                .flatten()
+                .filter(|entry| is_cached_data(&entry.path()))
+                .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+                .collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        files.sort();
+        let excess = files.len().saturating_sub(MAX_CACHED_DATA);
+        for (_, path) in files.into_iter().take(excess) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Failed to remove cached data {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn is_cached_data(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.ends_with(".bin.gz"))
+}
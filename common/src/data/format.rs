@@ -0,0 +1,46 @@
+//! The file formats of the data packs. The format of a file is chosen by its extension.
+
+use serde::de::DeserializeOwned;
+use std::ffi::OsStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Ron,
+    Json,
+    Toml,
+}
+
+impl DataFormat {
+    /// All the supported formats
+    pub const ALL: [DataFormat; 3] = [Self::Ron, Self::Json, Self::Toml];
+
+    /// Find the format of a file from its extension
+    pub fn from_extension(extension: &OsStr) -> Option<Self> {
+        Self::ALL.iter().copied().find(|format| extension == format.extension())
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ron => "ron",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ron => "RON",
+            Self::Json => "JSON",
+            Self::Toml => "TOML",
+        }
+    }
+
+    /// Parse the contents of a file
+    pub fn deserialize<T: DeserializeOwned>(self, contents: &str) -> Result<T, String> {
+        match self {
+            Self::Ron => ron::de::from_str(contents).map_err(|e| e.to_string()),
+            Self::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+}
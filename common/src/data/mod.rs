@@ -1,103 +1,204 @@
+pub mod cache;
+mod format;
+pub mod pack;
+mod report;
+mod source;
 pub mod vox;
+pub mod watcher;
 
-use anyhow::{Context, Result};
-use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
+pub use self::format::DataFormat;
+pub use self::report::{DataError, DataErrorReport};
+pub use self::source::DataSource;
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use image::{ImageBuffer, Rgba};
 use log::info;
-use texture_packer::{TexturePacker, TexturePackerConfig};
+use serde::{Deserialize, Serialize};
 use crate::{
-    block::{Block, BlockMesh, BlockType},
+    animation::{AnimationSet, BODY_PART},
+    block::{Block, BlockId, BlockMesh, BlockType},
     registry::Registry,
 };
-use crate::data::vox::{load_voxel_model, VoxelModel};
-use crate::item::{Item, ItemMesh, ItemType};
+use crate::data::vox::{parse_voxel_model, register_model, VoxelModel};
+use crate::item::{Item, ItemMesh, ItemStack, ItemType};
+use crate::loot::LootTable;
+use crate::theme::Theme;
+use crate::trade::{Trade, TradeListFile};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Data {
     pub blocks: Registry<Block>,
     pub meshes: Vec<BlockMesh>,
-    pub texture_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub textures: TextureArray,
     pub models: Registry<VoxelModel>,
     pub items: Registry<Item>,
     pub item_meshes: Vec<ItemMesh>,
+    /// The items dropped by each block, indexed by block id
+    pub block_items: Vec<Option<ItemStack>>,
+    /// The loot tables, by name
+    pub loot_tables: BTreeMap<String, LootTable>,
+    /// The trade lists of the supply terminals, by name
+    pub trade_lists: BTreeMap<String, Vec<Trade>>,
+    /// The names of the models the players can choose for their character
+    pub character_models: Vec<String>,
+    /// The animations of the models that have some, by the name of the model in the registry
+    pub animations: BTreeMap<String, AnimationSet>,
+    /// The `(name, source)` of the worldgen decorator scripts
+    pub decorator_scripts: Vec<(String, String)>,
+    /// The themes of the interface of the clients, by name
+    pub themes: BTreeMap<String, Theme>,
+}
+
+impl Data {
+    /// The trades of a block, if it is a supply terminal
+    pub fn terminal_trades(&self, block: BlockId) -> Option<&[Trade]> {
+        let trade_list = self.blocks.get_value_by_id(block as u32)?.block_type.trade_list()?;
+        self.trade_lists.get(trade_list).map(Vec::as_slice)
+    }
+}
+
+/// Blocks and items that are not defined in the data files, for example the ones registered by the server mods
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataExtensions {
+    pub blocks: Vec<(String, BlockType)>,
+    pub items: Vec<(String, ItemType)>,
 }
 
+/// Load the data from the data directory, or the embedded default data if there is no data directory.
+/// All the problems with the data files are collected, and returned together as a `DataErrorReport`.
 pub fn load_data(data_directory: PathBuf) -> Result<Data> {
+    load_data_with_extensions(data_directory, DataExtensions::default())
+}
+
+/// Same as `load_data`, and add the blocks and items of `extensions` after the ones of the data files
+pub fn load_data_with_extensions(data_directory: PathBuf, extensions: DataExtensions) -> Result<Data> {
     info!("Loading data from {:?}", &data_directory.display());
+    load_data_from_source(DataSource::new(data_directory), extensions)
+}
+
+/// Same as `load_data_with_extensions`, with the data files of `source`
+pub fn load_data_from_source(source: DataSource, extensions: DataExtensions) -> Result<Data> {
+    let mut report = DataErrorReport::default();
 
     let mut textures: Vec<PathBuf> = Vec::new();
     let mut texture_registery: Registry<()> = Default::default();
-    let textures_directory = data_directory.join("textures");
-    info!("Loading textures from {:?}", &textures_directory.display());
-    for dir_entry in std::fs::read_dir(textures_directory).context("couldn't read texture directory")? {
-        let dir_entry = dir_entry.context("couldn't read directory entry")?;
-        if dir_entry
-            .file_type()
-            .context("couldn't get file type of directory")?
-            .is_file() {
-            let file_path = dir_entry.path();
-
-            texture_registery.register(
-                file_path
-                    .file_stem()
-                    .context("couldn't get file stem")?
-                    .to_str()
-                    .unwrap()
-                    .to_owned(),
-                (),
-            )?;
-            textures.push(file_path);
+    info!("Loading textures");
+    for file_path in source.list_files("textures", &mut report) {
+        let name = match file_path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_owned(),
+            None => {
+                report.add(file_path.display(), "invalid texture file name");
+                continue;
+            }
+        };
+        if let Err(e) = texture_registery.register(name, ()) {
+            report.add(file_path.display(), e);
+            continue;
         }
+        textures.push(file_path);
     }
-    let (texture_atlas, texture_rects) = load_textures(textures)?;
+    let (texture_array, texture_rects) = load_textures(&source, textures, &mut report);
+    // Missing textures are reported, and replaced by the first layer so that the rest of the data can be checked
+    let get_texture_rect = |name: &String, user: &str, report: &mut DataErrorReport| {
+        match texture_registery.get_id_by_name(name) {
+            Some(id) => texture_rects[id as usize],
+            None => {
+                report.add(user, format!("unknown texture {}", name));
+                TextureRect { layer: 0, width: 1.0, height: 1.0 }
+            }
+        }
+    };
 
     let mut models = Registry::default();
 
-    let model_tree = load_voxel_model(
-        data_directory.join("model/tree.vox").to_str().unwrap()
-    ).unwrap();
-    models.register("tree".to_string(), model_tree)?;
-    let model_knight = load_voxel_model(
-        data_directory.join("model/chr_knight.vox").to_str().unwrap()
-    ).unwrap();
-    models.register("knight".to_string(), model_knight)?;
-
-    let items_directory = data_directory.join("items");
-    let item_datas: Vec<(String, ItemType)> = load_files_from_folder(items_directory);
+    let tree_path = source.path("model/tree.vox");
+    match source.read(&tree_path).ok().and_then(|buffer| parse_voxel_model(&buffer)) {
+        Some(model_tree) => {
+            register_model(&mut models, "tree".to_string(), model_tree)?;
+        }
+        None => report.add(tree_path.display(), "couldn't load voxel model"),
+    }
+    // Every `chr_<name>.vox` file is a character model that the players can choose
+    let mut character_models = Vec::new();
+    let model_files = source.list_files("model", &mut report);
+    for file_path in model_files.iter() {
+        let file_name = file_path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        if let Some(name) = file_name.strip_prefix("chr_").and_then(|name| name.strip_suffix(".vox")) {
+            match source.read(file_path).ok().and_then(|buffer| parse_voxel_model(&buffer)) {
+                Some(model) => {
+                    if let Err(e) = register_model(&mut models, name.to_owned(), model) {
+                        report.add(file_path.display(), e);
+                        continue;
+                    }
+                    character_models.push(name.to_owned());
+                }
+                None => report.add(file_path.display(), "couldn't load character model"),
+            }
+        }
+    }
+    character_models.sort();
+    models.register("fire".to_string(), self::vox::effects::generate_fire_model())?;
+    models.register("meteor".to_string(), self::vox::effects::generate_meteor_model())?;
+    models.register("drone".to_string(), self::vox::effects::generate_drone_model())?;
+    register_model(&mut models, "robot".to_string(), self::vox::effects::generate_robot_model())?;
+    // The animations of a model are in a data file with the same name, for example `chr_knight.ron`
+    let mut animations = BTreeMap::new();
+    for file_path in model_files.iter() {
+        let format = match file_path.extension().and_then(DataFormat::from_extension) {
+            Some(format) => format,
+            None => continue,
+        };
+        let stem = file_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+        let name = stem.strip_prefix("chr_").unwrap_or(stem);
+        let (model_name, model) = match models.get_id_by_name(&name.to_owned()) {
+            Some(id) => (models.get_name_by_id(id).unwrap(), models.get_value_by_id(id).unwrap()),
+            None => {
+                report.add(file_path.display(), format!("no model {} for these animations", name));
+                continue;
+            }
+        };
+        if let Some(animation_set) = parse_file::<AnimationSet>(&source, file_path, format, &mut report) {
+            for (animation_name, animation) in animation_set.iter() {
+                if let Err(e) = animation.validate() {
+                    report.add(file_path.display(), format!("animation {}: {}", animation_name, e));
+                }
+                let unknown_part = animation.parts.keys().find(|part| {
+                    *part != BODY_PART && !model.parts.iter().any(|model_part| model_part.name == **part)
+                });
+                if let Some(part) = unknown_part {
+                    let message = format!("animation {}: model {} has no part {}", animation_name, name, part);
+                    report.add(file_path.display(), message);
+                }
+            }
+            animations.insert(model_name.to_owned(), animation_set);
+        }
+    }
+
+    let mut item_datas: Vec<(String, ItemType)> = load_files_from_folder(&source, "items", &mut report);
+    item_datas.extend(extensions.items);
     let mut items = Registry::default();
     let mut item_meshes = Vec::new();
 
     for(name, ty) in item_datas.into_iter() {
         match &ty {
-            ItemType::NormalItem { texture } => {
-                let texture_rect =
-                    texture_rects[texture_registery.get_id_by_name(texture).unwrap() as usize];
-                let model = self::vox::item::generate_item_model(texture_rect, &texture_atlas);
-                let mesh_center = (
-                    model.size_x as f32 / 2.0,
-                    model.size_y as f32 / 2.0,
-                    model.size_z as f32 / 2.0,
-                    );
-                let scale = 1.0 / usize::max(model.size_x, model.size_y) as f32;
-                let mesh_id = models
-                    .register(format!("item:{}", name), model)
-                    .expect("couldn't register item");
-                items
-                    .register(name.clone(), Item {name, ty })
-                    .expect("couldn't register item");
-                item_meshes.push(ItemMesh::SimpleMesh {
-                    mesh_id,
-                    scale,
-                    mesh_center,
-                });
+            ItemType::NormalItem { texture, .. }
+            | ItemType::Consumable { texture, .. }
+            | ItemType::Flashlight { texture, .. } => {
+                let texture_rect = get_texture_rect(texture, &format!("item {}", name), &mut report);
+                if let Err(e) = items.register(name.clone(), Item { name: name.clone(), ty }) {
+                    report.add(format!("item {}", name), e);
+                    continue;
+                }
+                item_meshes.push(generate_item_mesh(&name, texture_rect, &texture_array, &mut models));
             }
+            ItemType::BlockItem { .. } => report.add(format!("item {}", name), "block items can't be loaded from files"),
         }
     }
 
-    let blocks_directory = data_directory.join("blocks");
-    let block_data: Vec<(String, BlockType)> = load_files_from_folder(blocks_directory);
+    let mut block_data: Vec<(String, BlockType)> = load_files_from_folder(&source, "blocks", &mut report);
+    block_data.extend(extensions.blocks);
 
     info!("Processing collected block and texture data");
     let mut blocks = Registry::default();
@@ -118,160 +219,417 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
             name: name.clone(),
             block_type: block_type.clone(),
         };
-        blocks.register(name, block)?;
-        let mesh = match block_type {
-            BlockType::Air => BlockMesh::Empty,
-            BlockType::NormalCube {
-                face_texture: names,
-            } => BlockMesh::FullCube {
+        let source = format!("block {}", name);
+        if let BlockType::OpenDoor { .. } | BlockType::UnlitLamp { .. } | BlockType::CropStage { .. } = block_type {
+            report.add(&source, "block states can't be loaded from files");
+            continue;
+        }
+        if let Err(e) = blocks.register(name.clone(), block) {
+            report.add(&source, e);
+            continue;
+        }
+        let mesh = match block_type.face_textures() {
+            None => BlockMesh::Empty,
+            Some(names) => BlockMesh::FullCube {
                 texture : [
-                    texture_rects[texture_registery.get_id_by_name(&names[0]).unwrap() as usize],
-                    texture_rects[texture_registery.get_id_by_name(&names[1]).unwrap() as usize],
-                    texture_rects[texture_registery.get_id_by_name(&names[2]).unwrap() as usize],
-                    texture_rects[texture_registery.get_id_by_name(&names[3]).unwrap() as usize],
-                    texture_rects[texture_registery.get_id_by_name(&names[4]).unwrap() as usize],
-                    texture_rects[texture_registery.get_id_by_name(&names[5]).unwrap() as usize]
+                    get_texture_rect(&names[0], &source, &mut report),
+                    get_texture_rect(&names[1], &source, &mut report),
+                    get_texture_rect(&names[2], &source, &mut report),
+                    get_texture_rect(&names[3], &source, &mut report),
+                    get_texture_rect(&names[4], &source, &mut report),
+                    get_texture_rect(&names[5], &source, &mut report),
                 ],
+                emissive: block_type.light_level() > 0,
             },
         };
-        meshes.push(mesh);
+        meshes.push(mesh.clone());
+        if let BlockType::Door { .. } = block_type {
+            let open_name = format!("{}:open", name);
+            let open_door = Block {
+                name: open_name.clone(),
+                block_type: BlockType::OpenDoor { closed: name.clone() },
+            };
+            blocks.register(open_name, open_door)?;
+            meshes.push(BlockMesh::Empty);
+        }
+        if let BlockType::Crop { stage_textures, .. } = &block_type {
+            if stage_textures.len() < 2 {
+                report.add(&source, "a crop needs at least two stages");
+            }
+            for (stage, face_texture) in stage_textures.iter().enumerate().skip(1) {
+                let stage_name = format!("{}:{}", name, stage);
+                let crop_stage = Block {
+                    name: stage_name.clone(),
+                    block_type: BlockType::CropStage { face_texture: face_texture.clone(), crop: name.clone(), stage },
+                };
+                blocks.register(stage_name, crop_stage)?;
+                meshes.push(BlockMesh::FullCube {
+                    texture: std::array::from_fn(|i| get_texture_rect(&face_texture[i], &source, &mut report)),
+                    emissive: false,
+                });
+            }
+        }
+        if let BlockType::Lamp { face_texture, .. } = block_type {
+            let unlit_name = format!("{}:off", name);
+            let unlit_lamp = Block {
+                name: unlit_name.clone(),
+                block_type: BlockType::UnlitLamp { face_texture, lit: name },
+            };
+            blocks.register(unlit_name, unlit_lamp)?;
+            meshes.push(match mesh {
+                BlockMesh::FullCube { texture, .. } => BlockMesh::FullCube { texture, emissive: false },
+                BlockMesh::Empty => BlockMesh::Empty,
+            });
+        }
+    }
+
+    info!("Registering block items");
+    let mut block_items = vec![None];
+    for block_id in 1..blocks.get_number_of_ids() {
+        let block = blocks.get_value_by_id(block_id).unwrap();
+        let mut count = 1;
+        let item_id = match (&block.block_type, block.block_type.face_textures()) {
+            // The other states of a block drop the same item as its default state
+            (BlockType::OpenDoor { closed: name }, _) | (BlockType::UnlitLamp { lit: name, .. }, _) => {
+                items.get_id_by_name(name)
+            }
+            // The grown crops drop their harvest instead
+            (BlockType::CropStage { crop, stage, .. }, _) => {
+                let crop_block = blocks.get_value_by_id(blocks.get_id_by_name(crop).unwrap()).unwrap();
+                match &crop_block.block_type {
+                    BlockType::Crop { stage_textures, harvest, harvest_count, .. } if *stage == stage_textures.len() - 1 => {
+                        count = *harvest_count;
+                        let harvest_id = items.get_id_by_name(harvest);
+                        if harvest_id.is_none() {
+                            report.add(format!("block {}", crop), format!("unknown harvest item {}", harvest));
+                        }
+                        harvest_id
+                    }
+                    _ => items.get_id_by_name(crop),
+                }
+            }
+            (_, None) => None,
+            (_, Some(names)) => {
+                // The missing textures were already reported with the block
+                let texture_rect = get_texture_rect(&names[0], &block.name, &mut DataErrorReport::default());
+                let item = Item {
+                    name: block.name.clone(),
+                    ty: ItemType::BlockItem { block: block.name.clone() },
+                };
+                // An item with the same name takes precedence over the block item
+                match items.register(block.name.clone(), item) {
+                    Ok(item_id) => {
+                        item_meshes.push(generate_item_mesh(&block.name, texture_rect, &texture_array, &mut models));
+                        Some(item_id)
+                    }
+                    Err(_) => items.get_id_by_name(&block.name),
+                }
+            }
+        };
+        block_items.push(item_id.map(|item| ItemStack::new(item, count)));
+    }
+
+    info!("Loading loot tables");
+    let mut loot_tables = BTreeMap::new();
+    // The loot tables are optional
+    if source.has_folder("loot_tables") {
+        for (name, loot_table) in load_files_from_folder::<LootTable>(&source, "loot_tables", &mut report) {
+            for error in loot_table.validate(&items) {
+                report.add(format!("loot table {}", name), error);
+            }
+            loot_tables.insert(name, loot_table);
+        }
+    }
+    for (_, name, block) in blocks.iter() {
+        match block.block_type.loot_table() {
+            Some(loot_table) if !loot_tables.contains_key(loot_table) => {
+                report.add(format!("block {}", name), format!("unknown loot table {}", loot_table));
+            }
+            _ => (),
+        }
+    }
+
+    info!("Loading trade lists");
+    let mut trade_lists = BTreeMap::new();
+    // The trade lists are optional
+    if source.has_folder("trades") {
+        for (name, trade_list) in load_files_from_folder::<TradeListFile>(&source, "trades", &mut report) {
+            match trade_list.resolve(&items) {
+                Ok(trades) => {
+                    trade_lists.insert(name, trades);
+                }
+                Err(errors) => {
+                    for error in errors {
+                        report.add(format!("trade list {}", name), error);
+                    }
+                }
+            }
+        }
+    }
+    for (_, name, block) in blocks.iter() {
+        match block.block_type.trade_list() {
+            Some(trade_list) if !trade_lists.contains_key(trade_list) => {
+                report.add(format!("block {}", name), format!("unknown trade list {}", trade_list));
+            }
+            _ => (),
+        }
+    }
+
+    info!("Loading decorator scripts");
+    let mut decorator_scripts = Vec::new();
+    // The decorators are optional
+    if source.has_folder("decorators") {
+        for file_path in source.list_files("decorators", &mut report) {
+            let name = match file_path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) if file_path.extension().map(|ext| ext == "rhai").unwrap_or(false) => name.to_owned(),
+                _ => {
+                    log::warn!("Skipping file {}, decorator scripts must be .rhai files", file_path.display());
+                    continue;
+                }
+            };
+            match source.read(&file_path).map(|buffer| String::from_utf8(buffer.into_owned())) {
+                Ok(Ok(script)) => decorator_scripts.push((name, script)),
+                Ok(Err(e)) => report.add(file_path.display(), format!("invalid UTF-8: {}", e)),
+                Err(e) => report.add(file_path.display(), format!("couldn't read file: {}", e)),
+            }
+        }
+    }
+
+    info!("Loading themes");
+    let mut themes = BTreeMap::new();
+    // The themes are optional, the clients use the built-in theme without them
+    if source.has_folder("themes") {
+        for (name, theme) in load_files_from_folder::<Theme>(&source, "themes", &mut report) {
+            for error in theme.validate() {
+                report.add(format!("theme {}", name), error);
+            }
+            themes.insert(name, theme);
+        }
+    }
+
+    if !report.is_empty() {
+        return Err(report.into());
     }
 
     info!("Processing block meshes");
     Ok(Data{
         blocks,
         meshes,
-        texture_atlas,
+        textures: texture_array,
         models,
         items,
-        item_meshes
+        item_meshes,
+        block_items,
+        loot_tables,
+        trade_lists,
+        character_models,
+        animations,
+        decorator_scripts,
+        themes,
     })
 }
 
+/// Generate the model of an item from its texture, and register it
+fn generate_item_mesh(
+    name: &str,
+    texture_rect: TextureRect,
+    textures: &TextureArray,
+    models: &mut Registry<VoxelModel>,
+) -> ItemMesh {
+    let model = self::vox::item::generate_item_model(texture_rect, textures);
+    let mesh_center = (
+        model.size_x as f32 / 2.0,
+        model.size_y as f32 / 2.0,
+        model.size_z as f32 / 2.0,
+        );
+    let scale = 1.0 / usize::max(model.size_x, model.size_y) as f32;
+    let mesh_id = models
+        .register(format!("item:{}", name), model)
+        .expect("couldn't register item");
+    ItemMesh::SimpleMesh {
+        mesh_id,
+        scale,
+        mesh_center,
+    }
+}
+
 
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// The place of a texture in the texture array
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct TextureRect {
-    pub x: f32,
-    pub y: f32,
+    /// The layer of the texture
+    pub layer: u32,
+    /// The size of the texture, relative to the size of the layers
     pub width: f32,
     pub height: f32,
 }
 
-pub const MAX_TEXTURE_SIZE: u32 = 2048;
+/// The textures of the blocks and of the items, one per layer of a texture array. Each layer has its own mipmaps, so
+/// the textures never bleed into each other.
+#[derive(Debug, Clone)]
+pub struct TextureArray {
+    /// Width and height of the layers, the largest width and height of the textures
+    pub layer_size: (u32, u32),
+    /// The pixels of each layer. The textures smaller than the layers start at the top left corner of their layer, and
+    /// are repeated to fill it.
+    pub layers: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+}
 
-const TEXTURE_PACKER_CONFIG: TexturePackerConfig = TexturePackerConfig {
-    max_width: MAX_TEXTURE_SIZE,
-    max_height: MAX_TEXTURE_SIZE,
-    allow_rotation: false,
-    force_max_dimensions: false,
-    border_padding: 0,
-    texture_padding: 0,
-    texture_extrusion: 0,
-    trim: false,
-    texture_outlines: false,
-};
+impl TextureArray {
+    /// Size of the layers in bytes
+    pub fn size_in_bytes(&self) -> usize {
+        self.layers.iter().map(|layer| layer.as_raw().len()).sum()
+    }
+}
+
+/// The texture arrays are stored as the size of their layers, followed by the raw pixels of every layer
+impl Serialize for TextureArray {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let layers = self.layers.iter().map(|layer| layer.as_raw()).collect::<Vec<_>>();
+        (self.layer_size, layers).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TextureArray {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ((width, height), layers): ((u32, u32), Vec<Vec<u8>>) = Deserialize::deserialize(deserializer)?;
+        let layers = layers
+            .into_iter()
+            .map(|pixels| ImageBuffer::from_raw(width, height, pixels))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| serde::de::Error::custom("a texture layer doesn't have the size of the layers"))?;
+        Ok(Self {
+            layer_size: (width, height),
+            layers,
+        })
+    }
+}
+
+pub const MAX_TEXTURE_SIZE: u32 = 2048;
+/// The minimum number of layers of a texture array that every GPU supports
+pub const MAX_TEXTURE_LAYERS: usize = 256;
 
 fn load_textures(
+    source: &DataSource,
     textures: Vec<PathBuf>,
-) -> Result<(ImageBuffer<Rgba<u8>, Vec<u8>>, Vec<TextureRect>)> {
-    use image::GenericImage;
-    use texture_packer::{exporter::ImageExporter, importer::ImageImporter};
-
-    let mut packer = TexturePacker::new_skyline(TEXTURE_PACKER_CONFIG);
-    for (i, path) in textures.iter().enumerate() {
-        packer.pack_own(
-            format!("{}", i),
-            ImageImporter::import_from_file(path).expect("Failed to read texture to pack"),
-        ).expect("Failed to pack textures");
+    report: &mut DataErrorReport,
+) -> (TextureArray, Vec<TextureRect>) {
+    let mut images = Vec::with_capacity(textures.len());
+    for path in textures.iter() {
+        let texture = match source.read(path).map_err(|e| e.to_string()).and_then(|buffer| {
+            image::load_from_memory(&buffer).map_err(|e| e.to_string())
+        }) {
+            Ok(texture) => texture.to_rgba8(),
+            Err(e) => {
+                report.add(path.display(), format!("couldn't read texture: {}", e));
+                images.push(None);
+                continue;
+            }
+        };
+        let (width, height) = texture.dimensions();
+        if width == 0 || height == 0 || width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            report.add(path.display(), format!("the texture size must be between 1 and {}", MAX_TEXTURE_SIZE));
+            images.push(None);
+        } else if images.iter().flatten().count() == MAX_TEXTURE_LAYERS {
+            report.add(path.display(), format!("too many textures, the maximum is {}", MAX_TEXTURE_LAYERS));
+            images.push(None);
+        } else {
+            images.push(Some(texture));
+        }
     }
 
-    let mut texture_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
-        ImageBuffer::new(MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE);
-    texture_buffer.copy_from(
-        &ImageExporter::export(&packer, None).expect("Failed to export texture from packer"),
-        0,
-        0,
-    ).expect("Failed to copy texture atlas to buffer");
-    texture_buffer
-        .save("atlas.png")
-        .expect("Failed to save texture atlas");
-    Ok((
-        texture_buffer,
-        (0..textures.len())
-            .map(|i| {
-                let frame = packer
-                    .get_frame(&format!("{}", i))
-                    .expect("Texture packer frame key doesn't exist")
-                    .frame;
+    let layer_size = images.iter().flatten().fold((1, 1), |(width, height), image| {
+        (width.max(image.width()), height.max(image.height()))
+    });
+    let mut layers = Vec::new();
+    let texture_rects = images
+        .iter()
+        .map(|image| match image {
+            Some(image) => {
+                let (width, height) = image.dimensions();
+                layers.push(ImageBuffer::from_fn(layer_size.0, layer_size.1, |x, y| {
+                    *image.get_pixel(x % width, y % height)
+                }));
                 TextureRect {
-                    x: frame.x as f32 / MAX_TEXTURE_SIZE as f32,
-                    y: frame.y as f32 / MAX_TEXTURE_SIZE as f32,
-                    width: frame.w as f32 / MAX_TEXTURE_SIZE as f32,
-                    height: frame.h as f32 / MAX_TEXTURE_SIZE as f32,
+                    layer: layers.len() as u32 - 1,
+                    width: width as f32 / layer_size.0 as f32,
+                    height: height as f32 / layer_size.1 as f32,
                 }
-            })
-            .collect(),
-    ))
+            }
+            // The texture couldn't be loaded, which was reported
+            None => TextureRect::default(),
+        })
+        .collect();
+    // The texture array needs at least one layer
+    if layers.is_empty() {
+        layers.push(ImageBuffer::new(layer_size.0, layer_size.1));
+    }
+    (TextureArray { layer_size, layers }, texture_rects)
 }
 
-/// Load all <name>.ron files from a given folder and parse them into type `T`.
-fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) -> Vec<(String, T)> {
+/// Load all <name>.<ext> files from a given folder of the data and parse them into type `T`,
+/// using the `DataFormat` of the extension. The files that can't be read or parsed are added to the report.
+fn load_files_from_folder<T: serde::de::DeserializeOwned>(
+    source: &DataSource,
+    folder: &str,
+    report: &mut DataErrorReport,
+) -> Vec<(String, T)> {
     let mut result = Vec::new();
     info!(
-        "Loading objects of type {} from directory {}",
+        "Loading objects of type {} from folder {}",
         std::any::type_name::<T>(),
-        directory.display(),
+        folder,
     );
-    for dir_entry in fs::read_dir(directory).expect("Failed to read from directory") {
-        let dir_entry = dir_entry.expect("Failed to read directory entry");
-        if dir_entry
-            .file_type()
-            .expect("Failed to get file type")
-            .is_file()
-        {
-            let file_path = dir_entry.path();
-
-            match file_path.extension() {
-                None => log::warn!(
-                    "No file extension for file {}, skipping...",
-                    file_path.display()
-                ),
-                Some(ext) => {
-                    if ext == "ron" {
-                        log::info!("Attempting to read file {}", file_path.display());
-                        let mut file =
-                            fs::File::open(file_path.clone()).expect("Failed to open file");
-                        let mut buffer = String::new();
-                        file.read_to_string(&mut buffer)
-                            .expect("Failed to read from file");
-                        let file_stem = file_path
-                            .file_stem()
-                            .expect("Failed to get file stem")
-                            .to_str()
-                            .unwrap()
-                            .to_owned();
-
-                        let parsed_file = {
-                            if ext == "ron" {
-                                match ron::de::from_str(&buffer) {
-                                    Ok(x) => x,
-                                    Err(e) => {
-                                        log::error!("Failed to parse RON: {}, skipping...", e);
-                                        continue;
-                                    }
-                                }
-                            } else {
-                                unreachable!("No parser for file format");
-                            }
-                        };
-                        result.push((file_stem, parsed_file));
-                    } else {
-                        log::warn!("Unsupported file extension {:?}, skipping...", ext);
-                        // TODO: display instead of debug
-                    }
+    for file_path in source.list_files(folder, report) {
+        let format = match file_path.extension() {
+            None => {
+                log::warn!("No file extension for file {}, skipping...", file_path.display());
+                continue;
+            }
+            Some(ext) => match DataFormat::from_extension(ext) {
+                Some(format) => format,
+                None => {
+                    log::warn!("Unsupported file extension {}, skipping...", ext.to_string_lossy());
+                    continue;
                 }
+            },
+        };
+        let file_stem = match file_path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => stem.to_owned(),
+            None => {
+                report.add(file_path.display(), "invalid file name");
+                continue;
             }
+        };
+        if let Some(parsed_file) = parse_file(source, &file_path, format, report) {
+            result.push((file_stem, parsed_file));
         }
     }
     result
 }
+
+/// Read a data file and parse it with the given format. The problems are added to the report.
+fn parse_file<T: serde::de::DeserializeOwned>(
+    source: &DataSource,
+    file_path: &Path,
+    format: DataFormat,
+    report: &mut DataErrorReport,
+) -> Option<T> {
+    log::info!("Attempting to read file {}", file_path.display());
+    let buffer = match source.read(file_path).and_then(|buffer| {
+        String::from_utf8(buffer.into_owned()).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            report.add(file_path.display(), format!("couldn't read file: {}", e));
+            return None;
+        }
+    };
+    match format.deserialize(&buffer) {
+        Ok(parsed_file) => Some(parsed_file),
+        Err(e) => {
+            report.add(file_path.display(), format!("malformed {}: {}", format.name(), e));
+            None
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! The data packs streamed by the servers to the clients that don't have their game data in cache: the data files
+//! and the blocks and items of the mods, loaded by the clients like the servers load their data directory.
+//!
+//! The pack is sent compressed, in chunks of `DATA_PACK_CHUNK_SIZE` bytes, after its hash and its size. The client
+//! checks the hash of the received bytes before loading the pack.
+
+use super::cache::hash_bytes;
+use super::{load_data_from_source, Data, DataExtensions, DataFormat, DataSource};
+use crate::content::ContentInfo;
+use crate::network::limits::decoding_options;
+use anyhow::{anyhow, Context, Result};
+use bincode::Options;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Size of the chunks of the data packs sent to the clients, in bytes
+pub const DATA_PACK_CHUNK_SIZE: usize = 64 * 1024;
+/// Maximum size of the compressed data packs accepted by the clients, in bytes
+pub const MAX_DATA_PACK_SIZE: usize = 512 * 1024 * 1024;
+/// Maximum size of the decompressed data packs, in bytes, so that a pack that decompresses to far more than it
+/// weighs can't make the client allocate more than this
+const MAX_DECODED_DATA_PACK_SIZE: u64 = 2 * MAX_DATA_PACK_SIZE as u64;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataPack {
+    /// The data files, with their paths relative to the data directory, sorted by path
+    pub files: Vec<(String, Vec<u8>)>,
+    /// The blocks and items of the mods
+    pub extensions: DataExtensions,
+}
+
+impl DataPack {
+    /// Read the files of the data directory, or the embedded default data if there is no data directory
+    pub fn read(data_directory: PathBuf, extensions: DataExtensions) -> Result<Self> {
+        let files = DataSource::new(data_directory).read_all().context("Failed to read the data pack")?;
+        Ok(Self { files, extensions })
+    }
+
+    /// Load the data of the pack, like `load_data_with_extensions` loads a data directory
+    pub fn load(self) -> Result<Data> {
+        let files = self.files.into_iter().map(|(path, contents)| (PathBuf::from(path), contents)).collect();
+        load_data_from_source(DataSource::Files(files), self.extensions)
+    }
+
+    /// Compress the pack to send it
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        bincode::serialize_into(&mut encoder, self).context("Failed to encode the data pack")?;
+        encoder.finish().context("Failed to compress the data pack")
+    }
+
+    /// Decompress a pack encoded by `encode`
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        decoding_options(MAX_DECODED_DATA_PACK_SIZE)
+            .deserialize_from(GzDecoder::new(bytes))
+            .context("Failed to decode the data pack")
+    }
+}
+
+/// Read the description of the data pack in the `pack.<ext>` file of the data directory, in any data format. The
+/// data directories without one are described by their name.
+pub fn read_pack_info(data_directory: PathBuf) -> Result<ContentInfo> {
+    let name = data_directory.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let source = DataSource::new(data_directory);
+    for format in DataFormat::ALL {
+        let path = source.path(&format!("pack.{}", format.extension()));
+        let contents = match source.read(&path) {
+            Ok(contents) => String::from_utf8(contents.into_owned())
+                .with_context(|| format!("Invalid UTF-8 in {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        return format
+            .deserialize(&contents)
+            .map_err(|e| anyhow!("Malformed {} {}: {}", format.name(), path.display(), e));
+    }
+    Ok(ContentInfo {
+        name,
+        version: "unknown".to_owned(),
+        min_game_version: None,
+    })
+}
+
+/// A data pack being received in chunks
+pub struct DataPackDownload {
+    hash: u64,
+    size: usize,
+    bytes: Vec<u8>,
+}
+
+impl DataPackDownload {
+    /// Start receiving the encoded pack with this hash and this size
+    pub fn new(hash: u64, size: usize) -> Result<Self> {
+        if size > MAX_DATA_PACK_SIZE {
+            return Err(anyhow!("The data pack is too large: {} bytes", size));
+        }
+        Ok(Self {
+            hash,
+            size,
+            bytes: Vec::with_capacity(size),
+        })
+    }
+
+    /// Add the next chunk of the pack
+    pub fn add_chunk(&mut self, chunk: &[u8]) -> Result<()> {
+        if self.bytes.len() + chunk.len() > self.size {
+            return Err(anyhow!("The data pack is larger than announced"));
+        }
+        self.bytes.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /// The fraction of the pack received, between 0 and 1
+    pub fn progress(&self) -> f32 {
+        if self.size == 0 {
+            1.0
+        } else {
+            self.bytes.len() as f32 / self.size as f32
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bytes.len() == self.size
+    }
+
+    /// Check the hash of the received pack, and decode it
+    pub fn finish(self) -> Result<DataPack> {
+        if !self.is_complete() {
+            return Err(anyhow!("The data pack is incomplete"));
+        }
+        if hash_bytes(&self.bytes) != self.hash {
+            return Err(anyhow!("The data pack doesn't have the announced hash"));
+        }
+        DataPack::decode(&self.bytes)
+    }
+}
@@ -0,0 +1,42 @@
+//! The errors found while loading the data, collected so that they can all be fixed at once
+
+/// A problem with one of the data files
+#[derive(Debug, Clone)]
+pub struct DataError {
+    /// The file or the object that has the problem
+    pub source: String,
+    pub message: String,
+}
+
+/// All the errors found while loading the data
+#[derive(Debug, Clone, Default)]
+pub struct DataErrorReport {
+    pub errors: Vec<DataError>,
+}
+
+impl DataErrorReport {
+    pub fn add(&mut self, source: impl ToString, message: impl ToString) {
+        let error = DataError {
+            source: source.to_string(),
+            message: message.to_string(),
+        };
+        log::error!("{}: {}", error.source, error.message);
+        self.errors.push(error);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl std::fmt::Display for DataErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{} error(s) in the game data:", self.errors.len())?;
+        for error in self.errors.iter() {
+            writeln!(f, "- {}: {}", error.source, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DataErrorReport {}
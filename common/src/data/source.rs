@@ -0,0 +1,205 @@
+//! Where the data files are read from: the data directory, or the default data embedded in the binary
+
+use super::DataErrorReport;
+use log::warn;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// The default data, used when there is no data directory. The paths are relative to the data directory.
+const EMBEDDED_FILES: &[(&str, &[u8])] = &[
+    ("blocks/dirt.ron", include_bytes!("../../default_data/blocks/dirt.ron")),
+    ("blocks/dirt_grass.ron", include_bytes!("../../default_data/blocks/dirt_grass.ron")),
+    ("blocks/elevator_caverns.ron", include_bytes!("../../default_data/blocks/elevator_caverns.ron")),
+    ("blocks/elevator_surface.ron", include_bytes!("../../default_data/blocks/elevator_surface.ron")),
+    ("blocks/grass.ron", include_bytes!("../../default_data/blocks/grass.ron")),
+    ("blocks/lamp.ron", include_bytes!("../../default_data/blocks/lamp.ron")),
+    ("blocks/lava.ron", include_bytes!("../../default_data/blocks/lava.ron")),
+    ("blocks/leaves.ron", include_bytes!("../../default_data/blocks/leaves.ron")),
+    ("blocks/med_station.ron", include_bytes!("../../default_data/blocks/med_station.ron")),
+    ("blocks/potato_plant.ron", include_bytes!("../../default_data/blocks/potato_plant.ron")),
+    ("blocks/respawn_pod.ron", include_bytes!("../../default_data/blocks/respawn_pod.ron")),
+    ("blocks/sand.ron", include_bytes!("../../default_data/blocks/sand.ron")),
+    ("blocks/sign.ron", include_bytes!("../../default_data/blocks/sign.ron")),
+    ("blocks/stone.ron", include_bytes!("../../default_data/blocks/stone.ron")),
+    ("blocks/supply_terminal.ron", include_bytes!("../../default_data/blocks/supply_terminal.ron")),
+    ("blocks/water.ron", include_bytes!("../../default_data/blocks/water.ron")),
+    ("blocks/wood.ron", include_bytes!("../../default_data/blocks/wood.ron")),
+    ("icon.png", include_bytes!("../../default_data/icon.png")),
+    ("items/energy_pack.ron", include_bytes!("../../default_data/items/energy_pack.ron")),
+    ("items/flashlight.ron", include_bytes!("../../default_data/items/flashlight.ron")),
+    ("items/ingot_iron.ron", include_bytes!("../../default_data/items/ingot_iron.ron")),
+    ("items/oxygen_canister.ron", include_bytes!("../../default_data/items/oxygen_canister.ron")),
+    ("items/potato.ron", include_bytes!("../../default_data/items/potato.ron")),
+    ("items/ration.ron", include_bytes!("../../default_data/items/ration.ron")),
+    ("loot_tables/leaves.ron", include_bytes!("../../default_data/loot_tables/leaves.ron")),
+    ("model/chr_astronaut.vox", include_bytes!("../../default_data/model/chr_astronaut.vox")),
+    ("model/chr_knight.ron", include_bytes!("../../default_data/model/chr_knight.ron")),
+    ("model/chr_knight.vox", include_bytes!("../../default_data/model/chr_knight.vox")),
+    ("model/robot.ron", include_bytes!("../../default_data/model/robot.ron")),
+    ("model/tree.vox", include_bytes!("../../default_data/model/tree.vox")),
+    ("pack.ron", include_bytes!("../../default_data/pack.ron")),
+    ("textures/dirt.png", include_bytes!("../../default_data/textures/dirt.png")),
+    ("textures/elevator.png", include_bytes!("../../default_data/textures/elevator.png")),
+    ("textures/energy_pack.png", include_bytes!("../../default_data/textures/energy_pack.png")),
+    ("textures/flashlight.png", include_bytes!("../../default_data/textures/flashlight.png")),
+    ("textures/grass_side.png", include_bytes!("../../default_data/textures/grass_side.png")),
+    ("textures/grass_top.png", include_bytes!("../../default_data/textures/grass_top.png")),
+    ("textures/ingot_iron.png", include_bytes!("../../default_data/textures/ingot_iron.png")),
+    ("textures/lamp.png", include_bytes!("../../default_data/textures/lamp.png")),
+    ("textures/lava.png", include_bytes!("../../default_data/textures/lava.png")),
+    ("textures/leaves.png", include_bytes!("../../default_data/textures/leaves.png")),
+    ("textures/med_station.png", include_bytes!("../../default_data/textures/med_station.png")),
+    ("textures/oxygen_canister.png", include_bytes!("../../default_data/textures/oxygen_canister.png")),
+    ("textures/potato.png", include_bytes!("../../default_data/textures/potato.png")),
+    ("textures/potato_plant_0.png", include_bytes!("../../default_data/textures/potato_plant_0.png")),
+    ("textures/potato_plant_1.png", include_bytes!("../../default_data/textures/potato_plant_1.png")),
+    ("textures/potato_plant_2.png", include_bytes!("../../default_data/textures/potato_plant_2.png")),
+    ("textures/ration.png", include_bytes!("../../default_data/textures/ration.png")),
+    ("textures/respawn_pod.png", include_bytes!("../../default_data/textures/respawn_pod.png")),
+    ("textures/sand.png", include_bytes!("../../default_data/textures/sand.png")),
+    ("textures/sign.png", include_bytes!("../../default_data/textures/sign.png")),
+    ("textures/stone.png", include_bytes!("../../default_data/textures/stone.png")),
+    ("textures/supply_terminal.png", include_bytes!("../../default_data/textures/supply_terminal.png")),
+    ("textures/water.png", include_bytes!("../../default_data/textures/water.png")),
+    ("textures/wood_side.png", include_bytes!("../../default_data/textures/wood_side.png")),
+    ("textures/wood_top.png", include_bytes!("../../default_data/textures/wood_top.png")),
+    ("themes/default.toml", include_bytes!("../../default_data/themes/default.toml")),
+    ("trades/supply.ron", include_bytes!("../../default_data/trades/supply.ron")),
+];
+
+pub enum DataSource {
+    /// A data directory on disk, which replaces all the embedded data
+    Directory(PathBuf),
+    Embedded,
+    /// The files of a data pack received from a server, with their paths relative to the data directory
+    Files(Vec<(PathBuf, Vec<u8>)>),
+}
+
+impl DataSource {
+    /// Use the data directory if it exists, and the embedded default data otherwise
+    pub fn new(data_directory: PathBuf) -> Self {
+        if data_directory.is_dir() {
+            Self::Directory(data_directory)
+        } else {
+            warn!(
+                "Data directory {} doesn't exist, using the default data",
+                data_directory.display(),
+            );
+            Self::Embedded
+        }
+    }
+
+    /// Return true if the data has a folder, for example `blocks`
+    pub fn has_folder(&self, folder: &str) -> bool {
+        match self {
+            Self::Directory(data_directory) => data_directory.join(folder).is_dir(),
+            Self::Embedded => EMBEDDED_FILES
+                .iter()
+                .any(|(path, _)| Path::new(path).parent() == Some(Path::new(folder))),
+            Self::Files(files) => files.iter().any(|(path, _)| path.parent() == Some(Path::new(folder))),
+        }
+    }
+
+    /// List the files of a folder of the data, for example `blocks`. The errors are added to the report.
+    pub fn list_files(&self, folder: &str, report: &mut DataErrorReport) -> Vec<PathBuf> {
+        match self {
+            Self::Directory(data_directory) => {
+                let directory = data_directory.join(folder);
+                let dir_entries = match std::fs::read_dir(&directory) {
+                    Ok(dir_entries) => dir_entries,
+                    Err(e) => {
+                        report.add(directory.display(), format!("couldn't read directory: {}", e));
+                        return Vec::new();
+                    }
+                };
+                let mut files = Vec::new();
+                for dir_entry in dir_entries {
+                    match dir_entry {
+                        Ok(dir_entry) if dir_entry.file_type().map(|ty| ty.is_file()).unwrap_or(false) => {
+                            files.push(dir_entry.path())
+                        }
+                        Ok(_) => (),
+                        Err(e) => report.add(directory.display(), format!("couldn't read directory entry: {}", e)),
+                    }
+                }
+                // Same order as the embedded files, so that the ids don't depend on the file system
+                files.sort();
+                files
+            }
+            Self::Embedded => EMBEDDED_FILES
+                .iter()
+                .map(|(path, _)| Path::new(path))
+                .filter(|path| path.parent() == Some(Path::new(folder)))
+                .map(Path::to_path_buf)
+                .collect(),
+            Self::Files(files) => files
+                .iter()
+                .map(|(path, _)| path)
+                .filter(|path| path.parent() == Some(Path::new(folder)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The path of a file of the data, for example `model/tree.vox`
+    pub fn path(&self, file: &str) -> PathBuf {
+        match self {
+            Self::Directory(data_directory) => data_directory.join(file),
+            Self::Embedded | Self::Files(_) => PathBuf::from(file),
+        }
+    }
+
+    /// Read a file returned by `list_files` or `path`
+    pub fn read(&self, path: &Path) -> std::io::Result<Cow<'static, [u8]>> {
+        match self {
+            Self::Directory(_) => std::fs::read(path).map(Cow::Owned),
+            Self::Embedded => EMBEDDED_FILES
+                .iter()
+                .find(|(embedded_path, _)| Path::new(embedded_path) == path)
+                .map(|(_, contents)| Cow::Borrowed(*contents))
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "not in the default data")),
+            Self::Files(files) => files
+                .iter()
+                .find(|(file_path, _)| file_path == path)
+                .map(|(_, contents)| Cow::Owned(contents.clone()))
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "not in the data pack")),
+        }
+    }
+
+    /// Read all the files of the data, with their paths relative to the data directory, sorted by path
+    pub fn read_all(&self) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+        let mut files = match self {
+            Self::Directory(data_directory) => {
+                let mut files = Vec::new();
+                read_directory(data_directory, data_directory, &mut files)?;
+                files
+            }
+            Self::Embedded => EMBEDDED_FILES
+                .iter()
+                .map(|(path, contents)| (path.to_string(), contents.to_vec()))
+                .collect(),
+            Self::Files(files) => files
+                .iter()
+                .map(|(path, contents)| (path.to_string_lossy().into_owned(), contents.clone()))
+                .collect(),
+        };
+        files.sort_by(|(path1, _), (path2, _)| path1.cmp(path2));
+        Ok(files)
+    }
+}
+
+/// Read the files of a directory and of its subdirectories, with their paths relative to `data_directory`
+fn read_directory(data_directory: &Path, directory: &Path, files: &mut Vec<(String, Vec<u8>)>) -> std::io::Result<()> {
+    for dir_entry in std::fs::read_dir(directory)? {
+        let path = dir_entry?.path();
+        if path.is_dir() {
+            read_directory(data_directory, &path, files)?;
+        } else {
+            let relative_path = path.strip_prefix(data_directory).unwrap_or(&path);
+            // The paths use the same separator on every platform
+            let name = relative_path.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/");
+            files.push((name, std::fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
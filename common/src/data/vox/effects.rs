@@ -0,0 +1,143 @@
+use crate::data::vox::{ModelPart, VoxelModel};
+use crate::worldgen::perlin::hash;
+
+/// Generate a cube of `size` voxels whose colors are picked randomly among `colors`.
+/// The colors are encoded like the voxels of a `VoxelModel`.
+fn generate_speckled_cube(size: usize, colors: &[u32], seed: i32) -> VoxelModel {
+    let mut voxels = Vec::with_capacity(size * size * size);
+    for i in 0..size {
+        for j in 0..size {
+            for k in 0..size {
+                let r = hash(hash(hash(i as i32 + seed).wrapping_add(j as i32)).wrapping_add(k as i32));
+                voxels.push(colors[r.rem_euclid(colors.len() as i32) as usize]);
+            }
+        }
+    }
+    VoxelModel {
+        size_x: size,
+        size_y: size,
+        size_z: size,
+        voxels,
+        full: vec![true; size * size * size],
+        parts: Vec::new(),
+    }
+}
+
+/// The model of the flames of the meteor trails
+pub fn generate_fire_model() -> VoxelModel {
+    generate_speckled_cube(3, &[0x0000ff, 0x0066ff, 0x00aaff, 0x33ddff], 1)
+}
+
+/// The model of a falling meteor
+pub fn generate_meteor_model() -> VoxelModel {
+    generate_speckled_cube(6, &[0x222222, 0x333333, 0x1a2a44, 0x0044aa], 2)
+}
+
+/// The model of the drones: a flat hull with a rotor at each corner and an eye in front
+pub fn generate_drone_model() -> VoxelModel {
+    const HULL: u32 = 0x3a3a3a;
+    const ROTOR: u32 = 0x777777;
+    const EYE: u32 = 0x2020ff;
+    let (size_x, size_y, size_z) = (8, 4, 8);
+    let mut voxels = Vec::with_capacity(size_x * size_y * size_z);
+    let mut full = Vec::with_capacity(size_x * size_y * size_z);
+    for i in 0..size_x {
+        for j in 0..size_y {
+            for k in 0..size_z {
+                let corner = (i < 2 || i >= size_x - 2) && (k < 2 || k >= size_z - 2);
+                let hull = (2..size_x - 2).contains(&i) && (2..size_z - 2).contains(&k) && (1..3).contains(&j);
+                // The drones face -z, like the players
+                let eye = (3..5).contains(&i) && j == 1 && k == 1;
+                let color = if eye {
+                    Some(EYE)
+                } else if hull {
+                    Some(HULL)
+                } else if corner && j == 3 {
+                    Some(ROTOR)
+                } else {
+                    None
+                };
+                voxels.push(color.unwrap_or(0));
+                full.push(color.is_some());
+            }
+        }
+    }
+    VoxelModel {
+        size_x,
+        size_y,
+        size_z,
+        voxels,
+        full,
+        parts: Vec::new(),
+    }
+}
+
+/// The model of the robots: a box on two wheels with a sensor in front and an arm on the right, which is lifted to mine.
+/// The wheels and the arm are parts of the model.
+pub fn generate_robot_model() -> VoxelModel {
+    const BODY: u32 = 0x2080e0;
+    const SENSOR: u32 = 0xe08020;
+    const WHEEL: u32 = 0x303030;
+    const HUB: u32 = 0x909090;
+    const ARM: u32 = 0x606060;
+    const CLAW: u32 = 0xb0b0b0;
+    let size = 12;
+    let mut model = VoxelModel::empty(size, size, size);
+    for i in 2..size - 2 {
+        for j in 2..size - 2 {
+            for k in 2..size - 2 {
+                model.set(i, j, k, BODY);
+            }
+        }
+    }
+    // The robots face -z, like the players
+    for i in 3..6 {
+        for j in 7..9 {
+            model.set(i, j, 1, SENSOR);
+        }
+    }
+    let wheel = || {
+        let mut wheel = VoxelModel::empty(2, 5, 5);
+        for i in 0..2 {
+            for j in 0..5_usize {
+                for k in 0..5_usize {
+                    let (dj, dk) = (j.abs_diff(2), k.abs_diff(2));
+                    if dj * dj + dk * dk <= 5 {
+                        wheel.set(i, j, k, if dj == 0 || dk == 0 { HUB } else { WHEEL });
+                    }
+                }
+            }
+        }
+        wheel
+    };
+    // The arm hangs from the shoulder, at the center of its grid
+    let mut arm = VoxelModel::empty(2, 12, 2);
+    for i in 0..2 {
+        for j in 0..6 {
+            for k in 0..2 {
+                arm.set(i, j, k, if j == 0 { CLAW } else { ARM });
+            }
+        }
+    }
+    model.parts = vec![
+        ModelPart {
+            name: "wheel_left".to_owned(),
+            parent: None,
+            origin: [0, 0, 4],
+            model: wheel(),
+        },
+        ModelPart {
+            name: "wheel_right".to_owned(),
+            parent: None,
+            origin: [size as i32 - 2, 0, 4],
+            model: wheel(),
+        },
+        ModelPart {
+            name: "arm".to_owned(),
+            parent: None,
+            origin: [8, 3, 0],
+            model: arm,
+        },
+    ];
+    model
+}
@@ -1,23 +1,21 @@
-use image::{ImageBuffer, Rgba};
-use crate::data::{TextureRect, MAX_TEXTURE_SIZE};
+use crate::data::{TextureArray, TextureRect};
 use crate::data::vox::VoxelModel;
 
 pub fn generate_item_model(
     texture: TextureRect,
-    atlas: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    textures: &TextureArray,
 ) -> VoxelModel {
 
-    let x = (texture.x * MAX_TEXTURE_SIZE as f32).round() as u32;
-    let y = (texture.y * MAX_TEXTURE_SIZE as f32).round() as u32;
-    let width = (texture.width * MAX_TEXTURE_SIZE as f32).round() as u32;
-    let height = (texture.height * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let layer = &textures.layers[texture.layer as usize];
+    let width = (texture.width * textures.layer_size.0 as f32).round() as u32;
+    let height = (texture.height * textures.layer_size.1 as f32).round() as u32;
 
     let mut full = Vec::with_capacity((width * height) as usize);
     let mut voxels = Vec::with_capacity((width * height) as usize);
 
-    for u in x.. (x + width) {
-        for v in (y.. (y + height)).rev() {
-            let rgba = atlas.get_pixel(u, v);
+    for u in 0..width {
+        for v in (0..height).rev() {
+            let rgba = layer.get_pixel(u, v);
             if rgba[3] == 255 {
                 full.push(true);
                 voxels.push(((rgba[2] as  u32) << 16) + ((rgba[1] as u32) << 8) + rgba[0] as u32);
@@ -34,5 +32,6 @@ pub fn generate_item_model(
         size_z: 1,
         voxels,
         full,
+        parts: Vec::new(),
     }
 }
\ No newline at end of file
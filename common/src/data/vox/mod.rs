@@ -1,9 +1,14 @@
-use std::fs::File;
-use std::io::Read;
-use std::str::from_utf8;
-
+pub mod effects;
 pub mod item;
 
+use crate::animation::BODY_PART;
+use crate::registry::{Registry, RegistryError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Deepest scene read from a MagicaVoxel file
+const MAX_SCENE_DEPTH: usize = 64;
+
 const DEFAULT_PALETTE: [u32; 256] = [
     0x00000000, 0xffffffff, 0xffccffff, 0xff99ffff, 0xff66ffff, 0xff33ffff, 0xff00ffff, 0xffffccff,
     0xffccccff, 0xff99ccff, 0xff66ccff, 0xff33ccff, 0xff00ccff, 0xffff99ff, 0xffcc99ff, 0xff9999ff,
@@ -39,149 +44,394 @@ const DEFAULT_PALETTE: [u32; 256] = [
     0xffbbbbbb, 0xffaaaaaa, 0xff888888, 0xff777777, 0xff555555, 0xff444444, 0xff222222, 0xff111111,
 ];
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoxelModel {
     pub size_x: usize,
     pub size_y: usize,
     pub size_z: usize,
     pub voxels: Vec<u32>,
     pub full: Vec<bool>,
+    /// The parts of the model that move on their own, such as wheels or a head. Their voxels are not in the model.
+    #[serde(default)]
+    pub parts: Vec<ModelPart>,
+}
+
+/// A part of a model, which the animations can move and rotate around its pivot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPart {
+    pub name: String,
+    /// Index of the part this part is attached to, which comes before it. The parts without parent are attached to the
+    /// model.
+    pub parent: Option<usize>,
+    /// Position of the corner of the part in the model, in voxels
+    pub origin: [i32; 3],
+    /// The voxels of the part, which rotates around the center of this grid
+    pub model: VoxelModel,
+}
+
+impl ModelPart {
+    /// Position of the point the part rotates around in the model, in voxels
+    pub fn pivot(&self) -> [f32; 3] {
+        [
+            self.origin[0] as f32 + self.model.size_x as f32 / 2.0,
+            self.origin[1] as f32 + self.model.size_y as f32 / 2.0,
+            self.origin[2] as f32 + self.model.size_z as f32 / 2.0,
+        ]
+    }
+}
+
+impl VoxelModel {
+    /// A model without voxels
+    pub fn empty(size_x: usize, size_y: usize, size_z: usize) -> Self {
+        Self {
+            size_x,
+            size_y,
+            size_z,
+            voxels: vec![0; size_x * size_y * size_z],
+            full: vec![false; size_x * size_y * size_z],
+            parts: Vec::new(),
+        }
+    }
+
+    /// Fill a voxel with a color, or return None if it is outside of the model
+    pub fn set(&mut self, x: usize, y: usize, z: usize, color: u32) -> Option<()> {
+        if x >= self.size_x || y >= self.size_y || z >= self.size_z {
+            return None;
+        }
+        let s = x * self.size_y * self.size_z + y * self.size_z + z;
+        self.voxels[s] = color;
+        self.full[s] = true;
+        Some(())
+    }
+
+    /// Number of voxels of the model and of its parts, full or not
+    pub fn total_voxels(&self) -> usize {
+        self.voxels.len() + self.parts.iter().map(|part| part.model.total_voxels()).sum::<usize>()
+    }
+
+    /// Whether the voxels of the model and of its parts match their sizes, and the names of the parts are unique
+    pub fn is_well_formed(&self) -> bool {
+        let size = self.size_x.checked_mul(self.size_y).and_then(|size| size.checked_mul(self.size_z));
+        let unique_names = self.parts.iter().enumerate().all(|(i, part)| {
+            self.parts[..i].iter().all(|other| other.name != part.name)
+        });
+        size == Some(self.voxels.len())
+            && size == Some(self.full.len())
+            && unique_names
+            && self.parts.iter().all(|part| part.model.is_well_formed())
+    }
+}
+
+/// The name under which a part of a model is registered
+pub fn part_model_name(model: &str, part: &str) -> String {
+    format!("{}/{}", model, part)
+}
+
+/// Register a model, then each of its parts under `part_model_name`
+pub fn register_model(
+    models: &mut Registry<VoxelModel>,
+    name: String,
+    model: VoxelModel,
+) -> Result<u32, RegistryError> {
+    let parts = model.parts.iter().map(|part| (part_model_name(&name, &part.name), part.model.clone()));
+    let parts = parts.collect::<Vec<_>>();
+    let id = models.register(name, model)?;
+    for (part_name, part_model) in parts {
+        models.register(part_name, part_model)?;
+    }
+    Ok(id)
+}
+
+/// Register a model and its parts like `register_model`, replacing the values of the names that are already
+/// registered. Return the ids of the model then of its parts.
+pub fn replace_model(models: &mut Registry<VoxelModel>, name: String, model: VoxelModel) -> Vec<u32> {
+    let parts = model.parts.iter().map(|part| (part_model_name(&name, &part.name), part.model.clone()));
+    let parts = parts.collect::<Vec<_>>();
+    std::iter::once((name, model))
+        .chain(parts)
+        .map(|(name, value)| match models.get_id_by_name(&name) {
+            Some(id) => {
+                models.set_value(id, value);
+                id
+            }
+            None => models.register(name, value).expect("the name is not registered"),
+        })
+        .collect()
 }
 
 pub fn load_voxel_model(path: &str) -> Option<VoxelModel> {
-    let file = File::open(path);
-    match file {
-        Ok(mut f) => {
-            let mut buffer = Vec::new();
-            match f.read_to_end(&mut buffer) {
-                Ok(_) => {
-                    let mut id = str_from_byte(&buffer[0..4]);
-
-                    if id == "VOX " {
-                        let mut big_endian = true;
-                        if four_bytes_to_u32(&buffer[4..8], true) != 150 {
-                            big_endian = false;
-                            assert_eq!(four_bytes_to_u32(&buffer[4..8], false), 150);
-                        }
-                        id = str_from_byte(&buffer[8..12]);
-                        let (size_x, size_y, size_z);
-                        if id == "MAIN" {
-                            let n = four_bytes_to_u32(&buffer[12..16], big_endian);
-                            let m = four_bytes_to_u32(&buffer[16..20], big_endian);
-                            assert_eq!(n, 0);
-                            let mut data = &buffer[20..22 + m as usize];
-                            id = str_from_byte(&data[0..4]);
-                            assert_eq!(id, "SIZE");
-
-                            {
-                                let n_size = four_bytes_to_u32(&buffer[4..8], big_endian);
-                                let m_size = four_bytes_to_u32(&buffer[8..12], big_endian);
-                                assert_eq!(n_size, 12);
-                                assert_eq!(m_size, 0);
-                                size_x = four_bytes_to_u32(&data[12..16], big_endian);
-                                size_z = four_bytes_to_u32(&data[16..20], big_endian);
-                                size_y = four_bytes_to_u32(&data[20..24], big_endian);
-                            }
-
-                            data = &data[24..];
-                            id = str_from_byte(&data[0..4]);
-                            let n_voxels;
-                            let mut voxel: Vec<(u8,u8,u8,u8)> = Vec::new();
-                            assert_eq!(id, "XYZI");
-                            {
-                                let n_size = four_bytes_to_u32(&buffer[4..8], big_endian);
-                                let m_size = four_bytes_to_u32(&buffer[8..12], big_endian);
-                                assert_eq!(m_size, 0);
-
-                                n_voxels = four_bytes_to_u32(&data[12..16], big_endian);
-                                assert_eq!(n_size, n_voxels * 4 + 4);
-                                for i in 0..(n_voxels as usize) {
-                                    let (a, b, c, d) = (
-                                        data[16 + 4 * i],
-                                        data[16 + 4 * i + 1],
-                                        data[16 + 4 * i + 2],
-                                        data[16 + 4 * i + 3],
-                                        );
-                                    voxel.push((a, b, c, d));
-                                }
-                            }
-                            let mut palette = [0; 256];
-
-                            if m > 40 + 4 * n_voxels {
-                                data = &data[16 + 4 * n_voxels as usize..];
-                                id = str_from_byte(&data[0..4]);
-                                if id == "RGBA" {
-                                    let n_size = four_bytes_to_u32(&buffer[4..8], big_endian);
-                                    let m_size = four_bytes_to_u32(&buffer[8..12], big_endian);
-                                    assert_eq!(m_size, 0);
-                                    assert_eq!(n_size, 4 * 256);
-                                    for i in 0..256 {
-                                        palette[i] = four_bytes_to_u32(
-                                            &data[12 + i * 4..12 + (i + 1) * 4],
-                                            big_endian,
-                                        );
-                                    }
-                                } else {
-                                    palette = DEFAULT_PALETTE;
-                                }
-                            } else {
-                                palette = DEFAULT_PALETTE;
-                            }
-
-                            let mut res = VoxelModel {
-                                size_x: size_x as usize,
-                                size_y: size_y as usize,
-                                size_z: size_z as usize,
-                                voxels: Vec::new(),
-                                full: Vec::new(),
-                            };
-
-                            for _i in 0..(size_x * size_y *size_z) {
-                                res.voxels.push(0);
-                                res.full.push(false);
-                            }
-
-                            for (x,y,z,i) in voxel.iter() {
-                                let s = ((*x as u32) * size_z * size_y
-                                + (*y as u32) * size_z
-                                + (*z as u32)) as usize;
-                                res.voxels[s] = palette[*i as usize];
-                                res.full[s] = true;
-                            }
-                            return Some(res)
-                        }else {
-
-                        }
-                    } else {
+    let buffer = std::fs::read(path).ok()?;
+    parse_voxel_model(&buffer)
+}
+
+/// A node of the scene of a MagicaVoxel file
+enum SceneNode {
+    /// Moves its child, and names it
+    Transform {
+        name: Option<String>,
+        child: u32,
+        translation: [i32; 3],
+    },
+    Group {
+        children: Vec<u32>,
+    },
+    /// Places models of the file
+    Shape {
+        models: Vec<u32>,
+    },
+}
 
+/// A model of the file placed in the scene
+struct PlacedModel {
+    model: usize,
+    /// Index of the part it belongs to, or None for the main model
+    part: Option<usize>,
+    /// Position of the center of the model, in the axes of the file
+    translation: [i32; 3],
+}
+
+/// Walk the scene of a MagicaVoxel file, to find where its models are and which part they belong to
+struct SceneWalk<'a> {
+    nodes: &'a HashMap<u32, SceneNode>,
+    /// The name, the parent and the position of the object of every part
+    parts: Vec<(String, Option<usize>, [i32; 3])>,
+    placed: Vec<PlacedModel>,
+}
+
+impl SceneWalk<'_> {
+    fn walk(&mut self, node: u32, part: Option<usize>, translation: [i32; 3], depth: usize) -> Option<()> {
+        // The nodes could form a cycle
+        if depth > MAX_SCENE_DEPTH {
+            return None;
+        }
+        match self.nodes.get(&node)? {
+            SceneNode::Transform { name, child, translation: t } => {
+                let translation = std::array::from_fn(|i| translation[i] + t[i]);
+                let part = match name {
+                    Some(name) if name == BODY_PART => None,
+                    Some(name) => {
+                        // The objects with the same name make a single part
+                        let existing = self.parts.iter().position(|(n, _, _)| n == name);
+                        Some(existing.unwrap_or_else(|| {
+                            self.parts.push((name.clone(), part, translation));
+                            self.parts.len() - 1
+                        }))
                     }
+                    None => part,
+                };
+                self.walk(*child, part, translation, depth + 1)?;
+            }
+            SceneNode::Group { children } => {
+                for &child in children {
+                    self.walk(child, part, translation, depth + 1)?;
+                }
+            }
+            SceneNode::Shape { models } => {
+                for &model in models {
+                    self.placed.push(PlacedModel { model: model as usize, part, translation });
                 }
-                _ => (),
             }
         }
-        _ => (),
-    };
-    return None
+        Some(())
+    }
 }
 
-fn four_bytes_to_u32(bytes: &[u8], big_endian: bool) -> u32 {
-    if big_endian {
-        return ((bytes[0] as u32) << 24)
-            + ((bytes[1] as u32) << 16)
-            + (bytes[2] as u32) << 8
-            + (bytes[3] as u32);
+/// Parse a model in the MagicaVoxel format.
+/// The z axis of MagicaVoxel points up, so it becomes the y axis of the model.
+///
+/// The named objects of the file are the parts of the model, and an object inside a named object is attached to it.
+/// The other objects, and the ones named `body`, make the rest of the model. The rotations of the objects are ignored.
+/// The files without objects only have their first model read.
+pub fn parse_voxel_model(buffer: &[u8]) -> Option<VoxelModel> {
+    if buffer.get(0..4)? != b"VOX " || read_u32(buffer, 4)? != 150 || buffer.get(8..12)? != b"MAIN" {
+        return None;
+    }
+    // Every chunk is made of an id, the size of its content, the size of its children, then the content.
+    // The other chunks are the children of the MAIN chunk.
+    let mut offset = 20 + read_u32(buffer, 12)? as usize;
+    let mut size = None;
+    // The size, in the axes of the model, and the voxels of every model of the file
+    let mut models = Vec::new();
+    let mut nodes = HashMap::new();
+    let mut palette = DEFAULT_PALETTE;
+    while offset + 12 <= buffer.len() {
+        let id = &buffer[offset..offset + 4];
+        let content_size = read_u32(buffer, offset + 4)? as usize;
+        let children_size = read_u32(buffer, offset + 8)? as usize;
+        let content = buffer.get(offset + 12..offset + 12 + content_size)?;
+        match id {
+            b"SIZE" => {
+                size = Some([read_u32(content, 0)?, read_u32(content, 8)?, read_u32(content, 4)?]);
+            }
+            b"XYZI" => {
+                let count = read_u32(content, 0)? as usize;
+                let data = content.get(4..4 + 4 * count)?;
+                let voxels = data.chunks(4).map(|v| (v[0], v[2], v[1], v[3])).collect::<Vec<_>>();
+                models.push((size.take()?, voxels));
+            }
+            b"RGBA" => {
+                // The color i of the file is the color i+1 of the palette, the color 0 is empty
+                for i in 0..255 {
+                    palette[i + 1] = read_u32(content, 4 * i)?;
+                }
+            }
+            b"nTRN" | b"nGRP" | b"nSHP" => {
+                let (node_id, node) = parse_scene_node(id, content)?;
+                nodes.insert(node_id, node);
+            }
+            _ => (),
+        }
+        offset += 12 + content_size + children_size;
+    }
+
+    let mut scene = SceneWalk {
+        nodes: &nodes,
+        parts: Vec::new(),
+        placed: Vec::new(),
+    };
+    if nodes.is_empty() {
+        let [size_x, size_y, size_z] = models.first()?.0;
+        let translation = [(size_x / 2) as i32, (size_y / 2) as i32, (size_z / 2) as i32];
+        scene.placed.push(PlacedModel { model: 0, part: None, translation });
     } else {
-        return ((bytes[0] as u32) << 24)
-        + ((bytes[1] as u32) << 16)
-        + ((bytes[2] as u32) << 8)
-        + (bytes[3] as u32);
+        scene.walk(0, None, [0, 0, 0], 0)?;
+    }
+    if scene.placed.is_empty() {
+        return None;
+    }
+
+    // The corners of the models placed in the scene, in the axes of the model
+    let corners = scene
+        .placed
+        .iter()
+        .map(|placed| {
+            let size = models.get(placed.model)?.0;
+            Some(std::array::from_fn::<i32, 3, _>(|i| placed.translation[i] - (size[i] / 2) as i32))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    // The bounds of the models of a part, or of all the models, which start from `start`
+    let bounds = |part: Option<usize>, start: [i32; 3]| {
+        let (mut min, mut max) = (start, start);
+        for (placed, corner) in scene.placed.iter().zip(corners.iter()) {
+            if part.is_none() || placed.part == part {
+                for i in 0..3 {
+                    min[i] = min[i].min(corner[i]);
+                    max[i] = max[i].max(corner[i] + models[placed.model].0[i] as i32);
+                }
+            }
+        }
+        (min, max)
+    };
+    // The voxels of some models of the scene, in a grid starting at `min`
+    let fill = |model: &mut VoxelModel, part: Option<usize>, min: [i32; 3]| {
+        for (placed, corner) in scene.placed.iter().zip(corners.iter()) {
+            if placed.part == part {
+                for &(x, y, z, i) in models[placed.model].1.iter() {
+                    let pos = [x as i32, y as i32, z as i32];
+                    let [x, y, z] = std::array::from_fn(|i| (corner[i] + pos[i] - min[i]) as usize);
+                    model.set(x, y, z, palette[i as usize])?;
+                }
+            }
+        }
+        Some(())
+    };
+
+    // The main model spans the whole scene, so that its parts stay in place
+    let (min, max) = bounds(None, corners[0]);
+    let mut res = VoxelModel::empty((max[0] - min[0]) as usize, (max[1] - min[1]) as usize, (max[2] - min[2]) as usize);
+    fill(&mut res, None, min)?;
+    for (index, (name, parent, translation)) in scene.parts.iter().enumerate() {
+        // A part without models of its own is empty, it only moves the parts attached to it
+        let start = match scene.placed.iter().position(|placed| placed.part == Some(index)) {
+            Some(first) => corners[first],
+            None => *translation,
+        };
+        let (part_min, part_max) = bounds(Some(index), start);
+        let mut model = VoxelModel::empty(
+            (part_max[0] - part_min[0]) as usize,
+            (part_max[1] - part_min[1]) as usize,
+            (part_max[2] - part_min[2]) as usize,
+        );
+        fill(&mut model, Some(index), part_min)?;
+        res.parts.push(ModelPart {
+            name: name.clone(),
+            parent: *parent,
+            origin: std::array::from_fn(|i| part_min[i] - min[i]),
+            model,
+        });
     }
+    Some(res)
 }
 
-fn str_from_byte(bytes: &[u8]) -> &str {
-    let s = from_utf8(bytes);
-    match s {
-        Ok(string) => string,
-        _ => "",
+/// Parse a transform, group or shape node, and return its id
+fn parse_scene_node(id: &[u8], content: &[u8]) -> Option<(u32, SceneNode)> {
+    let node_id = read_u32(content, 0)?;
+    let mut offset = 4;
+    let attributes = read_dict(content, &mut offset)?;
+    let node = match id {
+        b"nTRN" => {
+            let child = read_u32(content, offset)?;
+            // The reserved id, the layer and the number of frames come before the frames
+            let frame_count = read_u32(content, offset + 12)?;
+            offset += 16;
+            let mut translation = [0; 3];
+            if frame_count > 0 {
+                let frame = read_dict(content, &mut offset)?;
+                if let Some(t) = frame.get("_t") {
+                    let t = t.split_whitespace().map(|c| c.parse::<i32>().ok()).collect::<Option<Vec<_>>>()?;
+                    // The z axis of MagicaVoxel points up
+                    translation = [*t.first()?, *t.get(2)?, *t.get(1)?];
+                }
+            }
+            SceneNode::Transform {
+                name: attributes.get("_name").cloned(),
+                child,
+                translation,
+            }
+        }
+        b"nGRP" => {
+            let count = read_u32(content, offset)? as usize;
+            let children = (0..count).map(|i| read_u32(content, offset + 4 + 4 * i)).collect::<Option<_>>()?;
+            SceneNode::Group { children }
+        }
+        _ => {
+            let count = read_u32(content, offset)? as usize;
+            offset += 4;
+            let mut models = Vec::new();
+            for _ in 0..count {
+                models.push(read_u32(content, offset)?);
+                offset += 4;
+                read_dict(content, &mut offset)?;
+            }
+            SceneNode::Shape { models }
+        }
+    };
+    Some((node_id, node))
+}
+
+/// Read a dictionary of strings, made of its number of entries then of the key and the value of every entry
+fn read_dict(bytes: &[u8], offset: &mut usize) -> Option<HashMap<String, String>> {
+    let count = read_u32(bytes, *offset)?;
+    *offset += 4;
+    let mut dict = HashMap::new();
+    for _ in 0..count {
+        let key = read_string(bytes, offset)?;
+        let value = read_string(bytes, offset)?;
+        dict.insert(key, value);
     }
-}
\ No newline at end of file
+    Some(dict)
+}
+
+/// Read a string, made of its length then of its bytes
+fn read_string(bytes: &[u8], offset: &mut usize) -> Option<String> {
+    let length = read_u32(bytes, *offset)? as usize;
+    let string = bytes.get(*offset + 4..(*offset + 4).checked_add(length)?)?;
+    *offset += 4 + length;
+    Some(String::from_utf8_lossy(string).into_owned())
+}
+
+/// Read a little-endian number
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
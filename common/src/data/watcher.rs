@@ -0,0 +1,63 @@
+//! Detection of the changes to the data files, to reload them while the game is running
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between two scans of the data directory
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches the files of the data directory by comparing their modification times
+pub struct DataWatcher {
+    directory: PathBuf,
+    /// The modification time of each file during the last scan
+    files: BTreeMap<PathBuf, SystemTime>,
+    last_scan: Instant,
+}
+
+impl DataWatcher {
+    pub fn new(directory: PathBuf) -> Self {
+        let mut files = BTreeMap::new();
+        scan_directory(&directory, &mut files);
+        Self {
+            directory,
+            files,
+            last_scan: Instant::now(),
+        }
+    }
+
+    /// Return true if a file was added, removed or modified since the last call.
+    /// The directory is scanned at most once per `SCAN_INTERVAL`, so this can be called every tick.
+    pub fn has_changed(&mut self) -> bool {
+        if self.last_scan.elapsed() < SCAN_INTERVAL {
+            return false;
+        }
+        self.last_scan = Instant::now();
+        let mut files = BTreeMap::new();
+        scan_directory(&self.directory, &mut files);
+        if files != self.files {
+            self.files = files;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn scan_directory(directory: &Path, files: &mut BTreeMap<PathBuf, SystemTime>) {
+    // The files that can't be read will be reported when the data is loaded
+    let dir_entries = match std::fs::read_dir(directory) {
+        Ok(dir_entries) => dir_entries,
+        Err(_) => return,
+    };
+    for dir_entry in dir_entries.flatten() {
+        let path = dir_entry.path();
+        match dir_entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => scan_directory(&path, files),
+            Ok(metadata) => {
+                files.insert(path, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+            }
+            Err(_) => (),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod profiler;
+
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use lazy_static::lazy_static;
 use std::{collections::BTreeMap, sync::Arc, sync::RwLock};
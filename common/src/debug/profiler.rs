@@ -0,0 +1,217 @@
+//! Span-based profiler. Use `profile_scope!("name")` to measure the time spent until the end of the current scope.
+//! The spans of all threads are aggregated per frame by the current `Profiler`, which can also record them
+//! and write them in the Chrome tracing format (open the file in `chrome://tracing` or https://ui.perfetto.dev).
+
+use anyhow::{Context, Result};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use lazy_static::lazy_static;
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fmt::Write as _,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref PROFILER: Arc<RwLock<Option<Sender<Span>>>> = Arc::new(RwLock::new(None));
+}
+
+/// Maximum number of spans kept while recording a trace
+const MAX_TRACE_SPANS: usize = 1_000_000;
+/// Weight of the last frame in the average timings
+const AVERAGE_WEIGHT: f64 = 0.05;
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+thread_local! {
+    static THREAD_ID: Cell<u64> = Cell::new(0);
+}
+
+/// A small id for the current thread, used in the traces
+fn current_thread_id() -> u64 {
+    THREAD_ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+/// Measure the time spent until the end of the current scope
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::debug::profiler::ProfileScope::new($name);
+    };
+}
+
+/// A measured span of time
+#[derive(Debug, Clone)]
+struct Span {
+    name: &'static str,
+    thread_id: u64,
+    thread_name: Option<String>,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Guard measuring the time until it is dropped. Use the `profile_scope!` macro instead of creating it directly.
+pub struct ProfileScope {
+    name: &'static str,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl ProfileScope {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            _span: tracing::trace_span!("profile_scope", name).entered(),
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if let Some(sender) = PROFILER.read().unwrap().as_ref() {
+            // The profiler might have been replaced, in which case the span is lost
+            let _ = sender.send(Span {
+                name: self.name,
+                thread_id: current_thread_id(),
+                thread_name: std::thread::current().name().map(str::to_owned),
+                start: self.start,
+                duration: self.start.elapsed(),
+            });
+        }
+    }
+}
+
+/// Timing of all the spans with the same name
+#[derive(Debug, Clone, Copy, Default)]
+struct SpanStats {
+    /// Average time per frame, in milliseconds
+    average_millis: f64,
+    /// Average number of spans per frame
+    average_calls: f64,
+}
+
+/// Aggregates the spans of all threads. There can only be one active `Profiler` at any time.
+pub struct Profiler {
+    receiver: Receiver<Span>,
+    stats: BTreeMap<&'static str, SpanStats>,
+    /// The spans recorded for a trace, if one is being recorded
+    trace: Option<Vec<Span>>,
+    start_time: Instant,
+}
+
+impl Profiler {
+    /// Create a new `Profiler` and make it the current one.
+    pub fn new_current() -> Self {
+        let (sender, receiver) = unbounded();
+        *PROFILER.write().unwrap() = Some(sender);
+        Self {
+            receiver,
+            stats: BTreeMap::new(),
+            trace: None,
+            start_time: Instant::now(),
+        }
+    }
+
+    /// Aggregate the spans of the frame that just ended
+    pub fn end_frame(&mut self) {
+        let mut frame: BTreeMap<&'static str, (Duration, u32)> = BTreeMap::new();
+        while let Ok(span) = self.receiver.try_recv() {
+            let (duration, calls) = frame.entry(span.name).or_default();
+            *duration += span.duration;
+            *calls += 1;
+            if let Some(trace) = self.trace.as_mut() {
+                if trace.len() < MAX_TRACE_SPANS {
+                    trace.push(span);
+                }
+            }
+        }
+        for name in frame.keys() {
+            self.stats.entry(name).or_default();
+        }
+        // Spans that didn't happen during this frame count as 0
+        for (name, stats) in self.stats.iter_mut() {
+            let (duration, calls) = frame.get(name).copied().unwrap_or_default();
+            stats.average_millis += (duration.as_secs_f64() * 1000.0 - stats.average_millis) * AVERAGE_WEIGHT;
+            stats.average_calls += (calls as f64 - stats.average_calls) * AVERAGE_WEIGHT;
+        }
+    }
+
+    /// Text summary of the average timings, for the debug overlay
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (name, stats) in self.stats.iter() {
+            let _ = writeln!(
+                summary,
+                "{:24} {:7.2} ms/frame {:7.1} calls/frame",
+                name, stats.average_millis, stats.average_calls,
+            );
+        }
+        summary
+    }
+
+    pub fn is_recording_trace(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Start recording the spans for a trace
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    /// Stop recording the trace, and write it to `path` in the Chrome tracing JSON format
+    pub fn write_chrome_trace(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let spans = self.trace.take().unwrap_or_default();
+        let mut events = Vec::new();
+        let mut thread_names = BTreeMap::new();
+        for span in spans.iter() {
+            let start = span.start.saturating_duration_since(self.start_time);
+            events.push(format!(
+                "{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+                escape_json(span.name),
+                start.as_micros(),
+                span.duration.as_micros(),
+                span.thread_id,
+            ));
+            if let Some(thread_name) = span.thread_name.as_ref() {
+                thread_names.insert(span.thread_id, thread_name);
+            }
+        }
+        for (thread_id, thread_name) in thread_names {
+            events.push(format!(
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                thread_id,
+                escape_json(thread_name),
+            ));
+        }
+        let json = format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"));
+        std::fs::write(path.as_ref(), json)
+            .with_context(|| format!("Failed to write trace to {}", path.as_ref().display()))?;
+        log::info!("Wrote {} spans to {}", spans.len(), path.as_ref().display());
+        Ok(())
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
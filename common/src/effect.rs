@@ -0,0 +1,51 @@
+//! Status effects: temporary conditions of the players, such as the slowness of the dust storms. The server applies
+//! them with a duration and an amplifier, and sends them to the players, who see them on their HUD.
+
+use serde::{Deserialize, Serialize};
+
+/// The block that gives regeneration to the players who use it
+pub const MED_STATION_BLOCK: &str = "med_station";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum StatusEffectKind {
+    /// The player moves slower, for example in a dust storm
+    Slowness,
+    /// The player regains health over time, for example after using a med station
+    Regeneration,
+    /// The oxygen of the suit is low: the player moves slower and doesn't regenerate
+    Hypoxia,
+}
+
+impl StatusEffectKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Slowness => "Slowness",
+            Self::Regeneration => "Regeneration",
+            Self::Hypoxia => "Hypoxia",
+        }
+    }
+
+    /// Whether the effect hinders the player
+    pub fn is_harmful(self) -> bool {
+        match self {
+            Self::Slowness | Self::Hypoxia => true,
+            Self::Regeneration => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    /// The level of the effect minus one: the stronger effects have a higher amplifier
+    pub amplifier: u8,
+    /// Seconds before the effect wears off, None if it lasts as long as its cause
+    pub remaining: Option<f64>,
+}
+
+impl StatusEffect {
+    /// The level of the effect, starting at 1
+    pub fn level(&self) -> u32 {
+        self.amplifier as u32 + 1
+    }
+}
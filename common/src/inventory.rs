@@ -0,0 +1,256 @@
+use crate::item::{ItemId, ItemStack, MAX_STACK_SIZE};
+use serde::{Deserialize, Serialize};
+
+/// Name of the block robots deposit their cargo into, which the players can open too
+pub const STORAGE_CRATE_BLOCK: &str = "crate";
+/// Number of slots of a storage crate
+pub const STORAGE_CRATE_SLOTS: usize = 27;
+
+/// A fixed number of slots, each holding at most one item stack. The stacks with data only merge with the stacks of
+/// the same data, but they are counted and removed like the others.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    /// Create an empty inventory with `size` slots
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+        }
+    }
+
+    /// Number of slots
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    /// The stack of a slot, None if the slot is empty or doesn't exist
+    pub fn get(&self, slot: usize) -> Option<ItemStack> {
+        self.slots.get(slot).cloned().flatten()
+    }
+
+    /// Replace the content of a slot
+    pub fn set(&mut self, slot: usize, stack: Option<ItemStack>) {
+        self.slots[slot] = stack;
+    }
+
+    /// Remove one item from a slot, and return it
+    pub fn take_one(&mut self, slot: usize) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(slot)?.as_mut()?;
+        stack.count -= 1;
+        let taken = stack.with_count(1);
+        if stack.count == 0 {
+            self.slots[slot] = None;
+        }
+        Some(taken)
+    }
+
+    /// Remove up to `count` items from a slot, and return them
+    pub fn take(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(slot)?.as_mut()?;
+        let taken = stack.with_count(count.min(stack.count));
+        stack.count -= taken.count;
+        if stack.count == 0 {
+            self.slots[slot] = None;
+        }
+        Some(taken)
+    }
+
+    /// Put a stack in a slot, merging it with the stack of the slot if they stack. Return whatever didn't fit.
+    pub fn put(&mut self, slot: usize, mut stack: ItemStack) -> Option<ItemStack> {
+        match self.slots.get_mut(slot) {
+            Some(Some(s)) if s.stacks_with(&stack) => {
+                let moved = u32::min(MAX_STACK_SIZE.saturating_sub(s.count), stack.count);
+                s.count += moved;
+                stack.count -= moved;
+                (stack.count > 0).then_some(stack)
+            }
+            Some(content @ None) => {
+                *content = Some(stack);
+                None
+            }
+            _ => Some(stack),
+        }
+    }
+
+    /// Return true if no slot contains anything
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Return true if `stack` would fit entirely in the inventory
+    pub fn can_insert(&self, stack: &ItemStack) -> bool {
+        let mut room = 0;
+        for slot in self.slots.iter() {
+            room += match slot {
+                None => MAX_STACK_SIZE,
+                Some(s) if s.stacks_with(stack) => MAX_STACK_SIZE.saturating_sub(s.count),
+                Some(_) => 0,
+            };
+            if room >= stack.count {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Insert a stack, filling existing stacks of the same item first.
+    /// Return whatever didn't fit.
+    pub fn insert(&mut self, mut stack: ItemStack) -> Option<ItemStack> {
+        for slot in self.slots.iter_mut() {
+            if let Some(s) = slot {
+                if s.stacks_with(&stack) && s.count < MAX_STACK_SIZE {
+                    let moved = u32::min(MAX_STACK_SIZE - s.count, stack.count);
+                    s.count += moved;
+                    stack.count -= moved;
+                    if stack.count == 0 {
+                        return None;
+                    }
+                }
+            }
+        }
+        for slot in self.slots.iter_mut() {
+            if slot.is_none() {
+                let moved = u32::min(MAX_STACK_SIZE, stack.count);
+                *slot = Some(stack.with_count(moved));
+                stack.count -= moved;
+                if stack.count == 0 {
+                    return None;
+                }
+            }
+        }
+        Some(stack)
+    }
+
+    /// Total number of items `item` in all the slots
+    pub fn count(&self, item: ItemId) -> u32 {
+        self.slots.iter().flatten().filter(|s| s.item == item).map(|s| s.count).sum()
+    }
+
+    /// Remove `stack.count` items `stack.item`, starting with the last slots.
+    /// Return false and remove nothing if there are not enough.
+    pub fn remove(&mut self, stack: &ItemStack) -> bool {
+        if self.count(stack.item) < stack.count {
+            return false;
+        }
+        let mut remaining = stack.count;
+        for slot in self.slots.iter_mut().rev() {
+            if let Some(s) = slot {
+                if s.item == stack.item {
+                    let moved = u32::min(s.count, remaining);
+                    s.count -= moved;
+                    remaining -= moved;
+                    if s.count == 0 {
+                        *slot = None;
+                    }
+                    if remaining == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Remove and return all the stacks
+    pub fn take_all(&mut self) -> Vec<ItemStack> {
+        self.slots.iter_mut().filter_map(Option::take).collect()
+    }
+}
+
+/// An inventory that the slot transactions can change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InventoryId {
+    Player,
+    /// The container that the player opened
+    Container,
+}
+
+/// A slot of the inventory of the player or of the container they opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotId {
+    pub inventory: InventoryId,
+    pub slot: usize,
+}
+
+/// A change of the slots made by a player on the inventory screen. The client sends it, and the server checks and
+/// applies it before sending the new inventories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlotTransaction {
+    /// Move `count` items of a slot to another slot. They merge with the stack of the other slot, or the two stacks
+    /// are swapped if they don't stack and the whole stack is moved.
+    Move { from: SlotId, to: SlotId, count: u32 },
+    /// Move the stack of a slot to the other inventory, between the inventory of the player and the open container
+    QuickTransfer(SlotId),
+}
+
+impl SlotTransaction {
+    /// Apply the transaction to the inventory of a player and the container they opened, or return why it can't be
+    pub fn apply(&self, player: &mut Inventory, mut container: Option<&mut Inventory>) -> Result<(), &'static str> {
+        match *self {
+            SlotTransaction::Move { from, to, count } => {
+                let stack = select(from.inventory, player, &mut container)?.get(from.slot).ok_or("The slot is empty")?;
+                if count == 0 || count > stack.count {
+                    return Err("There are not enough items in the slot");
+                }
+                if from == to {
+                    return Ok(());
+                }
+                let target = select(to.inventory, player, &mut container)?;
+                let moved = match target.slots().get(to.slot).ok_or("The slot doesn't exist")? {
+                    None => count,
+                    Some(other) if other.stacks_with(&stack) => {
+                        u32::min(count, MAX_STACK_SIZE.saturating_sub(other.count))
+                    }
+                    Some(other) if count == stack.count => {
+                        // Swap the stacks
+                        let other = other.clone();
+                        target.set(to.slot, Some(stack));
+                        select(from.inventory, player, &mut container)?.set(from.slot, Some(other));
+                        return Ok(());
+                    }
+                    Some(_) => return Err("The stacks are different"),
+                };
+                if moved == 0 {
+                    return Err("The slot is full");
+                }
+                let taken = select(from.inventory, player, &mut container)?.take(from.slot, moved);
+                let taken = taken.expect("the slot has items");
+                let leftover = select(to.inventory, player, &mut container)?.put(to.slot, taken);
+                debug_assert!(leftover.is_none());
+                Ok(())
+            }
+            SlotTransaction::QuickTransfer(from) => {
+                let other = match from.inventory {
+                    InventoryId::Player => InventoryId::Container,
+                    InventoryId::Container => InventoryId::Player,
+                };
+                select(other, player, &mut container)?;
+                let stack = select(from.inventory, player, &mut container)?.take(from.slot, u32::MAX);
+                let stack = stack.ok_or("The slot is empty")?;
+                // What doesn't fit goes back to the slot, which is empty now
+                if let Some(leftover) = select(other, player, &mut container)?.insert(stack) {
+                    select(from.inventory, player, &mut container)?.put(from.slot, leftover);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn select<'a>(
+    id: InventoryId,
+    player: &'a mut Inventory,
+    container: &'a mut Option<&mut Inventory>,
+) -> Result<&'a mut Inventory, &'static str> {
+    match id {
+        InventoryId::Player => Ok(player),
+        InventoryId::Container => container.as_deref_mut().ok_or("No container is open"),
+    }
+}
@@ -1,14 +1,59 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub type ItemId = u32;
 
-#[derive(Debug, Clone, Deserialize)]
+/// Maximum number of items in a single stack
+pub const MAX_STACK_SIZE: u32 = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "Item")]
 pub enum ItemType {
-    NormalItem { texture: String },
+    NormalItem {
+        texture: String,
+        /// Shown in the tooltip of the item
+        #[serde(default)]
+        description: String,
+    },
+    /// An item that the players use to restore their vitals, such as food or an energy pack
+    Consumable {
+        texture: String,
+        #[serde(default)]
+        description: String,
+        /// Seconds during which the item is used before it is consumed
+        use_time: f64,
+        /// The amounts restored when the item is consumed
+        #[serde(default)]
+        health: f64,
+        #[serde(default)]
+        oxygen: f64,
+        #[serde(default)]
+        energy: f64,
+    },
+    /// A light held by the players, which uses the energy of their suit while it is on
+    Flashlight {
+        texture: String,
+        #[serde(default)]
+        description: String,
+        /// Energy used every second while the flashlight is on
+        energy_use: f64,
+    },
+    /// The item dropped by a block. Registered automatically for every block.
+    BlockItem { block: String },
 }
 
-#[derive(Debug, Clone)]
+impl ItemType {
+    /// The description of the item, empty if it has none
+    pub fn description(&self) -> &str {
+        match self {
+            Self::NormalItem { description, .. }
+            | Self::Consumable { description, .. }
+            | Self::Flashlight { description, .. } => description,
+            Self::BlockItem { .. } => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ItemMesh {
     SimpleMesh {
         mesh_id: u32,
@@ -17,8 +62,106 @@ pub enum ItemMesh {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
     pub name: String,
     pub ty: ItemType,
-}
\ No newline at end of file
+}
+
+/// A stack of identical items. The stacks of the same item only merge if their data is the same too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+    /// None for the items without data, which is most of them
+    #[serde(default)]
+    data: Option<Box<ItemData>>,
+}
+
+/// The data of a stack beyond its item, such as the wear of a tool. It is only read and changed through the
+/// accessors of `ItemStack`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemData {
+    /// The uses left before the item breaks
+    durability: Option<u32>,
+    /// The name given by the player, shown instead of the name of the item
+    custom_name: Option<String>,
+    /// The name of the robot program stored in the item
+    robot_program: Option<String>,
+    /// The energy stored in the item, in percent
+    charge: Option<u8>,
+}
+
+impl ItemStack {
+    pub fn new(item: ItemId, count: u32) -> Self {
+        Self { item, count, data: None }
+    }
+
+    /// Whether the two stacks can be merged: they have the same item and the same data
+    pub fn stacks_with(&self, other: &ItemStack) -> bool {
+        self.item == other.item && self.data == other.data
+    }
+
+    /// A stack of `count` items with the same item and data
+    pub fn with_count(&self, count: u32) -> Self {
+        Self { count, ..self.clone() }
+    }
+
+    /// Whether the stack has some data
+    pub fn has_data(&self) -> bool {
+        self.data.is_some()
+    }
+
+    /// The data of the stack, to be stored with it
+    pub fn data(&self) -> Option<&ItemData> {
+        self.data.as_deref()
+    }
+
+    /// Replace the data of the stack by stored data
+    pub fn set_data(&mut self, data: Option<ItemData>) {
+        self.data = data.filter(|data| *data != ItemData::default()).map(Box::new);
+    }
+
+    pub fn durability(&self) -> Option<u32> {
+        self.data.as_ref()?.durability
+    }
+
+    pub fn set_durability(&mut self, durability: Option<u32>) {
+        self.update_data(|data| data.durability = durability);
+    }
+
+    pub fn custom_name(&self) -> Option<&str> {
+        self.data.as_ref()?.custom_name.as_deref()
+    }
+
+    pub fn set_custom_name(&mut self, name: Option<String>) {
+        self.update_data(|data| data.custom_name = name);
+    }
+
+    pub fn robot_program(&self) -> Option<&str> {
+        self.data.as_ref()?.robot_program.as_deref()
+    }
+
+    pub fn set_robot_program(&mut self, program: Option<String>) {
+        self.update_data(|data| data.robot_program = program);
+    }
+
+    /// The energy stored in the item, in percent
+    pub fn charge(&self) -> Option<u8> {
+        self.data.as_ref()?.charge
+    }
+
+    pub fn set_charge(&mut self, charge: Option<u8>) {
+        self.update_data(|data| data.charge = charge.map(|charge| charge.min(100)));
+    }
+
+    /// Change the data, and drop it if nothing is left so that the stack merges with the stacks without data
+    fn update_data(&mut self, update: impl FnOnce(&mut ItemData)) {
+        let mut data = self.data.take().unwrap_or_default();
+        update(&mut data);
+        if *data != ItemData::default() {
+            self.data = Some(data);
+        }
+    }
+}
@@ -1,13 +1,30 @@
+pub mod achievement;
+pub mod animation;
+pub mod biome;
+pub mod claim;
+pub mod combat;
+pub mod content;
+pub mod effect;
 pub mod player;
 pub mod registry;
+pub mod robot;
+pub mod sign;
+pub mod statistics;
 pub mod worker;
 pub mod block;
 pub mod data;
 pub mod item;
+pub mod inventory;
+pub mod loot;
+pub mod meteor;
+pub mod mob;
 pub mod network;
+pub mod random;
 pub mod world;
 pub mod collections;
 pub mod physics;
 pub mod debug;
 pub mod time;
+pub mod theme;
+pub mod trade;
 pub mod worldgen;
\ No newline at end of file
@@ -0,0 +1,116 @@
+//! Loot tables, defined in the `loot_tables` folder of the data. A block whose type has a `loot_table` drops its loot
+//! instead of its item when a player breaks it, and the storage crates placed by the decorators are filled with the
+//! loot of their table the first time they are opened.
+
+use crate::item::{Item, ItemId, ItemStack};
+use crate::registry::Registry;
+use serde::{Deserialize, Serialize};
+
+/// A condition for an entry of a loot table to be drawn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LootCondition {
+    /// The block is broken by a player holding this item
+    Holding(String),
+    /// The block is broken by a player holding nothing. The storage crates are opened with an empty hand.
+    EmptyHand,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LootEntry {
+    pub item: String,
+    /// The chance of the entry to be drawn, relative to the weights of the other entries
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// The smallest and largest numbers of items, both included
+    #[serde(default = "default_count")]
+    pub count: (u32, u32),
+    /// The entry is only drawn if all its conditions are met
+    #[serde(default)]
+    pub conditions: Vec<LootCondition>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_count() -> (u32, u32) {
+    (1, 1)
+}
+
+fn default_rolls() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "LootTable")]
+pub struct LootTable {
+    /// The number of entries drawn, the same entry can be drawn several times
+    #[serde(default = "default_rolls")]
+    pub rolls: u32,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    /// Return the problems of the table, such as the unknown items
+    pub fn validate(&self, items: &Registry<Item>) -> Vec<String> {
+        let mut errors = Vec::new();
+        for entry in self.entries.iter() {
+            if items.get_id_by_name(&entry.item).is_none() {
+                errors.push(format!("unknown item {}", entry.item));
+            }
+            if entry.count.0 > entry.count.1 {
+                errors.push(format!("the count of {} is not a range", entry.item));
+            }
+            for condition in entry.conditions.iter() {
+                match condition {
+                    LootCondition::Holding(item) if items.get_id_by_name(item).is_none() => {
+                        errors.push(format!("unknown item {} in a condition", item));
+                    }
+                    _ => (),
+                }
+            }
+        }
+        errors
+    }
+
+    /// Draw the items of the loot, for a player holding `holding`. `random` returns numbers in [0; 1).
+    pub fn roll(
+        &self,
+        items: &Registry<Item>,
+        holding: Option<ItemId>,
+        mut random: impl FnMut() -> f64,
+    ) -> Vec<ItemStack> {
+        let entries = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry.conditions.iter().all(|condition| match condition {
+                    LootCondition::Holding(item) => holding.is_some() && items.get_id_by_name(item) == holding,
+                    LootCondition::EmptyHand => holding.is_none(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let total_weight = entries.iter().map(|entry| entry.weight as f64).sum::<f64>();
+        let mut stacks = Vec::new();
+        if total_weight <= 0.0 {
+            return stacks;
+        }
+        for _ in 0..self.rolls {
+            let mut target = random() * total_weight;
+            // The last entry is chosen if the rounding errors skip all of them
+            let entry = entries
+                .iter()
+                .find(|entry| {
+                    target -= entry.weight as f64;
+                    target < 0.0
+                })
+                .unwrap_or_else(|| entries.last().unwrap());
+            let (min, max) = entry.count;
+            let count = min + (random() * (max - min + 1) as f64) as u32;
+            if let (Some(item), true) = (items.get_id_by_name(&entry.item), count > 0) {
+                stacks.push(ItemStack::new(item, count.min(max)));
+            }
+        }
+        stacks
+    }
+}
@@ -0,0 +1,15 @@
+//! Meteors falling from the sky
+
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// What the clients know about a meteor
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MeteorInfo {
+    /// The position of the meteor, None before it appears in the sky
+    pub pos: Option<Vector3<f64>>,
+    /// Where the meteor will hit the ground
+    pub target: Vector3<f64>,
+    /// Seconds before the impact
+    pub time_to_impact: f64,
+}
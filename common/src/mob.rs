@@ -0,0 +1,51 @@
+//! Creatures roaming the world, such as the rogue drones
+
+use crate::physics::aabb::AABB;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Side of the cubic box of a creature
+pub const MOB_SIDE: f64 = 0.8;
+
+/// Some unique creature id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MobId(pub u32);
+
+/// What a creature is currently doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MobState {
+    /// Flying around with no target
+    Wandering,
+    /// Flying towards its target
+    Chasing,
+    /// Close enough to hit its target
+    Attacking,
+}
+
+/// What the clients know about a creature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MobInfo {
+    pub id: MobId,
+    /// The name of the kind of the creature, as in the world settings
+    pub kind: String,
+    /// Position of the center of the creature
+    pub pos: Vector3<f64>,
+    /// Direction the creature is facing, in degrees
+    pub yaw: f64,
+    pub state: MobState,
+    pub health: f64,
+    pub max_health: f64,
+}
+
+impl MobInfo {
+    /// The box of the creature, centered on its position
+    pub fn aabb(&self) -> AABB {
+        mob_aabb(self.pos)
+    }
+}
+
+/// The box of a creature centered on `pos`
+pub fn mob_aabb(pos: Vector3<f64>) -> AABB {
+    let half_side = Vector3::new(MOB_SIDE, MOB_SIDE, MOB_SIDE) / 2.0;
+    AABB::new(pos - half_side, (MOB_SIDE, MOB_SIDE, MOB_SIDE))
+}
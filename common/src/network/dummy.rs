@@ -1,54 +1,258 @@
+//! Network backend of the singleplayer game: the client and the server exchange the messages through channels.
+//! The server accepts any number of clients in the same process, created by a `DummyConnector`.
+//!
+//! Artificial latency, jitter, packet loss and reordering can be added with `set_network_conditions`,
+//! to test the game as if it was played on a bad connection.
+
 use super::messages::{ToClient, ToServer};
 use crate::{
-    network::{ClientEvent, ServerEvent},
+    network::{stats::NetworkStats, ClientEvent, ServerEvent},
     player::PlayerId,
+    random::Random,
 };
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Delay before a lost message that must arrive is sent again, in addition to a round trip
+const RETRANSMISSION_DELAY: Duration = Duration::from_millis(200);
+
+/// Artificial conditions applied to the messages in both directions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetworkConditions {
+    /// Delay of every message
+    pub latency: Duration,
+    /// Maximum random delay added to the latency
+    pub jitter: Duration,
+    /// Probability that a message is lost. The lost state updates are dropped because newer ones follow,
+    /// the other messages are sent again later like a reliable transport would.
+    pub drop_rate: f64,
+    /// Probability that a message is delayed by another latency, so that the next messages arrive before it
+    pub reorder_rate: f64,
+}
+
+static NETWORK_CONDITIONS: Mutex<NetworkConditions> = Mutex::new(NetworkConditions {
+    latency: Duration::ZERO,
+    jitter: Duration::ZERO,
+    drop_rate: 0.0,
+    reorder_rate: 0.0,
+});
+
+pub fn set_network_conditions(conditions: NetworkConditions) {
+    *NETWORK_CONDITIONS.lock().unwrap() = conditions;
+}
+
+pub fn get_network_conditions() -> NetworkConditions {
+    *NETWORK_CONDITIONS.lock().unwrap()
+}
+
+trait Message {
+    /// Whether the message is a state update sent regularly, which can be lost without consequences
+    fn is_state_update(&self) -> bool;
+}
+
+impl Message for ToServer {
+    fn is_state_update(&self) -> bool {
+        matches!(self, ToServer::UpdateInput(_))
+    }
+}
+
+impl Message for ToClient {
+    fn is_state_update(&self) -> bool {
+        matches!(
+            self,
+            ToClient::UpdatePhysics(_)
+                | ToClient::UpdateRobots(_)
+                | ToClient::UpdateDroppedItems(_)
+                | ToClient::UpdateTime(_)
+                | ToClient::UpdateMeteors(_)
+        )
+    }
+}
+
+/// The sending end of a channel, which decides when the messages arrive
+struct Link<T> {
+    sender: Sender<(Instant, T)>,
+    /// The arrival time of the last message that keeps the order
+    last_arrival: Instant,
+    random: Random,
+}
+
+impl<T: Message> Link<T> {
+    fn new(sender: Sender<(Instant, T)>, seed: i32) -> Self {
+        Self {
+            sender,
+            last_arrival: Instant::now(),
+            random: Random::new(seed),
+        }
+    }
+
+    fn send(&mut self, message: T) {
+        let conditions = get_network_conditions();
+        let mut arrival = Instant::now() + conditions.latency + conditions.jitter.mul_f64(self.random.next_f64());
+        if conditions.drop_rate > 0.0 && self.random.next_f64() < conditions.drop_rate {
+            if message.is_state_update() {
+                return;
+            }
+            arrival += conditions.latency * 2 + RETRANSMISSION_DELAY;
+        }
+        if conditions.reorder_rate > 0.0 && self.random.next_f64() < conditions.reorder_rate {
+            arrival += conditions.latency;
+        } else {
+            // The jitter alone doesn't change the order of the messages
+            arrival = arrival.max(self.last_arrival);
+            self.last_arrival = arrival;
+        }
+        // The receiver notices by itself when the other side is gone
+        let _ = self.sender.send((arrival, message));
+    }
+}
+
+/// The receiving end of a channel, which holds the messages until they arrive
+struct Inbox<T> {
+    receiver: Receiver<(Instant, T)>,
+    /// The messages that didn't arrive yet, by arrival time
+    pending: Vec<(Instant, T)>,
+}
+
+impl<T> Inbox<T> {
+    fn new(receiver: Receiver<(Instant, T)>) -> Self {
+        Self {
+            receiver,
+            pending: Vec::new(),
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let disconnected = loop {
+            match self.receiver.try_recv() {
+                Ok((arrival, message)) => {
+                    let index = self.pending.partition_point(|(other, _)| *other <= arrival);
+                    self.pending.insert(index, (arrival, message));
+                }
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        match self.pending.first() {
+            Some((arrival, _)) if *arrival <= Instant::now() => Ok(self.pending.remove(0).1),
+            None if disconnected => Err(TryRecvError::Disconnected),
+            _ => Err(TryRecvError::Empty),
+        }
+    }
+}
 
 pub struct DummyClient {
     first_queried: bool,
-    pub(self) to_server: Sender<ToServer>,
-    pub(self) to_client: Receiver<ToClient>,
+    stats: NetworkStats,
+    to_server: Link<ToServer>,
+    to_client: Inbox<ToClient>,
+}
+
+/// The server side of a connection
+struct Connection {
+    id: PlayerId,
+    to_client: Link<ToClient>,
+    to_server: Inbox<ToServer>,
 }
 
 pub struct DummyServer {
-    first_queried: bool,
-    pub(self) to_client: Sender<ToClient>,
-    pub(self) to_server: Receiver<ToServer>,
+    stats: NetworkStats,
+    new_connections: Receiver<Connection>,
+    connections: Vec<Connection>,
+    /// The connection to check first for messages, so that every client is served in turn
+    next_connection: usize,
 }
 
-pub fn new() -> (DummyClient, DummyServer) {
-    let server_to_client = channel();
-    let client_to_server = channel();
-    (
+/// Create new clients connected to a `DummyServer`, from any thread
+#[derive(Clone)]
+pub struct DummyConnector {
+    new_connections: Sender<Connection>,
+    next_id: Arc<AtomicU16>,
+}
+
+impl DummyConnector {
+    pub fn connect(&self) -> DummyClient {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let server_to_client = channel();
+        let client_to_server = channel();
+        // The seeds only need to differ between the links
+        let seed = 2 * id as i32;
+        let connection = Connection {
+            id: PlayerId(id),
+            to_client: Link::new(server_to_client.0, seed),
+            to_server: Inbox::new(client_to_server.1),
+        };
+        // The client is never connected if the server stopped
+        let _ = self.new_connections.send(connection);
         DummyClient {
             first_queried: true,
-            to_server: client_to_server.0,
-            to_client: server_to_client.1,
+            stats: NetworkStats::default(),
+            to_server: Link::new(client_to_server.0, seed + 1),
+            to_client: Inbox::new(server_to_client.1),
+        }
+    }
+}
+
+/// Create a server and a connector for its clients
+pub fn new_server() -> (DummyConnector, DummyServer) {
+    let (sender, receiver) = channel();
+    (
+        DummyConnector {
+            new_connections: sender,
+            next_id: Arc::new(AtomicU16::new(0)),
         },
         DummyServer {
-            first_queried: true,
-            to_client: server_to_client.0,
-            to_server: client_to_server.1,
+            stats: NetworkStats::default(),
+            new_connections: receiver,
+            connections: Vec::new(),
+            next_connection: 0,
         },
     )
 }
 
+/// Create a server with a single client
+pub fn new() -> (DummyClient, DummyServer) {
+    let (connector, server) = new_server();
+    (connector.connect(), server)
+}
+
 impl super::Server for DummyServer {
     fn receive_event(&mut self) -> ServerEvent {
-        if self.first_queried {
-            self.first_queried = false;
-            return ServerEvent::ClientConnected(PlayerId(0));
+        if let Ok(connection) = self.new_connections.try_recv() {
+            let id = connection.id;
+            self.connections.push(connection);
+            return ServerEvent::ClientConnected(id);
         }
-        match self.to_server.try_recv() {
-            Ok(m) => ServerEvent::ClientMessage(PlayerId(0), m),
-            Err(TryRecvError::Empty) => ServerEvent::NoEvent,
-            Err(TryRecvError::Disconnected) => panic!("Got to somehow terminate the server :)"),
+        for i in 0..self.connections.len() {
+            let index = (self.next_connection + i) % self.connections.len();
+            match self.connections[index].to_server.try_recv() {
+                Ok(m) => {
+                    self.next_connection = index + 1;
+                    self.stats.record_received(m.name(), m.size());
+                    return ServerEvent::ClientMessage(self.connections[index].id, m);
+                }
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => {
+                    let connection = self.connections.remove(index);
+                    return ServerEvent::ClientDisconnected(connection.id);
+                }
+            }
         }
+        ServerEvent::NoEvent
     }
 
-    fn send(&mut self, _: PlayerId, message: ToClient) {
-        self.to_client.send(message).unwrap();
+    fn send(&mut self, client: PlayerId, message: ToClient) {
+        self.stats.record_sent(message.name(), message.size());
+        // The messages sent to a client that just disconnected are lost
+        if let Some(connection) = self.connections.iter_mut().find(|connection| connection.id == client) {
+            connection.to_client.send(message);
+        }
+    }
+
+    fn stats(&self) -> NetworkStats {
+        self.stats.clone()
     }
 }
 
@@ -59,13 +263,21 @@ impl super::Client for DummyClient {
             return ClientEvent::Connected;
         }
         match self.to_client.try_recv() {
-            Ok(m) => ClientEvent::ServerMessage(m),
+            Ok(m) => {
+                self.stats.record_received(m.name(), m.size());
+                ClientEvent::ServerMessage(m)
+            }
             Err(TryRecvError::Empty) => ClientEvent::NoEvent,
-            Err(TryRecvError::Disconnected) => unreachable!(),
+            Err(TryRecvError::Disconnected) => ClientEvent::Disconnected,
         }
     }
 
     fn send(&mut self, message: ToServer) {
-        self.to_server.send(message).unwrap();
+        self.stats.record_sent(message.name(), message.size());
+        self.to_server.send(message);
+    }
+
+    fn stats(&self) -> NetworkStats {
+        self.stats.clone()
     }
 }
@@ -0,0 +1,241 @@
+//! Protection of the server against the clients that send too many messages or invalid ones.
+//!
+//! `LimitedServer` wraps any network backend, and drops the messages of a connection that exceeds its limits
+//! before the server sees them. The size of a message is the size of its bincode encoding, which the backends that
+//! decode bytes must also limit with `decoding_options`.
+
+use super::{
+    messages::{ToClient, ToServer},
+    stats::NetworkStats,
+    Server, ServerEvent,
+};
+use crate::debug::send_debug_info;
+use crate::player::PlayerId;
+use bincode::Options;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// The largest message decoded from bytes, such as a frame of a replay file, in bytes. A damaged length in the bytes
+/// can't make the decoder allocate more than this.
+pub const MAX_DECODED_MESSAGE_SIZE: u64 = 16_000_000;
+
+/// The options of `bincode::serialize` and `bincode::deserialize`, which stop decoding a value after `limit` bytes
+pub fn decoding_options(limit: u64) -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionLimits {
+    pub max_messages_per_second: f64,
+    pub max_bytes_per_second: f64,
+    /// The largest message accepted, in bytes
+    pub max_message_size: usize,
+    /// Number of seconds of the rates that a connection can send at once
+    pub burst_seconds: f64,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_messages_per_second: 500.0,
+            max_bytes_per_second: 1_000_000.0,
+            // Enough for the largest custom character model
+            max_message_size: 2_000_000,
+            burst_seconds: 2.0,
+        }
+    }
+}
+
+/// The number of messages dropped for each reason, since the server started
+#[derive(Debug, Clone, Copy, Default)]
+struct DroppedMessages {
+    too_large: u64,
+    invalid: u64,
+    rate_limited: u64,
+}
+
+/// A token bucket: the tokens are refilled at a constant rate, up to a maximum
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    rate: f64,
+    capacity: f64,
+}
+
+impl Bucket {
+    fn new(rate: f64, burst_seconds: f64) -> Self {
+        Self {
+            tokens: rate * burst_seconds,
+            rate,
+            capacity: rate * burst_seconds,
+        }
+    }
+
+    fn refill(&mut self, seconds: f64) {
+        self.tokens = (self.tokens + self.rate * seconds).min(self.capacity);
+    }
+}
+
+struct Connection {
+    messages: Bucket,
+    bytes: Bucket,
+    last_update: Instant,
+}
+
+pub struct LimitedServer<S: Server> {
+    inner: S,
+    limits: ConnectionLimits,
+    connections: HashMap<PlayerId, Connection>,
+    dropped: DroppedMessages,
+}
+
+impl<S: Server> LimitedServer<S> {
+    pub fn new(inner: S, limits: ConnectionLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            connections: HashMap::new(),
+            dropped: DroppedMessages::default(),
+        }
+    }
+
+    /// Return true if the message of a client can be given to the server
+    fn accept(&mut self, client: PlayerId, message: &ToServer) -> bool {
+        // The size of the encoding is computed up to the limit, the larger messages are not measured further
+        let max_size = self.limits.max_message_size as u64;
+        let size = decoding_options(max_size).serialized_size(message).map_or(usize::MAX, |size| size as usize);
+        if size > self.limits.max_message_size {
+            debug!("Dropped a message of more than {} bytes from client {:?}", max_size, client);
+            self.dropped.too_large += 1;
+            return false;
+        }
+        if let Err(reason) = message.validate() {
+            debug!("Dropped an invalid message from client {:?}: {}", client, reason);
+            self.dropped.invalid += 1;
+            return false;
+        }
+        let limits = self.limits;
+        let connection = self.connections.entry(client).or_insert_with(|| Connection {
+            messages: Bucket::new(limits.max_messages_per_second, limits.burst_seconds),
+            bytes: Bucket::new(limits.max_bytes_per_second, limits.burst_seconds),
+            last_update: Instant::now(),
+        });
+        let now = Instant::now();
+        let elapsed = (now - connection.last_update).as_secs_f64();
+        connection.last_update = now;
+        connection.messages.refill(elapsed);
+        connection.bytes.refill(elapsed);
+        if connection.messages.tokens < 1.0 || connection.bytes.tokens < size as f64 {
+            self.dropped.rate_limited += 1;
+            if self.dropped.rate_limited % 100 == 1 {
+                warn!("Client {:?} sends too many messages, dropping them", client);
+            }
+            return false;
+        }
+        connection.messages.tokens -= 1.0;
+        connection.bytes.tokens -= size as f64;
+        true
+    }
+}
+
+impl<S: Server> Server for LimitedServer<S> {
+    fn receive_event(&mut self) -> ServerEvent {
+        loop {
+            let event = self.inner.receive_event();
+            match &event {
+                ServerEvent::ClientMessage(client, message) => {
+                    if self.accept(*client, message) {
+                        return event;
+                    }
+                    send_debug_info(
+                        "Network",
+                        "dropped",
+                        format!(
+                            "Dropped messages: {} too large, {} invalid, {} rate limited",
+                            self.dropped.too_large, self.dropped.invalid, self.dropped.rate_limited
+                        ),
+                    );
+                }
+                ServerEvent::ClientDisconnected(client) => {
+                    self.connections.remove(client);
+                    return event;
+                }
+                _ => return event,
+            }
+        }
+    }
+
+    fn send(&mut self, client: PlayerId, message: ToClient) {
+        self.inner.send(client, message);
+    }
+
+    fn stats(&self) -> NetworkStats {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::dummy::new_server;
+    use nalgebra::Vector3;
+
+    fn limited_server(limits: ConnectionLimits) -> LimitedServer<impl Server> {
+        LimitedServer::new(new_server().1, limits)
+    }
+
+    #[test]
+    fn test_bucket_refill() {
+        let mut bucket = Bucket::new(10.0, 2.0);
+        assert_eq!(bucket.tokens, 20.0);
+        bucket.tokens = 0.0;
+        bucket.refill(0.5);
+        assert_eq!(bucket.tokens, 5.0);
+        // The tokens don't accumulate beyond the burst
+        bucket.refill(100.0);
+        assert_eq!(bucket.tokens, 20.0);
+    }
+
+    #[test]
+    fn test_burst() {
+        let limits = ConnectionLimits { max_messages_per_second: 0.001, burst_seconds: 10_000.0, ..Default::default() };
+        let mut server = limited_server(limits);
+        let client = PlayerId::from_raw(1);
+        let message = ToServer::Command("/help".to_owned());
+        for _ in 0..10 {
+            assert!(server.accept(client, &message));
+        }
+        assert!(!server.accept(client, &message));
+        assert_eq!(server.dropped.rate_limited, 1);
+        // The other clients have their own buckets
+        assert!(server.accept(PlayerId::from_raw(2), &message));
+    }
+
+    #[test]
+    fn test_drop_oversized_and_invalid() {
+        let limits = ConnectionLimits { max_message_size: 100, ..Default::default() };
+        let mut server = limited_server(limits);
+        let client = PlayerId::from_raw(1);
+        assert!(!server.accept(client, &ToServer::Command("a".repeat(200))));
+        assert_eq!(server.dropped.too_large, 1);
+        assert!(!server.accept(client, &ToServer::PlaceBlock(Vector3::new(f64::NAN, 0.0, 0.0), 0.0, 0.0)));
+        assert_eq!(server.dropped.invalid, 1);
+        assert!(server.accept(client, &ToServer::Command("a".repeat(50))));
+        assert_eq!(server.dropped.rate_limited, 0);
+    }
+
+    #[test]
+    fn test_decoding_limit() {
+        // The limit only applies to the values decoded from a reader, like the frames of the replays
+        let bytes = decoding_options(1000).serialize(&"a".repeat(100)).unwrap();
+        assert_eq!(decoding_options(1000).deserialize_from::<_, String>(&bytes[..]).unwrap().len(), 100);
+        assert!(decoding_options(50).deserialize_from::<_, String>(&bytes[..]).is_err());
+        // A damaged length doesn't make the decoder allocate it
+        let damaged = decoding_options(1000).serialize(&u64::MAX).unwrap();
+        assert!(decoding_options(1000).deserialize_from::<_, String>(&damaged[..]).is_err());
+    }
+}
@@ -1,15 +1,42 @@
 use crate::{
-    data::Data,
+    achievement::Achievement,
+    animation::MAX_ANIMATION_NAME_LENGTH,
+    biome::BiomeId,
+    claim::ClaimInfo,
+    combat::Combatant,
+    content::{ContentManifest, MAX_VERSION_LENGTH},
+    data::{Data, DataErrorReport},
+    effect::StatusEffect,
+    inventory::{Inventory, SlotTransaction},
+    item::{ItemId, ItemStack},
+    meteor::MeteorInfo,
+    mob::MobInfo,
     physics::simulation::ServerState,
     player::PlayerId,
-    player::{PlayerInput, RenderDistance},
-    world::{Chunk, LightChunk},
+    player::{PlayerCapabilities, PlayerInput, PlayerProfile, RenderDistance, Vitals},
+    robot::{area_volume, RobotId, RobotInfo, MAX_AREA_VOLUME},
+    sign::MAX_SIGN_TEXT_LENGTH,
+    statistics::PlayerStatistics,
+    time::WorldTime,
+    trade::Trade,
+    world::{BlockPos, Chunk, DimensionId, LightChunk, WorldHeight},
 };
+use crate::block::BlockId;
+use crate::physics::player::PhysicsPlayer;
+use crate::player::{CharacterModel, MAX_CUSTOM_MODEL_VOXELS};
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+/// Maximum length of the commands, in bytes
+pub const MAX_COMMAND_LENGTH: usize = 1024;
+/// Maximum length of the names sent by the players, in bytes. The names are shortened further by the server.
+const MAX_NAME_LENGTH: usize = 256;
+/// Maximum render distance in any direction, in chunks
+const MAX_RENDER_DISTANCE: u64 = 64;
+
 /// A message sent to the server by the client
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ToServer {
     /// Update player render distance
     SetRenderDistance(RenderDistance),
@@ -21,18 +48,351 @@ pub enum ToServer {
     SelectBlock(Vector3<f64>, f64, f64),
     /// Place a block
     PlaceBlock(Vector3<f64>, f64, f64),
+    /// Interact with a block, for example open a door
+    InteractBlock(Vector3<f64>, f64, f64),
+    /// Hit the creature, robot or player in front of the player, or of the robot they control (player pos, yaw, pitch)
+    Attack(Vector3<f64>, f64, f64),
+    /// Make the player's robot mine the area between two corners
+    AssignMiningArea(BlockPos, BlockPos),
+    /// Take (true) or release (false) control of the player's robot
+    SetRobotControl(bool),
+    /// Set the name and character model of the player, sent when joining the server
+    SetProfile(PlayerProfile),
+    /// Run a command, for example `/stats`
+    Command(String),
+    /// Ask for the statistics of the player
+    RequestStatistics,
+    /// Select a slot of the inventory of the player
+    SelectSlot(usize),
+    /// Start using the item of the selected slot, for example eat food
+    UseItem,
+    /// Measure the round-trip time, the server answers with a `Pong` with the same number
+    Ping(u32),
+    /// Play an animation of the character of the player that the other players see, for example `wave`
+    Emote(String),
+    /// Turn the headlights of the controlled robot on or off, or the flashlight held by the player
+    ToggleLight,
+    /// Make a trade of the supply terminal at some position, by its index in the trade list of the terminal
+    Trade(BlockPos, usize),
+    /// Ask for the data pack of the game data announced by a `DataHash`, which is not in the cache of the client
+    RequestData,
+    /// The version of the game of the client, sent when it can use the `Content` of the server
+    Hello(String),
+    /// Move items between the slots of the inventory of the player and of the container they opened
+    MoveItems(SlotTransaction),
+    /// The player closed the container they opened
+    CloseContainer,
+    /// Write the text of the sign at some position, an empty text erases it
+    SetSignText(BlockPos, String),
+}
+
+impl ToServer {
+    /// The name of the kind of message, used as the channel of the network statistics
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToServer::SetRenderDistance(_) => "SetRenderDistance",
+            ToServer::UpdateInput(_) => "UpdateInput",
+            ToServer::BreakBlock(_, _, _) => "BreakBlock",
+            ToServer::SelectBlock(_, _, _) => "SelectBlock",
+            ToServer::PlaceBlock(_, _, _) => "PlaceBlock",
+            ToServer::InteractBlock(_, _, _) => "InteractBlock",
+            ToServer::Attack(_, _, _) => "Attack",
+            ToServer::AssignMiningArea(_, _) => "AssignMiningArea",
+            ToServer::SetRobotControl(_) => "SetRobotControl",
+            ToServer::SetProfile(_) => "SetProfile",
+            ToServer::Command(_) => "Command",
+            ToServer::RequestStatistics => "RequestStatistics",
+            ToServer::SelectSlot(_) => "SelectSlot",
+            ToServer::UseItem => "UseItem",
+            ToServer::Ping(_) => "Ping",
+            ToServer::Emote(_) => "Emote",
+            ToServer::ToggleLight => "ToggleLight",
+            ToServer::Trade(_, _) => "Trade",
+            ToServer::RequestData => "RequestData",
+            ToServer::Hello(_) => "Hello",
+            ToServer::MoveItems(_) => "MoveItems",
+            ToServer::CloseContainer => "CloseContainer",
+            ToServer::SetSignText(_, _) => "SetSignText",
+        }
+    }
+
+    /// Estimate the size of the message once encoded, in bytes
+    pub fn size(&self) -> usize {
+        let content = match self {
+            ToServer::SetProfile(profile) => profile_size(profile),
+            ToServer::Command(text)
+            | ToServer::Emote(text)
+            | ToServer::Hello(text)
+            | ToServer::SetSignText(_, text) => text.len(),
+            _ => 0,
+        };
+        std::mem::size_of::<Self>() + content
+    }
+
+    /// Check the values that the server can't handle, before the message is processed
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let is_finite = |pos: &Vector3<f64>, yaw: f64, pitch: f64| pos.iter().all(|x| x.is_finite()) && yaw.is_finite() && pitch.is_finite();
+        match self {
+            ToServer::SetRenderDistance(render_distance) => {
+                let RenderDistance { x_max, x_min, y_max, y_min, z_max, z_min } = *render_distance;
+                if [x_max, x_min, y_max, y_min, z_max, z_min].iter().any(|&d| d > MAX_RENDER_DISTANCE) {
+                    return Err("render distance too large");
+                }
+            }
+            ToServer::BreakBlock(pos, yaw, pitch)
+            | ToServer::SelectBlock(pos, yaw, pitch)
+            | ToServer::PlaceBlock(pos, yaw, pitch)
+            | ToServer::InteractBlock(pos, yaw, pitch)
+            | ToServer::Attack(pos, yaw, pitch)
+                if !is_finite(pos, *yaw, *pitch) =>
+            {
+                return Err("position or orientation not finite");
+            }
+            ToServer::SetProfile(profile) => {
+                if profile.name.len() > MAX_NAME_LENGTH {
+                    return Err("name too long");
+                }
+                match &profile.model {
+                    CharacterModel::Builtin(name) if name.len() > MAX_NAME_LENGTH => return Err("model name too long"),
+                    CharacterModel::Custom(model) if model.total_voxels() > MAX_CUSTOM_MODEL_VOXELS => {
+                        return Err("custom model too large")
+                    }
+                    CharacterModel::Custom(model) if !model.is_well_formed() => return Err("malformed custom model"),
+                    _ => (),
+                }
+            }
+            ToServer::AssignMiningArea(from, to) if area_volume(*from, *to).is_none_or(|v| v > MAX_AREA_VOLUME) => {
+                return Err("mining area too large")
+            }
+            ToServer::Command(command) if command.len() > MAX_COMMAND_LENGTH => return Err("command too long"),
+            ToServer::Emote(name) if name.len() > MAX_ANIMATION_NAME_LENGTH => return Err("emote name too long"),
+            ToServer::Hello(version) if version.len() > MAX_VERSION_LENGTH => return Err("game version too long"),
+            ToServer::SetSignText(_, text) if text.len() > MAX_SIGN_TEXT_LENGTH => return Err("sign text too long"),
+            _ => (),
+        }
+        Ok(())
+    }
 }
 
-/// A message sent to the client by the server
-#[derive(Debug, Clone)]
+/// A message sent to the client by the server.
+/// The messages can be serialized to be saved in a replay, except the game data which is too large.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ToClient {
-    /// Send the game data
-    GameData(Data),
-    /// Send the chunk at some position
-    Chunk(Arc<Chunk>, Arc<LightChunk>),
+    /// Send the chunk at some position of a dimension, with its light and the biomes of its columns
+    Chunk(DimensionId, Arc<Chunk>, Arc<LightChunk>),
     /// Update the whole of the physics simulation
     // TODO: only send part of the physics simulation
     UpdatePhysics(ServerState),
     /// Set the id of a player
     CurrentId(PlayerId),
+    /// Update the state of all the robots
+    UpdateRobots(Vec<RobotInfo>),
+    /// Bind the player's camera to a robot, or back to the player
+    ControlRobot(Option<RobotId>),
+    /// Update the position of all the items lying in the world
+    UpdateDroppedItems(Vec<(Vector3<f64>, ItemStack)>),
+    /// Update the in-game time
+    UpdateTime(WorldTime),
+    /// An explosion happened (position, power, items to show as debris)
+    Explosion(Vector3<f64>, f64, Vec<ItemId>),
+    /// Update the incoming meteors
+    UpdateMeteors(Vec<MeteorInfo>),
+    /// Update the state of all the creatures
+    UpdateMobs(Vec<MobInfo>),
+    /// Update the name and character model of a player
+    PlayerProfile(PlayerId, PlayerProfile),
+    /// The achievements already unlocked by the player, sent when the player's name is known
+    Achievements(Vec<Achievement>),
+    /// The player unlocked an achievement
+    AchievementUnlocked(Achievement),
+    /// The player was kicked, for the given reason. The server ignores the player after this message.
+    Kicked(String),
+    /// The name and description of the commands of the server
+    ServerCommands(Vec<(String, String)>),
+    /// The text output of a command sent by the player
+    CommandOutput(String),
+    /// The statistics of the player
+    Statistics(PlayerStatistics),
+    /// The answer to a `Ping`
+    Pong(u32),
+    /// The player's camera is teleported far away (true), or arrived (false).
+    /// The client hides the world until the chunks around the destination are loaded.
+    Teleporting(bool),
+    /// The player's camera moved to another dimension (id, name): the chunks of the previous one are dropped
+    EnterDimension(DimensionId, String),
+    /// What the player is allowed to do
+    Capabilities(PlayerCapabilities),
+    /// The claims of the dimension of the player
+    Claims(Vec<ClaimInfo>),
+    /// The health of the player and the state of their suit
+    Vitals(Vitals),
+    /// The content of the inventory of the player
+    Inventory(Inventory),
+    /// The player started using the selected item for the given number of seconds (Some), or stopped (None)
+    UsingItem(Option<f64>),
+    /// Play an animation of the character of a player once, for example an emote
+    PlayAnimation(PlayerId, String),
+    /// The flashlight of a player was turned on (true) or off (false)
+    Flashlight(PlayerId, bool),
+    /// A block is being mined and shows cracks (position, stage), or is intact again (stage 0).
+    /// See `BLOCK_DAMAGE_STAGES`.
+    BlockDamage(BlockPos, u8),
+    /// The trades of the supply terminal at some position, sent when the player interacts with it
+    Trades(BlockPos, Vec<Trade>),
+    /// The content hash of the game data, sent when the player joins and when the data is reloaded. The client loads
+    /// the data with this hash from its cache, or answers with a `RequestData`.
+    DataHash(u64),
+    /// Start sending the data pack of the game data, after a `RequestData`: the hash of the encoded pack and its size
+    /// in bytes. The pack follows in `DataPackChunk` messages, see `common::data::pack`.
+    DataPack(u64, usize),
+    /// The next bytes of the data pack being sent
+    DataPackChunk(Vec<u8>),
+    /// The version of the game, the data pack and the mods of the server, sent first when the player joins
+    Content(ContentManifest),
+    /// The vertical bounds of the dimension of the player, sent when the player joins and enters a dimension
+    WorldHeight(WorldHeight),
+    /// The content of the container that the player opened at some position, sent when it is opened and each time it
+    /// changes
+    Container(BlockPos, Inventory),
+    /// The container that the player opened was closed by the server, because it was destroyed or is too far away
+    CloseContainer,
+    /// An entity of the dimension of the player was hurt: the health it lost, or the energy for the robots, and the
+    /// direction the damage came from, None if it has no direction
+    EntityDamaged(Combatant, f64, Option<Vector3<f64>>),
+    /// The status effects of the player, sent each time one is applied, changes or wears off
+    StatusEffects(Vec<StatusEffect>),
+    /// The player set their respawn point on the respawn pod at this position
+    RespawnPointSet(BlockPos),
+    /// The number of players resting in a respawn pod and the number of connected players, sent when they change.
+    /// The night is skipped once all the players rest.
+    RestingPlayers(u32, u32),
+    /// A dust storm started (true) or ended (false), sent to the players on the surface with the announcement, so
+    /// that the client can play its sound
+    DustStorm(bool),
+    /// Open the editor of the sign at some position, with its current text, sent when the player placed the sign or
+    /// interacts with it
+    EditSign(BlockPos, String),
+    /// The texts of the signs of the dimension of the player, sent when the player joins, enters a dimension and when
+    /// a text changes
+    Signs(Vec<(BlockPos, String)>),
+    // The variants that can't be serialized must stay last: serde numbers the variants after a skipped one
+    // differently when serializing and deserializing.
+    /// Send the game data directly, only used by the replays
+    #[serde(skip)]
+    GameData(Data),
+    /// The server couldn't load the game data
+    #[serde(skip)]
+    DataErrors(DataErrorReport),
+}
+
+/// Estimate the size of a profile once encoded, in bytes
+fn profile_size(profile: &PlayerProfile) -> usize {
+    profile.name.len()
+        + match &profile.model {
+            CharacterModel::Builtin(name) => name.len(),
+            // The color of every voxel and whether it is full
+            CharacterModel::Custom(model) => model.total_voxels() * 5,
+        }
+}
+
+impl ToClient {
+    /// The name of the kind of message, used as the channel of the network statistics
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToClient::GameData(_) => "GameData",
+            ToClient::DataErrors(_) => "DataErrors",
+            ToClient::Chunk(_, _, _) => "Chunk",
+            ToClient::UpdatePhysics(_) => "UpdatePhysics",
+            ToClient::CurrentId(_) => "CurrentId",
+            ToClient::UpdateRobots(_) => "UpdateRobots",
+            ToClient::ControlRobot(_) => "ControlRobot",
+            ToClient::UpdateDroppedItems(_) => "UpdateDroppedItems",
+            ToClient::UpdateTime(_) => "UpdateTime",
+            ToClient::Explosion(_, _, _) => "Explosion",
+            ToClient::UpdateMeteors(_) => "UpdateMeteors",
+            ToClient::UpdateMobs(_) => "UpdateMobs",
+            ToClient::PlayerProfile(_, _) => "PlayerProfile",
+            ToClient::Achievements(_) => "Achievements",
+            ToClient::AchievementUnlocked(_) => "AchievementUnlocked",
+            ToClient::Kicked(_) => "Kicked",
+            ToClient::ServerCommands(_) => "ServerCommands",
+            ToClient::CommandOutput(_) => "CommandOutput",
+            ToClient::Statistics(_) => "Statistics",
+            ToClient::Pong(_) => "Pong",
+            ToClient::Teleporting(_) => "Teleporting",
+            ToClient::EnterDimension(_, _) => "EnterDimension",
+            ToClient::Capabilities(_) => "Capabilities",
+            ToClient::Claims(_) => "Claims",
+            ToClient::Vitals(_) => "Vitals",
+            ToClient::Inventory(_) => "Inventory",
+            ToClient::UsingItem(_) => "UsingItem",
+            ToClient::PlayAnimation(_, _) => "PlayAnimation",
+            ToClient::Flashlight(_, _) => "Flashlight",
+            ToClient::BlockDamage(_, _) => "BlockDamage",
+            ToClient::Trades(_, _) => "Trades",
+            ToClient::DataHash(_) => "DataHash",
+            ToClient::DataPack(_, _) => "DataPack",
+            ToClient::DataPackChunk(_) => "DataPackChunk",
+            ToClient::Content(_) => "Content",
+            ToClient::WorldHeight(_) => "WorldHeight",
+            ToClient::Container(_, _) => "Container",
+            ToClient::CloseContainer => "CloseContainer",
+            ToClient::EntityDamaged(_, _, _) => "EntityDamaged",
+            ToClient::StatusEffects(_) => "StatusEffects",
+            ToClient::RespawnPointSet(_) => "RespawnPointSet",
+            ToClient::RestingPlayers(_, _) => "RestingPlayers",
+            ToClient::DustStorm(_) => "DustStorm",
+            ToClient::EditSign(_, _) => "EditSign",
+            ToClient::Signs(_) => "Signs",
+        }
+    }
+
+    /// Estimate the size of the message once encoded, in bytes
+    pub fn size(&self) -> usize {
+        use std::mem::size_of;
+        let content = match self {
+            // Most of the game data is the block textures
+            ToClient::GameData(data) => data.textures.size_in_bytes(),
+            ToClient::Chunk(_, chunk, light_chunk) => {
+                chunk.data.len() * size_of::<BlockId>()
+                    + chunk.biomes.len() * size_of::<BiomeId>()
+                    + light_chunk.light.len()
+            }
+            ToClient::UpdatePhysics(state) => {
+                state.physics_state.players.len() * (size_of::<PlayerId>() + size_of::<PhysicsPlayer>() + size_of::<PlayerInput>())
+            }
+            ToClient::UpdateRobots(robots) => robots.len() * size_of::<RobotInfo>(),
+            ToClient::UpdateDroppedItems(items) => items.len() * size_of::<(Vector3<f64>, ItemStack)>(),
+            ToClient::Explosion(_, _, debris) => debris.len() * size_of::<ItemId>(),
+            ToClient::UpdateMeteors(meteors) => meteors.len() * size_of::<MeteorInfo>(),
+            ToClient::UpdateMobs(mobs) => mobs.iter().map(|mob| size_of::<MobInfo>() + mob.kind.len()).sum(),
+            ToClient::PlayerProfile(_, profile) => profile_size(profile),
+            ToClient::Achievements(achievements) => achievements.len() * size_of::<Achievement>(),
+            ToClient::Kicked(text)
+            | ToClient::CommandOutput(text)
+            | ToClient::EnterDimension(_, text)
+            | ToClient::PlayAnimation(_, text)
+            | ToClient::EditSign(_, text) => text.len(),
+            ToClient::Signs(signs) => signs.iter().map(|(_, text)| size_of::<BlockPos>() + text.len()).sum(),
+            ToClient::ServerCommands(commands) => commands.iter().map(|(name, description)| name.len() + description.len()).sum(),
+            ToClient::Claims(claims) => claims.iter().map(|claim| claim.name.len() + claim.owner.len() + 6 * 8).sum(),
+            ToClient::Inventory(inventory) | ToClient::Container(_, inventory) => {
+                inventory.size() * size_of::<Option<ItemStack>>()
+            }
+            ToClient::DataPackChunk(bytes) => bytes.len(),
+            ToClient::StatusEffects(effects) => effects.len() * size_of::<StatusEffect>(),
+            ToClient::Content(content) => content
+                .data_packs
+                .iter()
+                .chain(content.mods.iter())
+                .map(|info| info.name.len() + info.version.len())
+                .sum(),
+            ToClient::Statistics(statistics) => statistics.blocks_mined.keys().map(|name| name.len() + size_of::<u64>()).sum(),
+            ToClient::Trades(_, trades) => {
+                trades.iter().map(|trade| (trade.cost.len() + 1) * size_of::<ItemStack>()).sum()
+            }
+            _ => 0,
+        };
+        size_of::<Self>() + content
+    }
 }
@@ -1,5 +1,10 @@
 use crate::player::PlayerId;
+use stats::NetworkStats;
+pub mod limits;
 pub mod messages;
+pub mod replay;
+pub mod stats;
+pub mod status;
 
 #[derive(Debug, Clone)]
 pub enum ServerEvent {
@@ -21,11 +26,16 @@ pub trait Server {
     fn receive_event(&mut self) -> ServerEvent;
 
     fn send(&mut self, client: PlayerId, message: messages::ToClient);
+
+    /// The messages sent and received by the server, for all the clients
+    fn stats(&self) -> NetworkStats;
 }
 
 pub trait Client {
     fn receive_event(&mut self) -> ClientEvent;
     fn send(&mut self, _: messages::ToServer);
+
+    fn stats(&self) -> NetworkStats;
 }
 
 pub mod dummy;
\ No newline at end of file
@@ -0,0 +1,273 @@
+//! Replays of a game session: a `RecordingClient` saves the messages received from the server with the time they
+//! arrived, and a `ReplayClient` plays them back as if they came from the server. The playback is controlled with
+//! a `ReplayControl`: pause, speed and seeking.
+//!
+//! A replay file starts with `REPLAY_MAGIC` and the version of the format, followed by the gzip-compressed frames
+//! encoded with bincode. The game data is not recorded, it is loaded again when the replay is played.
+
+use super::limits::{decoding_options, MAX_DECODED_MESSAGE_SIZE};
+use super::messages::{ToClient, ToServer};
+use super::{stats::NetworkStats, Client, ClientEvent};
+use crate::data::Data;
+use anyhow::{bail, Context, Result};
+use bincode::Options;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+const REPLAY_MAGIC: &[u8; 4] = b"VXRP";
+/// Increased when the messages change, since the replays of another version can't be decoded
+const REPLAY_VERSION: u32 = 3;
+pub const MIN_REPLAY_SPEED: f64 = 0.125;
+pub const MAX_REPLAY_SPEED: f64 = 8.0;
+
+/// Write the messages received by a client to a replay file
+pub struct ReplayRecorder {
+    encoder: GzEncoder<BufWriter<File>>,
+    start: Instant,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create replay file {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+        file.write_all(REPLAY_MAGIC)?;
+        file.write_all(&REPLAY_VERSION.to_le_bytes())?;
+        Ok(Self {
+            encoder: GzEncoder::new(file, Compression::fast()),
+            start: Instant::now(),
+        })
+    }
+
+    /// Record a message received now. The content of the server, the game data, its hash and its data pack are
+    /// skipped.
+    pub fn record(&mut self, message: &ToClient) -> Result<()> {
+        if matches!(
+            message,
+            ToClient::GameData(_)
+                | ToClient::Content(_)
+                | ToClient::DataHash(_)
+                | ToClient::DataPack(_, _)
+                | ToClient::DataPackChunk(_)
+                | ToClient::DataErrors(_)
+        ) {
+            return Ok(());
+        }
+        let time = self.start.elapsed().as_secs_f64();
+        bincode::serialize_into(&mut self.encoder, &(time, message)).context("Failed to write replay frame")
+    }
+}
+
+/// A client that records the messages it receives in a replay file.
+/// The file is complete when the client is dropped, but the frames written before a crash can still be played.
+pub struct RecordingClient<C: Client> {
+    client: C,
+    /// None if the recording failed
+    recorder: Option<ReplayRecorder>,
+}
+
+impl<C: Client> RecordingClient<C> {
+    pub fn new(client: C, path: &Path) -> Result<Self> {
+        Ok(Self {
+            client,
+            recorder: Some(ReplayRecorder::create(path)?),
+        })
+    }
+}
+
+impl<C: Client> Client for RecordingClient<C> {
+    fn receive_event(&mut self) -> ClientEvent {
+        let event = self.client.receive_event();
+        if let (ClientEvent::ServerMessage(message), Some(recorder)) = (&event, self.recorder.as_mut()) {
+            if let Err(e) = recorder.record(message) {
+                // The game goes on without the recording
+                log::error!("Stopped recording the replay: {:#}", e);
+                self.recorder = None;
+            }
+        }
+        event
+    }
+
+    fn send(&mut self, message: ToServer) {
+        self.client.send(message);
+    }
+
+    fn stats(&self) -> NetworkStats {
+        self.client.stats()
+    }
+}
+
+/// The state of the playback, shared by a `ReplayClient` and its `ReplayControl`
+struct Playback {
+    /// Seconds since the start of the replay
+    position: f64,
+    duration: f64,
+    speed: f64,
+    paused: bool,
+    /// Whether the messages must be delivered again from the beginning, after seeking backward
+    restart: bool,
+}
+
+/// Control the playback of a `ReplayClient`
+#[derive(Clone)]
+pub struct ReplayControl {
+    playback: Arc<Mutex<Playback>>,
+}
+
+impl ReplayControl {
+    /// Seconds since the start of the replay
+    pub fn position(&self) -> f64 {
+        self.playback.lock().unwrap().position
+    }
+
+    /// Length of the replay, in seconds
+    pub fn duration(&self) -> f64 {
+        self.playback.lock().unwrap().duration
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.playback.lock().unwrap().speed
+    }
+
+    /// Set the speed of the playback, clamped to [`MIN_REPLAY_SPEED`; `MAX_REPLAY_SPEED`]
+    pub fn set_speed(&self, speed: f64) {
+        self.playback.lock().unwrap().speed = speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.playback.lock().unwrap().paused
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.playback.lock().unwrap().paused = paused;
+    }
+
+    /// Jump to a position of the replay, in seconds.
+    /// Return true if the replay restarts from the beginning to go backward: the state built from the previous
+    /// messages must be reset before the next event is received.
+    pub fn seek(&self, position: f64) -> bool {
+        let mut playback = self.playback.lock().unwrap();
+        let position = position.clamp(0.0, playback.duration);
+        let restart = position < playback.position;
+        playback.restart |= restart;
+        playback.position = position;
+        restart
+    }
+}
+
+/// A client that plays a replay back. The messages sent by the game are ignored.
+pub struct ReplayClient {
+    /// The messages of the replay, sorted by time
+    frames: Vec<(f64, ToClient)>,
+    next_frame: usize,
+    /// Sent to the game right after the connection, like the server does
+    data: Option<Data>,
+    first_queried: bool,
+    playback: Arc<Mutex<Playback>>,
+    last_update: Instant,
+    stats: NetworkStats,
+}
+
+impl ReplayClient {
+    /// Load a replay file. The game data is not part of the replay, so it must be given.
+    pub fn open(path: &Path, data: Data) -> Result<(Self, ReplayControl)> {
+        let frames = read_frames(path)?;
+        let playback = Arc::new(Mutex::new(Playback {
+            position: 0.0,
+            duration: frames.last().map(|(time, _)| *time).unwrap_or(0.0),
+            speed: 1.0,
+            paused: false,
+            restart: false,
+        }));
+        let control = ReplayControl {
+            playback: playback.clone(),
+        };
+        let client = Self {
+            frames,
+            next_frame: 0,
+            data: Some(data),
+            first_queried: true,
+            playback,
+            last_update: Instant::now(),
+            stats: NetworkStats::default(),
+        };
+        Ok((client, control))
+    }
+}
+
+/// Read all the frames of a replay file. A file that was cut short, for example by a crash, is read up to the cut.
+fn read_frames(path: &Path) -> Result<Vec<(f64, ToClient)>> {
+    let file = File::open(path).with_context(|| format!("Failed to open replay file {}", path.display()))?;
+    let mut file = BufReader::new(file);
+    let mut header = [0; 8];
+    file.read_exact(&mut header).context("Failed to read replay header")?;
+    if &header[0..4] != REPLAY_MAGIC {
+        bail!("{} is not a replay file", path.display());
+    }
+    let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if version != REPLAY_VERSION {
+        bail!("Unsupported replay version {}, expected version {}", version, REPLAY_VERSION);
+    }
+    let mut decoder = GzDecoder::new(file);
+    let mut frames = Vec::new();
+    loop {
+        match decoding_options(MAX_DECODED_MESSAGE_SIZE).deserialize_from::<_, (f64, ToClient)>(&mut decoder) {
+            Ok(frame) => frames.push(frame),
+            Err(e) => {
+                match *e {
+                    bincode::ErrorKind::Io(ref e) if e.kind() == ErrorKind::UnexpectedEof => (),
+                    _ => log::warn!("Replay {} is damaged, playing the first {} messages: {}", path.display(), frames.len(), e),
+                }
+                break;
+            }
+        }
+    }
+    Ok(frames)
+}
+
+impl Client for ReplayClient {
+    fn receive_event(&mut self) -> ClientEvent {
+        if self.first_queried {
+            self.first_queried = false;
+            return ClientEvent::Connected;
+        }
+        if let Some(data) = self.data.take() {
+            return ClientEvent::ServerMessage(ToClient::GameData(data));
+        }
+
+        let now = Instant::now();
+        let mut playback = self.playback.lock().unwrap();
+        if playback.restart {
+            playback.restart = false;
+            self.next_frame = 0;
+        }
+        if !playback.paused {
+            let elapsed = (now - self.last_update).as_secs_f64();
+            playback.position = (playback.position + elapsed * playback.speed).min(playback.duration);
+        }
+        self.last_update = now;
+
+        match self.frames.get(self.next_frame) {
+            Some((time, message)) if *time <= playback.position => {
+                self.next_frame += 1;
+                let mut message = message.clone();
+                // The client compares the time of the physics states with the time of its own inputs
+                if let ToClient::UpdatePhysics(state) = &mut message {
+                    state.server_time = now;
+                }
+                self.stats.record_received(message.name(), message.size());
+                ClientEvent::ServerMessage(message)
+            }
+            _ => ClientEvent::NoEvent,
+        }
+    }
+
+    fn send(&mut self, _message: ToServer) {}
+
+    fn stats(&self) -> NetworkStats {
+        self.stats.clone()
+    }
+}
@@ -0,0 +1,82 @@
+//! Statistics of the messages sent and received by a network backend
+
+use std::collections::BTreeMap;
+
+/// The number of messages and bytes of one channel
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelStats {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl ChannelStats {
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+}
+
+/// The messages sent and received since the connection started.
+/// The channels are the kinds of messages, such as `Chunk` or `UpdateInput`.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkStats {
+    pub sent: BTreeMap<&'static str, ChannelStats>,
+    pub received: BTreeMap<&'static str, ChannelStats>,
+}
+
+impl NetworkStats {
+    pub fn record_sent(&mut self, channel: &'static str, bytes: usize) {
+        self.sent.entry(channel).or_default().add(bytes);
+    }
+
+    pub fn record_received(&mut self, channel: &'static str, bytes: usize) {
+        self.received.entry(channel).or_default().add(bytes);
+    }
+
+    pub fn total_sent(&self) -> ChannelStats {
+        total(&self.sent)
+    }
+
+    pub fn total_received(&self) -> ChannelStats {
+        total(&self.received)
+    }
+
+    /// Describe the bandwidth used since an older snapshot of the statistics, taken `seconds` ago
+    pub fn bandwidth_summary(&self, previous: &NetworkStats, seconds: f64) -> String {
+        let rate = |current: ChannelStats, previous: ChannelStats| {
+            let seconds = seconds.max(1e-3);
+            (
+                (current.bytes - previous.bytes) as f64 / 1000.0 / seconds,
+                (current.messages - previous.messages) as f64 / seconds,
+            )
+        };
+        let (up_kbps, up_mps) = rate(self.total_sent(), previous.total_sent());
+        let (down_kbps, down_mps) = rate(self.total_received(), previous.total_received());
+        let mut summary = format!(
+            "up: {:.1} kB/s, {:.0} msg/s\ndown: {:.1} kB/s, {:.0} msg/s",
+            up_kbps, up_mps, down_kbps, down_mps
+        );
+        // The busiest received channels
+        let mut channels = self
+            .received
+            .iter()
+            .map(|(channel, stats)| {
+                let previous = previous.received.get(channel).copied().unwrap_or_default();
+                (*channel, rate(*stats, previous).0)
+            })
+            .filter(|(_, kbps)| *kbps > 0.0)
+            .collect::<Vec<_>>();
+        channels.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        for (channel, kbps) in channels.into_iter().take(5) {
+            summary.push_str(&format!("\n  {}: {:.1} kB/s", channel, kbps));
+        }
+        summary
+    }
+}
+
+fn total(channels: &BTreeMap<&'static str, ChannelStats>) -> ChannelStats {
+    channels.values().fold(ChannelStats::default(), |total, stats| ChannelStats {
+        messages: total.messages + stats.messages,
+        bytes: total.bytes + stats.bytes,
+    })
+}
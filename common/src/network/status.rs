@@ -0,0 +1,41 @@
+//! Status protocol: the server list of the clients asks the servers for their status without joining.
+//!
+//! The client sends the line `status`, and the server answers with one line containing the `ServerStatus` in RON,
+//! then closes the connection.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+pub const STATUS_REQUEST: &str = "status";
+
+/// What a server tells about itself to the server lists
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub players: usize,
+    pub max_players: usize,
+    /// Message of the day
+    pub motd: String,
+    pub version: String,
+}
+
+/// Ask a server for its status. Return the status and the round-trip time of the request.
+pub fn query_status(address: &str, timeout: Duration) -> Result<(ServerStatus, Duration)> {
+    let socket_address = address
+        .to_socket_addrs()
+        .with_context(|| format!("Invalid address {}", address))?
+        .next()
+        .with_context(|| format!("No address found for {}", address))?;
+    let mut stream = TcpStream::connect_timeout(&socket_address, timeout).context("Failed to connect")?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let start = Instant::now();
+    writeln!(stream, "{}", STATUS_REQUEST).context("Failed to send the request")?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("Failed to receive the status")?;
+    let ping = start.elapsed();
+    let status = ron::de::from_str(response.trim()).context("Failed to parse the status")?;
+    Ok((status, ping))
+}
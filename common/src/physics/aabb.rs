@@ -1,7 +1,8 @@
 use super::BlockContainer;
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AABB {
     pub pos: Vector3<f64>,
     pub size_x: f64,
@@ -182,6 +183,32 @@ impl AABB {
         return res;
     }
 
+    /// Return the distance from `origin` to the box along the normalized direction `dir`, 0 if `origin` is inside
+    /// the box, or None if the ray misses it
+    pub fn intersect_ray(&self, origin: Vector3<f64>, dir: Vector3<f64>) -> Option<f64> {
+        let min = self.pos;
+        let max = self.pos + Vector3::new(self.size_x, self.size_y, self.size_z);
+        let mut t_min = 0.0f64;
+        let mut t_max = f64::INFINITY;
+        for i in 0..3 {
+            if dir[i].abs() < 1e-9 {
+                if origin[i] < min[i] || origin[i] > max[i] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (min[i] - origin[i]) / dir[i];
+            let t2 = (max[i] - origin[i]) / dir[i];
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+        if t_min <= t_max {
+            Some(t_min)
+        } else {
+            None
+        }
+    }
+
     /// Check whether the bounding box is touching the ground
     pub fn is_on_the_ground<BC: BlockContainer>(&mut self, world: &BC) -> bool {
         self.pos.y -= 0.0021;
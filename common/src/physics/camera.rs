@@ -3,7 +3,9 @@
 //! A `Camera` defines how a player's entity reacts to that player's inputs.
 
 use crate::{
-    debug::send_debug_info, physics::player::PhysicsPlayer, player::PlayerInput,
+    debug::send_debug_info,
+    physics::player::{PhysicsPlayer, KNOCKBACK_DECAY},
+    player::PlayerInput,
 };
 use super::BlockContainer;
 use nalgebra::Vector3;
@@ -32,7 +34,7 @@ pub fn default_camera<BC: BlockContainer>(
     // Compute the expected movement of the player, i.e. assuming there are no collisions.
     if input.flying || player.aabb.intersect_world(world) {
         const ACCELERATION: f64 = 50.0;
-        const MAX_SPEED: f64 = 30.0;
+        let max_speed = 30.0 * player.speed_multiplier;
         player.velocity.y = 0.0;
         // If the player is flying, then we update its velocity. By default, it falls off to 0
         let mut player_acceleration = Vector3::zeros();
@@ -53,15 +55,15 @@ pub fn default_camera<BC: BlockContainer>(
         let player_acceleration =
             (player_acceleration * 1.5 + auto_acceleration * 0.5) * ACCELERATION;
         player.velocity += player_acceleration * seconds_delta;
-        if player.velocity.norm() > MAX_SPEED {
-            player.velocity *= MAX_SPEED / player.velocity.norm();
+        if player.velocity.norm() > max_speed {
+            player.velocity *= max_speed / player.velocity.norm();
         }
-        let mut expected_movement = player.velocity * seconds_delta;
+        let mut expected_movement = (player.velocity + player.knockback) * seconds_delta;
         if input.key_move_up {
-            expected_movement.y += (seconds_delta * MAX_SPEED) as f64;
+            expected_movement.y += seconds_delta * max_speed;
         }
         if input.key_move_down {
-            expected_movement.y -= (seconds_delta * MAX_SPEED) as f64;
+            expected_movement.y -= seconds_delta * max_speed;
         }
         player.aabb.move_check_collision(world, expected_movement);
     } else {
@@ -84,7 +86,7 @@ pub fn default_camera<BC: BlockContainer>(
         if input.key_move_right {
             horizontal_velocity += movement_direction(input.yaw, 270.0);
         }
-        let horizontal_velocity = normalize_or_zero(horizontal_velocity) * HORIZONTAL_SPEED;
+        let horizontal_velocity = normalize_or_zero(horizontal_velocity) * HORIZONTAL_SPEED * player.speed_multiplier;
         if player.aabb.is_on_the_ground(world) {
             player.velocity.y = if input.key_move_up { JUMP_SPEED } else { 0.0 };
         } else {
@@ -93,9 +95,10 @@ pub fn default_camera<BC: BlockContainer>(
                 player.velocity.y = -MAX_DOWN_SPEED;
             }
         };
-        let expected_movement = (player.velocity + horizontal_velocity) * seconds_delta;
+        let expected_movement = (player.velocity + horizontal_velocity + player.knockback) * seconds_delta;
         player.aabb.move_check_collision(world, expected_movement);
     }
+    player.knockback *= f64::max(1.0 - KNOCKBACK_DECAY * seconds_delta, 0.0);
     // TODO: add a noclip camera mode
     send_debug_info(
         "Physics",
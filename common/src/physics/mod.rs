@@ -2,9 +2,15 @@ use crate::world::BlockPos;
 
 pub mod simulation;
 pub mod aabb;
-mod camera;
+pub mod camera;
 pub mod player;
 
 pub trait BlockContainer {
+    /// Return true if the block at `pos` stops movement
     fn is_block_full(&self, pos: BlockPos) -> bool;
+
+    /// Return true if the block at `pos` can be pointed at. Some blocks, such as open doors, can be pointed at without being full.
+    fn is_block_targetable(&self, pos: BlockPos) -> bool {
+        self.is_block_full(pos)
+    }
 }
\ No newline at end of file
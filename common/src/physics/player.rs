@@ -2,18 +2,25 @@ use crate::physics::aabb::AABB;
 use crate::world::BlockPos;
 use super::BlockContainer;
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
 
 const PLAYER_SIDE: f64 = 0.8;
 const PLAYER_HEIGHT: f64 = 1.8;
 const CAMERA_OFFSET: [f64; 3] = [0.4, 1.6, 0.4];
+/// Fraction of the knockback lost every second
+pub const KNOCKBACK_DECAY: f64 = 6.0;
 
 /// The physics representation of a player
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhysicsPlayer {
     /// The aabb of the player
     pub aabb: AABB,
     /// The current velocity of the player
     pub velocity: Vector3<f64>,
+    /// The velocity given by the hits, which fades quickly
+    pub knockback: Vector3<f64>,
+    /// Multiplier of the speed of the player, lowered by the status effects that slow them down
+    pub speed_multiplier: f64,
 }
 
 impl PhysicsPlayer {
@@ -78,7 +85,7 @@ impl PhysicsPlayer {
                 max_dist -= curr_min;
                 pos += curr_min * dir;
                 let block_pos = BlockPos::from(pos);
-                if world.is_block_full(block_pos) {
+                if world.is_block_targetable(block_pos) {
                     return Some((block_pos, face));
                 }
             }
@@ -94,6 +101,8 @@ impl Default for PhysicsPlayer {
                 (PLAYER_SIDE, PLAYER_HEIGHT, PLAYER_SIDE),
             ),
             velocity: Vector3::zeros(),
+            knockback: Vector3::zeros(),
+            speed_multiplier: 1.0,
         }
     }
 }
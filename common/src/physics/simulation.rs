@@ -1,23 +1,25 @@
 use crate::{
+    collections::RingBuffer,
     physics::camera::default_camera,
     physics::player::PhysicsPlayer,
     physics::BlockContainer,
     player::{PlayerId, PlayerInput},
 };
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     time::{Duration, Instant},
 };
 
 /// Input of the whole simulation.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Input {
     pub(self) player_inputs: HashMap<PlayerId, PlayerInput>,
 }
 
 /// Physics state of the whole simulation.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PhysicsState {
     pub players: HashMap<PlayerId, PhysicsPlayer>,
 }
@@ -26,10 +28,20 @@ impl PhysicsState {
     /// Step the full physics simulation.
     /// For now, it just moves all connected players.
     pub fn step_simulation<BC: BlockContainer>(&mut self, input: &Input, dt: Duration, world: &BC) {
+        self.step_simulation_in(input, dt, |_| world);
+    }
+
+    /// Step the full physics simulation, moving each player in the world returned by `world_of`
+    pub fn step_simulation_in<'a, BC: BlockContainer + 'a>(
+        &mut self,
+        input: &Input,
+        dt: Duration,
+        world_of: impl Fn(PlayerId) -> &'a BC,
+    ) {
         let seconds_delta = dt.as_secs_f64();
         for (&id, input) in input.player_inputs.iter() {
             let player = self.players.entry(id).or_insert(Default::default());
-            default_camera(player, *input, seconds_delta, world);
+            default_camera(player, *input, seconds_delta, world_of(id));
         }
         // Remove players that don't exist anymore
         self.players
@@ -38,17 +50,31 @@ impl PhysicsState {
 }
 
 /// A physics state sent by the server.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerState {
     pub physics_state: PhysicsState,
+    /// Only meaningful in the process of the server, a deserialized state is from the time it was deserialized
+    #[serde(skip, default = "Instant::now")]
     pub server_time: Instant,
     pub input: Input,
 }
 
+impl ServerState {
+    /// Keep only the players for which `keep` returns true, for example the players in the dimension of a client
+    pub fn retain_players(&mut self, mut keep: impl FnMut(PlayerId) -> bool) {
+        self.physics_state.players.retain(|&id, _| keep(id));
+        self.input.player_inputs.retain(|&id, _| keep(id));
+    }
+}
+
+/// Maximum number of client inputs waiting for the confirmation of the server. With more inputs, the oldest ones are
+/// dropped and the prediction drifts until the next server update.
+const MAX_PREDICTED_INPUTS: usize = 1024;
+
 /// The client's physics simulation
 pub struct ClientPhysicsSimulation {
-    /// Previous client inputs
-    client_inputs: Vec<(Instant, PlayerInput)>,
+    /// Previous client inputs, from the oldest to the newest
+    client_inputs: RingBuffer<(Instant, PlayerInput)>,
     /// Last state validated by the server
     last_server_state: ServerState,
     /// Current simulation state
@@ -63,7 +89,7 @@ impl ClientPhysicsSimulation {
     /// Create a new simulation from some `ServerState` and the client's id
     pub fn new(server_state: ServerState, player_id: PlayerId) -> Self {
         Self {
-            client_inputs: Vec::new(),
+            client_inputs: RingBuffer::new(MAX_PREDICTED_INPUTS),
             last_server_state: server_state.clone(),
             current_state: server_state.physics_state,
             needs_recomputing: false,
@@ -78,7 +104,7 @@ impl ClientPhysicsSimulation {
         // Drop inputs anterior to this server state
         let last_server_time = self.last_server_state.server_time;
         self.client_inputs
-            .retain(|(time, _)| *time > last_server_time);
+            .drop_oldest_while(|(time, _)| *time <= last_server_time);
         // Mark dirty
         self.needs_recomputing = true;
     }
@@ -97,6 +123,25 @@ impl ClientPhysicsSimulation {
         self.current_state.players.get(&self.player_id).unwrap()
     }
 
+    /// Get the current state of all the players
+    pub fn get_state(&self) -> &PhysicsState {
+        &self.current_state
+    }
+
+    /// Get the last known input of some player
+    pub fn get_player_input(&self, player_id: PlayerId) -> Option<PlayerInput> {
+        self.last_server_state.input.player_inputs.get(&player_id).copied()
+    }
+
+    /// Drop the predicted inputs and show the last server state as if it was received at `time`.
+    /// Used to stop the simulation, for example while a replay is paused.
+    pub fn freeze(&mut self, time: Instant) {
+        self.client_inputs.clear();
+        self.last_server_state.server_time = time;
+        self.current_state = self.last_server_state.physics_state.clone();
+        self.needs_recomputing = false;
+    }
+
     /// Step the simulation according to the current input and time
     pub fn step_simulation<BC: BlockContainer>(&mut self, input: PlayerInput, time: Instant, world: &BC) {
         // Recompute simulation if necessary
@@ -121,7 +166,7 @@ impl ClientPhysicsSimulation {
             }
         }
 
-        let previous_instant = match self.client_inputs.last() {
+        let previous_instant = match self.client_inputs.newest() {
             Some((time, _)) => *time,
             None => self.last_server_state.server_time,
         };
@@ -168,17 +213,51 @@ impl ServerPhysicsSimulation {
             .insert(player_id, input);
     }
 
+    /// Get the last input of some player
+    pub fn get_player_input(&self, player_id: PlayerId) -> Option<PlayerInput> {
+        self.server_state.input.player_inputs.get(&player_id).copied()
+    }
+
+    /// Move a player back to a position, and stop it
+    pub fn set_player_position(&mut self, player_id: PlayerId, pos: Vector3<f64>) {
+        if let Some(player) = self.server_state.physics_state.players.get_mut(&player_id) {
+            player.aabb.pos = pos;
+            player.velocity = Vector3::zeros();
+            player.knockback = Vector3::zeros();
+        }
+    }
+
+    /// Push a player, for example when they are hit
+    pub fn apply_knockback(&mut self, player_id: PlayerId, impulse: Vector3<f64>) {
+        if let Some(player) = self.server_state.physics_state.players.get_mut(&player_id) {
+            player.knockback += impulse;
+        }
+    }
+
+    /// Change how fast a player moves, for example when a status effect slows them down
+    pub fn set_speed_multiplier(&mut self, player_id: PlayerId, speed_multiplier: f64) {
+        if let Some(player) = self.server_state.physics_state.players.get_mut(&player_id) {
+            player.speed_multiplier = speed_multiplier;
+        }
+    }
+
     /// Remove a player from the simulation
     pub fn remove(&mut self, player_id: PlayerId) {
         self.server_state.input.player_inputs.remove(&player_id);
     }
 
-    /// Step the simulation according to the current input and time
-    pub fn step_simulation<BC: BlockContainer>(&mut self, time: Instant, world: &BC) {
-        self.server_state.physics_state.step_simulation(
+    /// Step the simulation by one tick of duration `dt`, happening at `time`.
+    /// Each player moves in the world returned by `world_of`: the world of their dimension.
+    pub fn step_simulation<'a, BC: BlockContainer + 'a>(
+        &mut self,
+        time: Instant,
+        dt: Duration,
+        world_of: impl Fn(PlayerId) -> &'a BC,
+    ) {
+        self.server_state.physics_state.step_simulation_in(
             &self.server_state.input,
-            time - self.server_state.server_time,
-            world,
+            dt,
+            world_of,
         );
         self.server_state.server_time = time;
     }
@@ -1,8 +1,22 @@
+use crate::data::vox::VoxelModel;
 use crate::world::ChunkPos;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+/// The character model of the players who didn't choose one
+pub const DEFAULT_CHARACTER_MODEL: &str = "knight";
+/// Maximum number of voxels of a custom character model, its parts included
+pub const MAX_CUSTOM_MODEL_VOXELS: usize = 64 * 64 * 64;
+/// Maximum length of a player name
+pub const MAX_PLAYER_NAME_LENGTH: usize = 32;
+/// Number of slots of the inventory of a player
+pub const PLAYER_INVENTORY_SLOTS: usize = 9;
+/// Maximum value of each of the vitals of a player
+pub const MAX_VITAL: f64 = 100.0;
+/// Distance from the eyes of a player, or from the center of a robot, within which they can hit
+pub const MELEE_REACH: f64 = 3.5;
 
 /// The input of a player
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PlayerInput {
     pub key_move_forward: bool,
     pub key_move_left: bool,
@@ -31,12 +45,125 @@ impl Default for PlayerInput {
     }
 }
 
+impl PlayerInput {
+    /// The same input, without any movement
+    pub fn without_movement(self) -> Self {
+        Self {
+            key_move_forward: false,
+            key_move_left: false,
+            key_move_backward: false,
+            key_move_right: false,
+            key_move_up: false,
+            key_move_down: false,
+            ..self
+        }
+    }
+}
+
+/// What a player is allowed to do, decided by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerCapabilities {
+    pub can_fly: bool,
+    /// Break and place blocks, and give mining areas to the robots
+    pub can_build: bool,
+}
+
+impl Default for PlayerCapabilities {
+    fn default() -> Self {
+        Self {
+            can_fly: true,
+            can_build: true,
+        }
+    }
+}
+
+/// The health of a player, and the oxygen and the energy of their suit, from 0 to `MAX_VITAL`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vitals {
+    pub health: f64,
+    pub oxygen: f64,
+    pub energy: f64,
+}
+
+impl Default for Vitals {
+    fn default() -> Self {
+        Self {
+            health: MAX_VITAL,
+            oxygen: MAX_VITAL,
+            energy: MAX_VITAL,
+        }
+    }
+}
+
+impl Vitals {
+    /// Add some amounts to the vitals, up to the maximum
+    pub fn restore(&mut self, health: f64, oxygen: f64, energy: f64) {
+        self.health = (self.health + health).min(MAX_VITAL);
+        self.oxygen = (self.oxygen + oxygen).min(MAX_VITAL);
+        self.energy = (self.energy + energy).min(MAX_VITAL);
+    }
+
+    /// The vitals rounded to whole numbers, as shown to the player
+    pub fn rounded(self) -> Self {
+        Self {
+            health: self.health.round(),
+            oxygen: self.oxygen.round(),
+            energy: self.energy.round(),
+        }
+    }
+}
+
 /// Some unique player id.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PlayerId(pub(crate) u16);
 
+impl PlayerId {
+    pub fn from_raw(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    pub fn raw(self) -> u16 {
+        self.0
+    }
+}
+
+/// The model of a player's character
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CharacterModel {
+    /// One of the character models of the game data
+    Builtin(String),
+    /// A model supplied by the player
+    Custom(VoxelModel),
+}
+
+/// How a player appears to the other players
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    pub name: String,
+    pub model: CharacterModel,
+}
+
+impl PlayerProfile {
+    /// Fix the parts of the profile that are not allowed: a name that is too long,
+    /// or a model that doesn't exist or is too large is replaced by the default model.
+    pub fn sanitized(self, character_models: &[String]) -> Self {
+        let name = self.name.trim().chars().take(MAX_PLAYER_NAME_LENGTH).collect();
+        let is_valid = match &self.model {
+            CharacterModel::Builtin(model) => character_models.contains(model),
+            CharacterModel::Custom(model) => model.is_well_formed() && model.total_voxels() <= MAX_CUSTOM_MODEL_VOXELS,
+        };
+        let model = if is_valid {
+            self.model
+        } else {
+            CharacterModel::Builtin(DEFAULT_CHARACTER_MODEL.to_owned())
+        };
+        Self { name, model }
+    }
+}
+
 /// The render distance of a player
-#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RenderDistance {
     pub x_max: u64,
     pub x_min: u64,
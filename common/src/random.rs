@@ -0,0 +1,23 @@
+//! Seeded pseudo-random numbers, for the gameplay that must play out the same way for the same seed
+
+use crate::worldgen::perlin::hash;
+
+/// A sequence of pseudo-random numbers, the same for the same seed. Any seed can be used, including 0.
+#[derive(Debug, Clone)]
+pub struct Random {
+    seed: i32,
+    counter: i32,
+}
+
+impl Random {
+    pub fn new(seed: i32) -> Self {
+        Self { seed, counter: 0 }
+    }
+
+    /// Return the next pseudo-random number, in [0; 1)
+    pub fn next_f64(&mut self) -> f64 {
+        self.counter = self.counter.wrapping_add(1);
+        let r = hash(hash(self.counter).wrapping_add(self.seed));
+        r as u32 as f64 / (u32::MAX as f64 + 1.0)
+    }
+}
@@ -1,4 +1,19 @@
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Namespace of the keys registered without one
+pub const DEFAULT_NAMESPACE: &str = "core";
+
+/// Add the default namespace to a key that doesn't have one: `stone` becomes `core:stone`.
+/// Keys that already contain a `:` are left untouched.
+pub fn namespaced_key(key: &str) -> String {
+    if key.contains(':') {
+        key.to_owned()
+    } else {
+        format!("{}:{}", DEFAULT_NAMESPACE, key)
+    }
+}
 
 #[derive(Debug)]
 pub enum RegistryError {
@@ -17,6 +32,62 @@ impl std::fmt::Display for RegistryError {
 
 impl std::error::Error for RegistryError {}
 
+/// Typed id of a value registered in a `Registry<T>`
+pub struct Id<T> {
+    raw: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Id<T> {
+    /// Wrap a raw id, which should come from a `Registry<T>`
+    pub fn from_raw(raw: u32) -> Self {
+        Self {
+            raw,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn raw(self) -> u32 {
+        self.raw
+    }
+}
+
+// Manual impls: the derives would require `T` to implement the traits as well
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> std::hash::Hash for Id<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.raw.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Id({})", self.raw)
+    }
+}
+
+/// The keys of a registry in id order, saved with the world so that the ids stored in the save can be
+/// translated when the data packs change
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdMap {
+    pub keys: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Registry<T> {
     name_to_id: HashMap<String, u32>,
@@ -25,7 +96,9 @@ pub struct Registry<T> {
 }
 
 impl<T> Registry<T> {
+    /// Register a value. The name is stored with its namespace, see `namespaced_key`.
     pub fn register(&mut self, name: String, value: T) -> Result<u32, RegistryError> {
+        let name = namespaced_key(&name);
         if self.name_to_id.contains_key(&name) {
             Err(RegistryError::KayAlreadyExists { key: name })
         } else {
@@ -37,8 +110,23 @@ impl<T> Registry<T> {
         }
     }
 
+    /// Find the id of a name, with or without the default namespace
     pub fn get_id_by_name(&self, name: &String) -> Option<u32> {
-        self.name_to_id.get(name).cloned()
+        self.name_to_id.get(&namespaced_key(name)).cloned()
+    }
+
+    pub fn get_handle(&self, name: &str) -> Option<Id<T>> {
+        self.name_to_id.get(&namespaced_key(name)).map(|&id| Id::from_raw(id))
+    }
+
+    /// Get the namespaced name of an id
+    pub fn get_name_by_id(&self, id: u32) -> Option<&str> {
+        self.id_to_name.get(id as usize).map(String::as_str)
+    }
+
+    /// Replace the value of an id. Panics if the id is not registered.
+    pub fn set_value(&mut self, id: u32, value: T) {
+        self.id_to_value[id as usize] = value;
     }
 
     pub fn get_number_of_ids(&self) -> u32 {
@@ -51,6 +139,58 @@ impl<T> Registry<T> {
         }
         return None;
     }
+
+    /// Get the value of a handle. Panics if the handle comes from another registry with fewer values.
+    pub fn get(&self, handle: Id<T>) -> &T {
+        &self.id_to_value[handle.raw as usize]
+    }
+
+    /// Iterate over the entries in id order
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &str, &T)> {
+        self.id_to_name
+            .iter()
+            .zip(self.id_to_value.iter())
+            .enumerate()
+            .map(|(id, (name, value))| (Id::from_raw(id as u32), name.as_str(), value))
+    }
+
+    pub fn id_map(&self) -> IdMap {
+        IdMap {
+            keys: self.id_to_name.clone(),
+        }
+    }
+
+    /// For each id of the saved map, the current id with the same key, if it still exists
+    pub fn translation_from(&self, saved: &IdMap) -> Vec<Option<u32>> {
+        saved
+            .keys
+            .iter()
+            .map(|key| self.name_to_id.get(&namespaced_key(key)).cloned())
+            .collect()
+    }
+}
+
+/// The registries are stored as their names and values in id order, the map from the names to the ids is rebuilt when
+/// they are loaded
+impl<T: Serialize> Serialize for Registry<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.id_to_name, &self.id_to_value).serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Registry<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (id_to_name, id_to_value): (Vec<String>, Vec<T>) = Deserialize::deserialize(deserializer)?;
+        if id_to_name.len() != id_to_value.len() {
+            return Err(serde::de::Error::custom("the registry doesn't have as many names as values"));
+        }
+        let name_to_id = id_to_name.iter().enumerate().map(|(id, name)| (name.clone(), id as u32)).collect();
+        Ok(Self {
+            name_to_id,
+            id_to_name,
+            id_to_value,
+        })
+    }
 }
 
 impl<T> Default for Registry<T> {
@@ -61,4 +201,4 @@ impl<T> Default for Registry<T> {
             id_to_value: Vec::new(),
         }
     }
-}
\ No newline at end of file
+}
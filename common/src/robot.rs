@@ -0,0 +1,68 @@
+use crate::physics::aabb::AABB;
+use crate::player::PlayerId;
+use crate::world::BlockPos;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Side of the cubic box of a robot
+pub const ROBOT_SIDE: f64 = 0.8;
+/// Maximum number of blocks in a mining area
+pub const MAX_AREA_VOLUME: i64 = 64 * 64 * 64;
+
+/// Number of blocks in the box between two corners, both included, or None if it is too large to be counted
+pub fn area_volume(a: BlockPos, b: BlockPos) -> Option<i64> {
+    let side = |a: i64, b: i64| a.max(b).checked_sub(a.min(b))?.checked_add(1);
+    side(a.px, b.px)?.checked_mul(side(a.py, b.py)?)?.checked_mul(side(a.pz, b.pz)?)
+}
+
+/// Some unique robot id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RobotId(pub u32);
+
+/// Why a robot stopped working
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PauseReason {
+    /// There is no storage crate next to the area
+    NoStorage,
+    /// The storage crate is full
+    StorageFull,
+    /// The robot is recharging
+    OutOfEnergy,
+}
+
+/// What a robot is currently doing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RobotState {
+    /// Nothing to do
+    Idle,
+    /// Mining the assigned area
+    Mining { progress: f64 },
+    /// Going back to the storage crate to empty the cargo
+    Returning,
+    Paused(PauseReason),
+}
+
+/// What the clients know about a robot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RobotInfo {
+    pub id: RobotId,
+    pub owner: PlayerId,
+    /// Position of the center of the robot
+    pub pos: Vector3<f64>,
+    pub state: RobotState,
+    pub energy: f64,
+    /// The player controlling the robot, if any
+    pub controller: Option<PlayerId>,
+    /// Direction the robot faces, in degrees like the yaw of the players
+    pub yaw: f64,
+    /// Whether the headlights of the robot are on
+    pub headlights: bool,
+}
+
+impl RobotInfo {
+    /// The box of the robot, centered on its position
+    pub fn aabb(&self) -> AABB {
+        let half_side = Vector3::new(ROBOT_SIDE, ROBOT_SIDE, ROBOT_SIDE) / 2.0;
+        AABB::new(self.pos - half_side, (ROBOT_SIDE, ROBOT_SIDE, ROBOT_SIDE))
+    }
+}
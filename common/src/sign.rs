@@ -0,0 +1,20 @@
+//! Signs: blocks showing a text written by the players. The server stores the text of each sign, and sends the texts
+//! of the signs of their dimension to the players, who see them above the signs.
+
+/// The block that shows a text
+pub const SIGN_BLOCK: &str = "sign";
+/// Maximum length of the text of a sign, in bytes
+pub const MAX_SIGN_TEXT_LENGTH: usize = 120;
+
+/// Clean up the text of a sign: the control characters, such as the line breaks, become spaces, the spaces at the
+/// ends are removed, and the text is cut to `MAX_SIGN_TEXT_LENGTH`
+pub fn clean_sign_text(text: &str) -> String {
+    let mut cleaned = String::new();
+    for c in text.trim().chars().map(|c| if c.is_control() { ' ' } else { c }) {
+        if cleaned.len() + c.len_utf8() > MAX_SIGN_TEXT_LENGTH {
+            break;
+        }
+        cleaned.push(c);
+    }
+    cleaned.trim_end().to_owned()
+}
@@ -0,0 +1,41 @@
+//! Statistics of the players, kept by the server
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStatistics {
+    /// The number of blocks mined, by block name
+    pub blocks_mined: BTreeMap<String, u64>,
+    pub blocks_placed: u64,
+    /// Distance walked, in blocks
+    pub distance_walked: f64,
+    /// Distance flown, in blocks
+    pub distance_flown: f64,
+    pub deaths: u64,
+    /// Time spent in the world, in seconds
+    pub playtime: f64,
+}
+
+impl PlayerStatistics {
+    /// The statistics as text, one line per statistic
+    pub fn summary(&self) -> Vec<String> {
+        let total_mined: u64 = self.blocks_mined.values().sum();
+        let playtime = self.playtime as u64;
+        let mut lines = vec![
+            format!("Playtime: {}h {:02}m {:02}s", playtime / 3600, playtime / 60 % 60, playtime % 60),
+            format!("Distance walked: {:.0} blocks", self.distance_walked),
+            format!("Distance flown: {:.0} blocks", self.distance_flown),
+            format!("Deaths: {}", self.deaths),
+            format!("Blocks placed: {}", self.blocks_placed),
+            format!("Blocks mined: {}", total_mined),
+        ];
+        // Most mined first
+        let mut blocks_mined = self.blocks_mined.iter().collect::<Vec<_>>();
+        blocks_mined.sort_by(|a, b| b.1.cmp(a.1));
+        for (block, count) in blocks_mined {
+            lines.push(format!("  {}: {}", block, count));
+        }
+        lines
+    }
+}
@@ -0,0 +1,93 @@
+//! Themes of the interface: the colors, the paddings and the font sizes of the menus and of the HUD. The themes are
+//! defined in the `themes` folder of the data, so that the data packs can reskin the interface. The widgets of the
+//! client belong to style classes, such as `button` or `panel`, and a theme only needs to set the properties it
+//! changes: the other ones keep the value of the built-in theme.
+
+use crate::data::DataFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The theme used when the theme chosen in the settings doesn't exist
+pub const DEFAULT_THEME: &str = "default";
+
+/// The built-in theme, also the `default` theme of the embedded data
+const BUILTIN_THEME: &str = include_str!("../default_data/themes/default.toml");
+
+/// The style of a class of widgets. The meaning of each property depends on the class, for example `accent` is the
+/// filled part of the sliders and the titles of the panels.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    /// The color of the text
+    pub color: Option<[f32; 4]>,
+    pub background: Option<[f32; 4]>,
+    /// The background under the mouse
+    pub hovered: Option<[f32; 4]>,
+    pub accent: Option<[f32; 4]>,
+    /// Space between the border and the content, in pixels
+    pub padding: Option<i32>,
+    /// Size of the font, in pixels
+    pub font_size: Option<f32>,
+}
+
+impl Style {
+    /// The properties of this style, and the properties of `fallback` for the ones this style doesn't set
+    pub fn or(&self, fallback: &Style) -> Style {
+        Style {
+            color: self.color.or(fallback.color),
+            background: self.background.or(fallback.background),
+            hovered: self.hovered.or(fallback.hovered),
+            accent: self.accent.or(fallback.accent),
+            padding: self.padding.or(fallback.padding),
+            font_size: self.font_size.or(fallback.font_size),
+        }
+    }
+
+    /// Return the problems of the style, such as the colors outside of [0; 1]
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let colors = [
+            ("color", self.color),
+            ("background", self.background),
+            ("hovered", self.hovered),
+            ("accent", self.accent),
+        ];
+        for (name, color) in colors.iter() {
+            if let Some(color) = color {
+                if color.iter().any(|component| !(0.0..=1.0).contains(component)) {
+                    errors.push(format!("the components of {} must be between 0 and 1", name));
+                }
+            }
+        }
+        if self.padding.map(|padding| padding < 0).unwrap_or(false) {
+            errors.push("the padding can't be negative".to_owned());
+        }
+        if self.font_size.map(|font_size| !font_size.is_finite() || font_size <= 0.0).unwrap_or(false) {
+            errors.push("the font size must be positive".to_owned());
+        }
+        errors
+    }
+}
+
+/// A theme: the styles of the classes of widgets, by name of class
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub classes: BTreeMap<String, Style>,
+}
+
+impl Theme {
+    /// The theme of the game, which sets all the properties of all the classes
+    pub fn builtin() -> Self {
+        DataFormat::Toml.deserialize(BUILTIN_THEME).expect("the built-in theme is valid")
+    }
+
+    /// Return the problems of the theme, such as the negative paddings
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (class, style) in self.classes.iter() {
+            errors.extend(style.validate().into_iter().map(|error| format!("class {}: {}", class, error)));
+        }
+        errors
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
 /// Helper struct to calculate the average time of an operation
@@ -117,4 +118,150 @@ impl BreakdownCounter {
         let total_micros = self.total_micros.iter().sum::<u128>() as f64;
         self.part_names.drain(..).zip(self.total_micros.iter()).map(|(s, m)| (s, *m as f64 / total_micros)).collect()
     }
-}
\ No newline at end of file
+}
+
+/// Duration of a simulation tick, for both the server and the client
+pub const TICK_DURATION: Duration = Duration::from_micros(1_000_000 / 60);
+/// Maximum number of ticks that can be pending. If the simulation falls further behind, the extra time is dropped
+/// instead of running more and more ticks to catch up.
+const MAX_PENDING_TICKS: u32 = 10;
+
+/// Fixed timestep clock: converts the elapsed real time into ticks of a fixed duration,
+/// so that the simulation doesn't depend on the frame rate.
+pub struct FixedTimestep {
+    tick_duration: Duration,
+    /// Simulated time that was not consumed by a tick yet
+    accumulator: Duration,
+    last_update: Instant,
+    /// Speed of the simulated time relative to the real time
+    time_scale: f64,
+    paused: bool,
+}
+
+impl FixedTimestep {
+    /// Create a new clock. The first tick is due immediately.
+    pub fn new(tick_duration: Duration) -> Self {
+        Self {
+            tick_duration,
+            accumulator: tick_duration,
+            last_update: Instant::now(),
+            time_scale: 1.0,
+            paused: false,
+        }
+    }
+
+    pub fn tick_duration(&self) -> Duration {
+        self.tick_duration
+    }
+
+    /// Accumulate the time elapsed since the last update
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last_update;
+        self.last_update = now;
+        if !self.paused {
+            self.accumulator += elapsed.mul_f64(self.time_scale);
+            self.accumulator = self.accumulator.min(self.tick_duration * MAX_PENDING_TICKS);
+        }
+    }
+
+    /// Return true if a tick must be run, and consume it
+    pub fn consume_tick(&mut self) -> bool {
+        if self.accumulator >= self.tick_duration {
+            self.accumulator -= self.tick_duration;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Real time to wait until the next tick
+    pub fn time_until_next_tick(&self) -> Duration {
+        if self.paused || self.time_scale <= 0.0 {
+            return self.tick_duration;
+        }
+        self.tick_duration
+            .saturating_sub(self.accumulator)
+            .div_f64(self.time_scale)
+    }
+
+    /// Progress towards the next tick, in [0; 1]. It is used to interpolate between the last two simulation states.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator.as_secs_f64() / self.tick_duration.as_secs_f64()).min(1.0)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Make the simulated time run faster (> 1) or slower (< 1) than the real time
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+}
+
+/// Length of a full day and night cycle, in seconds
+pub const DAY_LENGTH: f64 = 1200.0;
+
+/// Fraction of the sunlight that remains in the middle of the night
+const MIN_DAYLIGHT: f64 = 0.1;
+
+/// How much the path of the sun is tilted towards +z
+const SUN_TILT: f64 = 0.5;
+
+/// The in-game time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldTime {
+    seconds: f64,
+}
+
+impl WorldTime {
+    pub fn advance(&mut self, seconds: f64) {
+        self.seconds += seconds;
+    }
+
+    /// Seconds since the creation of the world
+    pub fn seconds(self) -> f64 {
+        self.seconds
+    }
+
+    /// Fraction of the current day, in [0; 1). The day starts at sunrise, and the night starts at 0.5.
+    pub fn time_of_day(self) -> f64 {
+        (self.seconds / DAY_LENGTH).fract()
+    }
+
+    pub fn is_day(self) -> bool {
+        self.time_of_day() < 0.5
+    }
+
+    /// Jump to the next sunrise, and return the number of seconds skipped
+    pub fn skip_to_morning(&mut self) -> f64 {
+        let morning = ((self.seconds / DAY_LENGTH).floor() + 1.0) * DAY_LENGTH;
+        let skipped = morning - self.seconds;
+        self.seconds = morning;
+        skipped
+    }
+
+    /// Brightness of the sunlight, in [MIN_DAYLIGHT; 1]. It fades around sunrise and sunset.
+    pub fn daylight(self) -> f64 {
+        let sun_height = (2.0 * std::f64::consts::PI * self.time_of_day()).sin();
+        (0.5 + 2.0 * sun_height).max(MIN_DAYLIGHT).min(1.0)
+    }
+
+    /// Unit vector pointing towards the sun. It rises in the +x direction, sets in the -x direction, and is tilted
+    /// towards +z so that it is never exactly vertical. At night, it is the direction of the sun below the horizon.
+    pub fn sun_direction(self) -> [f64; 3] {
+        let angle = 2.0 * std::f64::consts::PI * self.time_of_day();
+        let (x, y, z) = (angle.cos(), angle.sin(), SUN_TILT);
+        let norm = (x * x + y * y + z * z).sqrt();
+        [x / norm, y / norm, z / norm]
+    }
+}
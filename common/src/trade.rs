@@ -0,0 +1,62 @@
+//! Trades of the supply terminals: the players give items to a terminal and receive others in exchange. The trade
+//! lists are defined in the `trades` folder of the data, and every terminal block uses one of them.
+
+use crate::item::{Item, ItemStack, MAX_STACK_SIZE};
+use crate::registry::Registry;
+use serde::{Deserialize, Serialize};
+
+/// A trade list, as written in the data files
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "TradeList")]
+pub struct TradeListFile {
+    pub trades: Vec<TradeFile>,
+}
+
+/// A trade of a data file, with the names of the items and their counts
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Trade")]
+pub struct TradeFile {
+    pub cost: Vec<(String, u32)>,
+    pub result: (String, u32),
+}
+
+/// An exchange of the items `cost` for the item stack `result`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trade {
+    pub cost: Vec<ItemStack>,
+    pub result: ItemStack,
+}
+
+impl TradeListFile {
+    /// Resolve the names of the items of the trades. Return the problems of the list if there are some, such as the
+    /// unknown items.
+    pub fn resolve(&self, items: &Registry<Item>) -> Result<Vec<Trade>, Vec<String>> {
+        let mut errors = Vec::new();
+        let resolve_stack = |(name, count): &(String, u32), errors: &mut Vec<String>| {
+            if *count == 0 || *count > MAX_STACK_SIZE {
+                errors.push(format!("the count of {} must be between 1 and {}", name, MAX_STACK_SIZE));
+            }
+            let item = items.get_id_by_name(name);
+            if item.is_none() {
+                errors.push(format!("unknown item {}", name));
+            }
+            item.map(|item| ItemStack::new(item, *count))
+        };
+        let mut trades = Vec::new();
+        for trade in self.trades.iter() {
+            if trade.cost.is_empty() {
+                errors.push(format!("the trade of {} costs nothing", trade.result.0));
+            }
+            let cost = trade.cost.iter().map(|stack| resolve_stack(stack, &mut errors)).collect::<Vec<_>>();
+            let result = resolve_stack(&trade.result, &mut errors);
+            if let (Some(cost), Some(result)) = (cost.into_iter().collect(), result) {
+                trades.push(Trade { cost, result });
+            }
+        }
+        if errors.is_empty() {
+            Ok(trades)
+        } else {
+            Err(errors)
+        }
+    }
+}
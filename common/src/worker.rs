@@ -1,9 +1,18 @@
-//! Generic worker, allowing a computation to be performed in a separate thread
+//! Generic worker, allowing a computation to be performed in separate threads.
+//!
+//! The inputs are jobs, queued by priority. The queue is bounded: when it is full, `enqueue` gives the input back
+//! and the caller should try again later. Every job has a `CancelToken`, to drop the jobs that are no longer needed,
+//! such as the meshing of a chunk that was just unloaded.
 use std::{
+    collections::VecDeque,
     marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
     time::Instant,
 };
-use crossbeam_channel::{Receiver, Sender, TrySendError, bounded};
+use crossbeam_channel::{Receiver, Sender, bounded};
 use crate::{debug::send_worker_perf, time::AverageTimeCounter};
 
 /// A type that takes inputs of type `Input` produces outputs of type `Output`.
@@ -11,13 +20,80 @@ pub trait WorkerState<Input, Output> {
     fn compute(&mut self, input: Input) -> Output;
 }
 
+/// The priority of a job. The jobs with a higher priority are computed first, the jobs with the same priority are
+/// computed in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+const PRIORITY_COUNT: usize = 3;
+
+/// Cancels a job. A cancelled job is not computed, or its output is dropped if it was being computed.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+struct Job<Input> {
+    input: Input,
+    token: CancelToken,
+}
+
+/// The jobs waiting for a thread, one queue per priority
+struct JobQueue<Input> {
+    queues: [VecDeque<Job<Input>>; PRIORITY_COUNT],
+    /// Set when the worker is dropped, to stop the threads
+    closed: bool,
+}
+
+impl<Input> JobQueue<Input> {
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    /// The next job that wasn't cancelled, by priority
+    fn pop(&mut self) -> Option<Job<Input>> {
+        for queue in self.queues.iter_mut().rev() {
+            while let Some(job) = queue.pop_front() {
+                if !job.token.is_cancelled() {
+                    return Some(job);
+                }
+            }
+        }
+        None
+    }
+
+    fn remove_cancelled(&mut self) {
+        for queue in self.queues.iter_mut() {
+            queue.retain(|job| !job.token.is_cancelled());
+        }
+    }
+}
+
+struct SharedQueue<Input> {
+    jobs: Mutex<JobQueue<Input>>,
+    job_available: Condvar,
+    capacity: usize,
+}
+
 /// A generic worker allowing to offload expensive computations to other threads.
-/// The worker will try to process the inputs in order.
+/// The worker will try to process the inputs in order, by priority.
 /// `Input`: the input type
 /// `Output`: the output type
-/// `State`: the worker state
+/// `State`: the worker state, one per thread
 pub struct Worker<Input: Send + 'static, Output: Send + 'static, State: WorkerState<Input, Output> + Send + 'static> {
-    to_worker: Sender<Input>,
+    queue: Arc<SharedQueue<Input>>,
     from_worker: Receiver<Output>,
     _phantom: PhantomData<State>,
 }
@@ -25,47 +101,123 @@ pub struct Worker<Input: Send + 'static, Output: Send + 'static, State: WorkerSt
 impl<Input: Send + 'static, Output: Send + 'static, State: WorkerState<Input, Output> + Send + 'static> Worker<Input, Output, State> {
     /// Start a new worker with the given state using the provided channel size. The name is used for debug printing.
     pub fn new(state: State, channel_size: usize, name: String) -> Self {
-        let (in_sender, in_receiver) = bounded::<Input>(channel_size);
-        let (out_sender, out_receiver) = bounded::<Output>(channel_size);
+        Self::with_states(vec![state], channel_size, name)
+    }
 
-        std::thread::spawn(move || { // TODO: debug timing
-            let mut state = state;
-            let mut timing = AverageTimeCounter::new();
-            while let Ok(input) = in_receiver.recv() {
-                // Compute
-                let t1 = Instant::now();
-                let output = state.compute(input);
-                let t2 = Instant::now();
-                timing.add_time(t2 - t1);
-
-                // Send debug info
-                send_worker_perf("Workers", &name, &name, timing.average_time_micros() as f32, timing.average_iter_per_sec(), 0);
-
-                // Send result
-                match out_sender.send(output) {
-                    Ok(()) => (),
-                    Err(_) => break,
-                }
-            }
+    /// Start a new worker with one thread per state. At most `channel_size` inputs wait in the queue, and at most
+    /// `channel_size` outputs wait to be fetched: the threads stop when the outputs are not fetched.
+    pub fn with_states(states: Vec<State>, channel_size: usize, name: String) -> Self {
+        assert!(!states.is_empty(), "A worker needs at least one thread");
+        let queue = Arc::new(SharedQueue {
+            jobs: Mutex::new(JobQueue {
+                queues: Default::default(),
+                closed: false,
+            }),
+            job_available: Condvar::new(),
+            capacity: channel_size,
         });
+        let (out_sender, out_receiver) = bounded::<Output>(channel_size);
+
+        let thread_count = states.len();
+        for (i, state) in states.into_iter().enumerate() {
+            let thread_name = if thread_count == 1 { name.clone() } else { format!("{} {}", name, i + 1) };
+            let queue = queue.clone();
+            let out_sender = out_sender.clone();
+            std::thread::Builder::new()
+                .name(thread_name.clone())
+                .spawn(move || run_worker_thread(state, &queue, out_sender, &thread_name))
+                .expect("Failed to spawn worker thread");
+        }
 
         Self {
-            to_worker: in_sender,
+            queue,
             from_worker: out_receiver,
             _phantom: PhantomData,
         }
     }
 
-    /// Try to enqueue a new input in the worker queue. Doesn't block. Will return the input if the queue is full.
+    /// Try to enqueue a new input in the worker queue with the normal priority. Doesn't block. Will return the input
+    /// if the queue is full.
     pub fn enqueue(&self, input: Input) -> Result<(), Input> {
-        self.to_worker.try_send(input).map_err(|e| match e {
-            TrySendError::Full(input) => input,
-            TrySendError::Disconnected(_) => unreachable!("Worker channel disconnected"),
-        })
+        self.enqueue_with_priority(input, Priority::Normal).map(|_| ())
+    }
+
+    /// Try to enqueue a new input in the worker queue. Doesn't block. Will return the input if the queue is full,
+    /// or a token to cancel the job otherwise.
+    pub fn enqueue_with_priority(&self, input: Input, priority: Priority) -> Result<CancelToken, Input> {
+        let mut jobs = self.queue.jobs.lock().unwrap();
+        if jobs.len() >= self.queue.capacity {
+            // The cancelled jobs don't count
+            jobs.remove_cancelled();
+            if jobs.len() >= self.queue.capacity {
+                return Err(input);
+            }
+        }
+        let token = CancelToken::default();
+        jobs.queues[priority as usize].push_back(Job {
+            input,
+            token: token.clone(),
+        });
+        self.queue.job_available.notify_one();
+        Ok(token)
+    }
+
+    /// Number of jobs waiting for a thread, including the cancelled jobs that weren't removed yet
+    pub fn pending(&self) -> usize {
+        self.queue.jobs.lock().unwrap().len()
     }
 
     /// Try to get a new output from the worker. Doesn't block. Will return None if there is no available output.
     pub fn get_result(&self) -> Option<Output> {
         self.from_worker.try_recv().ok()
     }
-}
\ No newline at end of file
+}
+
+impl<Input: Send + 'static, Output: Send + 'static, State: WorkerState<Input, Output> + Send + 'static> Drop for Worker<Input, Output, State> {
+    fn drop(&mut self) {
+        self.queue.jobs.lock().unwrap().closed = true;
+        self.queue.job_available.notify_all();
+    }
+}
+
+fn run_worker_thread<Input, Output, State: WorkerState<Input, Output>>(
+    mut state: State,
+    queue: &SharedQueue<Input>,
+    out_sender: Sender<Output>,
+    name: &str,
+) {
+    let mut timing = AverageTimeCounter::new();
+    loop {
+        // Wait for a job
+        let (job, pending) = {
+            let mut jobs = queue.jobs.lock().unwrap();
+            loop {
+                if jobs.closed {
+                    return;
+                }
+                if let Some(job) = jobs.pop() {
+                    break (job, jobs.len());
+                }
+                jobs = queue.job_available.wait(jobs).unwrap();
+            }
+        };
+
+        // Compute
+        let t1 = Instant::now();
+        let output = state.compute(job.input);
+        let t2 = Instant::now();
+        timing.add_time(t2 - t1);
+
+        // Send debug info
+        send_worker_perf("Workers", name, name, timing.average_time_micros() as f32, timing.average_iter_per_sec(), pending);
+
+        // Send result, unless the job was cancelled during the computation
+        if job.token.is_cancelled() {
+            continue;
+        }
+        match out_sender.send(output) {
+            Ok(()) => (),
+            Err(_) => break,
+        }
+    }
+}
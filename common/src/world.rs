@@ -1,11 +1,13 @@
 use crate::{
+    biome::BiomeId,
     block::{Block, BlockId},
     registry::Registry,
 };
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
 
 /// The position of a block in the world.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BlockPos {
     pub px: i64,
     pub py: i64,
@@ -65,11 +67,70 @@ pub trait WorldGenerator {
     fn generate_chunk(&mut self, pos: ChunkPos, block_registry: &Registry<Block>) -> Chunk;
 }
 
+/// A dimension of the server, such as the surface or the lava caverns.
+/// Each dimension has its own generator and chunks, so the chunk positions are only meaningful within a dimension.
+/// The default is the surface.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DimensionId(pub u16);
+
+impl DimensionId {
+    /// The surface of the planet, where the players start
+    pub const SURFACE: Self = Self(0);
+}
+
+/// The vertical bounds of a dimension, in chunks, both included. The chunks outside of them are never generated,
+/// loaded or sent: the blocks below the dimension are solid, and the sunlight enters from above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldHeight {
+    pub min_chunk_y: i64,
+    pub max_chunk_y: i64,
+}
+
+impl WorldHeight {
+    /// No bounds, until the client receives the bounds of its dimension
+    pub const UNBOUNDED: Self = Self {
+        min_chunk_y: i64::MIN / CHUNK_SIZE as i64,
+        max_chunk_y: i64::MAX / CHUNK_SIZE as i64 - 1,
+    };
+
+    pub fn contains_chunk(self, pos: ChunkPos) -> bool {
+        (self.min_chunk_y..=self.max_chunk_y).contains(&pos.py)
+    }
+
+    /// Whether a block is below the dimension, where everything is solid
+    pub fn is_below(self, pos: BlockPos) -> bool {
+        pos.py < self.min_y()
+    }
+
+    /// The height of the lowest blocks of the dimension
+    pub fn min_y(self) -> i64 {
+        self.min_chunk_y * CHUNK_SIZE as i64
+    }
+
+    /// The height of the highest blocks of the dimension
+    pub fn max_y(self) -> i64 {
+        (self.max_chunk_y + 1) * CHUNK_SIZE as i64 - 1
+    }
+}
+
 /// Number of blocks along an axis of the chunk
 pub const CHUNK_SIZE: u32 = 32;
 
+/// Number of stages of the cracks of a block being mined, the stage 0 being an intact block
+pub const BLOCK_DAMAGE_STAGES: u8 = 8;
+
+/// Offsets of the six neighbors of a block, in the order of the faces (x/-x/y/-y/z/-z)
+pub const FACE_OFFSETS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
 /// Position of a chunk in the world
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChunkPos {
     pub px: i64,
     pub py: i64,
@@ -163,6 +224,7 @@ impl From<ChunkPos> for ChunkPosXZ {
 pub struct CompressedChunk {
     pub pos: ChunkPos,
     pub data: Vec<(u16, BlockId)>,
+    pub biomes: Vec<BiomeId>,
 }
 
 impl CompressedChunk {
@@ -185,6 +247,7 @@ impl CompressedChunk {
         Self {
             pos: chunk.pos,
             data: compressed_data,
+            biomes: chunk.biomes.clone(),
         }
     }
 
@@ -203,15 +266,53 @@ impl CompressedChunk {
         Chunk {
             pos: self.pos,
             data,
+            biomes: self.biomes.clone(),
+            structures: Vec::new(),
         }
     }
 }
 
 /// A chunk
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chunk {
     pub pos: ChunkPos,
     pub data: Vec<BlockId>,
+    /// The biome of each column of the chunk, see `get_biome_at`
+    pub biomes: Vec<BiomeId>,
+    /// The structures placed by the world generator whose origin is in the chunk. Only the server needs them.
+    #[serde(skip)]
+    pub structures: Vec<Structure>,
+}
+
+/// A structure placed by the world generator, such as a tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Structure {
+    /// The name of the decorator that placed it
+    pub name: String,
+    /// The corners of the bounding box of the structure, both included
+    pub min: BlockPos,
+    pub max: BlockPos,
+    /// The containers of the structure, with the loot table filling them the first time they are opened
+    pub loot: Vec<(BlockPos, String)>,
+}
+
+impl Structure {
+    /// The structure made of the blocks at `positions`, None if there are none
+    pub fn bounding(name: &str, mut positions: impl Iterator<Item = BlockPos>) -> Option<Self> {
+        let first = positions.next()?;
+        let (min, max) = positions.fold((first, first), |(min, max), pos| {
+            (
+                BlockPos::from((min.px.min(pos.px), min.py.min(pos.py), min.pz.min(pos.pz))),
+                BlockPos::from((max.px.max(pos.px), max.py.max(pos.py), max.pz.max(pos.pz))),
+            )
+        });
+        Some(Self {
+            name: name.to_owned(),
+            min,
+            max,
+            loot: Vec::new(),
+        })
+    }
 }
 
 impl Chunk {
@@ -222,7 +323,24 @@ impl Chunk {
                 (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize,
             )
         };
-        Self { pos, data }
+        let biomes = vec![BiomeId::default(); (CHUNK_SIZE * CHUNK_SIZE) as usize];
+        Self {
+            pos,
+            data,
+            biomes,
+            structures: Vec::new(),
+        }
+    }
+
+    /// Get the biome of the column at some position
+    #[inline(always)]
+    pub fn get_biome_at(&self, (px, pz): (u32, u32)) -> BiomeId {
+        self.biomes[(px * CHUNK_SIZE + pz) as usize]
+    }
+
+    /// Set the biome of every column of the chunk
+    pub fn fill_biomes(&mut self, biome: BiomeId) {
+        self.biomes.fill(biome);
     }
 
     /// Get block at some position
@@ -268,7 +386,7 @@ impl Chunk {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LightChunk {
     pub light: Vec<u8>,
     pub pos: ChunkPos,
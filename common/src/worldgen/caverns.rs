@@ -0,0 +1,67 @@
+//! The generator of the lava caverns: solid stone crossed by wide caves, whose bottom is flooded with lava
+
+use crate::biome::BiomeId;
+use crate::block::{Block, BlockId};
+use crate::registry::Registry;
+use crate::world::{Chunk, ChunkPos, WorldGenerator, CHUNK_SIZE};
+use crate::worldgen::perlin;
+
+/// The noise above which there is a cave
+const CAVE_THRESHOLD: f32 = 0.6;
+/// The caves are filled with lava below this height
+const LAVA_LEVEL: i64 = -24;
+const SEED: i32 = 7919;
+
+pub struct CavernWorldGenerator {
+    stone_block: BlockId,
+    /// None if the data has no lava, the caves are then empty
+    lava_block: Option<BlockId>,
+}
+
+impl CavernWorldGenerator {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let stone_block = block_registry.get_id_by_name(&"stone".to_owned()).unwrap() as BlockId;
+        let lava_block = block_registry.get_id_by_name(&"lava".to_owned()).map(|id| id as BlockId);
+        if lava_block.is_none() {
+            log::warn!("There is no lava block in the data, the caverns will not be flooded");
+        }
+        Self { stone_block, lava_block }
+    }
+}
+
+impl WorldGenerator for CavernWorldGenerator {
+    fn generate_chunk(&mut self, pos: ChunkPos, _block_registry: &Registry<Block>) -> Chunk {
+        let size = CHUNK_SIZE as usize;
+        let (x, y, z) = (pos.px * CHUNK_SIZE as i64, pos.py * CHUNK_SIZE as i64, pos.pz * CHUNK_SIZE as i64);
+        // The caves are wider than they are high
+        let noise = perlin::perlin(
+            x as f32,
+            y as f32,
+            z as f32,
+            size,
+            1.0 / 48.0,
+            1.0 / 24.0,
+            1.0 / 48.0,
+            4,
+            0.5,
+            SEED,
+        );
+        let mut chunk = Chunk::new(pos);
+        chunk.fill_biomes(BiomeId::CAVERNS);
+        for i in 0..CHUNK_SIZE {
+            for j in 0..CHUNK_SIZE {
+                for k in 0..CHUNK_SIZE {
+                    let block = if noise[(i as usize * size + j as usize) * size + k as usize] < CAVE_THRESHOLD {
+                        self.stone_block
+                    } else if y + (j as i64) < LAVA_LEVEL {
+                        self.lava_block.unwrap_or(0)
+                    } else {
+                        continue;
+                    };
+                    chunk.set_block_at((i, j, k), block);
+                }
+            }
+        }
+        chunk
+    }
+}
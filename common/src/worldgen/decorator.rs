@@ -3,12 +3,15 @@ use std::collections::HashSet;
 
 // TODO : Create a procedural decorator
 /// Struct used to generate pre-defined groups of block in the world
+#[derive(Clone)]
 pub(crate) struct Decorator {
+    pub name: &'static str, // the name of the structures placed by this decorator
     pub number_of_try: u32, // number of times this will be try to be spawn/chunks
     pub block_start_whitelist: HashSet<u16>, // the blocks allowed to be the start of the Decorator
     pub pass: Vec<DecoratorPass>, // the pass of each block for the decorator
 }
 
+#[derive(Clone)]
 pub struct DecoratorPass {
     pub block_type: u16,                  // the block type
     pub block_non_blocking: HashSet<u16>, // list of the block that will no be replaced but will not block the strucutre to spawn
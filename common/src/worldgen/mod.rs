@@ -5,23 +5,33 @@ use crate::worldgen::perlin::rand_pos_int;
 use crate::{
     block::Block,
     registry::Registry,
-    world::{Chunk, ChunkPos, CHUNK_SIZE, WorldGenerator},
+    world::{Chunk, ChunkPos, Structure, CHUNK_SIZE, WorldGenerator},
 };
 
 use crate::debug::send_debug_info;
 use crate::worldgen::decorator::Decorator;
 use crate::worldgen::decorator::DecoratorPass;
 use crate::worldgen::topology::{generate_chunk_topology, HeightMap};
+#[cfg(feature = "scripting")]
+use crate::worldgen::script::ScriptDecorators;
 
+pub mod caverns;
 pub mod perlin;
 #[macro_use]
 pub mod decorator;
+#[cfg(feature = "scripting")]
+pub mod script;
 pub mod topology;
 
+/// The generator of the surface. The clones of a generator share its decorator scripts, so that the worldgen threads
+/// clone a single generator.
+#[derive(Clone)]
 pub struct DefaultWorldGenerator {
     pregenerated_chunks: HashMap<ChunkPos, Chunk>,
     pregenerated_chunks_decorator_count: HashMap<ChunkPos, u32>,
     tree_decorator: Decorator,
+    #[cfg(feature = "scripting")]
+    script_decorators: ScriptDecorators,
     height_map: HeightMap,
 }
 
@@ -40,7 +50,9 @@ impl BlockToPlace {
 }
 
 impl DefaultWorldGenerator {
-    pub fn new(block_registry: &Registry<Block>) -> Self {
+    /// Create the generator. `decorator_scripts` are the `(name, source)` of the decorator scripts of the data,
+    /// which are ignored if the `scripting` feature is disabled.
+    pub fn new(block_registry: &Registry<Block>, decorator_scripts: &[(String, String)]) -> Self {
         let grass_block = block_registry.get_id_by_name(&"grass".to_owned()).unwrap() as u16;
         let leaves_block = block_registry.get_id_by_name(&"leaves".to_owned()).unwrap() as u16;
         let wood_block = block_registry.get_id_by_name(&"wood".to_owned()).unwrap() as u16;
@@ -75,12 +87,19 @@ impl DefaultWorldGenerator {
         }
 
         let tree_decorator = Decorator {
+            name: "tree",
             number_of_try: 32,
             block_start_whitelist: set![grass_block],
             pass: vec![pass_leaves, pass_wood],
         };
+        #[cfg(not(feature = "scripting"))]
+        if !decorator_scripts.is_empty() {
+            log::warn!("Decorator scripts are ignored because the scripting feature is disabled");
+        }
         Self {
             tree_decorator,
+            #[cfg(feature = "scripting")]
+            script_decorators: ScriptDecorators::new(decorator_scripts, block_registry),
             pregenerated_chunks_decorator_count: HashMap::new(),
             pregenerated_chunks: HashMap::new(),
             height_map: HeightMap::new(),
@@ -207,6 +226,13 @@ impl DefaultWorldGenerator {
                                 pass_count += 1;
                             }
                             if place {
+                                // the structures starting in the chunk being generated are recorded with it
+                                if (i, j, k) == (0, 0, 0) {
+                                    let placed = blocks_to_place_one.iter().flatten().map(|block| block.pos);
+                                    if let Some(structure) = Structure::bounding(decorator.name, placed) {
+                                        chunks[13].structures.push(structure);
+                                    }
+                                }
                                 // we add the block to full list of blocks to place
                                 for w in 0..decorator.pass.len() {
                                     for blocks in blocks_to_place_one[w].drain(..) {
@@ -269,8 +295,17 @@ impl WorldGenerator for DefaultWorldGenerator {
 
         let decorator = &self.tree_decorator;
         let chunk_center = chunks_vec[13].clone();
+        // The scripts must only see the terrain
+        #[cfg(feature = "scripting")]
+        let terrain = if self.script_decorators.is_empty() {
+            Vec::new()
+        } else {
+            chunks_vec.iter().cloned().map(std::sync::Arc::new).collect::<Vec<_>>()
+        };
 
         DefaultWorldGenerator::decorate_chunk(&mut chunks_vec, decorator);
+        #[cfg(feature = "scripting")]
+        self.script_decorators.decorate_chunk(&terrain, &mut chunks_vec[13]);
 
         let chunk_res = std::mem::replace(&mut chunks_vec[13], chunk_center);
 
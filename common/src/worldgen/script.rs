@@ -0,0 +1,248 @@
+//! Chunk decorators written in Rhai by the data packs. Only available with the `scripting` feature.
+//!
+//! A script of the `decorators` folder of the data defines a `decorate(chunk)` function, which is called once
+//! for every chunk whose neighbours are generated. `chunk` has the following properties and methods:
+//! - `chunk.x`, `chunk.y`, `chunk.z`: the position of the chunk, in chunks
+//! - `chunk.size`: the number of blocks along each side of the chunk
+//! - `chunk.get_block(x, y, z)`: the block of the terrain at a position in the chunk, or -1 outside of the chunk
+//! - `chunk.set_block(x, y, z, block)`: place a block. The position is relative to the chunk, and can be
+//!   at most one chunk away from it so that the structures can cross the borders of the chunks.
+//! - `chunk.set_loot(x, y, z, table)`: fill the container placed at a position, such as a storage crate, with the
+//!   loot table named `table` the first time it is opened
+//! - `chunk.random(n)`: a random integer between 0 and n - 1, the sequence only depends on the position of the chunk
+//!
+//! The function `block(name)` returns the id of a block, or -1 if it doesn't exist.
+//!
+//! The decorators only see the terrain, not the blocks placed by the other decorators, so that the generated
+//! world doesn't depend on the order of generation of the chunks. The blocks placed by one call to `decorate` are
+//! recorded as a structure named after the script, which the players can find with `/locate`. A call that fails
+//! places nothing, so that a broken script generates the same world whatever the order of the chunks.
+
+use crate::block::{Block, BlockId};
+use crate::registry::Registry;
+use crate::world::{BlockPos, Chunk, ChunkPos, Structure, CHUNK_SIZE};
+use crate::worldgen::perlin::{hash, rand_pos_int};
+use log::{error, info};
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Maximum number of operations of one call to `decorate`, so that a broken script can't block the world generation
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Number of calls to `decorate` whose results are kept. Each chunk is decorated from the 27 chunks around it, which
+/// are mostly decorated for its neighbours already.
+const MAX_CACHED_DECORATIONS: usize = 4096;
+
+/// The chunk given to the `decorate` function
+#[derive(Clone)]
+struct ChunkView {
+    chunk: Arc<Chunk>,
+    state: Arc<Mutex<ChunkViewState>>,
+}
+
+struct ChunkViewState {
+    rng: i32,
+    /// The blocks placed by the script, relative to the chunk
+    blocks: Vec<((i64, i64, i64), i64)>,
+    /// The loot tables of the containers placed by the script, relative to the chunk
+    loot: Vec<((i64, i64, i64), String)>,
+}
+
+impl ChunkView {
+    fn get_block(&mut self, x: i64, y: i64, z: i64) -> i64 {
+        let size = CHUNK_SIZE as i64;
+        if (0..size).contains(&x) && (0..size).contains(&y) && (0..size).contains(&z) {
+            self.chunk.get_block_at((x as u32, y as u32, z as u32)) as i64
+        } else {
+            -1
+        }
+    }
+
+    fn set_block(&mut self, x: i64, y: i64, z: i64, block: i64) {
+        let size = CHUNK_SIZE as i64;
+        let range = -size..2 * size;
+        if range.contains(&x) && range.contains(&y) && range.contains(&z) {
+            self.state.lock().unwrap().blocks.push(((x, y, z), block));
+        }
+    }
+
+    fn set_loot(&mut self, x: i64, y: i64, z: i64, table: &str) {
+        self.state.lock().unwrap().loot.push(((x, y, z), table.to_owned()));
+    }
+
+    fn random(&mut self, n: i64) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        state.rng = hash(state.rng);
+        if n > 0 {
+            (state.rng as i64).rem_euclid(n)
+        } else {
+            0
+        }
+    }
+}
+
+struct ScriptDecorator {
+    name: String,
+    ast: AST,
+    /// Whether the script already failed, only its first failure is logged
+    failed: AtomicBool,
+}
+
+/// The blocks and the loot placed by one call to `decorate`, in world coordinates
+#[derive(Default)]
+struct Decoration {
+    blocks: Vec<(BlockPos, BlockId)>,
+    loot: Vec<(BlockPos, String)>,
+}
+
+/// The results of the latest calls to `decorate`, by decorator and chunk
+#[derive(Default)]
+struct DecorationCache {
+    decorations: HashMap<(usize, ChunkPos), Arc<Decoration>>,
+    order: VecDeque<(usize, ChunkPos)>,
+}
+
+impl DecorationCache {
+    fn insert(&mut self, key: (usize, ChunkPos), decoration: Arc<Decoration>) {
+        if self.decorations.insert(key, decoration).is_none() {
+            self.order.push_back(key);
+        }
+        if self.order.len() > MAX_CACHED_DECORATIONS {
+            let oldest = self.order.pop_front().unwrap();
+            self.decorations.remove(&oldest);
+        }
+    }
+}
+
+/// The decorators of the data packs. The clones share the scripts and their results, so that the worldgen threads
+/// don't decorate the same chunks again.
+#[derive(Clone)]
+pub struct ScriptDecorators {
+    engine: Arc<Engine>,
+    decorators: Arc<Vec<ScriptDecorator>>,
+    number_of_blocks: u32,
+    cache: Arc<Mutex<DecorationCache>>,
+}
+
+impl ScriptDecorators {
+    /// Compile the scripts, given as `(name, source)`. The scripts that don't compile are skipped.
+    pub fn new(scripts: &[(String, String)], block_registry: &Registry<Block>) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine
+            .register_type_with_name::<ChunkView>("Chunk")
+            .register_get("x", |view: &mut ChunkView| view.chunk.pos.px)
+            .register_get("y", |view: &mut ChunkView| view.chunk.pos.py)
+            .register_get("z", |view: &mut ChunkView| view.chunk.pos.pz)
+            .register_get("size", |_: &mut ChunkView| CHUNK_SIZE as i64)
+            .register_fn("get_block", ChunkView::get_block)
+            .register_fn("set_block", ChunkView::set_block)
+            .register_fn("set_loot", ChunkView::set_loot)
+            .register_fn("random", ChunkView::random);
+        let blocks = block_registry.clone();
+        engine.register_fn("block", move |name: &str| {
+            blocks.get_id_by_name(&name.to_owned()).map(|id| id as i64).unwrap_or(-1)
+        });
+
+        let mut decorators = Vec::new();
+        for (name, source) in scripts {
+            match engine.compile(source) {
+                Ok(ast) => {
+                    if ast.iter_functions().any(|f| f.name == "decorate" && f.params.len() == 1) {
+                        info!("Loaded decorator script {}", name);
+                        decorators.push(ScriptDecorator {
+                            name: name.clone(),
+                            ast,
+                            failed: AtomicBool::new(false),
+                        });
+                    } else {
+                        error!("Decorator script {} has no decorate(chunk) function", name);
+                    }
+                }
+                Err(e) => error!("Failed to compile decorator script {}: {}", name, e),
+            }
+        }
+        Self {
+            engine: Arc::new(engine),
+            decorators: Arc::new(decorators),
+            number_of_blocks: block_registry.get_number_of_ids(),
+            cache: Arc::new(Mutex::new(DecorationCache::default())),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decorators.is_empty()
+    }
+
+    /// Decorate `center` with the blocks placed by the decorators of the 3x3x3 chunks around it.
+    /// `terrain` contains these chunks before decoration, in the same order as in `DefaultWorldGenerator`.
+    pub fn decorate_chunk(&mut self, terrain: &[Arc<Chunk>], center: &mut Chunk) {
+        let center_pos = center.pos;
+        for (index, decorator) in self.decorators.iter().enumerate() {
+            for chunk in terrain {
+                let decoration = self.decoration(index, chunk);
+                // The structure is recorded with the chunk it was decorated from, so that it is recorded once
+                if chunk.pos == center_pos {
+                    let positions = decoration.blocks.iter().map(|&(block_pos, _)| block_pos);
+                    if let Some(mut structure) = Structure::bounding(&decorator.name, positions) {
+                        structure.loot = decoration.loot.clone();
+                        center.structures.push(structure);
+                    }
+                }
+                // Only keep the blocks that are in the chunk being generated
+                for &(block_pos, block) in &decoration.blocks {
+                    if block_pos.containing_chunk_pos() == center_pos {
+                        center.set_block_at(block_pos.pos_in_containing_chunk(), block);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The blocks placed by a decorator from a chunk, computed once for all the chunks around it
+    fn decoration(&self, index: usize, chunk: &Arc<Chunk>) -> Arc<Decoration> {
+        let key = (index, chunk.pos);
+        if let Some(decoration) = self.cache.lock().unwrap().decorations.get(&key) {
+            return decoration.clone();
+        }
+        // Another thread may decorate the same chunk meanwhile, with the same result
+        let decoration = Arc::new(self.decorate(&self.decorators[index], index, chunk));
+        self.cache.lock().unwrap().insert(key, decoration.clone());
+        decoration
+    }
+
+    /// Call the `decorate` function of a decorator on a chunk
+    fn decorate(&self, decorator: &ScriptDecorator, index: usize, chunk: &Arc<Chunk>) -> Decoration {
+        let size = CHUNK_SIZE as i64;
+        let pos = chunk.pos;
+        let view = ChunkView {
+            chunk: chunk.clone(),
+            state: Arc::new(Mutex::new(ChunkViewState {
+                rng: rand_pos_int(pos.px as i32, pos.py as i32, pos.pz as i32, hash(index as i32)),
+                blocks: Vec::new(),
+                loot: Vec::new(),
+            })),
+        };
+        let result = self.engine.call_fn::<Dynamic>(&mut Scope::new(), &decorator.ast, "decorate", (view.clone(),));
+        if let Err(e) = result {
+            if !decorator.failed.swap(true, Ordering::Relaxed) {
+                error!("Decorator script {} failed, it decorates nothing where it fails: {}", decorator.name, e);
+            }
+            return Decoration::default();
+        }
+        let state = view.state.lock().unwrap();
+        let world_pos = |(x, y, z): (i64, i64, i64)| {
+            BlockPos::from((pos.px * size + x, pos.py * size + y, pos.pz * size + z))
+        };
+        Decoration {
+            blocks: state
+                .blocks
+                .iter()
+                .filter(|&&(_, block)| block >= 0 && block < self.number_of_blocks as i64)
+                .map(|&(block_pos, block)| (world_pos(block_pos), block as BlockId))
+                .collect(),
+            loot: state.loot.iter().map(|(block_pos, table)| (world_pos(*block_pos), table.clone())).collect(),
+        }
+    }
+}
@@ -1,11 +1,23 @@
+use crate::biome::BiomeId;
 use crate::block::Block;
 use crate::registry::Registry;
 use crate::world::{Chunk, CHUNK_SIZE, ChunkPosXZ};
 use crate::worldgen::perlin;
 use std::collections::HashMap;
 
+/// The ground is in the highlands from this height
+const HIGHLANDS_HEIGHT: i32 = 48;
+/// The ground up to this height is a shore, the sea level being at 0
+const SHORE_HEIGHT: i32 = 2;
+/// The temperature noise below which the biomes are cold, and above which they are warm
+const COLD_NOISE: f32 = 0.38;
+const WARM_NOISE: f32 = 0.62;
+const TEMPERATURE_SEED: i32 = 4;
+
+#[derive(Clone)]
 pub struct HeightMap {
     height_map: HashMap<ChunkPosXZ, Vec<i32>>,
+    biome_map: HashMap<ChunkPosXZ, Vec<BiomeId>>,
 }
 
 impl  HeightMap {
@@ -13,6 +25,7 @@ impl  HeightMap {
     pub fn new() ->Self{
         return Self{
             height_map: HashMap::new(),
+            biome_map: HashMap::new(),
         };
     }
 
@@ -29,6 +42,40 @@ impl  HeightMap {
         return self.height_map.get(&pos).unwrap();
     }
 
+    /// The biome of each column of the chunks at `pos`
+    pub fn get_chunk_biomes(&mut self, pos: ChunkPosXZ) -> &Vec<BiomeId> {
+        if !self.biome_map.contains_key(&pos) {
+            let c = CHUNK_SIZE as f32;
+            let temperature = perlin::perlin2d(
+                (pos.px as f32) * c,
+                (pos.pz as f32) * c,
+                CHUNK_SIZE as usize,
+                1.0 / 512.0,
+                1.0 / 512.0,
+                3,
+                0.5,
+                TEMPERATURE_SEED,
+            );
+            let heights = self.get_chunk_height_map(pos);
+            let biomes = heights.iter().zip(temperature).map(|(&height, noise)| column_biome(height, noise)).collect();
+            self.biome_map.insert(pos, biomes);
+        }
+        &self.biome_map[&pos]
+    }
+}
+
+/// The biome of a column from the height of its ground and its temperature noise
+fn column_biome(height: i32, temperature: f32) -> BiomeId {
+    let cold = temperature < COLD_NOISE;
+    match height {
+        _ if height < 0 => BiomeId::OCEAN,
+        _ if height <= SHORE_HEIGHT => BiomeId::SHORE,
+        _ if height >= HIGHLANDS_HEIGHT && cold => BiomeId::FROZEN_PEAKS,
+        _ if height >= HIGHLANDS_HEIGHT => BiomeId::HIGHLANDS,
+        _ if cold => BiomeId::TUNDRA,
+        _ if temperature > WARM_NOISE => BiomeId::STEPPE,
+        _ => BiomeId::PLAINS,
+    }
 }
 
 pub fn generate_ground_level(px: f32, pz: f32) -> Vec<f32> {
@@ -102,6 +149,7 @@ pub fn generate_chunk_topology(chunk: &mut Chunk, block_registry: &Registry<Bloc
     let water_block = block_registry.get_id_by_name(&"water".to_owned()).unwrap() as u16;
     let sand_block = block_registry.get_id_by_name(&"sand".to_owned()).unwrap() as u16;
 
+    chunk.biomes = height_map.get_chunk_biomes(chunk.pos.into()).clone();
     let h = height_map.get_chunk_height_map(chunk.pos.into());
 
     for i in 0..CHUNK_SIZE{
@@ -0,0 +1,129 @@
+//! Achievements of the players, unlocked by the events of the event bus and stored with the world
+
+use crate::events::{BlockBroken, BlockPlaced, Entity, EntitySpawned, EventBus, MeteorImpact, PlayerLeft};
+use anyhow::{Context, Result};
+use common::{achievement::Achievement, player::PlayerId};
+use log::info;
+use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Maximum distance between a player and a meteor impact to unlock `SurvivedMeteorImpact`
+const METEOR_IMPACT_DISTANCE: f64 = 40.0;
+
+/// Something received from the event bus that can unlock achievements
+enum Trigger {
+    Unlock(PlayerId, Achievement),
+    MeteorImpact(Vector3<f64>),
+    PlayerLeft(PlayerId),
+}
+
+pub struct Achievements {
+    path: PathBuf,
+    /// The achievements unlocked by each player. The players are identified by their name,
+    /// because their ids change every time they join.
+    unlocked: BTreeMap<String, BTreeSet<Achievement>>,
+    player_names: HashMap<PlayerId, String>,
+    triggers: Rc<RefCell<Vec<Trigger>>>,
+}
+
+impl Achievements {
+    /// Load the unlocked achievements from the world save, if it has some
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let unlocked = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read achievements")?;
+            ron::de::from_str(&buf).context("Failed to parse achievements")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            unlocked,
+            player_names: HashMap::new(),
+            triggers: Default::default(),
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let string = ron::ser::to_string_pretty(&self.unlocked, Default::default())
+            .context("Failed to serialize achievements")?;
+        std::fs::write(&self.path, string).context("Failed to write achievements")?;
+        Ok(())
+    }
+
+    pub fn subscribe(&self, events: &mut EventBus) {
+        let triggers = self.triggers.clone();
+        events.subscribe("achievements", move |event: &BlockBroken, _| {
+            triggers.borrow_mut().push(Trigger::Unlock(event.player, Achievement::FirstBlockMined));
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("achievements", move |event: &BlockPlaced, _| {
+            triggers.borrow_mut().push(Trigger::Unlock(event.player, Achievement::FirstBlockPlaced));
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("achievements", move |event: &EntitySpawned, _| {
+            if let Entity::Robot { owner, .. } = event.entity {
+                triggers.borrow_mut().push(Trigger::Unlock(owner, Achievement::FirstRobotBuilt));
+            }
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("achievements", move |event: &MeteorImpact, _| {
+            triggers.borrow_mut().push(Trigger::MeteorImpact(event.pos));
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("achievements", move |event: &PlayerLeft, _| {
+            triggers.borrow_mut().push(Trigger::PlayerLeft(event.player));
+        });
+    }
+
+    /// Set the name of a player, and return the achievements the player already unlocked
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) -> Vec<Achievement> {
+        let unlocked = self
+            .unlocked
+            .get(&name)
+            .map(|unlocked| unlocked.iter().copied().collect())
+            .unwrap_or_default();
+        self.player_names.insert(player, name);
+        unlocked
+    }
+
+    /// Unlock the achievements of the events received since the last call.
+    /// Return the newly unlocked achievements, to be sent to the players.
+    pub fn tick(&mut self, player_positions: &HashMap<PlayerId, Vector3<f64>>) -> Vec<(PlayerId, Achievement)> {
+        let mut newly_unlocked = Vec::new();
+        let triggers = std::mem::take(&mut *self.triggers.borrow_mut());
+        for trigger in triggers {
+            match trigger {
+                Trigger::Unlock(player, achievement) => self.unlock(player, achievement, &mut newly_unlocked),
+                Trigger::MeteorImpact(pos) => {
+                    for (&player, player_pos) in player_positions.iter() {
+                        if (player_pos - pos).norm() <= METEOR_IMPACT_DISTANCE {
+                            self.unlock(player, Achievement::SurvivedMeteorImpact, &mut newly_unlocked);
+                        }
+                    }
+                }
+                Trigger::PlayerLeft(player) => {
+                    self.player_names.remove(&player);
+                }
+            }
+        }
+        if !newly_unlocked.is_empty() {
+            if let Err(e) = self.save() {
+                log::error!("{:#}", e);
+            }
+        }
+        newly_unlocked
+    }
+
+    fn unlock(&mut self, player: PlayerId, achievement: Achievement, newly_unlocked: &mut Vec<(PlayerId, Achievement)>) {
+        // The achievements can't be stored before the player's name is known
+        if let Some(name) = self.player_names.get(&player) {
+            if self.unlocked.entry(name.clone()).or_default().insert(achievement) {
+                info!("Player {} unlocked achievement {:?}", name, achievement);
+                newly_unlocked.push((player, achievement));
+            }
+        }
+    }
+}
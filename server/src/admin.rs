@@ -0,0 +1,248 @@
+//! Remote administration: an optional TCP listener where the admin tools of the dedicated servers
+//! send console commands and receive the log output.
+//!
+//! The protocol is made of text lines. The first line sent by the tool must be `auth <token>`,
+//! answered by `ok`, or by `error <message>` before the connection is closed.
+//! Then every line sent by the tool is a command. Its output is sent back as lines starting with `out `,
+//! followed by `end`. The log lines are sent at any time, starting with `log `.
+//! The lines longer than `MAX_LINE` close the connection, and the connections beyond `MAX_CONNECTIONS` are refused.
+
+use crate::settings::AdminSettings;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time given to the tools to send their token
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum length of the lines sent by the tools, in bytes, line break included
+const MAX_LINE: u64 = 4096;
+/// Maximum number of tools connected at once, authenticated or not
+const MAX_CONNECTIONS: usize = 8;
+
+/// The connections that receive the log lines
+static LOG_LISTENERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+
+/// Send a log line to the connected admin tools. Called by the logger of the game.
+pub fn forward_log(line: &str) {
+    // Don't log anything here, the logger would call this function again
+    if let Ok(mut listeners) = LOG_LISTENERS.lock() {
+        listeners.retain(|listener| listener.send(format!("log {}", line)).is_ok());
+    }
+}
+
+/// A command received from an admin tool, waiting for its output
+pub struct AdminCommand {
+    command: String,
+    output: Sender<String>,
+}
+
+impl AdminCommand {
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Send the output of the command to the tool
+    pub fn respond(self, output: String) {
+        for line in output.lines() {
+            let _ = self.output.send(format!("out {}", line));
+        }
+        let _ = self.output.send("end".to_owned());
+    }
+}
+
+pub struct AdminListener {
+    commands: Receiver<AdminCommand>,
+}
+
+impl AdminListener {
+    /// Start listening for the admin tools in a background thread
+    pub fn start(settings: &AdminSettings) -> Result<Self> {
+        if settings.token.is_empty() {
+            anyhow::bail!("The remote administration token must not be empty");
+        }
+        let listener = TcpListener::bind(&settings.address)
+            .with_context(|| format!("Failed to listen for remote administration on {}", settings.address))?;
+        info!("Listening for remote administration on {}", settings.address);
+        let (command_sender, commands) = channel();
+        let token = settings.token.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+        std::thread::Builder::new()
+            .name("Admin listener".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(mut stream) => {
+                            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                                connections.fetch_sub(1, Ordering::SeqCst);
+                                warn!("Refused a remote administration connection: too many connections");
+                                let _ = writeln!(stream, "error Too many connections");
+                                continue;
+                            }
+                            let token = token.clone();
+                            let command_sender = command_sender.clone();
+                            let connections = connections.clone();
+                            std::thread::spawn(move || {
+                                let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+                                if let Err(e) = handle_connection(stream, &token, command_sender) {
+                                    warn!("Remote administration connection {} failed: {:#}", peer, e);
+                                }
+                                connections.fetch_sub(1, Ordering::SeqCst);
+                            });
+                        }
+                        Err(e) => warn!("Failed to accept a remote administration connection: {}", e),
+                    }
+                }
+            })
+            .context("Failed to start the remote administration thread")?;
+        Ok(Self { commands })
+    }
+
+    /// Return the commands received since the last call
+    pub fn receive_commands(&self) -> Vec<AdminCommand> {
+        self.commands.try_iter().collect()
+    }
+}
+
+fn handle_connection(stream: TcpStream, token: &str, commands: Sender<AdminCommand>) -> Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    writer.set_read_timeout(Some(AUTH_TIMEOUT))?;
+    let line = read_line(&mut reader)?.unwrap_or_default();
+    let authenticated = match line.trim_end().strip_prefix("auth ") {
+        Some(received) => tokens_match(received, token),
+        None => false,
+    };
+    if !authenticated {
+        writeln!(writer, "error Invalid token")?;
+        anyhow::bail!("invalid token");
+    }
+    writer.set_read_timeout(None)?;
+    writeln!(writer, "ok")?;
+    info!("Remote administration connected from {}", peer);
+
+    // The log lines and the command outputs are written by another thread
+    let connection = writer.try_clone()?;
+    let (output, lines) = channel::<String>();
+    LOG_LISTENERS.lock().unwrap().push(output.clone());
+    std::thread::spawn(move || {
+        for line in lines {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let command = match read_line(&mut reader) {
+            Ok(Some(line)) => line.trim().to_owned(),
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Remote administration connection {} failed: {:#}", peer, e);
+                break;
+            }
+        };
+        if command.is_empty() {
+            continue;
+        }
+        info!("Remote administration command from {}: {}", peer, command);
+        let command = AdminCommand {
+            command,
+            output: output.clone(),
+        };
+        if commands.send(command).is_err() {
+            // The server stopped
+            break;
+        }
+    }
+    info!("Remote administration disconnected from {}", peer);
+    // Stop the writing thread, which removes the connection from the log listeners
+    let _ = connection.shutdown(Shutdown::Both);
+    Ok(())
+}
+
+/// Read a line of at most `MAX_LINE` bytes, so that a tool can't make the server buffer an endless line.
+/// Return None once the connection is closed.
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = String::new();
+    if reader.by_ref().take(MAX_LINE).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.len() as u64 >= MAX_LINE && !line.ends_with('\n') {
+        anyhow::bail!("line longer than {} bytes", MAX_LINE);
+    }
+    Ok(Some(line))
+}
+
+/// Compare the tokens in a time that doesn't depend on the position of the first difference
+fn tokens_match(received: &str, expected: &str) -> bool {
+    received.len() == expected.len()
+        && received
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret2", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut reader = Cursor::new(b"auth secret\nplayers\n".to_vec());
+        assert_eq!(read_line(&mut reader).unwrap().as_deref(), Some("auth secret\n"));
+        assert_eq!(read_line(&mut reader).unwrap().as_deref(), Some("players\n"));
+        assert_eq!(read_line(&mut reader).unwrap(), None);
+
+        // The longest line accepted, line break included
+        let mut line = "a".repeat(MAX_LINE as usize - 1);
+        line.push('\n');
+        let mut reader = Cursor::new(line.clone().into_bytes());
+        assert_eq!(read_line(&mut reader).unwrap(), Some(line));
+        let mut reader = Cursor::new("a".repeat(MAX_LINE as usize).into_bytes());
+        assert!(read_line(&mut reader).is_err());
+    }
+
+    /// Connect a tool to `handle_connection` over the loopback, and send it `lines`
+    fn connect(lines: &str) -> (BufReader<TcpStream>, Receiver<AdminCommand>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut tool = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (command_sender, commands) = channel();
+        std::thread::spawn(move || handle_connection(stream, "secret", command_sender));
+        tool.write_all(lines.as_bytes()).unwrap();
+        (BufReader::new(tool), commands)
+    }
+
+    #[test]
+    fn test_authentication() {
+        let (mut tool, _) = connect("auth wrong\n");
+        assert_eq!(read_line(&mut tool).unwrap().as_deref(), Some("error Invalid token\n"));
+        assert_eq!(read_line(&mut tool).unwrap(), None);
+
+        let (mut tool, commands) = connect("auth secret\nplayers\n");
+        assert_eq!(read_line(&mut tool).unwrap().as_deref(), Some("ok\n"));
+        let command = commands.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(command.command(), "players");
+        command.respond("0 players connected".to_owned());
+        assert_eq!(read_line(&mut tool).unwrap().as_deref(), Some("out 0 players connected\n"));
+        assert_eq!(read_line(&mut tool).unwrap().as_deref(), Some("end\n"));
+        tool.get_ref().shutdown(Shutdown::Both).unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+//! Validation of the inputs and actions sent by the clients.
+//!
+//! The suspicious inputs are rejected and count as violations. The violations decay over time,
+//! and the players who accumulate too many of them are kicked.
+
+use crate::settings::AntiCheatSettings;
+use common::physics::simulation::ServerPhysicsSimulation;
+use common::player::{PlayerId, PlayerInput};
+use common::world::BlockPos;
+use log::warn;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+struct PlayerRecord {
+    violations: f64,
+    /// The position of the player after the last tick
+    last_position: Option<Vector3<f64>>,
+    /// The number of blocks the player can break right now, refilled over time
+    break_allowance: f64,
+}
+
+pub struct AntiCheat {
+    settings: AntiCheatSettings,
+    players: HashMap<PlayerId, PlayerRecord>,
+    /// The players to kick, with the reason
+    kicks: Vec<(PlayerId, String)>,
+}
+
+impl AntiCheat {
+    pub fn new(settings: AntiCheatSettings) -> Self {
+        Self {
+            settings,
+            players: HashMap::new(),
+            kicks: Vec::new(),
+        }
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.players.remove(&player);
+    }
+
+    /// Accept the new position of a player moved by the server, which would otherwise be moved back
+    pub fn teleported(&mut self, player: PlayerId, pos: Vector3<f64>) {
+        self.record(player).last_position = Some(pos);
+    }
+
+    /// Return the input if it is valid, with the pitch clamped. The invalid inputs are always rejected,
+    /// because they would break the physics simulation.
+    pub fn check_input(&mut self, player: PlayerId, mut input: PlayerInput) -> Option<PlayerInput> {
+        if !input.yaw.is_finite() || !input.pitch.is_finite() {
+            self.flag(player, "sent an invalid camera orientation");
+            return None;
+        }
+        input.pitch = input.pitch.clamp(-90.0, 90.0);
+        Some(input)
+    }
+
+    /// Check that the position sent with an action is close to the position of the player on the server
+    pub fn check_action_position(
+        &mut self,
+        player: PlayerId,
+        claimed_position: Vector3<f64>,
+        physics_simulation: &ServerPhysicsSimulation,
+    ) -> bool {
+        let physics_player = match physics_simulation.get_state().physics_state.players.get(&player) {
+            Some(physics_player) => physics_player,
+            None => return true,
+        };
+        if !self.settings.enabled || (claimed_position - physics_player.aabb.pos).norm() <= self.settings.max_position_error {
+            return true;
+        }
+        self.flag(player, "acted from a position far from the player");
+        false
+    }
+
+    /// Check that a block is close enough to the eyes of the player to be reached
+    pub fn check_reach(&mut self, player: PlayerId, block: BlockPos, physics_simulation: &ServerPhysicsSimulation) -> bool {
+        let physics_player = match physics_simulation.get_state().physics_state.players.get(&player) {
+            Some(physics_player) => physics_player,
+            None => return true,
+        };
+        let block_center = Vector3::new(block.px as f64 + 0.5, block.py as f64 + 0.5, block.pz as f64 + 0.5);
+        let distance = (block_center - physics_player.get_camera_position()).norm();
+        if !self.settings.enabled || distance <= self.settings.max_reach {
+            return true;
+        }
+        self.flag(player, "reached a block too far away");
+        false
+    }
+
+    /// Check that the player doesn't break the blocks faster than allowed
+    pub fn check_break(&mut self, player: PlayerId) -> bool {
+        if !self.settings.enabled {
+            return true;
+        }
+        let record = self.record(player);
+        if record.break_allowance >= 1.0 {
+            record.break_allowance -= 1.0;
+            return true;
+        }
+        self.flag(player, "broke blocks too fast");
+        false
+    }
+
+    /// Move back the players who moved too fast during the last physics tick, and let the violations decay
+    pub fn tick(&mut self, physics_simulation: &mut ServerPhysicsSimulation, dt: f64) {
+        if !self.settings.enabled {
+            return;
+        }
+        let max_distance = self.settings.max_speed * dt;
+        let mut moved_back = Vec::new();
+        for (&player, physics_player) in physics_simulation.get_state().physics_state.players.iter() {
+            let (violation_decay, max_breaks_per_second) = (self.settings.violation_decay, self.settings.max_breaks_per_second);
+            let record = self.record(player);
+            record.violations = (record.violations - violation_decay * dt).max(0.0);
+            record.break_allowance = (record.break_allowance + max_breaks_per_second * dt).min(max_breaks_per_second);
+            let pos = physics_player.aabb.pos;
+            match record.last_position {
+                Some(last_position) if (pos - last_position).norm() > max_distance => moved_back.push((player, last_position)),
+                _ => record.last_position = Some(pos),
+            }
+        }
+        for (player, last_position) in moved_back {
+            physics_simulation.set_player_position(player, last_position);
+            self.flag(player, "moved too fast");
+        }
+    }
+
+    /// Return the players to kick since the last call, and the reason
+    pub fn drain_kicks(&mut self) -> Vec<(PlayerId, String)> {
+        std::mem::take(&mut self.kicks)
+    }
+
+    fn record(&mut self, player: PlayerId) -> &mut PlayerRecord {
+        let break_allowance = self.settings.max_breaks_per_second;
+        self.players.entry(player).or_insert_with(|| PlayerRecord {
+            violations: 0.0,
+            last_position: None,
+            break_allowance,
+        })
+    }
+
+    /// Count a violation, and kick the player if there are too many of them
+    fn flag(&mut self, player: PlayerId, reason: &str) {
+        if !self.settings.enabled {
+            return;
+        }
+        let record = self.record(player);
+        record.violations += 1.0;
+        let violations = record.violations;
+        warn!("Player {:?} {} ({:.1} violations)", player, reason, violations);
+        if violations >= self.settings.kick_threshold && !self.kicks.iter().any(|(id, _)| *id == player) {
+            warn!("Kicking player {:?}", player);
+            self.kicks.push((player, format!("Kicked by the anti-cheat: {}", reason)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::physics::BlockContainer;
+    use std::time::{Duration, Instant};
+
+    struct EmptyWorld;
+
+    impl BlockContainer for EmptyWorld {
+        fn is_block_full(&self, _pos: BlockPos) -> bool {
+            false
+        }
+    }
+
+    fn simulation_with_player(player: PlayerId) -> ServerPhysicsSimulation {
+        let mut physics_simulation = ServerPhysicsSimulation::new();
+        physics_simulation.set_player_input(player, PlayerInput::default());
+        physics_simulation.step_simulation(Instant::now(), Duration::from_millis(1), |_| &EmptyWorld);
+        physics_simulation
+    }
+
+    #[test]
+    fn test_reach() {
+        let player = PlayerId::from_raw(1);
+        let physics_simulation = simulation_with_player(player);
+        let mut anti_cheat = AntiCheat::new(AntiCheatSettings::default());
+        let physics_player = &physics_simulation.get_state().physics_state.players[&player];
+        let camera = BlockPos::from(physics_player.get_camera_position());
+        let block = |dx: i64| BlockPos::from((camera.px + dx, camera.py, camera.pz));
+        assert!(anti_cheat.check_reach(player, block(3), &physics_simulation));
+        assert!(!anti_cheat.check_reach(player, block(100), &physics_simulation));
+        assert_eq!(anti_cheat.players[&player].violations, 1.0);
+    }
+
+    #[test]
+    fn test_speed() {
+        let player = PlayerId::from_raw(1);
+        let mut physics_simulation = simulation_with_player(player);
+        let settings = AntiCheatSettings { kick_threshold: 2.0, violation_decay: 0.0, ..Default::default() };
+        let mut anti_cheat = AntiCheat::new(settings);
+        let start = physics_simulation.get_state().physics_state.players[&player].aabb.pos;
+        anti_cheat.tick(&mut physics_simulation, 0.05);
+
+        // Slow enough
+        let walked = start + Vector3::new(1.0, 0.0, 0.0);
+        physics_simulation.set_player_position(player, walked);
+        anti_cheat.tick(&mut physics_simulation, 0.05);
+        assert_eq!(physics_simulation.get_state().physics_state.players[&player].aabb.pos, walked);
+
+        // Too fast: the player is moved back, and kicked at the second violation
+        for _ in 0..2 {
+            physics_simulation.set_player_position(player, walked + Vector3::new(10.0, 0.0, 0.0));
+            anti_cheat.tick(&mut physics_simulation, 0.05);
+            assert_eq!(physics_simulation.get_state().physics_state.players[&player].aabb.pos, walked);
+        }
+        let kicks = anti_cheat.drain_kicks();
+        assert_eq!(kicks.len(), 1);
+        assert_eq!(kicks[0].0, player);
+    }
+}
@@ -0,0 +1,243 @@
+//! Load test of the server: runs the server with many headless bots that walk around randomly and place and break
+//! blocks, then prints a summary of the server performance.
+//!
+//! Usage: `bot [bots] [seconds] [render distance]`, from the folder that contains the `data` of the game.
+//! The bots play in the `world` save of that folder.
+
+use common::debug::{DebugInfo, DebugInfoPart};
+use common::network::dummy::{new_server, DummyClient};
+use common::network::limits::{ConnectionLimits, LimitedServer};
+use common::network::messages::{ToClient, ToServer};
+use common::network::{Client, ClientEvent};
+use common::player::{CharacterModel, PlayerId, PlayerInput, PlayerProfile, RenderDistance, DEFAULT_CHARACTER_MODEL};
+use common::random::Random;
+use common::time::{FixedTimestep, TICK_DURATION};
+use nalgebra::Vector3;
+use server::launch_server;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_BOTS: usize = 10;
+const DEFAULT_SECONDS: u64 = 60;
+const DEFAULT_RENDER_DISTANCE: u64 = 8;
+/// Number of ticks between two pings of a bot
+const PING_INTERVAL: u64 = 60;
+/// Probability that a bot turns during a tick
+const TURN_PROBABILITY: f64 = 1.0 / 120.0;
+/// Probability that a bot places or breaks a block during a tick
+const ACTION_PROBABILITY: f64 = 1.0 / 60.0;
+
+struct Bot {
+    client: DummyClient,
+    name: String,
+    id: Option<PlayerId>,
+    position: Option<Vector3<f64>>,
+    input: PlayerInput,
+    random: Random,
+    ticks: u64,
+    physics_updates: u64,
+    chunks: u64,
+    last_ping: Option<(u32, Instant)>,
+    round_trip_times: Vec<Duration>,
+    kicked: Option<String>,
+}
+
+impl Bot {
+    fn new(client: DummyClient, index: usize) -> Self {
+        Self {
+            client,
+            name: format!("Bot {}", index),
+            id: None,
+            position: None,
+            input: PlayerInput {
+                key_move_forward: true,
+                ..Default::default()
+            },
+            random: Random::new(index as i32),
+            ticks: 0,
+            physics_updates: 0,
+            chunks: 0,
+            last_ping: None,
+            round_trip_times: Vec::new(),
+            kicked: None,
+        }
+    }
+
+    fn tick(&mut self, render_distance: u64) {
+        if self.kicked.is_some() {
+            return;
+        }
+        loop {
+            match self.client.receive_event() {
+                ClientEvent::NoEvent | ClientEvent::Disconnected => break,
+                ClientEvent::Connected => {
+                    self.client.send(ToServer::SetProfile(PlayerProfile {
+                        name: self.name.clone(),
+                        model: CharacterModel::Builtin(DEFAULT_CHARACTER_MODEL.to_owned()),
+                    }));
+                    self.client.send(ToServer::SetRenderDistance(RenderDistance {
+                        x_max: render_distance,
+                        x_min: render_distance,
+                        y_max: render_distance,
+                        y_min: render_distance,
+                        z_max: render_distance,
+                        z_min: render_distance,
+                    }));
+                }
+                ClientEvent::ServerMessage(message) => match message {
+                    ToClient::CurrentId(id) => self.id = Some(id),
+                    ToClient::UpdatePhysics(state) => {
+                        self.physics_updates += 1;
+                        if let Some(player) = self.id.and_then(|id| state.physics_state.players.get(&id)) {
+                            self.position = Some(player.aabb.pos);
+                        }
+                    }
+                    ToClient::Chunk(_, _, _) => self.chunks += 1,
+                    ToClient::Pong(ping) => {
+                        if let Some((last_ping, sent)) = self.last_ping {
+                            if last_ping == ping {
+                                self.round_trip_times.push(sent.elapsed());
+                                self.last_ping = None;
+                            }
+                        }
+                    }
+                    ToClient::Kicked(reason) => {
+                        println!("{} was kicked: {}", self.name, reason);
+                        self.kicked = Some(reason);
+                        return;
+                    }
+                    _ => (),
+                },
+            }
+        }
+
+        self.ticks += 1;
+        // Walk in a random horizontal direction, sometimes up or down
+        if self.random.next_f64() < TURN_PROBABILITY {
+            self.input.yaw = self.random.next_f64() * 360.0;
+            let vertical = self.random.next_f64();
+            self.input.key_move_up = vertical < 0.2;
+            self.input.key_move_down = vertical > 0.8;
+        }
+        self.client.send(ToServer::UpdateInput(self.input));
+        if let Some(position) = self.position {
+            if self.random.next_f64() < ACTION_PROBABILITY {
+                // Look at the ground in front of the bot
+                let (yaw, pitch) = (self.input.yaw, -60.0);
+                if self.random.next_f64() < 0.5 {
+                    self.client.send(ToServer::PlaceBlock(position, yaw, pitch));
+                } else {
+                    self.client.send(ToServer::BreakBlock(position, yaw, pitch));
+                }
+            }
+        }
+        if self.ticks.is_multiple_of(PING_INTERVAL) {
+            let ping = (self.ticks / PING_INTERVAL) as u32;
+            self.last_ping = Some((ping, Instant::now()));
+            self.client.send(ToServer::Ping(ping));
+        }
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], index: usize, default: T) -> T {
+    match args.get(index) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| panic!("Invalid argument {:?}", arg)),
+        None => default,
+    }
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let bot_count = parse_arg(&args, 0, DEFAULT_BOTS);
+    let seconds = parse_arg(&args, 1, DEFAULT_SECONDS);
+    let render_distance = parse_arg(&args, 2, DEFAULT_RENDER_DISTANCE);
+
+    // Collect the performance breakdown of the server loop
+    let mut debug_info = DebugInfo::new_current();
+
+    let (connector, server) = new_server();
+    let server = LimitedServer::new(server, ConnectionLimits::default());
+    let stop = Arc::new(AtomicBool::new(false));
+    let server_stop = stop.clone();
+    let server_thread = std::thread::spawn(move || {
+        if let Err(e) = launch_server(Box::new(server), server_stop) {
+            eprintln!("An error occurred while running the server: {:#}", e);
+            std::process::exit(1);
+        }
+    });
+
+    println!(
+        "Running {} bots for {} seconds with a render distance of {} chunks",
+        bot_count, seconds, render_distance
+    );
+    let mut bots = (0..bot_count).map(|i| Bot::new(connector.connect(), i)).collect::<Vec<_>>();
+    let start = Instant::now();
+    let duration = Duration::from_secs(seconds);
+    let mut timestep = FixedTimestep::new(TICK_DURATION);
+    let mut slowest_tick = Duration::ZERO;
+    while start.elapsed() < duration {
+        timestep.update();
+        if !timestep.consume_tick() {
+            std::thread::sleep(timestep.time_until_next_tick());
+            continue;
+        }
+        let tick_start = Instant::now();
+        for bot in bots.iter_mut() {
+            bot.tick(render_distance);
+        }
+        slowest_tick = slowest_tick.max(tick_start.elapsed());
+        // Don't let the debug info of the server pile up
+        debug_info.get_debug_info();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    // The server saves the world of the bots before stopping
+    stop.store(true, Ordering::Relaxed);
+    let _ = server_thread.join();
+
+    println!();
+    println!("Bots: {} ({} kicked)", bot_count, bots.iter().filter(|bot| bot.kicked.is_some()).count());
+    println!("Duration: {:.1} s", elapsed);
+    let updates_per_second = bots.iter().map(|bot| bot.physics_updates as f64 / elapsed).collect::<Vec<_>>();
+    println!(
+        "Server tick rate seen by the bots: {:.1} ticks/s on average, {:.1} ticks/s for the slowest bot (target {:.0})",
+        updates_per_second.iter().sum::<f64>() / bot_count.max(1) as f64,
+        updates_per_second.iter().cloned().fold(f64::INFINITY, f64::min),
+        1.0 / TICK_DURATION.as_secs_f64(),
+    );
+    let chunks = bots.iter().map(|bot| bot.chunks).sum::<u64>();
+    println!(
+        "Chunks streamed: {} ({:.1} chunks/s, {:.1} per bot)",
+        chunks,
+        chunks as f64 / elapsed,
+        chunks as f64 / bot_count.max(1) as f64,
+    );
+    let round_trip_times = bots.iter().flat_map(|bot| bot.round_trip_times.iter().cloned()).collect::<Vec<_>>();
+    if !round_trip_times.is_empty() {
+        let average = round_trip_times.iter().sum::<Duration>() / round_trip_times.len() as u32;
+        let max = round_trip_times.iter().max().unwrap();
+        println!("Round-trip time: {} ms on average, {} ms at most", average.as_millis(), max.as_millis());
+    }
+    let received = bots.iter().map(|bot| bot.client.stats().total_received()).fold((0, 0), |(messages, bytes), stats| {
+        (messages + stats.messages, bytes + stats.bytes)
+    });
+    println!(
+        "Received by the bots: {} messages, {:.1} KB/s",
+        received.0,
+        received.1 as f64 / 1000.0 / elapsed
+    );
+    println!("Slowest tick of the bots themselves: {} ms", slowest_tick.as_millis());
+
+    let sections = debug_info.get_debug_info();
+    if let Some(DebugInfoPart::PerfBreakdown(_, parts)) = sections.get("Server").and_then(|section| section.2.get("mainloop")) {
+        println!("Server loop breakdown:");
+        let mut parts = parts.clone();
+        parts.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (name, fraction) in parts.iter().filter(|(_, fraction)| *fraction >= 0.001) {
+            println!("  {:>5.1}% {}", fraction * 100.0, name);
+        }
+    }
+    if let Some(DebugInfoPart::Message(message)) = sections.get("Chunks").and_then(|section| section.2.get("server")) {
+        print!("{}", message);
+    }
+}
@@ -0,0 +1,56 @@
+//! The cracks of the blocks being mined, seen by the players nearby.
+//!
+//! The progress of the mining is turned into a damage stage for each block. Only the stages that changed are sent, to
+//! the players close enough to see them, and a block that is no longer being mined goes back to the stage 0.
+
+use common::world::{BlockPos, BLOCK_DAMAGE_STAGES};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Distance up to which the players see the cracks, in blocks
+pub const BLOCK_DAMAGE_DISTANCE: f64 = 64.0;
+
+#[derive(Default)]
+pub struct BlockDamages {
+    /// The stage of each block being mined on the surface
+    stages: HashMap<BlockPos, u8>,
+    /// The stages that changed since the last update
+    changes: Vec<(BlockPos, u8)>,
+}
+
+impl BlockDamages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the blocks being mined with their progress from 0 to 1. The blocks that are not in the list are intact.
+    pub fn update(&mut self, progress: impl Iterator<Item = (BlockPos, f64)>) {
+        let mut stages = HashMap::new();
+        for (pos, progress) in progress.filter(|(_, progress)| *progress > 0.0) {
+            let stage = (progress * BLOCK_DAMAGE_STAGES as f64).ceil().min(BLOCK_DAMAGE_STAGES as f64) as u8;
+            // Several robots may mine the same block, the most advanced is shown
+            let entry = stages.entry(pos).or_insert(stage);
+            *entry = (*entry).max(stage);
+        }
+        for (&pos, &stage) in stages.iter() {
+            if self.stages.get(&pos) != Some(&stage) {
+                self.changes.push((pos, stage));
+            }
+        }
+        for &pos in self.stages.keys().filter(|pos| !stages.contains_key(pos)) {
+            self.changes.push((pos, 0));
+        }
+        self.stages = stages;
+    }
+
+    /// The stages that changed since the last call, 0 for the blocks that are intact again
+    pub fn drain_changes(&mut self) -> Vec<(BlockPos, u8)> {
+        std::mem::take(&mut self.changes)
+    }
+}
+
+/// Whether a player at `pos` sees the cracks of a block
+pub fn is_in_view(pos: Vector3<f64>, block: BlockPos) -> bool {
+    let center = Vector3::new(block.px as f64 + 0.5, block.py as f64 + 0.5, block.pz as f64 + 0.5);
+    (center - pos).norm() <= BLOCK_DAMAGE_DISTANCE
+}
@@ -0,0 +1,300 @@
+//! Protected regions: a player claims a box of blocks with `/claim`, then only the owner and the members of the claim
+//! can break, place and interact with the blocks inside. The claims are stored with the world.
+
+use crate::dimension::Dimensions;
+use crate::permissions::{Permissions, CLAIMS_BYPASS};
+use anyhow::{Context, Result};
+use common::claim::ClaimInfo;
+use common::player::PlayerId;
+use common::world::{BlockPos, DimensionId};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+/// Largest size of a claim along each axis, for the players who can't bypass the claims
+const MAX_CLAIM_SIZE: i64 = 256;
+/// Largest number of claims owned by a player who can't bypass the claims
+const MAX_CLAIMS_PER_PLAYER: usize = 5;
+
+/// A claim, stored by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Claim {
+    owner: String,
+    /// The name of the dimension, since the ids of the dimensions can change
+    dimension: String,
+    /// The corners of the claim, both included
+    min: (i64, i64, i64),
+    max: (i64, i64, i64),
+    /// The players who can build in the claim besides the owner
+    members: BTreeSet<String>,
+}
+
+impl Claim {
+    fn contains(&self, pos: BlockPos) -> bool {
+        (self.min.0..=self.max.0).contains(&pos.px)
+            && (self.min.1..=self.max.1).contains(&pos.py)
+            && (self.min.2..=self.max.2).contains(&pos.pz)
+    }
+
+    fn intersects(&self, min: BlockPos, max: BlockPos) -> bool {
+        self.min.0 <= max.px
+            && min.px <= self.max.0
+            && self.min.1 <= max.py
+            && min.py <= self.max.1
+            && self.min.2 <= max.pz
+            && min.pz <= self.max.2
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        self.owner == name || self.members.contains(name)
+    }
+}
+
+pub struct Claims {
+    path: PathBuf,
+    claims: BTreeMap<String, Claim>,
+    /// The name of every dimension, to compare with the dimension of the claims
+    dimension_names: HashMap<DimensionId, String>,
+    player_names: HashMap<PlayerId, String>,
+    /// The corners selected by each player with `/claim pos1` and `/claim pos2`
+    selections: HashMap<PlayerId, [Option<(DimensionId, BlockPos)>; 2]>,
+    /// The dimension whose claims were last sent to each player, None once the claims changed
+    sent: HashMap<PlayerId, Option<DimensionId>>,
+}
+
+impl Claims {
+    /// Load the claims from the world save, if it has some
+    pub fn load(path: PathBuf, dimensions: &Dimensions) -> Result<Self> {
+        let claims = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read claims")?;
+            ron::de::from_str(&buf).context("Failed to parse claims")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            claims,
+            dimension_names: dimensions.ids().map(|id| (id, dimensions.name(id).to_owned())).collect(),
+            player_names: HashMap::new(),
+            selections: HashMap::new(),
+            sent: HashMap::new(),
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.claims, Default::default())
+            .context("Failed to serialize claims")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write claims"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    /// Send the claims again to everyone
+    fn changed(&mut self) {
+        self.save();
+        for sent in self.sent.values_mut() {
+            *sent = None;
+        }
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name);
+        self.sent.insert(player, None);
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.player_names.remove(&player);
+        self.selections.remove(&player);
+        self.sent.remove(&player);
+    }
+
+    fn in_dimension(&self, dimension: DimensionId) -> impl Iterator<Item = (&String, &Claim)> {
+        let dimension_name = self.dimension_names.get(&dimension);
+        self.claims.iter().filter(move |(_, claim)| Some(&claim.dimension) == dimension_name)
+    }
+
+    /// Whether a player can break, place or interact with a block. The players whose name is not known yet can't
+    /// build in the claims.
+    pub fn is_allowed(&self, player: PlayerId, dimension: DimensionId, pos: BlockPos, permissions: &Permissions) -> bool {
+        let name = self.player_names.get(&player).map(String::as_str).unwrap_or_default();
+        permissions.has_permission(player, CLAIMS_BYPASS)
+            || self.in_dimension(dimension).all(|(_, claim)| !claim.contains(pos) || claim.allows(name))
+    }
+
+    /// A claim that intersects the box between the corners `a` and `b` and where the player can't build, if there is
+    /// one
+    pub fn find_protected(
+        &self,
+        player: PlayerId,
+        dimension: DimensionId,
+        a: BlockPos,
+        b: BlockPos,
+        permissions: &Permissions,
+    ) -> Option<&str> {
+        if permissions.has_permission(player, CLAIMS_BYPASS) {
+            return None;
+        }
+        let (min, max) = box_bounds(a, b);
+        let name = self.player_names.get(&player).map(String::as_str).unwrap_or_default();
+        self.in_dimension(dimension)
+            .find(|(_, claim)| claim.intersects(min, max) && !claim.allows(name))
+            .map(|(claim_name, _)| claim_name.as_str())
+    }
+
+    /// The claims of the dimension of a player, if they must be sent to them because the claims or the dimension
+    /// changed
+    pub fn infos_to_send(&mut self, player: PlayerId, dimension: DimensionId) -> Option<Vec<ClaimInfo>> {
+        let sent = self.sent.get_mut(&player)?;
+        if *sent == Some(dimension) {
+            return None;
+        }
+        *sent = Some(dimension);
+        let name = self.player_names.get(&player).map(String::as_str).unwrap_or_default();
+        let infos = self.in_dimension(dimension).map(|(claim_name, claim)| ClaimInfo {
+            name: claim_name.clone(),
+            owner: claim.owner.clone(),
+            min: BlockPos::from(claim.min),
+            max: BlockPos::from(claim.max),
+            allowed: claim.allows(name),
+        });
+        Some(infos.collect())
+    }
+
+    /// Run `/claim` with the words after the command name, and return the text to show.
+    /// `position` is the dimension and the position of the player.
+    pub fn run_command<'a>(
+        &mut self,
+        player: PlayerId,
+        mut words: impl Iterator<Item = &'a str>,
+        position: Option<(DimensionId, Vector3<f64>)>,
+        permissions: &Permissions,
+    ) -> String {
+        let name = match self.player_names.get(&player) {
+            Some(name) => name.clone(),
+            None => return "You can't claim yet".to_owned(),
+        };
+        let bypass = permissions.has_permission(player, CLAIMS_BYPASS);
+        match (words.next(), words.next(), words.next()) {
+            (Some(corner @ ("pos1" | "pos2")), None, None) => {
+                let (dimension, pos) = match position {
+                    Some((dimension, pos)) => (dimension, BlockPos::from(pos)),
+                    None => return "You can't claim yet".to_owned(),
+                };
+                let index = if corner == "pos1" { 0 } else { 1 };
+                self.selections.entry(player).or_default()[index] = Some((dimension, pos));
+                format!("Corner {} set to {} {} {}", index + 1, pos.px, pos.py, pos.pz)
+            }
+            (Some("create"), Some(claim_name), None) => {
+                let (dimension, a, b) = match self.selections.get(&player) {
+                    Some([Some((dimension, a)), Some((other_dimension, b))]) if dimension == other_dimension => {
+                        (*dimension, *a, *b)
+                    }
+                    Some([Some(_), Some(_)]) => return "The corners are in different dimensions".to_owned(),
+                    _ => return "Select the corners first with /claim pos1 and /claim pos2".to_owned(),
+                };
+                if self.claims.contains_key(claim_name) {
+                    return format!("The claim {} already exists", claim_name);
+                }
+                let (min, max) = box_bounds(a, b);
+                if !bypass {
+                    if [max.px - min.px, max.py - min.py, max.pz - min.pz].iter().any(|&size| size >= MAX_CLAIM_SIZE) {
+                        return format!("A claim can't be larger than {} blocks", MAX_CLAIM_SIZE);
+                    }
+                    if self.claims.values().filter(|claim| claim.owner == name).count() >= MAX_CLAIMS_PER_PLAYER {
+                        return format!("You can't have more than {} claims", MAX_CLAIMS_PER_PLAYER);
+                    }
+                    if let Some((other, _)) = self.in_dimension(dimension).find(|(_, claim)| claim.intersects(min, max)) {
+                        return format!("The claim would overlap the claim {}", other);
+                    }
+                }
+                let claim = Claim {
+                    owner: name,
+                    dimension: self.dimension_names[&dimension].clone(),
+                    min: (min.px, min.py, min.pz),
+                    max: (max.px, max.py, max.pz),
+                    members: BTreeSet::new(),
+                };
+                self.claims.insert(claim_name.to_owned(), claim);
+                self.selections.remove(&player);
+                self.changed();
+                format!(
+                    "Claimed {} from {} {} {} to {} {} {}",
+                    claim_name, min.px, min.py, min.pz, max.px, max.py, max.pz
+                )
+            }
+            (Some(action @ ("delete" | "add" | "remove")), Some(claim_name), member) => {
+                let claim = match self.claims.get_mut(claim_name) {
+                    Some(claim) if claim.owner == name || bypass => claim,
+                    Some(_) => return format!("You don't own the claim {}", claim_name),
+                    None => return format!("Unknown claim: {}", claim_name),
+                };
+                let output = match (action, member) {
+                    ("delete", None) => {
+                        self.claims.remove(claim_name);
+                        format!("Deleted the claim {}", claim_name)
+                    }
+                    // The names can contain spaces
+                    ("add", Some(member)) => {
+                        let member = std::iter::once(member).chain(words).collect::<Vec<_>>().join(" ");
+                        claim.members.insert(member.clone());
+                        format!("{} can now build in {}", member, claim_name)
+                    }
+                    ("remove", Some(member)) => {
+                        let member = std::iter::once(member).chain(words).collect::<Vec<_>>().join(" ");
+                        if !claim.members.remove(&member) {
+                            return format!("{} is not a member of {}", member, claim_name);
+                        }
+                        format!("{} can't build in {} anymore", member, claim_name)
+                    }
+                    _ => return "Usage: /claim delete <claim>, or /claim add|remove <claim> <player>".to_owned(),
+                };
+                self.changed();
+                output
+            }
+            (Some("list"), None, None) => {
+                let lines = self
+                    .claims
+                    .iter()
+                    .filter(|(_, claim)| bypass || claim.allows(&name))
+                    .map(|(claim_name, claim)| {
+                        let mut line = format!(
+                            "{} by {} in {}, from {:?} to {:?}",
+                            claim_name, claim.owner, claim.dimension, claim.min, claim.max
+                        );
+                        if !claim.members.is_empty() {
+                            let members = claim.members.iter().cloned().collect::<Vec<_>>();
+                            line.push_str(&format!(", members: {}", members.join(", ")));
+                        }
+                        line
+                    })
+                    .collect::<Vec<_>>();
+                if lines.is_empty() {
+                    "You have no claims".to_owned()
+                } else {
+                    lines.join("\n")
+                }
+            }
+            (None, _, _) => {
+                let (dimension, pos) = match position {
+                    Some((dimension, pos)) => (dimension, BlockPos::from(pos)),
+                    None => return "You are nowhere yet".to_owned(),
+                };
+                match self.in_dimension(dimension).find(|(_, claim)| claim.contains(pos)) {
+                    Some((claim_name, claim)) => format!("You are in the claim {} of {}", claim_name, claim.owner),
+                    None => "You are not in a claim".to_owned(),
+                }
+            }
+            _ => "Usage: /claim [pos1|pos2|list], /claim create|delete <claim>, or /claim add|remove <claim> <player>"
+                .to_owned(),
+        }
+    }
+}
+
+/// The smallest and the largest corner of the box between two corners
+fn box_bounds(a: BlockPos, b: BlockPos) -> (BlockPos, BlockPos) {
+    let min = BlockPos::from((a.px.min(b.px), a.py.min(b.py), a.pz.min(b.pz)));
+    let max = BlockPos::from((a.px.max(b.px), a.py.max(b.py), a.pz.max(b.pz)));
+    (min, max)
+}
@@ -0,0 +1,174 @@
+//! Melee combat between the players, the robots they control and the creatures.
+//!
+//! A hit lands on the closest target in reach along the aim of the attacker, unless a block is in the way. It takes
+//! health from the players and the creatures and energy from the robots, pushes the target away, and leaves it
+//! invulnerable for a short time. The destroyed creatures drop their loot, and the players who run out of health
+//! respawn like after any other death.
+
+use crate::dropped_item::DroppedItems;
+use crate::explosion::explosion_strength;
+use crate::mob::Mobs;
+use crate::robot::Robots;
+use crate::settings::CombatSettings;
+use crate::survival::Survival;
+use crate::world::World;
+use common::{
+    combat::Combatant,
+    physics::{aabb::AABB, simulation::ServerPhysicsSimulation, BlockContainer},
+    player::{PlayerId, MELEE_REACH},
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Distance between the points checked along a line of sight
+const SIGHT_STEP: f64 = 0.25;
+/// Health lost for each point of explosion strength
+pub const HEALTH_PER_EXPLOSION_STRENGTH: f64 = 10.0;
+/// Vertical part of the knockback relative to its horizontal part, which lifts the targets from the ground
+const KNOCKBACK_LIFT: f64 = 0.4;
+
+/// A hit about to be applied to its target
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub attacker: Combatant,
+    pub target: Combatant,
+    pub damage: f64,
+    /// Direction of the blow, in which the target is pushed
+    pub direction: Vector3<f64>,
+    /// What a player is told if the hit kills them
+    pub cause: String,
+}
+
+pub struct Combat {
+    settings: CombatSettings,
+    /// Seconds before each entity that was hit can be hurt again
+    invulnerable: HashMap<Combatant, f64>,
+    /// The damage taken since the last time it was sent to the players: the target, the amount and the direction it
+    /// came from
+    damages: Vec<(Combatant, f64, Option<Vector3<f64>>)>,
+}
+
+impl Combat {
+    pub fn new(settings: CombatSettings) -> Self {
+        Self {
+            settings,
+            invulnerable: HashMap::new(),
+            damages: Vec::new(),
+        }
+    }
+
+    /// The hit of a player or of a controlled robot
+    pub fn melee_hit(&self, attacker: Combatant, target: Combatant, direction: Vector3<f64>, cause: String) -> Hit {
+        let damage = match attacker {
+            Combatant::Robot(_) => self.settings.robot_damage,
+            _ => self.settings.player_damage,
+        };
+        Hit { attacker, target, damage, direction, cause }
+    }
+
+    /// Apply a hit, unless its target is still invulnerable. Return whether it landed.
+    pub fn apply_hit(
+        &mut self,
+        hit: Hit,
+        physics_simulation: &mut ServerPhysicsSimulation,
+        survival: &mut Survival,
+        robots: &mut Robots,
+        mobs: &mut Mobs,
+        dropped_items: &mut DroppedItems,
+    ) -> bool {
+        if self.invulnerable.contains_key(&hit.target) {
+            return false;
+        }
+        self.invulnerable.insert(hit.target, self.settings.invulnerability);
+        let knockback = self.knockback(hit.direction);
+        let hurt = match hit.target {
+            Combatant::Player(player) => {
+                physics_simulation.apply_knockback(player, knockback);
+                survival.damage(player, hit.damage, hit.cause)
+            }
+            Combatant::Robot(robot) => robots.apply_hit(robot, hit.damage, dropped_items),
+            Combatant::Mob(mob) => {
+                for (pos, stack) in mobs.apply_hit(mob, hit.damage, hit.attacker, knockback) {
+                    dropped_items.spawn(pos, stack);
+                }
+                true
+            }
+        };
+        if hurt {
+            self.damages.push((hit.target, hit.damage, Some(-hit.direction)));
+        }
+        true
+    }
+
+    /// Hurt the creatures and hurt and push away the players caught in an explosion.
+    /// `players` are the ids and the centers of the players in its dimension, and `mobs` the creatures if it is on
+    /// the surface.
+    pub fn apply_explosion(
+        &mut self,
+        center: Vector3<f64>,
+        power: f64,
+        players: &[(PlayerId, Vector3<f64>)],
+        physics_simulation: &mut ServerPhysicsSimulation,
+        survival: &mut Survival,
+        mobs: Option<&mut Mobs>,
+    ) {
+        for (mob, damage, pos) in mobs.map(|mobs| mobs.apply_explosion(center, power)).unwrap_or_default() {
+            self.damages.push((Combatant::Mob(mob), damage, Some(center - pos)));
+        }
+        for &(player, pos) in players {
+            let strength = explosion_strength(center, power, pos);
+            if strength > 0.0 {
+                let cause = "You were caught in an explosion".to_owned();
+                let damage = strength * HEALTH_PER_EXPLOSION_STRENGTH;
+                if survival.damage(player, damage, cause) {
+                    self.damages.push((Combatant::Player(player), damage, Some(center - pos)));
+                }
+                physics_simulation.apply_knockback(player, self.knockback(pos - center) * (strength / power));
+            }
+        }
+    }
+
+    /// The damage taken since the last call, to send to the players
+    pub fn drain_damages(&mut self) -> Vec<(Combatant, f64, Option<Vector3<f64>>)> {
+        std::mem::take(&mut self.damages)
+    }
+
+    /// The impulse given to a target hit in some direction
+    fn knockback(&self, direction: Vector3<f64>) -> Vector3<f64> {
+        let horizontal = Vector3::new(direction.x, 0.0, direction.z);
+        let horizontal = if horizontal.norm() > 1e-9 { horizontal.normalize() } else { Vector3::zeros() };
+        (horizontal + Vector3::new(0.0, KNOCKBACK_LIFT, 0.0)) * self.settings.knockback
+    }
+
+    /// Let the invulnerability of the entities that were hit wear off
+    pub fn tick(&mut self, dt: f64) {
+        self.invulnerable.retain(|_, remaining| {
+            *remaining -= dt;
+            *remaining > 0.0
+        });
+    }
+}
+
+/// Find what an attacker aiming from `origin` in the normalized direction `dir` hits: the closest of the `candidates`
+/// in reach, unless a block is in the way
+pub fn find_melee_target(
+    world: &World,
+    origin: Vector3<f64>,
+    dir: Vector3<f64>,
+    candidates: &[(Combatant, AABB)],
+) -> Option<Combatant> {
+    candidates
+        .iter()
+        .filter_map(|(target, aabb)| Some((*target, aabb.intersect_ray(origin, dir)?)))
+        .filter(|&(_, distance)| distance <= MELEE_REACH && in_sight(world, origin, origin + dir * distance))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(target, _)| target)
+}
+
+/// Whether there are only blocks that are not full between two positions
+pub fn in_sight(world: &World, from: Vector3<f64>, to: Vector3<f64>) -> bool {
+    let delta = to - from;
+    let steps = (delta.norm() / SIGHT_STEP).ceil() as usize;
+    (1..steps).all(|i| !world.is_block_full(BlockPos::from(from + delta * (i as f64 / steps as f64))))
+}
@@ -0,0 +1,272 @@
+//! Commands sent by the players, such as `/stats`, and by the remote administration tools
+
+use crate::claims::Claims;
+use crate::dimension::Dimensions;
+use crate::gamerules::GameRules;
+use crate::history::BlockHistory;
+use crate::permissions::{command_node, Permissions, WORLD_EDIT};
+use crate::pregen::Pregenerator;
+use crate::respawn::RespawnPoints;
+use crate::statistics::Statistics;
+use crate::storm::DustStorms;
+use crate::structure::Structures;
+use crate::teleport::Teleports;
+use crate::world::World;
+use crate::PlayerData;
+use common::network::{messages::ToClient, Server};
+use common::player::PlayerId;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// The name and description of the commands, sent to the clients for the completion in their console
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("claim", "Protect an area: /claim pos1, /claim pos2, then /claim create <name>. Also /claim list, /claim delete <name>, /claim add|remove <name> <player>"),
+    ("debug", "Show the state of the server: /debug chunks for the memory of the loaded chunks"),
+    ("gamerule", "Show or change the game rules: /gamerule [rule] [true|false], or /gamerule player <name> <capability> <true|false>"),
+    ("group", "Show or change the permission group of a player: /group <player> [group]"),
+    ("history", "Show the world as it was some minutes ago: /history <minutes>, or /history now"),
+    ("home", "Teleport to your home, or save your position as your home with /home set"),
+    ("locate", "Find the nearest structure of the world generator, such as a tree: /locate <structure>"),
+    ("pregen", "Generate and save the chunks around the spawn: /pregen <radius in chunks>, /pregen to see the progress, or /pregen cancel"),
+    ("rollback", "Revert the blocks changed by a player in the last minutes: /rollback <player> <minutes>"),
+    ("spawnpoint", "Show, set at your position or clear the respawn point of a player: /spawnpoint <player> [here|clear]"),
+    ("stats", "Show your statistics"),
+    ("timelapse", "Show the changes of the last minutes in a few seconds: /timelapse <minutes> [seconds]"),
+    ("tp", "Teleport to a position or a player: /tp <x> <y> <z>, or /tp <player>"),
+];
+
+/// Seconds taken by a time-lapse by default
+const DEFAULT_TIMELAPSE_DURATION: f64 = 10.0;
+/// Largest coordinate accepted by `/tp`
+const MAX_COORDINATE: f64 = 1_000_000.0;
+
+/// The name and description of the remote administration commands
+pub const ADMIN_COMMANDS: &[(&str, &str)] = &[
+    ("gamerule [rule] [true|false]", "Show or change the game rules, or the capabilities of a player with gamerule player <name> <capability> <true|false>"),
+    ("group [player] [group]", "Show or change the permission group of a player, or list the groups"),
+    ("help", "List the commands"),
+    ("players", "List the connected players"),
+    ("pregen [radius|cancel]", "Generate and save the chunks within a radius in chunks around the spawn, or show the progress"),
+    ("rollback <player> <minutes>", "Revert the blocks changed by a player in the last minutes"),
+    ("say <message>", "Send a message to all the players"),
+    ("stats <player>", "Show the statistics of a player"),
+    ("storm [start [seconds]|stop]", "Start or stop a dust storm, or show when the next one rises"),
+];
+
+/// Run a command of a player, and return the text to show to the player
+#[allow(clippy::too_many_arguments)]
+pub fn run_command(
+    player: PlayerId,
+    command: &str,
+    statistics: &Statistics,
+    history: Option<&mut BlockHistory>,
+    teleports: &mut Teleports,
+    respawn_points: &mut RespawnPoints,
+    game_rules: &mut GameRules,
+    permissions: &mut Permissions,
+    claims: &mut Claims,
+    structures: &Structures,
+    dimensions: &mut Dimensions,
+    pregenerator: &mut Pregenerator,
+) -> String {
+    let mut words = command.trim().trim_start_matches('/').split_whitespace();
+    let name = words.next();
+    if let Some(name) = name.filter(|&name| COMMANDS.iter().any(|&(command, _)| command == name)) {
+        if !permissions.has_permission(player, &command_node(name)) {
+            return format!("You are not allowed to use /{}", name);
+        }
+    }
+    match (name, history) {
+        (Some("claim"), _) => claims.run_command(player, words, teleports.controlled_position(player), permissions),
+        (Some("debug"), _) => match words.next() {
+            Some("chunks") => dimensions.chunk_memory_summary().join("\n"),
+            _ => "Usage: /debug chunks".to_owned(),
+        },
+        (Some("gamerule"), _) => game_rules.run_command(words),
+        (Some("locate"), _) => structures.run_command(words, teleports.controlled_position(player)),
+        (Some("group"), _) => permissions.run_command(&words.collect::<Vec<_>>().join(" ")),
+        (Some("pregen"), _) => {
+            pregenerator.run_command(Some(player), &words.collect::<Vec<_>>().join(" "), dimensions.surface_mut())
+        }
+        (Some("spawnpoint"), _) => {
+            let argument = words.collect::<Vec<_>>().join(" ");
+            respawn_points.run_command(&argument, teleports.controlled_position(player), dimensions)
+        }
+        (Some("stats"), _) => match statistics.get(player) {
+            Some(player_statistics) => player_statistics.summary().join("\n"),
+            None => "No statistics yet".to_owned(),
+        },
+        (Some("history"), None) | (Some("timelapse"), None) | (Some("rollback"), None) => {
+            "The history of the blocks is disabled on this server".to_owned()
+        }
+        (Some("history"), Some(history)) => match words.next() {
+            Some("now") if history.show_present(player) => "Going back to the present".to_owned(),
+            Some("now") => "You are already in the present".to_owned(),
+            Some(minutes) => match parse_minutes(minutes, history) {
+                Ok(seconds) => {
+                    let authors = history.show_past(player, seconds, dimensions.surface());
+                    let mut output =
+                        format!("Showing the world as it was {} minutes ago, type /history now to come back", minutes);
+                    if !authors.is_empty() {
+                        output.push_str(&format!("\nChanged since then by: {}", authors.join(", ")));
+                    }
+                    output
+                }
+                Err(error) => error,
+            },
+            None => "Usage: /history <minutes>, or /history now".to_owned(),
+        },
+        (Some("timelapse"), Some(history)) => {
+            let minutes = match words.next() {
+                Some(minutes) => minutes,
+                None => return "Usage: /timelapse <minutes> [seconds]".to_owned(),
+            };
+            let duration = match words.next().map(str::parse::<f64>) {
+                None => DEFAULT_TIMELAPSE_DURATION,
+                Some(Ok(duration)) if duration > 0.0 && duration.is_finite() => duration,
+                Some(_) => return "The duration must be a positive number of seconds".to_owned(),
+            };
+            match parse_minutes(minutes, history) {
+                Ok(seconds) => {
+                    history.show_timelapse(player, seconds, duration);
+                    format!("Showing the last {} minutes in {} seconds", minutes, duration)
+                }
+                Err(error) => error,
+            }
+        }
+        (Some("rollback"), Some(_)) if !permissions.has_permission(player, WORLD_EDIT) => {
+            "You are not allowed to edit the world".to_owned()
+        }
+        (Some("rollback"), Some(history)) => {
+            rollback(&words.collect::<Vec<_>>().join(" "), history, dimensions.surface_mut())
+        }
+        (Some("tp"), _) => {
+            let arguments = words.collect::<Vec<_>>();
+            let coordinates = arguments.iter().map(|word| word.parse::<f64>()).collect::<Result<Vec<_>, _>>();
+            let (dimension, destination) = match coordinates {
+                _ if arguments.is_empty() => return "Usage: /tp <x> <y> <z>, or /tp <player>".to_owned(),
+                Ok(coordinates) if coordinates.len() == 3 => {
+                    if coordinates.iter().any(|coordinate| !coordinate.is_finite() || coordinate.abs() > MAX_COORDINATE) {
+                        return format!("The coordinates must be between -{} and {}", MAX_COORDINATE, MAX_COORDINATE);
+                    }
+                    // The coordinates are in the current dimension
+                    let dimension = match teleports.controlled_position(player) {
+                        Some((dimension, _)) => dimension,
+                        None => return "You can't be teleported yet".to_owned(),
+                    };
+                    (dimension, Vector3::new(coordinates[0], coordinates[1], coordinates[2]))
+                }
+                // The names can contain spaces
+                _ => {
+                    let name = arguments.join(" ");
+                    match teleports.find_player(&name).and_then(|other| teleports.controlled_position(other)) {
+                        Some(destination) => destination,
+                        None => return format!("{} is not connected", name),
+                    }
+                }
+            };
+            if teleports.teleport_controlled(player, dimension, destination) {
+                format!("Teleporting to {:.0} {:.0} {:.0}", destination.x, destination.y, destination.z)
+            } else {
+                "You can't be teleported yet".to_owned()
+            }
+        }
+        (Some("home"), _) => match words.next() {
+            Some("set") if teleports.set_home(player, dimensions) => "Home saved".to_owned(),
+            Some("set") => "Your home can't be saved yet".to_owned(),
+            Some(_) => "Usage: /home, or /home set".to_owned(),
+            None => match teleports.get_home(player, dimensions) {
+                Some((dimension, home)) if teleports.teleport_controlled(player, dimension, home) => {
+                    "Teleporting home".to_owned()
+                }
+                Some(_) => "You can't be teleported yet".to_owned(),
+                None => "You have no home yet, save your position with /home set".to_owned(),
+            },
+        },
+        (Some(name), _) => format!("Unknown command: {}", name),
+        (None, _) => "Empty command".to_owned(),
+    }
+}
+
+/// Parse a number of minutes in the past, within the retention of the history, and return it in seconds
+fn parse_minutes(minutes: &str, history: &BlockHistory) -> Result<f64, String> {
+    let max_minutes = history.retention() / 60.0;
+    match minutes.parse::<f64>() {
+        Ok(minutes) if minutes > 0.0 && minutes <= max_minutes => Ok(minutes * 60.0),
+        Ok(_) => Err(format!("The history goes back {} minutes", max_minutes)),
+        Err(_) => Err(format!("{} is not a number of minutes", minutes)),
+    }
+}
+
+/// Revert the blocks changed by a player, with the arguments `<player> <minutes>`
+fn rollback(argument: &str, history: &mut BlockHistory, world: &mut World) -> String {
+    // The names can contain spaces
+    let (player_name, minutes) = match argument.rsplit_once(' ') {
+        Some((player_name, minutes)) => (player_name.trim(), minutes),
+        None => return "Usage: rollback <player> <minutes>".to_owned(),
+    };
+    match parse_minutes(minutes, history) {
+        Ok(seconds) => {
+            let blocks = history.rollback_changes(player_name, seconds, world);
+            let count = world.set_blocks(&blocks);
+            format!("Reverted {} blocks changed by {} in the last {} minutes", count, player_name, minutes)
+        }
+        Err(error) => error,
+    }
+}
+
+/// Run a command of the remote administration, and return its output
+#[allow(clippy::too_many_arguments)]
+pub fn run_admin_command(
+    command: &str,
+    players: &HashMap<PlayerId, PlayerData>,
+    statistics: &Statistics,
+    history: Option<&mut BlockHistory>,
+    game_rules: &mut GameRules,
+    permissions: &mut Permissions,
+    pregenerator: &mut Pregenerator,
+    dust_storms: &mut DustStorms,
+    world: &mut World,
+    server: &mut dyn Server,
+) -> String {
+    let command = command.trim();
+    let (name, argument) = match command.split_once(' ') {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command, ""),
+    };
+    match name {
+        "help" => ADMIN_COMMANDS
+            .iter()
+            .map(|(name, description)| format!("{}: {}", name, description))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "gamerule" => game_rules.run_command(argument.split_whitespace()),
+        "group" => permissions.run_command(argument),
+        "pregen" => pregenerator.run_command(None, argument, world),
+        "players" => {
+            let mut lines = vec![format!("{} players connected", players.len())];
+            for (id, data) in players.iter() {
+                let name = data.profile.as_ref().map(|profile| profile.name.as_str()).unwrap_or("<unknown>");
+                lines.push(format!("{} (id {})", name, id.raw()));
+            }
+            lines.join("\n")
+        }
+        "say" if !argument.is_empty() => {
+            for &player in players.keys() {
+                server.send(player, ToClient::CommandOutput(format!("[Server] {}", argument)));
+            }
+            format!("Sent to {} players", players.len())
+        }
+        "rollback" => match history {
+            Some(history) => rollback(argument, history, world),
+            None => "The history of the blocks is disabled on this server".to_owned(),
+        },
+        "stats" if !argument.is_empty() => match statistics.get_by_name(argument) {
+            Some(player_statistics) => player_statistics.summary().join("\n"),
+            None => format!("No statistics for {}", argument),
+        },
+        "storm" => dust_storms.run_command(argument),
+        "say" | "stats" => "Missing argument, type help for the usage".to_owned(),
+        _ => format!("Unknown command: {}", name),
+    }
+}
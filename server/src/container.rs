@@ -0,0 +1,100 @@
+//! The containers that the players open on their inventory screen, the storage crates. The content of an open
+//! container is sent to the player each time it changes, and the container is closed when the player walks away or
+//! when it is destroyed.
+
+use crate::dimension::Dimensions;
+use crate::PlayerData;
+use common::{
+    block::BlockId,
+    inventory::{Inventory, STORAGE_CRATE_SLOTS},
+    network::{messages::ToClient, Server},
+    physics::simulation::ServerPhysicsSimulation,
+    player::PlayerId,
+    world::{BlockPos, DimensionId},
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Distance between the camera of a player and the center of the container they opened above which it is closed
+const CONTAINER_DISTANCE: f64 = 8.0;
+
+/// A container opened by a player
+struct OpenContainer {
+    dimension: DimensionId,
+    pos: BlockPos,
+    /// The content last sent to the player, None until it is sent
+    sent: Option<Inventory>,
+}
+
+pub struct Containers {
+    /// The block of the storage crates, None if the data has none
+    storage_block: Option<BlockId>,
+    open: HashMap<PlayerId, OpenContainer>,
+}
+
+impl Containers {
+    pub fn new(storage_block: Option<BlockId>) -> Self {
+        Self {
+            storage_block,
+            open: HashMap::new(),
+        }
+    }
+
+    pub fn is_container(&self, block: BlockId) -> bool {
+        self.storage_block == Some(block)
+    }
+
+    /// Open the container at `pos` for a player, closing the one they opened before
+    pub fn open(&mut self, player: PlayerId, dimension: DimensionId, pos: BlockPos) {
+        self.open.insert(player, OpenContainer { dimension, pos, sent: None });
+    }
+
+    pub fn close(&mut self, player: PlayerId) {
+        self.open.remove(&player);
+    }
+
+    /// The inventory of the container opened by a player, if there is one
+    pub fn get_open_mut<'a>(&self, player: PlayerId, dimensions: &'a mut Dimensions) -> Option<&'a mut Inventory> {
+        let open = self.open.get(&player)?;
+        Some(dimensions.get_mut(open.dimension).get_container_mut(open.pos, STORAGE_CRATE_SLOTS))
+    }
+
+    /// Send the content of the open containers that changed, and close the containers that the players can't reach
+    /// anymore or that were destroyed
+    pub fn send_updates(
+        &mut self,
+        dimensions: &mut Dimensions,
+        physics_simulation: &ServerPhysicsSimulation,
+        players: &HashMap<PlayerId, PlayerData>,
+        server: &mut dyn Server,
+    ) {
+        let storage_block = self.storage_block;
+        self.open.retain(|&player, open| {
+            let data = match players.get(&player) {
+                Some(data) => data,
+                // The player left
+                None => return false,
+            };
+            let camera = match physics_simulation.get_state().physics_state.players.get(&player) {
+                Some(physics_player) => physics_player.get_camera_position(),
+                None => return false,
+            };
+            let pos = open.pos;
+            let center = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+            let world = dimensions.get_mut(open.dimension);
+            let usable = data.dimension == open.dimension
+                && Some(world.get_block(pos)) == storage_block
+                && (center - camera).norm() <= CONTAINER_DISTANCE;
+            if !usable {
+                server.send(player, ToClient::CloseContainer);
+                return false;
+            }
+            let content = world.get_container_mut(pos, STORAGE_CRATE_SLOTS);
+            if open.sent.as_ref() != Some(content) {
+                open.sent = Some(content.clone());
+                server.send(player, ToClient::Container(pos, content.clone()));
+            }
+            true
+        });
+    }
+}
@@ -0,0 +1,57 @@
+//! Conveyor belts, moving the items that lie on top of them.
+
+use crate::logic::Signals;
+use crate::world::World;
+use common::{
+    block::{Block, BlockId, BlockType, Facing},
+    registry::Registry,
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+
+/// Speed of the belts in blocks per second
+const CONVEYOR_SPEED: f64 = 1.5;
+
+/// The conveyor blocks of the registry
+pub struct Conveyors {
+    /// The facing of each block if it is a conveyor, and whether it needs a signal to run
+    facings: Vec<Option<(Facing, bool)>>,
+}
+
+impl Conveyors {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let facings = block_registry
+            .iter()
+            .map(|(_, _, block)| match block.block_type {
+                BlockType::Conveyor { facing, requires_signal, .. } => Some((facing, requires_signal)),
+                _ => None,
+            })
+            .collect();
+        Self { facings }
+    }
+
+    /// Return the facing of the block if it is a conveyor
+    pub fn get_facing(&self, block: BlockId) -> Option<Facing> {
+        self.facings.get(block as usize).copied().flatten().map(|(facing, _)| facing)
+    }
+
+    /// Return the velocity given by the belt to an item resting on top of block `pos`, if that block is a running conveyor.
+    /// The item is also pulled towards the middle of the belt: this is what makes it follow corners
+    /// and what aligns the items coming from a side belt with the belt they merge into.
+    pub fn get_belt_velocity(&self, world: &World, signals: &Signals, below: BlockPos, item_pos: Vector3<f64>) -> Option<Vector3<f64>> {
+        let (facing, requires_signal) = self.facings.get(world.get_block(below) as usize).copied().flatten()?;
+        if requires_signal && !signals.is_powered(below) {
+            return None;
+        }
+        let (dx, dz) = facing.offset();
+        let center_x = below.px as f64 + 0.5;
+        let center_z = below.pz as f64 + 0.5;
+        let mut velocity = Vector3::new(dx as f64, 0.0, dz as f64) * CONVEYOR_SPEED;
+        if dx == 0 {
+            velocity.x = (center_x - item_pos.x).max(-1.0).min(1.0) * CONVEYOR_SPEED;
+        } else {
+            velocity.z = (center_z - item_pos.z).max(-1.0).min(1.0) * CONVEYOR_SPEED;
+        }
+        Some(velocity)
+    }
+}
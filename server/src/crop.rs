@@ -0,0 +1,121 @@
+//! Crops, growing in the greenhouses.
+//!
+//! Every crop that is not grown has a scheduled task to grow to its next stage. When the task runs, the crop only
+//! grows if it is lit enough and if the air above it is sealed, otherwise it tries again after the same time.
+
+use crate::scheduler::{seconds_to_ticks, Scheduler, Task};
+use crate::world::World;
+use common::{
+    block::{Block, BlockId, BlockType},
+    physics::BlockContainer,
+    registry::Registry,
+    world::BlockPos,
+};
+use std::collections::{HashSet, VecDeque};
+
+/// Largest volume of air around a crop in a greenhouse. The air of a larger volume is not sealed, which also covers
+/// the air that reaches the unloaded chunks.
+const MAX_GREENHOUSE_VOLUME: usize = 4096;
+/// Seconds before trying again to grow a crop whose chunk is not loaded
+const UNLOADED_CROP_DELAY: f64 = 30.0;
+
+/// The crop information of a block
+#[derive(Debug, Clone, Copy)]
+struct CropBlock {
+    /// The block of the next stage, None if the crop is grown
+    next_stage: Option<BlockId>,
+    /// Ticks needed to grow to the next stage
+    growth_ticks: u64,
+    min_light: u8,
+}
+
+/// All the crops of the world
+pub struct Crops {
+    /// The crop information of each block, if it is a crop
+    crop_blocks: Vec<Option<CropBlock>>,
+}
+
+impl Crops {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let mut crop_blocks = vec![None; block_registry.get_number_of_ids() as usize];
+        for id in 0..block_registry.get_number_of_ids() {
+            let block = block_registry.get_value_by_id(id).unwrap();
+            if let BlockType::Crop { stage_textures, growth_time, min_light, .. } = &block.block_type {
+                let stages = std::iter::once(id).chain((1..stage_textures.len()).map(|stage| {
+                    block_registry
+                        .get_id_by_name(&format!("{}:{}", block.name, stage))
+                        .expect("Crop has no stage block")
+                }));
+                let stages = stages.collect::<Vec<_>>();
+                for (stage, &stage_id) in stages.iter().enumerate() {
+                    crop_blocks[stage_id as usize] = Some(CropBlock {
+                        next_stage: stages.get(stage + 1).map(|&next| next as BlockId),
+                        growth_ticks: seconds_to_ticks(*growth_time),
+                        min_light: *min_light,
+                    });
+                }
+            }
+        }
+        Self { crop_blocks }
+    }
+
+    fn get_crop(&self, world: &World, pos: BlockPos) -> Option<CropBlock> {
+        self.crop_blocks.get(world.get_block(pos) as usize).copied().flatten()
+    }
+
+    /// Take block modifications into account: the crops that were planted or that grew are scheduled to grow
+    pub fn update_blocks(&self, world: &World, scheduler: &mut Scheduler, changed_blocks: &[BlockPos]) {
+        for pos in changed_blocks {
+            if let Some(CropBlock { next_stage: Some(_), growth_ticks, .. }) = self.get_crop(world, *pos) {
+                let task = Task::GrowCrop(*pos);
+                if !scheduler.is_scheduled(&task) {
+                    scheduler.run_in_ticks(growth_ticks, task);
+                }
+            }
+        }
+    }
+
+    /// Grow the crop at `pos` to its next stage if it is in a lit greenhouse, or try again later
+    pub fn grow(&self, world: &mut World, scheduler: &mut Scheduler, pos: BlockPos) {
+        if world.get_chunk(pos.containing_chunk_pos()).is_none() {
+            scheduler.run_in_ticks(seconds_to_ticks(UNLOADED_CROP_DELAY), Task::GrowCrop(pos));
+            return;
+        }
+        // The crop may have been broken or replaced since the task was scheduled
+        let (next_stage, crop) = match self.get_crop(world, pos) {
+            Some(crop @ CropBlock { next_stage: Some(next_stage), .. }) => (next_stage, crop),
+            _ => return,
+        };
+        let above = BlockPos::from((pos.px, pos.py + 1, pos.pz));
+        if world.get_light(above) >= crop.min_light && is_sealed(world, above) {
+            // Changing the block schedules the next stage
+            world.set_block(pos, next_stage);
+        } else {
+            scheduler.run_in_ticks(crop.growth_ticks, Task::GrowCrop(pos));
+        }
+    }
+}
+
+/// Whether the air at `start` is enclosed by full blocks. The open doors let the air out.
+fn is_sealed(world: &World, start: BlockPos) -> bool {
+    if world.is_block_full(start) {
+        return false;
+    }
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start);
+    queue.push_back(start);
+    while let Some(pos) = queue.pop_front() {
+        let neighbors = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+        for (dx, dy, dz) in neighbors {
+            let neighbor = BlockPos::from((pos.px + dx, pos.py + dy, pos.pz + dz));
+            if !world.is_block_full(neighbor) && visited.insert(neighbor) {
+                if visited.len() > MAX_GREENHOUSE_VOLUME {
+                    return false;
+                }
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    true
+}
@@ -0,0 +1,205 @@
+//! The dimensions of the server: the surface of Mars, and the lava caverns deep below. Each dimension is a separate
+//! world with its own generator and chunks, and the players move between them with the elevator blocks.
+//!
+//! The dropped items, the logic signals, the doors, the lamps and the explosions work in every dimension, each
+//! dimension having its own. The robots, the creatures, the crops, the meteors and the history of the block changes
+//! only exist on the surface for now.
+//!
+//! The vertical bounds of the dimensions are stored in the world meta, so that a save can make the caverns deeper or
+//! a test world flatter. The bounds are never changed for an existing save.
+
+use crate::loot::Loot;
+use crate::memory::ChunkMemory;
+use crate::save::WorldMeta;
+use crate::settings::WorldSettings;
+use crate::storage::WorldStorage;
+use crate::world::World;
+use anyhow::Result;
+use common::{
+    block::{BlockId, BlockType},
+    data::Data,
+    world::{DimensionId, WorldHeight},
+    worldgen::{caverns::CavernWorldGenerator, DefaultWorldGenerator},
+};
+use std::iter::FromIterator;
+use std::ops::{Index, IndexMut};
+use std::path::Path;
+
+/// The default bounds of the surface: the ground is between the heights -30 and 130
+const SURFACE_HEIGHT: WorldHeight = WorldHeight {
+    min_chunk_y: -2,
+    max_chunk_y: 4,
+};
+/// The default bounds of the caverns, which go deeper than the surface
+const CAVERNS_HEIGHT: WorldHeight = WorldHeight {
+    min_chunk_y: -4,
+    max_chunk_y: 4,
+};
+
+struct Dimension {
+    name: String,
+    world: World,
+}
+
+pub struct Dimensions {
+    /// The dimensions, indexed by their id. The surface comes first.
+    dimensions: Vec<Dimension>,
+    /// The dimension each block leads to, if it is an elevator
+    elevator_targets: Vec<Option<DimensionId>>,
+    /// The memory budget of the loaded chunks of all the dimensions
+    memory: ChunkMemory,
+}
+
+impl Dimensions {
+    /// Create the dimensions with the chunks stored in the save, and start the background workers of their worlds.
+    /// The dimensions whose bounds are not in `meta` yet add their default bounds to it.
+    pub fn new(game_data: &Data, settings: &WorldSettings, save_folder: &Path, meta: &mut WorldMeta) -> Result<Self> {
+        let blocks = &game_data.blocks;
+        let mut height = |name: &str, default: WorldHeight| *meta.heights.entry(name.to_owned()).or_insert(default);
+        // The worldgen threads share the decorator scripts of the surface
+        let surface_generator = DefaultWorldGenerator::new(blocks, &game_data.decorator_scripts);
+        let dimensions = vec![
+            Dimension {
+                name: "surface".to_owned(),
+                world: World::new(
+                    blocks.clone(),
+                    Loot::new(game_data, 0),
+                    || Box::new(surface_generator.clone()),
+                    settings.worldgen_threads,
+                    WorldStorage::open(save_folder, "surface", settings.storage.fsync)?,
+                    height("surface", SURFACE_HEIGHT),
+                ),
+            },
+            Dimension {
+                name: "caverns".to_owned(),
+                world: World::new(
+                    blocks.clone(),
+                    Loot::new(game_data, 1),
+                    || Box::new(CavernWorldGenerator::new(blocks)),
+                    settings.worldgen_threads,
+                    WorldStorage::open(save_folder, "caverns", settings.storage.fsync)?,
+                    height("caverns", CAVERNS_HEIGHT),
+                ),
+            },
+        ];
+        let elevator_targets = blocks
+            .iter()
+            .map(|(_, name, block)| match &block.block_type {
+                BlockType::Elevator { dimension, .. } => {
+                    let target = dimensions.iter().position(|other| other.name == *dimension);
+                    if target.is_none() {
+                        log::warn!("Elevator {} leads to dimension {} which doesn't exist", name, dimension);
+                    }
+                    target.map(|index| DimensionId(index as u16))
+                }
+                _ => None,
+            })
+            .collect();
+        Ok(Self {
+            dimensions,
+            elevator_targets,
+            memory: ChunkMemory::new(settings.chunk_memory_mb),
+        })
+    }
+
+    /// The ids of all the dimensions
+    pub fn ids(&self) -> impl Iterator<Item = DimensionId> {
+        (0..self.dimensions.len()).map(|index| DimensionId(index as u16))
+    }
+
+    pub fn name(&self, id: DimensionId) -> &str {
+        &self.dimensions[id.0 as usize].name
+    }
+
+    /// Find a dimension by name
+    pub fn find(&self, name: &str) -> Option<DimensionId> {
+        self.ids().find(|&id| self.name(id) == name)
+    }
+
+    pub fn get(&self, id: DimensionId) -> &World {
+        &self.dimensions[id.0 as usize].world
+    }
+
+    pub fn get_mut(&mut self, id: DimensionId) -> &mut World {
+        &mut self.dimensions[id.0 as usize].world
+    }
+
+    pub fn worlds_mut(&mut self) -> impl Iterator<Item = (DimensionId, &mut World)> {
+        self.dimensions
+            .iter_mut()
+            .enumerate()
+            .map(|(index, dimension)| (DimensionId(index as u16), &mut dimension.world))
+    }
+
+    /// The world of the surface, where the machines run
+    pub fn surface(&self) -> &World {
+        self.get(DimensionId::SURFACE)
+    }
+
+    pub fn surface_mut(&mut self) -> &mut World {
+        self.get_mut(DimensionId::SURFACE)
+    }
+
+    /// Unload the chunks that are not seen anymore if the loaded chunks use more memory than the budget
+    pub fn evict_chunks(&mut self) {
+        let Self { dimensions, memory, .. } = self;
+        memory.evict(dimensions.iter_mut().map(|dimension| &mut dimension.world).collect());
+    }
+
+    /// Describe the memory of the loaded chunks, for `/debug chunks`
+    pub fn chunk_memory_summary(&self) -> Vec<String> {
+        let worlds = self.dimensions.iter().map(|dimension| (dimension.name.as_str(), &dimension.world));
+        self.memory.summary(worlds)
+    }
+
+    /// The dimension an elevator block leads to, None if the block is not an elevator
+    pub fn elevator_target(&self, block: BlockId) -> Option<DimensionId> {
+        self.elevator_targets.get(block as usize).copied().flatten()
+    }
+
+    /// An elevator block leading to a dimension, if the data has one
+    pub fn elevator_to(&self, dimension: DimensionId) -> Option<BlockId> {
+        self.elevator_targets
+            .iter()
+            .position(|&target| target == Some(dimension))
+            .map(|block| block as BlockId)
+    }
+}
+
+/// One value for each dimension, such as the doors or the dropped items of the dimension
+pub struct PerDimension<T> {
+    values: Vec<T>,
+}
+
+impl<T> PerDimension<T> {
+    pub fn new(dimensions: &Dimensions, value: impl FnMut(DimensionId) -> T) -> Self {
+        dimensions.ids().map(value).collect()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (DimensionId, &mut T)> {
+        self.values.iter_mut().enumerate().map(|(index, value)| (DimensionId(index as u16), value))
+    }
+}
+
+/// The values must be in the order of the dimension ids
+impl<T> FromIterator<T> for PerDimension<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> Index<DimensionId> for PerDimension<T> {
+    type Output = T;
+
+    fn index(&self, id: DimensionId) -> &T {
+        &self.values[id.0 as usize]
+    }
+}
+
+impl<T> IndexMut<DimensionId> for PerDimension<T> {
+    fn index_mut(&mut self, id: DimensionId) -> &mut T {
+        &mut self.values[id.0 as usize]
+    }
+}
@@ -0,0 +1,174 @@
+//! Doors, hatches and airlocks.
+
+use crate::logic::Signals;
+use crate::scheduler::{seconds_to_ticks, Scheduler, Task};
+use crate::world::World;
+use common::{
+    block::{Block, BlockId, BlockType},
+    registry::Registry,
+    world::{BlockPos, DimensionId},
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Maximum distance between the two doors of an airlock, along each axis
+const AIRLOCK_RANGE: i64 = 4;
+/// Seconds needed to cycle an airlock before its door opens
+const AIRLOCK_CYCLE_TIME: f64 = 3.0;
+
+/// The door information of a block
+#[derive(Debug, Clone, Copy)]
+struct DoorBlock {
+    /// The block of the other state of the door
+    other_state: BlockId,
+    is_open: bool,
+    airlock: bool,
+}
+
+/// All the doors of a dimension
+pub struct Doors {
+    /// The dimension of the doors, whose airlocks are opened by scheduled tasks
+    dimension: DimensionId,
+    /// The door information of each block, if it is a door
+    door_blocks: Vec<Option<DoorBlock>>,
+    /// The doors of the world, and whether they received a signal last tick
+    doors: HashMap<BlockPos, bool>,
+}
+
+impl Doors {
+    pub fn new(block_registry: &Registry<Block>, dimension: DimensionId) -> Self {
+        let mut door_blocks = vec![None; block_registry.get_number_of_ids() as usize];
+        for id in 0..block_registry.get_number_of_ids() {
+            if let BlockType::Door { airlock, .. } = block_registry.get_value_by_id(id).unwrap().block_type {
+                let name = &block_registry.get_value_by_id(id).unwrap().name;
+                let open_id = block_registry
+                    .get_id_by_name(&format!("{}:open", name))
+                    .expect("Door has no open state");
+                door_blocks[id as usize] = Some(DoorBlock {
+                    other_state: open_id as BlockId,
+                    is_open: false,
+                    airlock,
+                });
+                door_blocks[open_id as usize] = Some(DoorBlock {
+                    other_state: id as BlockId,
+                    is_open: true,
+                    airlock,
+                });
+            }
+        }
+        Self {
+            dimension,
+            door_blocks,
+            doors: HashMap::new(),
+        }
+    }
+
+    fn get_door(&self, world: &World, pos: BlockPos) -> Option<DoorBlock> {
+        self.door_blocks.get(world.get_block(pos) as usize).copied().flatten()
+    }
+
+    /// Return true if the block is a door
+    pub fn is_door(&self, block: BlockId) -> bool {
+        self.door_blocks.get(block as usize).map(Option::is_some).unwrap_or(false)
+    }
+
+    /// Open or close a door
+    fn set_open(&self, world: &mut World, pos: BlockPos, open: bool) {
+        if let Some(door) = self.get_door(world, pos) {
+            if door.is_open != open {
+                world.set_block(pos, door.other_state);
+            }
+        }
+    }
+
+    /// Find the other door of an airlock
+    fn find_airlock_partner(&self, world: &World, pos: BlockPos) -> Option<BlockPos> {
+        let mut partner = None;
+        let mut partner_dist = i64::MAX;
+        for i in -AIRLOCK_RANGE..=AIRLOCK_RANGE {
+            for j in -AIRLOCK_RANGE..=AIRLOCK_RANGE {
+                for k in -AIRLOCK_RANGE..=AIRLOCK_RANGE {
+                    let dist = i.abs() + j.abs() + k.abs();
+                    // Don't pair the two blocks of a two-block high door
+                    if dist <= 1 || dist >= partner_dist {
+                        continue;
+                    }
+                    let other = BlockPos::from((pos.px + i, pos.py + j, pos.pz + k));
+                    if let Some(DoorBlock { airlock: true, .. }) = self.get_door(world, other) {
+                        partner = Some(other);
+                        partner_dist = dist;
+                    }
+                }
+            }
+        }
+        partner
+    }
+
+    /// Handle a player interacting with the door at `pos`. The airlock doors open later, with a scheduled task.
+    pub fn interact(&mut self, world: &mut World, scheduler: &mut Scheduler, pos: BlockPos) {
+        let door = match self.get_door(world, pos) {
+            Some(door) => door,
+            None => return,
+        };
+        if door.is_open || !door.airlock {
+            self.set_open(world, pos, !door.is_open);
+            return;
+        }
+        if scheduler.is_scheduled(&Task::OpenAirlock(pos, self.dimension)) {
+            return;
+        }
+        // Close the other door first, and open this one once the airlock has cycled
+        if let Some(partner) = self.find_airlock_partner(world, pos) {
+            self.set_open(world, partner, false);
+            scheduler.cancel(&Task::OpenAirlock(partner, self.dimension));
+        }
+        scheduler.run_in_ticks(seconds_to_ticks(AIRLOCK_CYCLE_TIME), Task::OpenAirlock(pos, self.dimension));
+    }
+
+    /// Open an airlock door once the airlock has cycled. Return the lower and upper corners of the chamber between
+    /// the two doors of the airlock, whose air was vented and refilled while it cycled.
+    pub fn finish_airlock_cycle(&self, world: &mut World, pos: BlockPos) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        self.set_open(world, pos, true);
+        let partner = self.find_airlock_partner(world, pos)?;
+        let lower = Vector3::new(pos.px.min(partner.px), pos.py.min(partner.py), pos.pz.min(partner.pz));
+        let upper = Vector3::new(pos.px.max(partner.px), pos.py.max(partner.py), pos.pz.max(partner.pz));
+        // The doors are two blocks high
+        let upper = upper + Vector3::new(1, 2, 1);
+        Some((lower.cast(), upper.cast()))
+    }
+
+    /// Take block modifications into account
+    pub fn update_blocks(&mut self, world: &World, changed_blocks: &[BlockPos]) {
+        for pos in changed_blocks {
+            if self.get_door(world, *pos).is_some() {
+                self.doors.entry(*pos).or_insert(false);
+            } else {
+                self.doors.remove(pos);
+            }
+        }
+    }
+
+    /// Open or close the doors whose signal changed
+    pub fn tick(&mut self, world: &mut World, scheduler: &mut Scheduler, signals: &Signals) {
+        let mut edges = Vec::new();
+        for (pos, was_powered) in self.doors.iter_mut() {
+            let powered = signals.is_powered(*pos);
+            if powered != *was_powered {
+                *was_powered = powered;
+                edges.push((*pos, powered));
+            }
+        }
+        for (pos, powered) in edges {
+            match self.get_door(world, pos) {
+                // Airlocks only cycle when they receive a signal
+                Some(door) if door.airlock => {
+                    if powered {
+                        self.interact(world, scheduler, pos);
+                    }
+                }
+                Some(_) => self.set_open(world, pos, powered),
+                None => {}
+            }
+        }
+    }
+}
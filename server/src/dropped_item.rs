@@ -0,0 +1,175 @@
+//! Items lying in the world
+
+use crate::conveyor::Conveyors;
+use crate::logic::Signals;
+use crate::world::World;
+use common::{
+    block::BlockId,
+    inventory::STORAGE_CRATE_SLOTS,
+    item::ItemStack,
+    physics::BlockContainer,
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+
+/// Falling acceleration of the items
+const GRAVITY_ACCELERATION: f64 = 25.0;
+/// Maximum falling speed
+const MAX_DOWN_SPEED: f64 = 30.0;
+/// Minimum distance between two items moved by a belt
+const ITEM_SPACING: f64 = 0.4;
+/// Seconds before a dropped item disappears
+const ITEM_LIFETIME: f64 = 300.0;
+/// Maximum number of items lying in the world. The oldest ones are removed first.
+const MAX_DROPPED_ITEMS: usize = 1024;
+
+/// An item stack lying in the world
+#[derive(Debug, Clone)]
+pub struct DroppedItem {
+    /// Position of the bottom center of the item
+    pub pos: Vector3<f64>,
+    pub stack: ItemStack,
+    vertical_velocity: f64,
+    /// Seconds since the item was dropped
+    age: f64,
+}
+
+/// All the items lying in the world
+pub struct DroppedItems {
+    items: Vec<DroppedItem>,
+    /// The block the items are inserted into when they are pushed against it
+    storage_block: Option<BlockId>,
+    /// The items spawned since the last call to `drain_spawned`
+    spawned: Vec<(Vector3<f64>, ItemStack)>,
+}
+
+impl DroppedItems {
+    pub fn new(storage_block: Option<BlockId>) -> Self {
+        Self {
+            items: Vec::new(),
+            storage_block,
+            spawned: Vec::new(),
+        }
+    }
+
+    /// Drop an item stack at some position
+    pub fn spawn(&mut self, pos: Vector3<f64>, stack: ItemStack) {
+        if self.items.len() == MAX_DROPPED_ITEMS {
+            self.items.remove(0);
+        }
+        self.items.push(DroppedItem {
+            pos,
+            stack: stack.clone(),
+            vertical_velocity: 0.0,
+            age: 0.0,
+        });
+        self.spawned.push((pos, stack));
+    }
+
+    /// Return the position and contents of the items spawned since the last call
+    pub fn drain_spawned(&mut self) -> Vec<(Vector3<f64>, ItemStack)> {
+        std::mem::take(&mut self.spawned)
+    }
+
+    /// Destroy the items closer than `radius` to `center`
+    pub fn remove_in_radius(&mut self, center: Vector3<f64>, radius: f64) {
+        self.items.retain(|item| (item.pos - center).norm() >= radius);
+    }
+
+    /// Give the items closer than `radius` to `center` to `take`, which returns what it didn't take
+    pub fn pick_up(&mut self, center: Vector3<f64>, radius: f64, mut take: impl FnMut(ItemStack) -> Option<ItemStack>) {
+        self.items.retain_mut(|item| {
+            if (item.pos - center).norm() >= radius {
+                return true;
+            }
+            match take(item.stack.clone()) {
+                Some(leftover) => {
+                    item.stack = leftover;
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// The position and contents of all the items, to be sent to the clients
+    pub fn get_infos(&self) -> Vec<(Vector3<f64>, ItemStack)> {
+        self.items.iter().map(|item| (item.pos, item.stack.clone())).collect()
+    }
+
+    /// Make the items fall, and move them along the conveyors
+    pub fn tick(&mut self, world: &mut World, conveyors: &Conveyors, signals: &Signals, dt: f64) {
+
+        for item in self.items.iter_mut() {
+            item.age += dt;
+        }
+        self.items.retain(|item| item.age < ITEM_LIFETIME);
+
+        // Movement is computed from the current positions, and then applied
+        let mut new_positions = Vec::with_capacity(self.items.len());
+        let mut stored = vec![false; self.items.len()];
+        for (i, item) in self.items.iter_mut().enumerate() {
+            let below = BlockPos::from(item.pos - Vector3::new(0.0, 0.01, 0.0));
+            if !world.is_block_full(below) {
+                // Falling
+                item.vertical_velocity = f64::max(item.vertical_velocity - GRAVITY_ACCELERATION * dt, -MAX_DOWN_SPEED);
+                let mut new_pos = item.pos + Vector3::new(0.0, item.vertical_velocity * dt, 0.0);
+                let new_below = BlockPos::from(new_pos);
+                if world.is_block_full(new_below) {
+                    new_pos.y = (new_below.py + 1) as f64;
+                    item.vertical_velocity = 0.0;
+                }
+                new_positions.push(new_pos);
+                continue;
+            }
+            item.vertical_velocity = 0.0;
+            let velocity = match conveyors.get_belt_velocity(world, signals, below, item.pos) {
+                Some(velocity) => velocity,
+                None => {
+                    new_positions.push(item.pos);
+                    continue;
+                }
+            };
+            let new_pos = item.pos + velocity * dt;
+            let new_block = BlockPos::from(new_pos);
+            if world.is_block_full(new_block) {
+                // Pushed against a block: the item enters it if it is a storage crate with enough room
+                if self.storage_block.is_some() && Some(world.get_block(new_block)) == self.storage_block {
+                    let container = world.get_container_mut(new_block, STORAGE_CRATE_SLOTS);
+                    if container.can_insert(&item.stack) {
+                        container.insert(item.stack.clone());
+                        stored[i] = true;
+                    }
+                }
+                new_positions.push(item.pos);
+            } else {
+                new_positions.push(new_pos);
+            }
+        }
+
+        // Items wait if there is another item right in front of them
+        for i in 0..self.items.len() {
+            let movement = new_positions[i] - self.items[i].pos;
+            if movement.x == 0.0 && movement.z == 0.0 {
+                self.items[i].pos = new_positions[i];
+                continue;
+            }
+            let blocked = self.items.iter().enumerate().any(|(j, other)| {
+                let to_other = other.pos - self.items[i].pos;
+                j != i
+                    && !stored[j]
+                    && to_other.norm() < ITEM_SPACING
+                    && to_other.dot(&movement) > 0.0
+            });
+            if !blocked {
+                self.items[i].pos = new_positions[i];
+            }
+        }
+
+        let mut i = 0;
+        self.items.retain(|_| {
+            i += 1;
+            !stored[i - 1]
+        });
+    }
+}
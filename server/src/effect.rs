@@ -0,0 +1,172 @@
+//! The status effects of the players. The effects with a duration wear off over time, such as the regeneration of
+//! the med stations. The others last as long as their cause: the slowness while the player is caught outside in a
+//! dust storm, and the hypoxia while the oxygen of their suit is low.
+//!
+//! The effects are applied every tick: the slowness and the hypoxia lower the speed of the player in the physics
+//! simulation, and the regeneration gives back health, unless the player has hypoxia.
+
+use crate::settings::EffectSettings;
+use crate::survival::Survival;
+use common::{
+    block::{Block, BlockId},
+    effect::{StatusEffect, StatusEffectKind, MED_STATION_BLOCK},
+    network::{messages::ToClient, Server},
+    physics::simulation::ServerPhysicsSimulation,
+    player::PlayerId,
+    registry::Registry,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// The lowest multiplier of the speed of the players, however slowed they are
+const MIN_SPEED_MULTIPLIER: f64 = 0.2;
+
+#[derive(Default)]
+struct PlayerEffects {
+    effects: BTreeMap<StatusEffectKind, StatusEffect>,
+    /// An effect was applied, changed or wore off since the effects were last sent to the player
+    changed: bool,
+}
+
+impl PlayerEffects {
+    /// Apply an effect, unless the player already has a stronger one of the same kind. Between two effects of the
+    /// same level, the one that lasts longer is kept.
+    fn apply(&mut self, effect: StatusEffect) {
+        // The effects that last as long as their cause are never replaced by the same effect with a duration
+        let lasts_longer = |old: Option<f64>, new: Option<f64>| match (old, new) {
+            (Some(old), Some(new)) => new > old,
+            _ => false,
+        };
+        let replace = match self.effects.get(&effect.kind) {
+            None => true,
+            Some(current) => {
+                effect.amplifier > current.amplifier
+                    || (effect.amplifier == current.amplifier && lasts_longer(current.remaining, effect.remaining))
+            }
+        };
+        if replace {
+            self.effects.insert(effect.kind, effect);
+            self.changed = true;
+        }
+    }
+
+    /// Set the level of an effect that lasts as long as its cause, or remove it if its cause is gone (None)
+    fn set_lasting(&mut self, kind: StatusEffectKind, amplifier: Option<u8>) {
+        let current = self.effects.get(&kind).filter(|effect| effect.remaining.is_none()).map(|effect| effect.amplifier);
+        if current == amplifier {
+            return;
+        }
+        match amplifier {
+            Some(amplifier) => {
+                self.effects.insert(kind, StatusEffect { kind, amplifier, remaining: None });
+            }
+            None => {
+                self.effects.remove(&kind);
+            }
+        }
+        self.changed = true;
+    }
+
+    fn level(&self, kind: StatusEffectKind) -> u32 {
+        self.effects.get(&kind).map_or(0, StatusEffect::level)
+    }
+}
+
+pub struct StatusEffects {
+    settings: EffectSettings,
+    /// The block of the med stations, None if the data has none
+    med_station: Option<BlockId>,
+    players: HashMap<PlayerId, PlayerEffects>,
+}
+
+impl StatusEffects {
+    pub fn new(settings: EffectSettings, blocks: &Registry<Block>) -> Self {
+        Self {
+            settings,
+            med_station: blocks.get_id_by_name(&MED_STATION_BLOCK.to_owned()).map(|id| id as BlockId),
+            players: HashMap::new(),
+        }
+    }
+
+    pub fn is_med_station(&self, block: BlockId) -> bool {
+        self.med_station == Some(block)
+    }
+
+    /// Apply an effect to a player for some seconds
+    pub fn apply(&mut self, player: PlayerId, kind: StatusEffectKind, amplifier: u8, duration: f64) {
+        let effect = StatusEffect { kind, amplifier, remaining: Some(duration) };
+        self.players.entry(player).or_default().apply(effect);
+    }
+
+    /// Give the regeneration of a med station to a player who used it
+    pub fn use_med_station(&mut self, player: PlayerId) {
+        // The level is counted from 1 in the settings
+        let amplifier = self.settings.med_station_level.saturating_sub(1);
+        self.apply(player, StatusEffectKind::Regeneration, amplifier, self.settings.med_station_duration);
+    }
+
+    /// Remove all the effects of a player, for example when they die
+    pub fn clear(&mut self, player: PlayerId) {
+        if let Some(effects) = self.players.get_mut(&player) {
+            if !effects.effects.is_empty() {
+                effects.effects.clear();
+                effects.changed = true;
+            }
+        }
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.players.remove(&player);
+    }
+
+    /// Update the effects that last as long as their cause, wear off the others, and apply them to the players.
+    /// `in_storm` are the players caught outside in a dust storm, with the level of the slowness of the storm.
+    pub fn tick(
+        &mut self,
+        players: impl Iterator<Item = PlayerId>,
+        in_storm: &HashMap<PlayerId, u8>,
+        survival: &mut Survival,
+        physics_simulation: &mut ServerPhysicsSimulation,
+        dt: f64,
+    ) {
+        for player in players {
+            let effects = self.players.entry(player).or_default();
+            let storm_amplifier = in_storm.get(&player).map(|level| level.saturating_sub(1));
+            effects.set_lasting(StatusEffectKind::Slowness, storm_amplifier);
+            let oxygen = survival.vitals(player).map(|vitals| vitals.oxygen);
+            let hypoxia = match oxygen {
+                Some(oxygen) if oxygen < self.settings.very_low_oxygen => Some(1),
+                Some(oxygen) if oxygen < self.settings.low_oxygen => Some(0),
+                _ => None,
+            };
+            effects.set_lasting(StatusEffectKind::Hypoxia, hypoxia);
+
+            // Only the effects that wore off are sent, the client counts down the others by itself
+            let count = effects.effects.len();
+            effects.effects.retain(|_, effect| match effect.remaining.as_mut() {
+                Some(remaining) => {
+                    *remaining -= dt;
+                    *remaining > 0.0
+                }
+                None => true,
+            });
+            effects.changed |= count != effects.effects.len();
+
+            let regeneration = effects.level(StatusEffectKind::Regeneration);
+            if regeneration > 0 && effects.level(StatusEffectKind::Hypoxia) == 0 {
+                survival.heal(player, regeneration as f64 * self.settings.regeneration_per_level * dt);
+            }
+            let slowness = effects.level(StatusEffectKind::Slowness) + effects.level(StatusEffectKind::Hypoxia);
+            let speed_multiplier = (1.0 - slowness as f64 * self.settings.slowness_per_level).max(MIN_SPEED_MULTIPLIER);
+            physics_simulation.set_speed_multiplier(player, speed_multiplier);
+        }
+    }
+
+    /// Send the effects that changed to their players
+    pub fn send_updates(&mut self, server: &mut dyn Server) {
+        for (&player, effects) in self.players.iter_mut() {
+            if std::mem::take(&mut effects.changed) {
+                server.send(player, ToClient::StatusEffects(effects.effects.values().copied().collect()));
+            }
+        }
+    }
+}
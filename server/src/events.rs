@@ -0,0 +1,189 @@
+//! Event bus shared by the server systems.
+//!
+//! The systems publish the game events, and the subscribers react to them without the publishers knowing about them.
+//! The events are queued when they are published, and delivered by `EventBus::dispatch` in the order of publication.
+//! Every event is given to the subscribers of its type in the order of their registration.
+
+use common::{
+    block::BlockId,
+    item::ItemStack,
+    player::PlayerId,
+    robot::RobotId,
+    world::{BlockPos, DimensionId},
+};
+use log::debug;
+use nalgebra::Vector3;
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+
+/// Maximum number of events delivered by one call to `dispatch`, in case the subscribers publish events endlessly
+const MAX_EVENTS_PER_DISPATCH: usize = 100_000;
+
+/// A block of the surface was modified, by a player or by any system
+#[derive(Debug, Clone)]
+pub struct BlockChanged {
+    pub pos: BlockPos,
+    pub old_block: BlockId,
+    pub new_block: BlockId,
+}
+
+/// A player broke a block
+#[derive(Debug, Clone)]
+pub struct BlockBroken {
+    pub player: PlayerId,
+    pub dimension: DimensionId,
+    pub pos: BlockPos,
+    pub block: BlockId,
+}
+
+/// A player placed a block
+#[derive(Debug, Clone)]
+pub struct BlockPlaced {
+    pub player: PlayerId,
+    pub dimension: DimensionId,
+    pub pos: BlockPos,
+    pub block: BlockId,
+}
+
+#[derive(Debug, Clone)]
+pub enum Entity {
+    Robot { id: RobotId, owner: PlayerId },
+    DroppedItem(ItemStack),
+}
+
+/// An entity appeared in the world
+#[derive(Debug, Clone)]
+pub struct EntitySpawned {
+    pub entity: Entity,
+    pub pos: Vector3<f64>,
+}
+
+/// A meteor hit the ground
+#[derive(Debug, Clone)]
+pub struct MeteorImpact {
+    pub pos: Vector3<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerJoined {
+    pub player: PlayerId,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerLeft {
+    pub player: PlayerId,
+}
+
+/// The events waiting to be delivered. The subscribers can publish new events with it.
+#[derive(Default)]
+pub struct EventQueue {
+    events: VecDeque<Box<dyn Any>>,
+}
+
+impl EventQueue {
+    pub fn publish<E: 'static>(&mut self, event: E) {
+        self.events.push_back(Box::new(event));
+    }
+}
+
+type Handler<E> = Box<dyn FnMut(&E, &mut EventQueue)>;
+
+/// The subscribers of one type of event
+struct Subscribers {
+    event_name: &'static str,
+    /// The names of the subscribers, in order
+    names: Vec<&'static str>,
+    /// A `Vec<Handler<E>>`
+    handlers: Box<dyn Any>,
+    /// Give an event to the handlers
+    deliver: fn(&mut dyn Any, &dyn Any, &mut EventQueue),
+}
+
+fn deliver<E: 'static>(handlers: &mut dyn Any, event: &dyn Any, queue: &mut EventQueue) {
+    let handlers = handlers.downcast_mut::<Vec<Handler<E>>>().unwrap();
+    let event = event.downcast_ref::<E>().unwrap();
+    for handler in handlers.iter_mut() {
+        handler(event, queue);
+    }
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    queue: EventQueue,
+    subscribers: HashMap<TypeId, Subscribers>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `handler` for every event of type `E`, after the subscribers that were registered before it
+    pub fn subscribe<E: 'static>(&mut self, name: &'static str, handler: impl FnMut(&E, &mut EventQueue) + 'static) {
+        let subscribers = self.subscribers.entry(TypeId::of::<E>()).or_insert_with(|| Subscribers {
+            event_name: std::any::type_name::<E>(),
+            names: Vec::new(),
+            handlers: Box::new(Vec::<Handler<E>>::new()),
+            deliver: deliver::<E>,
+        });
+        subscribers.names.push(name);
+        subscribers
+            .handlers
+            .downcast_mut::<Vec<Handler<E>>>()
+            .unwrap()
+            .push(Box::new(handler));
+    }
+
+    /// Queue an event, it will be delivered by the next call to `dispatch`
+    pub fn publish<E: 'static>(&mut self, event: E) {
+        self.queue.publish(event);
+    }
+
+    /// Deliver all the queued events, including the ones published by the subscribers during the dispatch
+    pub fn dispatch(&mut self) {
+        let mut delivered = 0;
+        while let Some(event) = self.queue.events.pop_front() {
+            if delivered == MAX_EVENTS_PER_DISPATCH {
+                log::warn!("Too many events, dropping {} events", self.queue.events.len() + 1);
+                self.queue.events.clear();
+                break;
+            }
+            delivered += 1;
+            // The events without subscribers are dropped
+            if let Some(subscribers) = self.subscribers.get_mut(&(*event).type_id()) {
+                (subscribers.deliver)(&mut *subscribers.handlers, &*event, &mut self.queue);
+            }
+        }
+    }
+
+    pub fn debug_info(&self) -> String {
+        let mut subscribers = self.subscribers.values().collect::<Vec<_>>();
+        subscribers.sort_by_key(|subscribers| subscribers.event_name);
+        let mut info = String::new();
+        for subscribers in subscribers {
+            let event_name = subscribers.event_name.rsplit("::").next().unwrap();
+            info.push_str(&format!("{}: {}\n", event_name, subscribers.names.join(", ")));
+        }
+        info
+    }
+}
+
+/// Log all the events, at the debug level
+pub fn subscribe_logger(events: &mut EventBus) {
+    events.subscribe("logger", |event: &BlockChanged, _| {
+        debug!("Block {:?} changed from {} to {}", event.pos, event.old_block, event.new_block)
+    });
+    events.subscribe("logger", |event: &BlockBroken, _| {
+        debug!("Player {:?} broke block {} at {:?} in {:?}", event.player, event.block, event.pos, event.dimension)
+    });
+    events.subscribe("logger", |event: &BlockPlaced, _| {
+        debug!("Player {:?} placed block {} at {:?} in {:?}", event.player, event.block, event.pos, event.dimension)
+    });
+    events.subscribe("logger", |event: &EntitySpawned, _| match &event.entity {
+        Entity::Robot { id, owner } => debug!("Robot {:?} of player {:?} spawned at {:?}", id, owner, event.pos),
+        Entity::DroppedItem(stack) => debug!("Item stack {:?} dropped at {:?}", stack, event.pos),
+    });
+    events.subscribe("logger", |event: &MeteorImpact, _| debug!("Meteor impact at {:?}", event.pos));
+    events.subscribe("logger", |event: &PlayerJoined, _| debug!("Player {:?} joined", event.player));
+    events.subscribe("logger", |event: &PlayerLeft, _| debug!("Player {:?} left", event.player));
+}
@@ -0,0 +1,189 @@
+//! Explosions, destroying the blocks around them.
+
+use crate::dropped_item::DroppedItems;
+use crate::logic::Signals;
+use crate::world::World;
+use common::{
+    block::BlockId,
+    data::Data,
+    item::{ItemId, ItemStack},
+    world::BlockPos,
+};
+use nalgebra::Vector3;
+use std::collections::{HashMap, VecDeque};
+
+/// Maximum power of an explosion, to bound the number of blocks it affects
+const MAX_POWER: f64 = 16.0;
+/// Maximum number of explosions handled per tick. Chain reactions continue on the next ticks.
+const MAX_EXPLOSIONS_PER_TICK: usize = 4;
+/// Maximum number of debris sent to the clients for each explosion
+const MAX_DEBRIS: usize = 16;
+
+/// Return the strength of an explosion at some position. It decreases linearly with the distance,
+/// and is 0 further than `power` blocks away.
+pub fn explosion_strength(center: Vector3<f64>, power: f64, pos: Vector3<f64>) -> f64 {
+    f64::max(power - (pos - center).norm(), 0.0)
+}
+
+/// An explosion that will happen during the next tick
+#[derive(Debug, Clone, Copy)]
+struct Explosion {
+    center: Vector3<f64>,
+    power: f64,
+}
+
+/// What the clients are told about an explosion: its center, power and debris
+pub type ExplosionEffect = (Vector3<f64>, f64, Vec<ItemId>);
+
+/// All the explosions and explosive blocks of the world
+pub struct Explosions {
+    /// The blast resistance of each block
+    blast_resistances: Vec<f64>,
+    /// The power of each block, if it is explosive
+    explosive_powers: Vec<Option<f64>>,
+    /// The items dropped by each block, used for the debris
+    block_items: Vec<Option<ItemStack>>,
+    /// The explosive blocks of the world, and whether they received a signal last tick
+    explosives: HashMap<BlockPos, bool>,
+    pending: VecDeque<Explosion>,
+}
+
+impl Explosions {
+    pub fn new(data: &Data) -> Self {
+        let block_types = (0..data.blocks.get_number_of_ids())
+            .map(|id| &data.blocks.get_value_by_id(id).unwrap().block_type);
+        Self {
+            blast_resistances: block_types.clone().map(|block_type| block_type.blast_resistance()).collect(),
+            explosive_powers: block_types.map(|block_type| block_type.explosive_power()).collect(),
+            block_items: data.block_items.clone(),
+            explosives: HashMap::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn get_explosive_power(&self, block: BlockId) -> Option<f64> {
+        self.explosive_powers.get(block as usize).copied().flatten()
+    }
+
+    /// Return true if the block is explosive
+    pub fn is_explosive(&self, block: BlockId) -> bool {
+        self.get_explosive_power(block).is_some()
+    }
+
+    /// Make an explosion happen during the next tick
+    pub fn trigger(&mut self, center: Vector3<f64>, power: f64) {
+        self.pending.push_back(Explosion {
+            center,
+            power: f64::min(power, MAX_POWER),
+        });
+    }
+
+    /// Detonate the explosive block at `pos`
+    pub fn detonate(&mut self, world: &World, pos: BlockPos) {
+        if let Some(power) = self.get_explosive_power(world.get_block(pos)) {
+            // Don't detonate the same block twice
+            if self.explosives.remove(&pos).is_some() {
+                let center = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+                self.trigger(center, power);
+            }
+        }
+    }
+
+    /// Take block modifications into account
+    pub fn update_blocks(&mut self, world: &World, changed_blocks: &[BlockPos]) {
+        for pos in changed_blocks {
+            if self.is_explosive(world.get_block(*pos)) {
+                self.explosives.entry(*pos).or_insert(false);
+            } else {
+                self.explosives.remove(pos);
+            }
+        }
+    }
+
+    /// Detonate the explosive blocks that receive a signal, and make the pending explosions happen.
+    /// Return what happened, to hurt the entities around and to be sent to the clients.
+    pub fn tick(
+        &mut self,
+        world: &mut World,
+        signals: &Signals,
+        dropped_items: &mut DroppedItems,
+    ) -> Vec<ExplosionEffect> {
+        let mut detonated = Vec::new();
+        for (pos, was_powered) in self.explosives.iter_mut() {
+            let powered = signals.is_powered(*pos);
+            if powered && !*was_powered {
+                detonated.push(*pos);
+            }
+            *was_powered = powered;
+        }
+        for pos in detonated {
+            self.detonate(world, pos);
+        }
+
+        let mut effects = Vec::new();
+        for _ in 0..MAX_EXPLOSIONS_PER_TICK {
+            let explosion = match self.pending.pop_front() {
+                Some(explosion) => explosion,
+                None => break,
+            };
+            effects.push(self.explode(world, explosion, dropped_items));
+        }
+        effects
+    }
+
+    fn explode(
+        &mut self,
+        world: &mut World,
+        explosion: Explosion,
+        dropped_items: &mut DroppedItems,
+    ) -> ExplosionEffect {
+        let Explosion { center, power } = explosion;
+        let center_block = BlockPos::from(center);
+        let radius = power.ceil() as i64;
+
+        // Collect the destroyed blocks first, so that the world is only updated once
+        let mut destroyed = Vec::new();
+        let mut debris = Vec::new();
+        for i in -radius..=radius {
+            for j in -radius..=radius {
+                for k in -radius..=radius {
+                    let pos = BlockPos::from((center_block.px + i, center_block.py + j, center_block.pz + k));
+                    let block = world.get_block(pos);
+                    if block == 0 {
+                        continue;
+                    }
+                    let block_center = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+                    let strength = explosion_strength(center, power, block_center);
+                    if strength <= 0.0 || strength < self.blast_resistances[block as usize] {
+                        continue;
+                    }
+                    destroyed.push((pos, 0));
+                    if let Some(stack) = &self.block_items[block as usize] {
+                        debris.push(stack.item);
+                    }
+                    // Chain reaction
+                    if pos != center_block {
+                        self.detonate(world, pos);
+                    }
+                }
+            }
+        }
+        world.set_blocks(&destroyed);
+
+        dropped_items.remove_in_radius(center, power);
+
+        // Only send a sample of the debris
+        let step = usize::max(debris.len() / MAX_DEBRIS, 1);
+        let debris = debris.into_iter().step_by(step).take(MAX_DEBRIS).collect();
+        (center, power, debris)
+    }
+
+    /// Text summary for the debug overlay
+    pub fn debug_info(&self) -> String {
+        format!(
+            "Explosive blocks = {}\nPending explosions = {}\n",
+            self.explosives.len(),
+            self.pending.len(),
+        )
+    }
+}
@@ -0,0 +1,184 @@
+//! The game rules of a world, such as the daylight cycle, and the capabilities of each player, such as flying.
+//! They are changed with the `/gamerule` command and stored with the world.
+
+use anyhow::{Context, Result};
+use common::player::{PlayerCapabilities, PlayerId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// The rules of the whole world
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// The players are hurt when they fall from high, once they have health
+    pub fall_damage: bool,
+    /// The time of the day advances
+    pub daylight_cycle: bool,
+    /// The creatures spawn in the world
+    pub mob_spawning: bool,
+    /// The players get a robot when they assign their first mining area
+    pub robot_spawning: bool,
+    /// The players keep their inventory when they die
+    pub keep_inventory: bool,
+    /// The players can hit each other
+    pub pvp: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            fall_damage: true,
+            daylight_cycle: true,
+            mob_spawning: true,
+            robot_spawning: true,
+            keep_inventory: false,
+            pvp: true,
+        }
+    }
+}
+
+impl Rules {
+    pub const NAMES: [&'static str; 6] =
+        ["fall_damage", "daylight_cycle", "mob_spawning", "robot_spawning", "keep_inventory", "pvp"];
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "fall_damage" => Some(&mut self.fall_damage),
+            "daylight_cycle" => Some(&mut self.daylight_cycle),
+            "mob_spawning" => Some(&mut self.mob_spawning),
+            "robot_spawning" => Some(&mut self.robot_spawning),
+            "keep_inventory" => Some(&mut self.keep_inventory),
+            "pvp" => Some(&mut self.pvp),
+            _ => None,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<bool> {
+        self.clone().get_mut(name).copied()
+    }
+}
+
+/// What is stored with the world
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedGameRules {
+    rules: Rules,
+    /// The capabilities of the players who don't have the default ones, by name like the statistics
+    capabilities: BTreeMap<String, PlayerCapabilities>,
+}
+
+pub struct GameRules {
+    path: PathBuf,
+    saved: SavedGameRules,
+    player_names: HashMap<PlayerId, String>,
+    /// The players whose capabilities changed since the last call to `drain_changed_capabilities`
+    changed_capabilities: Vec<PlayerId>,
+}
+
+impl GameRules {
+    /// Load the game rules from the world save, if it has some
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let saved = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read game rules")?;
+            ron::de::from_str(&buf).context("Failed to parse game rules")?
+        } else {
+            SavedGameRules::default()
+        };
+        Ok(Self {
+            path,
+            saved,
+            player_names: HashMap::new(),
+            changed_capabilities: Vec::new(),
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.saved, Default::default())
+            .context("Failed to serialize game rules")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write game rules"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.saved.rules
+    }
+
+    /// The capabilities of a player, the default ones until their name is known
+    pub fn capabilities(&self, player: PlayerId) -> PlayerCapabilities {
+        self.player_names
+            .get(&player)
+            .and_then(|name| self.saved.capabilities.get(name))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name);
+        self.changed_capabilities.push(player);
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.player_names.remove(&player);
+    }
+
+    /// The players whose capabilities must be sent to them again
+    pub fn drain_changed_capabilities(&mut self) -> Vec<PlayerId> {
+        std::mem::take(&mut self.changed_capabilities)
+    }
+
+    /// Run `/gamerule` with the words after the command name, and return the text to show
+    pub fn run_command<'a>(&mut self, mut words: impl Iterator<Item = &'a str>) -> String {
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (None, _, _, _) => Rules::NAMES
+                .iter()
+                .map(|&name| format!("{} = {}", name, self.saved.rules.get(name).unwrap()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            (Some("player"), Some(name), Some(capability), Some(value)) => {
+                let value = match parse_bool(value) {
+                    Ok(value) => value,
+                    Err(error) => return error,
+                };
+                let mut capabilities = self.saved.capabilities.get(name).copied().unwrap_or_default();
+                match capability {
+                    "can_fly" => capabilities.can_fly = value,
+                    "can_build" => capabilities.can_build = value,
+                    _ => return format!("Unknown capability: {}, use can_fly or can_build", capability),
+                }
+                if capabilities == PlayerCapabilities::default() {
+                    self.saved.capabilities.remove(name);
+                } else {
+                    self.saved.capabilities.insert(name.to_owned(), capabilities);
+                }
+                self.save();
+                let connected = self.player_names.iter().filter(|(_, player_name)| *player_name == name);
+                self.changed_capabilities.extend(connected.map(|(&id, _)| id));
+                format!("{} of {} set to {}", capability, name, value)
+            }
+            (Some("player"), _, _, _) => "Usage: /gamerule player <name> <can_fly|can_build> <true|false>".to_owned(),
+            (Some(rule), value, None, None) => {
+                let current = match self.saved.rules.get_mut(rule) {
+                    Some(current) => current,
+                    None => return format!("Unknown game rule: {}, the rules are {}", rule, Rules::NAMES.join(", ")),
+                };
+                match value.map(parse_bool) {
+                    None => format!("{} = {}", rule, current),
+                    Some(Ok(value)) => {
+                        *current = value;
+                        self.save();
+                        format!("{} set to {}", rule, value)
+                    }
+                    Some(Err(error)) => error,
+                }
+            }
+            _ => "Usage: /gamerule [rule] [true|false]".to_owned(),
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    value.parse().map_err(|_| format!("{} is not true or false", value))
+}
@@ -0,0 +1,470 @@
+//! The history of the block changes: every change of the world is recorded by chunk with the time it happened and the
+//! player who made it, to show the players the world as it was in the past, or the construction of their base as a
+//! time-lapse.
+//!
+//! The changes of a chunk are stored next to it by the storage of the world, with the names of the blocks so that they
+//! survive a change of the data packs, and they are only loaded when they are needed: to record a change of the chunk,
+//! to show its past, or to revert changes. Only the changes of the surface are recorded.
+
+use crate::events::{BlockBroken, BlockChanged, BlockPlaced, EventBus, PlayerLeft};
+use crate::settings::HistorySettings;
+use crate::world::World;
+use crate::PlayerData;
+use anyhow::{Context, Result};
+use common::{
+    block::{Block, BlockId},
+    network::{messages::ToClient, Server},
+    player::PlayerId,
+    registry::Registry,
+    world::{BlockPos, ChunkPos, DimensionId},
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Seconds between two removals of the changes older than the retention
+const PRUNE_INTERVAL: f64 = 60.0;
+/// Maximum number of past chunks sent to a player in one tick
+const MAX_CHUNKS_PER_TICK: usize = 20;
+/// Extension of the files storing the changes of the chunks
+const HISTORY_EXTENSION: &str = "history";
+
+enum Trigger {
+    Changed(BlockChanged),
+    /// A block of this position is about to be changed by the player
+    ChangedBy(PlayerId, BlockPos),
+    PlayerLeft(PlayerId),
+}
+
+/// One change of a block
+#[derive(Debug, Clone)]
+pub struct BlockChange {
+    /// Seconds of game time since the start of the server
+    pub time: f64,
+    pub pos: BlockPos,
+    pub old_block: BlockId,
+    pub new_block: BlockId,
+    /// The name of the player who made the change, None for the changes of the robots and the environment
+    pub player: Option<Rc<str>>,
+}
+
+/// The changes of a chunk as they are stored
+#[derive(Serialize, Deserialize)]
+struct StoredChanges {
+    /// The names of the blocks of the changes
+    blocks: Vec<String>,
+    changes: Vec<StoredChange>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredChange {
+    time: f64,
+    pos: (i64, i64, i64),
+    /// The index of the old block in the `blocks` of the chunk
+    old_block: usize,
+    new_block: usize,
+    player: Option<String>,
+}
+
+/// The state of the history saved with the game
+#[derive(Serialize, Deserialize, Default)]
+struct StoredHistory {
+    time: f64,
+}
+
+/// A player looking at the past: a still view of the past, or a time-lapse from the past to the present
+struct View {
+    /// The time shown first
+    from: f64,
+    /// The time shown at the end of the time-lapse
+    to: f64,
+    /// Seconds taken by the time-lapse, None for a still view
+    duration: Option<f64>,
+    elapsed: f64,
+    /// True once the player asked to go back to the present
+    ended: bool,
+    /// The chunks whose past state was sent to the player, with the version of the chunk the player had and the
+    /// number of changes that were applied
+    sent: HashMap<ChunkPos, (u64, usize)>,
+}
+
+impl View {
+    fn shown_time(&self) -> f64 {
+        match self.duration {
+            Some(duration) if duration > 0.0 => self.from + (self.to - self.from) * (self.elapsed / duration).min(1.0),
+            Some(_) => self.to,
+            None => self.from,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.ended || matches!(self.duration, Some(duration) if self.elapsed >= duration)
+    }
+}
+
+pub struct BlockHistory {
+    settings: HistorySettings,
+    path: PathBuf,
+    blocks: Registry<Block>,
+    /// The changes of each loaded chunk, sorted by time
+    changes: HashMap<ChunkPos, Vec<BlockChange>>,
+    /// The chunks whose changes were loaded from the storage
+    loaded: HashSet<ChunkPos>,
+    /// The loaded chunks whose changes differ from the stored ones
+    unsaved: HashSet<ChunkPos>,
+    player_names: HashMap<PlayerId, Rc<str>>,
+    views: HashMap<PlayerId, View>,
+    triggers: Rc<RefCell<Vec<Trigger>>>,
+    /// Seconds of game time since the creation of the history
+    time: f64,
+    time_since_prune: f64,
+}
+
+impl BlockHistory {
+    pub fn load(settings: HistorySettings, path: PathBuf, blocks: Registry<Block>) -> Result<Self> {
+        let stored: StoredHistory = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read the history")?;
+            ron::de::from_str(&buf).context("Failed to parse the history")?
+        } else {
+            StoredHistory::default()
+        };
+        Ok(Self {
+            settings,
+            path,
+            blocks,
+            changes: HashMap::new(),
+            loaded: HashSet::new(),
+            unsaved: HashSet::new(),
+            player_names: HashMap::new(),
+            views: HashMap::new(),
+            triggers: Default::default(),
+            time: stored.time,
+            time_since_prune: 0.0,
+        })
+    }
+
+    /// Save the changes that are not stored yet and the time of the history, periodically and when the server stops
+    pub fn save(&mut self, world: &World) {
+        for pos in std::mem::take(&mut self.unsaved) {
+            self.save_chunk(pos, world);
+        }
+        let result = ron::ser::to_string_pretty(&StoredHistory { time: self.time }, Default::default())
+            .context("Failed to serialize the history")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write the history"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    pub fn subscribe(&self, events: &mut EventBus) {
+        // The events of the players are published before the changes of the blocks they cause
+        let triggers = self.triggers.clone();
+        events.subscribe("history", move |event: &BlockBroken, _| {
+            if event.dimension == DimensionId::SURFACE {
+                triggers.borrow_mut().push(Trigger::ChangedBy(event.player, event.pos));
+            }
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("history", move |event: &BlockPlaced, _| {
+            if event.dimension == DimensionId::SURFACE {
+                triggers.borrow_mut().push(Trigger::ChangedBy(event.player, event.pos));
+            }
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("history", move |event: &BlockChanged, _| {
+            triggers.borrow_mut().push(Trigger::Changed(event.clone()));
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("history", move |event: &PlayerLeft, _| {
+            triggers.borrow_mut().push(Trigger::PlayerLeft(event.player));
+        });
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name.into());
+    }
+
+    /// Seconds of game time for which the changes are kept
+    pub fn retention(&self) -> f64 {
+        self.settings.retention
+    }
+
+    /// Record the changes received since the last call, and forget the changes older than the retention
+    pub fn tick(&mut self, dt: f64, world: &World) {
+        self.time += dt;
+        let triggers = std::mem::take(&mut *self.triggers.borrow_mut());
+        let mut authors = HashMap::new();
+        for trigger in triggers {
+            match trigger {
+                Trigger::ChangedBy(player, pos) => {
+                    authors.insert(pos, player);
+                }
+                Trigger::Changed(BlockChanged { pos, old_block, new_block }) => {
+                    let player = authors.remove(&pos).and_then(|player| self.player_names.get(&player).cloned());
+                    let chunk_pos = pos.containing_chunk_pos();
+                    self.load_chunk(chunk_pos, world);
+                    self.unsaved.insert(chunk_pos);
+                    self.changes.entry(chunk_pos).or_default().push(BlockChange {
+                        time: self.time,
+                        pos,
+                        old_block,
+                        new_block,
+                        player,
+                    });
+                }
+                Trigger::PlayerLeft(player) => {
+                    self.player_names.remove(&player);
+                    self.views.remove(&player);
+                }
+            }
+        }
+
+        self.time_since_prune += dt;
+        if self.time_since_prune >= PRUNE_INTERVAL {
+            self.time_since_prune = 0.0;
+            let oldest = self.time - self.settings.retention;
+            for (pos, changes) in self.changes.iter_mut() {
+                let old = changes.partition_point(|change| change.time < oldest);
+                if old > 0 {
+                    changes.drain(..old);
+                    self.unsaved.insert(*pos);
+                }
+            }
+            self.changes.retain(|_, changes| !changes.is_empty());
+        }
+    }
+
+    /// Save the changes of the chunks that are not loaded anymore and forget them, they are loaded again with the chunk
+    pub fn forget_unloaded_chunks(&mut self, world: &World) {
+        let unloaded = self.loaded.iter().filter(|pos| world.get_chunk(**pos).is_none()).copied().collect::<Vec<_>>();
+        for pos in unloaded {
+            if self.unsaved.remove(&pos) {
+                self.save_chunk(pos, world);
+            }
+            self.loaded.remove(&pos);
+            self.changes.remove(&pos);
+        }
+    }
+
+    /// Load the stored changes of a chunk, unless they are already loaded
+    fn load_chunk(&mut self, pos: ChunkPos, world: &World) {
+        if !self.loaded.insert(pos) {
+            return;
+        }
+        let stored = match world.storage().load_chunk_file::<StoredChanges>(pos, HISTORY_EXTENSION) {
+            Some(stored) => stored,
+            None => return,
+        };
+        // The blocks removed from the data packs since the changes were stored become air, like in the chunks
+        let ids = stored
+            .blocks
+            .iter()
+            .map(|name| self.blocks.get_id_by_name(name).unwrap_or(0) as BlockId)
+            .collect::<Vec<_>>();
+        let oldest = self.time - self.settings.retention;
+        let mut changes = Vec::with_capacity(stored.changes.len());
+        for change in stored.changes.into_iter().filter(|change| change.time >= oldest) {
+            let (old_block, new_block) = match (ids.get(change.old_block), ids.get(change.new_block)) {
+                (Some(&old_block), Some(&new_block)) => (old_block, new_block),
+                _ => continue,
+            };
+            changes.push(BlockChange {
+                time: change.time,
+                pos: BlockPos::from(change.pos),
+                old_block,
+                new_block,
+                player: change.player.map(Rc::from),
+            });
+        }
+        if !changes.is_empty() {
+            self.changes.insert(pos, changes);
+        }
+    }
+
+    /// Load the stored changes of all the loaded chunks of the world
+    fn load_loaded_chunks(&mut self, world: &World) {
+        for pos in world.loaded_chunk_positions() {
+            self.load_chunk(pos, world);
+        }
+    }
+
+    /// Store the changes of a loaded chunk, replacing the stored ones
+    fn save_chunk(&self, pos: ChunkPos, world: &World) {
+        let stored = self.changes.get(&pos).map(|changes| {
+            let mut blocks = Vec::new();
+            let mut indices = HashMap::new();
+            let mut index = |block: BlockId| {
+                *indices.entry(block).or_insert_with(|| {
+                    blocks.push(self.blocks.get_name_by_id(block as u32).unwrap_or("air").to_owned());
+                    blocks.len() - 1
+                })
+            };
+            let changes = changes
+                .iter()
+                .map(|change| StoredChange {
+                    time: change.time,
+                    pos: (change.pos.px, change.pos.py, change.pos.pz),
+                    old_block: index(change.old_block),
+                    new_block: index(change.new_block),
+                    player: change.player.as_ref().map(|player| player.to_string()),
+                })
+                .collect();
+            StoredChanges { blocks, changes }
+        });
+        world.storage().save_chunk_file(pos, HISTORY_EXTENSION, stored.as_ref());
+    }
+
+    /// The blocks to set to revert the changes made by a player in the last `seconds`: each block they changed gets
+    /// back the block it had before their first change. The blocks changed again since their last change are kept.
+    pub fn rollback_changes(&mut self, player_name: &str, seconds: f64, world: &World) -> Vec<(BlockPos, BlockId)> {
+        self.load_loaded_chunks(world);
+        let since = self.time - seconds;
+        // The block before the first change of the player, and the block after their last change
+        let mut changed_blocks: HashMap<BlockPos, (BlockId, BlockId)> = HashMap::new();
+        for changes in self.changes.values() {
+            let start = changes.partition_point(|change| change.time < since);
+            for change in &changes[start..] {
+                if change.player.as_deref() == Some(player_name) {
+                    changed_blocks.entry(change.pos).or_insert((change.old_block, change.new_block)).1 = change.new_block;
+                }
+            }
+        }
+        changed_blocks
+            .into_iter()
+            .filter(|(pos, (_, last_block))| world.get_block(*pos) == *last_block)
+            .map(|(pos, (first_block, _))| (pos, first_block))
+            .collect()
+    }
+
+    /// Show a player the world as it was `seconds_ago`, until `show_present` is called.
+    /// Return the names of the players who changed blocks since then, sorted.
+    pub fn show_past(&mut self, player: PlayerId, seconds_ago: f64, world: &World) -> Vec<Rc<str>> {
+        self.load_loaded_chunks(world);
+        let time = self.time - seconds_ago;
+        self.start_view(player, time, time, None);
+        let mut authors = self
+            .changes
+            .values()
+            .flat_map(|changes| changes.iter().rev().take_while(|change| change.time > time))
+            .filter_map(|change| change.player.clone())
+            .collect::<Vec<_>>();
+        authors.sort();
+        authors.dedup();
+        authors
+    }
+
+    /// Show a player the changes of the last `seconds_ago` in `duration` seconds, then the present
+    pub fn show_timelapse(&mut self, player: PlayerId, seconds_ago: f64, duration: f64) {
+        self.start_view(player, self.time - seconds_ago, self.time, Some(duration));
+    }
+
+    /// Show the present again to a player. Return false if they were not looking at the past.
+    pub fn show_present(&mut self, player: PlayerId) -> bool {
+        match self.views.get_mut(&player) {
+            Some(view) => {
+                view.ended = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn start_view(&mut self, player: PlayerId, from: f64, to: f64, duration: Option<f64>) {
+        // The chunks of the previous view are updated by the new one
+        let sent = self.views.remove(&player).map(|view| view.sent).unwrap_or_default();
+        self.views.insert(player, View { from, to, duration, elapsed: 0.0, ended: false, sent });
+    }
+
+    /// Send the past state of their loaded chunks to the players on the surface looking at the past.
+    /// This is called after the current chunks are sent, so that the past ones replace them.
+    pub fn send_views(
+        &mut self,
+        world: &World,
+        players: &mut HashMap<PlayerId, PlayerData>,
+        server: &mut dyn Server,
+        dt: f64,
+    ) {
+        // The changes of the chunks of the players looking at the past are needed to send them
+        let unloaded = self
+            .views
+            .keys()
+            .filter_map(|player| players.get(player).filter(|data| data.dimension == DimensionId::SURFACE))
+            .flat_map(|data| data.loaded_chunks.keys().copied())
+            .filter(|pos| !self.loaded.contains(pos))
+            .collect::<Vec<_>>();
+        for pos in unloaded {
+            self.load_chunk(pos, world);
+        }
+        let changes = &self.changes;
+        self.views.retain(|player, view| {
+            let data = match players.get_mut(player) {
+                Some(data) => data,
+                None => return false,
+            };
+            view.elapsed += dt;
+            if view.is_finished() {
+                // The current chunks will be sent again
+                for pos in view.sent.keys() {
+                    data.loaded_chunks.remove(pos);
+                }
+                if !view.ended {
+                    server.send(*player, ToClient::CommandOutput("End of the time-lapse".to_owned()));
+                }
+                return false;
+            }
+            // Only the surface has a history, and the player received the chunks of their dimension from scratch
+            if data.dimension != DimensionId::SURFACE {
+                view.sent.clear();
+                return true;
+            }
+
+            let time = view.shown_time();
+            let mut sent_chunks = 0;
+            for (&pos, &version) in data.loaded_chunks.iter() {
+                if sent_chunks == MAX_CHUNKS_PER_TICK {
+                    break;
+                }
+                let chunk_changes = match changes.get(&pos) {
+                    Some(chunk_changes) => chunk_changes,
+                    None => continue,
+                };
+                // The changes made after the shown time are undone
+                let applied = chunk_changes.partition_point(|change| change.time <= time);
+                if view.sent.get(&pos) == Some(&(version, applied)) {
+                    continue;
+                }
+                if applied == chunk_changes.len() && view.sent.remove(&pos).is_none() {
+                    // The player already has the current chunk
+                    continue;
+                }
+                let (chunk, light_chunk) = match (world.get_chunk(pos), world.get_light_chunk(pos)) {
+                    (Some(chunk), Some(light_chunk)) => (chunk, light_chunk),
+                    _ => continue,
+                };
+                let mut chunk = (*chunk).clone();
+                for change in chunk_changes[applied..].iter().rev() {
+                    chunk.set_block_at(change.pos.pos_in_containing_chunk(), change.old_block);
+                }
+                server.send(*player, ToClient::Chunk(DimensionId::SURFACE, Arc::new(chunk), light_chunk));
+                if applied < chunk_changes.len() {
+                    view.sent.insert(pos, (version, applied));
+                }
+                sent_chunks += 1;
+            }
+            view.sent.retain(|pos, _| data.loaded_chunks.contains_key(pos));
+            true
+        });
+    }
+
+    pub fn debug_info(&self) -> String {
+        format!(
+            "Recorded block changes: {} in {} chunks\nPlayers looking at the past: {}\n",
+            self.changes.values().map(Vec::len).sum::<usize>(),
+            self.changes.len(),
+            self.views.len(),
+        )
+    }
+}
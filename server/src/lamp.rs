@@ -0,0 +1,82 @@
+//! Lamps, switched on and off by the logic signals.
+
+use crate::logic::Signals;
+use crate::world::World;
+use common::{
+    block::{Block, BlockId, BlockType},
+    registry::Registry,
+    world::BlockPos,
+};
+use std::collections::HashSet;
+
+/// The lamp information of a block
+#[derive(Debug, Clone, Copy)]
+struct LampBlock {
+    /// The block of the other state of the lamp
+    other_state: BlockId,
+    is_lit: bool,
+    requires_signal: bool,
+}
+
+/// All the lamps of the world
+pub struct Lamps {
+    /// The lamp information of each block, if it is a lamp
+    lamp_blocks: Vec<Option<LampBlock>>,
+    /// The lamps of the world that need a signal to be lit
+    lamps: HashSet<BlockPos>,
+}
+
+impl Lamps {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let mut lamp_blocks = vec![None; block_registry.get_number_of_ids() as usize];
+        for id in 0..block_registry.get_number_of_ids() {
+            if let BlockType::Lamp { requires_signal, .. } = block_registry.get_value_by_id(id).unwrap().block_type {
+                let name = &block_registry.get_value_by_id(id).unwrap().name;
+                let unlit_id = block_registry
+                    .get_id_by_name(&format!("{}:off", name))
+                    .expect("Lamp has no unlit state");
+                lamp_blocks[id as usize] = Some(LampBlock {
+                    other_state: unlit_id as BlockId,
+                    is_lit: true,
+                    requires_signal,
+                });
+                lamp_blocks[unlit_id as usize] = Some(LampBlock {
+                    other_state: id as BlockId,
+                    is_lit: false,
+                    requires_signal,
+                });
+            }
+        }
+        Self {
+            lamp_blocks,
+            lamps: HashSet::new(),
+        }
+    }
+
+    fn get_lamp(&self, world: &World, pos: BlockPos) -> Option<LampBlock> {
+        self.lamp_blocks.get(world.get_block(pos) as usize).copied().flatten()
+    }
+
+    /// Take block modifications into account
+    pub fn update_blocks(&mut self, world: &World, changed_blocks: &[BlockPos]) {
+        for pos in changed_blocks {
+            if self.get_lamp(world, *pos).map(|lamp| lamp.requires_signal).unwrap_or(false) {
+                self.lamps.insert(*pos);
+            } else {
+                self.lamps.remove(pos);
+            }
+        }
+    }
+
+    /// Switch the lamps on or off depending on whether they receive a signal
+    pub fn tick(&mut self, world: &mut World, signals: &Signals) {
+        for pos in self.lamps.iter() {
+            if let Some(lamp) = self.get_lamp(world, *pos) {
+                if lamp.is_lit != signals.is_powered(*pos) {
+                    // Changing the block also updates the light around the lamp
+                    world.set_block(*pos, lamp.other_state);
+                }
+            }
+        }
+    }
+}
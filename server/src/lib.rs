@@ -1,43 +1,147 @@
-use crate::world::World;
+use crate::achievement::Achievements;
+use crate::admin::AdminListener;
+use crate::anticheat::AntiCheat;
+use crate::block_damage::{is_in_view, BlockDamages};
+use crate::claims::Claims;
+use crate::combat::{find_melee_target, Combat};
+use crate::command::{run_admin_command, run_command, COMMANDS};
+use crate::container::Containers;
+use crate::conveyor::Conveyors;
+use crate::crop::Crops;
+use crate::dimension::{Dimensions, PerDimension};
+use crate::door::Doors;
+use crate::history::BlockHistory;
+use crate::explosion::Explosions;
+use crate::gamerules::GameRules;
+use crate::lamp::Lamps;
+use crate::dropped_item::DroppedItems;
+use crate::effect::StatusEffects;
+use crate::events::{
+    subscribe_logger, BlockBroken, BlockChanged, BlockPlaced, Entity, EntitySpawned, EventBus, MeteorImpact, PlayerJoined,
+    PlayerLeft,
+};
+use crate::logic::Signals;
+use crate::meteor::Meteors;
+use crate::mob::Mobs;
+use crate::mods::Mods;
+use crate::permissions::{command_node, Permissions, BUILD, USE_MACHINES};
+use crate::pregen::Pregenerator;
+use crate::respawn::RespawnPoints;
+use crate::robot::{MiningArea, Robots};
+use crate::save::{load_world_meta, write_world_meta};
+use crate::scheduler::{seconds_to_ticks, Scheduler, Task};
+use crate::settings::{load_block_id_map, load_world_settings, write_block_id_map};
+use crate::sign::Signs;
+use crate::sleep::Sleep;
+use crate::statistics::Statistics;
+use crate::status::StatusListener;
+use crate::storage::{finish_translation, translate_stored_blocks};
+use crate::structure::Structures;
+use crate::storm::DustStorms;
+use crate::survival::Survival;
+use crate::teleport::{TeleportTarget, Teleports};
 use anyhow::Result;
 use log::info;
 use nalgebra::Vector3;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use common::block::BlockId;
+use common::item::{Item, ItemId, ItemStack};
+use common::robot::{RobotId, MAX_AREA_VOLUME};
 use common::physics::aabb::AABB;
 use common::physics::player::PhysicsPlayer;
 use common::{
-    data::load_data,
+    animation::MINE_ANIMATION,
+    combat::Combatant,
+    content::{ContentManifest, GAME_VERSION},
+    data::{
+        load_data_with_extensions,
+        cache::hash_bytes,
+        pack::{read_pack_info, DataPack, DATA_PACK_CHUNK_SIZE},
+        watcher::DataWatcher,
+        Data, DataErrorReport, DataExtensions,
+    },
     debug::{send_debug_info, send_perf_breakdown},
+    profile_scope,
     network::{
         messages::{ToClient, ToServer},
+        stats::NetworkStats,
         Server, ServerEvent,
     },
     physics::simulation::ServerPhysicsSimulation,
-    player::{CloseChunks, RenderDistance},
+    player::{CloseChunks, PlayerId, PlayerProfile, RenderDistance},
+    registry::Registry,
+    statistics::PlayerStatistics,
     world::{
         ChunkPos,
         BlockPos,
+        DimensionId,
+        FACE_OFFSETS,
     },
-    worldgen::DefaultWorldGenerator,
 };
-use common::time::BreakdownCounter;
+use common::time::{BreakdownCounter, FixedTimestep, WorldTime, TICK_DURATION};
 
+mod achievement;
+mod admin;
+mod anticheat;
+mod block_damage;
+mod claims;
+mod combat;
+mod command;
+mod container;
+mod conveyor;
+mod crop;
+mod dimension;
+mod door;
+mod dropped_item;
+mod effect;
+mod events;
+mod explosion;
+mod gamerules;
+mod history;
+mod lamp;
 mod light;
+mod logic;
+mod loot;
+mod memory;
+mod meteor;
+mod mob;
+mod mods;
+mod permissions;
+mod pregen;
+mod respawn;
+mod robot;
+mod save;
+mod scheduler;
+mod settings;
+mod sign;
+mod sleep;
+mod statistics;
+mod status;
+mod storage;
+mod storm;
+mod structure;
+mod survival;
+mod teleport;
 mod world;
 mod worldgen;
 
-// TODO: refactor
-const D: [[i64; 3]; 6] = [
-    [1, 0, 0],
-    [-1, 0, 0],
-    [0, 1, 0],
-    [0, -1, 0],
-    [0, 0, 1],
-    [0, 0, -1],
-];
+pub use crate::admin::forward_log;
+
+/// Seconds between two saves of what is saved periodically, such as the statistics
+const AUTOSAVE_INTERVAL: f64 = 60.0;
+
+/// Told to the players who try to change a block in the claim of someone else
+const PROTECTED_MESSAGE: &str = "This area is claimed by another player";
+
+/// Distance from the center of the players up to which they pick up the items lying in the world
+const PICKUP_RADIUS: f64 = 1.5;
+
+/// Number of chunks of the data packs sent to each player every tick, see `DATA_PACK_CHUNK_SIZE`
+const DATA_PACK_CHUNKS_PER_TICK: usize = 8;
 
 /// The data that the server stores for every player.
 pub struct PlayerData {
@@ -45,6 +149,17 @@ pub struct PlayerData {
     render_distance: RenderDistance,
     close_chunks: CloseChunks,
     block_to_place: BlockId,
+    robot: Option<RobotId>,
+    /// True if the player is controlling their robot
+    controlling_robot: bool,
+    /// The name and character model of the player, once they sent them
+    profile: Option<PlayerProfile>,
+    /// The dimension of the player, whose chunks they receive
+    dimension: DimensionId,
+    /// True if the hash of the game data was announced to the player, who can then ask for the data once
+    data_announced: bool,
+    /// The data pack being sent to the player, and the number of bytes already sent
+    data_pack_sent: Option<(Arc<Vec<u8>>, usize)>,
 }
 
 impl Default for PlayerData {
@@ -56,50 +171,423 @@ impl Default for PlayerData {
             render_distance,
             close_chunks,
             block_to_place: 1,
+            robot: None,
+            controlling_robot: false,
+            profile: None,
+            dimension: DimensionId::SURFACE,
+            data_announced: false,
+            data_pack_sent: None,
         }
     }
 }
 
-/// Start a new server instance.
-pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
-    info!("Starting server");
+/// Remove a player who left or was kicked
+#[allow(clippy::too_many_arguments)]
+fn remove_player(
+    id: PlayerId,
+    players: &mut HashMap<PlayerId, PlayerData>,
+    physics_simulation: &mut ServerPhysicsSimulation,
+    robots: &mut Robots,
+    anti_cheat: &mut AntiCheat,
+    game_rules: &mut GameRules,
+    permissions: &mut Permissions,
+    claims: &mut Claims,
+    signs: &mut Signs,
+    survival: &mut Survival,
+    status_effects: &mut StatusEffects,
+    items: &Registry<Item>,
+    events: &mut EventBus,
+) {
+    if let Some(data) = players.remove(&id) {
+        if let Some(robot_id) = data.robot {
+            robots.release_control(robot_id);
+        }
+        physics_simulation.remove(id);
+        anti_cheat.remove_player(id);
+        game_rules.remove_player(id);
+        permissions.remove_player(id);
+        claims.remove_player(id);
+        signs.remove_player(id);
+        survival.remove_player(id, items);
+        status_effects.remove_player(id);
+        events.publish(PlayerLeft { player: id });
+    }
+}
 
-    let mut server_timing = BreakdownCounter::new();
+/// Make the players in the dimension of a player, including them, play an animation of their character
+/// Read and encode the data pack of the data directory and of the mods, and compute its hash
+fn encode_data_pack(data_directory: PathBuf, extensions: DataExtensions) -> Result<(u64, Arc<Vec<u8>>)> {
+    let bytes = DataPack::read(data_directory, extensions)?.encode()?;
+    Ok((hash_bytes(&bytes), Arc::new(bytes)))
+}
 
-    // Load data
-    let game_data = load_data("data".into())?;
+/// Send the next chunks of the data pack being sent to a player, if there is one
+fn send_data_pack_chunks(server: &mut dyn Server, player: PlayerId, data: &mut PlayerData) {
+    if let Some((bytes, sent)) = &mut data.data_pack_sent {
+        for _ in 0..DATA_PACK_CHUNKS_PER_TICK {
+            let end = (*sent + DATA_PACK_CHUNK_SIZE).min(bytes.len());
+            server.send(player, ToClient::DataPackChunk(bytes[*sent..end].to_vec()));
+            *sent = end;
+            if *sent == bytes.len() {
+                data.data_pack_sent = None;
+                break;
+            }
+        }
+    }
+}
 
-    let mut world = World::new(
-        game_data.blocks.clone(),
-        Box::new(DefaultWorldGenerator::new(&game_data.blocks.clone())),
-    );
-    let mut players = HashMap::new();
-    let mut physics_simulation = ServerPhysicsSimulation::new();
-    let mut close_chunks_merged = Vec::new();
+fn play_animation(server: &mut dyn Server, players: &HashMap<PlayerId, PlayerData>, player: PlayerId, animation: &str) {
+    let dimension = players[&player].dimension;
+    for (&other, _) in players.iter().filter(|(_, data)| data.dimension == dimension) {
+        server.send(other, ToClient::PlayAnimation(player, animation.to_owned()));
+    }
+}
+
+/// A running game: the world, the players, and everything that happens to them.
+/// `launch_server` runs it in real time, the tests step it one tick at a time.
+pub struct ServerGame {
+    server: Box<dyn Server>,
+    server_timing: BreakdownCounter,
+    mods: Mods,
+    data_directory: PathBuf,
+    game_data: Data,
+    /// The content hash of the game data, which the clients use to cache it
+    data_hash: u64,
+    /// The encoded data pack of the game data and its hash, sent to the clients that don't have the data in cache
+    data_pack: (u64, Arc<Vec<u8>>),
+    /// The version of the game, the data pack and the mods, that the clients check before joining
+    content: ContentManifest,
+    events: EventBus,
+    achievements: Achievements,
+    statistics: Statistics,
+    /// The recorder of the block changes, disabled if None
+    history: Option<BlockHistory>,
+    teleports: Teleports,
+    respawn_points: RespawnPoints,
+    game_rules: GameRules,
+    permissions: Permissions,
+    claims: Claims,
+    signs: Signs,
+    structures: Structures,
+    survival: Survival,
+    status_effects: StatusEffects,
+    containers: Containers,
+    scheduler: Scheduler,
+    dimensions: Dimensions,
+    pregenerator: Pregenerator,
+    players: HashMap<PlayerId, PlayerData>,
+    physics_simulation: ServerPhysicsSimulation,
+    robots: Robots,
+    block_damages: BlockDamages,
+    conveyors: Conveyors,
+    dropped_items: PerDimension<DroppedItems>,
+    signals: PerDimension<Signals>,
+    doors: PerDimension<Doors>,
+    lamps: PerDimension<Lamps>,
+    crops: Crops,
+    explosions: PerDimension<Explosions>,
+    meteors: Meteors,
+    dust_storms: DustStorms,
+    mobs: Mobs,
+    combat: Combat,
+    world_time: WorldTime,
+    sleep: Sleep,
+    timestep: FixedTimestep,
+    close_chunks_merged: Vec<CloseChunkPos>,
+    data_watcher: Option<DataWatcher>,
+    admin: Option<AdminListener>,
+    anti_cheat: AntiCheat,
+    /// The statistics of the network a second ago, to compute the bandwidth
+    previous_network_stats: (Instant, NetworkStats),
+    status: Option<StatusListener>,
+}
+
+impl ServerGame {
+    /// Load the data and the save, and start the background workers
+    pub fn new(mut server: Box<dyn Server>, data_directory: PathBuf, save_folder: &Path) -> Result<Self> {
+        let server_timing = BreakdownCounter::new();
+
+        // Load the mods first, because they can add blocks and items to the data
+        let mut mods = Mods::load(Path::new("mods"));
+
+        // Load data
+        let game_data = match load_data_with_extensions(data_directory.clone(), mods.get_extensions()) {
+            Ok(game_data) => game_data,
+            Err(e) => {
+                // Tell the clients what is wrong with the data before stopping
+                let report = e.downcast_ref::<DataErrorReport>().cloned().unwrap_or_else(|| {
+                    let mut report = DataErrorReport::default();
+                    report.add("data", format!("{:#}", e));
+                    report
+                });
+                loop {
+                    match server.receive_event() {
+                        ServerEvent::NoEvent => break,
+                        ServerEvent::ClientConnected(id) => server.send(id, ToClient::DataErrors(report.clone())),
+                        _ => (),
+                    }
+                }
+                return Err(e);
+            }
+        };
+        mods.set_data(&game_data);
+        let data_hash = game_data.content_hash();
+        let data_pack = encode_data_pack(data_directory.clone(), mods.get_extensions())?;
+        let content = ContentManifest {
+            game_version: GAME_VERSION.to_owned(),
+            data_packs: vec![read_pack_info(data_directory.clone())?],
+            mods: mods.content(),
+        };
+        info!("Content: {:?}", content);
+        // The save is upgraded before anything is loaded from it
+        let mut world_meta = load_world_meta(save_folder)?;
+        let world_settings = load_world_settings(save_folder, &save_folder.join("settings.ron"))?;
+        // The ids of the blocks change with the data packs: keep the map of the save to translate the stored ids
+        let block_id_map_path = save_folder.join("block_ids.ron");
+        finish_translation(save_folder, &block_id_map_path)?;
+        if let Some(saved_id_map) = load_block_id_map(&block_id_map_path)? {
+            let translation = game_data.blocks.translation_from(&saved_id_map);
+            for (saved_id, current_id) in translation.iter().enumerate() {
+                match current_id {
+                    None => log::warn!("Block {} was removed from the data packs", saved_id_map.keys[saved_id]),
+                    Some(id) if *id != saved_id as u32 => {
+                        info!("Block {} moved from id {} to {}", saved_id_map.keys[saved_id], saved_id, id)
+                    }
+                    _ => (),
+                }
+            }
+            // The stored chunks are translated before the new ids are saved
+            if translation.iter().enumerate().any(|(saved_id, current_id)| *current_id != Some(saved_id as u32)) {
+                translate_stored_blocks(save_folder, &translation, &game_data.blocks.id_map(), &block_id_map_path)?;
+            }
+        }
+        write_block_id_map(&block_id_map_path, &game_data.blocks.id_map())?;
+
+        // The subscribers receive the events in the order they are registered
+        let mut events = EventBus::new();
+        subscribe_logger(&mut events);
+        mods.subscribe(&mut events);
+        let achievements = Achievements::load(save_folder.join("achievements.ron"))?;
+        achievements.subscribe(&mut events);
+        let statistics = Statistics::load(save_folder.join("statistics.ron"))?;
+        statistics.subscribe(&mut events);
+        let history = world_settings.history.clone()
+            .map(|settings| BlockHistory::load(settings, save_folder.join("history.ron"), game_data.blocks.clone()))
+            .transpose()?;
+        if let Some(history) = &history {
+            history.subscribe(&mut events);
+        }
+        let teleports = Teleports::load(save_folder.join("homes.ron"))?;
+        let respawn_points = RespawnPoints::load(save_folder.join("respawn_points.ron"), &game_data.blocks)?;
+        let game_rules = GameRules::load(save_folder.join("gamerules.ron"))?;
+        let permissions = Permissions::load(save_folder.join("permissions.ron"))?;
+
+        let dimensions = Dimensions::new(&game_data, &world_settings, save_folder, &mut world_meta)?;
+        write_world_meta(save_folder, &world_meta)?;
+        let pregenerator = Pregenerator::new();
+        let claims = Claims::load(save_folder.join("claims.ron"), &dimensions)?;
+        let signs = Signs::load(save_folder.join("signs.ron"), &game_data.blocks)?;
+        let structures = Structures::load(save_folder.join("structures.ron"), &dimensions)?;
+        let survival = Survival::load(save_folder.join("survival.ron"), world_settings.survival.clone())?;
+        let status_effects = StatusEffects::new(world_settings.effects, &game_data.blocks);
+        let mut scheduler = Scheduler::load(save_folder.join("scheduled_tasks.ron"))?;
+        scheduler.run_every(seconds_to_ticks(AUTOSAVE_INTERVAL), Task::Autosave);
+        let players = HashMap::new();
+        let physics_simulation = ServerPhysicsSimulation::new();
+        let robots = Robots::new(&game_data);
+        let block_damages = BlockDamages::new();
+        let conveyors = Conveyors::new(&game_data.blocks);
+        let containers = Containers::new(robots.get_storage_block());
+        let dropped_items = PerDimension::new(&dimensions, |_| DroppedItems::new(robots.get_storage_block()));
+        let signals = PerDimension::new(&dimensions, |_| Signals::new(&game_data.blocks));
+        let doors = PerDimension::new(&dimensions, |id| Doors::new(&game_data.blocks, id));
+        let lamps = PerDimension::new(&dimensions, |_| Lamps::new(&game_data.blocks));
+        let crops = Crops::new(&game_data.blocks);
+        let explosions = PerDimension::new(&dimensions, |_| Explosions::new(&game_data));
+        let meteors = Meteors::new(world_settings.meteors, &game_data.blocks);
+        let dust_storms = DustStorms::new(world_settings.dust_storms);
+        let mobs = Mobs::new(world_settings.mobs, &game_data.blocks, &game_data.items);
+        let combat = Combat::new(world_settings.combat);
+        let world_time = WorldTime::default();
+        let sleep = Sleep::default();
+        let mut timestep = FixedTimestep::new(TICK_DURATION);
+        timestep.set_time_scale(world_settings.time_scale);
+        let close_chunks_merged = Vec::new();
+        let data_watcher = if world_settings.hot_reload_data {
+            Some(DataWatcher::new(data_directory.clone()))
+        } else {
+            None
+        };
+        let admin = match &world_settings.admin {
+            Some(admin_settings) => Some(AdminListener::start(admin_settings)?),
+            None => None,
+        };
+        let anti_cheat = AntiCheat::new(world_settings.anti_cheat.clone());
+        let previous_network_stats = (Instant::now(), server.stats());
+        let status = match &world_settings.status {
+            Some(status_settings) => Some(StatusListener::start(status_settings)?),
+            None => None,
+        };
+
+        Ok(Self {
+            server,
+            server_timing,
+            mods,
+            data_directory,
+            game_data,
+            data_hash,
+            data_pack,
+            content,
+            events,
+            achievements,
+            statistics,
+            history,
+            teleports,
+            respawn_points,
+            game_rules,
+            permissions,
+            claims,
+            signs,
+            structures,
+            survival,
+            status_effects,
+            containers,
+            scheduler,
+            dimensions,
+            pregenerator,
+            players,
+            physics_simulation,
+            robots,
+            block_damages,
+            conveyors,
+            dropped_items,
+            signals,
+            doors,
+            lamps,
+            crops,
+            explosions,
+            meteors,
+            dust_storms,
+            mobs,
+            combat,
+            world_time,
+            sleep,
+            timestep,
+            close_chunks_merged,
+            data_watcher,
+            admin,
+            anti_cheat,
+            previous_network_stats,
+            status,
+        })
+    }
+
+    /// Handle the network events and the administration commands, and receive the chunks of the workers
+    fn handle_events(&mut self) {
+        let Self {
+            server,
+            server_timing,
+            game_data,
+            data_hash,
+            data_pack,
+            content,
+            events,
+            achievements,
+            statistics,
+            history,
+            teleports,
+            respawn_points,
+            game_rules,
+            permissions,
+            claims,
+            signs,
+            structures,
+            survival,
+            status_effects,
+            containers,
+            scheduler,
+            dimensions,
+            pregenerator,
+            players,
+            physics_simulation,
+            robots,
+            conveyors,
+            dropped_items,
+            doors,
+            explosions,
+            dust_storms,
+            mobs,
+            combat,
+            world_time,
+            sleep,
+            admin,
+            anti_cheat,
+            status,
+            ..
+        } = self;
 
-    info!("Server initialized successfully! Starting server loop");
-    loop {
         server_timing.start_frame();
 
         // Handle messages
+        let mut kicks = Vec::new();
         loop {
             match server.receive_event() {
                 ServerEvent::NoEvent => break,
                 ServerEvent::ClientConnected(id) => {
                     info!("Client connected to the server!");
                     physics_simulation.set_player_input(id, Default::default());
-                    players.insert(id, PlayerData::default());
-                    server.send(id, ToClient::GameData(game_data.clone()));
+                    players.insert(id, PlayerData { data_announced: true, ..Default::default() });
+                    survival.add_player(id);
+                    signs.add_player(id);
+                    events.publish(PlayerJoined { player: id });
+                    // The client checks that it can use the content before it uses the data
+                    server.send(id, ToClient::Content(content.clone()));
+                    server.send(id, ToClient::DataHash(*data_hash));
                     server.send(id, ToClient::CurrentId(id));
+                    // The players join on the surface
+                    server.send(id, ToClient::WorldHeight(dimensions.surface().height()));
+                    for (&other_id, other_data) in players.iter() {
+                        if let Some(profile) = &other_data.profile {
+                            server.send(id, ToClient::PlayerProfile(other_id, profile.clone()));
+                        }
+                    }
+                    survival.send_flashlights(id, &mut **server);
                 }
                 ServerEvent::ClientDisconnected(id) => {
-                    physics_simulation.remove(id);
-                    players.remove(&id);
+                    remove_player(
+                        id,
+                        players,
+                        physics_simulation,
+                        robots,
+                        anti_cheat,
+                        game_rules,
+                        permissions,
+                        claims,
+                        signs,
+                        survival,
+                        status_effects,
+                        &game_data.items,
+                        events,
+                    );
                 }
+                // The messages of the kicked players that were sent before the kick are ignored
+                ServerEvent::ClientMessage(id, _) if !players.contains_key(&id) => {}
                 ServerEvent::ClientMessage(id, message) => match message {
                     ToServer::UpdateInput(input) => {
-                        assert!(players.contains_key(&id));
-                        physics_simulation.set_player_input(id, input);
+                        let mut input = match anti_cheat.check_input(id, input) {
+                            Some(input) => input,
+                            None => continue,
+                        };
+                        input.flying &= game_rules.capabilities(id).can_fly && survival.can_fly(id);
+                        let player_data = players.get(&id).unwrap();
+                        match player_data.robot {
+                            Some(robot_id) if player_data.controlling_robot => {
+                                // The player's own entity stays still while the robot moves
+                                robots.set_control_input(robot_id, input);
+                                physics_simulation.set_player_input(id, input.without_movement());
+                            }
+                            _ => physics_simulation.set_player_input(id, input),
+                        }
                     }
                     ToServer::SetRenderDistance(render_distance) => {
                         assert!(players.contains_key(&id));
@@ -107,8 +595,23 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                             player_data.render_distance = render_distance
                         });
                     }
+                    ToServer::BreakBlock(_, _, _)
+                    | ToServer::PlaceBlock(_, _, _)
+                    | ToServer::AssignMiningArea(_, _)
+                    | ToServer::SetSignText(_, _)
+                        if !game_rules.capabilities(id).can_build || !permissions.has_permission(id, BUILD) =>
+                    {
+                        server.send(id, ToClient::CommandOutput("You are not allowed to build".to_owned()));
+                    }
+                    ToServer::InteractBlock(_, _, _) | ToServer::SetRobotControl(_) | ToServer::Trade(_, _)
+                        if !permissions.has_permission(id, USE_MACHINES) =>
+                    {
+                        server.send(id, ToClient::CommandOutput("You are not allowed to use the machines".to_owned()));
+                    }
                     ToServer::BreakBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
+                        if !anti_cheat.check_action_position(id, player_pos, physics_simulation) {
+                            continue;
+                        }
                         let physics_player = PhysicsPlayer {
                             aabb: AABB {
                                 pos: player_pos,
@@ -117,19 +620,33 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                                 size_z: 0.0,
                             },
                             velocity: Vector3::zeros(),
+                            knockback: Vector3::zeros(),
+                            speed_multiplier: 1.0,
                         };
                         let y = yaw.to_radians();
                         let p = pitch.to_radians();
                         let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+                        let dimension = players[&id].dimension;
+                        let world = dimensions.get_mut(dimension);
                         // TODO: don't hardcode max dist
                         if let Some((block, _face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
+                            physics_player.get_pointed_at(dir, 10.0, &*world)
                         {
-                            let chunk_pos = block.containing_chunk_pos();
-                            if let Some(chunk) = world.get_chunk(chunk_pos) {
-                                let mut new_chunk = (*chunk).clone();
-                                new_chunk.set_block_at(block.pos_in_containing_chunk(), 0);
-                                world.set_chunk(Arc::new(new_chunk));
+                            if !anti_cheat.check_reach(id, block, physics_simulation) || !anti_cheat.check_break(id) {
+                                continue;
+                            }
+                            if !claims.is_allowed(id, dimension, block, permissions) {
+                                server.send(id, ToClient::CommandOutput(PROTECTED_MESSAGE.to_owned()));
+                                continue;
+                            }
+                            let block_id = world.get_block(block);
+                            if world.set_block(block, 0) {
+                                let pos = Vector3::new(block.px as f64 + 0.5, block.py as f64, block.pz as f64 + 0.5);
+                                for stack in world.loot_mut().block_drops(block_id, survival.held_item(id)) {
+                                    dropped_items[dimension].spawn(pos, stack);
+                                }
+                                events.publish(BlockBroken { player: id, dimension, pos: block, block: block_id });
+                                play_animation(&mut **server, players, id, MINE_ANIMATION);
                             }
                         }
                     }
@@ -143,20 +660,25 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                                 size_z: 0.0,
                             },
                             velocity: Vector3::zeros(),
+                            knockback: Vector3::zeros(),
+                            speed_multiplier: 1.0,
                         };
                         let y = yaw.to_radians();
                         let p = pitch.to_radians();
                         let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+                        let world = dimensions.get(players[&id].dimension);
                         // TODO: don't hardcode max dist
                         if let Some((block, _face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
+                            physics_player.get_pointed_at(dir, 10.0, world)
                         {
                             // TODO: careful with more complicated blocks
                             players.get_mut(&id).unwrap().block_to_place = world.get_block(block);
                         }
                     }
                     ToServer::PlaceBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
+                        if !anti_cheat.check_action_position(id, player_pos, physics_simulation) {
+                            continue;
+                        }
                         let physics_player = PhysicsPlayer {
                             aabb: AABB {
                                 pos: player_pos,
@@ -165,114 +687,1081 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                                 size_z: 0.0,
                             },
                             velocity: Vector3::zeros(),
+                            knockback: Vector3::zeros(),
+                            speed_multiplier: 1.0,
                         };
                         let y = yaw.to_radians();
                         let p = pitch.to_radians();
                         let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+                        let dimension = players[&id].dimension;
+                        let world = dimensions.get_mut(dimension);
                         // TODO: don't hardcode max dist
                         if let Some((mut block, face)) =
-                        physics_player.get_pointed_at(dir, 10.0, &world)
+                        physics_player.get_pointed_at(dir, 10.0, &*world)
                         {
-                            block.px += D[face][0];
-                            block.py += D[face][1];
-                            block.pz += D[face][2];
-                            let chunk_pos = block.containing_chunk_pos();
-                            if let Some(chunk) = world.get_chunk(chunk_pos) {
-                                let mut new_chunk = (*chunk).clone();
-                                new_chunk.set_block_at(block.pos_in_containing_chunk(), players.get(&id).unwrap().block_to_place);
-                                world.set_chunk(Arc::new(new_chunk));
+                            block.px += FACE_OFFSETS[face][0];
+                            block.py += FACE_OFFSETS[face][1];
+                            block.pz += FACE_OFFSETS[face][2];
+                            if !anti_cheat.check_reach(id, block, physics_simulation) {
+                                continue;
+                            }
+                            if !claims.is_allowed(id, dimension, block, permissions) {
+                                server.send(id, ToClient::CommandOutput(PROTECTED_MESSAGE.to_owned()));
+                                continue;
+                            }
+                            let block_id = players.get(&id).unwrap().block_to_place;
+                            if world.set_block(block, block_id) {
+                                events.publish(BlockPlaced { player: id, dimension, pos: block, block: block_id });
+                                // The player who placed a sign writes its text
+                                if signs.is_sign(block_id) {
+                                    let text = signs.text(dimension, block, dimensions).to_owned();
+                                    server.send(id, ToClient::EditSign(block, text));
+                                }
+                            }
+                        }
+                    }
+                    ToServer::InteractBlock(player_pos, yaw, pitch) => {
+                        if !anti_cheat.check_action_position(id, player_pos, physics_simulation) {
+                            continue;
+                        }
+                        let physics_player = PhysicsPlayer {
+                            aabb: AABB {
+                                pos: player_pos,
+                                size_x: 0.0,
+                                size_y: 0.0,
+                                size_z: 0.0,
+                            },
+                            velocity: Vector3::zeros(),
+                            knockback: Vector3::zeros(),
+                            speed_multiplier: 1.0,
+                        };
+                        let y = yaw.to_radians();
+                        let p = pitch.to_radians();
+                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+                        let dimension = players[&id].dimension;
+                        // TODO: don't hardcode max dist
+                        if let Some((block, _face)) =
+                            physics_player.get_pointed_at(dir, 10.0, dimensions.get(dimension))
+                        {
+                            if !anti_cheat.check_reach(id, block, physics_simulation) {
+                                continue;
+                            }
+                            if !claims.is_allowed(id, dimension, block, permissions) {
+                                server.send(id, ToClient::CommandOutput(PROTECTED_MESSAGE.to_owned()));
+                                continue;
                             }
+                            let block_id = dimensions.get(dimension).get_block(block);
+                            if let Some(target) = dimensions.elevator_target(block_id) {
+                                // The player arrives on top of the same block in the other dimension
+                                let destination = Vector3::new(block.px as f64 + 0.5, block.py as f64 + 1.0, block.pz as f64 + 0.5);
+                                teleports.use_elevator(id, target, destination, dimensions.elevator_to(dimension));
+                            } else if let Some(trades) = game_data.terminal_trades(block_id) {
+                                server.send(id, ToClient::Trades(block, trades.to_vec()));
+                            } else if containers.is_container(block_id) {
+                                containers.open(id, dimension, block);
+                            } else if respawn_points.is_respawn_pod(block_id) {
+                                if respawn_points.use_pod(id, dimension, block, dimensions) {
+                                    server.send(id, ToClient::RespawnPointSet(block));
+                                }
+                                // At night, the players also rest in the pod to skip the night
+                                if !world_time.is_day() {
+                                    sleep.rest(id, dimension, block);
+                                }
+                            } else if status_effects.is_med_station(block_id) {
+                                status_effects.use_med_station(id);
+                                server.send(id, ToClient::CommandOutput("The med station treats your wounds".to_owned()));
+                            } else if signs.is_sign(block_id) {
+                                let text = signs.text(dimension, block, dimensions).to_owned();
+                                server.send(id, ToClient::EditSign(block, text));
+                            } else if doors[dimension].is_door(block_id) {
+                                doors[dimension].interact(dimensions.get_mut(dimension), scheduler, block);
+                            } else if explosions[dimension].is_explosive(block_id) {
+                                explosions[dimension].detonate(dimensions.get(dimension), block);
+                            }
+                        }
+                    }
+                    ToServer::Attack(player_pos, yaw, pitch) => {
+                        if !anti_cheat.check_action_position(id, player_pos, physics_simulation) {
+                            continue;
+                        }
+                        let y = yaw.to_radians();
+                        let p = pitch.to_radians();
+                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
+                        let player_data = &players[&id];
+                        let dimension = player_data.dimension;
+                        let name = player_data.profile.as_ref().map(|profile| profile.name.as_str()).unwrap_or("<unknown>");
+                        // A player controlling their robot hits with the robot
+                        let controlled_robot = player_data.robot.filter(|_| player_data.controlling_robot);
+                        let (attacker, origin, cause) = match controlled_robot.and_then(|robot_id| robots.get(robot_id)) {
+                            Some(robot) if robot.energy <= 0.0 => continue,
+                            Some(robot) => {
+                                let cause = format!("You were knocked out by the robot of {}", name);
+                                (Combatant::Robot(controlled_robot.unwrap()), robot.pos, cause)
+                            }
+                            None => match physics_simulation.get_state().physics_state.players.get(&id) {
+                                Some(player) => {
+                                    let cause = format!("You were knocked out by {}", name);
+                                    (Combatant::Player(id), player.get_camera_position(), cause)
+                                }
+                                None => continue,
+                            },
+                        };
+                        let mut candidates = Vec::new();
+                        if game_rules.rules().pvp {
+                            for (&other, player) in physics_simulation.get_state().physics_state.players.iter() {
+                                if other != id && players.get(&other).map(|data| data.dimension) == Some(dimension) {
+                                    candidates.push((Combatant::Player(other), player.aabb.clone()));
+                                }
+                            }
+                        }
+                        // The robots and the creatures only exist on the surface
+                        if dimension == DimensionId::SURFACE {
+                            let robots = robots.get_infos().into_iter().filter(|robot| Combatant::Robot(robot.id) != attacker);
+                            candidates.extend(robots.map(|robot| (Combatant::Robot(robot.id), robot.aabb())));
+                            candidates.extend(mobs.get_infos().into_iter().map(|mob| (Combatant::Mob(mob.id), mob.aabb())));
+                        }
+                        if attacker == Combatant::Player(id) {
+                            play_animation(&mut **server, players, id, MINE_ANIMATION);
+                        }
+                        if let Some(target) = find_melee_target(dimensions.get(dimension), origin, dir, &candidates) {
+                            let hit = combat.melee_hit(attacker, target, dir, cause);
+                            let dropped_items = &mut dropped_items[dimension];
+                            combat.apply_hit(hit, physics_simulation, survival, robots, mobs, dropped_items);
+                        }
+                    }
+                    ToServer::AssignMiningArea(_, _) if players[&id].dimension != DimensionId::SURFACE => {
+                        server.send(id, ToClient::CommandOutput("The robots only work on the surface".to_owned()));
+                    }
+                    ToServer::AssignMiningArea(from, to) => {
+                        let area = MiningArea::new(from, to);
+                        if area.volume().is_none_or(|volume| volume > MAX_AREA_VOLUME) {
+                            log::warn!("Player {:?} assigned a mining area that is too large", id);
+                            continue;
+                        }
+                        if let Some(claim) = claims.find_protected(id, DimensionId::SURFACE, from, to, permissions) {
+                            let message = format!("The mining area overlaps the claim {}", claim);
+                            server.send(id, ToClient::CommandOutput(message));
+                            continue;
+                        }
+                        let player_data = players.get_mut(&id).unwrap();
+                        let robot_id = match player_data.robot {
+                            Some(robot_id) => robot_id,
+                            None if !game_rules.rules().robot_spawning => {
+                                let message = "The robots can't be spawned on this server".to_owned();
+                                server.send(id, ToClient::CommandOutput(message));
+                                continue;
+                            }
+                            None => {
+                                let pos = physics_simulation
+                                    .get_state()
+                                    .physics_state
+                                    .players
+                                    .get(&id)
+                                    .unwrap()
+                                    .get_camera_position();
+                                let robot_id = robots.spawn(id, pos);
+                                events.publish(EntitySpawned { entity: Entity::Robot { id: robot_id, owner: id }, pos });
+                                player_data.robot = Some(robot_id);
+                                robot_id
+                            }
+                        };
+                        robots.assign_area(robot_id, area, dimensions.surface(), conveyors);
+                    }
+                    ToServer::SetRobotControl(control) => {
+                        let player_data = players.get_mut(&id).unwrap();
+                        if let Some(robot_id) = player_data.robot {
+                            // The robots stay on the surface
+                            if control && !player_data.controlling_robot && player_data.dimension == DimensionId::SURFACE {
+                                player_data.controlling_robot = robots.take_control(robot_id, id);
+                            } else if !control && player_data.controlling_robot {
+                                robots.release_control(robot_id);
+                                player_data.controlling_robot = false;
+                            }
+                            let controlled = if player_data.controlling_robot { Some(robot_id) } else { None };
+                            server.send(id, ToClient::ControlRobot(controlled));
+                        }
+                    }
+                    // The name identifies the player for the permissions, the claims and what is saved of them: it is
+                    // chosen once when joining, and two connected players can't share it
+                    ToServer::SetProfile(_) if players[&id].profile.is_some() => {
+                        server.send(id, ToClient::CommandOutput("You can't change your name or model".to_owned()));
+                    }
+                    ToServer::SetProfile(profile) => {
+                        let profile = profile.sanitized(&game_data.character_models);
+                        let mut profiles = players.values().filter_map(|data| data.profile.as_ref());
+                        if profiles.any(|other| other.name == profile.name) {
+                            kicks.push((id, format!("The name {} is already in use", profile.name)));
+                            continue;
+                        }
+                        info!("Player {:?} is now called {:?}", id, profile.name);
+                        for &other_id in players.keys() {
+                            server.send(other_id, ToClient::PlayerProfile(id, profile.clone()));
+                        }
+                        let unlocked = achievements.set_player_name(id, profile.name.clone());
+                        statistics.set_player_name(id, profile.name.clone());
+                        if let Some(history) = history.as_mut() {
+                            history.set_player_name(id, profile.name.clone());
+                        }
+                        teleports.set_player_name(id, profile.name.clone());
+                        respawn_points.set_player_name(id, profile.name.clone());
+                        game_rules.set_player_name(id, profile.name.clone());
+                        permissions.set_player_name(id, profile.name.clone());
+                        claims.set_player_name(id, profile.name.clone());
+                        survival.set_player_name(id, profile.name.clone(), &game_data.items);
+                        server.send(id, ToClient::Achievements(unlocked));
+                        players.get_mut(&id).unwrap().profile = Some(profile);
+                    }
+                    ToServer::Command(command) => {
+                        let output = run_command(
+                            id,
+                            &command,
+                            statistics,
+                            history.as_mut(),
+                            teleports,
+                            respawn_points,
+                            game_rules,
+                            permissions,
+                            claims,
+                            structures,
+                            dimensions,
+                            pregenerator,
+                        );
+                        server.send(id, ToClient::CommandOutput(output));
+                    }
+                    ToServer::RequestStatistics => {
+                        let player_statistics = statistics.get(id).cloned().unwrap_or_default();
+                        server.send(id, ToClient::Statistics(player_statistics));
+                    }
+                    ToServer::SelectSlot(slot) => survival.select_slot(id, slot),
+                    ToServer::UseItem => {
+                        if let Err(reason) = survival.start_use(id, &game_data.items) {
+                            server.send(id, ToClient::CommandOutput(reason.to_owned()));
+                        }
+                    }
+                    ToServer::Ping(ping) => server.send(id, ToClient::Pong(ping)),
+                    // The clients check the content themselves before joining, this is for the other clients
+                    ToServer::Hello(game_version) => {
+                        let missing = content.missing_for(&game_version);
+                        if !missing.is_empty() {
+                            kicks.push((id, format!("Missing content: {}", missing.join(", "))));
+                        }
+                    }
+                    // The data is large, so it is only sent once for each announced hash
+                    ToServer::RequestData => {
+                        let player_data = players.get_mut(&id).unwrap();
+                        if std::mem::take(&mut player_data.data_announced) {
+                            let (hash, bytes) = data_pack;
+                            server.send(id, ToClient::DataPack(*hash, bytes.len()));
+                            player_data.data_pack_sent = Some((bytes.clone(), 0));
                         }
                     }
+                    ToServer::Emote(name) => {
+                        // Only the animations of the character models can be played
+                        if game_data.animations.values().any(|animations| animations.contains_key(&name)) {
+                            play_animation(&mut **server, players, id, &name);
+                        } else {
+                            server.send(id, ToClient::CommandOutput(format!("Unknown emote: {}", name)));
+                        }
+                    }
+                    ToServer::ToggleLight => {
+                        let player_data = &players[&id];
+                        let result = match player_data.robot.filter(|_| player_data.controlling_robot) {
+                            Some(robot_id) => robots.toggle_headlights(robot_id),
+                            None => survival.toggle_flashlight(id, &game_data.items),
+                        };
+                        if let Err(reason) = result {
+                            server.send(id, ToClient::CommandOutput(reason.to_owned()));
+                        }
+                    }
+                    ToServer::Trade(pos, index) => {
+                        if !anti_cheat.check_reach(id, pos, physics_simulation) {
+                            continue;
+                        }
+                        let dimension = players[&id].dimension;
+                        if !claims.is_allowed(id, dimension, pos, permissions) {
+                            server.send(id, ToClient::CommandOutput(PROTECTED_MESSAGE.to_owned()));
+                            continue;
+                        }
+                        let block_id = dimensions.get(dimension).get_block(pos);
+                        let trade = match game_data.terminal_trades(block_id).and_then(|trades| trades.get(index)) {
+                            Some(trade) => trade,
+                            None => continue,
+                        };
+                        if let Err(reason) = survival.trade(id, trade) {
+                            server.send(id, ToClient::CommandOutput(reason.to_owned()));
+                        }
+                    }
+                    ToServer::MoveItems(transaction) => {
+                        let container = containers.get_open_mut(id, dimensions);
+                        if let Err(reason) = survival.move_items(id, &transaction, container) {
+                            server.send(id, ToClient::CommandOutput(reason.to_owned()));
+                        }
+                    }
+                    ToServer::CloseContainer => containers.close(id),
+                    ToServer::SetSignText(pos, text) => {
+                        if !anti_cheat.check_reach(id, pos, physics_simulation) {
+                            continue;
+                        }
+                        let dimension = players[&id].dimension;
+                        if !claims.is_allowed(id, dimension, pos, permissions) {
+                            server.send(id, ToClient::CommandOutput(PROTECTED_MESSAGE.to_owned()));
+                            continue;
+                        }
+                        signs.set_text(dimension, pos, &text, dimensions);
+                    }
                 },
             }
         }
+        for player in game_rules.drain_changed_capabilities() {
+            server.send(player, ToClient::Capabilities(game_rules.capabilities(player)));
+        }
+        // The players only see the commands they are allowed to use
+        for player in permissions.drain_changed_players() {
+            let allowed = COMMANDS
+                .iter()
+                .filter(|(name, _)| permissions.has_permission(player, &command_node(name)))
+                .map(|&(name, description)| (name.to_owned(), description.to_owned()));
+            server.send(player, ToClient::ServerCommands(allowed.collect()));
+        }
+        for (id, reason) in kicks.into_iter().chain(anti_cheat.drain_kicks()) {
+            server.send(id, ToClient::Kicked(reason));
+            remove_player(
+                id,
+                players,
+                physics_simulation,
+                robots,
+                anti_cheat,
+                game_rules,
+                permissions,
+                claims,
+                signs,
+                survival,
+                status_effects,
+                &game_data.items,
+                events,
+            );
+        }
         server_timing.record_part("Network events");
 
+        for command in admin.iter().flat_map(AdminListener::receive_commands) {
+            let output = run_admin_command(
+                command.command(),
+                players,
+                statistics,
+                history.as_mut(),
+                game_rules,
+                permissions,
+                pregenerator,
+                dust_storms,
+                dimensions.surface_mut(),
+                &mut **server,
+            );
+            command.respond(output);
+        }
+        if let Some(status) = status.as_ref() {
+            status.publish(players.len());
+        }
+        server_timing.record_part("Administration and status");
+
         // Receive generated chunks
-        world.get_new_generated_chunks();
+        for (dimension, world) in dimensions.worlds_mut() {
+            world.get_new_generated_chunks();
+            structures.record(dimension, world.drain_generated_structures());
+        }
         server_timing.record_part("Receive generated chunks");
 
         // Receive lighted chunks
-        world.get_new_light_chunks();
+        for (_, world) in dimensions.worlds_mut() {
+            world.get_new_light_chunks();
+        }
         server_timing.record_part("Receive lighted chunks");
+    }
 
-        // Tick game
-        physics_simulation.step_simulation(Instant::now(), &world);
+    /// Run one tick of the game
+    fn tick(&mut self, dt: Duration) {
+        let Self {
+            server,
+            server_timing,
+            mods,
+            data_directory,
+            game_data,
+            data_hash,
+            data_pack,
+            events,
+            achievements,
+            statistics,
+            history,
+            teleports,
+            respawn_points,
+            game_rules,
+            claims,
+            signs,
+            structures,
+            survival,
+            status_effects,
+            containers,
+            scheduler,
+            dimensions,
+            pregenerator,
+            players,
+            physics_simulation,
+            robots,
+            block_damages,
+            conveyors,
+            dropped_items,
+            signals,
+            doors,
+            lamps,
+            crops,
+            explosions,
+            meteors,
+            dust_storms,
+            mobs,
+            combat,
+            world_time,
+            sleep,
+            close_chunks_merged,
+            data_watcher,
+            anti_cheat,
+            previous_network_stats,
+            ..
+        } = self;
+
+        profile_scope!("server tick");
+        if game_rules.rules().daylight_cycle {
+            world_time.advance(dt.as_secs_f64());
+        }
+        physics_simulation.step_simulation(Instant::now(), dt, |player| {
+            dimensions.get(players.get(&player).map(|data| data.dimension).unwrap_or(DimensionId::SURFACE))
+        });
+        anti_cheat.tick(physics_simulation, dt.as_secs_f64());
         server_timing.record_part("Update physics");
 
+        // The players who ran out of health wake up at their respawn point, at their home, or at the spawn
+        for (player, cause) in survival.tick(physics_simulation, &game_data.items, dt.as_secs_f64()) {
+            let dimension = players[&player].dimension;
+            let body = physics_simulation.get_state().physics_state.players[&player].aabb.clone();
+            status_effects.clear(player);
+            let feet = body.pos + Vector3::new(body.size_x / 2.0, 0.0, body.size_z / 2.0);
+            for stack in survival.respawn(player, game_rules.rules().keep_inventory) {
+                dropped_items[dimension].spawn(feet, stack);
+            }
+            let respawn_point = respawn_points.respawn_point(player, dimensions).unwrap_or_else(|reason| {
+                server.send(player, ToClient::CommandOutput(reason.to_owned()));
+                None
+            });
+            let (home_dimension, destination) =
+                respawn_point.or_else(|| teleports.get_home(player, dimensions)).unwrap_or_else(|| {
+                    let spawn = PhysicsPlayer::default().aabb;
+                    (DimensionId::SURFACE, spawn.pos + Vector3::new(spawn.size_x / 2.0, 0.0, spawn.size_z / 2.0))
+                });
+            teleports.teleport(TeleportTarget::Player(player), home_dimension, destination, None);
+            server.send(player, ToClient::CommandOutput(cause));
+        }
+        server_timing.record_part("Update survival");
+
+        teleports.tick(dimensions, physics_simulation, robots, anti_cheat, players, &mut **server);
+        server_timing.record_part("Teleport entities");
+
+        // The events only report the block changes of the surface
+        for (dimension, world) in dimensions.worlds_mut() {
+            if dimension != DimensionId::SURFACE {
+                world.drain_block_changes();
+            }
+        }
+        let mut tasks = scheduler.tick();
+        // The night is skipped once all the players rest in a respawn pod, the machines catch up on the skipped time
+        if sleep.tick(players, physics_simulation, *world_time, &mut **server) {
+            let skipped = world_time.skip_to_morning();
+            for task in scheduler.fast_forward(seconds_to_ticks(skipped)) {
+                if !tasks.contains(&task) {
+                    tasks.push(task);
+                }
+            }
+            dust_storms.calm();
+            for &player in players.keys() {
+                server.send(player, ToClient::CommandOutput("The night is over, everyone wakes up rested".to_owned()));
+            }
+        }
+        for task in tasks {
+            match task {
+                Task::Autosave => {
+                    let items = &game_data.items;
+                    save_game(dimensions, statistics, structures, survival, history.as_mut(), items, scheduler)
+                }
+                Task::OpenAirlock(pos, dimension) => {
+                    // The suits of the players inside the airlock are refilled with its fresh air
+                    let world = dimensions.get_mut(dimension);
+                    if let Some((lower, upper)) = doors[dimension].finish_airlock_cycle(world, pos) {
+                        for (&id, player) in physics_simulation.get_state().physics_state.players.iter() {
+                            let aabb = &player.aabb;
+                            let center = aabb.pos + Vector3::new(aabb.size_x, aabb.size_y, aabb.size_z) / 2.0;
+                            let inside = (0..3).all(|i| lower[i] <= center[i] && center[i] <= upper[i]);
+                            if inside && players.get(&id).map(|data| data.dimension) == Some(dimension) {
+                                survival.refill_oxygen(id);
+                            }
+                        }
+                    }
+                }
+                Task::GrowCrop(pos) => crops.grow(dimensions.surface_mut(), scheduler, pos),
+                Task::ModTimer { mod_name, timer } => {
+                    mods.run_timer(dimensions.surface_mut(), scheduler, &mod_name, timer)
+                }
+            }
+        }
+        server_timing.record_part("Run scheduled tasks");
+
+        let world = dimensions.surface_mut();
+        robots.tick(world, conveyors, &mut dropped_items[DimensionId::SURFACE], dt.as_secs_f64());
+        block_damages.update(robots.mining_progress());
+        server_timing.record_part("Update robots");
+
+        for (pos, old_block, new_block) in world.drain_block_changes() {
+            events.publish(BlockChanged { pos, old_block, new_block });
+        }
+        for (pos, stack) in dropped_items[DimensionId::SURFACE].drain_spawned() {
+            events.publish(EntitySpawned { entity: Entity::DroppedItem(stack), pos });
+        }
+        for (_, dropped_items) in dropped_items.iter_mut() {
+            dropped_items.drain_spawned();
+        }
+        events.dispatch();
+        server_timing.record_part("Dispatch events");
+
+        let positions_by_player = physics_simulation
+            .get_state()
+            .physics_state
+            .players
+            .iter()
+            .map(|(&id, player)| (id, player.aabb.pos))
+            .collect();
+        for (player, achievement) in achievements.tick(&positions_by_player) {
+            server.send(player, ToClient::AchievementUnlocked(achievement));
+        }
+        server_timing.record_part("Update achievements");
+
+        statistics.tick(physics_simulation, &game_data.blocks, dt.as_secs_f64());
+        server_timing.record_part("Update statistics");
+
+        if let Some(history) = history.as_mut() {
+            history.tick(dt.as_secs_f64(), world);
+        }
+        server_timing.record_part("Record block history");
+
+        mods.tick(world, scheduler, dt.as_secs_f64());
+        server_timing.record_part("Update mods");
+
+        // The dropped items, the logic, the doors, the lamps and the explosions work the same in every dimension
+        let changed_blocks = dimensions
+            .worlds_mut()
+            .map(|(_, world)| world.drain_changed_blocks())
+            .collect::<PerDimension<_>>();
+        let player_centers = PerDimension::new(dimensions, |dimension| {
+            physics_simulation
+                .get_state()
+                .physics_state
+                .players
+                .iter()
+                .filter(|(id, _)| players.get(id).map(|data| data.dimension) == Some(dimension))
+                .map(|(&id, player)| {
+                    let aabb = &player.aabb;
+                    (id, aabb.pos + Vector3::new(aabb.size_x, aabb.size_y, aabb.size_z) / 2.0)
+                })
+                .collect::<Vec<_>>()
+        });
+        let player_entity_positions = PerDimension::new(dimensions, |dimension| {
+            physics_simulation
+                .get_state()
+                .physics_state
+                .players
+                .iter()
+                .filter(|(id, _)| players.get(id).map(|data| data.dimension) == Some(dimension))
+                .map(|(_, player)| player.aabb.pos)
+                .collect::<Vec<_>>()
+        });
+        for (dimension, world) in dimensions.worlds_mut() {
+            let mut entity_positions = player_entity_positions[dimension].clone();
+            if dimension == DimensionId::SURFACE {
+                entity_positions.extend(robots.get_infos().into_iter().map(|robot| robot.pos));
+            }
+            signals[dimension].update_blocks(world, &changed_blocks[dimension]);
+            signals[dimension].tick(*world_time, &entity_positions);
+        }
+        server_timing.record_part("Update logic signals");
+
+        for (dimension, world) in dimensions.worlds_mut() {
+            doors[dimension].update_blocks(world, &changed_blocks[dimension]);
+            doors[dimension].tick(world, scheduler, &signals[dimension]);
+        }
+        server_timing.record_part("Update doors");
+
+        for (dimension, world) in dimensions.worlds_mut() {
+            lamps[dimension].update_blocks(world, &changed_blocks[dimension]);
+            lamps[dimension].tick(world, &signals[dimension]);
+        }
+        server_timing.record_part("Update lamps");
+
+        let world = dimensions.surface_mut();
+        crops.update_blocks(world, scheduler, &changed_blocks[DimensionId::SURFACE]);
+        server_timing.record_part("Update crops");
+
+        let surface_entity_positions = &player_entity_positions[DimensionId::SURFACE];
+        meteors.tick(world, &mut explosions[DimensionId::SURFACE], surface_entity_positions, dt.as_secs_f64());
+        for pos in meteors.drain_impacts() {
+            events.publish(MeteorImpact { pos });
+        }
+        server_timing.record_part("Update meteors");
+
+        let mut explosion_effects = Vec::new();
+        for (dimension, world) in dimensions.worlds_mut() {
+            let explosions = &mut explosions[dimension];
+            explosions.update_blocks(world, &changed_blocks[dimension]);
+            let effects = explosions.tick(world, &signals[dimension], &mut dropped_items[dimension]);
+            explosion_effects.extend(effects.into_iter().map(|effect| (dimension, effect)));
+        }
+        server_timing.record_part("Update explosions");
+
+        // The items of the broken or exploded containers fall where the containers were
+        for (dimension, world) in dimensions.worlds_mut() {
+            for (pos, stacks) in world.drain_destroyed_containers() {
+                let pos = Vector3::new(pos.px as f64 + 0.5, pos.py as f64, pos.pz as f64 + 0.5);
+                for stack in stacks {
+                    dropped_items[dimension].spawn(pos, stack);
+                }
+            }
+        }
+
+        // The robots and the creatures only live on the surface
+        for (dimension, (center, power, _)) in explosion_effects.iter() {
+            let mobs = if *dimension == DimensionId::SURFACE {
+                robots.apply_explosion(*center, *power);
+                Some(&mut *mobs)
+            } else {
+                None
+            };
+            combat.apply_explosion(*center, *power, &player_centers[*dimension], physics_simulation, survival, mobs);
+        }
+        combat.tick(dt.as_secs_f64());
+        let world = dimensions.surface_mut();
+        let surface_players = &player_centers[DimensionId::SURFACE];
+        if game_rules.rules().mob_spawning {
+            mobs.spawn_around(world, surface_players, world_time.daylight(), dt.as_secs_f64());
+        }
+        for hit in mobs.tick(world, surface_players, robots, survival, dt.as_secs_f64()) {
+            let dropped_items = &mut dropped_items[DimensionId::SURFACE];
+            combat.apply_hit(hit, physics_simulation, survival, robots, mobs, dropped_items);
+        }
+        server_timing.record_part("Update creatures and combat");
+
+        for (dimension, world) in dimensions.worlds_mut() {
+            let dropped_items = &mut dropped_items[dimension];
+            dropped_items.tick(world, conveyors, &signals[dimension], dt.as_secs_f64());
+            for &(id, center) in player_centers[dimension].iter() {
+                dropped_items.pick_up(center, PICKUP_RADIUS, |stack| survival.give(id, stack));
+            }
+        }
+        server_timing.record_part("Update dropped items");
+        let world = dimensions.surface_mut();
+
+        if let Some(message) = dust_storms.tick(dt.as_secs_f64()) {
+            for &(player, _) in surface_players.iter() {
+                server.send(player, ToClient::CommandOutput(message.to_owned()));
+                server.send(player, ToClient::DustStorm(dust_storms.is_active()));
+            }
+        }
+        // The storm slows down the players who are outside, in the open
+        let mut in_storm = HashMap::new();
+        if dust_storms.is_active() {
+            for &(player, center) in surface_players.iter() {
+                if world.is_under_open_sky(BlockPos::from(center)) {
+                    in_storm.insert(player, dust_storms.slowness_level());
+                }
+            }
+        }
+        status_effects.tick(players.keys().copied(), &in_storm, survival, physics_simulation, dt.as_secs_f64());
+        server_timing.record_part("Update status effects");
+
+        // Reload the data if it changed on disk
+        if data_watcher.as_mut().map(DataWatcher::has_changed).unwrap_or(false) {
+            let new_data = load_data_with_extensions(data_directory.clone(), mods.get_extensions())
+                .and_then(|new_data| Ok((new_data, encode_data_pack(data_directory.clone(), mods.get_extensions())?)));
+            match new_data {
+                // The ids are stored everywhere in the world and the inventories, so they must not change
+                Ok((new_data, _)) if new_data.blocks.id_map() != game_data.blocks.id_map()
+                    || new_data.items.id_map() != game_data.items.id_map() => {
+                    log::warn!("Blocks or items were added or removed, restart the server to apply the changes");
+                }
+                Ok((new_data, new_data_pack)) => {
+                    info!("Reloaded the data");
+                    // The other block behaviours are only updated when the server restarts
+                    *conveyors = Conveyors::new(&new_data.blocks);
+                    for (_, world) in dimensions.worlds_mut() {
+                        world.loot_mut().set_data(&new_data);
+                    }
+                    mods.set_data(&new_data);
+                    *game_data = new_data;
+                    *data_hash = game_data.content_hash();
+                    *data_pack = new_data_pack;
+                    for (&player, data) in players.iter_mut() {
+                        // The clients ask for the new data, the old data pack isn't needed anymore
+                        data.data_announced = true;
+                        data.data_pack_sent = None;
+                        server.send(player, ToClient::DataHash(*data_hash));
+                    }
+                }
+                Err(e) => log::error!("Failed to reload the data: {:#}", e),
+            }
+        }
+        server_timing.record_part("Reload data");
+
+        for (&player, data) in players.iter_mut() {
+            send_data_pack_chunks(&mut **server, player, data);
+        }
+        server_timing.record_part("Send data packs");
+
         // Send physics updates to players
-        for (&player, _) in players.iter() {
-            server.send(
-                player,
-                ToClient::UpdatePhysics((*physics_simulation.get_state()).clone()),
-            );
+        let robot_infos = robots.get_infos();
+        let dropped_item_infos = PerDimension::new(dimensions, |dimension| dropped_items[dimension].get_infos());
+        let meteor_infos = meteors.get_infos();
+        let mob_infos = mobs.get_infos();
+        let damage_changes = block_damages.drain_changes();
+        let entity_damages = combat.drain_damages();
+        for (&player, data) in players.iter() {
+            // The players only see the players of their dimension
+            let mut physics_state = (*physics_simulation.get_state()).clone();
+            physics_state.retain_players(|other| players.get(&other).map(|other| other.dimension) == Some(data.dimension));
+            server.send(player, ToClient::UpdatePhysics(physics_state));
+            server.send(player, ToClient::UpdateTime(*world_time));
+            server.send(player, ToClient::UpdateDroppedItems(dropped_item_infos[data.dimension].clone()));
+            for (dimension, (center, power, debris)) in explosion_effects.iter() {
+                if *dimension == data.dimension {
+                    server.send(player, ToClient::Explosion(*center, *power, debris.clone()));
+                }
+            }
+            if data.dimension == DimensionId::SURFACE {
+                server.send(player, ToClient::UpdateRobots(robot_infos.clone()));
+                server.send(player, ToClient::UpdateMeteors(meteor_infos.clone()));
+                server.send(player, ToClient::UpdateMobs(mob_infos.clone()));
+                if let Some(physics_player) = physics_simulation.get_state().physics_state.players.get(&player) {
+                    let camera = physics_player.get_camera_position();
+                    for &(pos, stage) in damage_changes.iter().filter(|(pos, _)| is_in_view(camera, *pos)) {
+                        server.send(player, ToClient::BlockDamage(pos, stage));
+                    }
+                }
+            } else {
+                server.send(player, ToClient::UpdateRobots(Vec::new()));
+                server.send(player, ToClient::UpdateMeteors(Vec::new()));
+                server.send(player, ToClient::UpdateMobs(Vec::new()));
+            }
+            // The robots and the creatures only live on the surface
+            for &(target, amount, from) in entity_damages.iter() {
+                let dimension = match target {
+                    Combatant::Player(other) => players.get(&other).map(|other| other.dimension),
+                    Combatant::Robot(_) | Combatant::Mob(_) => Some(DimensionId::SURFACE),
+                };
+                if dimension == Some(data.dimension) {
+                    server.send(player, ToClient::EntityDamaged(target, amount, from));
+                }
+            }
+            if let Some(claim_infos) = claims.infos_to_send(player, data.dimension) {
+                server.send(player, ToClient::Claims(claim_infos));
+            }
+        }
+        survival.send_updates(&mut **server);
+        status_effects.send_updates(&mut **server);
+        containers.send_updates(dimensions, physics_simulation, players, &mut **server);
+        signs.remove_destroyed(dimensions);
+        for (&player, data) in players.iter() {
+            if let Some(texts) = signs.texts_to_send(player, data.dimension, dimensions) {
+                server.send(player, ToClient::Signs(texts));
+            }
         }
         server_timing.record_part("Send physics updates to players");
 
         // Send chunks to players
         let mut player_positions = Vec::new();
+        // The world is streamed around the entity the player's camera is bound to
+        let camera_positions = players
+            .iter()
+            .map(|(id, data)| {
+                let robot = data.robot.filter(|_| data.controlling_robot).and_then(|robot_id| robots.get(robot_id));
+                let pos = match robot {
+                    Some(robot) => robot.pos,
+                    None => physics_simulation.get_state().physics_state.players.get(id).unwrap().get_camera_position(),
+                };
+                (*id, pos)
+            })
+            .collect::<HashMap<_, _>>();
         for (player, data) in players.iter_mut() {
-            let player_pos = BlockPos::from(physics_simulation
-                .get_state()
-                .physics_state
-                .players
-                .get(player)
-                .unwrap()
-                .get_camera_position()
-            );
+            let player_pos = BlockPos::from(camera_positions[player]);
             let player_chunk = player_pos.containing_chunk_pos();
-            player_positions.push((player_chunk, data.render_distance));
+            let dimension = data.dimension;
+            player_positions.push((dimension, player_chunk, data.render_distance));
             // Send new chunks
-            let updates = world.send_chunks_to_player(player_chunk, data);
+            let updates = dimensions.get_mut(dimension).send_chunks_to_player(player_chunk, data);
             for (chunk, light_chunk) in updates {
-                server.send(*player, ToClient::Chunk(chunk, light_chunk));
+                server.send(*player, ToClient::Chunk(dimension, chunk, light_chunk));
             }
             // Drop chunks that are too far away
             let render_distance = data.render_distance;
             data.loaded_chunks
                 .retain(|chunk_pos, _| render_distance.is_chunk_visible(player_chunk, *chunk_pos));
         }
+        if let Some(history) = history.as_mut() {
+            history.send_views(dimensions.surface(), players, &mut **server, dt.as_secs_f64());
+        }
         server_timing.record_part("Send chunks to players");
 
         // Compute close chunks
         for (_, data) in players.iter_mut() {
             data.close_chunks.update(&data.render_distance);
         }
-        let all_close_chunks = players
-            .iter()
-            .map(|(id, data)| {
-                let player = physics_simulation.get_state().physics_state.players.get(id).unwrap();
-                let player_chunk = BlockPos::from(player.aabb.pos).containing_chunk_pos(); // TODO: have this in the physics state?
-                data.close_chunks.get_close_chunks().iter().map(|chunk_pos| CloseChunkPos::new(*chunk_pos, player_chunk)).collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
-        common::collections::merge_arrays(&mut close_chunks_merged, &all_close_chunks[..]);
-        let close_chunks = close_chunks_merged.iter().map(|&ccp| ccp.pos).collect::<Vec<_>>();
+        // The close chunks of each dimension, and the chunks needed by the entities other than the players
+        let mut close_chunks_by_dimension = Vec::new();
+        for dimension in dimensions.ids() {
+            let all_close_chunks = players
+                .iter()
+                .filter(|(_, data)| data.dimension == dimension)
+                .map(|(id, data)| {
+                    let player_chunk = BlockPos::from(camera_positions[id]).containing_chunk_pos(); // TODO: have this in the physics state?
+                    data.close_chunks.get_close_chunks().iter().map(|chunk_pos| CloseChunkPos::new(*chunk_pos, player_chunk)).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            common::collections::merge_arrays(close_chunks_merged, &all_close_chunks[..]);
+            // The destinations of the teleports come first, since players are waiting for them
+            let teleport_chunks = teleports.destination_chunks(dimension);
+            let mut close_chunks = teleport_chunks.clone();
+            close_chunks.extend(close_chunks_merged.iter().map(|&ccp| ccp.pos));
+            // Robots need the chunks they are working in
+            let mut entity_chunks = if dimension == DimensionId::SURFACE { robots.active_chunks() } else { Vec::new() };
+            close_chunks.extend(entity_chunks.iter().cloned());
+            entity_chunks.extend(teleport_chunks);
+            close_chunks_by_dimension.push((dimension, close_chunks, entity_chunks));
+        }
         server_timing.record_part("Compute close chunks");
         
         // Update light
-        world.enqueue_chunks_for_lighting(&close_chunks);
+        for (dimension, close_chunks, _) in close_chunks_by_dimension.iter() {
+            dimensions.get_mut(*dimension).enqueue_chunks_for_lighting(close_chunks);
+        }
         server_timing.record_part("Send chunks to light worker");
 
         // Update worldgen
-        world.enqueue_chunks_for_worldgen(&close_chunks);
+        for (dimension, close_chunks, _) in close_chunks_by_dimension.iter() {
+            dimensions.get_mut(*dimension).enqueue_chunks_for_worldgen(close_chunks);
+        }
         server_timing.record_part("Send chunks to worldgen worker");
 
-        // Drop chunks that are far from all players
-        world.drop_far_chunks(&player_positions);
-        server_timing.record_part("Drop far chunks");
+        // Pregenerate the chunks around the spawn once the chunks needed now are started
+        pregenerator.tick(dimensions.surface_mut(), &mut **server);
+        server_timing.record_part("Pregenerate chunks");
 
+        // Unload the chunks that are far from all players and robots, when their memory is needed
+        let entity_render_distance = RenderDistance {
+            x_max: 1,
+            x_min: 1,
+            y_max: 1,
+            y_min: 1,
+            z_max: 1,
+            z_min: 1,
+        };
+        for (dimension, _, entity_chunks) in close_chunks_by_dimension {
+            let mut positions = player_positions
+                .iter()
+                .filter(|(player_dimension, _, _)| *player_dimension == dimension)
+                .map(|&(_, chunk, render_distance)| (chunk, render_distance))
+                .collect::<Vec<_>>();
+            positions.extend(entity_chunks.into_iter().map(|chunk| (chunk, entity_render_distance)));
+            dimensions.get_mut(dimension).update_seen_chunks(&positions);
+        }
+        dimensions.evict_chunks();
+        if let Some(history) = history.as_mut() {
+            history.forget_unloaded_chunks(dimensions.surface());
+        }
+        server_timing.record_part("Unload chunks");
+
+        let loaded_chunks = dimensions.ids().map(|id| dimensions.get(id).num_loaded_chunks()).sum::<usize>();
+        let loaded_chunk_columns = dimensions.ids().map(|id| dimensions.get(id).num_loaded_chunk_columns()).sum::<usize>();
         send_debug_info("Chunks", "server",
                         format!(
                             "Server loaded chunks = {}\nServer loaded chunk columns = {}\n",
-                            world.num_loaded_chunks(),
-                            world.num_loaded_chunk_columns(),
+                            loaded_chunks,
+                            loaded_chunk_columns,
                         ));
+        for id in dimensions.ids() {
+            send_debug_info("Storage", dimensions.name(id), dimensions.get(id).storage().debug_info());
+        }
+        send_debug_info("Robots", "server", robots.debug_info());
+        for id in dimensions.ids() {
+            send_debug_info("Logic", dimensions.name(id), signals[id].debug_info());
+            send_debug_info("Explosions", dimensions.name(id), explosions[id].debug_info());
+        }
+        send_debug_info("Meteors", "server", meteors.debug_info());
+        send_debug_info("Creatures", "server", mobs.debug_info());
+        send_debug_info("Mods", "server", mods.debug_info());
+        send_debug_info("Scheduler", "server", scheduler.debug_info());
+        send_debug_info("Events", "server", events.debug_info());
+        if let Some(history) = history.as_ref() {
+            send_debug_info("History", "server", history.debug_info());
+        }
+        let elapsed = previous_network_stats.0.elapsed().as_secs_f64();
+        if elapsed >= 1.0 {
+            let network_stats = server.stats();
+            let summary = network_stats.bandwidth_summary(&previous_network_stats.1, elapsed);
+            send_debug_info("Network", "server", format!("Server\n{}", summary));
+            *previous_network_stats = (Instant::now(), network_stats);
+        }
 
         // Nothing else to do for now :-)
         send_perf_breakdown("Server", "mainloop", "Server main loop", server_timing.extract_part_averages());
     }
+
+    /// Save the chunks of every dimension and the state of the game, like the autosave. The chunks are written by the
+    /// storage of each dimension, at the latest when the game is dropped.
+    pub fn save(&mut self) {
+        let Self { dimensions, statistics, structures, survival, history, game_data, scheduler, .. } = self;
+        save_game(dimensions, statistics, structures, survival, history.as_mut(), &game_data.items, scheduler);
+    }
+
+    /// Handle the pending events and run one tick, whatever the time it takes
+    pub fn step(&mut self) {
+        self.handle_events();
+        let dt = self.timestep.tick_duration();
+        self.tick(dt);
+    }
+
+    /// Return the block at position `pos` of the surface
+    pub fn get_block(&self, pos: BlockPos) -> BlockId {
+        self.dimensions.surface().get_block(pos)
+    }
+
+    /// Set the block at position `pos` of the surface. Return false if the chunk is not loaded.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> bool {
+        self.dimensions.surface_mut().set_block(pos, block)
+    }
+
+    /// Run a command of the remote administration, such as `group <player> <group>`, and return its output
+    pub fn run_admin_command(&mut self, command: &str) -> String {
+        run_admin_command(
+            command,
+            &self.players,
+            &self.statistics,
+            self.history.as_mut(),
+            &mut self.game_rules,
+            &mut self.permissions,
+            &mut self.pregenerator,
+            &mut self.dust_storms,
+            self.dimensions.surface_mut(),
+            &mut *self.server,
+        )
+    }
+
+    /// Return the center of the known structure named `name` that is the nearest to `pos` on the surface
+    pub fn locate_structure(&self, name: &str, pos: Vector3<f64>) -> Option<BlockPos> {
+        self.structures.nearest(name, DimensionId::SURFACE, pos)
+    }
+
+    pub fn is_chunk_loaded(&self, pos: ChunkPos) -> bool {
+        self.dimensions.surface().get_chunk(pos).is_some()
+    }
+
+    /// Number of chunks of all the dimensions waiting to be written to the save
+    pub fn num_pending_chunk_writes(&self) -> usize {
+        self.dimensions.ids().map(|id| self.dimensions.get(id).storage().pending_writes()).sum()
+    }
+
+    pub fn has_player(&self, player: PlayerId) -> bool {
+        self.players.contains_key(&player)
+    }
+
+    pub fn get_physics_player(&self, player: PlayerId) -> Option<&PhysicsPlayer> {
+        self.physics_simulation.get_state().physics_state.players.get(&player)
+    }
+
+    pub fn get_player_statistics(&self, player: PlayerId) -> Option<&PlayerStatistics> {
+        self.statistics.get(player)
+    }
+
+    /// Return the dimension a player is in
+    pub fn get_player_dimension(&self, player: PlayerId) -> Option<DimensionId> {
+        self.players.get(&player).map(|data| data.dimension)
+    }
+
+    pub fn get_block_id(&self, name: &str) -> Option<BlockId> {
+        self.game_data.blocks.get_id_by_name(&name.to_owned()).map(|id| id as BlockId)
+    }
+
+    pub fn get_item_id(&self, name: &str) -> Option<ItemId> {
+        self.game_data.items.get_id_by_name(&name.to_owned())
+    }
+
+    /// Put an item stack in the inventory of a player, and return what didn't fit
+    pub fn give_item(&mut self, player: PlayerId, stack: ItemStack) -> Option<ItemStack> {
+        self.survival.give(player, stack)
+    }
+
+    pub fn get_world_time(&self) -> WorldTime {
+        self.world_time
+    }
+
+    pub fn advance_world_time(&mut self, seconds: f64) {
+        self.world_time.advance(seconds);
+    }
+
+    /// Take health from a player, who wakes up at their respawn point if they run out of it
+    pub fn damage_player(&mut self, player: PlayerId, amount: f64) -> bool {
+        self.survival.damage(player, amount, "Damaged by a test".to_owned())
+    }
+}
+
+/// Start a new server instance.
+/// Save the chunks of every dimension and the state of the game
+fn save_game(
+    dimensions: &mut Dimensions,
+    statistics: &Statistics,
+    structures: &mut Structures,
+    survival: &mut Survival,
+    history: Option<&mut BlockHistory>,
+    items: &Registry<Item>,
+    scheduler: &Scheduler,
+) {
+    for (_, world) in dimensions.worlds_mut() {
+        world.save_chunks();
+    }
+    if let Some(history) = history {
+        history.save(dimensions.surface());
+    }
+    statistics.save();
+    structures.save();
+    survival.save(items);
+    scheduler.save();
+}
+
+/// Run the server until `stop` is set, then save the game before returning
+pub fn launch_server(server: Box<dyn Server>, stop: Arc<AtomicBool>) -> Result<()> {
+    info!("Starting server");
+    let mut game = ServerGame::new(server, PathBuf::from("data"), Path::new("world"))?;
+
+    info!("Server initialized successfully! Starting server loop");
+    while !stop.load(Ordering::Relaxed) {
+        game.handle_events();
+
+        // Wait for the next tick
+        game.timestep.update();
+        if !game.timestep.consume_tick() {
+            std::thread::sleep(game.timestep.time_until_next_tick());
+            continue;
+        }
+        let dt = game.timestep.tick_duration();
+        game.tick(dt);
+    }
+
+    info!("Stopping server");
+    game.save();
+    // Dropping the game waits for the storage of each dimension to write its chunks
+    drop(game);
+    info!("Server stopped");
+    Ok(())
 }
 
 #[derive(Clone, Copy)]
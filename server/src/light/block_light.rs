@@ -0,0 +1,108 @@
+use common::{
+    block::BlockId,
+    world::{Chunk, CHUNK_SIZE},
+};
+use super::sunlight::{FastBFSQueue, LightData};
+use std::sync::Arc;
+
+const MAX_LIGHT: u32 = 15;
+
+/// Take a 3x3x3 chunks bloc and compute the light emitted by the blocks of `block_light_levels` by using a BFS.
+/// The block light is stored in the high 4 bits of `res`, the sunlight stays in the low 4 bits.
+pub fn compute_block_light(
+    chunks: &[Option<Arc<Chunk>>],
+    block_light_levels: &[u8],
+    queue: &mut FastBFSQueue,
+    light_data: &mut [u8],
+    res: &mut LightData,
+) {
+    assert!(light_data.len() >= (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize);
+    let csize = CHUNK_SIZE as usize;
+    let is_source = |block: BlockId| block_light_levels.get(block as usize).map(|l| *l > 0).unwrap_or(false);
+    // Most chunks don't contain any light source
+    if !chunks.iter().flatten().any(|c| c.data.iter().any(|b| is_source(*b))) {
+        return;
+    }
+    queue.clear();
+    for l in light_data.iter_mut() {
+        *l = 0;
+    }
+
+    // Only the blocks that are closer than MAX_LIGHT to the middle chunk can light it
+    const MIN_VAL: usize = (CHUNK_SIZE - MAX_LIGHT + 1) as usize;
+    const MAX_VAL: usize = (2 * CHUNK_SIZE + MAX_LIGHT - 1) as usize;
+    let get_block = |x: usize, y: usize, z: usize| -> BlockId {
+        match &chunks[(x / csize) * 9 + (y / csize) * 3 + (z / csize)] {
+            None => 0,
+            Some(c) => c.get_block_at(((x % csize) as u32, (y % csize) as u32, (z % csize) as u32)),
+        }
+    };
+    let index = |x: usize, y: usize, z: usize| x * csize * csize * 9 + y * csize * 3 + z;
+
+    // Fill the BFS queue with the light sources
+    for (chunk_index, chunk) in chunks.iter().enumerate() {
+        let c = match chunk {
+            Some(c) if c.data.iter().any(|b| is_source(*b)) => c,
+            _ => continue,
+        };
+        let (cx, cy, cz) = (chunk_index / 9, (chunk_index / 3) % 3, chunk_index % 3);
+        for i in 0..csize {
+            for j in 0..csize {
+                for k in 0..csize {
+                    let (x, y, z) = (cx * csize + i, cy * csize + j, cz * csize + k);
+                    if x < MIN_VAL || x >= MAX_VAL || y < MIN_VAL || y >= MAX_VAL || z < MIN_VAL || z >= MAX_VAL {
+                        continue;
+                    }
+                    let level = block_light_levels[c.get_block_at((i as u32, j as u32, k as u32)) as usize];
+                    if level > 0 {
+                        light_data[index(x, y, z)] = level;
+                        unsafe { queue.push((x, y, z, level)) };
+                    }
+                }
+            }
+        }
+    }
+
+    const DX: [isize; 6] = [1, -1, 0, 0, 0, 0];
+    const DY: [isize; 6] = [0, 0, 1, -1, 0, 0];
+    const DZ: [isize; 6] = [0, 0, 0, 0, 1, -1];
+
+    while !queue.is_empty() {
+        let (x, y, z, ll) = unsafe { *queue.pop() };
+        if ll <= 1 {
+            continue;
+        }
+        for i in 0..6 {
+            let (nx, ny, nz) = (x as isize + DX[i], y as isize + DY[i], z as isize + DZ[i]);
+            if nx < MIN_VAL as isize
+                || nx >= MAX_VAL as isize
+                || ny < MIN_VAL as isize
+                || ny >= MAX_VAL as isize
+                || nz < MIN_VAL as isize
+                || nz >= MAX_VAL as isize
+            {
+                continue;
+            }
+            let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+            // TODO : replace by is opaque
+            if get_block(nx, ny, nz) != 0 {
+                continue;
+            }
+            let s = index(nx, ny, nz);
+            if light_data[s] < ll - 1 {
+                light_data[s] = ll - 1;
+                unsafe { queue.push((nx, ny, nz, ll - 1)) };
+            }
+        }
+    }
+
+    for i in 0..csize {
+        for j in 0..csize {
+            for k in 0..csize {
+                let block_light = light_data[index(i + csize, j + csize, k + csize)];
+                let light = &mut res.light_level[i * csize * csize + j * csize + k];
+                *light = (*light & 0x0F) | (block_light << 4);
+            }
+        }
+    }
+}
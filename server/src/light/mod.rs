@@ -1,6 +1,7 @@
 use common::world::{Chunk, CHUNK_SIZE};
 use std::sync::Arc;
 
+mod block_light;
 mod sunlight;
 pub mod worker;
 
@@ -1,9 +1,7 @@
-use common::world::{Chunk, CHUNK_SIZE};
+use common::world::{Chunk, WorldHeight, CHUNK_SIZE};
 use super::HighestOpaqueBlock;
 use std::sync::Arc;
 
-// TODO : Add block that are source of light
-
 pub struct LightData {
     pub light_level: [u8; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
 }
@@ -16,10 +14,12 @@ impl LightData {
     }
 }
 
-/// Take a 3x3x3 chunks bloc and 3x3 HighestOpaqueBlock and compute the light by using a BFS
+/// Take a 3x3x3 chunks bloc and 3x3 HighestOpaqueBlock and compute the light by using a BFS.
+/// The sunlight enters from above the chunks, and the missing chunks below `height` are opaque.
 pub fn compute_light(
     chunks: Vec<Option<Arc<Chunk>>>,
     highest_opaque_blocks: Vec<Arc<HighestOpaqueBlock>>,
+    height: WorldHeight,
     queue: &mut FastBFSQueue,
     light_data: &mut [u8],
     opaque: &mut [bool],
@@ -70,6 +70,20 @@ pub fn compute_light(
                     }
                     // Then we fill the BFS queue
                     match chunk {
+                        // Below the world
+                        None if y0 + *cy as i64 - 1 < height.min_chunk_y => {
+                            for i in i_range {
+                                for j in j_range.clone() {
+                                    for k in k_range.clone() {
+                                        let s = (*cx * csize + i as usize) * csize * csize * 9
+                                            + (*cy * csize + j as usize) * csize * 3
+                                            + (*cz * csize + k as usize);
+                                        *opaque.get_unchecked_mut(s) = true;
+                                        *light_data.get_unchecked_mut(s) = 0;
+                                    }
+                                }
+                            }
+                        }
                         None => {
                             for i in i_range {
                                 for k in j_range.clone() {
@@ -1,33 +1,40 @@
 use common::{
     collections::zero_initialized_vec,
-    world::{Chunk, CHUNK_SIZE, LightChunk},
+    profile_scope,
+    world::{Chunk, CHUNK_SIZE, LightChunk, WorldHeight},
     worker::{Worker, WorkerState},
 };
 use super::HighestOpaqueBlock;
+use super::block_light::compute_block_light;
 use super::sunlight::{FastBFSQueue, compute_light};
 use std::sync::Arc;
 
 static LIGHTING_QUEUE_SIZE: usize = 20;
 
-pub fn start_lighting_worker() -> ChunkLightingWorker {
-    Worker::new(ChunkLightingState::new(), LIGHTING_QUEUE_SIZE, "Light".into())
+/// `block_light_levels` is the level of the light emitted by each block
+pub fn start_lighting_worker(block_light_levels: Vec<u8>) -> ChunkLightingWorker {
+    Worker::new(ChunkLightingState::new(block_light_levels), LIGHTING_QUEUE_SIZE, "Light".into())
 }
 
 /// The chunk-specific data that is needed to generate light for it.
 pub struct ChunkLightingData {
     pub chunks: Vec<Option<Arc<Chunk>>>,
     pub highest_opaque_blocks: Vec<Arc<HighestOpaqueBlock>>,
+    /// The vertical bounds of the world: the missing chunks below it are opaque
+    pub height: WorldHeight,
 }
 
 pub struct ChunkLightingState {
     queue_reuse: FastBFSQueue,
     light_data_reuse: Vec<u8>,
     opaque_reuse: Vec<bool>,
+    block_light_levels: Vec<u8>,
 }
 
 impl ChunkLightingState {
-    pub(self) fn new() -> Self {
+    pub(self) fn new(block_light_levels: Vec<u8>) -> Self {
         Self {
+            block_light_levels,
             queue_reuse: FastBFSQueue::new(),
             light_data_reuse: unsafe { zero_initialized_vec((CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize) },
             opaque_reuse: unsafe { zero_initialized_vec((CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize) },
@@ -38,14 +45,24 @@ impl ChunkLightingState {
 impl WorkerState<ChunkLightingData, Arc<LightChunk>> for ChunkLightingState {
     fn compute(&mut self, data: ChunkLightingData) -> Arc<LightChunk> {
         let pos = data.chunks[9+3+1].as_ref().expect("No middle chunk").pos;
+        profile_scope!("lighting");
+        let mut light = compute_light(
+            data.chunks.clone(),
+            data.highest_opaque_blocks,
+            data.height,
+            &mut self.queue_reuse,
+            &mut self.light_data_reuse,
+            &mut self.opaque_reuse,
+        );
+        compute_block_light(
+            &data.chunks,
+            &self.block_light_levels,
+            &mut self.queue_reuse,
+            &mut self.light_data_reuse,
+            &mut light,
+        );
         Arc::new(LightChunk {
-            light: compute_light(
-                data.chunks,
-                data.highest_opaque_blocks,
-                &mut self.queue_reuse,
-                &mut self.light_data_reuse,
-                &mut self.opaque_reuse,
-            ).light_level.to_vec(),
+            light: light.light_level.to_vec(),
             pos,
         })
     }
@@ -0,0 +1,151 @@
+//! The logic signal layer. Sensors and timers emit on/off signals that wires carry to the blocks next to them.
+
+use crate::world::World;
+use common::{
+    block::{Block, BlockType, LogicComponent},
+    registry::Registry,
+    time::WorldTime,
+    world::{BlockPos, FACE_OFFSETS},
+};
+use nalgebra::Vector3;
+use std::collections::{HashMap, HashSet};
+
+fn neighbors(pos: BlockPos) -> impl Iterator<Item = BlockPos> {
+    FACE_OFFSETS.iter().map(move |d| BlockPos::from((pos.px + d[0], pos.py + d[1], pos.pz + d[2])))
+}
+
+/// A set of connected wires, and the sources next to them
+struct WireNetwork {
+    wires: Vec<BlockPos>,
+    sources: Vec<BlockPos>,
+}
+
+/// The state of all the logic blocks of the world
+pub struct Signals {
+    /// The logic component of each block, if any
+    components: Vec<Option<LogicComponent>>,
+    /// The logic blocks of the world
+    blocks: HashMap<BlockPos, LogicComponent>,
+    /// The wire networks. They are rebuilt when a logic block is placed or removed.
+    networks: Vec<WireNetwork>,
+    networks_dirty: bool,
+    /// The positions that carry a signal: wires of powered networks and active sources
+    powered: HashSet<BlockPos>,
+}
+
+impl Signals {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let components = block_registry
+            .iter()
+            .map(|(_, _, block)| match block.block_type {
+                BlockType::Logic { component, .. } => Some(component),
+                _ => None,
+            })
+            .collect();
+        Self {
+            components,
+            blocks: HashMap::new(),
+            networks: Vec::new(),
+            networks_dirty: false,
+            powered: HashSet::new(),
+        }
+    }
+
+    /// Take block modifications into account
+    pub fn update_blocks(&mut self, world: &World, changed_blocks: &[BlockPos]) {
+        for pos in changed_blocks {
+            let component = self.components.get(world.get_block(*pos) as usize).copied().flatten();
+            let previous = match component {
+                Some(component) => self.blocks.insert(*pos, component),
+                None => self.blocks.remove(pos),
+            };
+            if previous.is_some() || component.is_some() {
+                self.networks_dirty = true;
+            }
+        }
+    }
+
+    /// Group the wires into connected networks
+    fn rebuild_networks(&mut self) {
+        self.networks.clear();
+        let mut visited = HashSet::new();
+        for (&start, component) in self.blocks.iter() {
+            if *component != LogicComponent::Wire || visited.contains(&start) {
+                continue;
+            }
+            let mut network = WireNetwork { wires: Vec::new(), sources: Vec::new() };
+            let mut stack = vec![start];
+            visited.insert(start);
+            while let Some(pos) = stack.pop() {
+                network.wires.push(pos);
+                for neighbor in neighbors(pos) {
+                    match self.blocks.get(&neighbor) {
+                        Some(LogicComponent::Wire) => {
+                            if visited.insert(neighbor) {
+                                stack.push(neighbor);
+                            }
+                        }
+                        Some(_) => network.sources.push(neighbor),
+                        None => {}
+                    }
+                }
+            }
+            network.sources.sort_by_key(|pos| (pos.px, pos.py, pos.pz));
+            network.sources.dedup();
+            self.networks.push(network);
+        }
+        self.networks_dirty = false;
+    }
+
+    /// Evaluate the sensors and timers, and propagate their signals through the wires.
+    /// `entity_positions` are the positions of the players and robots, for the proximity sensors.
+    pub fn tick(&mut self, time: WorldTime, entity_positions: &[Vector3<f64>]) {
+        if self.networks_dirty {
+            self.rebuild_networks();
+        }
+
+        self.powered.clear();
+        for (&pos, component) in self.blocks.iter() {
+            if is_source_active(*component, pos, time, entity_positions) {
+                self.powered.insert(pos);
+            }
+        }
+        for network in self.networks.iter() {
+            if network.sources.iter().any(|source| self.powered.contains(source)) {
+                self.powered.extend(network.wires.iter().cloned());
+            }
+        }
+    }
+
+    /// Return true if the block at `pos` receives a signal, i.e. if it is next to a powered wire or an active source
+    pub fn is_powered(&self, pos: BlockPos) -> bool {
+        neighbors(pos).any(|neighbor| self.powered.contains(&neighbor))
+    }
+
+    /// Text summary for the debug overlay
+    pub fn debug_info(&self) -> String {
+        format!(
+            "Logic blocks = {}\nWire networks = {}\nPowered blocks = {}\n",
+            self.blocks.len(),
+            self.networks.len(),
+            self.powered.len(),
+        )
+    }
+}
+
+/// Return true if the component emits a signal
+fn is_source_active(component: LogicComponent, pos: BlockPos, time: WorldTime, entity_positions: &[Vector3<f64>]) -> bool {
+    match component {
+        LogicComponent::Wire => false,
+        LogicComponent::DaylightSensor => time.is_day(),
+        LogicComponent::NightSensor => !time.is_day(),
+        LogicComponent::ProximitySensor { range } => {
+            let center = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+            entity_positions.iter().any(|p| (p - center).norm() <= range)
+        }
+        LogicComponent::Timer { on, off } => {
+            let period = on + off;
+            period > 0.0 && time.seconds() % period < on
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! The loot of the blocks broken by the players and of the storage crates placed by the world generator, drawn from
+//! the loot tables of the data.
+
+use common::{
+    block::BlockId,
+    data::Data,
+    item::{Item, ItemId, ItemStack},
+    loot::LootTable,
+    random::Random,
+    registry::Registry,
+};
+use std::collections::BTreeMap;
+
+pub struct Loot {
+    tables: BTreeMap<String, LootTable>,
+    /// The loot table of each block, indexed by block id
+    block_tables: Vec<Option<String>>,
+    /// The items dropped by the blocks without a loot table, indexed by block id
+    block_items: Vec<Option<ItemStack>>,
+    items: Registry<Item>,
+    seed: i32,
+    random: Random,
+}
+
+impl Loot {
+    /// The loot of the data, with the random numbers of `seed`
+    pub fn new(game_data: &Data, seed: i32) -> Self {
+        Self {
+            tables: game_data.loot_tables.clone(),
+            block_tables: game_data
+                .blocks
+                .iter()
+                .map(|(_, _, block)| block.block_type.loot_table().map(str::to_owned))
+                .collect(),
+            block_items: game_data.block_items.clone(),
+            items: game_data.items.clone(),
+            seed,
+            random: Random::new(seed),
+        }
+    }
+
+    /// The items of the data
+    pub fn items(&self) -> &Registry<Item> {
+        &self.items
+    }
+
+    /// Use the loot tables of new data, with the same ids
+    pub fn set_data(&mut self, game_data: &Data) {
+        *self = Self {
+            random: self.random.clone(),
+            ..Self::new(game_data, self.seed)
+        };
+    }
+
+    /// Draw the items of the loot table `name`, for a player holding `holding`. The unknown tables give nothing.
+    pub fn roll(&mut self, name: &str, holding: Option<ItemId>) -> Vec<ItemStack> {
+        let Self {
+            tables,
+            items,
+            random,
+            ..
+        } = self;
+        let table = match tables.get(name) {
+            Some(table) => table,
+            None => {
+                log::warn!("Unknown loot table {}", name);
+                return Vec::new();
+            }
+        };
+        table.roll(items, holding, || random.next_f64())
+    }
+
+    /// The items dropped by a block broken by a player holding `holding`: its loot, or else its item
+    pub fn block_drops(&mut self, block: BlockId, holding: Option<ItemId>) -> Vec<ItemStack> {
+        match self.block_tables.get(block as usize).cloned().flatten() {
+            Some(table) => self.roll(&table, holding),
+            None => self.block_items.get(block as usize).cloned().flatten().into_iter().collect(),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! The memory budget of the loaded chunks. The chunks that no player or entity sees anymore stay loaded, so that
+//! they don't have to be loaded again when the players come back, until the loaded chunks of all the dimensions use
+//! more memory than the budget: the chunks that were seen the longest time ago are then unloaded first. The chunks
+//! that are seen are never unloaded, even above the budget, and the changed chunks are only unloaded once saved.
+
+use crate::world::World;
+
+const MEGABYTE: usize = 1 << 20;
+
+/// The memory used by the loaded chunks of a world
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChunkMemoryUsage {
+    pub chunks: usize,
+    pub bytes: usize,
+    /// The chunks seen by the players and the entities, which are never unloaded
+    pub seen_chunks: usize,
+    pub seen_bytes: usize,
+}
+
+pub struct ChunkMemory {
+    budget: usize,
+    /// The memory used by the loaded chunks after the last eviction
+    used: usize,
+    /// Number of chunks unloaded because of the budget since the server started
+    evicted: u64,
+}
+
+impl ChunkMemory {
+    /// Keep the loaded chunks under `budget_mb` megabytes
+    pub fn new(budget_mb: usize) -> Self {
+        Self {
+            budget: budget_mb.saturating_mul(MEGABYTE),
+            used: 0,
+            evicted: 0,
+        }
+    }
+
+    /// Unload the chunks that were seen the longest time ago until the loaded chunks fit in the budget
+    pub fn evict(&mut self, mut worlds: Vec<&mut World>) {
+        self.used = worlds.iter().map(|world| world.memory_usage().bytes).sum();
+        if self.used <= self.budget {
+            return;
+        }
+        // The chunks that are not seen anymore, with the index of their world
+        let mut candidates = worlds
+            .iter()
+            .enumerate()
+            .flat_map(|(index, world)| {
+                let candidates = world.eviction_candidates().into_iter();
+                candidates.map(move |(last_seen, pos, bytes)| (last_seen, index, pos, bytes))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(last_seen, _, _, _)| last_seen);
+        for (_, index, pos, bytes) in candidates {
+            if self.used <= self.budget {
+                break;
+            }
+            if worlds[index].evict_chunk(pos) {
+                self.used -= bytes;
+                self.evicted += 1;
+            }
+        }
+    }
+
+    /// Describe the memory of the loaded chunks of each dimension, given with its name, for `/debug chunks`
+    pub fn summary<'a>(&self, worlds: impl Iterator<Item = (&'a str, &'a World)>) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (name, world) in worlds {
+            let usage = world.memory_usage();
+            lines.push(format!(
+                "{}: {} loaded chunks using {}, {} seen using {}, {} waiting to be written",
+                name,
+                usage.chunks,
+                megabytes(usage.bytes),
+                usage.seen_chunks,
+                megabytes(usage.seen_bytes),
+                world.storage().pending_writes()
+            ));
+        }
+        lines.push(format!(
+            "{} used of the {} budget, {} chunks unloaded for the budget",
+            megabytes(self.used),
+            megabytes(self.budget),
+            self.evicted
+        ));
+        if self.used > self.budget {
+            lines.push("Over the budget: the chunks seen by the players are never unloaded".to_owned());
+        }
+        lines
+    }
+}
+
+fn megabytes(bytes: usize) -> String {
+    format!("{:.1} MB", bytes as f64 / MEGABYTE as f64)
+}
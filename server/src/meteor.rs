@@ -0,0 +1,185 @@
+//! Meteor impacts: rare events that carve a crater and leave some ore behind.
+
+use crate::explosion::Explosions;
+use crate::settings::MeteorSettings;
+use crate::world::World;
+use common::{
+    block::{Block, BlockId},
+    meteor::MeteorInfo,
+    physics::BlockContainer,
+    random::Random,
+    registry::Registry,
+    world::BlockPos,
+};
+use log::warn;
+use nalgebra::Vector3;
+
+/// Speed of a falling meteor, in blocks per second
+const FALL_SPEED: f64 = 40.0;
+/// Seconds during which the meteor is visible before the impact
+const FALL_TIME: f64 = 3.0;
+/// Direction the meteors come from
+const FALL_DIRECTION: [f64; 3] = [0.4, 1.0, 0.2];
+/// Number of blocks scanned above and below the targeted player to find the ground
+const GROUND_SCAN_HEIGHT: i64 = 64;
+/// Radius of the ore deposit at the bottom of the crater
+const ORE_DEPOSIT_RADIUS: f64 = 1.5;
+
+/// A meteor that will hit the ground
+struct Meteor {
+    target: Vector3<f64>,
+    time_to_impact: f64,
+}
+
+impl Meteor {
+    fn get_info(&self) -> MeteorInfo {
+        let pos = if self.time_to_impact <= FALL_TIME {
+            let direction = Vector3::from(FALL_DIRECTION).normalize();
+            Some(self.target + direction * self.time_to_impact * FALL_SPEED)
+        } else {
+            None
+        };
+        MeteorInfo {
+            pos,
+            target: self.target,
+            time_to_impact: self.time_to_impact,
+        }
+    }
+}
+
+/// Schedules the meteors and makes them fall
+pub struct Meteors {
+    settings: MeteorSettings,
+    ore_block: Option<BlockId>,
+    /// Seconds before the next meteor is announced
+    next_meteor: f64,
+    meteors: Vec<Meteor>,
+    /// The craters that will receive an ore deposit once the explosion is over
+    craters: Vec<Vector3<f64>>,
+    /// The impacts since the last call to `drain_impacts`
+    impacts: Vec<Vector3<f64>>,
+    random: Random,
+}
+
+impl Meteors {
+    pub fn new(settings: MeteorSettings, block_registry: &Registry<Block>) -> Self {
+        let ore_block = block_registry.get_id_by_name(&settings.ore_block).map(|id| id as BlockId);
+        if settings.enabled && ore_block.is_none() {
+            warn!("Meteor ore block {:?} doesn't exist, meteors will not leave any ore", settings.ore_block);
+        }
+        let mut meteors = Self {
+            random: Random::new(settings.seed),
+            settings,
+            ore_block,
+            next_meteor: 0.0,
+            meteors: Vec::new(),
+            craters: Vec::new(),
+            impacts: Vec::new(),
+        };
+        meteors.next_meteor = meteors.random_interval();
+        meteors
+    }
+
+    fn random_interval(&mut self) -> f64 {
+        self.settings.mean_interval * (0.5 + self.random.next_f64())
+    }
+
+    /// The incoming meteors, to be sent to the clients
+    pub fn get_infos(&self) -> Vec<MeteorInfo> {
+        self.meteors.iter().map(Meteor::get_info).collect()
+    }
+
+    /// Pick an impact point close to one of the players
+    fn pick_target(&mut self, world: &World, player_positions: &[Vector3<f64>]) -> Option<Vector3<f64>> {
+        if player_positions.is_empty() {
+            return None;
+        }
+        let player = player_positions[(self.random.next_f64() * player_positions.len() as f64) as usize];
+        let angle = self.random.next_f64() * 2.0 * std::f64::consts::PI;
+        let distance = self.random.next_f64() * self.settings.max_distance;
+        let column = BlockPos::from(player + Vector3::new(angle.cos(), 0.0, angle.sin()) * distance);
+        (-GROUND_SCAN_HEIGHT..=GROUND_SCAN_HEIGHT)
+            .rev()
+            .map(|dy| BlockPos::from((column.px, column.py + dy, column.pz)))
+            .find(|pos| world.is_block_full(*pos))
+            .map(|ground| Vector3::new(ground.px as f64 + 0.5, ground.py as f64 + 1.0, ground.pz as f64 + 0.5))
+    }
+
+    /// Fill the bottom of a crater with ore
+    fn deposit_ore(&self, world: &mut World, crater: Vector3<f64>) {
+        let ore_block = match self.ore_block {
+            Some(ore_block) => ore_block,
+            None => return,
+        };
+        let center = BlockPos::from(crater);
+        let depth = self.settings.power.ceil() as i64 * 2;
+        let bottom = match (0..=depth)
+            .map(|dy| BlockPos::from((center.px, center.py - dy, center.pz)))
+            .find(|pos| world.is_block_full(*pos))
+        {
+            Some(bottom) => bottom,
+            None => return,
+        };
+        let radius = ORE_DEPOSIT_RADIUS.ceil() as i64;
+        let mut deposit = Vec::new();
+        for i in -radius..=radius {
+            for j in -radius..=radius {
+                for k in -radius..=radius {
+                    let pos = BlockPos::from((bottom.px + i, bottom.py + j, bottom.pz + k));
+                    if ((i * i + j * j + k * k) as f64).sqrt() <= ORE_DEPOSIT_RADIUS && world.is_block_full(pos) {
+                        deposit.push((pos, ore_block));
+                    }
+                }
+            }
+        }
+        world.set_blocks(&deposit);
+    }
+
+    /// Announce the new meteors, and make the incoming ones fall
+    pub fn tick(&mut self, world: &mut World, explosions: &mut Explosions, player_positions: &[Vector3<f64>], dt: f64) {
+
+        // The explosions of the previous impacts are over
+        for crater in std::mem::take(&mut self.craters) {
+            self.deposit_ore(world, crater);
+        }
+
+        if !self.settings.enabled {
+            return;
+        }
+
+        self.next_meteor -= dt;
+        if self.next_meteor <= 0.0 {
+            self.next_meteor = self.random_interval();
+            if let Some(target) = self.pick_target(world, player_positions) {
+                self.meteors.push(Meteor {
+                    target,
+                    time_to_impact: f64::max(self.settings.warning_time, FALL_TIME),
+                });
+            }
+        }
+
+        for meteor in self.meteors.iter_mut() {
+            meteor.time_to_impact -= dt;
+            if meteor.time_to_impact <= 0.0 {
+                explosions.trigger(meteor.target, self.settings.power);
+                self.craters.push(meteor.target);
+                self.impacts.push(meteor.target);
+            }
+        }
+        self.meteors.retain(|meteor| meteor.time_to_impact > 0.0);
+    }
+
+    /// Return the position of the impacts since the last call
+    pub fn drain_impacts(&mut self) -> Vec<Vector3<f64>> {
+        std::mem::take(&mut self.impacts)
+    }
+
+    /// Text summary for the debug overlay
+    pub fn debug_info(&self) -> String {
+        format!(
+            "Next meteor in {:.0} s\nIncoming meteors = {}\n",
+            self.next_meteor,
+            self.meteors.len(),
+        )
+    }
+}
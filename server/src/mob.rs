@@ -0,0 +1,391 @@
+//! Creatures, such as the rogue drones. Their kinds are defined in the world settings.
+//!
+//! The creatures spawn around the players, in the loaded chunks of the surface whose light and ground match the rules
+//! of their kind. Under the open sky, the light fades at night. They fly around until they see a target: the hostile
+//! creatures go for the closest player or robot, the others only for whoever hurt them.
+//! They disappear when they are far from every player, and drop their loot when they are destroyed by a hit.
+
+use crate::combat::{in_sight, Hit, HEALTH_PER_EXPLOSION_STRENGTH};
+use crate::explosion::explosion_strength;
+use crate::robot::Robots;
+use crate::settings::MobSettings;
+use crate::survival::Survival;
+use crate::world::World;
+use common::{
+    block::{Block, BlockId},
+    combat::Combatant,
+    item::{Item, ItemStack},
+    mob::{mob_aabb, MobId, MobInfo, MobState},
+    physics::{player::KNOCKBACK_DECAY, BlockContainer},
+    player::PlayerId,
+    random::Random,
+    registry::Registry,
+    world::{BlockPos, CHUNK_SIZE},
+};
+use log::warn;
+use nalgebra::Vector3;
+use std::collections::{BTreeMap, HashSet};
+
+/// Maximum horizontal distance between a wandering creature and its next destination
+const WANDER_RADIUS: f64 = 8.0;
+/// Longest time a wandering creature waits before flying somewhere else, in seconds
+const WANDER_PAUSE: f64 = 4.0;
+/// Distance from which a creature hits its target
+const ATTACK_RANGE: f64 = 1.5;
+/// Seconds a creature keeps chasing a target it can't see anymore
+const TARGET_MEMORY: f64 = 5.0;
+
+struct Mob {
+    kind: String,
+    pos: Vector3<f64>,
+    yaw: f64,
+    health: f64,
+    state: MobState,
+    /// Where the creature is wandering to, None while it waits
+    destination: Option<Vector3<f64>>,
+    /// Seconds before the creature wanders somewhere else
+    pause: f64,
+    /// The player or the robot the creature attacks
+    target: Option<Combatant>,
+    /// Seconds since the creature last saw its target
+    unseen_time: f64,
+    /// Seconds before the creature can hit again
+    cooldown: f64,
+    /// Whoever last hurt the creature, which it fights back even if it is not hostile
+    attacker: Option<Combatant>,
+    /// The velocity given by the hits, which fades quickly
+    knockback: Vector3<f64>,
+}
+
+impl Mob {
+    /// Fly towards a position, climbing over the obstacles. Return false if the creature is stuck, that is if it
+    /// doesn't get closer to the position.
+    fn fly_towards(&mut self, world: &World, destination: Vector3<f64>, speed: f64, dt: f64) -> bool {
+        let delta = destination - self.pos;
+        let distance = delta.norm();
+        if distance < 1e-3 {
+            return true;
+        }
+        let step = delta * (f64::min(speed * dt, distance) / distance);
+        let mut aabb = mob_aabb(self.pos);
+        let mut moved = aabb.move_check_collision(world, step);
+        if moved.norm() < step.norm() / 2.0 {
+            moved += aabb.move_check_collision(world, Vector3::new(0.0, speed * dt, 0.0));
+        }
+        self.pos += moved;
+        if delta.x != 0.0 || delta.z != 0.0 {
+            self.yaw = f64::atan2(-delta.x, -delta.z).to_degrees();
+        }
+        (destination - self.pos).norm() < distance - step.norm() / 4.0
+    }
+}
+
+/// All the creatures of the surface
+pub struct Mobs {
+    settings: MobSettings,
+    /// The blocks each kind of creature spawns on, None if it spawns on any block
+    spawn_blocks: BTreeMap<String, Option<HashSet<BlockId>>>,
+    /// The items each kind of creature drops
+    drops: BTreeMap<String, Vec<ItemStack>>,
+    mobs: BTreeMap<MobId, Mob>,
+    next_id: u32,
+    /// Seconds before the next spawn attempt
+    next_spawn: f64,
+    random: Random,
+}
+
+impl Mobs {
+    pub fn new(settings: MobSettings, block_registry: &Registry<Block>, item_registry: &Registry<Item>) -> Self {
+        let mut spawn_blocks = BTreeMap::new();
+        let mut drops = BTreeMap::new();
+        for (name, kind) in settings.kinds.iter() {
+            let blocks = kind.spawn_blocks.iter().filter_map(|block| {
+                let id = block_registry.get_id_by_name(block).map(|id| id as BlockId);
+                if id.is_none() {
+                    warn!("Creature {} spawns on the block {:?}, which doesn't exist", name, block);
+                }
+                id
+            });
+            let blocks = Some(blocks.collect()).filter(|_| !kind.spawn_blocks.is_empty());
+            spawn_blocks.insert(name.clone(), blocks);
+            let stacks = kind.drops.iter().filter_map(|(item, count)| {
+                let id = item_registry.get_id_by_name(item);
+                if id.is_none() {
+                    warn!("Creature {} drops the item {:?}, which doesn't exist", name, item);
+                }
+                Some(ItemStack::new(id?, *count))
+            });
+            drops.insert(name.clone(), stacks.collect());
+        }
+        Self {
+            next_spawn: settings.spawn_interval,
+            random: Random::new(settings.seed),
+            settings,
+            spawn_blocks,
+            drops,
+            mobs: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// The state of all the creatures, to be sent to the clients
+    pub fn get_infos(&self) -> Vec<MobInfo> {
+        self.mobs
+            .iter()
+            .map(|(id, mob)| MobInfo {
+                id: *id,
+                kind: mob.kind.clone(),
+                pos: mob.pos,
+                yaw: mob.yaw,
+                state: mob.state,
+                health: mob.health,
+                max_health: self.settings.kinds.get(&mob.kind).map_or(mob.health, |kind| kind.health),
+            })
+            .collect()
+    }
+
+    /// Create a creature at some position, return None if its kind doesn't exist
+    fn spawn(&mut self, kind: &str, pos: Vector3<f64>) -> Option<MobId> {
+        let health = self.settings.kinds.get(kind)?.health;
+        let id = MobId(self.next_id);
+        self.next_id += 1;
+        let mob = Mob {
+            kind: kind.to_owned(),
+            pos,
+            yaw: 0.0,
+            health,
+            state: MobState::Wandering,
+            destination: None,
+            pause: 0.0,
+            target: None,
+            unseen_time: 0.0,
+            cooldown: 0.0,
+            attacker: None,
+            knockback: Vector3::zeros(),
+        };
+        self.mobs.insert(id, mob);
+        Some(id)
+    }
+
+    /// Try to spawn a creature in every loaded chunk at the right distance from the players, once in a while.
+    /// `daylight` is the brightness of the sunlight, from 0 to 1.
+    pub fn spawn_around(&mut self, world: &World, players: &[(PlayerId, Vector3<f64>)], daylight: f64, dt: f64) {
+        self.next_spawn -= dt;
+        if self.next_spawn > 0.0 {
+            return;
+        }
+        self.next_spawn = self.settings.spawn_interval;
+        let max_mobs = self.settings.max_mobs_per_player * players.len();
+        for chunk_pos in world.loaded_chunk_positions() {
+            if self.mobs.len() >= max_mobs {
+                return;
+            }
+            let half_chunk = CHUNK_SIZE as f64 / 2.0;
+            let center = Vector3::new(chunk_pos.px as f64, chunk_pos.py as f64, chunk_pos.pz as f64) * CHUNK_SIZE as f64
+                + Vector3::new(half_chunk, half_chunk, half_chunk);
+            let distance = players.iter().map(|(_, pos)| (pos - center).norm()).fold(f64::INFINITY, f64::min);
+            if distance < self.settings.min_spawn_distance || distance > self.settings.max_spawn_distance {
+                continue;
+            }
+            if self.random.next_f64() >= self.settings.spawn_chance {
+                continue;
+            }
+            // The creatures spawn in the air, right above the ground below a random block of the chunk
+            let x = chunk_pos.px * CHUNK_SIZE as i64 + (self.random.next_f64() * CHUNK_SIZE as f64) as i64;
+            let z = chunk_pos.pz * CHUNK_SIZE as i64 + (self.random.next_f64() * CHUNK_SIZE as f64) as i64;
+            let bottom = chunk_pos.py * CHUNK_SIZE as i64;
+            let top = bottom + (self.random.next_f64() * CHUNK_SIZE as f64) as i64;
+            let spawn_pos = (bottom..=top)
+                .rev()
+                .map(|y| BlockPos::from((x, y, z)))
+                .find(|pos| !world.is_block_full(*pos) && world.is_block_full(BlockPos::from((x, pos.py - 1, z))));
+            let spawn_pos = match spawn_pos {
+                Some(spawn_pos) => spawn_pos,
+                None => continue,
+            };
+            let ground = world.get_block(BlockPos::from((x, spawn_pos.py - 1, z)));
+            let mut light = world.get_light(spawn_pos);
+            if world.is_under_open_sky(spawn_pos) {
+                light = (light as f64 * daylight).round() as u8;
+            }
+            let kinds = self
+                .settings
+                .kinds
+                .iter()
+                .filter(|(name, kind)| {
+                    let on_spawn_block = self.spawn_blocks[*name].as_ref().is_none_or(|blocks| blocks.contains(&ground));
+                    on_spawn_block && (kind.min_light..=kind.max_light).contains(&light)
+                })
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            if kinds.is_empty() {
+                continue;
+            }
+            let kind = kinds[(self.random.next_f64() * kinds.len() as f64) as usize].clone();
+            let pos = Vector3::new(x as f64 + 0.5, spawn_pos.py as f64 + 0.5, z as f64 + 0.5);
+            self.spawn(&kind, pos);
+        }
+    }
+
+    /// Move and despawn the creatures, and return the hits of those that attack.
+    /// `players` are the ids and the center of the players on the surface.
+    pub fn tick(
+        &mut self,
+        world: &World,
+        players: &[(PlayerId, Vector3<f64>)],
+        robots: &Robots,
+        survival: &Survival,
+        dt: f64,
+    ) -> Vec<Hit> {
+        let despawn_distance = self.settings.despawn_distance;
+        self.mobs.retain(|_, mob| {
+            let is_loaded = world.get_chunk(BlockPos::from(mob.pos).containing_chunk_pos()).is_some();
+            is_loaded && players.iter().any(|(_, pos)| (pos - mob.pos).norm() <= despawn_distance)
+        });
+
+        // The players who still have health and the robots that still have energy can be attacked
+        let mut targets = players
+            .iter()
+            .filter(|(id, _)| survival.is_alive(*id))
+            .map(|(id, pos)| (Combatant::Player(*id), *pos))
+            .collect::<Vec<_>>();
+        let robot_targets = robots.get_infos().into_iter().filter(|robot| robot.energy > 0.0);
+        targets.extend(robot_targets.map(|robot| (Combatant::Robot(robot.id), robot.pos)));
+
+        let mut hits = Vec::new();
+        let random_values = (0..3 * self.mobs.len()).map(|_| self.random.next_f64()).collect::<Vec<_>>();
+        let mut random_values = random_values.into_iter();
+        for (&id, mob) in self.mobs.iter_mut() {
+            let kind = match self.settings.kinds.get(&mob.kind) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            mob.cooldown = f64::max(mob.cooldown - dt, 0.0);
+            if mob.knockback.norm() > 1e-3 {
+                mob.pos += mob_aabb(mob.pos).move_check_collision(world, mob.knockback * dt);
+                mob.knockback *= f64::max(1.0 - KNOCKBACK_DECAY * dt, 0.0);
+            }
+            let position_of = |target| targets.iter().find(|(other, _)| *other == target).map(|(_, pos)| *pos);
+
+            // Forget the target once it is gone or hidden for too long
+            if let Some(target) = mob.target {
+                match position_of(target) {
+                    Some(pos) if in_sight(world, mob.pos, pos) => mob.unseen_time = 0.0,
+                    Some(_) if mob.unseen_time < TARGET_MEMORY => mob.unseen_time += dt,
+                    _ => mob.target = None,
+                }
+            }
+            if mob.target.is_none() {
+                let is_candidate = |target: Combatant, pos: Vector3<f64>| {
+                    (kind.hostile || mob.attacker == Some(target))
+                        && (pos - mob.pos).norm() <= kind.detection_range
+                        && in_sight(world, mob.pos, pos)
+                };
+                mob.target = targets
+                    .iter()
+                    .filter(|(target, pos)| is_candidate(*target, *pos))
+                    .min_by(|(_, a), (_, b)| (a - mob.pos).norm().total_cmp(&(b - mob.pos).norm()))
+                    .map(|(target, _)| *target);
+                mob.unseen_time = 0.0;
+            }
+
+            let (r1, r2, r3) = (random_values.next().unwrap(), random_values.next().unwrap(), random_values.next().unwrap());
+            match mob.target.and_then(position_of) {
+                Some(target_pos) if (target_pos - mob.pos).norm() <= ATTACK_RANGE => {
+                    mob.state = MobState::Attacking;
+                    let delta = target_pos - mob.pos;
+                    mob.yaw = f64::atan2(-delta.x, -delta.z).to_degrees();
+                    if mob.cooldown == 0.0 {
+                        mob.cooldown = kind.attack_interval;
+                        hits.push(Hit {
+                            attacker: Combatant::Mob(id),
+                            target: mob.target.unwrap(),
+                            damage: kind.damage,
+                            direction: delta,
+                            cause: format!("You were destroyed by a {}", mob.kind.replace('_', " ")),
+                        });
+                    }
+                }
+                Some(target_pos) => {
+                    mob.state = MobState::Chasing;
+                    mob.destination = None;
+                    mob.fly_towards(world, target_pos, kind.speed, dt);
+                }
+                None => {
+                    mob.state = MobState::Wandering;
+                    match mob.destination {
+                        Some(destination) => {
+                            let arrived = (destination - mob.pos).norm() < 0.1;
+                            if arrived || !mob.fly_towards(world, destination, kind.speed / 2.0, dt) {
+                                mob.destination = None;
+                                mob.pause = r1 * WANDER_PAUSE;
+                            }
+                        }
+                        None if mob.pause > 0.0 => mob.pause -= dt,
+                        None => {
+                            let angle = r1 * 2.0 * std::f64::consts::PI;
+                            let distance = r2 * WANDER_RADIUS;
+                            let dy = (r3 - 0.5) * 2.0;
+                            mob.destination = Some(mob.pos + Vector3::new(angle.cos() * distance, dy, angle.sin() * distance));
+                        }
+                    }
+                }
+            }
+        }
+        hits
+    }
+
+    /// Damage and push a creature that was hit, which then fights back.
+    /// Return the items it drops, with their position, if it is destroyed.
+    pub fn apply_hit(
+        &mut self,
+        id: MobId,
+        damage: f64,
+        attacker: Combatant,
+        knockback: Vector3<f64>,
+    ) -> Vec<(Vector3<f64>, ItemStack)> {
+        let mob = match self.mobs.get_mut(&id) {
+            Some(mob) => mob,
+            None => return Vec::new(),
+        };
+        mob.health -= damage;
+        mob.knockback += knockback;
+        mob.attacker = Some(attacker);
+        if mob.target.is_none() {
+            mob.target = Some(attacker);
+            mob.unseen_time = 0.0;
+        }
+        if mob.health > 0.0 {
+            return Vec::new();
+        }
+        let mob = self.mobs.remove(&id).unwrap();
+        let drops = self.drops.get(&mob.kind).cloned().unwrap_or_default();
+        drops.into_iter().map(|stack| (mob.pos, stack)).collect()
+    }
+
+    /// Damage the creatures caught in an explosion, and return the ids, damage and positions of the ones that were hurt
+    pub fn apply_explosion(&mut self, center: Vector3<f64>, power: f64) -> Vec<(MobId, f64, Vector3<f64>)> {
+        let mut hurt = Vec::new();
+        for (&id, mob) in self.mobs.iter_mut() {
+            let damage = explosion_strength(center, power, mob.pos) * HEALTH_PER_EXPLOSION_STRENGTH;
+            if damage > 0.0 {
+                mob.health -= damage;
+                hurt.push((id, damage, mob.pos));
+            }
+        }
+        self.mobs.retain(|_, mob| mob.health > 0.0);
+        hurt
+    }
+
+    /// Text summary of the creatures for the debug overlay
+    pub fn debug_info(&self) -> String {
+        let mut info = format!("Next spawn attempt in {:.0} s\n", self.next_spawn);
+        for (id, mob) in self.mobs.iter() {
+            info.push_str(&format!(
+                "Creature {} ({}): {:?}, health = {:.1}, target = {:?}\n",
+                id.0, mob.kind, mob.state, mob.health, mob.target,
+            ));
+        }
+        info
+    }
+}
@@ -0,0 +1,434 @@
+//! Server mods: WebAssembly modules loaded from the `mods` folder, which extend the game logic without recompiling.
+//!
+//! A mod can export the following functions, which are all optional:
+//! - `init()`: called once when the mod is loaded, before the data. Blocks and items can only be registered here.
+//! - `on_block_break(player: i32, x: i32, y: i32, z: i32, block: i32)`
+//! - `on_block_place(player: i32, x: i32, y: i32, z: i32, block: i32)`
+//! - `on_tick(millis: i32)`
+//! - `on_timer(timer: i32)`: a timer scheduled by the mod fired
+//!
+//! The host functions are imported from the `marsbots` module. The strings are passed as a pointer and a length
+//! in the exported `memory` of the mod.
+//! - `log(ptr: i32, len: i32)`
+//! - `register_block(name_ptr: i32, name_len: i32, texture_ptr: i32, texture_len: i32) -> i32`:
+//!   register a cube with the same texture on every face, return 0 on success
+//! - `register_item(name_ptr: i32, name_len: i32, texture_ptr: i32, texture_len: i32) -> i32`
+//! - `block_id(ptr: i32, len: i32) -> i32` and `item_id(ptr: i32, len: i32) -> i32`: -1 if the name is unknown
+//! - `get_block(x: i32, y: i32, z: i32) -> i32`: 0 (air) if the chunk is not loaded
+//! - `set_block(x: i32, y: i32, z: i32, block: i32)`
+//! - `insert_item(x: i32, y: i32, z: i32, item: i32, count: i32) -> i32`: put items in the storage crate at
+//!   the position, return the number of items that didn't fit
+//! - `schedule(ticks: i32, timer: i32)`: call `on_timer(timer)` in some ticks, even if the server restarts meanwhile
+//!
+//! The mods are sandboxed: they can only use the host functions, and every call has limited fuel and memory.
+//! The changes to the world are applied when the call returns. A mod that fails is disabled.
+//! The block events are received from the event bus, and given to the mods during the next tick.
+//! The mods only see the surface: the events of the other dimensions are not given to them.
+
+use crate::events::{BlockBroken, BlockPlaced, EventBus};
+use crate::scheduler::{Scheduler, Task};
+use crate::world::World;
+use anyhow::{Context, Result};
+use common::{
+    block::{Block, BlockId, BlockType},
+    content::ContentInfo,
+    data::{cache::hash_bytes, Data, DataExtensions},
+    inventory::{STORAGE_CRATE_BLOCK, STORAGE_CRATE_SLOTS},
+    item::{Item, ItemId, ItemStack, ItemType},
+    player::PlayerId,
+    registry::Registry,
+    world::{BlockPos, Chunk, ChunkPos, DimensionId},
+};
+use log::{error, info, warn};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use wasmi::{Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Maximum number of instructions executed by one call, roughly
+const FUEL_PER_CALL: u64 = 10_000_000;
+/// Maximum size of the memory of a mod, in bytes
+const MAX_MEMORY: usize = 16 * 1024 * 1024;
+/// Maximum length of the strings passed to the host functions
+const MAX_STRING_LENGTH: usize = 256;
+
+/// A change to the world requested by a mod
+enum ModCommand {
+    SetBlock(BlockPos, BlockId),
+    InsertItem(BlockPos, ItemStack),
+    /// Call `on_timer` with the timer in some ticks
+    Schedule(u64, i32),
+}
+
+/// The state of the host that the host functions can access
+struct ModHost {
+    mod_name: String,
+    limits: StoreLimits,
+    /// Whether `init` is running, which is the only time blocks and items can be registered
+    initializing: bool,
+    extensions: DataExtensions,
+    blocks: Arc<Registry<Block>>,
+    items: Arc<Registry<Item>>,
+    /// The chunks of the world when the call started
+    chunks: Arc<HashMap<ChunkPos, Arc<Chunk>>>,
+    /// The blocks set during the call, which are read before the chunks
+    set_blocks: HashMap<BlockPos, BlockId>,
+    commands: Vec<ModCommand>,
+}
+
+struct LoadedMod {
+    name: String,
+    /// The mods have no version number: the hash of their module tells their versions apart
+    version: String,
+    store: Store<ModHost>,
+    instance: Instance,
+    enabled: bool,
+}
+
+/// An event of the event bus, waiting to be given to the mods
+enum ModEvent {
+    BlockBreak(PlayerId, BlockPos, BlockId),
+    BlockPlace(PlayerId, BlockPos, BlockId),
+}
+
+/// All the loaded mods
+pub struct Mods {
+    mods: Vec<LoadedMod>,
+    pending_events: Rc<RefCell<Vec<ModEvent>>>,
+}
+
+impl Mods {
+    /// Load all the `.wasm` files of the folder and call their `init` function.
+    /// The mods that fail to load are skipped.
+    pub fn load(folder: &Path) -> Self {
+        let mut mods = Vec::new();
+        let pending_events = Default::default();
+        if !folder.is_dir() {
+            return Self { mods, pending_events };
+        }
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let linker = create_linker(&engine);
+
+        let mut paths = match std::fs::read_dir(folder) {
+            Ok(dir_entries) => dir_entries
+                .filter_map(|dir_entry| dir_entry.ok().map(|dir_entry| dir_entry.path()))
+                .filter(|path| path.extension().map(|ext| ext == "wasm").unwrap_or(false))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                error!("Failed to read mods folder {}: {}", folder.display(), e);
+                Vec::new()
+            }
+        };
+        paths.sort();
+        for path in paths {
+            match load_mod(&engine, &linker, &path) {
+                Ok(loaded_mod) => {
+                    info!("Loaded mod {}", loaded_mod.name);
+                    mods.push(loaded_mod);
+                }
+                Err(e) => error!("Failed to load mod {}: {:#}", path.display(), e),
+            }
+        }
+        Self { mods, pending_events }
+    }
+
+    /// Receive the events of the bus that the mods can handle
+    pub fn subscribe(&self, events: &mut EventBus) {
+        let pending_events = self.pending_events.clone();
+        events.subscribe("mods", move |event: &BlockBroken, _| {
+            if event.dimension == DimensionId::SURFACE {
+                pending_events.borrow_mut().push(ModEvent::BlockBreak(event.player, event.pos, event.block));
+            }
+        });
+        let pending_events = self.pending_events.clone();
+        events.subscribe("mods", move |event: &BlockPlaced, _| {
+            if event.dimension == DimensionId::SURFACE {
+                pending_events.borrow_mut().push(ModEvent::BlockPlace(event.player, event.pos, event.block));
+            }
+        });
+    }
+
+    /// The blocks and items registered by the mods, to be added to the data
+    pub fn get_extensions(&self) -> DataExtensions {
+        let mut extensions = DataExtensions::default();
+        for loaded_mod in self.mods.iter() {
+            extensions.blocks.extend(loaded_mod.store.data().extensions.blocks.iter().cloned());
+            extensions.items.extend(loaded_mod.store.data().extensions.items.iter().cloned());
+        }
+        extensions
+    }
+
+    /// The names and versions of the loaded mods, for the content of the server
+    pub fn content(&self) -> Vec<ContentInfo> {
+        self.mods
+            .iter()
+            .map(|loaded_mod| ContentInfo {
+                name: loaded_mod.name.clone(),
+                version: loaded_mod.version.clone(),
+                min_game_version: None,
+            })
+            .collect()
+    }
+
+    /// Give the loaded data to the mods. Must be called after the data is loaded or reloaded.
+    pub fn set_data(&mut self, data: &Data) {
+        let blocks = Arc::new(data.blocks.clone());
+        let items = Arc::new(data.items.clone());
+        for loaded_mod in self.mods.iter_mut() {
+            loaded_mod.store.data_mut().blocks = blocks.clone();
+            loaded_mod.store.data_mut().items = items.clone();
+        }
+    }
+
+    /// Give the pending events to the mods, then call their `on_tick` function
+    pub fn tick(&mut self, world: &mut World, scheduler: &mut Scheduler, dt: f64) {
+        let pending_events = std::mem::take(&mut *self.pending_events.borrow_mut());
+        for event in pending_events {
+            let (function, player, pos, block) = match event {
+                ModEvent::BlockBreak(player, pos, block) => ("on_block_break", player, pos, block),
+                ModEvent::BlockPlace(player, pos, block) => ("on_block_place", player, pos, block),
+            };
+            let params = (player.raw() as i32, pos.px as i32, pos.py as i32, pos.pz as i32, block as i32);
+            self.call_all(world, scheduler, None, function, params);
+        }
+        self.call_all(world, scheduler, None, "on_tick", (dt * 1000.0) as i32);
+    }
+
+    /// Call the `on_timer` function of a mod, for a timer it scheduled
+    pub fn run_timer(&mut self, world: &mut World, scheduler: &mut Scheduler, mod_name: &str, timer: i32) {
+        self.call_all(world, scheduler, Some(mod_name), "on_timer", timer);
+    }
+
+    /// Call the function of every enabled mod that exports it, or only of the mod `target`, then apply the changes
+    /// they made to the world
+    fn call_all<Params: wasmi::WasmParams + Copy>(
+        &mut self,
+        world: &mut World,
+        scheduler: &mut Scheduler,
+        target: Option<&str>,
+        function: &str,
+        params: Params,
+    ) {
+        // The snapshot is only taken if a mod handles the event
+        let mut chunks = None;
+        let called = |loaded_mod: &&mut LoadedMod| loaded_mod.enabled && target.is_none_or(|name| loaded_mod.name == name);
+        for loaded_mod in self.mods.iter_mut().filter(called) {
+            let func = match loaded_mod.instance.get_typed_func::<Params, ()>(&loaded_mod.store, function) {
+                Ok(func) => func,
+                Err(_) => continue,
+            };
+            let chunks = chunks.get_or_insert_with(|| Arc::new(world.chunk_snapshot())).clone();
+            let host = loaded_mod.store.data_mut();
+            host.chunks = chunks;
+            host.set_blocks.clear();
+            loaded_mod.store.set_fuel(FUEL_PER_CALL).expect("fuel metering is enabled");
+            if let Err(e) = func.call(&mut loaded_mod.store, params) {
+                error!("Mod {} failed in {} and was disabled: {}", loaded_mod.name, function, e);
+                loaded_mod.enabled = false;
+            }
+            let host = loaded_mod.store.data_mut();
+            host.chunks = Default::default();
+            for command in host.commands.drain(..) {
+                match command {
+                    ModCommand::SetBlock(pos, block) => {
+                        world.set_block(pos, block);
+                    }
+                    ModCommand::InsertItem(pos, stack) => {
+                        world.get_container_mut(pos, STORAGE_CRATE_SLOTS).insert(stack);
+                    }
+                    ModCommand::Schedule(ticks, timer) => {
+                        let task = Task::ModTimer { mod_name: loaded_mod.name.clone(), timer };
+                        scheduler.run_in_ticks(ticks, task);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn debug_info(&self) -> String {
+        let mut info = String::new();
+        for loaded_mod in self.mods.iter() {
+            let state = if loaded_mod.enabled { "enabled" } else { "disabled" };
+            info.push_str(&format!("Mod {}: {}\n", loaded_mod.name, state));
+        }
+        info
+    }
+}
+
+fn load_mod(engine: &Engine, linker: &Linker<ModHost>, path: &Path) -> Result<LoadedMod> {
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .context("invalid mod file name")?
+        .to_owned();
+    let bytes = std::fs::read(path).context("failed to read mod")?;
+    let version = format!("{:016x}", hash_bytes(&bytes));
+    let module = Module::new(engine, &bytes[..]).context("invalid WebAssembly module")?;
+    let host = ModHost {
+        mod_name: name.clone(),
+        limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).instances(1).build(),
+        initializing: true,
+        extensions: DataExtensions::default(),
+        blocks: Default::default(),
+        items: Default::default(),
+        chunks: Default::default(),
+        set_blocks: HashMap::new(),
+        commands: Vec::new(),
+    };
+    let mut store = Store::new(engine, host);
+    store.limiter(|host| &mut host.limits);
+    store.set_fuel(FUEL_PER_CALL).expect("fuel metering is enabled");
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .context("failed to instantiate mod")?;
+    if let Ok(init) = instance.get_typed_func::<(), ()>(&store, "init") {
+        init.call(&mut store, ()).context("init failed")?;
+    }
+    store.data_mut().initializing = false;
+    Ok(LoadedMod {
+        name,
+        version,
+        store,
+        instance,
+        enabled: true,
+    })
+}
+
+/// Read a string from the memory of the mod
+fn read_string(caller: &Caller<'_, ModHost>, ptr: i32, len: i32) -> Option<String> {
+    if ptr < 0 || len < 0 || len as usize > MAX_STRING_LENGTH {
+        return None;
+    }
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let mut buffer = vec![0; len as usize];
+    memory.read(caller, ptr as usize, &mut buffer).ok()?;
+    String::from_utf8(buffer).ok()
+}
+
+fn get_block(host: &ModHost, pos: BlockPos) -> BlockId {
+    if let Some(&block) = host.set_blocks.get(&pos) {
+        return block;
+    }
+    host.chunks
+        .get(&pos.containing_chunk_pos())
+        .map(|chunk| chunk.get_block_at(pos.pos_in_containing_chunk()))
+        .unwrap_or(0)
+}
+
+/// Register a block or an item defined by a mod. Return 0 on success.
+fn register(caller: &mut Caller<'_, ModHost>, kind: &str, name: Option<String>, texture: Option<String>) -> i32 {
+    let (name, texture) = match (name, texture) {
+        (Some(name), Some(texture)) => (name, texture),
+        _ => return -1,
+    };
+    let host = caller.data_mut();
+    if !host.initializing {
+        warn!("Mod {} tried to register {} {} after init", host.mod_name, kind, name);
+        return -1;
+    }
+    // The mod's content is in its own namespace
+    let name = format!("{}:{}", host.mod_name, name);
+    if kind == "block" {
+        let block_type = BlockType::NormalCube {
+            face_texture: vec![texture; 6],
+            blast_resistance: None,
+            loot_table: None,
+        };
+        host.extensions.blocks.push((name, block_type));
+    } else {
+        host.extensions.items.push((name, ItemType::NormalItem { texture, description: String::new() }));
+    }
+    0
+}
+
+fn create_linker(engine: &Engine) -> Linker<ModHost> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap("marsbots", "log", |caller: Caller<'_, ModHost>, ptr: i32, len: i32| {
+            if let Some(message) = read_string(&caller, ptr, len) {
+                info!("[mod {}] {}", caller.data().mod_name, message);
+            }
+        })
+        .unwrap()
+        .func_wrap(
+            "marsbots",
+            "register_block",
+            |mut caller: Caller<'_, ModHost>, name_ptr: i32, name_len: i32, texture_ptr: i32, texture_len: i32| {
+                let name = read_string(&caller, name_ptr, name_len);
+                let texture = read_string(&caller, texture_ptr, texture_len);
+                register(&mut caller, "block", name, texture)
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "marsbots",
+            "register_item",
+            |mut caller: Caller<'_, ModHost>, name_ptr: i32, name_len: i32, texture_ptr: i32, texture_len: i32| {
+                let name = read_string(&caller, name_ptr, name_len);
+                let texture = read_string(&caller, texture_ptr, texture_len);
+                register(&mut caller, "item", name, texture)
+            },
+        )
+        .unwrap()
+        .func_wrap("marsbots", "block_id", |caller: Caller<'_, ModHost>, ptr: i32, len: i32| {
+            read_string(&caller, ptr, len)
+                .and_then(|name| caller.data().blocks.get_id_by_name(&name))
+                .map(|id| id as i32)
+                .unwrap_or(-1)
+        })
+        .unwrap()
+        .func_wrap("marsbots", "item_id", |caller: Caller<'_, ModHost>, ptr: i32, len: i32| {
+            read_string(&caller, ptr, len)
+                .and_then(|name| caller.data().items.get_id_by_name(&name))
+                .map(|id| id as i32)
+                .unwrap_or(-1)
+        })
+        .unwrap()
+        .func_wrap("marsbots", "get_block", |caller: Caller<'_, ModHost>, x: i32, y: i32, z: i32| {
+            get_block(caller.data(), BlockPos::from((x as i64, y as i64, z as i64))) as i32
+        })
+        .unwrap()
+        .func_wrap(
+            "marsbots",
+            "set_block",
+            |mut caller: Caller<'_, ModHost>, x: i32, y: i32, z: i32, block: i32| {
+                let host = caller.data_mut();
+                if block < 0 || block as u32 >= host.blocks.get_number_of_ids() {
+                    return;
+                }
+                let pos = BlockPos::from((x as i64, y as i64, z as i64));
+                host.set_blocks.insert(pos, block as BlockId);
+                host.commands.push(ModCommand::SetBlock(pos, block as BlockId));
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "marsbots",
+            "insert_item",
+            |mut caller: Caller<'_, ModHost>, x: i32, y: i32, z: i32, item: i32, count: i32| {
+                let host = caller.data_mut();
+                if item < 0 || item as u32 >= host.items.get_number_of_ids() || count <= 0 {
+                    return count.max(0);
+                }
+                // Only the storage crates can receive items
+                let pos = BlockPos::from((x as i64, y as i64, z as i64));
+                let storage_block = host.blocks.get_id_by_name(&STORAGE_CRATE_BLOCK.to_owned());
+                if storage_block != Some(get_block(host, pos) as u32) {
+                    return count;
+                }
+                host.commands.push(ModCommand::InsertItem(pos, ItemStack::new(item as ItemId, count as u32)));
+                0
+            },
+        )
+        .unwrap()
+        .func_wrap("marsbots", "schedule", |mut caller: Caller<'_, ModHost>, ticks: i32, timer: i32| {
+            caller.data_mut().commands.push(ModCommand::Schedule(ticks.max(1) as u64, timer));
+        })
+        .unwrap();
+    linker
+}
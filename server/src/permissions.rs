@@ -0,0 +1,202 @@
+//! Permission groups: every player belongs to a group, and every group grants permission nodes, such as `build` or
+//! `command.tp`. The groups and the group of each player are defined in `permissions.ron` with the world save.
+//!
+//! A node is granted by the same node, by a wildcard over its prefix such as `command.*`, or by `*`. A group also
+//! grants the nodes of the group it inherits from.
+
+use anyhow::{Context, Result};
+use common::player::PlayerId;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Break and place blocks, and assign mining areas to the robots
+pub const BUILD: &str = "build";
+/// Interact with the doors, the explosives and the elevators, and control the robots
+pub const USE_MACHINES: &str = "use_machines";
+/// Change many blocks at once, such as with `/rollback`
+pub const WORLD_EDIT: &str = "world_edit";
+/// Build in the claims of the other players, and claim without limits
+pub const CLAIMS_BYPASS: &str = "claims.bypass";
+
+/// The node of a player command
+pub fn command_node(name: &str) -> String {
+    format!("command.{}", name)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PermissionGroup {
+    /// The group whose nodes are also granted
+    pub inherits: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+/// The content of `permissions.ron`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PermissionSettings {
+    /// The group of the players who are not listed in `players`
+    pub default_group: String,
+    pub groups: BTreeMap<String, PermissionGroup>,
+    /// The group of each player, by name like the statistics
+    pub players: BTreeMap<String, String>,
+}
+
+impl Default for PermissionSettings {
+    fn default() -> Self {
+        let group = |inherits: Option<&str>, permissions: &[&str]| PermissionGroup {
+            inherits: inherits.map(str::to_owned),
+            permissions: permissions.iter().map(|&node| node.to_owned()).collect(),
+        };
+        let mut groups = BTreeMap::new();
+        groups.insert("guest".to_owned(), group(None, &["command.stats", "command.home", "command.locate"]));
+        groups.insert(
+            "member".to_owned(),
+            group(
+                Some("guest"),
+                &[BUILD, USE_MACHINES, "command.claim", "command.tp", "command.history", "command.timelapse"],
+            ),
+        );
+        groups.insert("builder".to_owned(), group(Some("member"), &[WORLD_EDIT, "command.rollback"]));
+        groups.insert("admin".to_owned(), group(None, &["*"]));
+        Self {
+            default_group: "member".to_owned(),
+            groups,
+            players: BTreeMap::new(),
+        }
+    }
+}
+
+pub struct Permissions {
+    path: PathBuf,
+    settings: PermissionSettings,
+    player_names: HashMap<PlayerId, String>,
+    /// The players whose group changed since the last call to `drain_changed_players`
+    changed_players: Vec<PlayerId>,
+}
+
+impl Permissions {
+    /// Load the permission groups from the world save, creating the default groups if the file doesn't exist
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let settings: PermissionSettings = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read permissions")?;
+            ron::de::from_str(&buf).context("Failed to parse permissions")?
+        } else {
+            info!("Writing the default permission groups to {:?}", path);
+            PermissionSettings::default()
+        };
+        if !settings.groups.contains_key(&settings.default_group) {
+            anyhow::bail!("The default permission group {} doesn't exist", settings.default_group);
+        }
+        for (name, group) in settings.groups.iter() {
+            if let Some(inherits) = group.inherits.as_ref().filter(|inherits| !settings.groups.contains_key(*inherits)) {
+                anyhow::bail!("The permission group {} inherits from {}, which doesn't exist", name, inherits);
+            }
+        }
+        let permissions = Self {
+            path,
+            settings,
+            player_names: HashMap::new(),
+            changed_players: Vec::new(),
+        };
+        permissions.save();
+        Ok(permissions)
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.settings, Default::default())
+            .context("Failed to serialize permissions")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write permissions"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    /// The group of a player, by name. The players who are not listed, or whose group was removed from the file,
+    /// are in the default group.
+    pub fn group_of(&self, name: &str) -> &str {
+        match self.settings.players.get(name) {
+            Some(group) if self.settings.groups.contains_key(group) => group,
+            _ => &self.settings.default_group,
+        }
+    }
+
+    /// Whether a player has a permission. The players whose name is not known yet are in the default group.
+    pub fn has_permission(&self, player: PlayerId, node: &str) -> bool {
+        let group = match self.player_names.get(&player) {
+            Some(name) => self.group_of(name),
+            None => &self.settings.default_group,
+        };
+        self.group_has_permission(group, node)
+    }
+
+    fn group_has_permission(&self, group: &str, node: &str) -> bool {
+        // The inheritance can loop if the file was edited by hand
+        let mut visited = HashSet::new();
+        let mut current = Some(group);
+        while let Some(name) = current.filter(|name| visited.insert(*name)) {
+            let group = match self.settings.groups.get(name) {
+                Some(group) => group,
+                None => return false,
+            };
+            if group.permissions.iter().any(|granted| grants(granted, node)) {
+                return true;
+            }
+            current = group.inherits.as_deref();
+        }
+        false
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name);
+        self.changed_players.push(player);
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.player_names.remove(&player);
+    }
+
+    /// The players whose permissions changed, who must be sent their commands again
+    pub fn drain_changed_players(&mut self) -> Vec<PlayerId> {
+        std::mem::take(&mut self.changed_players)
+    }
+
+    /// Run `group` with the words after the command name, and return the text to show
+    pub fn run_command(&mut self, argument: &str) -> String {
+        // The names can contain spaces
+        let (name, group) = match argument.rsplit_once(' ') {
+            Some((name, group)) => (name.trim(), group),
+            None if argument.is_empty() => {
+                let groups = self.settings.groups.keys().cloned().collect::<Vec<_>>();
+                return format!("The groups are {}, the default group is {}", groups.join(", "), self.settings.default_group);
+            }
+            None => return format!("{} is in the group {}", argument, self.group_of(argument)),
+        };
+        if !self.settings.groups.contains_key(group) {
+            // The last word may be a part of the name
+            if self.settings.players.contains_key(argument) || self.player_names.values().any(|name| name == argument) {
+                return format!("{} is in the group {}", argument, self.group_of(argument));
+            }
+            return format!("Unknown group: {}", group);
+        }
+        if group == self.settings.default_group {
+            self.settings.players.remove(name);
+        } else {
+            self.settings.players.insert(name.to_owned(), group.to_owned());
+        }
+        self.save();
+        let connected = self.player_names.iter().filter(|(_, player_name)| *player_name == name);
+        self.changed_players.extend(connected.map(|(&id, _)| id));
+        format!("{} is now in the group {}", name, group)
+    }
+}
+
+/// Whether a granted node, which can end with a wildcard, grants a node
+fn grants(granted: &str, node: &str) -> bool {
+    match granted.strip_suffix('*') {
+        Some(prefix) => prefix.is_empty() || (prefix.ends_with('.') && node.starts_with(prefix)),
+        None => granted == node,
+    }
+}
@@ -0,0 +1,132 @@
+//! Pregeneration of the surface around the spawn with `/pregen <radius>`, so that the server doesn't generate the
+//! chunks while the players explore them. The chunks are generated by the worldgen workers of the surface and saved
+//! without being loaded, a few at a time and only while no chunk is generated for the players.
+
+use crate::world::World;
+use common::network::{messages::ToClient, Server};
+use common::physics::player::PhysicsPlayer;
+use common::player::PlayerId;
+use common::world::{BlockPos, ChunkPos, WorldHeight};
+use log::info;
+use std::time::Instant;
+
+/// Largest radius accepted by `/pregen`, in chunks
+const MAX_RADIUS: i64 = 64;
+/// The progress is reported each time this percentage of the chunks is done
+const PROGRESS_STEP: usize = 10;
+
+struct Pregeneration {
+    /// The player who started the pregeneration and is told its progress, None for the remote administration
+    requester: Option<PlayerId>,
+    /// The chunks that were not started yet, the nearest to the spawn last
+    remaining: Vec<ChunkPos>,
+    total: usize,
+    done: usize,
+    /// The percentage of the chunks that were done when the progress was last reported
+    reported: usize,
+    start: Instant,
+}
+
+impl Pregeneration {
+    fn progress(&self) -> String {
+        format!("Pregenerated {}% of the chunks ({}/{})", self.done * 100 / self.total, self.done, self.total)
+    }
+}
+
+#[derive(Default)]
+pub struct Pregenerator {
+    current: Option<Pregeneration>,
+}
+
+impl Pregenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `/pregen`, from a player or from the remote administration
+    pub fn run_command(&mut self, requester: Option<PlayerId>, argument: &str, world: &mut World) -> String {
+        match (argument, &self.current) {
+            ("", Some(pregeneration)) => pregeneration.progress(),
+            ("", None) => "Usage: /pregen <radius in chunks>, or /pregen cancel".to_owned(),
+            ("cancel", Some(pregeneration)) => {
+                let message = format!("Pregeneration cancelled. {}", pregeneration.progress());
+                world.cancel_pregeneration();
+                self.current = None;
+                message
+            }
+            ("cancel", None) => "No pregeneration is running".to_owned(),
+            (_, Some(_)) => "A pregeneration is already running, see /pregen or stop it with /pregen cancel".to_owned(),
+            (radius, None) => match radius.parse::<i64>() {
+                Ok(radius) if (1..=MAX_RADIUS).contains(&radius) => {
+                    let pregeneration = start(requester, radius, world.height());
+                    let message = format!(
+                        "Pregenerating {} chunks within {} chunks of the spawn",
+                        pregeneration.total, radius
+                    );
+                    info!("{}", message);
+                    self.current = Some(pregeneration);
+                    message
+                }
+                _ => format!("The radius must be a number of chunks between 1 and {}", MAX_RADIUS),
+            },
+        }
+    }
+
+    /// Start the generation of the next chunks, and report the progress to the requester
+    pub fn tick(&mut self, world: &mut World, server: &mut dyn Server) {
+        let pregeneration = match self.current.as_mut() {
+            Some(pregeneration) => pregeneration,
+            None => return,
+        };
+        while let Some(&pos) = pregeneration.remaining.last() {
+            if !world.pregenerate_chunk(pos) {
+                break;
+            }
+            pregeneration.remaining.pop();
+        }
+        pregeneration.done += world.drain_pregenerated_chunks();
+        let percent = pregeneration.done * 100 / pregeneration.total;
+        let finished = pregeneration.remaining.is_empty() && world.num_pregenerating_chunks() == 0;
+        let message = if finished {
+            let seconds = pregeneration.start.elapsed().as_secs();
+            format!("Pregenerated {} chunks in {} seconds", pregeneration.total, seconds)
+        } else if percent / PROGRESS_STEP > pregeneration.reported / PROGRESS_STEP {
+            pregeneration.reported = percent;
+            pregeneration.progress()
+        } else {
+            return;
+        };
+        info!("{}", message);
+        if let Some(requester) = pregeneration.requester {
+            server.send(requester, ToClient::CommandOutput(message));
+        }
+        if finished {
+            self.current = None;
+        }
+    }
+}
+
+/// Start pregenerating the chunks within `radius` chunks of the spawn, from the bottom to the top of the world
+fn start(requester: Option<PlayerId>, radius: i64, height: WorldHeight) -> Pregeneration {
+    let spawn = BlockPos::from(PhysicsPlayer::default().aabb.pos).containing_chunk_pos();
+    let mut remaining = Vec::new();
+    for dx in -radius..=radius {
+        for dz in -radius..=radius {
+            if dx * dx + dz * dz <= radius * radius {
+                for py in height.min_chunk_y..=height.max_chunk_y {
+                    remaining.push(ChunkPos::from((spawn.px + dx, py, spawn.pz + dz)));
+                }
+            }
+        }
+    }
+    // The chunks near the spawn first, since the players arrive there
+    remaining.sort_by_key(|pos| std::cmp::Reverse((pos.px - spawn.px).pow(2) + (pos.pz - spawn.pz).pow(2)));
+    Pregeneration {
+        requester,
+        total: remaining.len(),
+        remaining,
+        done: 0,
+        reported: 0,
+        start: Instant::now(),
+    }
+}
@@ -0,0 +1,211 @@
+//! The respawn points of the players. A player who activates a respawn pod wakes up on it when they die, instead of
+//! at their home or at the spawn. The administrators can also set the respawn point of a player with `/spawnpoint`.
+//!
+//! The respawn points are checked when the player dies: the floor must still be solid, it must still be a respawn
+//! pod if the point was set by one, and the player must fit above it. The points that are not safe anymore are
+//! forgotten. The points in chunks that are not loaded can't be checked, the teleport then moves the player up until
+//! they don't collide with the blocks.
+
+use crate::dimension::Dimensions;
+use crate::world::World;
+use anyhow::{Context, Result};
+use common::{
+    block::{Block, BlockId},
+    physics::BlockContainer,
+    player::PlayerId,
+    registry::Registry,
+    world::{BlockPos, DimensionId},
+};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// The block that sets the respawn point of the players who activate it
+pub const RESPAWN_POD_BLOCK: &str = "respawn_pod";
+
+/// A respawn point, stored by the name of its player
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RespawnPoint {
+    /// The name of the dimension, since the ids of the dimensions can change
+    dimension: String,
+    /// The block the player wakes up on
+    floor: (i64, i64, i64),
+    /// Whether the point was set by a respawn pod, which must still be there
+    pod: bool,
+}
+
+impl RespawnPoint {
+    fn floor(&self) -> BlockPos {
+        BlockPos::from(self.floor)
+    }
+
+    /// The middle of the bottom of the player who wakes up on the floor
+    fn destination(&self) -> Vector3<f64> {
+        let (x, y, z) = self.floor;
+        Vector3::new(x as f64 + 0.5, y as f64 + 1.0, z as f64 + 0.5)
+    }
+}
+
+pub struct RespawnPoints {
+    path: PathBuf,
+    points: BTreeMap<String, RespawnPoint>,
+    player_names: HashMap<PlayerId, String>,
+    /// The block of the respawn pods, None if the data has none
+    pod_block: Option<BlockId>,
+}
+
+impl RespawnPoints {
+    /// Load the respawn points from the world save, if it has some
+    pub fn load(path: PathBuf, blocks: &Registry<Block>) -> Result<Self> {
+        let points = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read respawn points")?;
+            ron::de::from_str(&buf).context("Failed to parse respawn points")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            points,
+            player_names: HashMap::new(),
+            pod_block: blocks.get_id_by_name(&RESPAWN_POD_BLOCK.to_owned()).map(|id| id as BlockId),
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.points, Default::default())
+            .context("Failed to serialize respawn points")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write respawn points"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name);
+    }
+
+    pub fn is_respawn_pod(&self, block: BlockId) -> bool {
+        self.pod_block == Some(block)
+    }
+
+    /// Set the respawn point of a player on the respawn pod they activated. Return false if their name is not known.
+    pub fn use_pod(
+        &mut self,
+        player: PlayerId,
+        dimension: DimensionId,
+        pod: BlockPos,
+        dimensions: &Dimensions,
+    ) -> bool {
+        let name = match self.player_names.get(&player) {
+            Some(name) => name.clone(),
+            None => return false,
+        };
+        let point = RespawnPoint {
+            dimension: dimensions.name(dimension).to_owned(),
+            floor: (pod.px, pod.py, pod.pz),
+            pod: true,
+        };
+        self.points.insert(name, point);
+        self.save();
+        true
+    }
+
+    /// Check the safety of a respawn point, if its chunks are loaded
+    fn check(&self, point: &RespawnPoint, world: &World) -> Result<(), &'static str> {
+        let floor = point.floor();
+        let blocks = [0, 1, 2].map(|dy| BlockPos::from((floor.px, floor.py + dy, floor.pz)));
+        if !blocks.iter().all(|pos| world.get_chunk(pos.containing_chunk_pos()).is_some()) {
+            return Ok(());
+        }
+        if point.pod && self.pod_block != Some(world.get_block(floor)) {
+            return Err("Your respawn pod was destroyed");
+        }
+        if !world.is_block_full(floor) {
+            return Err("The floor of your respawn point is gone");
+        }
+        if world.is_block_full(blocks[1]) || world.is_block_full(blocks[2]) {
+            return Err("Your respawn point is obstructed");
+        }
+        Ok(())
+    }
+
+    /// Where a player who died wakes up, None if they have no respawn point. The point is forgotten if it is not safe
+    /// anymore or if its dimension doesn't exist anymore, and the reason is returned instead.
+    pub fn respawn_point(
+        &mut self,
+        player: PlayerId,
+        dimensions: &Dimensions,
+    ) -> Result<Option<(DimensionId, Vector3<f64>)>, &'static str> {
+        let name = match self.player_names.get(&player) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let point = match self.points.get(name) {
+            Some(point) => point,
+            None => return Ok(None),
+        };
+        let checked = match dimensions.find(&point.dimension) {
+            Some(dimension) => self.check(point, dimensions.get(dimension)).map(|()| (dimension, point.destination())),
+            None => Err("The dimension of your respawn point doesn't exist anymore"),
+        };
+        if checked.is_err() {
+            self.points.remove(name);
+            self.save();
+        }
+        checked.map(Some)
+    }
+
+    /// Show, set or clear the respawn point of a player with `<player> [here|clear]`. `position` is the dimension and
+    /// the position of the player who runs the command, if they are in the world.
+    pub fn run_command(
+        &mut self,
+        argument: &str,
+        position: Option<(DimensionId, Vector3<f64>)>,
+        dimensions: &Dimensions,
+    ) -> String {
+        // The names can contain spaces
+        let (name, action) = match argument.rsplit_once(' ') {
+            Some((name, action)) if action == "here" || action == "clear" => (name.trim(), Some(action)),
+            _ => (argument, None),
+        };
+        if name.is_empty() {
+            return "Usage: /spawnpoint <player> [here|clear]".to_owned();
+        }
+        match action {
+            None => match self.points.get(name) {
+                Some(point) => {
+                    let (x, y, z) = point.floor;
+                    let kind = if point.pod { "the respawn pod" } else { "the block" };
+                    format!("{} wakes up on {} at {} {} {} in {}", name, kind, x, y, z, point.dimension)
+                }
+                None => format!("{} has no respawn point", name),
+            },
+            Some("clear") => match self.points.remove(name) {
+                Some(_) => {
+                    self.save();
+                    format!("Cleared the respawn point of {}", name)
+                }
+                None => format!("{} has no respawn point", name),
+            },
+            Some(_) => {
+                let (dimension, pos) = match position {
+                    Some(position) => position,
+                    None => return "You are not in the world yet".to_owned(),
+                };
+                let floor = BlockPos::from(pos - Vector3::new(0.0, 0.5, 0.0));
+                let point = RespawnPoint {
+                    dimension: dimensions.name(dimension).to_owned(),
+                    floor: (floor.px, floor.py, floor.pz),
+                    pod: false,
+                };
+                if self.check(&point, dimensions.get(dimension)).is_err() {
+                    return "The respawn point needs a solid floor and room to stand above it".to_owned();
+                }
+                self.points.insert(name.to_owned(), point);
+                self.save();
+                format!("{} will wake up at {} {} {}", name, floor.px, floor.py + 1, floor.pz)
+            }
+        }
+    }
+}
@@ -0,0 +1,538 @@
+//! Mining robots. They are simulated entirely on the server.
+
+use crate::conveyor::Conveyors;
+use crate::dropped_item::DroppedItems;
+use crate::explosion::explosion_strength;
+use crate::world::World;
+use common::{
+    block::BlockId,
+    data::Data,
+    inventory::{Inventory, STORAGE_CRATE_BLOCK, STORAGE_CRATE_SLOTS},
+    item::ItemStack,
+    physics::{aabb::AABB, camera::default_camera, player::PhysicsPlayer},
+    player::{PlayerId, PlayerInput},
+    robot::{area_volume, PauseReason, RobotId, RobotInfo, RobotState, ROBOT_SIDE},
+    world::{BlockPos, ChunkPos},
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Number of slots of a robot's cargo
+const CARGO_SLOTS: usize = 4;
+/// Energy of a fully charged robot
+const MAX_ENERGY: f64 = 100.0;
+/// Energy used to mine one block
+const ENERGY_PER_BLOCK: f64 = 1.0;
+/// Energy used to move one block
+const ENERGY_PER_BLOCK_MOVED: f64 = 0.05;
+/// Energy lost per unit of explosion strength
+const ENERGY_PER_EXPLOSION_STRENGTH: f64 = 10.0;
+/// Energy used per second while the headlights are on
+const HEADLIGHTS_ENERGY_USE: f64 = 0.2;
+/// Energy regained per second by the solar panels
+const SOLAR_RECHARGE_RATE: f64 = 0.5;
+/// Movement speed in blocks per second
+const SPEED: f64 = 4.0;
+/// Maximum distance at which a robot can mine or reach its crate
+const REACH: f64 = 1.5;
+/// Seconds needed to mine one block
+const MINING_TIME: f64 = 0.5;
+/// Maximum number of positions scanned per robot per tick when looking for the next block to mine
+const MAX_SCANNED_BLOCKS: usize = 512;
+
+/// A rectangular region that is excavated layer by layer, from top to bottom
+#[derive(Debug, Clone, Copy)]
+pub struct MiningArea {
+    min: BlockPos,
+    max: BlockPos,
+    /// The next position to scan
+    cursor: BlockPos,
+    finished: bool,
+}
+
+impl MiningArea {
+    /// Create the area between two corners, both included
+    pub fn new(a: BlockPos, b: BlockPos) -> Self {
+        let min = BlockPos::from((a.px.min(b.px), a.py.min(b.py), a.pz.min(b.pz)));
+        let max = BlockPos::from((a.px.max(b.px), a.py.max(b.py), a.pz.max(b.pz)));
+        Self {
+            min,
+            max,
+            cursor: BlockPos::from((min.px, max.py, min.pz)),
+            finished: false,
+        }
+    }
+
+    /// Number of blocks in the area, or None if it is too large to be counted
+    pub fn volume(&self) -> Option<i64> {
+        area_volume(self.min, self.max)
+    }
+
+    /// Move the cursor to the next position. Layers are scanned from the top.
+    fn advance(&mut self) {
+        self.cursor.px += 1;
+        if self.cursor.px > self.max.px {
+            self.cursor.px = self.min.px;
+            self.cursor.pz += 1;
+            if self.cursor.pz > self.max.pz {
+                self.cursor.pz = self.min.pz;
+                self.cursor.py -= 1;
+                if self.cursor.py < self.min.py {
+                    self.finished = true;
+                }
+            }
+        }
+    }
+
+    /// Look for the next block to mine
+    fn next_block(&mut self, world: &World, storage_block: Option<BlockId>) -> ScanResult {
+        for _ in 0..MAX_SCANNED_BLOCKS {
+            if self.finished {
+                return ScanResult::Finished;
+            }
+            let pos = self.cursor;
+            if world.get_chunk(pos.containing_chunk_pos()).is_none() {
+                return ScanResult::Pending;
+            }
+            self.advance();
+            let block = world.get_block(pos);
+            if block != 0 && Some(block) != storage_block {
+                return ScanResult::Found(pos);
+            }
+        }
+        ScanResult::Pending
+    }
+
+    /// The storage crate positions that are adjacent to the area, on its top layer
+    fn adjacent_positions(&self) -> impl Iterator<Item = BlockPos> + '_ {
+        let y = self.max.py;
+        let along_x = (self.min.px - 1..=self.max.px + 1)
+            .flat_map(move |x| vec![(x, y, self.min.pz - 1), (x, y, self.max.pz + 1)]);
+        let along_z = (self.min.pz..=self.max.pz)
+            .flat_map(move |z| vec![(self.min.px - 1, y, z), (self.max.px + 1, y, z)]);
+        along_x.chain(along_z).map(BlockPos::from)
+    }
+}
+
+enum ScanResult {
+    Found(BlockPos),
+    /// More blocks need to be scanned or loaded
+    Pending,
+    Finished,
+}
+
+/// A mining robot
+#[derive(Debug, Clone)]
+pub struct Robot {
+    pub owner: PlayerId,
+    pub pos: Vector3<f64>,
+    pub energy: f64,
+    pub cargo: Inventory,
+    pub state: RobotState,
+    /// The player controlling the robot, and their last input
+    pub controller: Option<(PlayerId, PlayerInput)>,
+    /// Direction the robot faces, in degrees
+    pub yaw: f64,
+    pub headlights: bool,
+    velocity: Vector3<f64>,
+    area: Option<MiningArea>,
+    /// The block currently being mined
+    target: Option<BlockPos>,
+    /// The storage crate the cargo is deposited into
+    storage: Option<BlockPos>,
+}
+
+impl Robot {
+    fn new(owner: PlayerId, pos: Vector3<f64>) -> Self {
+        Self {
+            owner,
+            pos,
+            energy: MAX_ENERGY,
+            cargo: Inventory::new(CARGO_SLOTS),
+            state: RobotState::Idle,
+            controller: None,
+            yaw: 0.0,
+            headlights: false,
+            velocity: Vector3::zeros(),
+            area: None,
+            target: None,
+            storage: None,
+        }
+    }
+
+    /// Move towards the center of a block. Return true once it is in reach.
+    fn move_towards(&mut self, pos: BlockPos, dt: f64) -> bool {
+        let target = Vector3::new(pos.px as f64 + 0.5, pos.py as f64 + 0.5, pos.pz as f64 + 0.5);
+        let delta = target - self.pos;
+        let distance = delta.norm();
+        if distance <= REACH {
+            return true;
+        }
+        let step = f64::min(SPEED * dt, distance - REACH);
+        self.pos += delta * (step / distance);
+        if delta.x != 0.0 || delta.z != 0.0 {
+            self.yaw = f64::atan2(-delta.x, -delta.z).to_degrees();
+        }
+        self.energy -= step * ENERGY_PER_BLOCK_MOVED;
+        false
+    }
+
+    /// The box of the robot, centered on its position
+    pub fn aabb(&self) -> AABB {
+        let half_side = Vector3::new(ROBOT_SIDE, ROBOT_SIDE, ROBOT_SIDE) / 2.0;
+        AABB::new(self.pos - half_side, (ROBOT_SIDE, ROBOT_SIDE, ROBOT_SIDE))
+    }
+
+    /// Move according to the input of the controlling player
+    fn move_controlled(&mut self, input: PlayerInput, dt: f64, world: &World) {
+        let half_side = Vector3::new(ROBOT_SIDE, ROBOT_SIDE, ROBOT_SIDE) / 2.0;
+        let mut body = PhysicsPlayer {
+            aabb: self.aabb(),
+            velocity: self.velocity,
+            knockback: Vector3::zeros(),
+            speed_multiplier: 1.0,
+        };
+        // Robots hover
+        let input = PlayerInput { flying: true, ..input };
+        default_camera(&mut body, input, dt, world);
+        let new_pos = body.aabb.pos + half_side;
+        self.energy -= (new_pos - self.pos).norm() * ENERGY_PER_BLOCK_MOVED;
+        self.pos = new_pos;
+        self.velocity = body.velocity;
+        self.yaw = input.yaw;
+    }
+
+    /// Move the cargo to the storage crate, or drop it on the conveyor. Return true if everything fit.
+    fn deposit(&mut self, world: &mut World, conveyors: &Conveyors, dropped_items: &mut DroppedItems) -> bool {
+        let storage = match self.storage {
+            Some(storage) if conveyors.get_facing(world.get_block(storage)).is_some() => {
+                let top = Vector3::new(storage.px as f64 + 0.5, storage.py as f64 + 1.0, storage.pz as f64 + 0.5);
+                for stack in self.cargo.take_all() {
+                    dropped_items.spawn(top, stack);
+                }
+                return true;
+            }
+            Some(storage) => world.get_container_mut(storage, STORAGE_CRATE_SLOTS),
+            None => return false,
+        };
+        let mut all_fit = true;
+        for stack in self.cargo.take_all() {
+            if let Some(rest) = storage.insert(stack) {
+                all_fit = false;
+                self.cargo.insert(rest);
+            }
+        }
+        all_fit
+    }
+}
+
+/// All the robots in the world
+pub struct Robots {
+    robots: HashMap<RobotId, Robot>,
+    next_id: u32,
+    /// The storage crate block, if it exists
+    storage_block: Option<BlockId>,
+    /// The items dropped by each block
+    block_items: Vec<Option<ItemStack>>,
+}
+
+impl Robots {
+    pub fn new(game_data: &Data) -> Self {
+        Self {
+            robots: HashMap::new(),
+            next_id: 0,
+            storage_block: game_data
+                .blocks
+                .get_id_by_name(&STORAGE_CRATE_BLOCK.to_owned())
+                .map(|id| id as BlockId),
+            block_items: game_data.block_items.clone(),
+        }
+    }
+
+    /// The storage crate block, if it exists
+    pub fn get_storage_block(&self) -> Option<BlockId> {
+        self.storage_block
+    }
+
+    /// Create a new robot
+    pub fn spawn(&mut self, owner: PlayerId, pos: Vector3<f64>) -> RobotId {
+        let id = RobotId(self.next_id);
+        self.next_id += 1;
+        self.robots.insert(id, Robot::new(owner, pos));
+        id
+    }
+
+    pub fn get(&self, id: RobotId) -> Option<&Robot> {
+        self.robots.get(&id)
+    }
+
+    /// Let a player control their robot. Return false if the robot doesn't belong to the player or is already controlled.
+    pub fn take_control(&mut self, id: RobotId, player: PlayerId) -> bool {
+        match self.robots.get_mut(&id) {
+            Some(robot) if robot.owner == player && robot.controller.is_none() => {
+                robot.controller = Some((player, PlayerInput::default().without_movement()));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Give the robot back to its automatic behavior
+    pub fn release_control(&mut self, id: RobotId) {
+        if let Some(robot) = self.robots.get_mut(&id) {
+            robot.controller = None;
+            robot.velocity = Vector3::zeros();
+        }
+    }
+
+    /// Move a robot instantly, and stop it
+    pub fn teleport(&mut self, id: RobotId, pos: Vector3<f64>) {
+        if let Some(robot) = self.robots.get_mut(&id) {
+            robot.pos = pos;
+            robot.velocity = Vector3::zeros();
+        }
+    }
+
+    /// Update the input of the player controlling a robot
+    pub fn set_control_input(&mut self, id: RobotId, input: PlayerInput) {
+        if let Some(robot) = self.robots.get_mut(&id) {
+            if let Some((_, robot_input)) = robot.controller.as_mut() {
+                *robot_input = input;
+            }
+        }
+    }
+
+    /// Turn the headlights of a robot on or off, and return whether they are on, or why they can't be turned on
+    pub fn toggle_headlights(&mut self, id: RobotId) -> Result<bool, &'static str> {
+        let robot = self.robots.get_mut(&id).ok_or("You have no robot")?;
+        if !robot.headlights && robot.energy <= 0.0 {
+            return Err("Your robot has no energy left");
+        }
+        robot.headlights = !robot.headlights;
+        Ok(robot.headlights)
+    }
+
+    /// The state of all the robots, to be sent to the clients
+    pub fn get_infos(&self) -> Vec<RobotInfo> {
+        self.robots
+            .iter()
+            .map(|(id, robot)| RobotInfo {
+                id: *id,
+                owner: robot.owner,
+                pos: robot.pos,
+                state: robot.state,
+                energy: robot.energy,
+                controller: robot.controller.map(|(player, _)| player),
+                yaw: robot.yaw,
+                headlights: robot.headlights,
+            })
+            .collect()
+    }
+
+    /// The blocks being mined by the robots, with the progress of the mining from 0 to 1
+    pub fn mining_progress(&self) -> impl Iterator<Item = (BlockPos, f64)> + '_ {
+        // The controlled robots stop mining
+        self.robots.values().filter_map(|robot| match (robot.state, robot.target) {
+            (RobotState::Mining { progress }, Some(target)) if robot.controller.is_none() => Some((target, progress)),
+            _ => None,
+        })
+    }
+
+    /// Assign a mining area to a robot. The cargo will be deposited in a storage crate or on a conveyor adjacent to the area.
+    pub fn assign_area(&mut self, id: RobotId, area: MiningArea, world: &World, conveyors: &Conveyors) {
+        let storage_block = self.storage_block;
+        if let Some(robot) = self.robots.get_mut(&id) {
+            robot.storage = area
+                .adjacent_positions()
+                .find(|pos| is_storage(world.get_block(*pos), storage_block, conveyors));
+            robot.area = Some(area);
+            robot.target = None;
+            robot.state = match robot.storage {
+                Some(_) => RobotState::Mining { progress: 0.0 },
+                None => RobotState::Paused(PauseReason::NoStorage),
+            };
+        }
+    }
+
+    /// Positions of the chunks robots are working in, so that they can be kept loaded
+    pub fn active_chunks(&self) -> Vec<ChunkPos> {
+        self.robots
+            .values()
+            .filter(|robot| robot.state != RobotState::Idle && robot.controller.is_none())
+            .map(|robot| robot.target.map(|t| t.containing_chunk_pos()).unwrap_or_else(|| BlockPos::from(robot.pos).containing_chunk_pos()))
+            .collect()
+    }
+
+    /// Update all the robots
+    pub fn tick(&mut self, world: &mut World, conveyors: &Conveyors, dropped_items: &mut DroppedItems, dt: f64) {
+
+        for robot in self.robots.values_mut() {
+            Self::tick_robot(robot, world, dt, self.storage_block, &self.block_items, conveyors, dropped_items);
+        }
+    }
+
+    fn tick_robot(
+        robot: &mut Robot,
+        world: &mut World,
+        dt: f64,
+        storage_block: Option<BlockId>,
+        block_items: &[Option<ItemStack>],
+        conveyors: &Conveyors,
+        dropped_items: &mut DroppedItems,
+    ) {
+        // The storage might have been destroyed
+        if let Some(storage) = robot.storage {
+            if !is_storage(world.get_block(storage), storage_block, conveyors) && world.get_chunk(storage.containing_chunk_pos()).is_some() {
+                robot.storage = None;
+                robot.state = RobotState::Paused(PauseReason::NoStorage);
+            }
+        }
+
+        if robot.headlights {
+            robot.energy = f64::max(robot.energy - HEADLIGHTS_ENERGY_USE * dt, 0.0);
+            robot.headlights = robot.energy > 0.0;
+        }
+
+        if let Some((_, input)) = robot.controller {
+            // The solar panels keep working while a player drives the robot, so it can't stay stuck without energy
+            robot.energy = f64::min(robot.energy + SOLAR_RECHARGE_RATE * dt, MAX_ENERGY);
+            if robot.energy > 0.0 {
+                robot.move_controlled(input, dt, world);
+            }
+            return;
+        }
+
+        match robot.state {
+            RobotState::Idle => {}
+            RobotState::Paused(PauseReason::OutOfEnergy) => {
+                robot.energy = f64::min(robot.energy + SOLAR_RECHARGE_RATE * dt, MAX_ENERGY);
+                if robot.energy >= MAX_ENERGY {
+                    robot.state = RobotState::Mining { progress: 0.0 };
+                }
+            }
+            RobotState::Paused(PauseReason::StorageFull) => {
+                if robot.deposit(world, conveyors, dropped_items) {
+                    robot.state = RobotState::Mining { progress: 0.0 };
+                }
+            }
+            RobotState::Paused(PauseReason::NoStorage) => {
+                // Wait for the player to place a crate and reassign the area
+            }
+            RobotState::Returning => {
+                let storage = robot.storage.expect("Returning robot has no storage");
+                if robot.move_towards(storage, dt) {
+                    robot.state = if !robot.deposit(world, conveyors, dropped_items) {
+                        RobotState::Paused(PauseReason::StorageFull)
+                    } else if robot.area.is_some() {
+                        RobotState::Mining { progress: 0.0 }
+                    } else {
+                        RobotState::Idle
+                    };
+                }
+            }
+            RobotState::Mining { progress } => {
+                if robot.energy <= 0.0 {
+                    robot.state = RobotState::Paused(PauseReason::OutOfEnergy);
+                    return;
+                }
+                let target = match robot.target {
+                    Some(target) => target,
+                    None => match robot.area.as_mut().map(|area| area.next_block(world, storage_block)) {
+                        Some(ScanResult::Found(pos)) => {
+                            robot.target = Some(pos);
+                            pos
+                        }
+                        Some(ScanResult::Pending) => return,
+                        Some(ScanResult::Finished) | None => {
+                            robot.area = None;
+                            robot.state = if robot.cargo.is_empty() {
+                                RobotState::Idle
+                            } else {
+                                RobotState::Returning
+                            };
+                            return;
+                        }
+                    },
+                };
+
+                let block = world.get_block(target);
+                if block == 0 {
+                    // Somebody else mined it
+                    robot.target = None;
+                    return;
+                }
+                let drop = block_items.get(block as usize).cloned().flatten();
+                if let Some(drop) = &drop {
+                    if !robot.cargo.can_insert(drop) {
+                        robot.state = RobotState::Returning;
+                        return;
+                    }
+                }
+
+                if robot.move_towards(target, dt) {
+                    let progress = progress + dt / MINING_TIME;
+                    if progress >= 1.0 {
+                        if world.set_block(target, 0) {
+                            if let Some(drop) = drop {
+                                robot.cargo.insert(drop);
+                            }
+                            robot.energy -= ENERGY_PER_BLOCK;
+                            robot.target = None;
+                        }
+                        robot.state = RobotState::Mining { progress: 0.0 };
+                    } else {
+                        robot.state = RobotState::Mining { progress };
+                    }
+                }
+            }
+        }
+    }
+
+    /// Damage the robots caught in an explosion. Robots have no health: the blast drains their battery.
+    pub fn apply_explosion(&mut self, center: Vector3<f64>, power: f64) {
+        for robot in self.robots.values_mut() {
+            let strength = explosion_strength(center, power, robot.pos);
+            robot.energy = f64::max(robot.energy - strength * ENERGY_PER_EXPLOSION_STRENGTH, 0.0);
+        }
+    }
+
+    /// Damage a robot that was hit. Like the explosions, the hits drain the battery, and a robot knocked out by a hit
+    /// drops its cargo. Return whether the robot exists.
+    pub fn apply_hit(&mut self, id: RobotId, damage: f64, dropped_items: &mut DroppedItems) -> bool {
+        match self.robots.get_mut(&id) {
+            Some(robot) => {
+                let was_running = robot.energy > 0.0;
+                robot.energy = f64::max(robot.energy - damage, 0.0);
+                if was_running && robot.energy == 0.0 {
+                    for stack in robot.cargo.take_all() {
+                        dropped_items.spawn(robot.pos, stack);
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Text summary of the robots for the debug overlay
+    pub fn debug_info(&self) -> String {
+        let mut info = String::new();
+        for (id, robot) in self.robots.iter() {
+            info.push_str(&format!(
+                "Robot {} of {:?}: {:?}, energy = {:.1}, cargo = {}/{}\n",
+                id.0,
+                robot.owner,
+                robot.state,
+                robot.energy,
+                robot.cargo.slots().iter().filter(|slot| slot.is_some()).count(),
+                robot.cargo.size(),
+            ));
+        }
+        info
+    }
+}
+
+/// Return true if robots can deposit their cargo into or onto the block
+fn is_storage(block: BlockId, storage_block: Option<BlockId>, conveyors: &Conveyors) -> bool {
+    Some(block) == storage_block || conveyors.get_facing(block).is_some()
+}
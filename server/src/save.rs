@@ -0,0 +1,211 @@
+//! The format of the world saves. The version of the format is stored in `meta.ron` with the save, and the saves of
+//! the older versions are upgraded when they are loaded by running the migrations from their version, one after the
+//! other. The save is copied next to it before the first migration, and the version is written after each migration,
+//! so an upgrade that fails can be resumed or the backup restored.
+
+use crate::storage::{list_chunks, stored_chunk_path, CHUNKS_FOLDER};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use common::world::WorldHeight;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The version of the saves written by this server
+pub const SAVE_FORMAT_VERSION: u32 = 2;
+const META_FILE: &str = "meta.ron";
+/// Width of the progress bar of the migrations, in characters
+const PROGRESS_BAR_WIDTH: usize = 30;
+/// The progress is logged each time this percentage of a migration is done
+const PROGRESS_STEP: usize = 5;
+
+/// The description of a save, written by the server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WorldMeta {
+    pub format_version: u32,
+    /// The vertical bounds of each dimension, by name. The dimensions that are missing get their default bounds
+    /// when the server starts.
+    pub heights: BTreeMap<String, WorldHeight>,
+}
+
+impl Default for WorldMeta {
+    fn default() -> Self {
+        Self {
+            format_version: SAVE_FORMAT_VERSION,
+            heights: BTreeMap::new(),
+        }
+    }
+}
+
+/// A step upgrading the saves of a version to the next version
+struct Migration {
+    from_version: u32,
+    description: &'static str,
+    run: fn(&Path, &mut MigrationProgress) -> Result<()>,
+}
+
+/// The migrations, in order
+const MIGRATIONS: &[Migration] = &[Migration {
+    from_version: 1,
+    description: "Moving the stored chunks into region folders",
+    run: move_chunks_into_regions,
+}];
+
+/// Load the description of a save, upgrading the save to the current version first if it is older. A new save is
+/// created with the current version.
+pub fn load_world_meta(save_folder: &Path) -> Result<WorldMeta> {
+    let path = save_folder.join(META_FILE);
+    let mut meta = if path.is_file() {
+        let buf = std::fs::read_to_string(&path).context("Failed to read world meta")?;
+        ron::de::from_str(&buf).context("Failed to parse world meta")?
+    } else if is_empty_folder(save_folder) {
+        info!("Creating a new save in {}", save_folder.display());
+        std::fs::create_dir_all(save_folder).context("Failed to create the save folder")?;
+        WorldMeta::default()
+    } else {
+        // The saves written before the versions didn't have a meta file
+        WorldMeta {
+            format_version: 1,
+            ..Default::default()
+        }
+    };
+    if meta.format_version > SAVE_FORMAT_VERSION {
+        return Err(anyhow!(
+            "The save was written by a newer version of the server, with format {} instead of {}",
+            meta.format_version,
+            SAVE_FORMAT_VERSION
+        ));
+    }
+    if meta.format_version < SAVE_FORMAT_VERSION {
+        info!("Upgrading the save from format {} to {}", meta.format_version, SAVE_FORMAT_VERSION);
+        back_up(save_folder, meta.format_version)?;
+    }
+    let saved_version = meta.format_version;
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from_version >= saved_version) {
+        let mut progress = MigrationProgress::new(migration.description);
+        (migration.run)(save_folder, &mut progress)
+            .with_context(|| format!("Failed to upgrade the save from format {}", migration.from_version))?;
+        progress.finish();
+        meta.format_version = migration.from_version + 1;
+        write_world_meta(save_folder, &meta)?;
+    }
+    write_world_meta(save_folder, &meta)?;
+    Ok(meta)
+}
+
+/// Write the description of a save, for example after the dimensions added their default bounds
+pub fn write_world_meta(save_folder: &Path, meta: &WorldMeta) -> Result<()> {
+    let string = ron::ser::to_string_pretty(meta, Default::default()).context("Failed to serialize world meta")?;
+    std::fs::write(save_folder.join(META_FILE), string).context("Failed to write world meta")
+}
+
+fn is_empty_folder(folder: &Path) -> bool {
+    std::fs::read_dir(folder).map_or(true, |mut entries| entries.next().is_none())
+}
+
+/// The folder of the backup of a save made before upgrading it from a version, next to the save
+fn backup_folder(save_folder: &Path, version: u32) -> PathBuf {
+    let name = save_folder.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    save_folder.with_file_name(format!("{}.backup-v{}", name, version))
+}
+
+/// Copy the save before upgrading it. An existing backup is kept: it is the save before an upgrade that failed.
+fn back_up(save_folder: &Path, version: u32) -> Result<()> {
+    let backup = backup_folder(save_folder, version);
+    if backup.exists() {
+        info!("Keeping the existing backup {}", backup.display());
+        return Ok(());
+    }
+    info!("Backing up the save to {}", backup.display());
+    // The save is copied to a temporary folder first, so that a partial copy is never taken for a backup
+    let temporary = backup.with_extension("tmp");
+    let _ = std::fs::remove_dir_all(&temporary);
+    copy_folder(save_folder, &temporary).context("Failed to back up the save, it was not upgraded")?;
+    std::fs::rename(&temporary, &backup).context("Failed to back up the save, it was not upgraded")
+}
+
+fn copy_folder(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+    for entry in std::fs::read_dir(from).with_context(|| format!("Failed to list {}", from.display()))? {
+        let path = entry?.path();
+        let destination = to.join(path.file_name().expect("the entries have a name"));
+        if path.is_dir() {
+            copy_folder(&path, &destination)?;
+        } else {
+            std::fs::copy(&path, &destination).with_context(|| format!("Failed to copy {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// The progress of a migration, logged as a progress bar
+pub struct MigrationProgress {
+    description: &'static str,
+    total: usize,
+    done: usize,
+    /// The percentage that was logged last
+    reported: usize,
+}
+
+impl MigrationProgress {
+    fn new(description: &'static str) -> Self {
+        info!("{}", description);
+        Self {
+            description,
+            total: 0,
+            done: 0,
+            reported: 0,
+        }
+    }
+
+    /// Set the number of steps of the migration
+    pub fn set_total(&mut self, total: usize) {
+        self.total = total;
+    }
+
+    /// Count a step as done
+    pub fn advance(&mut self) {
+        self.done += 1;
+        let percent = self.done * 100 / self.total.max(1);
+        if percent / PROGRESS_STEP > self.reported / PROGRESS_STEP {
+            self.reported = percent;
+            self.log(percent);
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.reported < 100 {
+            self.log(100);
+        }
+    }
+
+    fn log(&self, percent: usize) {
+        let filled = percent.min(100) * PROGRESS_BAR_WIDTH / 100;
+        let bar = format!("{}{}", "#".repeat(filled), " ".repeat(PROGRESS_BAR_WIDTH - filled));
+        info!("{} [{}] {}% ({}/{})", self.description, bar, percent, self.done, self.total);
+    }
+}
+
+/// Version 1 to 2: the chunks of the dimensions were all in the folder of their dimension
+fn move_chunks_into_regions(save_folder: &Path, progress: &mut MigrationProgress) -> Result<()> {
+    let chunks_folder = save_folder.join(CHUNKS_FOLDER);
+    if !chunks_folder.is_dir() {
+        return Ok(());
+    }
+    let mut chunks = Vec::new();
+    let dimensions =
+        std::fs::read_dir(&chunks_folder).with_context(|| format!("Failed to list {}", chunks_folder.display()))?;
+    for dimension in dimensions.flatten().filter(|entry| entry.path().is_dir()) {
+        let folder = dimension.path();
+        chunks.extend(list_chunks(&folder)?.into_iter().map(|(pos, path)| (stored_chunk_path(&folder, pos), path)));
+    }
+    progress.set_total(chunks.len());
+    for (destination, path) in chunks {
+        let region = destination.parent().expect("the chunks are in a region folder");
+        std::fs::create_dir_all(region).with_context(|| format!("Failed to create {}", region.display()))?;
+        std::fs::rename(&path, &destination).with_context(|| format!("Failed to move {}", path.display()))?;
+        progress.advance();
+    }
+    Ok(())
+}
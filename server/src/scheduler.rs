@@ -0,0 +1,148 @@
+//! Scheduled tasks: something to do in some ticks, or every some ticks.
+//!
+//! The tasks are data rather than closures, so that the pending one-shot tasks can be saved with the world and still
+//! run after a restart, such as the cycle of an airlock or the timer of a mod. The repeating tasks are not saved: they
+//! are scheduled again when the server starts.
+
+use anyhow::{Context, Result};
+use common::time::TICK_DURATION;
+use common::world::{BlockPos, DimensionId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Something to do at a given tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Task {
+    /// Save what is saved periodically
+    Autosave,
+    /// Open an airlock door once the airlock has cycled. The dimension is missing from the older saves.
+    OpenAirlock(BlockPos, #[serde(default)] DimensionId),
+    /// Grow a crop to its next stage, if it is in a lit greenhouse
+    GrowCrop(BlockPos),
+    /// Call the `on_timer` function of a mod
+    ModTimer { mod_name: String, timer: i32 },
+}
+
+struct ScheduledTask {
+    task: Task,
+    /// The number of ticks between two runs, None if the task only runs once
+    interval: Option<u64>,
+}
+
+/// A pending one-shot task, as saved with the world
+#[derive(Serialize, Deserialize)]
+struct SavedTask {
+    remaining_ticks: u64,
+    task: Task,
+}
+
+pub struct Scheduler {
+    path: PathBuf,
+    /// The number of ticks since the server started
+    tick: u64,
+    /// The tasks by tick, then by order of scheduling
+    tasks: BTreeMap<(u64, u64), ScheduledTask>,
+    next_order: u64,
+}
+
+impl Scheduler {
+    /// Load the pending tasks from the world save, if it has some
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let saved_tasks: Vec<SavedTask> = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read scheduled tasks")?;
+            ron::de::from_str(&buf).context("Failed to parse scheduled tasks")?
+        } else {
+            Vec::new()
+        };
+        let mut scheduler = Self {
+            path,
+            tick: 0,
+            tasks: BTreeMap::new(),
+            next_order: 0,
+        };
+        for saved_task in saved_tasks {
+            scheduler.run_in_ticks(saved_task.remaining_ticks, saved_task.task);
+        }
+        Ok(scheduler)
+    }
+
+    /// Save the pending one-shot tasks
+    pub fn save(&self) {
+        let saved_tasks = self
+            .tasks
+            .iter()
+            .filter(|(_, scheduled)| scheduled.interval.is_none())
+            .map(|(&(tick, _), scheduled)| SavedTask {
+                remaining_ticks: tick - self.tick,
+                task: scheduled.task.clone(),
+            })
+            .collect::<Vec<_>>();
+        let result = ron::ser::to_string_pretty(&saved_tasks, Default::default())
+            .context("Failed to serialize scheduled tasks")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write scheduled tasks"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    fn schedule(&mut self, ticks: u64, task: Task, interval: Option<u64>) {
+        // A task never runs during the tick that scheduled it
+        let key = (self.tick + ticks.max(1), self.next_order);
+        self.next_order += 1;
+        self.tasks.insert(key, ScheduledTask { task, interval });
+    }
+
+    /// Run a task once, in `ticks` ticks
+    pub fn run_in_ticks(&mut self, ticks: u64, task: Task) {
+        self.schedule(ticks, task, None);
+    }
+
+    /// Run a task every `ticks` ticks, starting in `ticks` ticks
+    pub fn run_every(&mut self, ticks: u64, task: Task) {
+        let interval = ticks.max(1);
+        self.schedule(interval, task, Some(interval));
+    }
+
+    /// Whether a task is scheduled
+    pub fn is_scheduled(&self, task: &Task) -> bool {
+        self.tasks.values().any(|scheduled| scheduled.task == *task)
+    }
+
+    /// Cancel every scheduled run of a task
+    pub fn cancel(&mut self, task: &Task) {
+        self.tasks.retain(|_, scheduled| scheduled.task != *task);
+    }
+
+    /// Advance by one tick, and return the tasks to run during this tick in the order they were scheduled
+    pub fn tick(&mut self) -> Vec<Task> {
+        self.fast_forward(1)
+    }
+
+    /// Skip `ticks` ticks at once, and return the tasks that came due in the meantime. The repeating tasks are only
+    /// returned once, and run again at their usual interval after the skip.
+    pub fn fast_forward(&mut self, ticks: u64) -> Vec<Task> {
+        self.tick += ticks;
+        let mut due = Vec::new();
+        while let Some(entry) = self.tasks.first_entry() {
+            if entry.key().0 > self.tick {
+                break;
+            }
+            let scheduled = entry.remove();
+            if let Some(interval) = scheduled.interval {
+                self.schedule(interval, scheduled.task.clone(), Some(interval));
+            }
+            due.push(scheduled.task);
+        }
+        due
+    }
+
+    pub fn debug_info(&self) -> String {
+        format!("Tick {}, {} scheduled tasks", self.tick, self.tasks.len())
+    }
+}
+
+/// The number of ticks in a duration, at least one
+pub fn seconds_to_ticks(seconds: f64) -> u64 {
+    ((seconds / TICK_DURATION.as_secs_f64()).round() as u64).max(1)
+}
@@ -0,0 +1,429 @@
+use anyhow::{Context, Result};
+use common::registry::IdMap;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    path::Path,
+};
+
+/// Load the settings of the world, creating the default settings if the file doesn't exist
+pub fn load_world_settings(folder_path: &Path, file_path: &Path) -> Result<WorldSettings> {
+    info!("Loading world settings from {:?}", file_path.display());
+    let settings = if file_path.is_file() {
+        let mut settings_file = OpenOptions::new()
+            .read(true)
+            .open(file_path)
+            .context("Failed to open world settings file")?;
+        let mut buf = String::new();
+        settings_file.read_to_string(&mut buf).context("Failed to read world settings")?;
+        ron::de::from_str(&buf).context("Failed to parse world settings")?
+    } else {
+        std::fs::create_dir_all(folder_path)?;
+        let settings = WorldSettings::default();
+        write_world_settings(file_path, &settings)?;
+        settings
+    };
+    Ok(settings)
+}
+
+fn write_world_settings(path: impl AsRef<Path>, settings: &WorldSettings) -> Result<()> {
+    info!("Writing world settings to {:?}", path.as_ref());
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(path)
+        .context("Failed to open world settings file")?;
+    let string = ron::ser::to_string_pretty(settings, Default::default()).context("Failed to serialize world settings")?;
+    file.write_all(string.as_bytes())
+        .context("Failed to write world settings to file")?;
+    Ok(())
+}
+
+/// Load the block id map stored with the world, if the world was already saved
+pub fn load_block_id_map(file_path: &Path) -> Result<Option<IdMap>> {
+    if !file_path.is_file() {
+        return Ok(None);
+    }
+    let buf = std::fs::read_to_string(file_path).context("Failed to read block id map")?;
+    Ok(Some(ron::de::from_str(&buf).context("Failed to parse block id map")?))
+}
+
+pub fn write_block_id_map(file_path: &Path, id_map: &IdMap) -> Result<()> {
+    let string = ron::ser::to_string_pretty(id_map, Default::default()).context("Failed to serialize block id map")?;
+    std::fs::write(file_path, string).context("Failed to write block id map")?;
+    Ok(())
+}
+
+/// The settings of a world, stored with its save
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WorldSettings {
+    /// Speed of the game relative to the real time
+    pub time_scale: f64,
+    /// Development mode: reload the data when the files change, and send it to the players
+    pub hot_reload_data: bool,
+    pub meteors: MeteorSettings,
+    /// The remote administration listener, disabled if None
+    pub admin: Option<AdminSettings>,
+    /// The listener answering the status requests of the server lists, disabled if None
+    pub status: Option<StatusSettings>,
+    pub anti_cheat: AntiCheatSettings,
+    /// The recorder of the block changes, for the `/history` and `/timelapse` commands, disabled if None
+    pub history: Option<HistorySettings>,
+    /// Number of threads generating the chunks of each dimension
+    pub worldgen_threads: usize,
+    pub storage: StorageSettings,
+    /// Memory of the loaded chunks of all the dimensions, in megabytes, above which the chunks that no player sees
+    /// anymore are unloaded
+    pub chunk_memory_mb: usize,
+    pub survival: SurvivalSettings,
+    pub mobs: MobSettings,
+    pub combat: CombatSettings,
+    pub effects: EffectSettings,
+    pub dust_storms: DustStormSettings,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            time_scale: 1.0,
+            hot_reload_data: false,
+            meteors: MeteorSettings::default(),
+            admin: None,
+            status: None,
+            anti_cheat: AntiCheatSettings::default(),
+            history: None,
+            worldgen_threads: 2,
+            storage: StorageSettings::default(),
+            chunk_memory_mb: 512,
+            survival: SurvivalSettings::default(),
+            mobs: MobSettings::default(),
+            combat: CombatSettings::default(),
+            effects: EffectSettings::default(),
+            dust_storms: DustStormSettings::default(),
+        }
+    }
+}
+
+/// The limits checked by the anti-cheat
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AntiCheatSettings {
+    pub enabled: bool,
+    /// Maximum distance between the position sent with an action and the position of the player on the server
+    pub max_position_error: f64,
+    /// Maximum distance between the eyes of the player and the center of the blocks they break or place
+    pub max_reach: f64,
+    pub max_breaks_per_second: f64,
+    /// Maximum speed of the players, in blocks per second
+    pub max_speed: f64,
+    /// Number of violations forgiven every second
+    pub violation_decay: f64,
+    /// Number of violations that gets a player kicked
+    pub kick_threshold: f64,
+}
+
+impl Default for AntiCheatSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_position_error: 4.0,
+            max_reach: 11.0,
+            max_breaks_per_second: 10.0,
+            max_speed: 50.0,
+            violation_decay: 0.5,
+            kick_threshold: 20.0,
+        }
+    }
+}
+
+/// The settings of the storage of the chunks in the save
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct StorageSettings {
+    pub fsync: FsyncPolicy,
+}
+
+/// When the chunks written to the save are flushed to the disk. The chunks that are not flushed yet can be lost if
+/// the machine stops, but not if only the server stops.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    /// Let the operating system flush the files, the fastest
+    Never,
+    /// Flush the written chunks each time no chunk is left to write
+    #[default]
+    Batch,
+    /// Flush each chunk before writing the next one, the safest
+    Always,
+}
+
+/// The settings of the block change recorder
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HistorySettings {
+    /// Seconds of game time after which the changes are forgotten
+    pub retention: f64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self { retention: 6.0 * 3600.0 }
+    }
+}
+
+/// The settings of the status listener
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatusSettings {
+    /// The address and port to listen on, such as `0.0.0.0:25567`
+    pub address: String,
+    /// Message of the day, shown in the server lists
+    pub motd: String,
+    /// The maximum number of players shown in the server lists
+    pub max_players: usize,
+}
+
+/// The settings of the remote administration listener
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminSettings {
+    /// The address and port to listen on, such as `127.0.0.1:25566`
+    pub address: String,
+    /// The token that the admin tools must send before any command
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MeteorSettings {
+    pub enabled: bool,
+    /// Average number of seconds between two meteors
+    pub mean_interval: f64,
+    /// Seconds between the warning and the impact
+    pub warning_time: f64,
+    /// Power of the explosion that carves the crater
+    pub power: f64,
+    /// The block deposited at the center of the crater
+    pub ore_block: String,
+    /// Maximum horizontal distance between the impact and the targeted player
+    pub max_distance: f64,
+    pub seed: i32,
+}
+
+impl Default for MeteorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mean_interval: 1800.0,
+            warning_time: 20.0,
+            power: 6.0,
+            ore_block: "meteorite_ore".to_owned(),
+            max_distance: 48.0,
+            seed: 0,
+        }
+    }
+}
+
+/// The consumption of the suits of the players
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SurvivalSettings {
+    /// The suits use oxygen and energy, and the players can die
+    pub enabled: bool,
+    /// Seconds for a full suit to run out of oxygen
+    pub oxygen_duration: f64,
+    /// Seconds of flight for a full suit to run out of energy
+    pub flight_duration: f64,
+    /// Health lost every second without oxygen
+    pub suffocation_damage: f64,
+    /// The items given to the players when they join for the first time, by name
+    pub starting_items: Vec<(String, u32)>,
+}
+
+impl Default for SurvivalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            oxygen_duration: 1200.0,
+            flight_duration: 600.0,
+            suffocation_damage: 10.0,
+            starting_items: vec![
+                ("ration".to_owned(), 4),
+                ("oxygen_canister".to_owned(), 4),
+                ("energy_pack".to_owned(), 4),
+                ("flashlight".to_owned(), 1),
+            ],
+        }
+    }
+}
+
+/// The spawning of the creatures
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MobSettings {
+    /// Seconds between two spawn attempts in every loaded chunk
+    pub spawn_interval: f64,
+    /// Chance that a creature spawns in a chunk at each attempt
+    pub spawn_chance: f64,
+    /// The creatures spawn between these distances from the closest player
+    pub min_spawn_distance: f64,
+    pub max_spawn_distance: f64,
+    /// The creatures farther than this from every player disappear
+    pub despawn_distance: f64,
+    /// Maximum number of creatures for each player on the surface
+    pub max_mobs_per_player: usize,
+    pub kinds: BTreeMap<String, MobKind>,
+    pub seed: i32,
+}
+
+impl Default for MobSettings {
+    fn default() -> Self {
+        let mut kinds = BTreeMap::new();
+        kinds.insert(
+            "rogue_drone".to_owned(),
+            MobKind {
+                hostile: true,
+                health: 20.0,
+                speed: 4.0,
+                damage: 8.0,
+                attack_interval: 1.5,
+                detection_range: 16.0,
+                spawn_blocks: Vec::new(),
+                min_light: 0,
+                max_light: 7,
+                drops: vec![("ingot_iron".to_owned(), 1)],
+            },
+        );
+        kinds.insert(
+            "survey_drone".to_owned(),
+            MobKind {
+                hostile: false,
+                health: 12.0,
+                speed: 3.0,
+                damage: 4.0,
+                attack_interval: 2.0,
+                detection_range: 12.0,
+                spawn_blocks: vec!["grass".to_owned(), "sand".to_owned()],
+                min_light: 8,
+                max_light: 15,
+                drops: vec![("energy_pack".to_owned(), 1)],
+            },
+        );
+        Self {
+            spawn_interval: 10.0,
+            spawn_chance: 0.02,
+            min_spawn_distance: 24.0,
+            max_spawn_distance: 64.0,
+            despawn_distance: 96.0,
+            max_mobs_per_player: 8,
+            kinds,
+            seed: 0,
+        }
+    }
+}
+
+/// A kind of creature, and where it spawns
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MobKind {
+    /// The hostile creatures attack the players and the robots they see, the others only fight back
+    pub hostile: bool,
+    pub health: f64,
+    /// Blocks per second
+    pub speed: f64,
+    /// Health taken from a player, or energy from a robot, by each hit
+    pub damage: f64,
+    /// Seconds between two hits
+    pub attack_interval: f64,
+    /// Distance at which the creature notices its targets
+    pub detection_range: f64,
+    /// The blocks the creature spawns on, any block if empty
+    pub spawn_blocks: Vec<String>,
+    /// The light level where the creature spawns, both included
+    pub min_light: u8,
+    pub max_light: u8,
+    /// The items dropped by the creature when it is destroyed, by name
+    #[serde(default)]
+    pub drops: Vec<(String, u32)>,
+}
+
+/// The hits of the players and of the robots they control
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CombatSettings {
+    /// Health taken by the hit of a player
+    pub player_damage: f64,
+    /// Health taken by the hit of a controlled robot
+    pub robot_damage: f64,
+    /// Seconds during which an entity that was hit can't be hurt again
+    pub invulnerability: f64,
+    /// Speed given to the entities that are hit, in blocks per second
+    pub knockback: f64,
+}
+
+impl Default for CombatSettings {
+    fn default() -> Self {
+        Self {
+            player_damage: 5.0,
+            robot_damage: 8.0,
+            invulnerability: 0.5,
+            knockback: 8.0,
+        }
+    }
+}
+
+/// The strength of the status effects of the players
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EffectSettings {
+    /// Fraction of the speed lost for each level of slowness and of hypoxia
+    pub slowness_per_level: f64,
+    /// Health regained every second for each level of regeneration
+    pub regeneration_per_level: f64,
+    /// Level and seconds of the regeneration given by the med stations
+    pub med_station_level: u8,
+    pub med_station_duration: f64,
+    /// Oxygen below which the players get hypoxia, and below which the hypoxia gets worse
+    pub low_oxygen: f64,
+    pub very_low_oxygen: f64,
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            slowness_per_level: 0.25,
+            regeneration_per_level: 2.0,
+            med_station_level: 2,
+            med_station_duration: 10.0,
+            low_oxygen: 20.0,
+            very_low_oxygen: 5.0,
+        }
+    }
+}
+
+/// The dust storms sweeping the surface, which slow down the players who are not sheltered
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DustStormSettings {
+    pub enabled: bool,
+    /// Average number of seconds between two storms
+    pub mean_interval: f64,
+    /// Seconds during which a storm blows
+    pub duration: f64,
+    /// Level of the slowness of the players caught outside
+    pub slowness_level: u8,
+    pub seed: i32,
+}
+
+impl Default for DustStormSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mean_interval: 2400.0,
+            duration: 120.0,
+            slowness_level: 1,
+            seed: 0,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! The texts of the signs. The player who places a sign is asked for its text, and the players who can build there can
+//! change it later by interacting with the sign. The texts are stored in the world save and sent to the players of the
+//! dimension of the signs each time they change.
+//!
+//! The text of a sign is forgotten once the sign is destroyed. The signs in chunks that are not loaded can't be
+//! checked, their texts are kept until the chunks are loaded again.
+
+use crate::dimension::Dimensions;
+use anyhow::{Context, Result};
+use common::{
+    block::{Block, BlockId},
+    player::PlayerId,
+    registry::Registry,
+    sign::{clean_sign_text, SIGN_BLOCK},
+    world::{BlockPos, DimensionId},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+pub struct Signs {
+    path: PathBuf,
+    /// The texts of the signs of each dimension, by the name of the dimension since the ids of the dimensions can
+    /// change
+    texts: BTreeMap<String, BTreeMap<(i64, i64, i64), String>>,
+    /// The block of the signs, None if the data has none
+    sign_block: Option<BlockId>,
+    /// The dimension whose signs were last sent to each player, None once the texts changed
+    sent: HashMap<PlayerId, Option<DimensionId>>,
+}
+
+impl Signs {
+    /// Load the texts of the signs from the world save, if it has some
+    pub fn load(path: PathBuf, blocks: &Registry<Block>) -> Result<Self> {
+        let texts = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read sign texts")?;
+            ron::de::from_str(&buf).context("Failed to parse sign texts")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            texts,
+            sign_block: blocks.get_id_by_name(&SIGN_BLOCK.to_owned()).map(|id| id as BlockId),
+            sent: HashMap::new(),
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.texts, Default::default())
+            .context("Failed to serialize sign texts")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write sign texts"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    /// Send the texts again to everyone
+    fn changed(&mut self) {
+        self.save();
+        for sent in self.sent.values_mut() {
+            *sent = None;
+        }
+    }
+
+    pub fn add_player(&mut self, player: PlayerId) {
+        self.sent.insert(player, None);
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.sent.remove(&player);
+    }
+
+    pub fn is_sign(&self, block: BlockId) -> bool {
+        self.sign_block == Some(block)
+    }
+
+    /// The text of the sign at some position, empty if it has none
+    pub fn text(&self, dimension: DimensionId, pos: BlockPos, dimensions: &Dimensions) -> &str {
+        self.texts
+            .get(dimensions.name(dimension))
+            .and_then(|texts| texts.get(&(pos.px, pos.py, pos.pz)))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Write the text of the sign at some position, which must be a sign. The text is cleaned up first, and erased if
+    /// nothing is left.
+    pub fn set_text(&mut self, dimension: DimensionId, pos: BlockPos, text: &str, dimensions: &Dimensions) {
+        if !self.is_sign(dimensions.get(dimension).get_block(pos)) {
+            return;
+        }
+        let text = clean_sign_text(text);
+        let texts = self.texts.entry(dimensions.name(dimension).to_owned()).or_default();
+        let key = (pos.px, pos.py, pos.pz);
+        let previous = if text.is_empty() {
+            texts.remove(&key)
+        } else {
+            texts.insert(key, text.clone())
+        };
+        if previous.map_or(!text.is_empty(), |previous| previous != text) {
+            self.changed();
+        }
+    }
+
+    /// Forget the texts of the signs that were destroyed
+    pub fn remove_destroyed(&mut self, dimensions: &Dimensions) {
+        let sign_block = self.sign_block;
+        let mut removed = false;
+        for (dimension_name, texts) in self.texts.iter_mut() {
+            let world = match dimensions.find(dimension_name) {
+                Some(dimension) => dimensions.get(dimension),
+                None => continue,
+            };
+            texts.retain(|&pos, _| {
+                let pos = BlockPos::from(pos);
+                let destroyed =
+                    world.get_chunk(pos.containing_chunk_pos()).is_some() && Some(world.get_block(pos)) != sign_block;
+                removed |= destroyed;
+                !destroyed
+            });
+        }
+        if removed {
+            self.texts.retain(|_, texts| !texts.is_empty());
+            self.changed();
+        }
+    }
+
+    /// The texts of the signs of the dimension of a player, if they must be sent to them because the texts or the
+    /// dimension changed since they were last sent
+    pub fn texts_to_send(
+        &mut self,
+        player: PlayerId,
+        dimension: DimensionId,
+        dimensions: &Dimensions,
+    ) -> Option<Vec<(BlockPos, String)>> {
+        let sent = self.sent.get_mut(&player)?;
+        if *sent == Some(dimension) {
+            return None;
+        }
+        *sent = Some(dimension);
+        let texts = self.texts.get(dimensions.name(dimension)).into_iter().flatten();
+        Some(texts.map(|(&pos, text)| (BlockPos::from(pos), text.clone())).collect())
+    }
+}
@@ -0,0 +1,68 @@
+//! Resting in the respawn pods to skip the night. The players rest by using a respawn pod at night, and stop resting
+//! when they walk away from it. Once all the connected players rest, the night is skipped: the world time jumps to
+//! the next morning, the scheduled tasks that came due in the meantime run, and the dust storms calm down.
+
+use crate::PlayerData;
+use common::{
+    network::{messages::ToClient, Server},
+    physics::simulation::ServerPhysicsSimulation,
+    player::PlayerId,
+    time::WorldTime,
+    world::{BlockPos, DimensionId},
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Distance from the respawn pod beyond which a player stops resting
+const REST_DISTANCE: f64 = 3.0;
+
+#[derive(Default)]
+pub struct Sleep {
+    /// The resting players, with the dimension and the top of the middle of their respawn pod
+    resting: HashMap<PlayerId, (DimensionId, Vector3<f64>)>,
+    /// The number of resting players and of connected players last sent to the players
+    sent: (usize, usize),
+}
+
+impl Sleep {
+    /// Make a player rest on a respawn pod until the morning, or until they walk away
+    pub fn rest(&mut self, player: PlayerId, dimension: DimensionId, pod: BlockPos) {
+        let top = Vector3::new(pod.px as f64 + 0.5, pod.py as f64 + 1.0, pod.pz as f64 + 0.5);
+        self.resting.insert(player, (dimension, top));
+    }
+
+    /// Stop the rest of the players who walked away from their pod or left the game, or of everyone in the morning,
+    /// and tell the players how many rest. Return true if all the connected players rest, and then wake them up.
+    pub fn tick(
+        &mut self,
+        players: &HashMap<PlayerId, PlayerData>,
+        physics_simulation: &ServerPhysicsSimulation,
+        world_time: WorldTime,
+        server: &mut dyn Server,
+    ) -> bool {
+        if world_time.is_day() {
+            self.resting.clear();
+        }
+        let state = physics_simulation.get_state();
+        self.resting.retain(|player, (dimension, pod)| {
+            match (players.get(player), state.physics_state.players.get(player)) {
+                (Some(data), Some(physics_player)) => {
+                    data.dimension == *dimension && (physics_player.aabb.pos - *pod).norm() <= REST_DISTANCE
+                }
+                _ => false,
+            }
+        });
+        let count = (self.resting.len(), players.len());
+        if count != self.sent {
+            self.sent = count;
+            for &player in players.keys() {
+                server.send(player, ToClient::RestingPlayers(count.0 as u32, count.1 as u32));
+            }
+        }
+        let all_resting = !players.is_empty() && self.resting.len() == players.len();
+        if all_resting {
+            self.resting.clear();
+        }
+        all_resting
+    }
+}
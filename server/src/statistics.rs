@@ -0,0 +1,143 @@
+//! Statistics of the players, updated by the events of the event bus and stored with the world
+
+use crate::events::{BlockBroken, BlockPlaced, EventBus, PlayerLeft};
+use anyhow::{Context, Result};
+use common::{
+    block::{Block, BlockId},
+    physics::simulation::ServerPhysicsSimulation,
+    player::PlayerId,
+    registry::Registry,
+    statistics::PlayerStatistics,
+};
+use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Movements longer than this in one tick are teleports, which don't count in the distances
+const MAX_DISTANCE_PER_TICK: f64 = 10.0;
+
+enum Trigger {
+    BlockMined(PlayerId, BlockId),
+    BlockPlaced(PlayerId),
+    PlayerLeft(PlayerId),
+}
+
+pub struct Statistics {
+    path: PathBuf,
+    /// The statistics of each player, by name like the achievements
+    statistics: BTreeMap<String, PlayerStatistics>,
+    player_names: HashMap<PlayerId, String>,
+    /// The position of each player during the last tick
+    last_positions: HashMap<PlayerId, Vector3<f64>>,
+    triggers: Rc<RefCell<Vec<Trigger>>>,
+}
+
+impl Statistics {
+    /// Load the statistics from the world save, if it has some
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let statistics = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read statistics")?;
+            ron::de::from_str(&buf).context("Failed to parse statistics")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            statistics,
+            player_names: HashMap::new(),
+            last_positions: HashMap::new(),
+            triggers: Default::default(),
+        })
+    }
+
+    /// Save the statistics, when a player leaves and periodically
+    pub fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.statistics, Default::default())
+            .context("Failed to serialize statistics")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write statistics"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    pub fn subscribe(&self, events: &mut EventBus) {
+        let triggers = self.triggers.clone();
+        events.subscribe("statistics", move |event: &BlockBroken, _| {
+            triggers.borrow_mut().push(Trigger::BlockMined(event.player, event.block));
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("statistics", move |event: &BlockPlaced, _| {
+            triggers.borrow_mut().push(Trigger::BlockPlaced(event.player));
+        });
+        let triggers = self.triggers.clone();
+        events.subscribe("statistics", move |event: &PlayerLeft, _| {
+            triggers.borrow_mut().push(Trigger::PlayerLeft(event.player));
+        });
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name);
+    }
+
+    /// The statistics of a player, if the player's name is known
+    pub fn get(&self, player: PlayerId) -> Option<&PlayerStatistics> {
+        self.player_names.get(&player).and_then(|name| self.statistics.get(name))
+    }
+
+    /// The statistics of a player, including the players that are not connected
+    pub fn get_by_name(&self, name: &str) -> Option<&PlayerStatistics> {
+        self.statistics.get(name)
+    }
+
+    fn get_mut(&mut self, player: PlayerId) -> Option<&mut PlayerStatistics> {
+        let name = self.player_names.get(&player)?;
+        Some(self.statistics.entry(name.clone()).or_default())
+    }
+
+    /// Count the events received since the last call, and the playtime and distance travelled during the tick
+    pub fn tick(&mut self, physics_simulation: &ServerPhysicsSimulation, block_registry: &Registry<Block>, dt: f64) {
+        let triggers = std::mem::take(&mut *self.triggers.borrow_mut());
+        for trigger in triggers {
+            match trigger {
+                Trigger::BlockMined(player, block) => {
+                    let name = block_registry.get_name_by_id(block as u32).unwrap_or("unknown").to_owned();
+                    if let Some(statistics) = self.get_mut(player) {
+                        *statistics.blocks_mined.entry(name).or_default() += 1;
+                    }
+                }
+                Trigger::BlockPlaced(player) => {
+                    if let Some(statistics) = self.get_mut(player) {
+                        statistics.blocks_placed += 1;
+                    }
+                }
+                Trigger::PlayerLeft(player) => {
+                    self.player_names.remove(&player);
+                    self.last_positions.remove(&player);
+                    self.save();
+                }
+            }
+        }
+
+        let players = &physics_simulation.get_state().physics_state.players;
+        for (&player, physics_player) in players.iter() {
+            let pos = physics_player.aabb.pos;
+            let last_pos = self.last_positions.insert(player, pos);
+            let flying = physics_simulation.get_player_input(player).map(|input| input.flying).unwrap_or(false);
+            if let Some(statistics) = self.get_mut(player) {
+                statistics.playtime += dt;
+                if let Some(last_pos) = last_pos {
+                    let distance = (pos - last_pos).norm();
+                    if distance <= MAX_DISTANCE_PER_TICK {
+                        if flying {
+                            statistics.distance_flown += distance;
+                        } else {
+                            statistics.distance_walked += distance;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Listener answering the status requests of the server lists, see `common::network::status`
+
+use crate::settings::StatusSettings;
+use anyhow::{Context, Result};
+use common::collections::DoubleBuffer;
+use common::network::status::{ServerStatus, STATUS_REQUEST};
+use log::{info, warn};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Time given to the clients to send their whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The longest request line, so that a client can't make the server buffer an endless line
+const MAX_LINE: usize = 1024;
+
+pub struct StatusListener {
+    /// The status written by the ticks of the server, the requests are answered with the last published one
+    status: Arc<DoubleBuffer<ServerStatus>>,
+}
+
+impl StatusListener {
+    /// Start answering the status requests in a background thread
+    pub fn start(settings: &StatusSettings) -> Result<Self> {
+        let listener = TcpListener::bind(&settings.address)
+            .with_context(|| format!("Failed to listen for status requests on {}", settings.address))?;
+        info!("Listening for status requests on {}", settings.address);
+        let status = Arc::new(DoubleBuffer::new(ServerStatus {
+            players: 0,
+            max_players: settings.max_players,
+            motd: settings.motd.clone(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        }));
+        let published = status.clone();
+        std::thread::Builder::new()
+            .name("Status listener".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    // Copied so that the ticks can publish while the request is answered
+                    let status = published.read().clone();
+                    // The requests are tiny, they are answered one after the other
+                    let result = stream.map_err(anyhow::Error::from).and_then(|stream| answer(stream, &status));
+                    if let Err(e) = result {
+                        warn!("Failed to answer a status request: {:#}", e);
+                    }
+                }
+            })
+            .context("Failed to start the status thread")?;
+        Ok(Self { status })
+    }
+
+    /// Update the status once per tick. The requests see the last published status, never a status being updated.
+    pub fn publish(&self, players: usize) {
+        self.status.write().players = players;
+        self.status.publish();
+    }
+}
+
+fn answer(mut stream: TcpStream, status: &ServerStatus) -> Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = read_request(&mut stream)?;
+    if request.trim() != STATUS_REQUEST {
+        anyhow::bail!("unknown request {:?}", request.trim());
+    }
+    let response = ron::ser::to_string(status).context("Failed to serialize the status")?;
+    writeln!(stream, "{}", response)?;
+    Ok(())
+}
+
+/// Read the request line, of at most `MAX_LINE` bytes. The whole line must arrive within `REQUEST_TIMEOUT`, so that a
+/// client sending it byte by byte doesn't hold the listener.
+fn read_request(stream: &mut TcpStream) -> Result<String> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut request = Vec::new();
+    let mut buffer = [0; 256];
+    while !request.contains(&b'\n') {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            anyhow::bail!("request not received within {:?}", REQUEST_TIMEOUT);
+        }
+        stream.set_read_timeout(Some(remaining))?;
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if request.len() > MAX_LINE {
+            anyhow::bail!("request longer than {} bytes", MAX_LINE);
+        }
+    }
+    let line = request.split(|&byte| byte == b'\n').next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(line).into_owned())
+}
@@ -0,0 +1,574 @@
+//! Storage of the chunks in the world save, so that the worlds are kept when their chunks are unloaded and when the
+//! server restarts. Each dimension has a folder in `chunks/`, with a gzip-compressed bincode file for each chunk
+//! named after its position. The files are grouped in region folders of `REGION_SIZE` by `REGION_SIZE` columns, so
+//! that the folders stay small in the large worlds.
+//!
+//! The chunks are written by a thread of each dimension, so that the saves never block the ticks. A chunk saved again
+//! before it was written is only written once, with its latest blocks, and the chunks waiting to be written are
+//! loaded from the queue instead of the disk. When the files are flushed to the disk depends on the `FsyncPolicy`.
+//!
+//! The chunks are stored with the block ids of the save, see `block_ids.ron`: when the ids change with the data
+//! packs, `translate_stored_blocks` rewrites all the stored chunks before the new ids are saved. The translated chunks
+//! are staged next to the stored ones, and only replace them once they are all written, so that a translation that
+//! was interrupted is either started again or finished, and never translates a chunk twice.
+//!
+//! The other data of a chunk is stored next to it in RON files, such as the items of its containers and the loot of
+//! its unopened generated containers, with the names of the items since their ids can change. They are written by the
+//! same thread as the chunks, and a file is removed once the chunk has no data of its kind left.
+
+use crate::settings::{write_block_id_map, FsyncPolicy};
+use anyhow::{Context, Result};
+use common::block::BlockId;
+use common::debug::send_worker_perf;
+use common::inventory::Inventory;
+use common::item::{Item, ItemData, ItemStack};
+use common::registry::{IdMap, Registry};
+use common::time::AverageTimeCounter;
+use common::world::{Chunk, ChunkPos};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+/// The folder of the stored chunks in the save, with a folder for each dimension
+pub const CHUNKS_FOLDER: &str = "chunks";
+const CHUNK_EXTENSION: &str = "chunk";
+const CONTAINERS_EXTENSION: &str = "containers";
+/// Width of the regions, in chunks
+const REGION_SIZE: i64 = 32;
+/// The extension of the chunks translated to new block ids, until they replace the stored chunks
+const TRANSLATED_EXTENSION: &str = "translated";
+/// The block ids of the translated chunks, in the chunks folder once all the chunks are translated and until they
+/// replaced the stored ones
+const TRANSLATED_IDS_FILE: &str = "translated_block_ids.ron";
+
+/// The stored chunks of a dimension
+pub struct WorldStorage {
+    folder: PathBuf,
+    /// The positions of the stored chunks and of the chunks waiting to be written, to know whether a chunk is stored
+    /// without reading the disk
+    stored: HashSet<ChunkPos>,
+    queue: Arc<SharedWriteQueue>,
+    /// The thread writing the chunks, None once it was stopped
+    writer: Option<JoinHandle<()>>,
+}
+
+/// The containers of a chunk, as stored next to it
+#[derive(Default, Serialize, Deserialize)]
+pub struct StoredContainers {
+    /// The items of the containers that were opened
+    pub inventories: BTreeMap<(i64, i64, i64), StoredInventory>,
+    /// The loot table of the containers placed by the world generator that were not opened yet
+    pub generated_loot: BTreeMap<(i64, i64, i64), String>,
+}
+
+/// An inventory as stored in the save
+#[derive(Serialize, Deserialize)]
+pub struct StoredInventory {
+    /// The name and the number of the items of every slot, since the ids of the items can change
+    slots: Vec<Option<(String, u32)>>,
+    /// The data of the stacks that have some, by slot
+    #[serde(default)]
+    slot_data: BTreeMap<usize, ItemData>,
+}
+
+impl StoredInventory {
+    pub fn new(inventory: &Inventory, items: &Registry<Item>) -> Self {
+        let slots = inventory.slots().iter().map(|slot| {
+            slot.as_ref().and_then(|stack| Some((items.get_name_by_id(stack.item)?.to_owned(), stack.count)))
+        });
+        let slot_data = inventory.slots().iter().enumerate().filter_map(|(slot, stack)| {
+            let data = stack.as_ref()?.data()?;
+            Some((slot, data.clone()))
+        });
+        Self {
+            slots: slots.collect(),
+            slot_data: slot_data.collect(),
+        }
+    }
+
+    /// The stored inventory with the current ids of the items. The items removed from the data packs are lost.
+    pub fn to_inventory(&self, items: &Registry<Item>) -> Inventory {
+        let mut inventory = Inventory::new(self.slots.len());
+        for (slot, content) in self.slots.iter().enumerate() {
+            let stack = content.as_ref().and_then(|(item_name, count)| match items.get_id_by_name(item_name) {
+                Some(item) => {
+                    let mut stack = ItemStack::new(item, *count);
+                    stack.set_data(self.slot_data.get(&slot).cloned());
+                    Some(stack)
+                }
+                None => {
+                    log::warn!("Item {} of a container was removed from the data packs", item_name);
+                    None
+                }
+            });
+            inventory.set(slot, stack);
+        }
+        inventory
+    }
+}
+
+impl StoredContainers {
+    pub fn is_empty(&self) -> bool {
+        self.inventories.is_empty() && self.generated_loot.is_empty()
+    }
+}
+
+/// The files of a chunk waiting to be written
+#[derive(Default, Clone)]
+struct PendingWrite {
+    chunk: Option<Arc<Chunk>>,
+    /// The RON files stored next to the chunk by extension, None for the files to remove
+    files: BTreeMap<&'static str, Option<Arc<String>>>,
+}
+
+/// The chunks waiting to be written, in the order they were first saved
+#[derive(Default)]
+struct WriteQueue {
+    /// The latest version of the files of each chunk waiting to be written
+    pending: HashMap<ChunkPos, PendingWrite>,
+    order: VecDeque<ChunkPos>,
+    /// The files being written, if there are some
+    writing: Option<(ChunkPos, PendingWrite)>,
+    /// Set when the storage is dropped: the thread stops once the queue is empty
+    closed: bool,
+    written: u64,
+    /// Number of saves replacing a chunk that was waiting to be written
+    coalesced: u64,
+    failed: u64,
+}
+
+struct SharedWriteQueue {
+    queue: Mutex<WriteQueue>,
+    chunk_available: Condvar,
+}
+
+impl SharedWriteQueue {
+    /// Update the files of a chunk waiting to be written with `update`, which returns whether it replaced a file
+    /// that was waiting already
+    fn push(&self, pos: ChunkPos, update: impl FnOnce(&mut PendingWrite) -> bool) {
+        let mut queue = self.queue.lock().unwrap();
+        let is_new = !queue.pending.contains_key(&pos);
+        if update(queue.pending.entry(pos).or_default()) {
+            queue.coalesced += 1;
+        }
+        if is_new {
+            queue.order.push_back(pos);
+        }
+        self.chunk_available.notify_one();
+    }
+
+    /// A file of a chunk that is waiting to be written or being written, if there is one
+    fn queued<T>(&self, pos: ChunkPos, file: impl Fn(&PendingWrite) -> Option<T>) -> Option<T> {
+        let queue = self.queue.lock().unwrap();
+        let writing = queue.writing.as_ref().filter(|(writing_pos, _)| *writing_pos == pos);
+        queue.pending.get(&pos).and_then(&file).or_else(|| file(&writing?.1))
+    }
+}
+
+impl WorldStorage {
+    /// Open the storage of a dimension of the save, creating it if it doesn't exist, and start its writer thread
+    pub fn open(save_folder: &Path, dimension: &str, fsync: FsyncPolicy) -> Result<Self> {
+        let folder = save_folder.join(CHUNKS_FOLDER).join(dimension);
+        std::fs::create_dir_all(&folder).with_context(|| format!("Failed to create {}", folder.display()))?;
+        let stored = list_stored_chunks(&folder)?.into_iter().map(|(pos, _)| pos).collect::<HashSet<_>>();
+        info!("{} stored chunks in dimension {}", stored.len(), dimension);
+        let queue = Arc::new(SharedWriteQueue {
+            queue: Mutex::new(WriteQueue::default()),
+            chunk_available: Condvar::new(),
+        });
+        let name = format!("Storage {}", dimension);
+        let writer = {
+            let (folder, queue, name) = (folder.clone(), queue.clone(), name.clone());
+            std::thread::Builder::new()
+                .name(name.clone())
+                .spawn(move || run_writer_thread(&folder, &queue, fsync, &name))
+                .context("Failed to start the storage thread")?
+        };
+        Ok(Self {
+            folder,
+            stored,
+            queue,
+            writer: Some(writer),
+        })
+    }
+
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.stored.contains(&pos)
+    }
+
+    /// Read a stored chunk, from the write queue if it wasn't written yet. The damaged chunks are ignored, so they
+    /// are generated again.
+    pub fn load_chunk(&self, pos: ChunkPos) -> Option<Chunk> {
+        if !self.contains(pos) {
+            return None;
+        }
+        if let Some(chunk) = self.queue.queued(pos, |files| files.chunk.clone()) {
+            return Some((*chunk).clone());
+        }
+        match read_chunk(&self.path(pos)) {
+            Ok(chunk) if chunk.pos == pos => Some(chunk),
+            Ok(chunk) => {
+                log::warn!("Stored chunk {:?} contains chunk {:?}", pos, chunk.pos);
+                None
+            }
+            Err(e) => {
+                log::warn!("{:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Store a chunk, replacing the stored one. The chunk is written by the writer thread, which logs the errors.
+    pub fn save_chunk(&mut self, chunk: Arc<Chunk>) {
+        let pos = chunk.pos;
+        self.queue.push(pos, |files| files.chunk.replace(chunk).is_some());
+        self.stored.insert(pos);
+    }
+
+    /// Read the stored containers of a chunk
+    pub fn load_containers(&self, pos: ChunkPos) -> StoredContainers {
+        self.load_chunk_file(pos, CONTAINERS_EXTENSION).unwrap_or_default()
+    }
+
+    /// Store the containers of a chunk, replacing the stored ones
+    pub fn save_containers(&self, pos: ChunkPos, containers: StoredContainers) {
+        self.save_chunk_file(pos, CONTAINERS_EXTENSION, Some(&containers).filter(|containers| !containers.is_empty()));
+    }
+
+    /// Read a RON file stored next to a chunk, from the write queue if it wasn't written yet. None if there is none.
+    /// Damaged files are logged and ignored.
+    pub fn load_chunk_file<T: DeserializeOwned>(&self, pos: ChunkPos, extension: &'static str) -> Option<T> {
+        let path = self.path(pos).with_extension(extension);
+        let result = match self.queue.queued(pos, |files| files.files.get(extension).cloned()) {
+            Some(queued) => Ok(queued.map(|string| (*string).clone())),
+            None if !path.is_file() => Ok(None),
+            None => {
+                let string = std::fs::read_to_string(&path);
+                string.map(Some).with_context(|| format!("Failed to read {}", path.display()))
+            }
+        };
+        let result = result.and_then(|string| match string {
+            Some(string) => ron::de::from_str(&string).map(Some).with_context(|| format!("Damaged {}", path.display())),
+            None => Ok(None),
+        });
+        result.unwrap_or_else(|e| {
+            log::warn!("{:#}", e);
+            None
+        })
+    }
+
+    /// Store a RON file next to a chunk, replacing the stored one, or remove it if `value` is None. It is written by
+    /// the writer thread, like the chunks.
+    pub fn save_chunk_file<T: Serialize>(&self, pos: ChunkPos, extension: &'static str, value: Option<&T>) {
+        let string = match value.map(|value| ron::ser::to_string(value)).transpose() {
+            Ok(string) => string.map(Arc::new),
+            Err(e) => {
+                log::error!("Failed to serialize the {} of chunk {:?}: {}", extension, pos, e);
+                return;
+            }
+        };
+        self.queue.push(pos, |files| files.files.insert(extension, string).is_some());
+    }
+
+    /// Number of chunks whose files are waiting to be written
+    pub fn pending_writes(&self) -> usize {
+        let queue = self.queue.queue.lock().unwrap();
+        queue.order.len() + queue.writing.is_some() as usize
+    }
+
+    pub fn debug_info(&self) -> String {
+        let pending = self.pending_writes();
+        let queue = self.queue.queue.lock().unwrap();
+        format!(
+            "{} stored chunks, {} waiting to be written\n{} written, {} coalesced, {} failed\n",
+            self.stored.len(),
+            pending,
+            queue.written,
+            queue.coalesced,
+            queue.failed
+        )
+    }
+
+    fn path(&self, pos: ChunkPos) -> PathBuf {
+        stored_chunk_path(&self.folder, pos)
+    }
+}
+
+impl Drop for WorldStorage {
+    /// Write the chunks that are still waiting before the storage is closed
+    fn drop(&mut self) {
+        self.queue.queue.lock().unwrap().closed = true;
+        self.queue.chunk_available.notify_all();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Write the chunks of the queue until the storage is dropped
+fn run_writer_thread(folder: &Path, shared: &SharedWriteQueue, fsync: FsyncPolicy, name: &str) {
+    let mut timing = AverageTimeCounter::new();
+    // The files written since the queue was last empty, flushed to the disk with `FsyncPolicy::Batch`
+    let mut unsynced = BTreeSet::new();
+    loop {
+        // Wait for a chunk
+        let (pos, files, pending) = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(pos) = queue.order.pop_front() {
+                    let files = queue.pending.remove(&pos).expect("the queued chunks are pending");
+                    queue.writing = Some((pos, files.clone()));
+                    break (pos, files, queue.order.len());
+                }
+                if !unsynced.is_empty() {
+                    // The batch is flushed without holding the lock, then the queue is checked again
+                    drop(queue);
+                    sync_files(std::mem::take(&mut unsynced));
+                    queue = shared.queue.lock().unwrap();
+                    continue;
+                }
+                if queue.closed {
+                    return;
+                }
+                queue = shared.chunk_available.wait(queue).unwrap();
+            }
+        };
+
+        // Write them
+        let start = Instant::now();
+        let path = stored_chunk_path(folder, pos);
+        let sync = fsync == FsyncPolicy::Always;
+        let mut results = Vec::new();
+        if let Some(chunk) = &files.chunk {
+            results.push((path.clone(), write_stored_file(&path, |path| write_chunk(path, chunk, sync))));
+        }
+        for (extension, string) in &files.files {
+            let path = path.with_extension(extension);
+            let result = match string {
+                Some(string) => write_stored_file(&path, |path| write_file(path, string.as_bytes(), sync)),
+                None => remove_stored_file(&path),
+            };
+            results.push((path, result));
+        }
+        timing.add_time(start.elapsed());
+        send_worker_perf("Workers", name, name, timing.average_time_micros() as f32, timing.average_iter_per_sec(), pending);
+
+        let mut queue = shared.queue.lock().unwrap();
+        queue.writing = None;
+        for (path, result) in results {
+            match result {
+                Ok(()) => {
+                    queue.written += 1;
+                    // The removed files are not flushed
+                    if fsync == FsyncPolicy::Batch && path.is_file() {
+                        unsynced.insert(path);
+                    }
+                }
+                Err(e) => {
+                    queue.failed += 1;
+                    log::error!("{:#}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Write a file of a chunk in its region folder, creating the folder if it is the first chunk of the region
+fn write_stored_file(path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let region = path.parent().expect("the chunks are in a region folder");
+    if !region.is_dir() {
+        std::fs::create_dir_all(region).with_context(|| format!("Failed to create {}", region.display()))?;
+    }
+    write(path)
+}
+
+/// Remove a file of a chunk, if there is one
+fn remove_stored_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Flush written files to the disk, with the folders that contain them so that their names are kept too
+fn sync_files(paths: BTreeSet<PathBuf>) {
+    let folders = paths.iter().filter_map(|path| path.parent().map(Path::to_owned)).collect::<BTreeSet<_>>();
+    for path in paths.iter().chain(folders.iter()) {
+        if let Err(e) = File::open(path).and_then(|file| file.sync_all()) {
+            log::error!("Failed to flush {} to the disk: {}", path.display(), e);
+        }
+    }
+}
+
+/// The path of a chunk in the folder of a dimension
+pub fn stored_chunk_path(folder: &Path, pos: ChunkPos) -> PathBuf {
+    let region = format!("r.{}.{}", pos.px.div_euclid(REGION_SIZE), pos.pz.div_euclid(REGION_SIZE));
+    folder.join(region).join(format!("{}.{}.{}.{}", pos.px, pos.py, pos.pz, CHUNK_EXTENSION))
+}
+
+fn read_chunk(path: &Path) -> Result<Chunk> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    bincode::deserialize_from(GzDecoder::new(BufReader::new(file)))
+        .with_context(|| format!("Damaged chunk {}", path.display()))
+}
+
+/// Write a chunk to a temporary file first, so that the stored chunk is never half written. With `sync`, the file
+/// and its folder are flushed to the disk before returning.
+fn write_chunk(path: &Path, chunk: &Chunk, sync: bool) -> Result<()> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    bincode::serialize_into(&mut encoder, chunk).context("Failed to encode the chunk")?;
+    let bytes = encoder.finish().context("Failed to encode the chunk")?;
+    write_file(path, &bytes, sync)
+}
+
+/// Write a file to a temporary file first, so that it is never half written. With `sync`, the file and its folder
+/// are flushed to the disk before returning.
+fn write_file(path: &Path, contents: &[u8], sync: bool) -> Result<()> {
+    let temporary_path = path.with_extension("tmp");
+    let file = File::create(&temporary_path).with_context(|| format!("Failed to create {}", temporary_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(contents).with_context(|| format!("Failed to write {}", temporary_path.display()))?;
+    writer.flush().with_context(|| format!("Failed to write {}", temporary_path.display()))?;
+    if sync {
+        writer.get_ref().sync_all().with_context(|| format!("Failed to flush {}", temporary_path.display()))?;
+    }
+    std::fs::rename(&temporary_path, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    if sync {
+        let folder = path.parent().expect("the files are in a folder");
+        File::open(folder)
+            .and_then(|folder| folder.sync_all())
+            .with_context(|| format!("Failed to flush {}", folder.display()))?;
+    }
+    Ok(())
+}
+
+/// The positions and paths of the chunks in the region folders of a dimension
+fn list_stored_chunks(folder: &Path) -> Result<Vec<(ChunkPos, PathBuf)>> {
+    let mut chunks = Vec::new();
+    let entries = std::fs::read_dir(folder).with_context(|| format!("Failed to list {}", folder.display()))?;
+    for region in entries.flatten().filter(|entry| entry.path().is_dir()) {
+        chunks.extend(list_chunks(&region.path())?);
+    }
+    Ok(chunks)
+}
+
+/// The positions and paths of the chunks of all the dimensions
+fn list_all_stored_chunks(chunks_folder: &Path) -> Result<Vec<(ChunkPos, PathBuf)>> {
+    let mut chunks = Vec::new();
+    let entries =
+        std::fs::read_dir(chunks_folder).with_context(|| format!("Failed to list {}", chunks_folder.display()))?;
+    for dimension in entries.flatten().filter(|entry| entry.path().is_dir()) {
+        chunks.extend(list_stored_chunks(&dimension.path())?);
+    }
+    Ok(chunks)
+}
+
+/// The paths of the files with some extension in the region folders of all the dimensions
+fn list_all_stored_files(chunks_folder: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let list = |folder: &Path| {
+        let entries = std::fs::read_dir(folder).with_context(|| format!("Failed to list {}", folder.display()))?;
+        Ok::<_, anyhow::Error>(entries.flatten().map(|entry| entry.path()).collect::<Vec<_>>())
+    };
+    let mut files = Vec::new();
+    for dimension in list(chunks_folder)?.into_iter().filter(|path| path.is_dir()) {
+        for region in list(&dimension)?.into_iter().filter(|path| path.is_dir()) {
+            let paths = list(&region)?.into_iter();
+            files.extend(paths.filter(|path| path.extension().and_then(|e| e.to_str()) == Some(extension)));
+        }
+    }
+    Ok(files)
+}
+
+/// The positions and paths of the chunk files directly in a folder
+pub fn list_chunks(folder: &Path) -> Result<Vec<(ChunkPos, PathBuf)>> {
+    let entries = std::fs::read_dir(folder).with_context(|| format!("Failed to list {}", folder.display()))?;
+    Ok(entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| Some((parse_chunk_file_name(&path)?, path)))
+        .collect())
+}
+
+/// The position of a chunk from the name of its file, such as `-1.0.2.chunk`
+fn parse_chunk_file_name(path: &Path) -> Option<ChunkPos> {
+    let name = path.file_name()?.to_str()?.strip_suffix(CHUNK_EXTENSION)?.strip_suffix('.')?;
+    let mut coordinates = name.split('.').map(str::parse::<i64>);
+    let pos = ChunkPos::from((coordinates.next()?.ok()?, coordinates.next()?.ok()?, coordinates.next()?.ok()?));
+    coordinates.next().is_none().then_some(pos)
+}
+
+/// Replace the block ids of the chunks stored in every dimension of the save, using the current id of each saved id,
+/// and save the new `ids` to `block_id_map_path`. The blocks that were removed from the data packs become air. The
+/// damaged chunks are skipped, like when they are loaded, and generated again.
+pub fn translate_stored_blocks(
+    save_folder: &Path,
+    translation: &[Option<u32>],
+    ids: &IdMap,
+    block_id_map_path: &Path,
+) -> Result<()> {
+    let chunks_folder = save_folder.join(CHUNKS_FOLDER);
+    if !chunks_folder.is_dir() {
+        return Ok(());
+    }
+    // The translated chunks are staged next to the stored ones, which are not changed yet
+    let mut staged = BTreeSet::new();
+    for (_, path) in list_all_stored_chunks(&chunks_folder)? {
+        let mut chunk = match read_chunk(&path) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                log::warn!("{:#}", e);
+                continue;
+            }
+        };
+        for block in chunk.data.iter_mut() {
+            *block = translation.get(*block as usize).copied().flatten().unwrap_or(0) as BlockId;
+        }
+        let staged_path = path.with_extension(TRANSLATED_EXTENSION);
+        write_chunk(&staged_path, &chunk, false)?;
+        staged.insert(staged_path);
+    }
+    info!("Translated the block ids of {} stored chunks", staged.len());
+    sync_files(staged);
+
+    // From now on, the translated chunks replace the stored ones even if the server stops before the end
+    let string = ron::ser::to_string_pretty(ids, Default::default()).context("Failed to serialize block id map")?;
+    write_file(&chunks_folder.join(TRANSLATED_IDS_FILE), string.as_bytes(), true)?;
+    finish_translation(save_folder, block_id_map_path)
+}
+
+/// Finish the translation of the stored chunks if one was interrupted, before the block ids of the save are read.
+/// If all the chunks were translated, they replace the stored ones and their ids are saved to `block_id_map_path`,
+/// otherwise the chunks that were translated are removed, since the stored chunks still use the ids of the save.
+pub fn finish_translation(save_folder: &Path, block_id_map_path: &Path) -> Result<()> {
+    let chunks_folder = save_folder.join(CHUNKS_FOLDER);
+    if !chunks_folder.is_dir() {
+        return Ok(());
+    }
+    let ids_path = chunks_folder.join(TRANSLATED_IDS_FILE);
+    let staged = list_all_stored_files(&chunks_folder, TRANSLATED_EXTENSION)?;
+    if !ids_path.is_file() {
+        for path in staged {
+            std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    let buf = std::fs::read_to_string(&ids_path).context("Failed to read the block ids of the translated chunks")?;
+    let ids = ron::de::from_str(&buf).context("Failed to parse the block ids of the translated chunks")?;
+    for path in staged.iter() {
+        let stored_path = path.with_extension(CHUNK_EXTENSION);
+        std::fs::rename(path, &stored_path).with_context(|| format!("Failed to write {}", stored_path.display()))?;
+    }
+    sync_files(staged.into_iter().map(|path| path.with_extension(CHUNK_EXTENSION)).collect());
+    write_block_id_map(block_id_map_path, &ids)?;
+    std::fs::remove_file(&ids_path).with_context(|| format!("Failed to remove {}", ids_path.display()))
+}
@@ -0,0 +1,115 @@
+//! Dust storms: from time to time, the wind rises over the whole surface and slows down the players who are not
+//! sheltered under a roof. The players are told when a storm starts and when it is over.
+
+use crate::settings::DustStormSettings;
+use common::random::Random;
+
+pub struct DustStorms {
+    settings: DustStormSettings,
+    /// Seconds before the next storm, while there is none
+    next_storm: f64,
+    /// Seconds before the current storm is over, None if there is no storm
+    remaining: Option<f64>,
+    /// Whether the players were told that a storm is blowing
+    announced: bool,
+    random: Random,
+}
+
+impl DustStorms {
+    pub fn new(settings: DustStormSettings) -> Self {
+        let mut storms = Self {
+            random: Random::new(settings.seed),
+            settings,
+            next_storm: 0.0,
+            remaining: None,
+            announced: false,
+        };
+        storms.next_storm = storms.random_interval();
+        storms
+    }
+
+    fn random_interval(&mut self) -> f64 {
+        self.settings.mean_interval * (0.5 + self.random.next_f64())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remaining.is_some()
+    }
+
+    /// Level of the slowness of the players caught outside
+    pub fn slowness_level(&self) -> u8 {
+        self.settings.slowness_level
+    }
+
+    /// Start a storm that blows for `duration` seconds, replacing the current one
+    pub fn start(&mut self, duration: f64) {
+        self.remaining = Some(duration);
+    }
+
+    /// End the current storm
+    pub fn stop(&mut self) {
+        if self.remaining.take().is_some() {
+            self.next_storm = self.random_interval();
+        }
+    }
+
+    /// End the current storm, and wait a whole interval before the next one, as after a storm
+    pub fn calm(&mut self) {
+        self.remaining = None;
+        self.next_storm = self.random_interval();
+    }
+
+    /// Start and end the storms. Return the message to send to the players if a storm started or ended.
+    pub fn tick(&mut self, dt: f64) -> Option<&'static str> {
+        match self.remaining.as_mut() {
+            Some(remaining) => {
+                *remaining -= dt;
+                if *remaining <= 0.0 {
+                    self.stop();
+                }
+            }
+            None if self.settings.enabled => {
+                self.next_storm -= dt;
+                if self.next_storm <= 0.0 {
+                    self.start(self.settings.duration);
+                }
+            }
+            None => (),
+        }
+        if self.announced == self.is_active() {
+            return None;
+        }
+        self.announced = self.is_active();
+        if self.announced {
+            Some("A dust storm is rising, take shelter!")
+        } else {
+            Some("The dust storm is over")
+        }
+    }
+
+    /// Start or stop a storm with `storm start [seconds]` or `storm stop`, or show whether a storm is blowing
+    pub fn run_command(&mut self, argument: &str) -> String {
+        let mut words = argument.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => match self.remaining {
+                Some(remaining) => format!("A dust storm is blowing for {:.0} more seconds", remaining),
+                None if self.settings.enabled => format!("Next dust storm in {:.0} seconds", self.next_storm),
+                None => "The dust storms are disabled".to_owned(),
+            },
+            (Some("start"), duration) => {
+                let duration = match duration.map(str::parse::<f64>) {
+                    None => self.settings.duration,
+                    Some(Ok(duration)) if duration > 0.0 => duration,
+                    Some(_) => return "The duration must be a positive number of seconds".to_owned(),
+                };
+                self.start(duration);
+                format!("Started a dust storm for {:.0} seconds", duration)
+            }
+            (Some("stop"), None) => {
+                self.stop();
+                "Stopped the dust storm".to_owned()
+            }
+            _ => "Usage: storm [start [seconds]|stop]".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! The structures placed by the world generators, such as the trees, stored with the world so that the players can
+//! find the nearest one with `/locate <structure>`. Only the structures of the chunks generated so far are known.
+
+use crate::dimension::Dimensions;
+use anyhow::{Context, Result};
+use common::world::{BlockPos, DimensionId, Structure};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+/// A structure, stored by name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct StoredStructure {
+    /// The name of the dimension, since the ids of the dimensions can change
+    dimension: String,
+    /// The corners of the bounding box of the structure, both included
+    min: (i64, i64, i64),
+    max: (i64, i64, i64),
+}
+
+impl StoredStructure {
+    fn center(&self) -> Vector3<f64> {
+        let min = Vector3::new(self.min.0 as f64, self.min.1 as f64, self.min.2 as f64);
+        let max = Vector3::new(self.max.0 as f64, self.max.1 as f64, self.max.2 as f64);
+        (min + max + Vector3::new(1.0, 1.0, 1.0)) / 2.0
+    }
+}
+
+pub struct Structures {
+    path: PathBuf,
+    /// The structures of each name. A chunk generated again gives the same structures, which are only stored once.
+    structures: BTreeMap<String, BTreeSet<StoredStructure>>,
+    /// The name of every dimension, to compare with the dimension of the structures
+    dimension_names: HashMap<DimensionId, String>,
+    /// Whether structures were found since the last save
+    changed: bool,
+}
+
+impl Structures {
+    /// Load the structures from the world save, if it has some
+    pub fn load(path: PathBuf, dimensions: &Dimensions) -> Result<Self> {
+        let structures = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read structures")?;
+            ron::de::from_str(&buf).context("Failed to parse structures")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            structures,
+            dimension_names: dimensions.ids().map(|id| (id, dimensions.name(id).to_owned())).collect(),
+            changed: false,
+        })
+    }
+
+    /// Save the structures periodically, if new ones were found
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+        let result = ron::ser::to_string_pretty(&self.structures, Default::default())
+            .context("Failed to serialize structures")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write structures"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+        self.changed = false;
+    }
+
+    /// Record the structures of the chunks generated in a dimension
+    pub fn record(&mut self, dimension: DimensionId, structures: Vec<Structure>) {
+        let dimension = &self.dimension_names[&dimension];
+        for structure in structures {
+            let stored = StoredStructure {
+                dimension: dimension.clone(),
+                min: (structure.min.px, structure.min.py, structure.min.pz),
+                max: (structure.max.px, structure.max.py, structure.max.pz),
+            };
+            self.changed |= self.structures.entry(structure.name).or_default().insert(stored);
+        }
+    }
+
+    /// The center of the known structure named `name` that is the nearest to `pos` in a dimension
+    pub fn nearest(&self, name: &str, dimension: DimensionId, pos: Vector3<f64>) -> Option<BlockPos> {
+        let dimension = &self.dimension_names[&dimension];
+        self.structures
+            .get(name)?
+            .iter()
+            .filter(|structure| structure.dimension == *dimension)
+            .map(StoredStructure::center)
+            .min_by(|a, b| (a - pos).norm_squared().total_cmp(&(b - pos).norm_squared()))
+            .map(BlockPos::from)
+    }
+
+    /// Run `/locate`, from the position of the player if it is known
+    pub fn run_command<'a>(
+        &self,
+        mut words: impl Iterator<Item = &'a str>,
+        position: Option<(DimensionId, Vector3<f64>)>,
+    ) -> String {
+        let name = match words.next() {
+            Some(name) => name,
+            None if self.structures.is_empty() => return "Usage: /locate <structure>".to_owned(),
+            None => {
+                let names = self.structures.keys().cloned().collect::<Vec<_>>();
+                return format!("Usage: /locate <structure>, among {}", names.join(", "));
+            }
+        };
+        let (dimension, pos) = match position {
+            Some(position) => position,
+            None => return "You can't locate structures yet".to_owned(),
+        };
+        match self.nearest(name, dimension, pos) {
+            Some(structure) => {
+                let center = Vector3::new(structure.px as f64, structure.py as f64, structure.pz as f64);
+                format!(
+                    "The nearest {} is at {} {} {}, {:.0} blocks away",
+                    name,
+                    structure.px,
+                    structure.py,
+                    structure.pz,
+                    (center - pos).norm(),
+                )
+            }
+            None => format!("No {} was found nearby yet", name),
+        }
+    }
+}
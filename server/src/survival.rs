@@ -0,0 +1,461 @@
+//! The survival of the players: their health, the oxygen and the energy of their suit, and the consumables that restore
+//! them. The suits use oxygen all the time, and energy while flying or lighting a flashlight. A player without oxygen
+//! loses health, and a player without energy can't fly. The players also lose health when they are hit.
+//!
+//! The consumables are used from the inventory of the player: the use takes some time, then one item of the selected
+//! slot is consumed. The vitals and the inventories are stored by name with the world.
+
+use crate::settings::SurvivalSettings;
+use anyhow::{Context, Result};
+use common::{
+    inventory::{Inventory, SlotTransaction},
+    item::{Item, ItemData, ItemId, ItemStack, ItemType},
+    network::{messages::ToClient, Server},
+    physics::simulation::ServerPhysicsSimulation,
+    player::{PlayerId, Vitals, MAX_VITAL, PLAYER_INVENTORY_SLOTS},
+    registry::Registry,
+    trade::Trade,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// The vitals and the inventory of a player, as stored with the world
+#[derive(Serialize, Deserialize)]
+struct SavedPlayer {
+    vitals: Vitals,
+    /// The name and the number of the items of every slot, since the ids of the items can change
+    slots: Vec<Option<(String, u32)>>,
+    /// The data of the stacks that have some, by slot
+    #[serde(default)]
+    slot_data: BTreeMap<usize, ItemData>,
+}
+
+/// A consumable being used
+struct ItemUse {
+    slot: usize,
+    item: ItemId,
+    /// Seconds before the item is consumed
+    remaining: f64,
+}
+
+struct PlayerSurvival {
+    vitals: Vitals,
+    inventory: Inventory,
+    selected_slot: usize,
+    using: Option<ItemUse>,
+    /// The rounded vitals last sent to the player
+    sent_vitals: Option<Vitals>,
+    inventory_changed: bool,
+    /// The use of an item that started (Some) or stopped (None) since the last update sent to the player
+    use_changed: Option<Option<f64>>,
+    /// What last hurt the player, told to them when they die
+    last_damage: Option<String>,
+    /// Whether the flashlight held by the player is on
+    flashlight: bool,
+    /// The flashlight was turned on or off since the last update sent to the players
+    flashlight_changed: bool,
+}
+
+impl Default for PlayerSurvival {
+    fn default() -> Self {
+        Self {
+            vitals: Vitals::default(),
+            inventory: Inventory::new(PLAYER_INVENTORY_SLOTS),
+            selected_slot: 0,
+            using: None,
+            sent_vitals: None,
+            inventory_changed: true,
+            use_changed: None,
+            last_damage: None,
+            flashlight: false,
+            flashlight_changed: false,
+        }
+    }
+}
+
+impl PlayerSurvival {
+    fn cancel_use(&mut self) {
+        if self.using.take().is_some() {
+            self.use_changed = Some(None);
+        }
+    }
+
+    /// The energy used every second by the item of the selected slot, if it is a flashlight
+    fn held_flashlight(&self, items: &Registry<Item>) -> Option<f64> {
+        let stack = self.inventory.get(self.selected_slot)?;
+        match items.get_value_by_id(stack.item)?.ty {
+            ItemType::Flashlight { energy_use, .. } => Some(energy_use),
+            _ => None,
+        }
+    }
+
+    fn set_flashlight(&mut self, on: bool) {
+        if self.flashlight != on {
+            self.flashlight = on;
+            self.flashlight_changed = true;
+        }
+    }
+
+    fn to_saved(&self, items: &Registry<Item>) -> SavedPlayer {
+        let slots = self.inventory.slots().iter().map(|slot| {
+            slot.as_ref().and_then(|stack| Some((items.get_name_by_id(stack.item)?.to_owned(), stack.count)))
+        });
+        let slot_data = self.inventory.slots().iter().enumerate().filter_map(|(slot, stack)| {
+            let data = stack.as_ref()?.data()?;
+            Some((slot, data.clone()))
+        });
+        SavedPlayer {
+            vitals: self.vitals,
+            slots: slots.collect(),
+            slot_data: slot_data.collect(),
+        }
+    }
+}
+
+pub struct Survival {
+    path: PathBuf,
+    settings: SurvivalSettings,
+    /// What is stored of every player who ever joined, by name. The connected players are stored again when they
+    /// leave and when the world is saved.
+    saved: BTreeMap<String, SavedPlayer>,
+    player_names: HashMap<PlayerId, String>,
+    players: HashMap<PlayerId, PlayerSurvival>,
+}
+
+impl Survival {
+    /// Load the vitals and the inventories from the world save, if it has some
+    pub fn load(path: PathBuf, settings: SurvivalSettings) -> Result<Self> {
+        let saved = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read the vitals and inventories")?;
+            ron::de::from_str(&buf).context("Failed to parse the vitals and inventories")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            settings,
+            saved,
+            player_names: HashMap::new(),
+            players: HashMap::new(),
+        })
+    }
+
+    /// Save the vitals and the inventories of all the players
+    pub fn save(&mut self, items: &Registry<Item>) {
+        for (id, name) in self.player_names.iter() {
+            self.saved.insert(name.clone(), self.players[id].to_saved(items));
+        }
+        let result = ron::ser::to_string_pretty(&self.saved, Default::default())
+            .context("Failed to serialize the vitals and inventories")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write the vitals and inventories"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    pub fn add_player(&mut self, player: PlayerId) {
+        self.players.insert(player, PlayerSurvival::default());
+    }
+
+    /// Restore the vitals and the inventory of a player who joined before, or give the starting items to a new player
+    pub fn set_player_name(&mut self, player: PlayerId, name: String, items: &Registry<Item>) {
+        let survival = match self.players.get_mut(&player) {
+            Some(survival) => survival,
+            None => return,
+        };
+        if let Some(previous_name) = self.player_names.insert(player, name.clone()) {
+            self.saved.insert(previous_name, survival.to_saved(items));
+        }
+        *survival = PlayerSurvival::default();
+        match self.saved.get(&name) {
+            Some(saved) => {
+                survival.vitals = saved.vitals;
+                for (slot, content) in saved.slots.iter().enumerate().take(PLAYER_INVENTORY_SLOTS) {
+                    let stack = content.as_ref().and_then(|(item_name, count)| {
+                        match items.get_id_by_name(item_name) {
+                            Some(item) => {
+                                let mut stack = ItemStack::new(item, *count);
+                                stack.set_data(saved.slot_data.get(&slot).cloned());
+                                Some(stack)
+                            }
+                            None => {
+                                log::warn!("Item {} of {} was removed from the data packs", item_name, name);
+                                None
+                            }
+                        }
+                    });
+                    survival.inventory.set(slot, stack);
+                }
+            }
+            None => {
+                for (item_name, count) in self.settings.starting_items.iter() {
+                    match items.get_id_by_name(item_name) {
+                        Some(item) => {
+                            survival.inventory.insert(ItemStack::new(item, *count));
+                        }
+                        None => log::warn!("Unknown starting item: {}", item_name),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Store the vitals and the inventory of a player who left
+    pub fn remove_player(&mut self, player: PlayerId, items: &Registry<Item>) {
+        if let (Some(survival), Some(name)) = (self.players.remove(&player), self.player_names.remove(&player)) {
+            self.saved.insert(name, survival.to_saved(items));
+            self.save(items);
+        }
+    }
+
+    /// Whether a player has enough energy to fly
+    pub fn can_fly(&self, player: PlayerId) -> bool {
+        !self.settings.enabled || self.players.get(&player).is_some_and(|survival| survival.vitals.energy > 0.0)
+    }
+
+    /// Select a slot of the inventory of a player, which stops the use of the previous one
+    pub fn select_slot(&mut self, player: PlayerId, slot: usize) {
+        if let Some(survival) = self.players.get_mut(&player) {
+            if slot < survival.inventory.size() && slot != survival.selected_slot {
+                survival.selected_slot = slot;
+                survival.cancel_use();
+            }
+        }
+    }
+
+    /// The item in the selected slot of a player, if any
+    pub fn held_item(&self, player: PlayerId) -> Option<ItemId> {
+        let survival = self.players.get(&player)?;
+        survival.inventory.get(survival.selected_slot).map(|stack| stack.item)
+    }
+
+    /// Start using the item of the selected slot of a player, or return why it can't be used
+    pub fn start_use(&mut self, player: PlayerId, items: &Registry<Item>) -> Result<(), &'static str> {
+        let survival = self.players.get_mut(&player).ok_or("You can't use items yet")?;
+        if survival.using.is_some() {
+            return Err("You are already using an item");
+        }
+        let stack = survival.inventory.get(survival.selected_slot).ok_or("There is nothing to use in this slot")?;
+        match items.get_value_by_id(stack.item).map(|item| &item.ty) {
+            Some(&ItemType::Consumable { use_time, .. }) => {
+                survival.using = Some(ItemUse {
+                    slot: survival.selected_slot,
+                    item: stack.item,
+                    remaining: use_time,
+                });
+                survival.use_changed = Some(Some(use_time));
+                Ok(())
+            }
+            _ => Err("This item can't be used"),
+        }
+    }
+
+    /// Turn the flashlight held by a player on or off, and return whether it is on, or why it can't be turned on
+    pub fn toggle_flashlight(&mut self, player: PlayerId, items: &Registry<Item>) -> Result<bool, &'static str> {
+        let survival = self.players.get_mut(&player).ok_or("You can't use items yet")?;
+        if survival.flashlight {
+            survival.set_flashlight(false);
+            return Ok(false);
+        }
+        if survival.held_flashlight(items).is_none() {
+            return Err("You are not holding a flashlight");
+        }
+        if self.settings.enabled && survival.vitals.energy <= 0.0 {
+            return Err("Your suit has no energy left");
+        }
+        survival.set_flashlight(true);
+        Ok(true)
+    }
+
+    /// Put an item stack in the inventory of a player, and return what didn't fit
+    pub fn give(&mut self, player: PlayerId, stack: ItemStack) -> Option<ItemStack> {
+        let survival = match self.players.get_mut(&player) {
+            Some(survival) => survival,
+            None => return Some(stack),
+        };
+        let leftover = survival.inventory.insert(stack.clone());
+        if leftover.as_ref() != Some(&stack) {
+            survival.inventory_changed = true;
+        }
+        leftover
+    }
+
+    /// Exchange the items of the cost of a trade for its result in the inventory of a player, or return why they can't
+    pub fn trade(&mut self, player: PlayerId, trade: &Trade) -> Result<(), &'static str> {
+        let survival = self.players.get_mut(&player).ok_or("You can't trade yet")?;
+        // The trade is made on a copy, so that nothing is taken if it fails
+        let mut inventory = survival.inventory.clone();
+        if !trade.cost.iter().all(|stack| inventory.remove(stack)) {
+            return Err("You don't have the items for this trade");
+        }
+        if inventory.insert(trade.result.clone()).is_some() {
+            return Err("Your inventory is full");
+        }
+        survival.inventory = inventory;
+        survival.inventory_changed = true;
+        // The item being used may have been traded
+        survival.cancel_use();
+        Ok(())
+    }
+
+    /// Apply a change of the slots made on the inventory screen of a player, with the container they opened if there
+    /// is one, or return why it can't be made
+    pub fn move_items(
+        &mut self,
+        player: PlayerId,
+        transaction: &SlotTransaction,
+        container: Option<&mut Inventory>,
+    ) -> Result<(), &'static str> {
+        let survival = self.players.get_mut(&player).ok_or("You don't have an inventory yet")?;
+        transaction.apply(&mut survival.inventory, container)?;
+        survival.inventory_changed = true;
+        // The item being used may have been moved
+        survival.cancel_use();
+        Ok(())
+    }
+
+    /// Take health from a player, if the players can die. `cause` is told to the player if they die.
+    /// Return whether the player was hurt.
+    pub fn damage(&mut self, player: PlayerId, amount: f64, cause: String) -> bool {
+        if !self.settings.enabled {
+            return false;
+        }
+        match self.players.get_mut(&player) {
+            Some(survival) => {
+                survival.vitals.health = (survival.vitals.health - amount).max(0.0);
+                survival.last_damage = Some(cause);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Give back health to a player who is still alive
+    pub fn heal(&mut self, player: PlayerId, amount: f64) {
+        if let Some(survival) = self.players.get_mut(&player).filter(|survival| survival.vitals.health > 0.0) {
+            survival.vitals.restore(amount, 0.0, 0.0);
+        }
+    }
+
+    /// Fill the oxygen of the suit of a player who is still alive
+    pub fn refill_oxygen(&mut self, player: PlayerId) {
+        if let Some(survival) = self.players.get_mut(&player).filter(|survival| survival.vitals.health > 0.0) {
+            survival.vitals.restore(0.0, MAX_VITAL, 0.0);
+        }
+    }
+
+    /// Whether a player still has health
+    pub fn is_alive(&self, player: PlayerId) -> bool {
+        self.players.get(&player).is_some_and(|survival| survival.vitals.health > 0.0)
+    }
+
+    pub fn vitals(&self, player: PlayerId) -> Option<Vitals> {
+        self.players.get(&player).map(|survival| survival.vitals)
+    }
+
+    /// Consume the oxygen and the energy of the suits, and finish the uses of the consumables.
+    /// Return the players who ran out of health, with what killed them.
+    pub fn tick(
+        &mut self,
+        physics_simulation: &ServerPhysicsSimulation,
+        items: &Registry<Item>,
+        dt: f64,
+    ) -> Vec<(PlayerId, String)> {
+        let mut dead = Vec::new();
+        for (&id, survival) in self.players.iter_mut() {
+            if let Some(item_use) = survival.using.as_mut() {
+                item_use.remaining -= dt;
+                if item_use.remaining <= 0.0 {
+                    let (slot, item) = (item_use.slot, item_use.item);
+                    survival.cancel_use();
+                    // The item may have left the slot since the use started
+                    if survival.inventory.get(slot).map(|stack| stack.item) == Some(item) {
+                        survival.inventory.take_one(slot);
+                        survival.inventory_changed = true;
+                        if let Some(ItemType::Consumable { health, oxygen, energy, .. }) =
+                            items.get_value_by_id(item).map(|item| &item.ty)
+                        {
+                            survival.vitals.restore(*health, *oxygen, *energy);
+                        }
+                    }
+                }
+            }
+            // The flashlight goes out when it leaves the hand of the player
+            let flashlight_use = survival.held_flashlight(items).filter(|_| survival.flashlight);
+            if flashlight_use.is_none() {
+                survival.set_flashlight(false);
+            }
+            if !self.settings.enabled {
+                continue;
+            }
+            if let Some(energy_use) = flashlight_use {
+                survival.vitals.energy = (survival.vitals.energy - energy_use * dt).max(0.0);
+                if survival.vitals.energy == 0.0 {
+                    survival.set_flashlight(false);
+                }
+            }
+            let vitals = &mut survival.vitals;
+            vitals.oxygen = (vitals.oxygen - MAX_VITAL / self.settings.oxygen_duration * dt).max(0.0);
+            if physics_simulation.get_player_input(id).is_some_and(|input| input.flying) {
+                vitals.energy = (vitals.energy - MAX_VITAL / self.settings.flight_duration * dt).max(0.0);
+            }
+            if vitals.oxygen == 0.0 {
+                vitals.health = (vitals.health - self.settings.suffocation_damage * dt).max(0.0);
+                survival.last_damage = Some("You ran out of oxygen".to_owned());
+            }
+            if survival.vitals.health == 0.0 {
+                dead.push((id, survival.last_damage.take().unwrap_or_default()));
+            }
+        }
+        dead
+    }
+
+    /// Give back full vitals to a player who died, and return the items they lost
+    pub fn respawn(&mut self, player: PlayerId, keep_inventory: bool) -> Vec<ItemStack> {
+        let survival = match self.players.get_mut(&player) {
+            Some(survival) => survival,
+            None => return Vec::new(),
+        };
+        survival.vitals = Vitals::default();
+        survival.last_damage = None;
+        survival.cancel_use();
+        if keep_inventory {
+            return Vec::new();
+        }
+        survival.inventory_changed = true;
+        survival.inventory.take_all()
+    }
+
+    /// Tell a player who joined which flashlights of the other players are on
+    pub fn send_flashlights(&self, player: PlayerId, server: &mut dyn Server) {
+        for (&id, _) in self.players.iter().filter(|(_, survival)| survival.flashlight) {
+            server.send(player, ToClient::Flashlight(id, true));
+        }
+    }
+
+    /// Send the vitals, the inventories and the uses of items that changed to the players, and the flashlights that
+    /// were turned on or off to everyone
+    pub fn send_updates(&mut self, server: &mut dyn Server) {
+        let ids = self.players.keys().copied().collect::<Vec<_>>();
+        for (&id, survival) in self.players.iter_mut() {
+            if std::mem::take(&mut survival.flashlight_changed) {
+                for &other in ids.iter() {
+                    server.send(other, ToClient::Flashlight(id, survival.flashlight));
+                }
+            }
+            // The vitals change a little every tick, they are only sent when the rounded values change
+            let rounded = survival.vitals.rounded();
+            if survival.sent_vitals != Some(rounded) {
+                survival.sent_vitals = Some(rounded);
+                server.send(id, ToClient::Vitals(survival.vitals));
+            }
+            if std::mem::take(&mut survival.inventory_changed) {
+                server.send(id, ToClient::Inventory(survival.inventory.clone()));
+            }
+            if let Some(use_time) = survival.use_changed.take() {
+                server.send(id, ToClient::UsingItem(use_time));
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Teleportation of the players and the robots, and the homes of the players.
+//!
+//! The chunks of the destination are generated before the entity is moved, then the entity is moved up until it
+//! doesn't collide with the blocks anymore. The players who are teleported far away are told to hide the world until
+//! the chunks around them are loaded.
+//!
+//! The players can also be teleported to another dimension, for example by an elevator. The arrival of an elevator is
+//! dug out instead, and an elevator leading back is built under the player.
+
+use crate::anticheat::AntiCheat;
+use crate::dimension::Dimensions;
+use crate::robot::Robots;
+use crate::world::World;
+use crate::PlayerData;
+use anyhow::{Context, Result};
+use common::{
+    block::BlockId,
+    network::{messages::ToClient, Server},
+    physics::{aabb::AABB, simulation::ServerPhysicsSimulation},
+    player::PlayerId,
+    robot::RobotId,
+    world::{BlockPos, ChunkPos, DimensionId},
+};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Time after which a teleport is cancelled if its destination is still not generated
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum number of blocks an entity is moved up to find free space at the destination
+const MAX_CLIMB: i64 = 48;
+
+/// An entity that can be teleported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeleportTarget {
+    Player(PlayerId),
+    Robot(RobotId),
+}
+
+struct PendingTeleport {
+    target: TeleportTarget,
+    dimension: DimensionId,
+    /// The middle of the bottom of the entity at the destination
+    destination: Vector3<f64>,
+    /// The elevator to build under the entity at the destination, which is then dug out instead of climbed
+    elevator: Option<BlockId>,
+    /// The player who asked for the teleport, told if it fails
+    requester: Option<PlayerId>,
+    requested: Instant,
+    /// The player whose camera follows the entity, if they were told to hide the world
+    faded: Option<PlayerId>,
+}
+
+/// A home saved by a player
+#[derive(Serialize, Deserialize)]
+struct Home {
+    /// The name of the dimension, since the ids of the dimensions can change
+    dimension: String,
+    position: (f64, f64, f64),
+}
+
+pub struct Teleports {
+    path: PathBuf,
+    /// The home of each player, by name like the statistics
+    homes: BTreeMap<String, Home>,
+    player_names: HashMap<PlayerId, String>,
+    /// The entity each player controls, with its dimension and the middle of its bottom, updated every tick
+    controlled: HashMap<PlayerId, (TeleportTarget, DimensionId, Vector3<f64>)>,
+    pending: Vec<PendingTeleport>,
+}
+
+impl Teleports {
+    /// Load the homes from the world save, if it has some
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let homes = if path.is_file() {
+            let buf = std::fs::read_to_string(&path).context("Failed to read homes")?;
+            ron::de::from_str(&buf).context("Failed to parse homes")?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            homes,
+            player_names: HashMap::new(),
+            controlled: HashMap::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.homes, Default::default())
+            .context("Failed to serialize homes")
+            .and_then(|string| std::fs::write(&self.path, string).context("Failed to write homes"));
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+        }
+    }
+
+    pub fn set_player_name(&mut self, player: PlayerId, name: String) {
+        self.player_names.insert(player, name);
+    }
+
+    /// Find a connected player by name
+    pub fn find_player(&self, name: &str) -> Option<PlayerId> {
+        self.player_names.iter().find(|(_, player_name)| *player_name == name).map(|(&id, _)| id)
+    }
+
+    /// The dimension and the position of the entity controlled by a player: the player or their robot
+    pub fn controlled_position(&self, player: PlayerId) -> Option<(DimensionId, Vector3<f64>)> {
+        self.controlled.get(&player).map(|&(_, dimension, pos)| (dimension, pos))
+    }
+
+    /// Save the position of the entity controlled by a player as their home. Return false if their name is not known.
+    pub fn set_home(&mut self, player: PlayerId, dimensions: &Dimensions) -> bool {
+        match (self.player_names.get(&player), self.controlled_position(player)) {
+            (Some(name), Some((dimension, pos))) => {
+                let home = Home {
+                    dimension: dimensions.name(dimension).to_owned(),
+                    position: (pos.x, pos.y, pos.z),
+                };
+                self.homes.insert(name.clone(), home);
+                self.save();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The home of a player, None if they have none or if its dimension doesn't exist anymore
+    pub fn get_home(&self, player: PlayerId, dimensions: &Dimensions) -> Option<(DimensionId, Vector3<f64>)> {
+        let home = self.homes.get(self.player_names.get(&player)?)?;
+        let (x, y, z) = home.position;
+        Some((dimensions.find(&home.dimension)?, Vector3::new(x, y, z)))
+    }
+
+    /// Teleport the entity controlled by a player: the player or their robot
+    pub fn teleport_controlled(&mut self, player: PlayerId, dimension: DimensionId, destination: Vector3<f64>) -> bool {
+        match self.controlled.get(&player) {
+            Some(&(target, _, _)) => {
+                self.teleport(target, dimension, destination, Some(player));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Teleport an entity so that the middle of its bottom is at `destination`, or above if it doesn't fit there.
+    /// The entity is moved once the destination is generated. A previous teleport of the entity is replaced.
+    pub fn teleport(
+        &mut self,
+        target: TeleportTarget,
+        dimension: DimensionId,
+        destination: Vector3<f64>,
+        requester: Option<PlayerId>,
+    ) {
+        self.pending.retain(|teleport| teleport.target != target);
+        self.pending.push(PendingTeleport {
+            target,
+            dimension,
+            destination,
+            elevator: None,
+            requester,
+            requested: Instant::now(),
+            faded: None,
+        });
+    }
+
+    /// Move the entity controlled by a player through an elevator. It arrives at `destination` in `dimension`, which
+    /// is dug out, on top of `elevator` if it is given.
+    pub fn use_elevator(
+        &mut self,
+        player: PlayerId,
+        dimension: DimensionId,
+        destination: Vector3<f64>,
+        elevator: Option<BlockId>,
+    ) -> bool {
+        if !self.teleport_controlled(player, dimension, destination) {
+            return false;
+        }
+        self.pending.last_mut().unwrap().elevator = elevator;
+        true
+    }
+
+    /// The chunks of a dimension that must be generated for the teleports in progress
+    pub fn destination_chunks(&self, dimension: DimensionId) -> Vec<ChunkPos> {
+        self.pending
+            .iter()
+            .filter(|teleport| teleport.dimension == dimension)
+            .flat_map(|teleport| {
+                // Players are the largest entities, the robots fit in the same chunks.
+                // The block below the destination is included for the elevators.
+                let column = AABB::new(
+                    teleport.destination - Vector3::new(0.5, 1.0, 0.5),
+                    (1.0, 3.0 + MAX_CLIMB as f64, 1.0),
+                );
+                chunks_of(&column)
+            })
+            .collect()
+    }
+
+    /// Move the entities whose destination is ready, and update the entities controlled by the players
+    pub fn tick(
+        &mut self,
+        dimensions: &mut Dimensions,
+        physics_simulation: &mut ServerPhysicsSimulation,
+        robots: &mut Robots,
+        anti_cheat: &mut AntiCheat,
+        players: &mut HashMap<PlayerId, PlayerData>,
+        server: &mut dyn Server,
+    ) {
+        self.pending.retain_mut(|teleport| {
+            let (body, viewer) = match teleport.target {
+                TeleportTarget::Player(id) => match physics_simulation.get_state().physics_state.players.get(&id) {
+                    Some(player) => (player.aabb.clone(), Some(id)),
+                    None => return false,
+                },
+                TeleportTarget::Robot(id) => match robots.get(id) {
+                    Some(robot) => (robot.aabb(), robot.controller.map(|(player, _)| player)),
+                    None => return false,
+                },
+            };
+            let mut body = AABB {
+                pos: teleport.destination - Vector3::new(body.size_x / 2.0, 0.0, body.size_z / 2.0),
+                ..body
+            };
+            // The world is hidden while the chunks around a far destination are loaded
+            if teleport.faded.is_none() {
+                if let Some((viewer, data)) = viewer.and_then(|viewer| Some((viewer, players.get(&viewer)?))) {
+                    let destination_chunk = BlockPos::from(teleport.destination).containing_chunk_pos();
+                    if data.dimension != teleport.dimension || !data.loaded_chunks.contains_key(&destination_chunk) {
+                        server.send(viewer, ToClient::Teleporting(true));
+                        teleport.faded = Some(viewer);
+                    }
+                }
+            }
+
+            let world = dimensions.get_mut(teleport.dimension);
+            let climb = AABB {
+                pos: body.pos - Vector3::new(0.0, 1.0, 0.0),
+                size_y: body.size_y + 1.0 + MAX_CLIMB as f64,
+                ..body.clone()
+            };
+            let result = if matches!(teleport.target, TeleportTarget::Robot(_)) && teleport.dimension != DimensionId::SURFACE {
+                Err("Robots can't leave the surface")
+            } else if !world.height().contains_chunk(BlockPos::from(teleport.destination).containing_chunk_pos()) {
+                Err("The destination is outside of the world")
+            } else if chunks_of(&climb).into_iter().all(|pos| world.is_chunk_ready(pos)) {
+                match teleport.elevator {
+                    Some(elevator) => {
+                        dig_arrival(world, &body, elevator);
+                        Ok(body.pos)
+                    }
+                    None => {
+                        // Move up to the first place where the entity fits
+                        let mut free_pos = None;
+                        for _ in 0..=MAX_CLIMB {
+                            if !body.intersect_world(&*world) {
+                                free_pos = Some(body.pos);
+                                break;
+                            }
+                            body.pos.y += 1.0;
+                        }
+                        free_pos.ok_or("There is no free space at the destination")
+                    }
+                }
+            } else if teleport.requested.elapsed() > LOAD_TIMEOUT {
+                Err("The destination took too long to load")
+            } else {
+                return true;
+            };
+
+            match result {
+                Ok(pos) => match teleport.target {
+                    TeleportTarget::Player(id) => {
+                        physics_simulation.set_player_position(id, pos);
+                        anti_cheat.teleported(id, pos);
+                        if let Some(data) = players.get_mut(&id).filter(|data| data.dimension != teleport.dimension) {
+                            // The chunks of the new dimension are sent from scratch
+                            data.dimension = teleport.dimension;
+                            data.loaded_chunks.clear();
+                            let name = dimensions.name(teleport.dimension).to_owned();
+                            server.send(id, ToClient::EnterDimension(teleport.dimension, name));
+                            server.send(id, ToClient::WorldHeight(dimensions.get(teleport.dimension).height()));
+                        }
+                    }
+                    TeleportTarget::Robot(id) => {
+                        robots.teleport(id, pos + Vector3::new(body.size_x, body.size_y, body.size_z) / 2.0);
+                    }
+                },
+                Err(error) => {
+                    if let Some(requester) = teleport.requester {
+                        server.send(requester, ToClient::CommandOutput(error.to_owned()));
+                    }
+                }
+            }
+            if let Some(viewer) = teleport.faded {
+                server.send(viewer, ToClient::Teleporting(false));
+            }
+            false
+        });
+
+        self.player_names.retain(|id, _| players.contains_key(id));
+        self.controlled = players
+            .iter()
+            .filter_map(|(&id, data)| {
+                let robot = data.robot.filter(|_| data.controlling_robot);
+                let controlled = match robot.and_then(|robot_id| Some((robot_id, robots.get(robot_id)?))) {
+                    Some((robot_id, robot)) => {
+                        let aabb = robot.aabb();
+                        let pos = aabb.pos + Vector3::new(aabb.size_x / 2.0, 0.0, aabb.size_z / 2.0);
+                        (TeleportTarget::Robot(robot_id), DimensionId::SURFACE, pos)
+                    }
+                    None => {
+                        let aabb = &physics_simulation.get_state().physics_state.players.get(&id)?.aabb;
+                        let pos = aabb.pos + Vector3::new(aabb.size_x / 2.0, 0.0, aabb.size_z / 2.0);
+                        (TeleportTarget::Player(id), data.dimension, pos)
+                    }
+                };
+                Some((id, controlled))
+            })
+            .collect();
+    }
+}
+
+/// Make room for an entity arriving through an elevator, and build the elevator under it
+fn dig_arrival(world: &mut World, body: &AABB, elevator: BlockId) {
+    let min = BlockPos::from(body.pos);
+    let max = BlockPos::from(body.pos + Vector3::new(body.size_x, body.size_y, body.size_z));
+    let mut blocks = vec![(BlockPos::from((min.px, min.py - 1, min.pz)), elevator)];
+    for px in min.px..=max.px {
+        for py in min.py..=max.py {
+            for pz in min.pz..=max.pz {
+                blocks.push((BlockPos::from((px, py, pz)), 0));
+            }
+        }
+    }
+    blocks.retain(|&(pos, block)| world.get_block(pos) != block);
+    world.set_blocks(&blocks);
+}
+
+/// The chunks a box is in
+fn chunks_of(aabb: &AABB) -> Vec<ChunkPos> {
+    let min = BlockPos::from(aabb.pos).containing_chunk_pos();
+    let max = BlockPos::from(aabb.pos + Vector3::new(aabb.size_x, aabb.size_y, aabb.size_z)).containing_chunk_pos();
+    let mut chunks = Vec::new();
+    for px in min.px..=max.px {
+        for py in min.py..=max.py {
+            for pz in min.pz..=max.pz {
+                chunks.push(ChunkPos::from((px, py, pz)));
+            }
+        }
+    }
+    chunks
+}
@@ -1,26 +1,41 @@
 use std::{
     collections::{HashMap, HashSet},
+    mem::size_of,
     sync::Arc,
+    time::Instant,
 };
 use common::{
-    block::{Block, BlockId},
+    biome::BiomeId,
+    block::{get_block_light_levels, get_solid_blocks, Block, BlockId, BlockType},
+    inventory::Inventory,
+    item::ItemStack,
     player::RenderDistance,
     physics::BlockContainer,
     registry::Registry,
+    worker::{CancelToken, Priority},
     world::{
         Chunk, ChunkPos, ChunkPosXZ,
         BlockPos,
         LightChunk,
+        Structure,
         WorldGenerator,
+        WorldHeight,
+        CHUNK_SIZE,
     },
 };
 use crate::{
+    loot::Loot,
+    memory::ChunkMemoryUsage,
     light::HighestOpaqueBlock,
+    storage::{StoredContainers, StoredInventory, WorldStorage},
     light::worker::{ChunkLightingData, ChunkLightingWorker, start_lighting_worker},
     worldgen::{WorldGenerationWorker, start_worldgen_worker},
 };
 use lazy_static::lazy_static;
 
+/// Maximum number of chunks pregenerated at once, so that the worldgen queue keeps room for the chunks of the players
+const MAX_PREGENERATING_CHUNKS: usize = 8;
+
 lazy_static! {
     static ref EMPTY_HOB: Arc<HighestOpaqueBlock> = {
         Arc::new(HighestOpaqueBlock::new())
@@ -31,6 +46,7 @@ lazy_static! {
 /// It is responsible for
 /// * storing chunk data
 /// * generating the chunks
+/// * storing the chunks in the save
 /// * updating the lighting
 pub struct World {
     /// The chunks
@@ -39,34 +55,127 @@ pub struct World {
     chunk_columns: HashMap<ChunkPosXZ, ServerChunkColumn>,
     /// The next chunk version. When the chunk version changes, we know we must send the updated chunk to the clients.
     next_chunk_version: u64,
-    /// The chunks in the worldgen queue
-    worldgen_queue: HashSet<ChunkPos>,
+    /// The chunks in the worldgen queue, with the token to cancel their generation
+    worldgen_queue: HashMap<ChunkPos, CancelToken>,
+    /// The chunks being generated by `pregenerate_chunk`, with the token to cancel their generation
+    pregen_queue: HashMap<ChunkPos, CancelToken>,
+    /// Number of pregenerated chunks saved since the last call to `drain_pregenerated_chunks`
+    pregenerated_chunks: usize,
     /// The worldgen worker
     worldgen_worker: WorldGenerationWorker,
+    /// The chunks stored in the save
+    storage: WorldStorage,
+    /// The vertical bounds of the world, the chunks outside of them are never loaded
+    height: WorldHeight,
     /// The light worker
     light_worker: ChunkLightingWorker,
+    /// The inventories of the container blocks, such as storage crates
+    containers: HashMap<BlockPos, Inventory>,
+    /// The loot table of the containers placed by the world generator that were not opened yet
+    generated_loot: HashMap<BlockPos, String>,
+    /// The chunks whose containers were read from the storage. The containers of the chunks that are not loaded anymore
+    /// are forgotten when they are saved.
+    containers_loaded: HashSet<ChunkPos>,
+    /// The chunks whose containers changed since they were last saved
+    containers_changed: HashSet<ChunkPos>,
+    loot: Loot,
+    /// The blocks modified since the last call to `drain_changed_blocks`
+    changed_blocks: Vec<BlockPos>,
+    /// The position, previous block and new block of the blocks modified since the last call to `drain_block_changes`
+    block_changes: Vec<(BlockPos, BlockId, BlockId)>,
+    /// The position and items of the containers destroyed since the last call to `drain_destroyed_containers`
+    destroyed_containers: Vec<(BlockPos, Vec<ItemStack>)>,
+    /// Whether each block is solid
+    solid_blocks: Vec<bool>,
+    /// Whether each block has a behavior that the machines keep track of, such as the doors and the lamps
+    special_blocks: Vec<bool>,
+    /// The structures of the chunks generated since the last call to `drain_generated_structures`
+    generated_structures: Vec<Structure>,
 }
 
 impl World {
     pub fn new(
         block_registry: Registry<Block>,
-        world_generator: Box<dyn WorldGenerator + Send>
+        loot: Loot,
+        make_generator: impl Fn() -> Box<dyn WorldGenerator + Send>,
+        worldgen_threads: usize,
+        storage: WorldStorage,
+        height: WorldHeight,
     ) -> Self {
         Self {
+            solid_blocks: get_solid_blocks(&block_registry),
+            special_blocks: block_registry
+                .iter()
+                .map(|(_, _, block)| !matches!(block.block_type, BlockType::Air | BlockType::NormalCube { .. }))
+                .collect(),
+            light_worker: start_lighting_worker(get_block_light_levels(&block_registry)),
             chunks: HashMap::default(),
             chunk_columns: HashMap::default(),
             next_chunk_version: 0,
-            worldgen_queue: HashSet::default(),
-            worldgen_worker: start_worldgen_worker(block_registry, world_generator),
-            light_worker: start_lighting_worker(),
+            worldgen_queue: HashMap::default(),
+            pregen_queue: HashMap::default(),
+            pregenerated_chunks: 0,
+            worldgen_worker: start_worldgen_worker(block_registry, make_generator, worldgen_threads),
+            storage,
+            height,
+            containers: HashMap::new(),
+            generated_loot: HashMap::new(),
+            containers_loaded: HashSet::new(),
+            containers_changed: HashSet::new(),
+            loot,
+            changed_blocks: Vec::new(),
+            block_changes: Vec::new(),
+            destroyed_containers: Vec::new(),
+            generated_structures: Vec::new(),
         }
     }
 
+    /// The vertical bounds of the world
+    pub fn height(&self) -> WorldHeight {
+        self.height
+    }
+
+    /// The chunks stored in the save
+    pub fn storage(&self) -> &WorldStorage {
+        &self.storage
+    }
+
+    /// Return true if the chunk is loaded, or if it is outside of the vertical bounds and will never be
+    pub fn is_chunk_ready(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos) || !self.height.contains_chunk(pos)
+    }
+
     /// Return some chunk if is loaded
     pub fn get_chunk(&self, pos: ChunkPos) -> Option<Arc<Chunk>> {
         self.chunks.get(&pos).map(|server_chunk| server_chunk.chunk.clone())
     }
 
+    /// Return the light of some chunk if it is loaded
+    pub fn get_light_chunk(&self, pos: ChunkPos) -> Option<Arc<LightChunk>> {
+        self.chunks.get(&pos).map(|server_chunk| server_chunk.light_chunk.clone())
+    }
+
+    /// Return true if no opaque block of the loaded chunks is above position `pos`
+    pub fn is_under_open_sky(&self, pos: BlockPos) -> bool {
+        match self.chunk_columns.get(&pos.containing_chunk_pos().into()) {
+            None => false,
+            Some(column) => {
+                let (i, _, k) = pos.pos_in_containing_chunk();
+                column.highest_opaque_block.y[(i * CHUNK_SIZE + k) as usize] < pos.py
+            }
+        }
+    }
+
+    /// Return the positions of all the loaded chunks
+    pub fn loaded_chunk_positions(&self) -> Vec<ChunkPos> {
+        self.chunks.keys().copied().collect()
+    }
+
+    /// Return all the loaded chunks. The chunks are shared, so this is cheap.
+    pub fn chunk_snapshot(&self) -> HashMap<ChunkPos, Arc<Chunk>> {
+        self.chunks.iter().map(|(pos, server_chunk)| (*pos, server_chunk.chunk.clone())).collect()
+    }
+
     /// Return block at position `pos` in the world. 0 is returned if the chunk does not exists/is not loaded
     pub fn get_block(&self, pos: BlockPos) -> BlockId {
         match self.chunks.get(&pos.containing_chunk_pos()) {
@@ -75,6 +184,123 @@ impl World {
         }
     }
 
+    /// Return the light level at position `pos`. 0 is returned if the chunk is not loaded
+    pub fn get_light(&self, pos: BlockPos) -> u8 {
+        match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => 0,
+            Some(server_chunk) => server_chunk.light_chunk.get_light_at(pos.pos_in_containing_chunk()),
+        }
+    }
+
+    /// Set the block at position `pos`. Return false if the chunk is not loaded.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> bool {
+        self.set_blocks(&[(pos, block)]) == 1
+    }
+
+    /// Set many blocks at once. Every modified chunk is only updated and relighted once.
+    /// Return the number of blocks that were set, the blocks in unloaded chunks are ignored.
+    pub fn set_blocks(&mut self, blocks: &[(BlockPos, BlockId)]) -> usize {
+        let mut new_chunks: HashMap<ChunkPos, Chunk> = HashMap::new();
+        let mut count = 0;
+        for &(pos, block) in blocks {
+            let chunk_pos = pos.containing_chunk_pos();
+            if !new_chunks.contains_key(&chunk_pos) {
+                match self.get_chunk(chunk_pos) {
+                    None => continue,
+                    Some(chunk) => new_chunks.insert(chunk_pos, (*chunk).clone()),
+                };
+            }
+            let chunk = new_chunks.get_mut(&chunk_pos).unwrap();
+            let old_block = chunk.get_block_at(pos.pos_in_containing_chunk());
+            chunk.set_block_at(pos.pos_in_containing_chunk(), block);
+            if old_block != block {
+                self.destroy_container(pos);
+            }
+            self.changed_blocks.push(pos);
+            self.block_changes.push((pos, old_block, block));
+            count += 1;
+        }
+        for (_, chunk) in new_chunks.into_iter() {
+            self.set_chunk(Arc::new(chunk));
+        }
+        count
+    }
+
+    /// Return the blocks modified by `set_block` since the last call
+    pub fn drain_changed_blocks(&mut self) -> Vec<BlockPos> {
+        std::mem::take(&mut self.changed_blocks)
+    }
+
+    /// Return the position, previous block and new block of the blocks modified by `set_block` since the last call
+    pub fn drain_block_changes(&mut self) -> Vec<(BlockPos, BlockId, BlockId)> {
+        std::mem::take(&mut self.block_changes)
+    }
+
+    /// Return the position and items of the containers destroyed by `set_block` since the last call
+    pub fn drain_destroyed_containers(&mut self) -> Vec<(BlockPos, Vec<ItemStack>)> {
+        std::mem::take(&mut self.destroyed_containers)
+    }
+
+    /// Remove the container at position `pos`, if there is one, and keep its items so that they can be dropped. A
+    /// container placed by the world generator that was not opened yet gives its loot.
+    fn destroy_container(&mut self, pos: BlockPos) {
+        self.load_containers(pos.containing_chunk_pos());
+        let items = match self.containers.remove(&pos) {
+            Some(mut inventory) => inventory.take_all(),
+            None => match self.generated_loot.remove(&pos) {
+                Some(table) => self.loot.roll(&table, None),
+                None => return,
+            },
+        };
+        self.containers_changed.insert(pos.containing_chunk_pos());
+        if !items.is_empty() {
+            self.destroyed_containers.push((pos, items));
+        }
+    }
+
+    /// Return the structures of the chunks generated since the last call. A chunk generated again after being
+    /// unloaded returns its structures again.
+    pub fn drain_generated_structures(&mut self) -> Vec<Structure> {
+        std::mem::take(&mut self.generated_structures)
+    }
+
+    /// Return the inventory of the container at position `pos`, creating one with `size` slots if there is none yet.
+    /// A container placed by the world generator is filled with its loot when it is created.
+    pub fn get_container_mut(&mut self, pos: BlockPos, size: usize) -> &mut Inventory {
+        self.load_containers(pos.containing_chunk_pos());
+        // The inventory can be modified through the reference
+        self.containers_changed.insert(pos.containing_chunk_pos());
+        let Self { containers, generated_loot, loot, .. } = self;
+        containers.entry(pos).or_insert_with(|| {
+            let mut inventory = Inventory::new(size);
+            if let Some(table) = generated_loot.remove(&pos) {
+                for stack in loot.roll(&table, None) {
+                    inventory.insert(stack);
+                }
+            }
+            inventory
+        })
+    }
+
+    /// Read the containers of a chunk from the storage, unless they were already
+    fn load_containers(&mut self, chunk_pos: ChunkPos) {
+        if !self.containers_loaded.insert(chunk_pos) {
+            return;
+        }
+        let stored = self.storage.load_containers(chunk_pos);
+        for (pos, inventory) in stored.inventories {
+            self.containers.insert(BlockPos::from(pos), inventory.to_inventory(self.loot.items()));
+        }
+        for (pos, table) in stored.generated_loot {
+            self.generated_loot.insert(BlockPos::from(pos), table);
+        }
+    }
+
+    /// The loot of the blocks and of the generated containers of this world
+    pub fn loot_mut(&mut self) -> &mut Loot {
+        &mut self.loot
+    }
+
     /// Update the highest opaque block in the column, and mark relevant chunks for a light update.
     /// To be called after every chunk loading or modification.
     fn update_chunk_column(&mut self, pos: ChunkPos) {
@@ -121,10 +347,14 @@ impl World {
                 version: 0,
                 is_in_light_queue: false,
                 needs_light_update: true,
+                is_saved: false,
+                is_seen: true,
+                last_seen: Instant::now(),
             }
         });
         server_chunk.chunk = chunk;
         server_chunk.needs_light_update = true;
+        server_chunk.is_saved = false;
         server_chunk.version = self.next_chunk_version;
         self.next_chunk_version += 1;
 
@@ -145,10 +375,140 @@ impl World {
     pub fn get_new_generated_chunks(&mut self) {
         // TODO: maybe don't update all the light column every time
         // TODO: if there are multiple chunks in the same column this may save time
-        while let Some(chunk) = self.worldgen_worker.get_result() {
-            self.worldgen_queue.remove(&chunk.pos);
+        while let Some(mut chunk) = self.worldgen_worker.get_result() {
+            // The chunks whose generation was cancelled are in neither queue, they are not needed anymore
+            if self.pregen_queue.remove(&chunk.pos).is_some() {
+                // The pregenerated chunks are only saved, the players load them from the storage when they need them
+                self.record_structures(&mut chunk);
+                self.storage.save_chunk(Arc::new(chunk));
+                self.pregenerated_chunks += 1;
+            } else if self.worldgen_queue.remove(&chunk.pos).is_some() {
+                self.record_structures(&mut chunk);
+                self.record_special_blocks(&chunk);
+                self.set_chunk(Arc::new(chunk));
+            }
+        }
+    }
+
+    /// Keep the structures of a generated chunk and the loot of their containers
+    fn record_structures(&mut self, chunk: &mut Chunk) {
+        for (pos, table) in chunk.structures.iter().flat_map(|structure| structure.loot.iter()) {
+            // The containers that were already opened keep their items
+            self.load_containers(pos.containing_chunk_pos());
+            if !self.containers.contains_key(pos) && self.generated_loot.get(pos) != Some(table) {
+                self.generated_loot.insert(*pos, table.clone());
+                self.containers_changed.insert(pos.containing_chunk_pos());
+            }
+        }
+        self.generated_structures.append(&mut chunk.structures);
+    }
+
+    /// Add the special blocks of a chunk being loaded to the changed blocks, so that the machines find them
+    fn record_special_blocks(&mut self, chunk: &Chunk) {
+        let size = CHUNK_SIZE as i64;
+        for (i, &block) in chunk.data.iter().enumerate() {
+            if self.special_blocks[block as usize] {
+                // The same order as `Chunk::get_block_at`
+                let (x, y, z) = (i as i64 / size / size, i as i64 / size % size, i as i64 % size);
+                let pos = (chunk.pos.px * size + x, chunk.pos.py * size + y, chunk.pos.pz * size + z);
+                self.changed_blocks.push(BlockPos::from(pos));
+            }
+        }
+    }
+
+    /// Load a chunk from the storage, or start generating it. Return false if the worldgen queue is full.
+    fn load_or_generate_chunk(&mut self, pos: ChunkPos, priority: Priority) -> bool {
+        if let Some(chunk) = self.storage.load_chunk(pos) {
+            self.record_special_blocks(&chunk);
             self.set_chunk(Arc::new(chunk));
+            self.chunks.get_mut(&pos).unwrap().is_saved = true;
+            return true;
+        }
+        match self.worldgen_worker.enqueue_with_priority(pos, priority) {
+            Ok(token) => {
+                self.worldgen_queue.insert(pos, token);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Whether a chunk is being generated for the players or pregenerated
+    fn is_chunk_generating(&self, pos: ChunkPos) -> bool {
+        self.worldgen_queue.contains_key(&pos) || self.pregen_queue.contains_key(&pos)
+    }
+
+    /// Start generating a chunk to save it before the players need it. The chunks that are already loaded, stored
+    /// or being generated, and the chunks outside of the vertical bounds, are counted as pregenerated right away.
+    /// Return false if the chunk can't be started yet: only
+    /// a few chunks are pregenerated at once, and none while chunks are generated for the players.
+    pub fn pregenerate_chunk(&mut self, pos: ChunkPos) -> bool {
+        // The loaded chunks are saved when they are unloaded
+        if self.is_chunk_ready(pos) || self.storage.contains(pos) || self.is_chunk_generating(pos) {
+            self.pregenerated_chunks += 1;
+            return true;
+        }
+        if self.pregen_queue.len() >= MAX_PREGENERATING_CHUNKS || !self.worldgen_queue.is_empty() {
+            return false;
         }
+        match self.worldgen_worker.enqueue_with_priority(pos, Priority::Low) {
+            Ok(token) => {
+                self.pregen_queue.insert(pos, token);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Return the number of pregenerated chunks saved or skipped since the last call
+    pub fn drain_pregenerated_chunks(&mut self) -> usize {
+        std::mem::take(&mut self.pregenerated_chunks)
+    }
+
+    /// Number of chunks being pregenerated
+    pub fn num_pregenerating_chunks(&self) -> usize {
+        self.pregen_queue.len()
+    }
+
+    /// Cancel the generation of the chunks started by `pregenerate_chunk`
+    pub fn cancel_pregeneration(&mut self) {
+        for (_, token) in self.pregen_queue.drain() {
+            token.cancel();
+        }
+        self.pregenerated_chunks = 0;
+    }
+
+    /// Save the loaded chunks that changed since they were loaded or saved, and the containers that changed. The
+    /// containers of the chunks that are not loaded anymore are forgotten once they are saved.
+    pub fn save_chunks(&mut self) {
+        let Self { chunks, storage, .. } = self;
+        for server_chunk in chunks.values_mut().filter(|server_chunk| !server_chunk.is_saved) {
+            save_chunk(storage, server_chunk);
+        }
+
+        // Every changed chunk is saved, the ones that have no container left too so that their file is removed
+        let mut changed: HashMap<ChunkPos, StoredContainers> =
+            self.containers_changed.drain().map(|pos| (pos, StoredContainers::default())).collect();
+        for (pos, inventory) in &self.containers {
+            if let Some(stored) = changed.get_mut(&pos.containing_chunk_pos()) {
+                let inventory = StoredInventory::new(inventory, self.loot.items());
+                stored.inventories.insert((pos.px, pos.py, pos.pz), inventory);
+            }
+        }
+        for (pos, table) in &self.generated_loot {
+            if let Some(stored) = changed.get_mut(&pos.containing_chunk_pos()) {
+                stored.generated_loot.insert((pos.px, pos.py, pos.pz), table.clone());
+            }
+        }
+        for (pos, stored) in changed {
+            self.storage.save_containers(pos, stored);
+        }
+
+        let chunks = &self.chunks;
+        self.containers_loaded.retain(|pos| chunks.contains_key(pos));
+        let loaded = &self.containers_loaded;
+        self.containers.retain(|pos, _| loaded.contains(&pos.containing_chunk_pos()));
+        self.generated_loot.retain(|pos, _| loaded.contains(&pos.containing_chunk_pos()));
     }
 
     /// Fetch the new light chunks from the light worker
@@ -211,41 +571,86 @@ impl World {
             }
         }
 
-        ChunkLightingData { chunks, highest_opaque_blocks }
+        ChunkLightingData { chunks, highest_opaque_blocks, height: self.height }
     }
 
     /// Start the worldgen of a few chunks
     pub fn enqueue_chunks_for_worldgen(&mut self, player_close_chunks: &[ChunkPos]) {
         for pos in player_close_chunks {
-            if !self.chunks.contains_key(pos) && !self.worldgen_queue.contains(pos) {
-                let res = self.worldgen_worker.enqueue(*pos);
-                match res {
-                    // If the worldgen queue is not full, update chunk status
-                    Ok(()) => {
-                        self.worldgen_queue.insert(*pos);
-                    },
-                    // If the worldgen queue is full, stop
-                    Err(_) => break,
+            if !self.is_chunk_ready(*pos) && !self.is_chunk_generating(*pos) {
+                // If the worldgen queue is full, stop
+                if !self.load_or_generate_chunk(*pos, Priority::Normal) {
+                    break;
                 }
             }
         }
     }
 
-    /// Drop far chunks
-    pub fn drop_far_chunks(&mut self, player_positions: &[(ChunkPos, RenderDistance)]) {
-        let loaded_chunks = self.chunks.keys().cloned().collect::<Vec<_>>();
-        'chunks: for chunk_pos in loaded_chunks {
-            for (player_chunk, render_distance) in player_positions {
-                if render_distance.is_chunk_visible(*player_chunk, chunk_pos) {
-                    continue 'chunks
-                }
+    /// Mark the chunks seen by the players and the entities, and save the chunks that are not seen anymore so that
+    /// they can be unloaded when the memory is needed, see `ChunkMemory`
+    pub fn update_seen_chunks(&mut self, player_positions: &[(ChunkPos, RenderDistance)]) {
+        let is_visible = |chunk_pos: ChunkPos| {
+            player_positions
+                .iter()
+                .any(|(player_chunk, render_distance)| render_distance.is_chunk_visible(*player_chunk, chunk_pos))
+        };
+        // Cancel the generation of the chunks that are now too far
+        self.worldgen_queue.retain(|chunk_pos, token| {
+            let visible = is_visible(*chunk_pos);
+            if !visible {
+                token.cancel();
+            }
+            visible
+        });
+        let now = Instant::now();
+        let Self { chunks, storage, .. } = self;
+        for (chunk_pos, server_chunk) in chunks.iter_mut() {
+            server_chunk.is_seen = is_visible(*chunk_pos);
+            if server_chunk.is_seen {
+                server_chunk.last_seen = now;
+            } else if !server_chunk.is_saved {
+                save_chunk(storage, server_chunk);
             }
-            self.unload_chunk(chunk_pos);
         }
     }
 
-    /// Unload chunk
-    // TODO: persist to disk
+    /// The memory used by the loaded chunks
+    pub fn memory_usage(&self) -> ChunkMemoryUsage {
+        let mut usage = ChunkMemoryUsage::default();
+        for server_chunk in self.chunks.values() {
+            let bytes = server_chunk.memory();
+            usage.chunks += 1;
+            usage.bytes += bytes;
+            if server_chunk.is_seen {
+                usage.seen_chunks += 1;
+                usage.seen_bytes += bytes;
+            }
+        }
+        usage
+    }
+
+    /// The chunks that can be unloaded: they are not seen anymore and they are saved.
+    /// Return when they were last seen, their position and their memory.
+    pub fn eviction_candidates(&self) -> Vec<(Instant, ChunkPos, usize)> {
+        self.chunks
+            .iter()
+            .filter(|(_, server_chunk)| !server_chunk.is_seen && server_chunk.is_saved)
+            .map(|(pos, server_chunk)| (server_chunk.last_seen, *pos, server_chunk.memory()))
+            .collect()
+    }
+
+    /// Unload a chunk to free memory. Return false if it can't be: it is seen, or it changed since it was saved.
+    pub fn evict_chunk(&mut self, pos: ChunkPos) -> bool {
+        match self.chunks.get(&pos) {
+            Some(server_chunk) if !server_chunk.is_seen && server_chunk.is_saved => {
+                self.unload_chunk(pos);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Unload a chunk, which must be saved first
     fn unload_chunk(&mut self, pos: ChunkPos) {
         self.chunks.remove(&pos);
         let column_pos = ChunkPosXZ::from(pos);
@@ -276,12 +681,14 @@ impl World {
                 if updates.len() == MAX_CHUNKS {
                     break
                 }
-            } else {
-                // Generate the chunk
-                let res = self.worldgen_worker.enqueue(pos);
-                if res.is_ok() {
-                    self.worldgen_queue.insert(pos);
-                }
+            } else if self.height.contains_chunk(pos) && !self.is_chunk_generating(pos) {
+                // Load or generate the chunk, first the chunks around the player so that they don't fall
+                let priority = if pos.squared_euclidian_distance(player_chunk) <= 3 {
+                    Priority::High
+                } else {
+                    Priority::Normal
+                };
+                self.load_or_generate_chunk(pos, priority);
             }
         }
         updates
@@ -300,11 +707,11 @@ impl World {
 
 impl BlockContainer for World {
     fn is_block_full(&self, pos: BlockPos) -> bool {
-        // TODO: use BlockRegistry
-        match self.chunks.get(&pos.containing_chunk_pos()) {
-            None => false,
-            Some(chunk) => chunk.chunk.get_block_at(pos.pos_in_containing_chunk()) != 0,
-        }
+        self.height.is_below(pos) || self.solid_blocks[self.get_block(pos) as usize]
+    }
+
+    fn is_block_targetable(&self, pos: BlockPos) -> bool {
+        self.get_block(pos) != 0
     }
 }
 
@@ -320,6 +727,28 @@ struct ServerChunk {
     pub is_in_light_queue: bool,
     /// True if the chunk needs a light update, for example before it never had one or because it changed.
     pub needs_light_update: bool,
+    /// True if the chunk didn't change since it was loaded from the storage or saved
+    pub is_saved: bool,
+    /// True if a player or an entity sees the chunk, it is never unloaded then
+    pub is_seen: bool,
+    pub last_seen: Instant,
+}
+
+impl ServerChunk {
+    /// Estimate the memory used by the chunk, with its light and its highest opaque blocks
+    fn memory(&self) -> usize {
+        size_of::<Self>()
+            + self.chunk.data.len() * size_of::<BlockId>()
+            + self.chunk.biomes.len() * size_of::<BiomeId>()
+            + self.light_chunk.light.len()
+            + size_of::<HighestOpaqueBlock>()
+    }
+}
+
+/// Save a loaded chunk to the storage. The chunk is written in the background.
+fn save_chunk(storage: &mut WorldStorage, server_chunk: &mut ServerChunk) {
+    storage.save_chunk(server_chunk.chunk.clone());
+    server_chunk.is_saved = true;
 }
 
 /// The data for each chunk column stored by the server
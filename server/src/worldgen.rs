@@ -3,15 +3,21 @@ use common::{
     registry::Registry,
     world::{Chunk, ChunkPos, WorldGenerator},
 };
+use common::profile_scope;
 use common::worker::{WorkerState, Worker};
 
 static WORLDGEN_QUEUE_SIZE: usize = 20;
 
+/// Start a worldgen worker with `threads` threads, each with its own generator
 pub fn start_worldgen_worker(
     block_registry: Registry<Block>,
-    world_generator: Box<dyn WorldGenerator + Send>
+    make_generator: impl Fn() -> Box<dyn WorldGenerator + Send>,
+    threads: usize,
 ) -> WorldGenerationWorker {
-    Worker::new(WorldGenerationState::new(block_registry, world_generator), WORLDGEN_QUEUE_SIZE, "Worldgen".into())
+    let states = (0..threads.max(1))
+        .map(|_| WorldGenerationState::new(block_registry.clone(), make_generator()))
+        .collect();
+    Worker::with_states(states, WORLDGEN_QUEUE_SIZE, "Worldgen".into())
 }
 
 pub struct WorldGenerationState {
@@ -30,6 +36,7 @@ impl WorldGenerationState {
 
 impl WorkerState<ChunkPos, Chunk> for WorldGenerationState {
     fn compute(&mut self, pos: ChunkPos) -> Chunk {
+        profile_scope!("worldgen");
         self.world_generator.generate_chunk(pos, &self.block_registry)
     }
 }
@@ -0,0 +1,143 @@
+//! Run the server and scripted clients over the dummy network, one tick at a time
+
+use common::network::dummy::{new_server, DummyClient, DummyConnector};
+use common::network::messages::{ToClient, ToServer};
+use common::network::{Client, ClientEvent};
+use common::player::{CharacterModel, PlayerId, PlayerProfile, DEFAULT_CHARACTER_MODEL};
+use server::ServerGame;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The longest time the tests wait for the chunks of the background workers
+const WORKER_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct TestServer {
+    pub game: ServerGame,
+    connector: DummyConnector,
+    save_folder: PathBuf,
+}
+
+impl TestServer {
+    /// Start a server with the embedded data and a new save. The name must be unique among the tests.
+    pub fn start(name: &str) -> Self {
+        Self::start_with_save(name, |_| ())
+    }
+
+    /// Start a server with the embedded data and a save written by `write_save` in the empty save folder
+    pub fn start_with_save(name: &str, write_save: impl FnOnce(&Path)) -> Self {
+        let save_folder = std::env::temp_dir().join(format!("marsbots-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&save_folder);
+        write_save(&save_folder);
+        let (connector, server) = new_server();
+        // The data directory doesn't exist, so the embedded data is used
+        let data_directory = save_folder.join("no-data");
+        let game = ServerGame::new(Box::new(server), data_directory, &save_folder).expect("Failed to start the server");
+        Self {
+            game,
+            connector,
+            save_folder,
+        }
+    }
+
+    /// Stop the server like `launch_server`, saving the game, and start it again on the same save. The clients of the
+    /// stopped server are disconnected.
+    pub fn restart(&mut self) {
+        self.game.save();
+        self.step_until(|game| game.num_pending_chunk_writes() == 0);
+        let (connector, server) = new_server();
+        let data_directory = self.save_folder.join("no-data");
+        self.game = ServerGame::new(Box::new(server), data_directory, &self.save_folder).expect("Failed to restart");
+        self.connector = connector;
+    }
+
+    /// Connect a new client, and run the server until it knows its id
+    pub fn connect(&mut self, name: &str) -> TestClient {
+        let mut client = TestClient {
+            client: self.connector.connect(),
+            name: name.to_owned(),
+            id: None,
+            messages: Vec::new(),
+        };
+        for _ in 0..10 {
+            self.game.step();
+            client.receive();
+            if client.id.is_some() {
+                // Let the server handle the profile
+                self.game.step();
+                return client;
+            }
+        }
+        panic!("{} didn't receive their id", name);
+    }
+
+    /// The folder of the save of the server
+    pub fn save_folder(&self) -> &Path {
+        &self.save_folder
+    }
+
+    pub fn step(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.game.step();
+        }
+    }
+
+    /// Run the server until the condition is true. The world is generated and lighted by background workers,
+    /// so the ticks are run until they are done or the timeout is reached.
+    pub fn step_until(&mut self, mut condition: impl FnMut(&ServerGame) -> bool) {
+        let start = Instant::now();
+        while !condition(&self.game) {
+            assert!(start.elapsed() < WORKER_TIMEOUT, "The condition was not met in time");
+            self.game.step();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.save_folder);
+    }
+}
+
+pub struct TestClient {
+    client: DummyClient,
+    name: String,
+    pub id: Option<PlayerId>,
+    /// The messages received from the server, except the frequent state updates
+    pub messages: Vec<ToClient>,
+}
+
+impl TestClient {
+    pub fn id(&self) -> PlayerId {
+        self.id.expect("The client is not connected")
+    }
+
+    pub fn send(&mut self, message: ToServer) {
+        self.client.send(message);
+    }
+
+    /// Receive the messages sent by the server since the last call
+    pub fn receive(&mut self) {
+        loop {
+            match self.client.receive_event() {
+                ClientEvent::NoEvent | ClientEvent::Disconnected => break,
+                ClientEvent::Connected => {
+                    self.client.send(ToServer::SetProfile(PlayerProfile {
+                        name: self.name.clone(),
+                        model: CharacterModel::Builtin(DEFAULT_CHARACTER_MODEL.to_owned()),
+                    }));
+                }
+                ClientEvent::ServerMessage(message) => match message {
+                    ToClient::CurrentId(id) => self.id = Some(id),
+                    ToClient::UpdatePhysics(_)
+                    | ToClient::UpdateRobots(_)
+                    | ToClient::UpdateDroppedItems(_)
+                    | ToClient::UpdateTime(_)
+                    | ToClient::UpdateMeteors(_)
+                    | ToClient::Chunk(_, _, _) => (),
+                    message => self.messages.push(message),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,1199 @@
+mod harness;
+
+use common::combat::Combatant;
+use common::content::GAME_VERSION;
+use common::data::{cache::DataCache, pack::DataPackDownload};
+use common::effect::StatusEffectKind;
+use common::inventory::{Inventory, InventoryId, SlotId, SlotTransaction};
+use common::item::ItemStack;
+use common::network::messages::{ToClient, ToServer};
+use common::player::{
+    CharacterModel, PlayerInput, PlayerProfile, DEFAULT_CHARACTER_MODEL, MAX_VITAL, PLAYER_INVENTORY_SLOTS,
+};
+use common::registry::IdMap;
+use common::theme::DEFAULT_THEME;
+use common::time::DAY_LENGTH;
+use common::world::{BlockPos, Chunk, ChunkPos, DimensionId, WorldHeight};
+use flate2::{write::GzEncoder, Compression};
+use harness::{TestClient, TestServer};
+use std::path::Path;
+
+#[test]
+fn join_and_see_the_other_players() {
+    let mut server = TestServer::start("join");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    assert_ne!(alice.id(), bob.id());
+    assert!(server.game.has_player(alice.id()));
+    assert!(server.game.has_player(bob.id()));
+
+    // Bob received the profile of Alice when joining, and Alice received the profile of Bob
+    server.step(1);
+    alice.receive();
+    bob.receive();
+    let has_profile = |messages: &[ToClient], player, name| {
+        messages.iter().any(|message| {
+            matches!(message, ToClient::PlayerProfile(id, profile) if *id == player && profile.name == name)
+        })
+    };
+    assert!(has_profile(&alice.messages, bob.id(), "Bob"));
+    assert!(has_profile(&bob.messages, alice.id(), "Alice"));
+}
+
+#[test]
+fn move_forward() {
+    let mut server = TestServer::start("move");
+    let mut alice = server.connect("Alice");
+    let start = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+
+    alice.send(ToServer::UpdateInput(PlayerInput {
+        key_move_forward: true,
+        ..Default::default()
+    }));
+    server.step(60);
+    let end = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+    // With a yaw of 0, forward is towards -z
+    assert!(end.z < start.z - 1.0, "moved from {:?} to {:?}", start, end);
+    assert!((end.x - start.x).abs() < 1e-6);
+
+    // The player slows down until they stop
+    alice.send(ToServer::UpdateInput(PlayerInput::default()));
+    server.step(120);
+    let stopped = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+    server.step(10);
+    assert!((server.game.get_physics_player(alice.id()).unwrap().aabb.pos - stopped).norm() < 0.1);
+}
+
+#[test]
+fn place_and_break_a_block_then_rejoin() {
+    let mut server = TestServer::start("blocks");
+    let mut alice = server.connect("Alice");
+    let physics_player = server.game.get_physics_player(alice.id()).unwrap();
+    let position = physics_player.aabb.pos;
+    // The blocks are pointed at from the camera
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..4).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    // Make a floor below the player, with nothing in between
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), 0));
+    assert!(server.game.set_block(below(3), 1));
+
+    // Look straight down
+    alice.send(ToServer::PlaceBlock(position, 0.0, -90.0));
+    server.step(1);
+    assert_eq!(server.game.get_block(below(2)), 1);
+    assert_eq!(server.game.get_player_statistics(alice.id()).unwrap().blocks_placed, 1);
+
+    alice.send(ToServer::BreakBlock(position, 0.0, -90.0));
+    server.step(1);
+    assert_eq!(server.game.get_block(below(2)), 0);
+    let mined = server.game.get_player_statistics(alice.id()).unwrap().blocks_mined.values().sum::<u64>();
+    assert_eq!(mined, 1);
+
+    // The statistics are kept by name when the player leaves and joins again
+    let old_id = alice.id();
+    drop(alice);
+    server.step(1);
+    assert!(!server.game.has_player(old_id));
+    let alice = server.connect("Alice");
+    assert!(server.game.has_player(alice.id()));
+    assert_eq!(server.game.get_player_statistics(alice.id()).unwrap().blocks_placed, 1);
+}
+
+#[test]
+fn save_the_world_when_the_server_stops() {
+    let mut server = TestServer::start("stop");
+    let _alice = server.connect("Alice");
+    let pos = BlockPos::from((0, 32, 0));
+    server.step_until(|game| game.is_chunk_loaded(pos.containing_chunk_pos()));
+    assert!(server.game.set_block(pos, 1));
+
+    // The block is saved although the server stops long before the next autosave
+    server.restart();
+    let _alice = server.connect("Alice");
+    server.step_until(|game| game.is_chunk_loaded(pos.containing_chunk_pos()));
+    assert_eq!(server.game.get_block(pos), 1);
+}
+
+#[test]
+fn roll_back_a_change_made_before_a_restart() {
+    let mut server = TestServer::start_with_save("history", |save_folder| {
+        std::fs::create_dir_all(save_folder).unwrap();
+        std::fs::write(save_folder.join("settings.ron"), "(history: Some((retention: 3600.0)))").unwrap();
+    });
+    let mut alice = server.connect("Alice");
+    let physics_player = server.game.get_physics_player(alice.id()).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..4).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    for dy in 0..3 {
+        assert!(server.game.set_block(below(dy), 0));
+    }
+    assert!(server.game.set_block(below(3), 1));
+    // The changes of the floor are recorded before the one of Alice
+    server.step(1);
+    alice.send(ToServer::PlaceBlock(position, 0.0, -90.0));
+    server.step(1);
+    assert_eq!(server.game.get_block(below(2)), 1);
+
+    // The history of the chunk is stored with it
+    drop(alice);
+    server.restart();
+    let _alice = server.connect("Alice");
+    server.step_until(|game| game.is_chunk_loaded(below(2).containing_chunk_pos()));
+    let output = server.game.run_admin_command("rollback Alice 10");
+    assert!(output.starts_with("Reverted 1 blocks"), "{}", output);
+    assert_eq!(server.game.get_block(below(2)), 0);
+}
+
+#[test]
+fn teleport_far_away_and_back_home() {
+    let mut server = TestServer::start("teleport");
+    let mut alice = server.connect("Alice");
+    let start = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+    alice.send(ToServer::Command("/home set".to_owned()));
+    server.step(1);
+
+    // The destination is generated before Alice is moved, then she is moved up out of the ground
+    alice.send(ToServer::Command("/tp 1000 -5 2000".to_owned()));
+    let id = alice.id();
+    server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x > 900.0);
+    let arrival = server.game.get_physics_player(id).unwrap().aabb.pos;
+    assert!((arrival.z - 2000.0).abs() < 1.0, "arrived at {:?}", arrival);
+    assert!(arrival.y >= -5.0);
+    // The anti-cheat accepts the new position
+    server.step(10);
+    assert!(server.game.get_physics_player(id).unwrap().aabb.pos.x > 900.0);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::Teleporting(true))));
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::Teleporting(false))));
+
+    alice.send(ToServer::Command("/home".to_owned()));
+    server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x < 100.0);
+    let home = server.game.get_physics_player(id).unwrap().aabb.pos;
+    assert!((home.x - start.x).abs() < 1e-6 && (home.z - start.z).abs() < 1e-6, "went home to {:?}", home);
+}
+
+#[test]
+fn take_the_elevator_to_the_caverns() {
+    let mut server = TestServer::start("elevator");
+    let mut alice = server.connect("Alice");
+    let id = alice.id();
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..3).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    let elevator = server.game.get_block_id("elevator_caverns").unwrap();
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), elevator));
+
+    // Alice arrives in the caverns on top of an elevator leading back to the surface
+    alice.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step_until(|game| game.get_player_dimension(id) != Some(DimensionId::SURFACE));
+    alice.receive();
+    let caverns = server.game.get_player_dimension(id).unwrap();
+    assert!(alice.messages.iter().any(|message| {
+        matches!(message, ToClient::EnterDimension(dimension, name) if *dimension == caverns && name == "caverns")
+    }));
+    let arrival = server.game.get_physics_player(id).unwrap().aabb.pos;
+    assert!((arrival.x - position.x).abs() < 1.0 && (arrival.z - position.z).abs() < 1.0, "arrived at {:?}", arrival);
+
+    // And takes it back to the surface
+    server.step(10);
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    alice.send(ToServer::InteractBlock(physics_player.aabb.pos, 0.0, -90.0));
+    server.step_until(|game| game.get_player_dimension(id) == Some(DimensionId::SURFACE));
+    let back = server.game.get_physics_player(id).unwrap().aabb.pos;
+    assert!((back.x - position.x).abs() < 1.0 && (back.z - position.z).abs() < 1.0, "went back to {:?}", back);
+}
+
+#[test]
+fn mine_in_the_caverns_and_pick_up_what_drops() {
+    let mut server = TestServer::start("caverns_drops");
+    let mut alice = server.connect("Alice");
+    let id = alice.id();
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..3).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), server.game.get_block_id("elevator_caverns").unwrap()));
+    alice.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step_until(|game| game.get_player_dimension(id) != Some(DimensionId::SURFACE));
+    server.step(10);
+
+    // Alice breaks the elevator she arrived on, and picks up the block that drops in the caverns
+    let elevator = server.game.get_item_id("elevator_surface").unwrap();
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    alice.send(ToServer::BreakBlock(physics_player.aabb.pos, 0.0, -90.0));
+    // And flies down into the hole to pick it up
+    alice.send(ToServer::UpdateInput(PlayerInput {
+        key_move_down: true,
+        flying: true,
+        ..Default::default()
+    }));
+    let has_elevator = |messages: &[ToClient]| {
+        messages.iter().any(|message| match message {
+            ToClient::Inventory(inventory) => inventory.slots().iter().flatten().any(|stack| stack.item == elevator),
+            _ => false,
+        })
+    };
+    for _ in 0..100 {
+        server.step(1);
+        alice.receive();
+        if has_elevator(&alice.messages) {
+            return;
+        }
+    }
+    panic!("Alice didn't pick up the elevator");
+}
+
+#[test]
+fn forbid_a_player_to_fly() {
+    let mut server = TestServer::start("gamerule");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    server.game.run_admin_command("group Alice admin");
+    alice.send(ToServer::Command("/gamerule player Bob can_fly false".to_owned()));
+    server.step(1);
+    bob.receive();
+    assert!(bob.messages.iter().any(|message| matches!(message, ToClient::Capabilities(c) if !c.can_fly && c.can_build)));
+
+    // Bob can't take off, even if his input says he is flying
+    let start = server.game.get_physics_player(bob.id()).unwrap().aabb.pos;
+    bob.send(ToServer::UpdateInput(PlayerInput {
+        flying: true,
+        key_move_up: true,
+        ..Default::default()
+    }));
+    server.step(30);
+    let end = server.game.get_physics_player(bob.id()).unwrap().aabb.pos;
+    assert!(end.y <= start.y + 0.5, "flew from {:?} to {:?}", start, end);
+}
+
+#[test]
+fn guests_can_neither_build_nor_teleport() {
+    let mut server = TestServer::start("permissions");
+    let mut alice = server.connect("Alice");
+    server.game.run_admin_command("group Alice guest");
+    server.step(1);
+    alice.receive();
+    let commands = alice.messages.iter().rev().find_map(|message| match message {
+        ToClient::ServerCommands(commands) => Some(commands),
+        _ => None,
+    });
+    assert!(commands.unwrap().iter().all(|(name, _)| name != "tp" && name != "gamerule"));
+
+    alice.messages.clear();
+    let position = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+    alice.send(ToServer::Command("/tp 0 100 0".to_owned()));
+    alice.send(ToServer::PlaceBlock(position, 0.0, -90.0));
+    server.step(1);
+    alice.receive();
+    let outputs = alice.messages.iter().filter_map(|message| match message {
+        ToClient::CommandOutput(output) => Some(output.as_str()),
+        _ => None,
+    });
+    assert_eq!(outputs.collect::<Vec<_>>(), ["You are not allowed to use /tp", "You are not allowed to build"]);
+    assert_eq!(server.game.get_player_statistics(alice.id()).map_or(0, |statistics| statistics.blocks_placed), 0);
+}
+
+#[test]
+fn keep_the_name_chosen_when_joining() {
+    let mut server = TestServer::start("names");
+    let mut alice = server.connect("Alice");
+    let mut mallory = server.connect("Mallory");
+    server.game.run_admin_command("group Alice admin");
+
+    // Mallory can't take the name of the administrator once they joined
+    mallory.send(ToServer::SetProfile(PlayerProfile {
+        name: "Alice".to_owned(),
+        model: CharacterModel::Builtin(DEFAULT_CHARACTER_MODEL.to_owned()),
+    }));
+    mallory.send(ToServer::Command("/group Mallory admin".to_owned()));
+    server.step(1);
+    mallory.receive();
+    let outputs = mallory.messages.iter().filter_map(|message| match message {
+        ToClient::CommandOutput(output) => Some(output.as_str()),
+        _ => None,
+    });
+    let expected = ["You can't change your name or model", "You are not allowed to use /group"];
+    assert_eq!(outputs.collect::<Vec<_>>(), expected);
+
+    // Nor join with it while Alice is connected
+    let mut impostor = server.connect("Alice");
+    server.step(1);
+    impostor.receive();
+    alice.receive();
+    let kicked = |messages: &[ToClient]| messages.iter().any(|message| matches!(message, ToClient::Kicked(_)));
+    assert!(kicked(&impostor.messages));
+    assert!(!kicked(&alice.messages));
+}
+
+#[test]
+fn claim_an_area_that_others_cannot_mine() {
+    let mut server = TestServer::start("claims");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    for command in ["/claim pos1", "/claim pos2", "/claim create garden"] {
+        alice.send(ToServer::Command(command.to_owned()));
+    }
+    server.step(1);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::CommandOutput(output) if output.starts_with("Claimed garden"))));
+    bob.receive();
+    let claim = bob.messages.iter().rev().find_map(|message| match message {
+        ToClient::Claims(claims) => claims.first().cloned(),
+        _ => None,
+    });
+    let claim = claim.expect("Bob didn't receive the claim");
+    assert!(claim.owner == "Alice" && !claim.allowed);
+
+    // Bob can't send his robot to mine around the claim, until Alice lets him
+    let from = BlockPos::from((claim.min.px - 2, claim.min.py - 2, claim.min.pz - 2));
+    let to = BlockPos::from((claim.max.px + 2, claim.max.py + 2, claim.max.pz + 2));
+    bob.messages.clear();
+    bob.send(ToServer::AssignMiningArea(from, to));
+    server.step(1);
+    bob.receive();
+    let refused = |messages: &[ToClient]| {
+        messages.iter().any(|message| matches!(message, ToClient::CommandOutput(output) if output.contains("claim garden")))
+    };
+    assert!(refused(&bob.messages));
+
+    alice.send(ToServer::Command("/claim add garden Bob".to_owned()));
+    server.step(1);
+    bob.messages.clear();
+    bob.send(ToServer::AssignMiningArea(from, to));
+    server.step(1);
+    bob.receive();
+    assert!(!refused(&bob.messages));
+    assert!(bob.messages.iter().any(|message| matches!(message, ToClient::Claims(claims) if claims[0].allowed)));
+}
+
+#[test]
+fn eat_a_ration_from_the_starting_items() {
+    let mut server = TestServer::start("consumables");
+    let mut alice = server.connect("Alice");
+    let ration = server.game.get_item_id("ration").unwrap();
+    server.step(1);
+    alice.receive();
+    let last_inventory = |messages: &[ToClient]| {
+        messages.iter().rev().find_map(|message| match message {
+            ToClient::Inventory(inventory) => Some(inventory.clone()),
+            _ => None,
+        })
+    };
+    // New players get some consumables
+    let inventory = last_inventory(&alice.messages).expect("Alice didn't receive her inventory");
+    let slot = inventory.slots().iter().position(|stack| stack.as_ref().is_some_and(|stack| stack.item == ration));
+    let slot = slot.unwrap();
+    let rations = inventory.get(slot).unwrap().count;
+
+    // The server refuses to use an empty slot
+    alice.messages.clear();
+    alice.send(ToServer::SelectSlot(PLAYER_INVENTORY_SLOTS - 1));
+    alice.send(ToServer::UseItem);
+    server.step(1);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::CommandOutput(_))));
+    assert!(!alice.messages.iter().any(|message| matches!(message, ToClient::UsingItem(_))));
+
+    // Eating takes some time, then one ration is gone
+    alice.messages.clear();
+    alice.send(ToServer::SelectSlot(slot));
+    alice.send(ToServer::UseItem);
+    server.step(1);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::UsingItem(Some(_)))));
+    assert!(last_inventory(&alice.messages).is_none());
+    server.step(120);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::UsingItem(None))));
+    let inventory = last_inventory(&alice.messages).expect("The inventory didn't change");
+    assert_eq!(inventory.get(slot).map_or(0, |stack| stack.count), rations - 1);
+}
+
+#[test]
+fn hit_another_player_who_is_pushed_back() {
+    let mut server = TestServer::start("combat");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    server.step(1);
+    let start = server.game.get_physics_player(bob.id()).unwrap().aabb.pos;
+    let position = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+
+    // Bob stands where Alice spawned, and he can't be hurt again right after the first hit
+    alice.send(ToServer::Attack(position, 0.0, 0.0));
+    alice.send(ToServer::Attack(position, 0.0, 0.0));
+    server.step(10);
+    bob.receive();
+    let last_health = |messages: &[ToClient]| {
+        messages.iter().rev().find_map(|message| match message {
+            ToClient::Vitals(vitals) => Some(vitals.health),
+            _ => None,
+        })
+    };
+    let health = last_health(&bob.messages).expect("Bob didn't receive his vitals");
+    assert!(health < MAX_VITAL && health > MAX_VITAL - 10.0, "Bob has {} health", health);
+    // Alice looks towards -z, so Bob is pushed that way
+    let end = server.game.get_physics_player(bob.id()).unwrap().aabb.pos;
+    assert!(end.z < start.z - 0.5, "pushed from {:?} to {:?}", start, end);
+    // Both players see the hit, which came from Alice's side
+    alice.receive();
+    for messages in [&alice.messages, &bob.messages] {
+        let damages = messages
+            .iter()
+            .filter_map(|message| match message {
+                ToClient::EntityDamaged(target, amount, from) => Some((*target, *amount, *from)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(damages.len(), 1, "{:?}", damages);
+        let (target, amount, from) = damages[0];
+        assert_eq!(target, Combatant::Player(bob.id()));
+        assert!((amount - (MAX_VITAL - health)).abs() < 1e-6, "{} damage for {} health", amount, health);
+        assert!(from.is_some_and(|from| from.z > 0.0), "the damage came from {:?}", from);
+    }
+
+    // Nothing happens when the players can't hit each other
+    server.game.run_admin_command("group Alice admin");
+    alice.send(ToServer::Command("/gamerule pvp false".to_owned()));
+    server.step(60);
+    bob.messages.clear();
+    let position = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+    alice.send(ToServer::Attack(position, 0.0, 0.0));
+    server.step(1);
+    bob.receive();
+    assert!(last_health(&bob.messages).is_none_or(|new_health| new_health >= health));
+    assert!(!bob.messages.iter().any(|message| matches!(message, ToClient::EntityDamaged(_, _, _))));
+}
+
+#[test]
+fn wave_to_the_other_players() {
+    let mut server = TestServer::start("emote");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    server.step(1);
+
+    alice.send(ToServer::Emote("wave".to_owned()));
+    alice.send(ToServer::Emote("moonwalk".to_owned()));
+    server.step(1);
+    alice.receive();
+    bob.receive();
+    let alice_id = alice.id();
+    let played = |messages: &[ToClient], name: &str| {
+        messages.iter().any(|message| {
+            matches!(message, ToClient::PlayAnimation(id, animation) if *id == alice_id && animation == name)
+        })
+    };
+    assert!(played(&bob.messages, "wave"));
+    // Only the animations of the character models can be played
+    assert!(!played(&bob.messages, "moonwalk"));
+    let refused = |message: &ToClient| matches!(message, ToClient::CommandOutput(text) if text.contains("moonwalk"));
+    assert!(alice.messages.iter().any(refused));
+}
+
+#[test]
+fn light_a_flashlight_that_the_other_players_see() {
+    let mut server = TestServer::start("flashlight");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    let flashlight = server.game.get_item_id("flashlight").unwrap();
+    server.step(1);
+    alice.receive();
+    let inventory = alice.messages.iter().rev().find_map(|message| match message {
+        ToClient::Inventory(inventory) => Some(inventory.clone()),
+        _ => None,
+    });
+    let inventory = inventory.expect("Alice didn't receive her inventory");
+    let slot = inventory.slots().iter().position(|stack| stack.as_ref().is_some_and(|stack| stack.item == flashlight));
+    let slot = slot.unwrap();
+    let alice_id = alice.id();
+    let last_flashlight = |messages: &[ToClient]| {
+        messages.iter().rev().find_map(|message| match message {
+            ToClient::Flashlight(id, on) if *id == alice_id => Some(*on),
+            _ => None,
+        })
+    };
+
+    // Only a flashlight in the hand can be turned on
+    alice.messages.clear();
+    alice.send(ToServer::SelectSlot(PLAYER_INVENTORY_SLOTS - 1));
+    alice.send(ToServer::ToggleLight);
+    server.step(1);
+    alice.receive();
+    bob.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::CommandOutput(_))));
+    assert_eq!(last_flashlight(&bob.messages), None);
+
+    alice.send(ToServer::SelectSlot(slot));
+    alice.send(ToServer::ToggleLight);
+    server.step(1);
+    bob.receive();
+    assert_eq!(last_flashlight(&bob.messages), Some(true));
+    // The players who join later see it too
+    let mut carol = server.connect("Carol");
+    server.step(1);
+    carol.receive();
+    assert_eq!(last_flashlight(&carol.messages), Some(true));
+
+    // It goes out when it is put away
+    bob.messages.clear();
+    alice.send(ToServer::SelectSlot(PLAYER_INVENTORY_SLOTS - 1));
+    server.step(1);
+    bob.receive();
+    assert_eq!(last_flashlight(&bob.messages), Some(false));
+}
+
+#[test]
+fn locate_the_nearest_tree() {
+    let mut server = TestServer::start("locate");
+    let mut alice = server.connect("Alice");
+    alice.send(ToServer::Command("/locate".to_owned()));
+    server.step(1);
+    alice.receive();
+    let output_starts_with = |messages: &[ToClient], start: &str| {
+        messages.iter().any(|message| matches!(message, ToClient::CommandOutput(text) if text.starts_with(start)))
+    };
+    assert!(output_starts_with(&alice.messages, "Usage"));
+
+    // The trees are found as the chunks around Alice are generated
+    let position = server.game.get_physics_player(alice.id()).unwrap().aabb.pos;
+    server.step_until(|game| game.locate_structure("tree", position).is_some());
+    let tree = server.game.locate_structure("tree", position).unwrap();
+    alice.messages.clear();
+    alice.send(ToServer::Command("/locate tree".to_owned()));
+    server.step(1);
+    alice.receive();
+    let expected = format!("The nearest tree is at {} {} {}", tree.px, tree.py, tree.pz);
+    assert!(output_starts_with(&alice.messages, &expected));
+}
+
+#[test]
+fn trade_stone_for_a_ration_at_a_supply_terminal() {
+    let mut server = TestServer::start("trade");
+    let mut alice = server.connect("Alice");
+    let id = alice.id();
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..3).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    let terminal = server.game.get_block_id("supply_terminal").unwrap();
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), terminal));
+    let stone = server.game.get_item_id("stone").unwrap();
+    let ration = server.game.get_item_id("ration").unwrap();
+    assert_eq!(server.game.give_item(id, ItemStack::new(stone, 40)), None);
+
+    alice.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step(1);
+    alice.receive();
+    let trades = alice.messages.iter().find_map(|message| match message {
+        ToClient::Trades(pos, trades) if *pos == below(2) => Some(trades.clone()),
+        _ => None,
+    });
+    let trades = trades.expect("Alice didn't receive the trades");
+    let index = trades.iter().position(|trade| trade.cost == [ItemStack::new(stone, 32)]).unwrap();
+    assert_eq!(trades[index].result, ItemStack::new(ration, 1));
+
+    // The stone is taken and the ration is given
+    alice.messages.clear();
+    alice.send(ToServer::Trade(below(2), index));
+    server.step(1);
+    alice.receive();
+    let inventory = alice.messages.iter().rev().find_map(|message| match message {
+        ToClient::Inventory(inventory) => Some(inventory.clone()),
+        _ => None,
+    });
+    let inventory = inventory.expect("The inventory didn't change");
+    assert_eq!(inventory.count(stone), 8);
+    assert_eq!(inventory.count(ration), 5);
+
+    // The server refuses the trades that Alice can't afford
+    alice.messages.clear();
+    alice.send(ToServer::Trade(below(2), index));
+    server.step(1);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::CommandOutput(_))));
+    assert!(!alice.messages.iter().any(|message| matches!(message, ToClient::Inventory(_))));
+}
+
+#[test]
+fn regenerate_at_a_med_station_and_slow_down_in_a_dust_storm() {
+    let mut server = TestServer::start("effects");
+    let mut alice = server.connect("Alice");
+    let id = alice.id();
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..3).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    let med_station = server.game.get_block_id("med_station").unwrap();
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), med_station));
+    let last_effects = |alice: &mut TestClient| {
+        alice.receive();
+        let effects = alice.messages.iter().rev().find_map(|message| match message {
+            ToClient::StatusEffects(effects) => Some(effects.clone()),
+            _ => None,
+        });
+        alice.messages.clear();
+        effects
+    };
+
+    // The med station gives regeneration for some time
+    alice.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step(1);
+    let effects = last_effects(&mut alice).expect("Alice didn't receive her status effects");
+    let regeneration = effects.iter().find(|effect| effect.kind == StatusEffectKind::Regeneration).unwrap();
+    assert!(regeneration.level() > 1);
+    assert!(regeneration.remaining.is_some());
+
+    // The players caught outside in a dust storm are slowed down until it is over
+    server.game.run_admin_command("storm start 60");
+    server.step(1);
+    let effects = last_effects(&mut alice).expect("Alice wasn't slowed down by the storm");
+    let slowness = effects.iter().find(|effect| effect.kind == StatusEffectKind::Slowness).unwrap();
+    assert_eq!(slowness.remaining, None);
+    assert!(server.game.get_physics_player(id).unwrap().speed_multiplier < 1.0);
+    server.game.run_admin_command("storm stop");
+    server.step(1);
+    let effects = last_effects(&mut alice).expect("The slowness didn't wear off");
+    assert!(!effects.iter().any(|effect| effect.kind == StatusEffectKind::Slowness));
+    assert_eq!(server.game.get_physics_player(id).unwrap().speed_multiplier, 1.0);
+}
+
+#[test]
+fn wake_up_on_a_respawn_pod_until_it_is_destroyed() {
+    let mut server = TestServer::start("respawn");
+    let mut alice = server.connect("Alice");
+    let id = alice.id();
+    let physics_player = server.game.get_physics_player(id).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..3).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    let pod = server.game.get_block_id("respawn_pod").unwrap();
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), pod));
+
+    alice.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step(1);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::RespawnPointSet(pos) if *pos == below(2))));
+
+    // Alice wakes up on the pod after dying far away
+    alice.send(ToServer::Command("/tp 300 -5 0".to_owned()));
+    server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x > 250.0);
+    assert!(server.game.damage_player(id, 2.0 * MAX_VITAL));
+    server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x < 250.0);
+    let respawn = server.game.get_physics_player(id).unwrap().aabb.pos;
+    let offset = (respawn.x - below(2).px as f64 - 0.5, respawn.z - below(2).pz as f64 - 0.5);
+    assert!(offset.0.abs() < 0.5 && offset.1.abs() < 0.5, "woke up at {:?}", respawn);
+    assert!(respawn.y >= below(1).py as f64);
+
+    // The respawn point is forgotten once the pod is gone
+    assert!(server.game.set_block(below(2), 0));
+    alice.messages.clear();
+    alice.send(ToServer::Command("/tp 300 -5 0".to_owned()));
+    server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x > 250.0);
+    assert!(server.game.damage_player(id, 2.0 * MAX_VITAL));
+    server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x < 250.0);
+    alice.receive();
+    let destroyed = |message: &ToClient| matches!(message, ToClient::CommandOutput(text) if text.contains("destroyed"));
+    assert!(alice.messages.iter().any(destroyed));
+
+    // Only the administrators can set the respawn points of the players
+    let output_starts_with = |messages: &[ToClient], start: &str| {
+        messages.iter().any(|message| matches!(message, ToClient::CommandOutput(text) if text.starts_with(start)))
+    };
+    alice.messages.clear();
+    alice.send(ToServer::Command("/spawnpoint Alice".to_owned()));
+    server.step(1);
+    alice.receive();
+    assert!(output_starts_with(&alice.messages, "You are not allowed"));
+    server.game.run_admin_command("group Alice admin");
+    alice.messages.clear();
+    alice.send(ToServer::Command("/spawnpoint Alice".to_owned()));
+    server.step(1);
+    alice.receive();
+    assert!(output_starts_with(&alice.messages, "Alice has no respawn point"));
+}
+
+#[test]
+fn skip_the_night_once_everyone_rests_in_a_respawn_pod() {
+    let mut server = TestServer::start("sleep");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    let physics_player = server.game.get_physics_player(alice.id()).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..3).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    let pod = server.game.get_block_id("respawn_pod").unwrap();
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), pod));
+    server.game.run_admin_command("storm start 600");
+    server.game.advance_world_time(0.6 * DAY_LENGTH);
+    server.step(1);
+    alice.receive();
+    bob.receive();
+
+    // The night goes on while only Alice rests
+    alice.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step(2);
+    alice.receive();
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::RestingPlayers(1, 2))));
+    assert!(!server.game.get_world_time().is_day());
+
+    // Once Bob rests too, the morning comes and the storm is over
+    bob.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step(2);
+    bob.receive();
+    let time = server.game.get_world_time();
+    assert!(time.is_day() && time.time_of_day() < 0.01, "woke up at {}", time.time_of_day());
+    let storm_over = |message: &ToClient| match message {
+        ToClient::CommandOutput(text) => text == "The dust storm is over",
+        _ => false,
+    };
+    assert!(bob.messages.iter().any(storm_over));
+    assert!(bob.messages.iter().any(|message| matches!(message, ToClient::DustStorm(false))));
+}
+
+#[test]
+fn write_on_a_sign_that_the_other_players_read() {
+    let mut server = TestServer::start("signs");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    let physics_player = server.game.get_physics_player(alice.id()).unwrap();
+    let position = physics_player.aabb.pos;
+    let camera = BlockPos::from(physics_player.get_camera_position());
+    let below = |dy: i64| BlockPos::from((camera.px, camera.py - dy, camera.pz));
+    server.step_until(|game| (0..4).all(|dy| game.is_chunk_loaded(below(dy).containing_chunk_pos())));
+    let sign = server.game.get_block_id("sign").unwrap();
+    assert!(server.game.set_block(below(0), 0));
+    assert!(server.game.set_block(below(1), 0));
+    assert!(server.game.set_block(below(2), 0));
+    assert!(server.game.set_block(below(3), sign));
+    let last_signs = |messages: &[ToClient]| {
+        messages.iter().rev().find_map(|message| match message {
+            ToClient::Signs(signs) => Some(signs.clone()),
+            _ => None,
+        })
+    };
+
+    // Alice places a sign on the sign below her, and is asked for its text
+    alice.send(ToServer::SelectBlock(position, 0.0, -90.0));
+    alice.send(ToServer::PlaceBlock(position, 0.0, -90.0));
+    server.step(1);
+    alice.receive();
+    assert_eq!(server.game.get_block(below(2)), sign);
+    let editing_new_sign = |message: &ToClient| matches!(message, ToClient::EditSign(_, text) if text.is_empty());
+    assert!(alice.messages.iter().any(editing_new_sign));
+
+    // The text is cleaned up and sent to Bob
+    alice.send(ToServer::SetSignText(below(2), "  Welcome\nto Mars  ".to_owned()));
+    server.step(1);
+    bob.receive();
+    assert_eq!(last_signs(&bob.messages), Some(vec![(below(2), "Welcome to Mars".to_owned())]));
+
+    // Bob opens the editor with the text of the sign
+    bob.messages.clear();
+    bob.send(ToServer::InteractBlock(position, 0.0, -90.0));
+    server.step(1);
+    bob.receive();
+    let editing = |message: &ToClient| matches!(message, ToClient::EditSign(_, text) if text == "Welcome to Mars");
+    assert!(bob.messages.iter().any(editing));
+
+    // The text is forgotten once the sign is destroyed
+    assert!(server.game.set_block(below(2), 0));
+    bob.messages.clear();
+    server.step(1);
+    bob.receive();
+    assert_eq!(last_signs(&bob.messages), Some(Vec::new()));
+}
+
+#[test]
+fn stream_the_data_pack_when_the_data_is_not_cached() {
+    let mut server = TestServer::start("data_cache");
+    let mut alice = server.connect("Alice");
+    // The server only announces the hash of its data
+    let hash = alice.messages.iter().find_map(|message| match message {
+        ToClient::DataHash(hash) => Some(*hash),
+        _ => None,
+    });
+    let hash = hash.expect("Alice didn't receive the hash of the data");
+    assert!(!alice.messages.iter().any(|message| matches!(message, ToClient::DataPack(_, _))));
+
+    // And streams the data pack once when it is asked for
+    alice.messages.clear();
+    alice.send(ToServer::RequestData);
+    alice.send(ToServer::RequestData);
+    server.step(10);
+    alice.receive();
+    let mut download = None;
+    for message in alice.messages.drain(..) {
+        match message {
+            ToClient::DataPack(pack_hash, size) => {
+                assert!(download.is_none(), "Alice received the data pack twice");
+                download = Some(DataPackDownload::new(pack_hash, size).unwrap());
+            }
+            ToClient::DataPackChunk(bytes) => download.as_mut().unwrap().add_chunk(&bytes).unwrap(),
+            _ => (),
+        }
+    }
+    let download = download.expect("Alice didn't receive the data pack");
+    assert!(download.is_complete());
+    // Loading the pack gives the data of the server
+    let data = download.finish().unwrap().load().unwrap();
+    assert_eq!(data.content_hash(), hash);
+    // With the themes of the interface
+    assert!(data.themes.contains_key(DEFAULT_THEME));
+
+    // The data is found in the cache with its hash
+    let cache_folder = std::env::temp_dir().join(format!("marsbots-test-data-cache-{}", std::process::id()));
+    let cache = DataCache::new(cache_folder.clone());
+    assert!(cache.load(hash).is_none());
+    cache.store(hash, &data);
+    let cached_data = cache.load(hash).expect("The data wasn't cached");
+    assert_eq!(cached_data.content_hash(), hash);
+    assert_eq!(cached_data.blocks.id_map(), data.blocks.id_map());
+    let _ = std::fs::remove_dir_all(&cache_folder);
+}
+
+#[test]
+fn kick_the_players_who_cannot_use_the_content() {
+    let mut server = TestServer::start("content");
+    let mut alice = server.connect("Alice");
+    // The content is sent before the game data
+    let content = match alice.messages.first() {
+        Some(ToClient::Content(content)) => content.clone(),
+        message => panic!("Alice received {:?} before the content", message),
+    };
+    assert_eq!(content.game_version, GAME_VERSION);
+    assert_eq!(content.data_packs[0].name, "marsbots");
+    assert!(content.missing_for(GAME_VERSION).is_empty());
+    alice.send(ToServer::Hello(GAME_VERSION.to_owned()));
+
+    // Bob's game is outdated
+    let mut bob = server.connect("Bob");
+    assert_eq!(content.missing_for("0.0.1").len(), 1);
+    bob.send(ToServer::Hello("0.0.1".to_owned()));
+    server.step(1);
+    alice.receive();
+    bob.receive();
+    assert!(bob.messages.iter().any(|message| matches!(message, ToClient::Kicked(reason) if reason.contains("0.0.1"))));
+    assert!(!alice.messages.iter().any(|message| matches!(message, ToClient::Kicked(_))));
+
+    // A data pack can require a newer game
+    let mut newer_content = content.clone();
+    newer_content.data_packs[0].min_game_version = Some("99.0".to_owned());
+    let missing = newer_content.missing_for(GAME_VERSION);
+    assert_eq!(missing, vec!["Data pack marsbots 0.1.0 requires MarsBots 99.0".to_owned()]);
+}
+
+#[test]
+fn pregenerate_and_save_the_chunks_around_the_spawn() {
+    let mut server = TestServer::start("pregen");
+    let mut alice = server.connect("Alice");
+    let mut bob = server.connect("Bob");
+    server.game.run_admin_command("group Alice admin");
+    let output = |messages: &[ToClient], start: &str| {
+        messages.iter().any(|message| matches!(message, ToClient::CommandOutput(text) if text.starts_with(start)))
+    };
+    bob.send(ToServer::Command("/pregen 1".to_owned()));
+    alice.send(ToServer::Command("/pregen 100".to_owned()));
+    server.step(1);
+    bob.receive();
+    alice.receive();
+    assert!(output(&bob.messages, "You are not allowed to use /pregen"));
+    assert!(output(&alice.messages, "The radius must be"));
+
+    // 13 columns of 7 chunks, the height of the surface.
+    // The chunks loaded around the players are saved when they are unloaded.
+    alice.send(ToServer::Command("/pregen 2".to_owned()));
+    server.step(1);
+    alice.receive();
+    assert!(output(&alice.messages, "Pregenerating 91 chunks"));
+    server.step_until(|_| {
+        alice.receive();
+        output(&alice.messages, "Pregenerated 91 chunks")
+    });
+    let progress_reports = alice.messages.iter().filter(|message| {
+        matches!(message, ToClient::CommandOutput(text) if text.ends_with("/91)"))
+    });
+    assert!(progress_reports.count() >= 2);
+    // At most the 27 chunks around the players are loaded instead of stored. The chunks are written in the background.
+    server.step_until(|game| game.num_pending_chunk_writes() == 0);
+    let regions = std::fs::read_dir(server.save_folder().join("chunks").join("surface")).unwrap();
+    let stored_chunks = regions.map(|region| std::fs::read_dir(region.unwrap().path()).unwrap().count()).sum::<usize>();
+    assert!(stored_chunks >= 91 - 27, "{} chunks were stored", stored_chunks);
+}
+
+#[test]
+fn upgrade_an_old_save_after_backing_it_up() {
+    // The first saves had the chunks of each dimension in a single folder, and no meta file
+    let chunk_pos = ChunkPos::from((0, 1, 0));
+    let mut chunk = Chunk::new(chunk_pos);
+    chunk.set_block_at((0, 0, 0), 1);
+    let mut server = TestServer::start_with_save("upgrade", |save_folder| {
+        let folder = save_folder.join("chunks").join("surface");
+        std::fs::create_dir_all(&folder).unwrap();
+        let file = std::fs::File::create(folder.join("0.1.0.chunk")).unwrap();
+        let mut encoder = GzEncoder::new(file, Compression::fast());
+        bincode::serialize_into(&mut encoder, &chunk).unwrap();
+        encoder.finish().unwrap();
+    });
+    let save_folder = server.save_folder().to_owned();
+    let name = save_folder.file_name().unwrap().to_string_lossy();
+    let backup = save_folder.with_file_name(format!("{}.backup-v1", name));
+    assert!(backup.join("chunks").join("surface").join("0.1.0.chunk").is_file());
+    assert!(save_folder.join("chunks").join("surface").join("r.0.0").join("0.1.0.chunk").is_file());
+    let meta = std::fs::read_to_string(save_folder.join("meta.ron")).unwrap();
+    assert!(meta.contains("format_version: 2"), "{}", meta);
+
+    // The stored chunk is loaded instead of being generated
+    let _alice = server.connect("Alice");
+    server.step_until(|game| game.is_chunk_loaded(chunk_pos));
+    assert_eq!(server.game.get_block(BlockPos::from((0, 32, 0))), 1);
+    assert_eq!(server.game.get_block(BlockPos::from((1, 32, 0))), 0);
+    let _ = std::fs::remove_dir_all(&backup);
+}
+
+#[test]
+fn translate_the_stored_chunks_once_when_the_block_ids_change() {
+    let ids = {
+        let server = TestServer::start("translation_ids");
+        std::fs::read_to_string(server.save_folder().join("block_ids.ron")).unwrap()
+    };
+    let ids: IdMap = ron::de::from_str(&ids).unwrap();
+    // The save has the ids 1 and 2 swapped
+    let mut saved_ids = ids.clone();
+    saved_ids.keys.swap(1, 2);
+    let saved_ids = ron::ser::to_string(&saved_ids).unwrap();
+    let write_chunk = |path: &Path, block| {
+        let mut chunk = Chunk::new(ChunkPos::from((0, 1, 0)));
+        chunk.set_block_at((0, 0, 0), block);
+        let mut encoder = GzEncoder::new(std::fs::File::create(path).unwrap(), Compression::fast());
+        bincode::serialize_into(&mut encoder, &chunk).unwrap();
+        encoder.finish().unwrap();
+    };
+    let mut server = TestServer::start_with_save("translation", |save_folder| {
+        let region = save_folder.join("chunks").join("surface").join("r.0.0");
+        std::fs::create_dir_all(&region).unwrap();
+        std::fs::write(save_folder.join("meta.ron"), "(format_version: 2)").unwrap();
+        std::fs::write(save_folder.join("block_ids.ron"), &saved_ids).unwrap();
+        write_chunk(&region.join("0.1.0.chunk"), 1);
+        // A chunk translated by a translation that was interrupted before the end is translated again
+        write_chunk(&region.join("0.1.0.translated"), 3);
+        // And the damaged chunks are skipped
+        std::fs::write(region.join("0.2.0.chunk"), "damaged").unwrap();
+    });
+    let region = server.save_folder().join("chunks").join("surface").join("r.0.0");
+    assert!(!region.join("0.1.0.translated").exists());
+    let new_ids = std::fs::read_to_string(server.save_folder().join("block_ids.ron")).unwrap();
+    assert_eq!(ron::de::from_str::<IdMap>(&new_ids).unwrap(), ids);
+    let _alice = server.connect("Alice");
+    server.step_until(|game| game.is_chunk_loaded(ChunkPos::from((0, 1, 0))));
+    assert_eq!(server.game.get_block(BlockPos::from((0, 32, 0))), 2);
+    drop(server);
+
+    // A translation interrupted once all the chunks were translated is finished with the translated chunks
+    let mut server = TestServer::start_with_save("translation_end", |save_folder| {
+        let chunks = save_folder.join("chunks");
+        let region = chunks.join("surface").join("r.0.0");
+        std::fs::create_dir_all(&region).unwrap();
+        std::fs::write(save_folder.join("meta.ron"), "(format_version: 2)").unwrap();
+        std::fs::write(save_folder.join("block_ids.ron"), &saved_ids).unwrap();
+        std::fs::write(chunks.join("translated_block_ids.ron"), ron::ser::to_string(&ids).unwrap()).unwrap();
+        write_chunk(&region.join("0.1.0.chunk"), 3);
+        write_chunk(&region.join("0.1.0.translated"), 2);
+    });
+    assert!(!server.save_folder().join("chunks").join("translated_block_ids.ron").exists());
+    let _alice = server.connect("Alice");
+    server.step_until(|game| game.is_chunk_loaded(ChunkPos::from((0, 1, 0))));
+    assert_eq!(server.game.get_block(BlockPos::from((0, 32, 0))), 2);
+}
+
+#[test]
+fn keep_the_chunks_within_the_height_of_the_world() {
+    // A flat test world, the spawn is in the highest chunks
+    let mut server = TestServer::start_with_save("height", |save_folder| {
+        std::fs::create_dir_all(save_folder).unwrap();
+        let meta = "(format_version: 2, heights: {\"surface\": (min_chunk_y: 0, max_chunk_y: 1)})";
+        std::fs::write(save_folder.join("meta.ron"), meta).unwrap();
+    });
+    let mut alice = server.connect("Alice");
+    server.game.run_admin_command("group Alice admin");
+    server.step_until(|game| game.is_chunk_loaded(ChunkPos::from((0, 0, 0))));
+    server.step(20);
+    assert!(!server.game.is_chunk_loaded(ChunkPos::from((0, -1, 0))));
+    assert!(!server.game.is_chunk_loaded(ChunkPos::from((0, 2, 0))));
+    alice.receive();
+    let height = WorldHeight {
+        min_chunk_y: 0,
+        max_chunk_y: 1,
+    };
+    assert!(alice.messages.iter().any(|message| matches!(message, ToClient::WorldHeight(sent) if *sent == height)));
+    assert!(alice.messages.iter().all(|message| match message {
+        ToClient::Chunk(_, chunk, _) => height.contains_chunk(chunk.pos),
+        _ => true,
+    }));
+    // The other dimensions got their default bounds
+    let meta = std::fs::read_to_string(server.save_folder().join("meta.ron")).unwrap();
+    assert!(meta.contains("\"caverns\""), "{}", meta);
+
+    alice.send(ToServer::Command("/tp 0 -100 0".to_owned()));
+    server.step_until(|_| {
+        alice.receive();
+        alice.messages.iter().any(|message| {
+            matches!(message, ToClient::CommandOutput(text) if text == "The destination is outside of the world")
+        })
+    });
+}
+
+#[test]
+fn unload_the_chunks_no_one_sees_beyond_the_memory_budget() {
+    let spawn_chunk = ChunkPos::from((0, 1, 0));
+    let teleport_away = |server: &mut TestServer| {
+        let mut alice = server.connect("Alice");
+        server.game.run_admin_command("group Alice admin");
+        server.step_until(|game| game.is_chunk_loaded(spawn_chunk));
+        alice.send(ToServer::Command("/tp 1000 40 2000".to_owned()));
+        let id = alice.id();
+        server.step_until(|game| game.get_physics_player(id).unwrap().aabb.pos.x > 900.0);
+        server.step(5);
+        alice
+    };
+
+    // The chunks that are not seen anymore stay loaded while they fit in the budget
+    let mut server = TestServer::start("memory-budget");
+    let _alice = teleport_away(&mut server);
+    assert!(server.game.is_chunk_loaded(spawn_chunk));
+
+    // Without memory for them, they are unloaded once saved
+    let mut server = TestServer::start_with_save("memory-evict", |save_folder| {
+        std::fs::create_dir_all(save_folder).unwrap();
+        std::fs::write(save_folder.join("settings.ron"), "(chunk_memory_mb: 0)").unwrap();
+    });
+    let mut alice = teleport_away(&mut server);
+    assert!(!server.game.is_chunk_loaded(spawn_chunk));
+    alice.send(ToServer::Command("/debug chunks".to_owned()));
+    server.step(1);
+    alice.receive();
+    let summary = alice.messages.iter().find_map(|message| match message {
+        ToClient::CommandOutput(text) if text.starts_with("surface: ") => Some(text),
+        _ => None,
+    });
+    let summary = summary.expect("no chunk summary");
+    assert!(summary.contains("chunks unloaded for the budget"), "{}", summary);
+    assert!(summary.contains("Over the budget"), "{}", summary);
+}
+
+#[test]
+fn keep_the_data_of_the_item_stacks_across_rejoins() {
+    let mut server = TestServer::start("item_data");
+    let mut alice = server.connect("Alice");
+    let stone = server.game.get_item_id("stone").unwrap();
+    let mut named = ItemStack::new(stone, 3);
+    named.set_custom_name(Some("Lucky stone".to_owned()));
+    named.set_charge(Some(150));
+    assert_eq!(named.charge(), Some(100));
+    let last_inventory = |alice: &mut TestClient| {
+        alice.receive();
+        alice.messages.iter().rev().find_map(|message| match message {
+            ToClient::Inventory(inventory) => Some(inventory.clone()),
+            _ => None,
+        })
+    };
+
+    // The stacks with data don't merge with the stacks without
+    assert_eq!(server.game.give_item(alice.id(), ItemStack::new(stone, 2)), None);
+    assert_eq!(server.game.give_item(alice.id(), named.clone()), None);
+    server.step(1);
+    let inventory = last_inventory(&mut alice).expect("Alice didn't receive her inventory");
+    let stones = inventory.slots().iter().flatten().filter(|stack| stack.item == stone).collect::<Vec<_>>();
+    assert_eq!(stones.len(), 2);
+    assert!(stones.contains(&&named));
+
+    // The data is saved with the player
+    drop(alice);
+    server.step(1);
+    let mut alice = server.connect("Alice");
+    server.step(1);
+    let inventory = last_inventory(&mut alice).expect("Alice didn't receive her inventory");
+    assert!(inventory.slots().iter().flatten().any(|stack| *stack == named));
+    assert_eq!(inventory.count(stone), 5);
+}
+
+#[test]
+fn split_merge_and_swap_stacks_on_the_inventory_screen() {
+    let mut server = TestServer::start("slots");
+    let mut alice = server.connect("Alice");
+    let stone = server.game.get_item_id("stone").unwrap();
+    let last_inventory = |alice: &mut TestClient| {
+        alice.receive();
+        let inventory = alice.messages.iter().rev().find_map(|message| match message {
+            ToClient::Inventory(inventory) => Some(inventory.clone()),
+            _ => None,
+        });
+        alice.messages.clear();
+        inventory.expect("Alice didn't receive her inventory")
+    };
+    assert_eq!(server.game.give_item(alice.id(), ItemStack::new(stone, 10)), None);
+    server.step(1);
+    let inventory = last_inventory(&mut alice);
+    let stone_slot = inventory.slots().iter().position(|stack| stack.as_ref().is_some_and(|s| s.item == stone));
+    let stone_slot = stone_slot.unwrap();
+    let empty = |inventory: &Inventory| inventory.slots().iter().rposition(Option::is_none).unwrap();
+    let slot = |slot: usize| SlotId { inventory: InventoryId::Player, slot };
+
+    // Split half of the stack onto an empty slot
+    let split_slot = empty(&inventory);
+    alice.send(ToServer::MoveItems(SlotTransaction::Move { from: slot(stone_slot), to: slot(split_slot), count: 5 }));
+    server.step(1);
+    let inventory = last_inventory(&mut alice);
+    assert_eq!(inventory.get(stone_slot), Some(ItemStack::new(stone, 5)));
+    assert_eq!(inventory.get(split_slot), Some(ItemStack::new(stone, 5)));
+
+    // Swap the stone with another stack
+    let other_slot = inventory.slots().iter().position(|stack| stack.as_ref().is_some_and(|s| s.item != stone));
+    let other_slot = other_slot.unwrap();
+    let other = inventory.get(other_slot).unwrap();
+    alice.send(ToServer::MoveItems(SlotTransaction::Move { from: slot(split_slot), to: slot(other_slot), count: 5 }));
+    server.step(1);
+    let inventory = last_inventory(&mut alice);
+    assert_eq!(inventory.get(other_slot), Some(ItemStack::new(stone, 5)));
+    assert_eq!(inventory.get(split_slot), Some(other));
+
+    // Merge the two halves again
+    alice.send(ToServer::MoveItems(SlotTransaction::Move { from: slot(other_slot), to: slot(stone_slot), count: 5 }));
+    server.step(1);
+    let inventory = last_inventory(&mut alice);
+    assert_eq!(inventory.get(stone_slot), Some(ItemStack::new(stone, 10)));
+    assert_eq!(inventory.get(other_slot), None);
+
+    // The server refuses the transactions that don't match the inventory
+    let too_many = SlotTransaction::Move { from: slot(stone_slot), to: slot(other_slot), count: 11 };
+    for transaction in [too_many, SlotTransaction::QuickTransfer(slot(stone_slot))] {
+        alice.send(ToServer::MoveItems(transaction));
+        server.step(1);
+        alice.receive();
+        assert!(alice.messages.iter().any(|message| matches!(message, ToClient::CommandOutput(_))));
+        assert!(!alice.messages.iter().any(|message| matches!(message, ToClient::Inventory(_))));
+        alice.messages.clear();
+    }
+}